@@ -0,0 +1,98 @@
+//! Email address parsing and display helpers.
+use mailparse::{MailAddr, MailAddrList, MailHeader, SingleInfo, addrparse, addrparse_header};
+
+/// One parsed address: optional display name plus the bare `local@domain`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mailbox {
+    pub name: Option<String>,
+    pub addr: String,
+}
+
+/// Parses a decoded address string (`"Jane Doe" <jane@example.com>`, `jane@example.com`, ...)
+/// and returns the first single mailbox.
+pub fn parse_mailbox(raw: &str) -> Option<Mailbox> {
+    addrparse(raw).ok().and_then(first_mailbox)
+}
+
+/// Like [`parse_mailbox`] but works on the raw header, so RFC 2047 encoded names containing
+/// commas or quotes are decoded before tokenizing.
+pub fn parse_mailbox_header(header: &MailHeader) -> Option<Mailbox> {
+    addrparse_header(header).ok().and_then(first_mailbox)
+}
+
+/// Every mailbox of a decoded `To`/`Cc`/`Bcc` value, in order; group members are flattened and
+/// an unparseable list yields nothing.
+pub fn parse_address_list(raw: &str) -> Vec<Mailbox> {
+    let Ok(list) = addrparse(raw) else {
+        return Vec::new();
+    };
+    list.into_inner()
+        .into_iter()
+        .flat_map(|addr| match addr {
+            MailAddr::Single(single) => vec![single],
+            MailAddr::Group(group) => group.addrs,
+        })
+        .filter(|single| !single.addr.trim().is_empty())
+        .map(to_mailbox)
+        .collect()
+}
+
+fn first_mailbox(list: MailAddrList) -> Option<Mailbox> {
+    list.into_inner().into_iter().find_map(|addr| {
+        let single = match addr {
+            MailAddr::Single(single) => single,
+            MailAddr::Group(group) => group.addrs.into_iter().next()?,
+        };
+        Some(to_mailbox(single))
+    })
+}
+
+fn to_mailbox(single: SingleInfo) -> Mailbox {
+    Mailbox {
+        name: single
+            .display_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty()),
+        addr: single.addr,
+    }
+}
+
+/// Name to show in list views: the display name when present, else the address. Rows stored
+/// before names were split out may still hold a raw `Name <addr>` string in `addr`.
+pub fn friendly_from(name: Option<&str>, addr: Option<&str>) -> String {
+    if let Some(name) = name.filter(|n| !n.trim().is_empty()) {
+        return name.to_string();
+    }
+    match addr {
+        Some(raw) => match parse_mailbox(raw) {
+            Some(Mailbox {
+                name: Some(name), ..
+            }) => name,
+            Some(mailbox) => mailbox.addr,
+            None => raw.to_string(),
+        },
+        None => "Unknown".to_string(),
+    }
+}
+
+/// Full `Name <addr>` form for detail views.
+pub fn full_from(name: Option<&str>, addr: Option<&str>) -> String {
+    match (name.filter(|n| !n.trim().is_empty()), addr) {
+        (Some(name), Some(addr)) => format!("{} <{}>", name, addr),
+        (Some(name), None) => name.to_string(),
+        (None, Some(addr)) => addr.to_string(),
+        (None, None) => "Unknown".to_string(),
+    }
+}
+
+/// Canonical form of a `Message-ID` header value (`<local@domain>` → `local@domain`) for
+/// cross-folder matching; `None` when the value holds no id.
+pub fn normalize_message_id(raw: &str) -> Option<String> {
+    let raw = raw.trim();
+    let id = match (raw.find('<'), raw.find('>')) {
+        (Some(start), Some(end)) if start < end => &raw[start + 1..end],
+        _ => raw,
+    };
+    let id: String = id.chars().filter(|c| !c.is_whitespace()).collect();
+    (!id.is_empty()).then_some(id)
+}
@@ -1,292 +1,1330 @@
-use crate::cli::Cli;
+use crate::address::parse_mailbox;
+use crate::cli::{self, AccountsCommand, Cli, Command};
+use crate::collation;
+use crate::compose::{self, Draft};
 use crate::config::AppDefaults;
+use crate::daemon::Schedule;
+use crate::imap::{self, TraceLog};
+use crate::learn::{self, LearnConfig};
 use crate::onboarding;
-use crate::storage::Database;
-use crate::sync::SyncEngine;
+use crate::preview;
+use crate::profile;
+use crate::sanitize::{self, resanitize};
+use crate::smart_folders::SmartQuery;
+use crate::smtp::{self, SmtpEndpoint};
+use crate::status;
+use crate::storage::crypto::ColumnCipher;
+use crate::storage::db::{base_data_dir, default_data_dir};
+use crate::storage::reply_later;
+use crate::storage::{MailStore, StorageBackend, open_store};
+use crate::sync::{self, CacheFreshness, FolderLabels, FolderOp, SyncEngine, SyncOptions};
+use crate::timefmt::{DisplayTz, local_date};
+use crate::translate::{self, Translator};
 use crate::tui;
-use crate::types::Account;
-use anyhow::Result;
-use chrono::{DateTime, Utc};
+use crate::types::{Account, Provider, now_ts};
+use anyhow::{Result, bail};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 use tracing::{info, warn};
 
 pub async fn run(cli: Cli) -> Result<()> {
+    let base_dir = base_data_dir()?;
+    let env_profile = std::env::var("OTTO_PROFILE").ok();
+    profile::set_active(profile::resolve(
+        cli.profile.as_deref(),
+        env_profile.as_deref(),
+        &base_dir,
+    )?);
+
+    match &cli.command {
+        None => cli::sync::run(&cli).await,
+        Some(Command::Daemon) => cli::daemon::run(&cli).await,
+        Some(Command::Backfill(args)) => cli::backfill::run(&cli, args).await,
+        Some(Command::FetchBodies(args)) => cli::fetch_bodies::run(&cli, args).await,
+        Some(Command::Refetch(args)) => cli::refetch::run(&cli, args).await,
+        Some(Command::Trace(args)) => cli::trace::run(&cli, args).await,
+        Some(Command::Append(args)) => cli::append::run(&cli, args).await,
+        Some(Command::Send(args)) => cli::send::run(&cli, args).await,
+        Some(Command::FolderPolicy(args)) => cli::folder_policy::run(&cli, args).await,
+        Some(Command::SmartFolder(args)) => cli::smart_folder::run(&cli, args).await,
+        Some(Command::Cleanup(args)) => cli::cleanup::run(&cli, args).await,
+        Some(Command::Suggestions(args)) => cli::suggestions::run(&cli, args).await,
+        Some(Command::Notify(args)) => cli::notify::run(&cli, args).await,
+        Some(Command::Folders(args)) => cli::folders::run(&cli, args).await,
+        Some(Command::Verify(args)) => cli::verify::run(&cli, args).await,
+        Some(Command::Status(args)) => cli::status::run(&cli, args).await,
+        Some(Command::Conflicts(args)) => cli::conflicts::run(&cli, args).await,
+        Some(Command::Ops(args)) => cli::ops::run(&cli, args).await,
+        Some(Command::Audit(args)) => cli::audit::run(&cli, args).await,
+        Some(Command::AllMail(args)) => cli::all_mail::run(&cli, args).await,
+        Some(Command::Pause(args)) => cli::pause::run(&cli, args).await,
+        Some(Command::ImapServer(args)) => cli::imap_server::run(&cli, args).await,
+        Some(Command::EncryptColumns(args)) => cli::encrypt_columns::run(&cli, args).await,
+        Some(Command::Thread(args)) => cli::thread::run(&cli, args).await,
+        Some(Command::Share(args)) => cli::share::run(&cli, args).await,
+        Some(Command::Responses(args)) => cli::responses::run(&cli, args).await,
+        Some(Command::ReplyLater(args)) => cli::reply_later::run(&cli, args).await,
+        Some(Command::Note(args)) => cli::note::run(&cli, args).await,
+        Some(Command::Signature(args)) => cli::signature::run(&cli, args).await,
+        Some(Command::Translate(args)) => cli::translate::run(&cli, args).await,
+        Some(Command::Resanitize(args)) => cli::resanitize::run(&cli, args).await,
+        Some(Command::CompressBodies(args)) => cli::compress_bodies::run(&cli, args).await,
+        Some(Command::Accounts { action }) => cli::accounts::run(&cli, action).await,
+        Some(Command::Profile { action }) => cli::profile::run(action, &base_dir),
+    }
+}
+
+/// Loads the settings and applies the process-wide ones (pools, budgets, timeouts, tracing).
+pub(crate) fn load_defaults() -> Result<AppDefaults> {
     let defaults = AppDefaults::load()?;
-    let db = Arc::new(Database::new_default().await?);
-    info!(path = %db.path().display(), "Using SQLite store");
+    sync::set_max_pooled_connections(defaults.max_pooled_connections);
+    sync::set_memory_budget(defaults.memory_budget_bytes);
+    imap::set_timeouts(defaults.imap_timeouts);
+    sanitize::set_render_budget(defaults.html_render_budget);
+    preview::set_default_source(defaults.preview_source);
+    collation::set_collation(defaults.collation.clone());
+    configure_imap_trace(defaults.imap_trace_max_bytes);
+    Ok(defaults)
+}
 
-    let mut accounts = db.list_accounts().await?;
+/// What a command handler works with: the parsed flags, the settings, the open store and the
+/// accounts in it.
+pub(crate) struct Session<'a> {
+    pub(crate) cli: &'a Cli,
+    pub(crate) defaults: AppDefaults,
+    pub(crate) db: Arc<dyn MailStore>,
+    pub(crate) accounts: Vec<Account>,
+    pub(crate) timer: StartupTimer,
+}
 
-    if cli.add_account || accounts.is_empty() {
-        let (account, _token) = onboarding::onboard_account(&defaults).await?;
-        db.save_account(&account).await?;
-        accounts = db.list_accounts().await?;
-        info!(account = %account.id, "Account added");
+impl<'a> Session<'a> {
+    /// Opens the store and loads the accounts; never onboards or connects. Enough for
+    /// read-only commands such as `otto status`, which status bars poll.
+    pub(crate) async fn open(cli: &'a Cli) -> Result<Self> {
+        let defaults = load_defaults()?;
+        Self::open_with(cli, defaults, StartupTimer::new()).await
     }
 
-    if accounts.is_empty() {
-        warn!("No accounts configured. Run with --add-account to onboard.");
-        return Ok(());
+    pub(crate) async fn open_with(
+        cli: &'a Cli,
+        defaults: AppDefaults,
+        mut timer: StartupTimer,
+    ) -> Result<Self> {
+        let db = open_mail_store(&defaults).await?;
+        timer.phase("open store");
+        let accounts = db.list_accounts().await?;
+        timer.phase("load accounts");
+        Ok(Self {
+            cli,
+            defaults,
+            db,
+            accounts,
+            timer,
+        })
     }
 
-    if cli.tui {
-        launch_tui(&cli, &accounts, db.clone()).await?;
-        return Ok(());
+    /// [`Session::open`] followed by [`Session::prepare`]: what commands that work on the
+    /// accounts need.
+    pub(crate) async fn ready(cli: &'a Cli) -> Result<Self> {
+        Self::open(cli).await?.prepare().await
     }
 
-    if !cli.no_sync {
-        let engine = SyncEngine::new(db.clone());
-        engine.sync_all(&accounts, cli.force).await?;
-    } else {
-        info!("Skipping sync; using cached data only");
+    /// Travel mode (`--offline`/`OTTO_OFFLINE`): nothing may open a connection.
+    pub(crate) fn offline(&self) -> bool {
+        self.cli.offline || self.defaults.offline
     }
 
-    // Display latest 10 emails
-    println!("\n{}", "=".repeat(80));
-    println!("📬 Latest 10 Emails");
-    println!("{}\n", "=".repeat(80));
-
-    for account in &accounts {
-        let messages = db.load_messages(&account.id, 10).await?;
+    /// Refuses to go on when this invocation would connect to a server in travel mode.
+    pub(crate) fn check_network(&self) -> Result<()> {
+        if self.offline()
+            && let Some(what) = network_command(self.cli, self.accounts.is_empty())
+        {
+            bail!(
+                "{} needs the network; run without --offline/OTTO_OFFLINE",
+                what
+            );
+        }
+        Ok(())
+    }
 
-        if messages.is_empty() {
-            println!("No messages found for {}\n", account.email);
-            continue;
+    /// Runs OAuth onboarding for `--add-account` or a fresh store, then registers the column
+    /// ciphers of the accounts.
+    pub(crate) async fn prepare(mut self) -> Result<Self> {
+        self.check_network()?;
+        if self.cli.add_account || self.accounts.is_empty() {
+            let (account, _token, discovered) =
+                onboarding::onboard_account(&self.defaults, &self.cli.provider).await?;
+            self.db.save_account(&account).await?;
+            if !discovered.is_empty() {
+                self.db
+                    .record_discovered_folders(&account.id, &discovered)
+                    .await?;
+            }
+            self.accounts = self.db.list_accounts().await?;
+            info!(account = %account.id, "Account added");
         }
 
-        for (i, (msg, body)) in messages.iter().enumerate() {
-            let date = msg
-                .internal_date
-                .map(|ts| {
-                    DateTime::<Utc>::from_timestamp(ts, 0)
-                        .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
-                        .unwrap_or_else(|| "Unknown".to_string())
-                })
-                .unwrap_or_else(|| "Unknown".to_string());
+        if self.accounts.is_empty() {
+            bail!("no accounts configured; run with --add-account to onboard");
+        }
+        register_ciphers(self.db.as_ref(), &self.accounts)?;
+        Ok(self)
+    }
 
-            let from = msg.from.as_deref().unwrap_or("Unknown");
-            let subject = msg.subject.as_deref().unwrap_or("(No Subject)");
+    pub(crate) fn engine(&self) -> SyncEngine {
+        SyncEngine::new(self.db.clone(), self.defaults.max_concurrent_folders)
+    }
 
-            // Decode MIME-encoded subjects for display
-            let subject = decode_mime_words(subject);
+    pub(crate) fn sync_options(&self) -> SyncOptions {
+        sync_options(self.cli, &self.defaults)
+    }
+}
 
-            let is_read = msg.flags.iter().any(|f| f.eq("Seen") || f.eq("\\Seen"));
-            let status = if is_read { "R" } else { "U" };
+/// Where the TUI's store and accounts come from: opened by `run` already, or opened by the
+/// backend task while the TUI is already on screen.
+pub(crate) enum TuiStartup {
+    Ready(Arc<dyn MailStore>, Vec<Account>),
+    Lazy(Box<AppDefaults>),
+}
 
-            println!("{}. [{}] [{}] {}", i + 1, date, status, subject);
-            println!("   From: {}", from);
-            println!("   Folder: {}", msg.folder);
+/// Points new IMAP connections at the rotating trace log in the data directory while
+/// `OTTO_IMAP_TRACE` is on; an unchanged setting keeps the open log.
+fn configure_imap_trace(max_bytes: Option<u64>) {
+    let Some(max_bytes) = max_bytes else {
+        imap::trace::set_shared_log(None);
+        return;
+    };
+    let path = match default_data_dir() {
+        Ok(dir) => dir.join(IMAP_TRACE_FILE),
+        Err(e) => {
+            warn!(error = %e, "IMAP trace needs a data directory; tracing stays off");
+            return;
+        }
+    };
+    if imap::trace::shared_log()
+        .is_some_and(|log| log.path() == path && log.max_bytes() == Some(max_bytes))
+    {
+        return;
+    }
+    match TraceLog::rotating(&path, max_bytes) {
+        Ok(log) => {
+            info!(path = %path.display(), "Tracing IMAP connections (credentials redacted)");
+            imap::trace::set_shared_log(Some(Arc::new(log)));
+        }
+        Err(e) => warn!(error = %e, "Opening the IMAP trace log failed; tracing stays off"),
+    }
+}
 
-            if let Some(body_record) = body
-                && let Some(text) = &body_record.sanitized_text
-            {
-                let preview = text
-                    .lines()
-                    .filter(|line| !line.trim().is_empty())
-                    .take(2)
-                    .collect::<Vec<_>>()
-                    .join(" ");
-
-                let preview = if preview.chars().count() > 100 {
-                    let truncated: String = preview.chars().take(100).collect();
-                    format!("{}...", truncated)
-                } else {
-                    preview
-                };
+/// `otto --tui`/`--triage` on an existing local store: the TUI can draw before the store is
+/// opened. First runs (onboarding), other commands and remote stores keep the blocking path.
+pub(crate) fn tui_starts_lazily(cli: &Cli, defaults: &AppDefaults) -> bool {
+    (cli.tui || cli.triage)
+        && cli.command.is_none()
+        && !cli.add_account
+        && folder_op(cli).is_none()
+        && StorageBackend::from_url(defaults.database_url.as_deref())
+            .and_then(|backend| backend.sqlite_path())
+            .ok()
+            .flatten()
+            .is_some_and(|path| path.exists())
+}
 
-                if !preview.is_empty() {
-                    println!("   Preview: {}", preview);
-                }
+/// Opens the store and does the cleanup that must happen before any sync writes.
+async fn open_mail_store(defaults: &AppDefaults) -> Result<Arc<dyn MailStore>> {
+    let db = open_store(defaults.database_url.as_deref()).await?;
+    db.set_body_storage(defaults.body_storage);
+    // Nothing is syncing yet, so dropped blob files can go safely.
+    match db.purge_blob_files().await {
+        Ok(0) => {}
+        Ok(removed) => info!(removed, "Purged unreferenced blob files"),
+        Err(e) => warn!(error = %e, "Purging blob files failed"),
+    }
+    match db.recover_interrupted_writes().await {
+        Ok(report) if report.is_empty() => {}
+        Ok(report) => {
+            warn!(
+                orphan_bodies = report.orphan_bodies,
+                orphan_rows = report.orphan_rows,
+                bodies_requeued = report.bodies_requeued,
+                blob_files_missing = report.blob_files_missing,
+                blob_files_removed = report.blob_files_removed,
+                folders_rewound = report.folders_rewound.len(),
+                "Repaired leftovers of an interrupted write"
+            );
+            for folder in &report.folders_rewound {
+                warn!(
+                    account = %folder.account_id,
+                    folder = %folder.folder,
+                    from_uid = folder.rewound_to + 1,
+                    to_uid = folder.checkpoint_uid,
+                    "Folder checkpoint was committed without its messages; refetching the UID range"
+                );
             }
+        }
+        Err(e) => warn!(error = %e, "Recovering from interrupted writes failed"),
+    }
+    info!(store = %db.describe(), "Using mail store");
+    Ok(db)
+}
+
+/// Logs how long each startup phase took, for tracking where time to first screen goes.
+pub(crate) struct StartupTimer {
+    started: Instant,
+    phase_started: Instant,
+}
 
-            println!();
+impl StartupTimer {
+    pub(crate) fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            phase_started: now,
         }
     }
 
-    println!("{}", "=".repeat(80));
+    /// Logs the phase that just ended and starts the next one.
+    fn phase(&mut self, phase: &'static str) {
+        let now = Instant::now();
+        info!(
+            phase,
+            ms = (now - self.phase_started).as_millis() as u64,
+            total_ms = (now - self.started).as_millis() as u64,
+            "Startup phase done"
+        );
+        self.phase_started = now;
+    }
 
-    Ok(())
+    fn total_ms(&self) -> u128 {
+        self.started.elapsed().as_millis()
+    }
 }
 
-async fn launch_tui(cli: &Cli, accounts: &[Account], db: Arc<Database>) -> Result<()> {
-    if let Some(account) = accounts.first() {
-        let messages = db.load_messages(&account.id, 50).await?;
-        let mail_items = tui::build_mail_items(&messages);
-        let (update_tx, update_rx) = mpsc::channel();
+/// Shows the TUI at once and leaves everything else to a backend task: opening the store
+/// (lazy startup), loading the list, then syncing, with readiness reported in the status bar.
+pub(crate) async fn launch_tui(
+    cli: &Cli,
+    defaults: &AppDefaults,
+    startup: TuiStartup,
+    offline: bool,
+    timer: StartupTimer,
+) -> Result<()> {
+    let (update_tx, update_rx) = mpsc::channel();
+    let offline = Arc::new(AtomicBool::new(offline));
+    let cancel = CancellationToken::new();
+    let (reload_tx, reload_rx) = tokio::sync::mpsc::unbounded_channel();
+    // Safe mode keeps the TUI read-only by not wiring an action handler at all; an account
+    // in safe mode is only known once loaded (`TuiEvent::ReadOnly`).
+    let (action_tx, action_rx) = if cli.safe_mode {
+        (None, None)
+    } else {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (Some(tx), Some(rx))
+    };
 
-        if !cli.no_sync {
-            let start_tx = update_tx.clone();
-            let sync_tx = update_tx.clone();
-            let db_for_sync = db.clone();
-            let accounts_for_sync = accounts.to_vec();
-            let account_id = account.id.clone();
-            let force = cli.force;
+    let backend = TuiBackend {
+        display_tz: defaults.display_tz,
+        updates: update_tx,
+        offline: offline.clone(),
+        cancel: cancel.clone(),
+        no_sync: cli.no_sync,
+        options: sync_options(cli, defaults),
+        max_concurrent_folders: defaults.max_concurrent_folders,
+        watch: cli.watch,
+        reload_tx: reload_tx.clone(),
+        reload_rx,
+        action_rx,
+        translator: defaults
+            .translate
+            .clone()
+            .map(|c| Arc::new(Translator::new(c))),
+        learn: defaults.learn,
+        smtp: defaults.smtp.clone(),
+    };
+    let task = tokio::spawn(backend.run(startup, timer));
 
-            let _ = start_tx.send(tui::TuiEvent::SyncStarted);
+    let state = tui::TuiState {
+        mail_items: Vec::new(),
+        sidebar: tui::Sidebar::default(),
+        updates: Some(update_rx),
+        actions: action_tx,
+        reload: Some(reload_tx),
+        triage: cli.triage,
+        offline,
+        queued_ops: 0,
+        badges: defaults.tui_badges,
+        collapse_repeats: defaults.tui_collapse_repeats,
+        sort: defaults.tui_sort,
+        translation_view: defaults
+            .translate
+            .as_ref()
+            .map(|config| config.view)
+            .unwrap_or_default(),
+    };
 
-            tokio::spawn(async move {
-                let engine = SyncEngine::new(db_for_sync.clone());
-                if let Err(e) = engine.sync_all(&accounts_for_sync, force).await {
-                    warn!(error = %e, "Background sync failed");
+    let result = tokio::task::block_in_place(|| tui::run(state));
+    // Let a running pass commit its current batch and pool its connections instead of
+    // being torn down with the runtime.
+    cancel.cancel();
+    match tokio::time::timeout(SHUTDOWN_GRACE, task).await {
+        Err(_) => {
+            warn!("Background sync did not stop in time; it resumes from its checkpoint next run")
+        }
+        Ok(Ok(Err(e))) => return Err(e),
+        Ok(_) => {}
+    }
+    result
+}
+
+/// The TUI's side of the app on the tokio runtime: startup, the action handler, background
+/// sync and settings reloads.
+struct TuiBackend {
+    display_tz: DisplayTz,
+    updates: mpsc::Sender<tui::TuiEvent>,
+    offline: Arc<AtomicBool>,
+    cancel: CancellationToken,
+    no_sync: bool,
+    options: SyncOptions,
+    max_concurrent_folders: usize,
+    watch: bool,
+    reload_tx: UnboundedSender<()>,
+    reload_rx: UnboundedReceiver<()>,
+    action_rx: Option<UnboundedReceiver<tui::TuiAction>>,
+    /// Backend for `T` in the TUI (`OTTO_TRANSLATE_URL`).
+    translator: Option<Arc<Translator>>,
+    /// Learned rule suggestions from repeated actions (`OTTO_LEARN_*`).
+    learn: LearnConfig,
+    /// Submission server for messages composed in the TUI (`w`).
+    smtp: SmtpEndpoint,
+}
+
+impl TuiBackend {
+    fn status(&self, status: impl Into<String>) {
+        let _ = self.updates.send(tui::TuiEvent::Status(status.into()));
+    }
+
+    /// Runs until the TUI quits. A startup failure is shown in the status bar and returned.
+    async fn run(self, startup: TuiStartup, timer: StartupTimer) -> Result<()> {
+        let updates = self.updates.clone();
+        let result = self.start(startup, timer).await;
+        if let Err(e) = &result {
+            let _ = updates.send(tui::TuiEvent::Status(format!("Startup failed: {:#}", e)));
+        }
+        result
+    }
+
+    async fn start(self, startup: TuiStartup, mut timer: StartupTimer) -> Result<()> {
+        let (db, accounts) = match startup {
+            TuiStartup::Ready(db, accounts) => (db, accounts),
+            TuiStartup::Lazy(defaults) => {
+                self.status("Opening mail cache...");
+                let db = open_mail_store(&defaults).await?;
+                timer.phase("open store");
+                let accounts = db.list_accounts().await?;
+                register_ciphers(db.as_ref(), &accounts)?;
+                timer.phase("load accounts");
+                (db, accounts)
+            }
+        };
+        let Some(account) = accounts.first() else {
+            self.status("No accounts configured; quit and run otto --add-account");
+            return Ok(());
+        };
+
+        self.status("Loading messages...");
+        let list = load_mail_items(db.as_ref(), &account.id, self.display_tz).await?;
+        timer.phase("load messages");
+        if !list.send(&self.updates) || self.cancel.is_cancelled() {
+            return Ok(());
+        }
+        self.status(format!("Cache ready in {} ms", timer.total_ms()));
+
+        if account.settings.safe_mode {
+            let _ = self.updates.send(tui::TuiEvent::ReadOnly);
+        } else if let Some(action_rx) = self.action_rx {
+            // Suggestions left unanswered last time are asked again.
+            match learn::pending(db.as_ref(), &account.id).await {
+                Ok(pending) if !pending.is_empty() => {
+                    let _ = self.updates.send(tui::TuiEvent::Suggestions(pending));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(account = %account.id, error = %e, "Loading rule suggestions failed")
                 }
-                let _ = sync_tx.send(tui::TuiEvent::SyncFinished);
+            }
+            tokio::spawn(handle_tui_actions(
+                db.clone(),
+                account.clone(),
+                self.smtp.clone(),
+                self.display_tz,
+                self.translator.clone(),
+                self.learn,
+                self.offline.clone(),
+                action_rx,
+                self.updates.clone(),
+            ));
+        }
 
-                match db_for_sync.load_messages(&account_id, 50).await {
-                    Ok(messages) => {
-                        let items = tui::build_mail_items(&messages);
-                        let _ = sync_tx.send(tui::TuiEvent::MailItems(items));
+        let background = BackgroundSync {
+            db: db.clone(),
+            account_id: account.id.clone(),
+            display_tz: self.display_tz,
+            updates: self.updates.clone(),
+            offline: self.offline,
+            cancel: self.cancel,
+        };
+        let running = if background.is_offline() {
+            let _ = self.updates.send(tui::TuiEvent::Status(
+                "Offline: sync paused, changes queue locally ([o] to go online)".to_string(),
+            ));
+            None
+        } else if self.no_sync {
+            info!("Skipping sync; TUI will use cached data only");
+            let (db, accounts, updates) = (db.clone(), accounts.clone(), self.updates.clone());
+            tokio::spawn(async move {
+                let stale = check_cache_freshness(db, &accounts).await;
+                let status = match stale.first() {
+                    None => "Cached folders match the server".to_string(),
+                    Some(first) if stale.len() == 1 => format!("Cache out of date: {}", first),
+                    Some(first) => format!(
+                        "Cache out of date in {} places, e.g. {}",
+                        stale.len(),
+                        first
+                    ),
+                };
+                let _ = updates.send(tui::TuiEvent::Status(status));
+            });
+            None
+        } else {
+            Some(background.spawn(accounts.clone(), self.max_concurrent_folders, self.options))
+        };
+
+        // Settings reload: `R` in the TUI, or a change to `.env` / stored account settings.
+        tokio::spawn(watch_settings(db, self.reload_tx));
+        reload_settings(
+            background,
+            self.no_sync,
+            SyncOptions {
+                force: false,
+                ..self.options
+            },
+            self.reload_rx,
+            running,
+            (self.watch && !self.no_sync).then_some(self.max_concurrent_folders),
+        )
+        .await;
+        Ok(())
+    }
+}
+
+/// Applies message actions from the TUI and sends it the refreshed list after each.
+/// Translations (`T`, through `translator`) are answered with their own event instead, and
+/// applied archive/delete/label ops are counted per sender for learned rules (`learn`).
+#[allow(clippy::too_many_arguments)] // the backend's settings, split out for the spawned task
+async fn handle_tui_actions(
+    db: Arc<dyn MailStore>,
+    account: Account,
+    smtp: SmtpEndpoint,
+    display_tz: DisplayTz,
+    translator: Option<Arc<Translator>>,
+    learn_config: LearnConfig,
+    offline: Arc<AtomicBool>,
+    mut action_rx: UnboundedReceiver<tui::TuiAction>,
+    refresh_tx: mpsc::Sender<tui::TuiEvent>,
+) {
+    let account_id = account.id.clone();
+    while let Some(action) = action_rx.recv().await {
+        match action {
+            tui::TuiAction::Apply { op, message_ids } => {
+                match db.apply_message_op(&account_id, &op, &message_ids).await {
+                    Ok(n) => {
+                        info!(account = %account_id, op = op.kind(), count = n, "Queued message op");
+                        match learn::observe(
+                            db.as_ref(),
+                            &account_id,
+                            &op,
+                            &message_ids,
+                            learn_config,
+                        )
+                        .await
+                        {
+                            Ok(suggestions) if suggestions.is_empty() => {}
+                            Ok(suggestions) if learn_config.auto_accept => {
+                                let names: Vec<String> =
+                                    suggestions.iter().map(|s| s.rule_name()).collect();
+                                let _ = refresh_tx.send(tui::TuiEvent::Status(format!(
+                                    "Created learned rule(s): {}",
+                                    names.join(", ")
+                                )));
+                            }
+                            Ok(suggestions) => {
+                                let _ = refresh_tx.send(tui::TuiEvent::Suggestions(suggestions));
+                            }
+                            Err(e) => {
+                                warn!(account = %account_id, error = %e, "Counting the action for rule suggestions failed")
+                            }
+                        }
                     }
                     Err(e) => {
-                        warn!(account = %account_id, error = %e, "Reloading messages after sync failed");
+                        warn!(account = %account_id, op = op.kind(), error = %e, "Applying message op failed")
                     }
                 }
-            });
-        } else {
-            info!("Skipping sync; TUI will use cached data only");
+            }
+            tui::TuiAction::SettleSuggestion { suggestion, accept } => {
+                let result = if accept {
+                    learn::accept(db.as_ref(), &account_id, &suggestion).await
+                } else {
+                    learn::reject(db.as_ref(), &account_id, &suggestion).await
+                };
+                let status = match result {
+                    Ok(()) if accept => format!(
+                        "Created rule {} (otto cleanup shows and removes it)",
+                        suggestion.rule_name()
+                    ),
+                    Ok(()) => format!("Won't suggest {} again", suggestion.rule_name()),
+                    Err(e) => {
+                        warn!(account = %account_id, error = %e, "Settling rule suggestion failed");
+                        format!("Settling the suggestion failed: {:#}", e)
+                    }
+                };
+                let _ = refresh_tx.send(tui::TuiEvent::Status(status));
+                // The list itself did not change.
+                continue;
+            }
+            tui::TuiAction::ReplyLater { message_ids, due } => {
+                let status = match reply_later::parse_due(&due, today(display_tz)) {
+                    Ok(due) => match db.set_reply_later(&account_id, &message_ids, due).await {
+                        Ok(n) => format!(
+                            "Reply later: {} message(s), {}",
+                            n,
+                            due.map_or("no due date".to_string(), |d| format!("due {}", d))
+                        ),
+                        Err(e) => {
+                            warn!(account = %account_id, error = %e, "Queueing reply later failed");
+                            "Queueing reply later failed".to_string()
+                        }
+                    },
+                    Err(e) => format!("{:#}", e),
+                };
+                let _ = refresh_tx.send(tui::TuiEvent::Status(status));
+            }
+            tui::TuiAction::ReplyDone { message_ids } => {
+                if let Err(e) = db.clear_reply_later(&account_id, &message_ids).await {
+                    warn!(account = %account_id, error = %e, "Clearing reply later failed");
+                }
+            }
+            tui::TuiAction::Note { message_id, note } => {
+                let result = if note.is_empty() {
+                    db.clear_message_note(&account_id, &message_id).await
+                } else {
+                    db.set_message_note(&account_id, &message_id, &note).await
+                };
+                let status = match result {
+                    Ok(_) if note.is_empty() => "Note removed".to_string(),
+                    Ok(_) => "Note saved".to_string(),
+                    Err(e) => {
+                        warn!(account = %account_id, error = %e, "Saving note failed");
+                        "Saving note failed".to_string()
+                    }
+                };
+                let _ = refresh_tx.send(tui::TuiEvent::Status(status));
+            }
+            tui::TuiAction::Rerender { message_id } => {
+                let status =
+                    match resanitize::rerender_message(db.as_ref(), &account_id, &message_id).await
+                    {
+                        Ok(true) => "Rendered in full".to_string(),
+                        Ok(false) => "Raw message not stored; cannot re-render".to_string(),
+                        Err(e) => {
+                            warn!(account = %account_id, error = %e, "Re-rendering body failed");
+                            format!("Re-rendering failed: {:#}", e)
+                        }
+                    };
+                let _ = refresh_tx.send(tui::TuiEvent::Status(status));
+            }
+            tui::TuiAction::Translate { message_id } => {
+                // Backends can take a while; keep handling other actions meanwhile.
+                let (db, account_id, translator, refresh_tx) = (
+                    db.clone(),
+                    account_id.clone(),
+                    translator.clone(),
+                    refresh_tx.clone(),
+                );
+                let offline = offline.load(Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let Some(target) = translator.as_ref().map(|t| t.config().target.clone())
+                    else {
+                        let _ = refresh_tx.send(tui::TuiEvent::Status(
+                            "No translation backend configured (set OTTO_TRANSLATE_URL)"
+                                .to_string(),
+                        ));
+                        return;
+                    };
+                    let event = match translate::translate_message(
+                        db.as_ref(),
+                        translator.as_deref(),
+                        &account_id,
+                        &message_id,
+                        &target,
+                        false,
+                        offline,
+                    )
+                    .await
+                    {
+                        Ok(translation) => tui::TuiEvent::Translation {
+                            message_id,
+                            target: translation.target,
+                            text: translation.text,
+                        },
+                        Err(e) => {
+                            warn!(account = %account_id, error = %e, "Translating message failed");
+                            tui::TuiEvent::Status(format!("Translation failed: {:#}", e))
+                        }
+                    };
+                    let _ = refresh_tx.send(event);
+                });
+                // Nothing in the list changed.
+                continue;
+            }
+            tui::TuiAction::SuggestRecipients { query } => {
+                let suggestions = db
+                    .suggest_addresses(&account_id, &query, RECIPIENT_SUGGESTIONS)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!(account = %account_id, error = %e, "Suggesting recipients failed");
+                        Vec::new()
+                    });
+                let _ = refresh_tx.send(tui::TuiEvent::RecipientSuggestions { query, suggestions });
+                continue;
+            }
+            tui::TuiAction::Send { draft } => {
+                if offline.load(Ordering::SeqCst) {
+                    let _ = refresh_tx.send(tui::TuiEvent::Sent(Err(
+                        "Offline: go back online ([o]) to send".to_string(),
+                    )));
+                    continue;
+                }
+                // SMTP can be slow; keep handling other actions meanwhile.
+                let (db, account, smtp, refresh_tx) = (
+                    db.clone(),
+                    account.clone(),
+                    smtp.clone(),
+                    refresh_tx.clone(),
+                );
+                tokio::spawn(async move {
+                    let result = send_draft(db.as_ref(), &smtp, &account, draft).await;
+                    if let Err(e) = &result {
+                        warn!(account = %account.id, error = %e, "Sending composed message failed");
+                    }
+                    let _ = refresh_tx.send(tui::TuiEvent::Sent(
+                        result.map_err(|e| format!("Sending failed: {:#}", e)),
+                    ));
+                });
+                continue;
+            }
         }
 
-        let state = tui::TuiState {
-            mail_items,
-            updates: Some(update_rx),
-        };
+        match load_mail_items(db.as_ref(), &account_id, display_tz).await {
+            Ok(list) => {
+                if !list.send(&refresh_tx) {
+                    break;
+                }
+            }
+            Err(e) => {
+                warn!(account = %account_id, error = %e, "Reloading messages after op failed");
+            }
+        }
+    }
+}
 
-        tokio::task::block_in_place(|| tui::run(state))?;
-    } else {
-        warn!("No accounts available for TUI; falling back to simple list.");
+/// Addresses offered under a recipient being typed in the compose view.
+const RECIPIENT_SUGGESTIONS: usize = 6;
+
+/// Sends a draft composed in the TUI from the account's own address, with its signature,
+/// through the same Gmail SMTP submission `otto send` uses.
+async fn send_draft(
+    db: &dyn MailStore,
+    smtp: &SmtpEndpoint,
+    account: &Account,
+    mut draft: Draft,
+) -> Result<()> {
+    if account.provider != Provider::GmailImap {
+        bail!(
+            "sending goes through Gmail SMTP; {} is not a Gmail account",
+            account.email
+        );
+    }
+    draft.from = account.email.clone();
+    if let Some(signature) = db.load_signature(&account.id, None).await? {
+        draft.body_markdown = compose::apply_signature(&draft.body_markdown, None, &signature);
     }
+    let message = compose::build_message(&draft)?;
+    let recipients: Vec<String> = draft
+        .to
+        .iter()
+        .chain(&draft.cc)
+        .filter_map(|raw| parse_mailbox(raw))
+        .map(|mailbox| mailbox.addr)
+        .collect();
+    smtp::send_from_account(smtp, account, &recipients, &message).await
+}
 
-    Ok(())
+/// What the TUI's background sync needs; kept so a settings reload can restart it.
+#[derive(Clone)]
+struct BackgroundSync {
+    db: Arc<dyn MailStore>,
+    /// Account whose messages the TUI lists (reloaded after each pass).
+    account_id: String,
+    display_tz: DisplayTz,
+    updates: mpsc::Sender<tui::TuiEvent>,
+    /// Offline (travel) mode; no pass starts while it is set.
+    offline: Arc<AtomicBool>,
+    /// Cancelled when the TUI quits; stops the running pass and any further ones.
+    cancel: CancellationToken,
 }
 
-#[allow(unused_assignments)]
-fn decode_mime_words(text: &str) -> String {
-    // Decode MIME-encoded words like =?UTF-8?Q?...?= or =?UTF-8?B?...?=
-    if !text.contains("=?") {
-        return text.to_string();
-    }
-
-    let mut result = String::new();
-    let mut remaining = text;
-    let mut last_was_encoded = false;
-
-    while let Some(start) = remaining.find("=?") {
-        // Add text before the encoded word
-        let before = &remaining[..start];
-        if !before.is_empty() {
-            // If last was encoded and this is just whitespace, skip it
-            if last_was_encoded && before.trim().is_empty() {
-                // Skip whitespace between consecutive encoded words
-            } else {
-                result.push_str(before);
-                last_was_encoded = false;
+impl BackgroundSync {
+    fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    /// Runs one sync pass, forwarding progress to the TUI and refreshing its list when done.
+    /// If ops were queued, the pass ends with a summary of what was sent.
+    fn spawn(
+        &self,
+        accounts: Vec<Account>,
+        max_concurrent_folders: usize,
+        options: SyncOptions,
+    ) -> JoinHandle<()> {
+        let engine = SyncEngine::new(self.db.clone(), max_concurrent_folders)
+            .with_cancellation(self.cancel.clone());
+        let _ = self.updates.send(tui::TuiEvent::SyncStarted);
+
+        // Forward engine progress into the TUI channel until the engine is dropped.
+        let mut progress_rx = engine.subscribe();
+        let progress_tx = self.updates.clone();
+        tokio::spawn(async move {
+            loop {
+                match progress_rx.recv().await {
+                    Ok(event) => {
+                        if progress_tx.send(tui::TuiEvent::Progress(event)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
             }
-        }
+        });
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            let queued_before = match this.db.list_pending_ops(&this.account_id).await {
+                Ok(ops) => ops.len(),
+                Err(_) => 0,
+            };
+            let report = engine.sync_all(&accounts, options).await;
+            let _ = this.updates.send(tui::TuiEvent::SyncFinished(report));
 
-        // Find the end of this encoded word by parsing the structure
-        // Format: =?charset?encoding?encoded-text?=
-        // We need to skip 2 '?' and find the 3rd one followed by '='
-        let search_start = start + 2; // Skip "=?"
-        let mut question_count = 0;
-        let mut end_pos = None;
-
-        for (i, ch) in remaining[search_start..].char_indices() {
-            if ch == '?' {
-                question_count += 1;
-                if question_count == 2 {
-                    // Found the '?' before encoded-text, now look for closing ?=
-                    let rest = &remaining[search_start + i + 1..];
-                    if let Some(closing) = rest.find("?=") {
-                        end_pos = Some(search_start + i + 1 + closing + 2);
-                        break;
+            match load_mail_items(this.db.as_ref(), &this.account_id, this.display_tz).await {
+                Ok(list) => {
+                    let queued = list.queued;
+                    list.send(&this.updates);
+                    if queued_before > 0 {
+                        let _ = this
+                            .updates
+                            .send(tui::TuiEvent::Status(flush_summary(queued_before, queued)));
                     }
                 }
+                Err(e) => {
+                    warn!(account = %this.account_id, error = %e, "Reloading messages after sync failed");
+                }
             }
+        })
+    }
+}
+
+/// Re-reads `.env` and the stored accounts on each request, then starts a fresh sync pass so
+/// folder lists, per-folder policies and concurrency take effect without restarting the TUI.
+/// With `--watch` (`watch` = folder concurrency) it also starts a pass for each account whose
+/// poll interval has elapsed. Returns once the TUI quits and the running pass has stopped.
+async fn reload_settings(
+    background: BackgroundSync,
+    no_sync: bool,
+    options: SyncOptions,
+    mut requests: UnboundedReceiver<()>,
+    mut running: Option<JoinHandle<()>>,
+    mut watch: Option<usize>,
+) {
+    let mut schedule = Schedule::default();
+    if watch.is_some()
+        && let Ok(accounts) = background.db.list_accounts().await
+    {
+        // The startup pass counts as every account's first scheduled sync.
+        schedule.mark_started(&accounts, now_ts());
+    }
+    loop {
+        let next_due = match watch {
+            Some(_) if !background.is_offline() => background
+                .db
+                .list_accounts()
+                .await
+                .ok()
+                .and_then(|accounts| schedule.next_due_in(&accounts, now_ts())),
+            _ => None,
+        };
+        let scheduled = tokio::select! {
+            biased;
+            _ = background.cancel.cancelled() => break,
+            request = requests.recv() => {
+                if request.is_none() {
+                    break;
+                }
+                false
+            }
+            _ = tokio::time::sleep(next_due.unwrap_or_default()), if next_due.is_some() => true,
+        };
+
+        // Folder tasks are detached from the pass, so let a running pass finish instead of
+        // aborting it halfway.
+        if let Some(handle) = running.take()
+            && !handle.is_finished()
+        {
+            if !scheduled {
+                let _ = background.updates.send(tui::TuiEvent::Status(
+                    "Reload waits for the running sync to finish".to_string(),
+                ));
+            }
+            let _ = handle.await;
+        }
+        if background.cancel.is_cancelled() {
+            break;
         }
 
-        if let Some(end) = end_pos {
-            let encoded = &remaining[start..end];
+        if scheduled {
+            let accounts = match background.db.list_accounts().await {
+                Ok(accounts) => accounts,
+                Err(e) => {
+                    warn!(error = %e, "Loading accounts for scheduled sync failed");
+                    continue;
+                }
+            };
+            let due = schedule.take_due(&accounts, now_ts());
+            if let Some(max_concurrent_folders) = watch
+                && !due.is_empty()
+                && !background.is_offline()
+            {
+                running = Some(background.spawn(due, max_concurrent_folders, options));
+            }
+            continue;
+        }
+        // Collapse a burst of requests (key presses, several file writes) into one reload.
+        while requests.try_recv().is_ok() {}
 
-            if let Some(decoded) = decode_mime_word(encoded) {
-                result.push_str(&decoded);
-                last_was_encoded = true;
-            } else {
-                // If decode failed, keep the original text
-                result.push_str(encoded);
-                last_was_encoded = false;
+        if let Err(e) = dotenvy::dotenv_override()
+            && !e.not_found()
+        {
+            warn!(error = %e, "Reloading .env failed");
+        }
+        let defaults = match AppDefaults::load() {
+            Ok(defaults) => defaults,
+            Err(e) => {
+                warn!(error = %e, "Reloading defaults failed");
+                continue;
             }
+        };
+        background.db.set_body_storage(defaults.body_storage);
+        sync::set_max_pooled_connections(defaults.max_pooled_connections);
+        sync::set_memory_budget(defaults.memory_budget_bytes);
+        imap::set_timeouts(defaults.imap_timeouts);
+        sanitize::set_render_budget(defaults.html_render_budget);
+        preview::set_default_source(defaults.preview_source);
+        collation::set_collation(defaults.collation.clone());
+        configure_imap_trace(defaults.imap_trace_max_bytes);
+        let accounts = match background.db.list_accounts().await.and_then(|accounts| {
+            register_ciphers(background.db.as_ref(), &accounts)?;
+            Ok(accounts)
+        }) {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                warn!(error = %e, "Reloading accounts failed");
+                let _ = background
+                    .updates
+                    .send(tui::TuiEvent::Status("Settings reload failed".to_string()));
+                continue;
+            }
+        };
 
-            remaining = &remaining[end..];
+        info!(accounts = accounts.len(), "Settings reloaded");
+        let _ = background.updates.send(tui::TuiEvent::Status(format!(
+            "Settings reloaded ({} account(s))",
+            accounts.len()
+        )));
+        if !no_sync && !background.is_offline() {
+            let options = SyncOptions {
+                flag_conflicts: defaults.flag_conflicts,
+                ..options
+            };
+            if watch.is_some() {
+                watch = Some(defaults.max_concurrent_folders);
+                schedule.mark_started(&accounts, now_ts());
+            }
+            running = Some(background.spawn(accounts, defaults.max_concurrent_folders, options));
         } else {
-            // No valid closing found, just add the rest
-            result.push_str(&remaining[start..]);
-            break;
+            // No pass will refresh the list, so show changed folders and smart folders now.
+            match load_mail_items(
+                background.db.as_ref(),
+                &background.account_id,
+                background.display_tz,
+            )
+            .await
+            {
+                Ok(list) => {
+                    list.send(&background.updates);
+                }
+                Err(e) => {
+                    warn!(account = %background.account_id, error = %e, "Reloading messages failed")
+                }
+            }
         }
     }
 
-    result.push_str(remaining);
-    result
+    // On quit the pass was cancelled; wait for it to wind down at its next batch boundary.
+    if let Some(handle) = running {
+        let _ = handle.await;
+    }
 }
 
-fn decode_mime_word(word: &str) -> Option<String> {
-    // Format: =?charset?encoding?encoded-text?=
-    if !word.starts_with("=?") || !word.ends_with("?=") {
-        return None;
-    }
+/// Shared trace log in the data directory (`OTTO_IMAP_TRACE`); rotated copies get `.1`, `.2`, ...
+const IMAP_TRACE_FILE: &str = "imap-trace.log";
 
-    let inner = &word[2..word.len() - 2];
-    let parts: Vec<&str> = inner.splitn(3, '?').collect();
+/// How long quitting the TUI waits for a running sync to stop at a batch boundary.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(10);
 
-    if parts.len() != 3 {
-        return None;
+/// Upper bound on the startup cache check per account, so `--no-sync` stays quick offline.
+const CACHE_CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Spot-checks cached folders against the server (one STATUS each) for `--no-sync` runs and
+/// returns one line per folder, or account, that needs attention.
+pub(crate) async fn check_cache_freshness(
+    db: Arc<dyn MailStore>,
+    accounts: &[Account],
+) -> Vec<String> {
+    let engine = SyncEngine::new(db, 1);
+    let mut lines = Vec::new();
+    for account in accounts {
+        match tokio::time::timeout(CACHE_CHECK_TIMEOUT, engine.validate_cache(account)).await {
+            Ok(Ok(checks)) => lines.extend(
+                checks
+                    .iter()
+                    .filter(|c| c.freshness != CacheFreshness::Fresh)
+                    .map(|c| format!("{} {}: {}", account.email, c.folder, c.freshness)),
+            ),
+            Ok(Err(e)) => {
+                warn!(account = %account.id, error = %e, "Cache validation failed");
+                lines.push(format!(
+                    "{}: could not reach the server to check",
+                    account.email
+                ));
+            }
+            Err(_) => lines.push(format!("{}: server check timed out", account.email)),
+        }
     }
+    lines
+}
 
-    let encoding = parts[1].to_uppercase();
-    let encoded_text = parts[2];
+/// How often `watch_settings` checks `.env` and the accounts table for changes.
+const SETTINGS_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
-    match encoding.as_str() {
-        "Q" => decode_quoted_printable_rfc2047(encoded_text),
-        "B" => decode_base64_simple(encoded_text),
+/// Requests a reload when the `.env` file's mtime or any account's `updated_at` changes.
+async fn watch_settings(db: Arc<dyn MailStore>, reload: UnboundedSender<()>) {
+    // Resolves the same `.env` main loaded; without override this leaves the environment as is.
+    let env_path = dotenvy::dotenv().ok();
+    let fingerprint = || async {
+        let env_mtime = env_path
+            .as_ref()
+            .and_then(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok());
+        let accounts: Vec<(String, i64)> = db
+            .list_accounts()
+            .await
+            .map(|accounts| accounts.into_iter().map(|a| (a.id, a.updated_at)).collect())
+            .unwrap_or_default();
+        (env_mtime, accounts)
+    };
+
+    let mut last = fingerprint().await;
+    let mut ticker = tokio::time::interval(SETTINGS_POLL_INTERVAL);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let current = fingerprint().await;
+        if current != last {
+            last = current;
+            if reload.send(()).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// What in this invocation would connect to a server, if anything (checked in offline mode).
+fn network_command(cli: &Cli, needs_onboarding: bool) -> Option<&'static str> {
+    if let Some(Command::Accounts {
+        action: AccountsCommand::Add { .. } | AccountsCommand::Import { .. },
+    }) = cli.command
+    {
+        return Some("otto accounts");
+    }
+    if cli.add_account || needs_onboarding {
+        return Some("Onboarding");
+    }
+    if folder_op(cli).is_some() {
+        return Some("A folder operation");
+    }
+    match &cli.command {
+        Some(Command::Daemon) => Some("otto daemon"),
+        Some(Command::Verify(_)) => Some("otto verify"),
+        Some(Command::Backfill(_)) => Some("otto backfill"),
+        Some(Command::FetchBodies(_)) => Some("otto fetch-bodies"),
+        Some(Command::Refetch(_)) => Some("otto refetch"),
+        Some(Command::Trace(_)) => Some("otto trace"),
+        Some(Command::Append(_)) => Some("otto append"),
+        Some(Command::Send(args)) if !args.dry_run => Some("otto send"),
+        Some(Command::Notify(args)) if args.test => Some("otto notify --test"),
         _ => None,
     }
 }
 
-fn decode_quoted_printable_rfc2047(text: &str) -> Option<String> {
-    let mut result = Vec::new();
-    let mut i = 0;
-    let bytes = text.as_bytes();
-
-    while i < bytes.len() {
-        match bytes[i] {
-            b'=' if i + 2 < bytes.len() => {
-                // Try to decode hex
-                let hex_str = std::str::from_utf8(&bytes[i + 1..i + 3]).ok()?;
-                if let Ok(byte) = u8::from_str_radix(hex_str, 16) {
-                    result.push(byte);
-                    i += 3;
-                } else {
-                    // Not valid hex, just add the '='
-                    result.push(b'=');
-                    i += 1;
-                }
-            }
-            b'_' => {
-                result.push(b' ');
-                i += 1;
-            }
-            b => {
-                result.push(b);
-                i += 1;
+/// The TUI's message list with queued-op markers, plus the number of ops queued in total.
+/// Newest messages the TUI loads; smart folders (and sidebar counts the server hasn't reported)
+/// cover this window.
+const TUI_MESSAGE_WINDOW: usize = 200;
+
+/// What one TUI list refresh sends: the newest messages, the sidebar and the queued-op count.
+struct MailList {
+    items: Vec<tui::MailItem>,
+    sidebar: tui::Sidebar,
+    queued: usize,
+}
+
+impl MailList {
+    /// False once the TUI has gone away.
+    fn send(self, updates: &mpsc::Sender<tui::TuiEvent>) -> bool {
+        updates.send(tui::TuiEvent::Sidebar(self.sidebar)).is_ok()
+            && updates.send(tui::TuiEvent::MailItems(self.items)).is_ok()
+            && updates.send(tui::TuiEvent::QueuedOps(self.queued)).is_ok()
+    }
+}
+
+/// Reads the account fresh each time so smart folders saved elsewhere show on the next refresh.
+async fn load_mail_items(db: &dyn MailStore, account_id: &str, tz: DisplayTz) -> Result<MailList> {
+    let account = db
+        .list_accounts()
+        .await?
+        .into_iter()
+        .find(|a| a.id == account_id);
+    let smart: Vec<(String, SmartQuery)> = account
+        .iter()
+        .flat_map(|a| &a.settings.smart_folders)
+        .filter_map(|folder| match SmartQuery::parse(&folder.query) {
+            Ok(query) => Some((folder.name.clone(), query)),
+            Err(e) => {
+                warn!(account = %account_id, name = %folder.name, error = %e, "Skipping smart folder with an invalid query");
+                None
             }
+        })
+        .collect();
+
+    let messages = db.load_messages(account_id, TUI_MESSAGE_WINDOW).await?;
+    let mut items = tui::build_mail_items(&messages, tz);
+    let queued = db.list_pending_ops(account_id).await?;
+    let mut per_message: HashMap<&str, usize> = HashMap::new();
+    for op in &queued {
+        *per_message.entry(op.target.as_str()).or_default() += 1;
+    }
+    let folders: Vec<String> = account
+        .iter()
+        .flat_map(|a| a.settings.enabled_folders().cloned())
+        .collect();
+    let labels = FolderLabels::load(db, account_id).await?;
+    let now = now_ts();
+    let reply_today = today(tz);
+    let reply_later: HashMap<String, tui::ReplyMark> = db
+        .load_reply_later(account_id)
+        .await?
+        .into_iter()
+        .map(|entry| {
+            let mark = tui::ReplyMark {
+                due: entry.due,
+                overdue: entry.is_overdue(reply_today),
+            };
+            (entry.message_id, mark)
+        })
+        .collect();
+    let notes: HashMap<String, String> = db
+        .load_message_notes(account_id)
+        .await?
+        .into_iter()
+        .map(|entry| (entry.message_id, entry.note))
+        .collect();
+    let summaries = db.load_message_summaries(account_id).await?;
+    let settings = account.as_ref().map(|a| &a.settings);
+    for (item, (msg, _)) in items.iter_mut().zip(&messages) {
+        item.preview = preview::preview_text(
+            preview::source_for(settings, &msg.folder),
+            &item.body,
+            summaries.get(&item.id).map(String::as_str),
+            1,
+        );
+        item.reply_later = reply_later.get(&item.id).cloned();
+        item.note = notes.get(&item.id).cloned();
+        item.queued_ops = per_message.get(item.id.as_str()).copied().unwrap_or(0);
+        item.folders = folders
+            .iter()
+            .filter(|f| labels.contains(f, &msg.folder, &msg.labels))
+            .cloned()
+            .collect();
+        item.smart_folders = smart
+            .iter()
+            .filter(|(_, query)| query.matches_with_note(msg, item.note.as_deref(), now, tz))
+            .map(|(name, _)| name.clone())
+            .collect();
+    }
+
+    let mut sync = BTreeMap::new();
+    let mut quota = None;
+    let mut counts = BTreeMap::new();
+    if let Some(account) = &account {
+        if queued.is_empty() {
+            counts = db
+                .load_folder_counts(account_id)
+                .await?
+                .into_iter()
+                .collect();
+        }
+        quota = db
+            .load_account_quota(account_id)
+            .await?
+            .as_ref()
+            .and_then(status::quota_summary);
+        let states = db.list_folders(account_id).await?;
+        let runs = db.load_latest_sync_runs(account_id).await?;
+        for folder in sync::synced_folders(db, account).await? {
+            let state = tui::FolderSync {
+                last_sync_ts: states
+                    .iter()
+                    .find(|s| s.name == folder)
+                    .and_then(|s| s.last_sync_ts),
+                last_error: runs
+                    .iter()
+                    .find(|run| run.folder == folder && !run.ok)
+                    .map(|run| run.error.clone().unwrap_or_else(|| "failed".to_string())),
+            };
+            sync.insert(folder, state);
         }
     }
+    let sidebar = tui::Sidebar {
+        folders,
+        smart_folders: smart.into_iter().map(|(name, _)| name).collect(),
+        sync,
+        poll_interval_secs: 60
+            * i64::from(
+                account
+                    .as_ref()
+                    .map_or(5, |a| a.settings.poll_interval_minutes)
+                    .max(1),
+            ),
+        quota,
+        counts,
+    };
+    Ok(MailList {
+        items,
+        sidebar,
+        queued: queued.len(),
+    })
+}
+
+/// Today's date in the display timezone, for due dates.
+pub(crate) fn today(tz: DisplayTz) -> chrono::NaiveDate {
+    local_date(now_ts(), tz).unwrap_or_else(|| chrono::Local::now().date_naive())
+}
+
+/// Reports what a sync pass sent of the `before` ops queued when it started.
+pub(crate) fn flush_summary(before: usize, after: usize) -> String {
+    let sent = before.saturating_sub(after);
+    if after == 0 {
+        format!("Sent all {} queued change(s)", sent)
+    } else {
+        format!(
+            "Sent {} of {} queued change(s); {} still queued",
+            sent, before, after
+        )
+    }
+}
+
+pub(crate) fn folder_op(cli: &Cli) -> Option<(String, FolderOp)> {
+    if let Some(folder) = &cli.archive_folder {
+        return Some((
+            folder.clone(),
+            FolderOp::Archive {
+                before: cli.older_than,
+            },
+        ));
+    }
+    cli.mark_folder_read
+        .as_ref()
+        .map(|folder| (folder.clone(), FolderOp::MarkAllRead))
+}
+
+fn sync_options(cli: &Cli, defaults: &AppDefaults) -> SyncOptions {
+    SyncOptions {
+        force: cli.force,
+        headers_first: cli.headers_first,
+        flag_conflicts: defaults.flag_conflicts,
+        safe_mode: cli.safe_mode,
+        unread_only: cli.unread_only,
+    }
+}
+
+/// First Ctrl-C cancels `cancel`, so running syncs stop at their next batch boundary with
+/// their connections pooled; a second Ctrl-C exits at once.
+pub(crate) async fn cancel_on_ctrl_c(cancel: CancellationToken) {
+    if tokio::signal::ctrl_c().await.is_err() {
+        return;
+    }
+    warn!("Stopping after the current batch (Ctrl-C again to quit now)");
+    cancel.cancel();
+    if tokio::signal::ctrl_c().await.is_ok() {
+        std::process::exit(130);
+    }
+}
 
-    String::from_utf8(result).ok()
+/// Loads the keyring key of every account with column encryption so the store seals and opens
+/// its rows; a missing key is an error since those rows would be unreadable.
+pub(crate) fn register_ciphers(db: &dyn MailStore, accounts: &[Account]) -> Result<()> {
+    for account in accounts {
+        let cipher = if account.settings.encrypt_columns {
+            Some(ColumnCipher::load(&account.id)?)
+        } else {
+            None
+        };
+        db.register_cipher(&account.id, cipher);
+    }
+    Ok(())
 }
 
-fn decode_base64_simple(text: &str) -> Option<String> {
-    use base64::Engine;
-    let decoded = base64::engine::general_purpose::STANDARD
-        .decode(text.as_bytes())
-        .ok()?;
-    String::from_utf8(decoded).ok()
+/// Accounts matching an id/email filter; `None` selects every account.
+pub(crate) fn select_accounts<'a>(
+    accounts: &'a [Account],
+    filter: Option<&str>,
+) -> Vec<&'a Account> {
+    accounts
+        .iter()
+        .filter(|a| filter.is_none_or(|id| a.id == id || a.email == id))
+        .collect()
 }
@@ -0,0 +1,214 @@
+//! Cleanup rules: a smart-folder query with an age and an action, e.g. archive
+//! `from:news@example.com` older than 7 days, delete `label:Promotions` after 30 days, or label
+//! `from:billing@example.com` as Receipts right away (rules learned by `crate::learn`). The
+//! daemon runs an account's rules before its scheduled pass, at most once an hour. Matches are
+//! queued like TUI actions (`apply_message_op`), so that pass sends them and a server rejection
+//! rolls them back. Runs that cleaned something go to `cleanup_runs` for `otto cleanup --report`.
+use std::collections::HashSet;
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::smart_folders::SmartQuery;
+use crate::storage::MailStore;
+use crate::storage::cleanup::CleanupRun;
+use crate::storage::ops::MessageOp;
+use crate::timefmt::DisplayTz;
+use crate::types::{Account, FolderRole, MessageRecord};
+
+/// Least time between two scheduled runs of an account's rules.
+pub const CLEANUP_INTERVAL_SECS: i64 = 60 * 60;
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupAction {
+    /// Out of the Inbox into All Mail (in All Mail mode, drop the `\Inbox` label).
+    Archive,
+    /// Into Trash.
+    Delete,
+    /// Add the rule's `label`, leaving the message where it is.
+    Label,
+}
+
+impl CleanupAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CleanupAction::Archive => "archive",
+            CleanupAction::Delete => "delete",
+            CleanupAction::Label => "label",
+        }
+    }
+}
+
+/// A named rule (stored on the account, run in order).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CleanupRule {
+    pub name: String,
+    /// Smart-folder query selecting the messages (see `crate::smart_folders`).
+    pub query: String,
+    /// Only messages received more than this many days ago.
+    pub older_than_days: u32,
+    pub action: CleanupAction,
+    /// The label a `label` rule adds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl CleanupRule {
+    /// Messages received before this (unix seconds) are old enough.
+    pub fn cutoff(&self, now: i64) -> i64 {
+        now - i64::from(self.older_than_days) * DAY_SECS
+    }
+
+    /// The op queued for each match; `None` for a `label` rule without a label.
+    fn op(&self) -> Option<MessageOp> {
+        match self.action {
+            CleanupAction::Archive => Some(MessageOp::Archive),
+            CleanupAction::Delete => Some(MessageOp::Delete),
+            CleanupAction::Label => self.label.clone().map(MessageOp::AddLabel),
+        }
+    }
+
+    /// The action as shown in listings (`label Receipts` for label rules).
+    pub fn describe_action(&self) -> String {
+        match (&self.action, &self.label) {
+            (CleanupAction::Label, Some(label)) => format!("label {}", label),
+            (action, _) => action.as_str().to_string(),
+        }
+    }
+
+    /// What the rule would clean: old enough, matching the query, and not already where the
+    /// action leaves them (in Trash, in All Mail without the `\Inbox` label, or carrying the
+    /// label).
+    pub fn select<'a>(
+        &self,
+        query: &SmartQuery,
+        messages: &'a [MessageRecord],
+        now: i64,
+        tz: DisplayTz,
+        archive: &str,
+        trash: &str,
+    ) -> Vec<&'a MessageRecord> {
+        let cutoff = self.cutoff(now);
+        messages
+            .iter()
+            .filter(|msg| msg.internal_date.is_some_and(|date| date < cutoff))
+            .filter(|msg| msg.folder != trash)
+            .filter(|msg| {
+                self.action != CleanupAction::Archive
+                    || msg.folder != archive
+                    || msg.labels.iter().any(|l| l.eq_ignore_ascii_case("\\Inbox"))
+            })
+            .filter(|msg| match (&self.action, &self.label) {
+                (CleanupAction::Label, Some(label)) => {
+                    !msg.labels.iter().any(|l| l.eq_ignore_ascii_case(label))
+                }
+                _ => true,
+            })
+            .filter(|msg| query.matches(msg, now, tz))
+            .collect()
+    }
+}
+
+/// A rule age: `<N>d`, `<N>w` or a bare number of days.
+pub fn parse_age(raw: &str) -> Result<u32> {
+    let raw = raw.trim();
+    let (number, unit) = match raw.char_indices().last() {
+        Some((at, 'd' | 'D')) => (&raw[..at], 1),
+        Some((at, 'w' | 'W')) => (&raw[..at], 7),
+        _ => (raw, 1),
+    };
+    let count: u32 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid age {:?}; expected e.g. 7d or 2w", raw))?;
+    count
+        .checked_mul(unit)
+        .ok_or_else(|| anyhow!("age {:?} is too large", raw))
+}
+
+/// What one rule cleaned (or, in a dry run, would clean).
+#[derive(Debug)]
+pub struct CleanupOutcome {
+    pub rule: String,
+    pub action: CleanupAction,
+    pub messages: Vec<MessageRecord>,
+}
+
+/// Runs the account's rules in order (only the rule named `only`, if given) and queues the
+/// matches, unless `dry_run`. A message taken by an earlier rule is left to it. Rules whose
+/// query no longer parses are skipped with a warning.
+pub async fn run_rules(
+    db: &dyn MailStore,
+    account: &Account,
+    only: Option<&str>,
+    now: i64,
+    tz: DisplayTz,
+    dry_run: bool,
+) -> Result<Vec<CleanupOutcome>> {
+    let rules: Vec<&CleanupRule> = account
+        .settings
+        .cleanup_rules
+        .iter()
+        .filter(|rule| only.is_none_or(|name| rule.name == name))
+        .collect();
+    let Some(oldest_cutoff) = rules.iter().map(|rule| rule.cutoff(now)).max() else {
+        return Ok(Vec::new());
+    };
+    let messages = db.load_messages_before(&account.id, oldest_cutoff).await?;
+    let archive = db.role_folder(&account.id, FolderRole::All).await?;
+    let trash = db.role_folder(&account.id, FolderRole::Trash).await?;
+
+    let mut taken: HashSet<&str> = HashSet::new();
+    let mut outcomes = Vec::new();
+    for rule in rules {
+        let Some(op) = rule.op() else {
+            warn!(account = %account.id, rule = %rule.name, "Skipping label cleanup rule without a label");
+            continue;
+        };
+        let query = match SmartQuery::parse(&rule.query) {
+            Ok(query) => query,
+            Err(e) => {
+                warn!(account = %account.id, rule = %rule.name, error = %e, "Skipping cleanup rule with an invalid query");
+                continue;
+            }
+        };
+        let selected: Vec<&MessageRecord> = rule
+            .select(&query, &messages, now, tz, &archive, &trash)
+            .into_iter()
+            .filter(|msg| taken.insert(msg.id.as_str()))
+            .collect();
+        if selected.is_empty() {
+            continue;
+        }
+        let ids: Vec<String> = selected.iter().map(|msg| msg.id.clone()).collect();
+        if !dry_run {
+            db.apply_message_op(&account.id, &op, &ids)
+                .await
+                .with_context(|| format!("queueing cleanup rule {}", rule.name))?;
+            db.record_cleanup_run(&CleanupRun {
+                account_id: account.id.clone(),
+                ts: now,
+                rule: rule.name.clone(),
+                action: rule.action.as_str().to_string(),
+                message_ids: ids,
+            })
+            .await?;
+            info!(
+                account = %account.id,
+                rule = %rule.name,
+                action = rule.action.as_str(),
+                count = selected.len(),
+                "Cleanup rule queued messages"
+            );
+        }
+        outcomes.push(CleanupOutcome {
+            rule: rule.name.clone(),
+            action: rule.action,
+            messages: selected.into_iter().cloned().collect(),
+        });
+    }
+    Ok(outcomes)
+}
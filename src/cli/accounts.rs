@@ -0,0 +1,165 @@
+use anyhow::{Context, Result, bail};
+
+use crate::app::{Session, select_accounts};
+use crate::cli::{AccountsCommand, Cli};
+use crate::credentials;
+use crate::onboarding::{self, PasswordAccountSpec};
+use crate::presets::{self, PresetAuth};
+use crate::types::{Credential, Provider, now_ts};
+
+/// `otto accounts add|import|presets|password`; runs before OAuth onboarding, which would
+/// otherwise start for a fresh store.
+pub(crate) async fn run(cli: &Cli, action: &AccountsCommand) -> Result<()> {
+    let session = Session::open(cli).await?;
+    session.check_network()?;
+    let specs = match action {
+        AccountsCommand::Add {
+            email,
+            host,
+            preset,
+            port,
+            tls,
+            password_cmd,
+            password_stdin,
+        } => {
+            let spec = PasswordAccountSpec {
+                email: email.clone(),
+                host: host.clone(),
+                preset: preset.clone(),
+                port: *port,
+                tls: *tls,
+                password_cmd: password_cmd.clone(),
+            };
+            spec.endpoint()?;
+            if *password_stdin && !session.accounts.iter().any(|a| a.id == spec.email) {
+                credentials::store_password(&spec.email, &read_password_stdin()?)?;
+            }
+            vec![spec]
+        }
+        AccountsCommand::Import { file } => {
+            let text = std::fs::read_to_string(file)
+                .with_context(|| format!("reading {}", file.display()))?;
+            onboarding::parse_accounts_file(&text)?
+        }
+        AccountsCommand::Presets => {
+            for preset in presets::PRESETS {
+                let auth = match &preset.auth {
+                    PresetAuth::OAuth(_) => {
+                        format!("OAuth (otto --add-account --provider {})", preset.name)
+                    }
+                    PresetAuth::AppPassword { where_to_get } => {
+                        format!("app password ({})", where_to_get)
+                    }
+                };
+                println!(
+                    "{:<9} {} - {}:{} ({})",
+                    preset.name,
+                    preset.label,
+                    preset.host,
+                    preset.port,
+                    preset.tls.as_str()
+                );
+                println!("          sign-in: {}", auth);
+                println!(
+                    "          folders: {}",
+                    preset
+                        .folders
+                        .iter()
+                        .map(|(_, name)| *name)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            return Ok(());
+        }
+        AccountsCommand::Password {
+            account,
+            cmd,
+            stdin,
+            oauth: _,
+        } => {
+            let Some(found) = select_accounts(&session.accounts, Some(account))
+                .into_iter()
+                .next()
+            else {
+                bail!("no account matches {}", account);
+            };
+            let mut updated = found.clone();
+            updated.settings.credential = match cmd {
+                Some(command) => Credential::PasswordCommand {
+                    command: command.clone(),
+                },
+                None if *stdin => {
+                    credentials::store_password(&updated.id, &read_password_stdin()?)?;
+                    Credential::Password
+                }
+                None if updated.provider == Provider::Imap => bail!(
+                    "{}: generic IMAP accounts have no OAuth sign-in; use --cmd or --stdin",
+                    updated.email
+                ),
+                None => Credential::OAuth,
+            };
+            if updated.settings.credential != Credential::Password {
+                credentials::delete_password(&updated.id)?;
+            }
+            updated.updated_at = now_ts();
+            session.db.save_account(&updated).await?;
+            println!(
+                "{}: signs in with {}",
+                updated.email,
+                updated.settings.credential.describe()
+            );
+            return Ok(());
+        }
+    };
+    for spec in &specs {
+        if session.accounts.iter().any(|a| a.id == spec.email) {
+            println!("{}: already added; skipped", spec.email);
+            continue;
+        }
+        let (account, discovered) =
+            onboarding::onboard_password_account(&session.defaults, spec).await?;
+        session.db.save_account(&account).await?;
+        if !discovered.is_empty() {
+            session
+                .db
+                .record_discovered_folders(&account.id, &discovered)
+                .await?;
+        }
+        println!(
+            "{}: added ({}:{}, {} folder(s){})",
+            account.email,
+            account.settings.imap.host,
+            account.settings.imap.port,
+            account.settings.folders.len(),
+            if discovered.is_empty() {
+                "; server not reached"
+            } else {
+                ""
+            }
+        );
+        if discovered.is_empty()
+            && let Some(preset) = spec.preset()?
+            && let PresetAuth::AppPassword { where_to_get } = &preset.auth
+        {
+            println!(
+                "  {} needs an app password, not the account password: {}",
+                preset.label, where_to_get
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The first line of stdin, for passwords piped in by scripts (`pass show x | otto ...`).
+fn read_password_stdin() -> Result<String> {
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .context("reading the password from stdin")?;
+    let password = line.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        bail!("no password on stdin");
+    }
+    Ok(password.to_string())
+}
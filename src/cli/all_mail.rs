@@ -0,0 +1,52 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::sync;
+use crate::types::{Provider, now_ts};
+
+#[derive(Args, Debug)]
+pub struct AllMailArgs {
+    /// Account id/email to update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Go back to syncing each folder separately.
+    #[arg(long)]
+    pub disable: bool,
+}
+
+/// Turns All Mail mode on or off and prints the folders each account syncs.
+pub(crate) async fn run(cli: &Cli, args: &AllMailArgs) -> Result<()> {
+    let AllMailArgs { account, disable } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account to update");
+    }
+    for account in selected {
+        if account.provider != Provider::GmailImap && !*disable {
+            println!(
+                "{}: All Mail mode needs a Gmail account; skipped",
+                account.email
+            );
+            continue;
+        }
+        let mut account = account.clone();
+        if account.settings.all_mail_mode != *disable {
+            account.settings.all_mail_mode = !*disable;
+            account.updated_at = now_ts();
+            session.db.save_account(&account).await?;
+        }
+        let folders = sync::synced_folders(session.db.as_ref(), &account).await?;
+        println!(
+            "{}: All Mail mode {}; syncing {}",
+            account.email,
+            if *disable { "off" } else { "on" },
+            folders.join(", ")
+        );
+    }
+    Ok(())
+}
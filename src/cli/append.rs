@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+
+use crate::app::{Session, cancel_on_ctrl_c, select_accounts};
+use crate::cli::Cli;
+use crate::imap::AppendMessage;
+
+#[derive(Args, Debug)]
+pub struct AppendArgs {
+    /// Folder to upload into.
+    pub folder: String,
+
+    /// Files or directories of `.eml` files.
+    #[arg(required = true, value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+
+    /// Account id/email (required with several accounts).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Store the messages as read (`\Seen`).
+    #[arg(long)]
+    pub seen: bool,
+
+    /// Extra flag to set on every message (repeatable), e.g. `--flag '\Flagged'`.
+    #[arg(long = "flag", value_name = "FLAG")]
+    pub flags: Vec<String>,
+}
+
+/// Uploads `.eml` files to a server folder; they are cached by the folder's next sync.
+pub(crate) async fn run(cli: &Cli, args: &AppendArgs) -> Result<()> {
+    let AppendArgs {
+        folder,
+        paths,
+        account,
+        seen,
+        flags,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    let [account] = selected.as_slice() else {
+        bail!(
+            "otto append needs exactly one account; {} match (use --account)",
+            selected.len()
+        );
+    };
+    if cli.safe_mode || account.settings.safe_mode {
+        bail!("{} is in safe mode; not uploading", account.email);
+    }
+    let folder = &account.settings.server_folder(folder);
+    let files = eml_files(paths)?;
+    if files.is_empty() {
+        bail!("no .eml files found");
+    }
+    let mut flags = flags.clone();
+    if *seen {
+        flags.push("\\Seen".to_string());
+    }
+    let messages = files
+        .iter()
+        .map(|path| {
+            let raw = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+            Ok(AppendMessage::from_raw(raw, flags.clone()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let engine = session.engine();
+    let interrupt = tokio::spawn(cancel_on_ctrl_c(engine.cancel_token()));
+    let result = engine.append_messages(account, folder, &messages).await;
+    interrupt.abort();
+    let report = result?;
+    for (idx, reason) in &report.rejected {
+        println!("  refused {}: {}", files[*idx].display(), reason);
+    }
+    println!(
+        "Uploaded {} of {} message(s) to {}; they appear after the next sync",
+        report.appended,
+        files.len(),
+        folder
+    );
+    Ok(())
+}
+
+/// `paths` with directories replaced by the `.eml` files directly inside them, sorted by name.
+fn eml_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut found: Vec<PathBuf> = std::fs::read_dir(path)
+            .with_context(|| format!("reading directory {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| {
+                file.is_file()
+                    && file
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("eml"))
+            })
+            .collect();
+        found.sort();
+        files.extend(found);
+    }
+    Ok(files)
+}
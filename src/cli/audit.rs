@@ -0,0 +1,94 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::imap::build_uid_sequence;
+use crate::storage::audit::AuditRecord;
+use crate::timefmt::{DisplayTz, format_absolute};
+use crate::types::Account;
+
+#[derive(Args, Debug)]
+pub struct AuditArgs {
+    /// Account id/email to show (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Only entries from this date on (YYYY-MM-DD).
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<NaiveDate>,
+
+    /// Show at most N entries, newest first.
+    #[arg(long, value_name = "N", default_value_t = 50)]
+    pub limit: usize,
+}
+
+/// Prints the audit log; read-only like `otto status`, so it never onboards or connects.
+pub(crate) async fn run(cli: &Cli, args: &AuditArgs) -> Result<()> {
+    let AuditArgs {
+        account,
+        since,
+        limit,
+    } = args;
+    let session = Session::open(cli).await?;
+    let since = since
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc().timestamp());
+    let selected: Vec<Option<&Account>> = match account {
+        Some(filter) => select_accounts(&session.accounts, Some(filter))
+            .into_iter()
+            .map(Some)
+            .collect(),
+        None => vec![None],
+    };
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    for account in selected {
+        let records = session
+            .db
+            .load_audit_log(account.map(|a| a.id.as_str()), since, *limit)
+            .await?;
+        for record in &records {
+            print_audit(&session.accounts, record, session.defaults.display_tz);
+        }
+    }
+    Ok(())
+}
+
+fn print_audit(accounts: &[Account], record: &AuditRecord, tz: DisplayTz) {
+    let email = accounts
+        .iter()
+        .find(|a| a.id == record.account_id)
+        .map_or(record.account_id.as_str(), |a| a.email.as_str());
+    let target = match &record.destination {
+        Some(dest) => format!("{} -> {}", record.folder, dest),
+        None => record.folder.clone(),
+    };
+    let op_ids = if record.op_ids.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " ops {}",
+            record
+                .op_ids
+                .iter()
+                .map(|id| format!("#{}", id))
+                .collect::<Vec<_>>()
+                .join(",")
+        )
+    };
+    println!(
+        "{}  {}  {} {} {}  uids {}{}  {}",
+        format_absolute(record.ts, tz),
+        email,
+        record.actor,
+        record.op,
+        target,
+        build_uid_sequence(&record.uids),
+        op_ids,
+        record.outcome
+    );
+}
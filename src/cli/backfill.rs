@@ -0,0 +1,39 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+
+#[derive(Args, Debug)]
+pub struct BackfillArgs {
+    /// Account id/email to backfill (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Oldest date to fetch (YYYY-MM-DD).
+    #[arg(long)]
+    pub until: NaiveDate,
+}
+
+/// Fetches mail older than each account's cutoff back to `--until`.
+pub(crate) async fn run(cli: &Cli, args: &BackfillArgs) -> Result<()> {
+    let BackfillArgs { account, until } = args;
+    let session = Session::ready(cli).await?;
+    let engine = session.engine();
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account to backfill");
+    }
+    for account in selected {
+        match engine.backfill(account, *until).await {
+            Ok(n) => println!(
+                "{}: backfilled {} message(s) back to {}",
+                account.email, n, until
+            ),
+            Err(e) => warn!(account = %account.id, error = %e, "Backfill failed"),
+        }
+    }
+    Ok(())
+}
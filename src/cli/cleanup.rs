@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use clap::Args;
+use tracing::warn;
+
+use crate::address::friendly_from;
+use crate::app::{Session, select_accounts};
+use crate::cleanup::{self, CleanupAction, CleanupRule};
+use crate::cli::Cli;
+use crate::encoded_words::decode_mime_words;
+use crate::smart_folders::SmartQuery;
+use crate::timefmt::{DisplayTz, format_absolute, format_timestamp};
+use crate::types::{MessageRecord, now_ts};
+
+#[derive(Args, Debug)]
+pub struct CleanupArgs {
+    /// Account id/email to show or update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Rule to add, replace, remove or run (default: all of them).
+    pub name: Option<String>,
+
+    /// Query terms, e.g. "from:news@example.com" or "label:Promotions is:read".
+    #[arg(requires_all = ["name", "older_than"], conflicts_with_all = ["remove", "run", "report"])]
+    pub query: Option<String>,
+
+    /// Only messages older than this: <N>d or <N>w.
+    #[arg(long, value_name = "AGE", requires = "query")]
+    pub older_than: Option<String>,
+
+    /// Move matches to Trash instead of archiving them.
+    #[arg(long, requires = "query")]
+    pub delete: bool,
+
+    /// Add this label to matches instead of archiving them.
+    #[arg(
+        long,
+        value_name = "LABEL",
+        requires = "query",
+        conflicts_with = "delete"
+    )]
+    pub label: Option<String>,
+
+    /// Remove the named rule.
+    #[arg(long, requires = "name", conflicts_with_all = ["run", "report"])]
+    pub remove: bool,
+
+    /// Run the rules now; the changes are queued and sent by the next sync.
+    #[arg(long, conflicts_with = "report")]
+    pub run: bool,
+
+    /// With --run, list what would be cleaned without changing anything.
+    #[arg(long, requires = "run")]
+    pub dry_run: bool,
+
+    /// Show what earlier runs cleaned, newest first.
+    #[arg(long)]
+    pub report: bool,
+
+    /// With --report, only runs from this date on (YYYY-MM-DD).
+    #[arg(long, value_name = "DATE", requires = "report")]
+    pub since: Option<NaiveDate>,
+}
+
+/// Edits, lists or runs cleanup rules, or reports what earlier runs cleaned.
+pub(crate) async fn run(cli: &Cli, args: &CleanupArgs) -> Result<()> {
+    let CleanupArgs {
+        account,
+        name,
+        query,
+        older_than,
+        delete,
+        label,
+        remove,
+        run,
+        dry_run,
+        report,
+        since,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let tz = session.defaults.display_tz;
+
+    if *report {
+        let since = since
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|dt| dt.and_utc().timestamp());
+        for account in selected {
+            let runs = session
+                .db
+                .load_cleanup_runs(Some(&account.id), since, CLEANUP_REPORT_RUNS)
+                .await?;
+            for run in runs
+                .iter()
+                .filter(|r| name.is_none() || Some(&r.rule) == name.as_ref())
+            {
+                let cached = session
+                    .db
+                    .load_messages_by_ids(&account.id, &run.message_ids)
+                    .await?;
+                println!(
+                    "{}  {}  {}: {} {} message(s)",
+                    format_absolute(run.ts, tz),
+                    account.email,
+                    run.rule,
+                    run.action,
+                    run.message_ids.len()
+                );
+                for msg in &cached {
+                    println!("  {}", cleanup_line(msg, tz));
+                }
+                if cached.len() < run.message_ids.len() {
+                    println!(
+                        "  ({} no longer cached)",
+                        run.message_ids.len() - cached.len()
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let new_rule = match (name, query) {
+        (Some(name), Some(query)) => {
+            SmartQuery::parse(query).with_context(|| format!("invalid query {:?}", query))?;
+            Some(CleanupRule {
+                name: name.clone(),
+                query: query.clone(),
+                older_than_days: cleanup::parse_age(older_than.as_deref().unwrap_or_default())?,
+                action: if *delete {
+                    CleanupAction::Delete
+                } else if label.is_some() {
+                    CleanupAction::Label
+                } else {
+                    CleanupAction::Archive
+                },
+                label: label.clone(),
+            })
+        }
+        _ => None,
+    };
+    for account in selected {
+        let mut account = account.clone();
+        let rules = &mut account.settings.cleanup_rules;
+        let changed = match (&new_rule, name) {
+            (Some(rule), _) => {
+                match rules.iter_mut().find(|r| r.name == rule.name) {
+                    Some(existing) => *existing = rule.clone(),
+                    None => rules.push(rule.clone()),
+                }
+                true
+            }
+            (None, Some(name)) if *remove => {
+                let before = rules.len();
+                rules.retain(|r| &r.name != name);
+                if rules.len() == before {
+                    warn!(account = %account.id, name = %name, "No such cleanup rule");
+                }
+                rules.len() != before
+            }
+            _ => false,
+        };
+        if changed {
+            account.updated_at = now_ts();
+            session.db.save_account(&account).await?;
+        }
+
+        println!("{}:", account.email);
+        if *run {
+            if cli.safe_mode || account.settings.safe_mode {
+                warn!(account = %account.id, "Safe mode enabled; skipping cleanup rules");
+                continue;
+            }
+            let outcomes = cleanup::run_rules(
+                session.db.as_ref(),
+                &account,
+                name.as_deref(),
+                now_ts(),
+                tz,
+                *dry_run,
+            )
+            .await?;
+            if outcomes.is_empty() {
+                println!("  nothing to clean");
+            }
+            for outcome in &outcomes {
+                println!(
+                    "  {}: {} {} message(s){}",
+                    outcome.rule,
+                    outcome.action.as_str(),
+                    outcome.messages.len(),
+                    if *dry_run { " (dry run)" } else { "" }
+                );
+                for msg in &outcome.messages {
+                    println!("    {}", cleanup_line(msg, tz));
+                }
+            }
+            continue;
+        }
+        for rule in &account.settings.cleanup_rules {
+            if name.is_none() || Some(&rule.name) == name.as_ref() {
+                println!(
+                    "  {}  {} older than {}d  {}",
+                    rule.name,
+                    rule.describe_action(),
+                    rule.older_than_days,
+                    rule.query
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One cleaned message in `otto cleanup` output: date, sender and subject.
+fn cleanup_line(msg: &MessageRecord, tz: DisplayTz) -> String {
+    format!(
+        "{}  {} — {}",
+        format_timestamp(msg.internal_date, tz),
+        friendly_from(msg.from_name.as_deref(), msg.from.as_deref()),
+        decode_mime_words(msg.subject.as_deref().unwrap_or("(No Subject)"))
+    )
+}
+
+/// Runs shown per account by `otto cleanup --report`.
+const CLEANUP_REPORT_RUNS: usize = 20;
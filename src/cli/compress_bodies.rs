@@ -0,0 +1,44 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+
+#[derive(Args, Debug)]
+pub struct CompressBodiesArgs {
+    /// Account id/email to process (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Skip the VACUUM (freed space is then only reused by later writes).
+    #[arg(long)]
+    pub no_vacuum: bool,
+}
+
+/// Compresses raw bodies stored before compression, then vacuums the store.
+pub(crate) async fn run(cli: &Cli, args: &CompressBodiesArgs) -> Result<()> {
+    let CompressBodiesArgs { account, no_vacuum } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let mut compressed = 0;
+    for account in selected {
+        let stats = session.db.compress_raw_bodies(&account.id).await?;
+        compressed += stats.compressed;
+        println!(
+            "{}: compressed {} raw body(ies), {:.1} MiB -> {:.1} MiB",
+            account.email,
+            stats.compressed,
+            stats.bytes_before as f64 / (1024.0 * 1024.0),
+            stats.bytes_after as f64 / (1024.0 * 1024.0)
+        );
+    }
+    if compressed > 0 && !*no_vacuum {
+        println!("Vacuuming the database...");
+        session.db.vacuum().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::storage::ops::{ConflictResolution, OpConflict};
+use crate::timefmt::{DisplayTz, format_absolute};
+use crate::types::Account;
+
+#[derive(Args, Debug)]
+pub struct ConflictsArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Send the local changes on the next sync, overwriting the server.
+    #[arg(long, conflicts_with = "keep_server")]
+    pub keep_local: bool,
+
+    /// Take the server's flags and labels and drop the local changes.
+    #[arg(long)]
+    pub keep_server: bool,
+
+    /// Op ids to settle (default: every conflict of the selected accounts).
+    pub ids: Vec<i64>,
+}
+
+/// Lists held-back changes, or settles them with `--keep-local`/`--keep-server`. Never
+/// onboards or connects.
+pub(crate) async fn run(cli: &Cli, args: &ConflictsArgs) -> Result<()> {
+    let ConflictsArgs {
+        account,
+        keep_local,
+        keep_server,
+        ids,
+    } = args;
+    let session = Session::open(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let resolution = if *keep_local {
+        Some(ConflictResolution::KeepLocal)
+    } else if *keep_server {
+        Some(ConflictResolution::KeepServer)
+    } else {
+        None
+    };
+    for account in selected {
+        let conflicts = session.db.list_op_conflicts(&account.id).await?;
+        let Some(resolution) = resolution else {
+            for conflict in &conflicts {
+                print_conflict(account, conflict, session.defaults.display_tz);
+            }
+            continue;
+        };
+        let chosen: Vec<i64> = conflicts
+            .iter()
+            .map(|c| c.id)
+            .filter(|id| ids.is_empty() || ids.contains(id))
+            .collect();
+        let settled = session.db.resolve_op_conflicts(&chosen, resolution).await?;
+        println!(
+            "{}: {} {} conflicting change(s)",
+            account.email,
+            match resolution {
+                ConflictResolution::KeepLocal => "released",
+                ConflictResolution::KeepServer => "dropped",
+            },
+            settled
+        );
+    }
+    Ok(())
+}
+
+/// One `otto audit` line: when, account, actor, command, target, UIDs, settled ops, outcome.
+fn print_conflict(account: &Account, conflict: &OpConflict, tz: DisplayTz) {
+    let op = match conflict.op.label().or(conflict.op.destination()) {
+        Some(arg) => format!("{} {}", conflict.op.kind(), arg),
+        None => conflict.op.kind().to_string(),
+    };
+    let location = match (&conflict.folder, conflict.uid) {
+        (Some(folder), Some(uid)) => format!("{} uid {}", folder, uid),
+        (Some(folder), None) => folder.clone(),
+        _ => "(message gone)".to_string(),
+    };
+    println!(
+        "#{}  {}  {}  {}  {}  {}",
+        conflict.id,
+        account.email,
+        format_absolute(conflict.detected_at, tz),
+        op,
+        location,
+        conflict.subject.as_deref().unwrap_or("(no subject)")
+    );
+    println!(
+        "    local:  flags [{}] labels [{}]",
+        conflict.local_flags.join(" "),
+        conflict.local_labels.join(" ")
+    );
+    println!(
+        "    server: flags [{}] labels [{}]",
+        conflict.server_flags.join(" "),
+        conflict.server_labels.join(" ")
+    );
+}
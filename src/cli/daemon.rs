@@ -0,0 +1,16 @@
+use anyhow::Result;
+
+use crate::app::Session;
+use crate::cli::Cli;
+use crate::daemon;
+
+/// `otto daemon`: syncs on a schedule until stopped.
+pub(crate) async fn run(cli: &Cli) -> Result<()> {
+    let session = Session::ready(cli).await?;
+    daemon::run(
+        session.db.clone(),
+        &session.defaults,
+        session.sync_options(),
+    )
+    .await
+}
@@ -0,0 +1,62 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::storage::crypto::ColumnCipher;
+
+#[derive(Args, Debug)]
+pub struct EncryptColumnsArgs {
+    /// Account id/email to update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Decrypt the columns again and turn encryption off.
+    #[arg(long)]
+    pub disable: bool,
+}
+
+/// Seals or opens the sensitive columns of accounts and flips their `encrypt_columns` setting.
+pub(crate) async fn run(cli: &Cli, args: &EncryptColumnsArgs) -> Result<()> {
+    let EncryptColumnsArgs { account, disable } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account to update");
+    }
+    for account in selected {
+        if account.settings.encrypt_columns != *disable {
+            println!(
+                "{}: column encryption is already {}",
+                account.email,
+                if *disable { "off" } else { "on" }
+            );
+            continue;
+        }
+        let resealed = if *disable {
+            let cipher = ColumnCipher::load(&account.id)?;
+            let n = session
+                .db
+                .reseal_account(&account.id, Some(&cipher), None)
+                .await?;
+            session.db.register_cipher(&account.id, None);
+            n
+        } else {
+            let cipher = ColumnCipher::load_or_create(&account.id)?;
+            let n = session
+                .db
+                .reseal_account(&account.id, None, Some(&cipher))
+                .await?;
+            session.db.register_cipher(&account.id, Some(cipher));
+            n
+        };
+        println!(
+            "{}: {} {} row(s)",
+            account.email,
+            if *disable { "decrypted" } else { "encrypted" },
+            resealed
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+
+#[derive(Args, Debug)]
+pub struct FetchBodiesArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Message ids to fetch (default: every skipped body).
+    pub ids: Vec<String>,
+}
+
+/// Downloads bodies skipped for exceeding the size limit.
+pub(crate) async fn run(cli: &Cli, args: &FetchBodiesArgs) -> Result<()> {
+    let FetchBodiesArgs { account, ids } = args;
+    let session = Session::ready(cli).await?;
+    let engine = session.engine();
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    for account in selected {
+        match engine.fetch_oversized_bodies(account, ids).await {
+            Ok(n) => println!("{}: fetched {} body(ies)", account.email, n),
+            Err(e) => warn!(account = %account.id, error = %e, "Fetching bodies failed"),
+        }
+    }
+    Ok(())
+}
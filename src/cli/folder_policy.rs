@@ -0,0 +1,124 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::types::{BodyFetch, PreviewSource, now_ts};
+
+#[derive(Args, Debug)]
+pub struct FolderPolicyArgs {
+    /// Account id/email to update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Folder the policy applies to.
+    #[arg(long)]
+    pub folder: String,
+
+    /// Folder-specific cutoff date (YYYY-MM-DD).
+    #[arg(long, value_name = "DATE", conflicts_with = "clear_cutoff")]
+    pub cutoff: Option<NaiveDate>,
+
+    /// Drop the folder cutoff and use the account cutoff again.
+    #[arg(long)]
+    pub clear_cutoff: bool,
+
+    /// Store headers, flags and labels only; never download bodies. Bodies already
+    /// cached in the folder are deleted.
+    #[arg(long, conflicts_with = "full_bodies")]
+    pub metadata_only: bool,
+
+    /// Download full bodies (the default policy).
+    #[arg(long)]
+    pub full_bodies: bool,
+
+    /// Leave the folder out of sync and backfill.
+    #[arg(long, conflicts_with = "enable")]
+    pub disable: bool,
+
+    /// Sync the folder again after --disable.
+    #[arg(long)]
+    pub enable: bool,
+
+    /// Where the folder's list previews come from: the body without quotes and
+    /// boilerplate, the stored summary, or the first line as-is.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "SOURCE",
+        conflicts_with = "default_preview"
+    )]
+    pub preview: Option<PreviewSource>,
+
+    /// Use the global preview source (`OTTO_PREVIEW_SOURCE`) again.
+    #[arg(long)]
+    pub default_preview: bool,
+}
+
+/// Updates a folder's sync overrides; `--metadata-only` also drops its cached bodies.
+pub(crate) async fn run(cli: &Cli, args: &FolderPolicyArgs) -> Result<()> {
+    let FolderPolicyArgs {
+        account,
+        folder,
+        cutoff,
+        clear_cutoff,
+        metadata_only,
+        full_bodies,
+        disable,
+        enable,
+        preview,
+        default_preview,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account to update");
+    }
+    for account in selected {
+        let folder = &account.settings.server_folder(folder);
+        if !account.settings.folders.contains(folder) {
+            warn!(account = %account.id, folder = %folder, "Folder is not in the account's sync list");
+        }
+        let mut account = account.clone();
+        let policy = account
+            .settings
+            .folder_policies
+            .entry(folder.clone())
+            .or_default();
+        if let Some(date) = cutoff {
+            policy.cutoff_since = Some(*date);
+        }
+        if *clear_cutoff {
+            policy.cutoff_since = None;
+        }
+        if *metadata_only {
+            policy.body_fetch = BodyFetch::MetadataOnly;
+        }
+        if *full_bodies {
+            policy.body_fetch = BodyFetch::Full;
+        }
+        if *disable {
+            policy.enabled = false;
+        }
+        if *enable {
+            policy.enabled = true;
+        }
+        if preview.is_some() || *default_preview {
+            policy.preview = *preview;
+        }
+        let policy = *policy;
+        account.updated_at = now_ts();
+        session.db.save_account(&account).await?;
+        println!("{}: {} -> {:?}", account.email, folder, policy);
+        if *metadata_only {
+            let dropped = session.db.drop_folder_bodies(&account.id, folder).await?;
+            println!(
+                "{}: dropped {} cached body(ies) in {}",
+                account.email, dropped, folder
+            );
+        }
+    }
+    Ok(())
+}
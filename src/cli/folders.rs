@@ -0,0 +1,115 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::onboarding;
+use crate::types::now_ts;
+
+#[derive(Args, Debug)]
+pub struct FoldersArgs {
+    /// Account id/email to show or update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Re-run folder discovery (IMAP LIST) before listing.
+    #[arg(long)]
+    pub refresh: bool,
+
+    /// Add a discovered folder to the sync list (repeatable).
+    #[arg(long, value_name = "FOLDER")]
+    pub sync: Vec<String>,
+
+    /// Remove a folder from the sync list (repeatable).
+    #[arg(long, value_name = "FOLDER")]
+    pub unsync: Vec<String>,
+}
+
+/// Lists server folders (discovering them when needed) and edits the sync list.
+pub(crate) async fn run(cli: &Cli, args: &FoldersArgs) -> Result<()> {
+    let FoldersArgs {
+        account,
+        refresh,
+        sync,
+        unsync,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let engine = session.engine();
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    for account in selected {
+        let mut account = account.clone();
+        let mut discovered = session.db.list_discovered_folders(&account.id).await?;
+        if session.offline() {
+            warn!(account = %account.id, "Offline: showing folders from the last discovery");
+        } else if *refresh || discovered.is_empty() {
+            match engine.discover_folders(&mut account).await {
+                Ok(mailboxes) => discovered = mailboxes,
+                Err(e) => {
+                    warn!(account = %account.id, error = %e, "Folder discovery failed");
+                    continue;
+                }
+            }
+        }
+
+        let mut changed = false;
+        for folder in sync {
+            let folder = &account.settings.server_folder(folder);
+            let Some(mailbox) = discovered
+                .iter()
+                .find(|m| onboarding::same_folder(&m.name, folder))
+            else {
+                warn!(account = %account.id, folder = %folder, "Folder not found on the server (try --refresh)");
+                continue;
+            };
+            if !mailbox.selectable() {
+                warn!(account = %account.id, folder = %folder, "Folder cannot be selected; not syncing it");
+                continue;
+            }
+            if !account.settings.folders.contains(&mailbox.name) {
+                account.settings.folders.push(mailbox.name.clone());
+                changed = true;
+            }
+        }
+        for folder in unsync {
+            let folder = &account.settings.server_folder(folder);
+            let before = account.settings.folders.len();
+            account
+                .settings
+                .folders
+                .retain(|f| !onboarding::same_folder(f, folder));
+            changed |= account.settings.folders.len() != before;
+        }
+        if changed {
+            account.updated_at = now_ts();
+            session.db.save_account(&account).await?;
+        }
+
+        println!("{}:", account.email);
+        for mailbox in &discovered {
+            let synced = account.settings.folders.contains(&mailbox.name);
+            println!(
+                "  [{}] {}{}",
+                if synced { "x" } else { " " },
+                mailbox.name,
+                if mailbox.attributes.is_empty() {
+                    String::new()
+                } else {
+                    format!("  ({})", mailbox.attributes.join(" "))
+                }
+            );
+        }
+        for folder in &account.settings.folders {
+            if !discovered
+                .iter()
+                .any(|m| onboarding::same_folder(&m.name, folder))
+            {
+                println!("  [x] {}  (not on server)", folder);
+            }
+        }
+    }
+    Ok(())
+}
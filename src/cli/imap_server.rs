@@ -0,0 +1,249 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::imap::{check_tls_policy, load_ca_file, load_client_cert};
+use crate::timefmt::format_timestamp;
+use crate::types::{ClientCert, ImapEndpoint, TlsMode, TlsPolicy, TlsVersion, now_ts};
+
+#[derive(Args, Debug)]
+pub struct ImapServerArgs {
+    /// Account id/email to update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Server host name or address.
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// Server port (993 for tls, usually 143 for starttls).
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// Transport security; plain is only accepted for localhost.
+    #[arg(long, value_enum)]
+    pub tls: Option<TlsMode>,
+
+    /// Trust only the server certificate with this SHA-256 fingerprint (hex, colons
+    /// allowed), e.g. a bridge's self-signed certificate.
+    #[arg(long, value_name = "SHA256", conflicts_with = "no_pin")]
+    pub pin_cert: Option<String>,
+
+    /// Drop the pinned fingerprint and verify against the system CA store again.
+    #[arg(long)]
+    pub no_pin: bool,
+
+    /// Also trust the CAs in this PEM bundle (e.g. an internal company CA).
+    #[arg(long, value_name = "PEM", conflicts_with = "no_ca_file")]
+    pub ca_file: Option<PathBuf>,
+
+    /// Stop trusting the extra CA bundle.
+    #[arg(long)]
+    pub no_ca_file: bool,
+
+    /// Present this PEM certificate (chain) to servers that require mutual TLS.
+    #[arg(
+        long,
+        value_name = "PEM",
+        requires = "client_key",
+        conflicts_with = "no_client_cert"
+    )]
+    pub client_cert: Option<PathBuf>,
+
+    /// Private key of the client certificate (PEM).
+    #[arg(long, value_name = "PEM", requires = "client_cert")]
+    pub client_key: Option<PathBuf>,
+
+    /// Stop presenting a client certificate.
+    #[arg(long)]
+    pub no_client_cert: bool,
+
+    /// Lowest TLS version the account's IMAP and SMTP connections accept.
+    #[arg(long, value_enum, value_name = "VERSION")]
+    pub min_tls: Option<TlsVersion>,
+
+    /// Only offer these cipher suites, by IANA name (comma-separated, e.g.
+    /// TLS13_AES_256_GCM_SHA384,TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384).
+    #[arg(long, value_name = "SUITES", value_delimiter = ',')]
+    pub cipher_suites: Option<Vec<String>>,
+
+    /// Go back to the default TLS policy (TLS 1.2+, every suite rustls offers).
+    #[arg(long, conflicts_with_all = ["min_tls", "cipher_suites"])]
+    pub default_tls_policy: bool,
+
+    /// Cap message downloads at this many bytes per second, shared by the account's folders
+    /// (replaces the `OTTO_MAX_DOWNLOAD_BPS` value the account was added with).
+    #[arg(
+        long,
+        value_name = "BPS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "no_download_limit"
+    )]
+    pub max_download_bps: Option<u64>,
+
+    /// Download at full speed again.
+    #[arg(long)]
+    pub no_download_limit: bool,
+}
+
+/// Shows or changes the IMAP server, TLS policy and download limit of accounts; certificate and
+/// key files are checked before anything is saved.
+pub(crate) async fn run(cli: &Cli, args: &ImapServerArgs) -> Result<()> {
+    let ImapServerArgs {
+        account,
+        host,
+        port,
+        tls,
+        pin_cert,
+        no_pin,
+        ca_file,
+        no_ca_file,
+        client_cert,
+        client_key,
+        no_client_cert,
+        min_tls,
+        cipher_suites,
+        default_tls_policy,
+        max_download_bps,
+        no_download_limit,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account to update");
+    }
+    let pin = pin_cert
+        .as_deref()
+        .map(ImapEndpoint::parse_fingerprint)
+        .transpose()?;
+    // Checked (and made absolute) now rather than on the next connection.
+    let ca_file = match ca_file {
+        Some(path) => {
+            load_ca_file(path)?;
+            Some(std::path::absolute(path).context("resolving the CA bundle path")?)
+        }
+        None => None,
+    };
+    let client_cert = match (client_cert, client_key) {
+        (Some(cert), Some(key)) => {
+            let client = ClientCert {
+                cert: std::path::absolute(cert).context("resolving the client certificate path")?,
+                key: std::path::absolute(key).context("resolving the client key path")?,
+            };
+            load_client_cert(&client)?;
+            Some(client)
+        }
+        _ => None,
+    };
+    for account in selected {
+        let mut account = account.clone();
+        let mut imap = account.settings.imap.clone();
+        let mut tls_policy = account.settings.tls_policy.clone();
+        if *default_tls_policy {
+            tls_policy = TlsPolicy::default();
+        }
+        if let Some(version) = min_tls {
+            tls_policy.min_version = Some(*version);
+        }
+        if let Some(suites) = cipher_suites {
+            tls_policy.cipher_suites = suites
+                .iter()
+                .map(|suite| suite.trim().to_ascii_uppercase())
+                .filter(|suite| !suite.is_empty())
+                .collect();
+        }
+        check_tls_policy(&tls_policy)?;
+        if let Some(host) = host {
+            imap.host = host.clone();
+        }
+        if let Some(tls) = tls
+            && imap.tls != *tls
+        {
+            imap.tls = *tls;
+            imap.port = tls.default_port();
+        }
+        if let Some(port) = port {
+            imap.port = *port;
+        }
+        if pin.is_some() {
+            imap.cert_sha256 = pin.clone();
+        } else if *no_pin {
+            imap.cert_sha256 = None;
+        }
+        if ca_file.is_some() {
+            imap.ca_file = ca_file.clone();
+        } else if *no_ca_file {
+            imap.ca_file = None;
+        }
+        if client_cert.is_some() {
+            imap.client_cert = client_cert.clone();
+        } else if *no_client_cert {
+            imap.client_cert = None;
+        }
+        if imap.tls == TlsMode::Plain && !imap.is_loopback() {
+            bail!(
+                "{}: TLS mode plain is only allowed for localhost, not {}",
+                account.email,
+                imap.host
+            );
+        }
+        let mut download_limit = account.settings.max_download_bytes_per_sec;
+        if max_download_bps.is_some() {
+            download_limit = *max_download_bps;
+        } else if *no_download_limit {
+            download_limit = None;
+        }
+        if imap != account.settings.imap
+            || tls_policy != account.settings.tls_policy
+            || download_limit != account.settings.max_download_bytes_per_sec
+        {
+            account.settings.imap = imap;
+            account.settings.tls_policy = tls_policy;
+            account.settings.max_download_bytes_per_sec = download_limit;
+            account.updated_at = now_ts();
+            session.db.save_account(&account).await?;
+        }
+        let imap = &account.settings.imap;
+        println!(
+            "{}: {}:{} ({}){}{}{}{}{}",
+            account.email,
+            imap.host,
+            imap.port,
+            imap.tls.as_str(),
+            imap.cert_sha256
+                .as_deref()
+                .map(|sha| format!(", pinned certificate {}", sha))
+                .unwrap_or_default(),
+            imap.ca_file
+                .as_deref()
+                .map(|path| format!(", extra CAs from {}", path.display()))
+                .unwrap_or_default(),
+            imap.client_cert
+                .as_ref()
+                .map(|client| format!(", client certificate {}", client.cert.display()))
+                .unwrap_or_default(),
+            if account.settings.tls_policy.is_default() {
+                String::new()
+            } else {
+                format!(", {}", account.settings.tls_policy.describe())
+            },
+            account
+                .settings
+                .max_download_bytes_per_sec
+                .map(|bps| format!(", downloads capped at {} bytes/s", bps))
+                .unwrap_or_default()
+        );
+        if let Some(identity) = session.db.load_server_identity(&account.id).await? {
+            println!(
+                "  server ID: {} (as of {})",
+                identity.describe(),
+                format_timestamp(Some(identity.fetched_at), session.defaults.display_tz)
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,326 @@
+//! Command-line surface: `Cli` and its subcommands. Each subcommand's arguments and handler
+//! live in the module of the same name; `app::run` dispatches to them.
+pub mod accounts;
+pub mod all_mail;
+pub mod append;
+pub mod audit;
+pub mod backfill;
+pub mod cleanup;
+pub mod compress_bodies;
+pub mod conflicts;
+pub mod daemon;
+pub mod encrypt_columns;
+pub mod fetch_bodies;
+pub mod folder_policy;
+pub mod folders;
+pub mod imap_server;
+pub mod note;
+pub mod notify;
+pub mod ops;
+pub mod pause;
+pub mod profile;
+pub mod refetch;
+pub mod reply_later;
+pub mod resanitize;
+pub mod responses;
+pub mod send;
+pub mod share;
+pub mod signature;
+pub mod smart_folder;
+pub mod status;
+pub mod suggestions;
+pub mod sync;
+pub mod thread;
+pub mod trace;
+pub mod translate;
+pub mod verify;
+
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use clap::{ArgGroup, Parser, Subcommand};
+
+use crate::types::{Provider, TlsMode};
+
+/// Command-line options for Otto.
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Add a new account via OAuth onboarding
+    #[arg(long)]
+    pub add_account: bool,
+
+    /// Profile to run in: its own data directory, database and keyring entries (default: the
+    /// one `otto profile switch` selected, or `OTTO_PROFILE`).
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Provider signed in to by OAuth onboarding (`--add-account`, or the first run).
+    #[arg(long, value_enum, default_value = "gmail")]
+    pub provider: Provider,
+
+    /// Disable sync for this run (serve from cache only).
+    #[arg(long)]
+    pub no_sync: bool,
+
+    /// Force full sync, bypassing MODSEQ optimization.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Baseline scans fetch headers only; bodies download afterwards, newest first.
+    #[arg(long)]
+    pub headers_first: bool,
+
+    /// Force safe mode (disable mutations) even if account-level safe_mode is false.
+    #[arg(long)]
+    pub safe_mode: bool,
+
+    /// Only fetch unread mail this run (quick check on metered connections); the next full
+    /// sync catches up on everything else.
+    #[arg(long)]
+    pub unread_only: bool,
+
+    /// Launch the TUI overlay instead of printing a simple list.
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Open the TUI in triage mode: one unread message at a time, single-key decisions.
+    #[arg(long)]
+    pub triage: bool,
+
+    /// Travel mode: no network at all. Message actions queue locally and are sent once back
+    /// online (`o` in the TUI, or a run without --offline).
+    #[arg(long)]
+    pub offline: bool,
+
+    /// In the TUI, keep syncing each account every `poll_interval_minutes` instead of once.
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Mark every message in FOLDER as read on the server, then exit.
+    #[arg(long, value_name = "FOLDER", conflicts_with = "archive_folder")]
+    pub mark_folder_read: Option<String>,
+
+    /// Move every message in FOLDER to All Mail on the server, then exit.
+    #[arg(long, value_name = "FOLDER")]
+    pub archive_folder: Option<String>,
+
+    /// With --archive-folder, only archive messages received before this date (YYYY-MM-DD).
+    #[arg(long, value_name = "DATE", requires = "archive_folder")]
+    pub older_than: Option<NaiveDate>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Keep running and sync each account on its poll interval (Ctrl-C to stop).
+    Daemon,
+
+    /// Fetch mail older than the account cutoff, paging backwards until --until.
+    Backfill(backfill::BackfillArgs),
+
+    /// Download bodies skipped for exceeding the account's max message size.
+    FetchBodies(fetch_bodies::FetchBodiesArgs),
+
+    /// Re-download and re-sanitize the bodies of specific messages (e.g. after a truncated fetch).
+    Refetch(refetch::RefetchArgs),
+
+    /// Sync one folder while recording the raw IMAP conversation (credentials redacted) to a
+    /// file, for server-compatibility bug reports.
+    Trace(trace::TraceArgs),
+
+    /// Upload `.eml` files (or every `.eml` in a directory) to a server folder with APPEND,
+    /// e.g. to import mail or restore a backup. Each message keeps its Date header as the
+    /// server's arrival time; it shows up locally after the folder's next sync.
+    Append(append::AppendArgs),
+
+    /// Mail merge: send one personalized message per CSV row over SMTP.
+    Send(send::SendArgs),
+
+    /// Set per-folder overrides of the account sync settings.
+    FolderPolicy(folder_policy::FolderPolicyArgs),
+
+    /// List, add or remove smart folders: saved queries shown as folders in the TUI sidebar.
+    SmartFolder(smart_folder::SmartFolderArgs),
+
+    /// List, add or remove cleanup rules: archive, delete or label messages matching a
+    /// smart-folder query once they are older than an age. `otto daemon` runs them before its scheduled
+    /// passes (at most hourly); --run runs them now and --report shows what they cleaned.
+    Cleanup(cleanup::CleanupArgs),
+
+    /// List learned rule suggestions (a sender whose mail you archived, deleted or labeled by
+    /// hand `OTTO_LEARN_THRESHOLD` times), or accept one (it becomes a cleanup rule) or reject
+    /// it.
+    Suggestions(suggestions::SuggestionsArgs),
+
+    /// List, add or remove new-mail notification rules: which mail (a smart-folder query, by
+    /// default unread INBOX mail) goes to which channel. `otto daemon` sends them after each
+    /// pass; --test sends a sample through a rule's channel now.
+    Notify(notify::NotifyArgs),
+
+    /// List the account's server folders (from the last discovery) and choose which to sync.
+    Folders(folders::FoldersArgs),
+
+    /// Compare cached messages and flags with the server and report drift.
+    Verify(verify::VerifyArgs),
+
+    /// Print unread counts and sync freshness from the cache for status bars.
+    Status(status::StatusArgs),
+
+    /// List queued changes held back because the server changed the same message, or settle
+    /// them with --keep-local / --keep-server.
+    Conflicts(conflicts::ConflictsArgs),
+
+    /// List queued changes waiting to be sent, with failed attempts and dead-lettered ones
+    /// (rejected too often), or settle dead-lettered changes with --retry / --drop.
+    Ops(ops::OpsArgs),
+
+    /// Show the audit log of moves, deletes and expunges otto sent to the server.
+    Audit(audit::AuditArgs),
+
+    /// Sync Gmail's All Mail once (plus Trash and Spam) instead of each folder, taking INBOX,
+    /// Sent and label membership from X-GM-LABELS.
+    AllMail(all_mail::AllMailArgs),
+
+    /// Pause syncing an account (e.g. while its credentials are broken or the server rate-limits
+    /// it) without deleting it; its cache stays readable.
+    Pause(pause::PauseArgs),
+
+    /// Show or change the IMAP server an account connects to (e.g. a local Protonmail Bridge
+    /// or Davmail), the TLS policy of its connections and its download limit; with no changes,
+    /// prints the current settings.
+    ImapServer(imap_server::ImapServerArgs),
+
+    /// Encrypt subject, sender and body columns with a per-account key kept in the OS keyring.
+    EncryptColumns(encrypt_columns::EncryptColumnsArgs),
+
+    /// Show a thread's reply graph (who answered whom, and when) as an indented tree, or as
+    /// Graphviz DOT with --dot (e.g. `otto thread <ID> --dot | dot -Tsvg > thread.svg`).
+    Thread(thread::ThreadArgs),
+
+    /// Export a cached thread as one self-contained, read-only HTML page for sharing outside
+    /// email: sanitized text bodies, no scripts, no remote content, Bcc left out.
+    Share(share::ShareArgs),
+
+    /// Show which sent messages got a reply and how fast (average response time), and list
+    /// those still awaiting one. Built from thread linkage, so the Sent folder (or All Mail)
+    /// must be synced.
+    Responses(responses::ResponsesArgs),
+
+    /// List the reply-later queue, or add messages to it (`--due` sets a due date) or take them
+    /// off (`--done`).
+    ReplyLater(reply_later::ReplyLaterArgs),
+
+    /// List private notes on messages, or show, set (`TEXT`) or remove (`--clear`) the note of
+    /// one message. Notes stay local and are matched by smart-folder queries.
+    Note(note::NoteArgs),
+
+    /// Show, set (`TEXT` or `--file`) or remove (`--clear`) the signature added below mail
+    /// composed in the TUI and `otto send` messages; `--alias` sets one for a send-as address.
+    Signature(signature::SignatureArgs),
+
+    /// Translate a cached message's text body with the configured backend
+    /// (`OTTO_TRANSLATE_URL`) and print it. Translations are cached per message and language,
+    /// so repeating the command (or `T` in the TUI) works offline.
+    Translate(translate::TranslateArgs),
+
+    /// Re-run the sanitizer over stored raw messages sanitized by an older version.
+    Resanitize(resanitize::ResanitizeArgs),
+
+    /// Compress raw messages stored before compression, then vacuum the database.
+    CompressBodies(compress_bodies::CompressBodiesArgs),
+
+    /// Add accounts that log in with a password (from the keyring or a command such as pass or
+    /// op) instead of OAuth, for servers without XOAUTH2 and scripted provisioning.
+    Accounts {
+        #[command(subcommand)]
+        action: AccountsCommand,
+    },
+
+    /// List profiles or switch the one used without --profile. Each profile has its own data
+    /// directory and keyring entries, so personal and work mail never mix.
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ProfileCommand {
+    /// List the profiles with their data directories; `*` marks the active one.
+    List,
+
+    /// Use NAME from now on (created if new; `default` is the plain data directory).
+    Switch {
+        /// Profile name: letters, digits, '-' or '_'.
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AccountsCommand {
+    /// Add one account.
+    Add {
+        /// Address to log in as; also the account id.
+        #[arg(long)]
+        email: String,
+
+        /// IMAP server host name or address.
+        #[arg(long, required_unless_present = "preset")]
+        host: Option<String>,
+
+        /// Server and special folder names of a known provider (see `otto accounts presets`);
+        /// --host/--port override it.
+        #[arg(long, value_name = "NAME")]
+        preset: Option<String>,
+
+        /// Server port (default: the preset's, else 993 for tls and 143 otherwise).
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// Transport security; plain is only accepted for localhost.
+        #[arg(long, value_enum, default_value_t = TlsMode::Tls)]
+        tls: TlsMode,
+
+        /// Shell command printing the password on its first line, e.g. "pass show mail/work".
+        #[arg(long, value_name = "CMD", required_unless_present = "password_stdin")]
+        password_cmd: Option<String>,
+
+        /// Read the password from the first line of stdin and keep it in the OS keyring.
+        #[arg(long, conflicts_with = "password_cmd")]
+        password_stdin: bool,
+    },
+
+    /// Add every `[[account]]` entry of a TOML file (keys: email, host or preset, port, tls,
+    /// password_cmd); accounts that already exist are skipped. Entries without password_cmd
+    /// use a keyring password, set afterwards with `otto accounts password --stdin`.
+    Import {
+        /// Accounts file to read.
+        file: PathBuf,
+    },
+
+    /// List the provider presets: server, sign-in method and special folder names.
+    Presets,
+
+    /// Change how an existing account signs in.
+    #[command(group(ArgGroup::new("how").required(true).args(["cmd", "stdin", "oauth"])))]
+    Password {
+        /// Account id/email to update.
+        #[arg(long)]
+        account: String,
+
+        /// Use a shell command printing the password on its first line.
+        #[arg(long, value_name = "CMD")]
+        cmd: Option<String>,
+
+        /// Read the password from the first line of stdin and keep it in the OS keyring.
+        #[arg(long)]
+        stdin: bool,
+
+        /// Go back to Google OAuth, deleting any keyring password.
+        #[arg(long)]
+        oauth: bool,
+    },
+}
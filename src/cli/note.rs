@@ -0,0 +1,88 @@
+use anyhow::{Result, bail};
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::encoded_words::decode_mime_words;
+
+#[derive(Args, Debug)]
+pub struct NoteArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Message id; none lists every note.
+    pub id: Option<String>,
+
+    /// The note (replaces an existing one).
+    #[arg(requires = "id", conflicts_with = "clear")]
+    pub text: Option<String>,
+
+    /// Remove the message's note.
+    #[arg(long, requires = "id")]
+    pub clear: bool,
+}
+
+/// Lists notes, or shows, sets or removes the note of one message.
+pub(crate) async fn run(cli: &Cli, args: &NoteArgs) -> Result<()> {
+    let NoteArgs {
+        account,
+        id,
+        text,
+        clear,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    if text.as_deref().is_some_and(|t| t.trim().is_empty()) {
+        bail!("empty note; use --clear to remove one");
+    }
+    if let Some(id) = id {
+        let mut found = false;
+        for account in &selected {
+            let changed = match text {
+                Some(text) => {
+                    session
+                        .db
+                        .set_message_note(&account.id, id, text.trim())
+                        .await?
+                }
+                None if *clear => session.db.clear_message_note(&account.id, id).await?,
+                None => false,
+            };
+            let note = session
+                .db
+                .load_message_notes(&account.id)
+                .await?
+                .into_iter()
+                .find(|entry| &entry.message_id == id);
+            match (note, *clear) {
+                (_, true) if changed => println!("{}: note on {} removed", account.email, id),
+                (Some(entry), _) => println!("{}: {}: {}", account.email, id, entry.note),
+                (None, _) => continue,
+            }
+            found = true;
+        }
+        if !found {
+            bail!("no note or message {:?}", id);
+        }
+        return Ok(());
+    }
+    for account in selected {
+        let notes = session.db.load_message_notes(&account.id).await?;
+        println!("{}: {} note(s)", account.email, notes.len());
+        for entry in &notes {
+            println!(
+                "  {}  {} — {}",
+                entry.message_id,
+                entry.from.as_deref().unwrap_or("(unknown)"),
+                decode_mime_words(entry.subject.as_deref().unwrap_or("(No Subject)"))
+            );
+            println!("    {}", entry.note);
+        }
+    }
+    Ok(())
+}
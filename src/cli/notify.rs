@@ -0,0 +1,159 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::notify::{self, ChannelConfig, NoteMessage, Notification, NotifyRule};
+use crate::smart_folders::SmartQuery;
+use crate::types::now_ts;
+
+#[derive(Args, Debug)]
+pub struct NotifyArgs {
+    /// Account id/email to show or update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Rule to add, replace, remove or test (default: list them all).
+    pub name: Option<String>,
+
+    /// Only new mail matching these query terms, e.g. "from:boss@example.com".
+    #[arg(long, requires = "channel")]
+    pub query: Option<String>,
+
+    /// Pop up a desktop notification (notify-send).
+    #[arg(long, group = "channel", requires = "name")]
+    pub desktop: bool,
+
+    /// POST the notification as JSON to this URL.
+    #[arg(long, value_name = "URL", group = "channel", requires = "name")]
+    pub webhook: Option<String>,
+
+    /// Publish to this ntfy topic.
+    #[arg(long, value_name = "TOPIC", group = "channel", requires = "name")]
+    pub ntfy: Option<String>,
+
+    /// ntfy server for --ntfy (default https://ntfy.sh).
+    #[arg(long, value_name = "URL", requires = "ntfy")]
+    pub ntfy_server: Option<String>,
+
+    /// Mail a summary through the account's SMTP, to ADDR or the account itself.
+    #[arg(
+        long,
+        value_name = "ADDR",
+        group = "channel",
+        requires = "name",
+        num_args = 0..=1,
+        default_missing_value = ""
+    )]
+    pub email: Option<String>,
+
+    /// Remove the named rule.
+    #[arg(long, requires = "name", conflicts_with_all = ["channel", "test"])]
+    pub remove: bool,
+
+    /// Send a sample notification through the named rule's channel.
+    #[arg(long, requires = "name", conflicts_with = "channel")]
+    pub test: bool,
+}
+
+/// Edits or lists notification rules; `--test` sends a sample through a rule's channel.
+pub(crate) async fn run(cli: &Cli, args: &NotifyArgs) -> Result<()> {
+    let NotifyArgs {
+        account,
+        name,
+        query,
+        desktop,
+        webhook,
+        ntfy,
+        ntfy_server,
+        email,
+        remove,
+        test,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let channel = if *desktop {
+        Some(ChannelConfig::Desktop)
+    } else if let Some(url) = webhook {
+        Some(ChannelConfig::Webhook { url: url.clone() })
+    } else if let Some(topic) = ntfy {
+        Some(ChannelConfig::Ntfy {
+            topic: topic.clone(),
+            server: ntfy_server
+                .clone()
+                .unwrap_or_else(|| notify::DEFAULT_NTFY_SERVER.to_string()),
+        })
+    } else {
+        email.as_ref().map(|to| ChannelConfig::Email {
+            to: Some(to.clone()).filter(|to| !to.trim().is_empty()),
+        })
+    };
+    if let Some(query) = query {
+        SmartQuery::parse(query).with_context(|| format!("invalid query {:?}", query))?;
+    }
+    for account in selected {
+        let mut account = account.clone();
+        let rules = &mut account.settings.notify_rules;
+        let changed = match (&channel, name) {
+            (Some(channel), Some(name)) => {
+                let rule = NotifyRule {
+                    name: name.clone(),
+                    query: query.clone(),
+                    channel: channel.clone(),
+                };
+                match rules.iter_mut().find(|r| r.name == rule.name) {
+                    Some(existing) => *existing = rule,
+                    None => rules.push(rule),
+                }
+                true
+            }
+            (None, Some(name)) if *remove => {
+                let before = rules.len();
+                rules.retain(|r| &r.name != name);
+                if rules.len() == before {
+                    warn!(account = %account.id, name = %name, "No such notification rule");
+                }
+                rules.len() != before
+            }
+            _ => false,
+        };
+        if changed {
+            account.updated_at = now_ts();
+            session.db.save_account(&account).await?;
+        }
+
+        println!("{}:", account.email);
+        let shown = account
+            .settings
+            .notify_rules
+            .iter()
+            .filter(|rule| name.is_none() || Some(&rule.name) == name.as_ref());
+        for rule in shown {
+            println!(
+                "  {}  {}  {}",
+                rule.name,
+                rule.channel.describe(),
+                rule.query.as_deref().unwrap_or("(new INBOX mail)")
+            );
+            if *test {
+                let sample = NoteMessage {
+                    id: "test".to_string(),
+                    folder: "INBOX".to_string(),
+                    from: "otto".to_string(),
+                    subject: "Test notification".to_string(),
+                };
+                let note = Notification::new(&account.email, &rule.name, vec![sample]);
+                notify::channel(&rule.channel, &account, &session.defaults.smtp)
+                    .send(&note)
+                    .await
+                    .with_context(|| format!("testing {}", rule.channel.describe()))?;
+                println!("  sent a test notification");
+            }
+        }
+    }
+    Ok(())
+}
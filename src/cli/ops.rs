@@ -0,0 +1,118 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::storage::ops::PendingOp;
+use crate::timefmt::{DisplayTz, format_absolute};
+
+#[derive(Args, Debug)]
+pub struct OpsArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Only list dead-lettered changes.
+    #[arg(long)]
+    pub dead: bool,
+
+    /// Send the dead-lettered changes again on the next sync.
+    #[arg(long, conflicts_with = "drop")]
+    pub retry: bool,
+
+    /// Drop the dead-lettered changes and undo them locally.
+    #[arg(long)]
+    pub drop: bool,
+
+    /// Op ids to settle (default: every dead-lettered op of the selected accounts).
+    pub ids: Vec<i64>,
+}
+
+/// Lists queued changes, or retries or drops dead-lettered ones. Never onboards or connects.
+pub(crate) async fn run(cli: &Cli, args: &OpsArgs) -> Result<()> {
+    let OpsArgs {
+        account,
+        dead,
+        retry,
+        drop,
+        ids,
+    } = args;
+    let session = Session::open(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    for account in selected {
+        let queued = session.db.list_pending_ops(&account.id).await?;
+        if *retry || *drop {
+            let chosen: Vec<i64> = queued
+                .iter()
+                .filter(|op| op.dead_at.is_some())
+                .map(|op| op.id)
+                .filter(|id| ids.is_empty() || ids.contains(id))
+                .collect();
+            if *retry {
+                let released = session.db.retry_dead_ops(&chosen).await?;
+                println!(
+                    "{}: {} change(s) released for retry",
+                    account.email, released
+                );
+            } else {
+                session.db.rollback_pending_ops(&chosen).await?;
+                println!(
+                    "{}: {} change(s) dropped and undone locally",
+                    account.email,
+                    chosen.len()
+                );
+            }
+            continue;
+        }
+        let shown: Vec<&PendingOp> = queued
+            .iter()
+            .filter(|op| !*dead || op.dead_at.is_some())
+            .collect();
+        let dead_count = queued.iter().filter(|op| op.dead_at.is_some()).count();
+        println!(
+            "{}: {} queued, {} dead-lettered",
+            account.email,
+            queued.len() - dead_count,
+            dead_count
+        );
+        for op in shown {
+            print_pending_op(op, session.defaults.display_tz);
+        }
+    }
+    Ok(())
+}
+
+/// One queued op in `otto ops` output: id, queue time, op and message, then its failures.
+fn print_pending_op(op: &PendingOp, tz: DisplayTz) {
+    let payload: serde_json::Value = op
+        .payload
+        .as_deref()
+        .and_then(|p| serde_json::from_str(p).ok())
+        .unwrap_or_default();
+    let arg = payload
+        .get("label")
+        .or_else(|| payload.get("dest"))
+        .and_then(|v| v.as_str());
+    println!(
+        "#{}  {}  {}{}  {}{}",
+        op.id,
+        format_absolute(op.created_at, tz),
+        op.kind,
+        arg.map(|a| format!(" {}", a)).unwrap_or_default(),
+        op.target,
+        op.dead_at
+            .map(|at| format!("  DEAD since {}", format_absolute(at, tz)))
+            .unwrap_or_default()
+    );
+    if op.attempts > 0 {
+        println!(
+            "    {} rejected attempt(s): {}",
+            op.attempts,
+            op.last_error.as_deref().unwrap_or("")
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::types::now_ts;
+
+#[derive(Args, Debug)]
+pub struct PauseArgs {
+    /// Account id/email to update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Sync the account again.
+    #[arg(long)]
+    pub resume: bool,
+}
+
+/// Pauses or resumes syncing accounts.
+pub(crate) async fn run(cli: &Cli, args: &PauseArgs) -> Result<()> {
+    let PauseArgs { account, resume } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account to update");
+    }
+    for account in selected {
+        let mut account = account.clone();
+        if account.settings.enabled != *resume {
+            account.settings.enabled = *resume;
+            account.updated_at = now_ts();
+            session.db.save_account(&account).await?;
+        }
+        println!(
+            "{}: sync {}",
+            account.email,
+            if *resume { "enabled" } else { "paused" }
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::cli::ProfileCommand;
+use crate::profile;
+
+/// `otto profile list|switch`; needs neither the store nor the network.
+pub(crate) fn run(action: &ProfileCommand, base_dir: &Path) -> Result<()> {
+    match action {
+        ProfileCommand::List => {
+            let active = profile::active();
+            for name in profile::list(base_dir)? {
+                let selected = match &active {
+                    Some(active) => *active == name,
+                    None => name == profile::DEFAULT_PROFILE,
+                };
+                let dir = profile::data_dir(
+                    base_dir,
+                    Some(name.as_str()).filter(|n| *n != profile::DEFAULT_PROFILE),
+                );
+                println!(
+                    "{} {:<16} {}",
+                    if selected { "*" } else { " " },
+                    name,
+                    dir.display()
+                );
+            }
+        }
+        ProfileCommand::Switch { name } => {
+            let dir = profile::switch(base_dir, name)?;
+            println!("Switched to profile {} ({})", name, dir.display());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+
+#[derive(Args, Debug)]
+pub struct RefetchArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Message ids to refetch.
+    #[arg(required = true)]
+    pub ids: Vec<String>,
+}
+
+/// Re-downloads and re-sanitizes the bodies of the given messages.
+pub(crate) async fn run(cli: &Cli, args: &RefetchArgs) -> Result<()> {
+    let RefetchArgs { account, ids } = args;
+    let session = Session::ready(cli).await?;
+    let engine = session.engine();
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let mut stored = 0;
+    for account in selected {
+        match engine.refetch(account, ids).await {
+            Ok(n) => stored += n,
+            Err(e) => warn!(account = %account.id, error = %e, "Refetching bodies failed"),
+        }
+    }
+    println!("Refetched {} of {} message(s)", stored, ids.len());
+    Ok(())
+}
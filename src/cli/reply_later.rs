@@ -0,0 +1,71 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts, today};
+use crate::cli::Cli;
+use crate::encoded_words::decode_mime_words;
+use crate::storage::reply_later;
+
+#[derive(Args, Debug)]
+pub struct ReplyLaterArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Message ids to add (or, with --done, remove); none lists the queue.
+    pub ids: Vec<String>,
+
+    /// Due date: YYYY-MM-DD, +N days, today or tomorrow.
+    #[arg(long, value_name = "DATE", conflicts_with = "done")]
+    pub due: Option<String>,
+
+    /// Remove the messages from the queue (replied).
+    #[arg(long, requires = "ids")]
+    pub done: bool,
+}
+
+/// Adds messages to or takes them off the reply-later queue, then lists it.
+pub(crate) async fn run(cli: &Cli, args: &ReplyLaterArgs) -> Result<()> {
+    let ReplyLaterArgs {
+        account,
+        ids,
+        due,
+        done,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let due = reply_later::parse_due(
+        due.as_deref().unwrap_or(""),
+        today(session.defaults.display_tz),
+    )?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let today = today(session.defaults.display_tz);
+    for account in selected {
+        if *done {
+            session.db.clear_reply_later(&account.id, ids).await?;
+        } else if !ids.is_empty() {
+            session.db.set_reply_later(&account.id, ids, due).await?;
+        }
+
+        let queue = session.db.load_reply_later(&account.id).await?;
+        println!("{}: {} to reply to", account.email, queue.len());
+        for entry in &queue {
+            let due = match entry.due {
+                Some(due) if entry.is_overdue(today) => format!("{} overdue", due),
+                Some(due) => due.to_string(),
+                None => "-".to_string(),
+            };
+            println!(
+                "  {:<18} {}  {} — {}",
+                due,
+                entry.message_id,
+                entry.from.as_deref().unwrap_or("(unknown)"),
+                decode_mime_words(entry.subject.as_deref().unwrap_or("(No Subject)"))
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use anyhow::Result;
+use clap::Args;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::app::{Session, cancel_on_ctrl_c, select_accounts};
+use crate::cli::Cli;
+use crate::sanitize::resanitize;
+
+#[derive(Args, Debug)]
+pub struct ResanitizeArgs {
+    /// Account id/email to process (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Rebuild every stored body, not only ones from older sanitizer versions.
+    #[arg(long)]
+    pub all: bool,
+}
+
+/// Re-runs the sanitizer over stored bodies; Ctrl-C stops after the current page.
+pub(crate) async fn run(cli: &Cli, args: &ResanitizeArgs) -> Result<()> {
+    let ResanitizeArgs { account, all } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let cancel = CancellationToken::new();
+    let interrupt = tokio::spawn(cancel_on_ctrl_c(cancel.clone()));
+    for account in selected {
+        let rewritten =
+            resanitize::resanitize_account(session.db.as_ref(), &account.id, *all, &cancel).await?;
+        println!("{}: resanitized {} body(ies)", account.email, rewritten);
+    }
+    interrupt.abort();
+    if cancel.is_cancelled() {
+        println!("Interrupted; run otto resanitize again to continue.");
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::Args;
+use tracing::warn;
+
+use crate::address::friendly_from;
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::responses::{self, format_duration};
+use crate::types::now_ts;
+
+#[derive(Args, Debug)]
+pub struct ResponsesArgs {
+    /// Account id/email to show (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Only mail sent from this date on (YYYY-MM-DD; default: the last 30 days).
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<NaiveDate>,
+
+    /// Also list the answered messages with their response times.
+    #[arg(long)]
+    pub answered: bool,
+}
+
+/// Prints reply statistics of sent mail and the messages still awaiting an answer.
+pub(crate) async fn run(cli: &Cli, args: &ResponsesArgs) -> Result<()> {
+    let ResponsesArgs {
+        account,
+        since,
+        answered,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let since = match since.and_then(|d| d.and_hms_opt(0, 0, 0)) {
+        Some(dt) => dt.and_utc().timestamp(),
+        None => now_ts() - RESPONSES_DEFAULT_DAYS * 24 * 60 * 60,
+    };
+    let now = now_ts();
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    for account in selected {
+        let stats = responses::collect(session.db.as_ref(), account, since).await?;
+        println!(
+            "{}: {} sent, {} answered{}, {} awaiting reply",
+            account.email,
+            stats.sent,
+            stats.answered.len(),
+            stats
+                .average_response_secs()
+                .map(|secs| format!(" (average {})", format_duration(secs)))
+                .unwrap_or_default(),
+            stats.awaiting.len()
+        );
+        for msg in &stats.awaiting {
+            println!(
+                "  waiting {:<7} {}  to {} — {}",
+                format_duration(now - msg.internal_date.unwrap_or(now)),
+                msg.id,
+                msg.to.as_deref().unwrap_or("(unknown)"),
+                msg.subject.as_deref().unwrap_or("(no subject)")
+            );
+        }
+        if *answered {
+            for entry in &stats.answered {
+                println!(
+                    "  replied {:<7} {}  by {} — {}",
+                    format_duration(entry.response_secs()),
+                    entry.sent.id,
+                    friendly_from(
+                        entry.reply.from_name.as_deref(),
+                        entry.reply.from.as_deref()
+                    ),
+                    entry.sent.subject.as_deref().unwrap_or("(no subject)")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Window `otto responses` looks at without `--since`.
+const RESPONSES_DEFAULT_DAYS: i64 = 30;
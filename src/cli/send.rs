@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use tokio_util::sync::CancellationToken;
+
+use crate::app::{Session, cancel_on_ctrl_c, select_accounts};
+use crate::cli::Cli;
+use crate::compose::merge::{self, MergeLog, MergeTemplate};
+use crate::oauth::{authorize_with_scopes, mail_scopes};
+use crate::smtp::SmtpClient;
+use crate::types::Provider;
+
+#[derive(Args, Debug)]
+pub struct SendArgs {
+    /// Account id/email to send from (required with several accounts).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Contacts CSV with an `email` column; every column can fill a `{{column}}` placeholder.
+    #[arg(long, value_name = "CSV")]
+    pub merge: PathBuf,
+
+    /// Markdown template whose first line is `Subject: ...`.
+    #[arg(long, value_name = "FILE")]
+    pub template: PathBuf,
+
+    /// Seconds to wait between messages.
+    #[arg(long, value_name = "SECS", default_value_t = 5)]
+    pub delay: u64,
+
+    /// Progress log (default: `<CSV>.sent.log`); addresses already sent to are skipped.
+    #[arg(long, value_name = "FILE")]
+    pub log: Option<PathBuf>,
+
+    /// Render and validate every message and report, without sending.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Mail merge over Gmail SMTP, skipping rows the progress log already lists.
+pub(crate) async fn run(cli: &Cli, args: &SendArgs) -> Result<()> {
+    let SendArgs {
+        account,
+        merge,
+        template,
+        delay,
+        log,
+        dry_run,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    let [sender] = selected.as_slice() else {
+        bail!(
+            "otto send needs exactly one account; {} match (use --account)",
+            selected.len()
+        );
+    };
+    if sender.provider != Provider::GmailImap {
+        bail!(
+            "otto send only submits through Gmail SMTP; {} is not a Gmail account",
+            sender.email
+        );
+    }
+    if !sender.settings.credential.is_oauth() {
+        bail!(
+            "otto send signs in to Gmail SMTP with OAuth; {} uses a {}",
+            sender.email,
+            sender.settings.credential.describe()
+        );
+    }
+    let template = std::fs::read_to_string(template)
+        .with_context(|| format!("reading {}", template.display()))?;
+    let template = MergeTemplate::parse(&template)?;
+    let rows = merge::read_contacts(merge)?;
+    let log = MergeLog::new(log.clone().unwrap_or_else(|| MergeLog::default_path(merge)));
+    let already_sent = log.sent()?;
+    let signature = session.db.load_signature(&sender.id, None).await?;
+    let messages: Vec<_> = merge::render_all(&template, &rows, &sender.email, signature.as_ref())?
+        .into_iter()
+        .filter(|m| !already_sent.contains(&m.row.email().to_lowercase()))
+        .collect();
+    println!(
+        "{} row(s), {} already sent per {}, {} to send",
+        rows.len(),
+        rows.len() - messages.len(),
+        log.path().display(),
+        messages.len()
+    );
+    if *dry_run || messages.is_empty() {
+        for message in &messages {
+            println!(
+                "  row {}: {} | {}",
+                message.row.line,
+                message.row.email(),
+                message.draft.subject
+            );
+        }
+        return Ok(());
+    }
+
+    let scopes = mail_scopes(&sender.provider);
+    let token = authorize_with_scopes(&sender.provider, &scopes, &sender.id).await?;
+    let mut client = SmtpClient::connect(
+        &session.defaults.smtp,
+        &sender.settings.tls_policy,
+        &sender.email,
+        &token.access_token,
+    )
+    .await?;
+    let cancel = CancellationToken::new();
+    let interrupt = tokio::spawn(cancel_on_ctrl_c(cancel.clone()));
+    let result = merge::send_all(
+        &mut client,
+        &messages,
+        &log,
+        Duration::from_secs(*delay),
+        &cancel,
+    )
+    .await;
+    interrupt.abort();
+    let _ = client.quit().await;
+    let summary = result?;
+    println!(
+        "Sent {}, failed {} (log: {})",
+        summary.sent,
+        summary.failed,
+        log.path().display()
+    );
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::share::{self, ShareOptions};
+
+#[derive(Args, Debug)]
+pub struct ShareArgs {
+    /// Message id or thread id.
+    pub id: String,
+
+    /// Account id/email to search (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// File to write the page to.
+    #[arg(long, value_name = "FILE")]
+    pub out: PathBuf,
+
+    /// Embed the attachments in the page as downloads (default: list their names only).
+    #[arg(long)]
+    pub attachments: bool,
+}
+
+/// Writes a cached thread as one self-contained HTML page.
+pub(crate) async fn run(cli: &Cli, args: &ShareArgs) -> Result<()> {
+    let ShareArgs {
+        id,
+        account,
+        out,
+        attachments,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    for account in selected {
+        let messages = session.db.load_thread(&account.id, id).await?;
+        if messages.is_empty() {
+            continue;
+        }
+        let html = share::render_thread_html(
+            &messages,
+            ShareOptions {
+                attachments: *attachments,
+                tz: session.defaults.display_tz,
+            },
+        );
+        std::fs::write(out, html).with_context(|| format!("writing {}", out.display()))?;
+        println!("{}: wrote {}", account.email, out.display());
+        return Ok(());
+    }
+    bail!("no cached message or thread {}", id)
+}
@@ -0,0 +1,116 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::types::Signature;
+
+#[derive(Args, Debug)]
+pub struct SignatureArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Send-as address the signature is for (default: the account's own signature).
+    #[arg(long, value_name = "ADDR")]
+    pub alias: Option<String>,
+
+    /// The signature text (replaces an existing one); `\n` starts a new line.
+    #[arg(conflicts_with_all = ["file", "clear"])]
+    pub text: Option<String>,
+
+    /// Read the signature from this file instead.
+    #[arg(long, value_name = "FILE", conflicts_with = "clear")]
+    pub file: Option<PathBuf>,
+
+    /// On replies, put the signature above the quoted text instead of at the bottom.
+    #[arg(long, conflicts_with = "clear")]
+    pub above_quote: bool,
+
+    /// Remove the signature.
+    #[arg(long)]
+    pub clear: bool,
+}
+
+/// Shows, sets or removes the signature mail sent from the accounts ends with.
+pub(crate) async fn run(cli: &Cli, args: &SignatureArgs) -> Result<()> {
+    let SignatureArgs {
+        account,
+        alias,
+        text,
+        file,
+        above_quote,
+        clear,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let body = match (text, file) {
+        (Some(text), _) => Some(text.replace("\\n", "\n")),
+        (None, Some(file)) => Some(
+            std::fs::read_to_string(file).with_context(|| format!("reading {}", file.display()))?,
+        ),
+        (None, None) => None,
+    };
+    if body.as_deref().is_some_and(|b| b.trim().is_empty()) {
+        bail!("empty signature; use --clear to remove one");
+    }
+    if body.is_none() && *above_quote {
+        bail!("--above-quote needs the signature text or --file");
+    }
+    let target = alias.as_deref().unwrap_or("default");
+    for account in selected {
+        if let Some(body) = &body {
+            session
+                .db
+                .save_signature(&Signature {
+                    account_id: account.id.clone(),
+                    alias: alias.clone(),
+                    body: body.trim_end().to_string(),
+                    above_quote: *above_quote,
+                })
+                .await?;
+        } else if *clear {
+            let removed = session
+                .db
+                .clear_signature(&account.id, alias.as_deref())
+                .await?;
+            println!(
+                "{}: {} signature {}",
+                account.email,
+                target,
+                if removed { "removed" } else { "was not set" }
+            );
+            continue;
+        }
+        match session
+            .db
+            .load_signature(&account.id, alias.as_deref())
+            .await?
+        {
+            Some(signature) => {
+                let placement = if signature.above_quote {
+                    "above quotes"
+                } else {
+                    "at the bottom"
+                };
+                println!(
+                    "{}: {} signature ({}):",
+                    account.email,
+                    signature.alias.as_deref().unwrap_or("default"),
+                    placement
+                );
+                for line in signature.body.lines() {
+                    println!("  {}", line);
+                }
+            }
+            None => println!("{}: no signature", account.email),
+        }
+    }
+    Ok(())
+}
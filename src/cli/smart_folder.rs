@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::smart_folders::{SmartFolder, SmartQuery};
+use crate::types::now_ts;
+
+#[derive(Args, Debug)]
+pub struct SmartFolderArgs {
+    /// Account id/email to show or update (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Smart folder to add, replace or remove (default: list them).
+    pub name: Option<String>,
+
+    /// Query terms, e.g. "is:unread from:boss@example.com" or "subject:receipt date:this-month".
+    #[arg(requires = "name", conflicts_with = "remove")]
+    pub query: Option<String>,
+
+    /// Remove the named smart folder.
+    #[arg(long, requires = "name")]
+    pub remove: bool,
+}
+
+/// Adds, replaces or removes a smart folder, then lists them.
+pub(crate) async fn run(cli: &Cli, args: &SmartFolderArgs) -> Result<()> {
+    let SmartFolderArgs {
+        account,
+        name,
+        query,
+        remove,
+    } = args;
+    let session = Session::ready(cli).await?;
+    if let Some(query) = query {
+        SmartQuery::parse(query).with_context(|| format!("invalid query {:?}", query))?;
+    }
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    for account in selected {
+        let mut account = account.clone();
+        let folders = &mut account.settings.smart_folders;
+        let changed = match (name, query) {
+            (Some(name), Some(query)) => {
+                match folders.iter_mut().find(|f| &f.name == name) {
+                    Some(existing) => existing.query = query.clone(),
+                    None => folders.push(SmartFolder {
+                        name: name.clone(),
+                        query: query.clone(),
+                    }),
+                }
+                true
+            }
+            (Some(name), None) if *remove => {
+                let before = folders.len();
+                folders.retain(|f| &f.name != name);
+                if folders.len() == before {
+                    warn!(account = %account.id, name = %name, "No such smart folder");
+                }
+                folders.len() != before
+            }
+            _ => false,
+        };
+        if changed {
+            account.updated_at = now_ts();
+            session.db.save_account(&account).await?;
+        }
+
+        println!("{}:", account.email);
+        for folder in &account.settings.smart_folders {
+            if name.is_none() || Some(&folder.name) == name.as_ref() {
+                println!("  {}  {}", folder.name, folder.query);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::app::Session;
+use crate::cli::Cli;
+use crate::status::{self, StatusFormat};
+use crate::types::now_ts;
+
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    /// Output format.
+    #[arg(long, value_enum, default_value = "json")]
+    pub format: StatusFormat,
+}
+
+/// Prints unread counts and freshness from the cache. Status bars poll this, so it must never
+/// onboard or touch the network.
+pub(crate) async fn run(cli: &Cli, args: &StatusArgs) -> Result<()> {
+    let session = Session::open(cli).await?;
+    let statuses = status::collect(session.db.as_ref(), &session.accounts, now_ts()).await?;
+    print!(
+        "{}",
+        status::render(
+            args.format,
+            &statuses,
+            chrono::Utc::now(),
+            session.defaults.display_tz
+        )
+    );
+    if args.format != StatusFormat::I3blocks {
+        println!();
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use anyhow::{Result, bail};
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::learn;
+
+#[derive(Args, Debug)]
+pub struct SuggestionsArgs {
+    /// Account id/email (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Suggested rule name, as listed.
+    pub name: Option<String>,
+
+    /// Create the suggested cleanup rule.
+    #[arg(long, requires = "name", conflicts_with = "reject")]
+    pub accept: bool,
+
+    /// Decline it; it is not suggested again.
+    #[arg(long, requires = "name")]
+    pub reject: bool,
+}
+
+/// Lists learned rule suggestions, or accepts or rejects one.
+pub(crate) async fn run(cli: &Cli, args: &SuggestionsArgs) -> Result<()> {
+    let SuggestionsArgs {
+        account,
+        name,
+        accept,
+        reject,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account");
+    }
+    let mut found = false;
+    for account in selected {
+        let pending = learn::pending(session.db.as_ref(), &account.id).await?;
+        if let Some(name) = name {
+            let Some(suggestion) = pending.iter().find(|s| &s.rule_name() == name) else {
+                continue;
+            };
+            found = true;
+            if *accept {
+                learn::accept(session.db.as_ref(), &account.id, suggestion).await?;
+                println!("{}: created cleanup rule {}", account.email, name);
+            } else if *reject {
+                learn::reject(session.db.as_ref(), &account.id, suggestion).await?;
+                println!("{}: {} won't be suggested again", account.email, name);
+            } else {
+                println!("{}: {}  {}", account.email, name, suggestion.question());
+            }
+            continue;
+        }
+        println!("{}: {} suggestion(s)", account.email, pending.len());
+        for suggestion in &pending {
+            println!("  {}  {}", suggestion.rule_name(), suggestion.question());
+        }
+    }
+    if let Some(name) = name
+        && !found
+    {
+        bail!("no pending suggestion {:?}", name);
+    }
+    Ok(())
+}
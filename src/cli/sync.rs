@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::address::friendly_from;
+use crate::app::{
+    Session, StartupTimer, TuiStartup, cancel_on_ctrl_c, check_cache_freshness, flush_summary,
+    folder_op, launch_tui, load_defaults, tui_starts_lazily,
+};
+use crate::cli::Cli;
+use crate::encoded_words::decode_mime_words;
+use crate::preview;
+use crate::progress;
+use crate::timefmt::format_timestamp;
+use crate::types::BodyStatus;
+
+/// Plain `otto`: a folder operation, the TUI, or a sync followed by the latest messages.
+pub(crate) async fn run(cli: &Cli) -> Result<()> {
+    let defaults = load_defaults()?;
+    let timer = StartupTimer::new();
+    if tui_starts_lazily(cli, &defaults) {
+        let offline = cli.offline || defaults.offline;
+        let startup = TuiStartup::Lazy(Box::new(defaults.clone()));
+        return launch_tui(cli, &defaults, startup, offline, timer).await;
+    }
+    let session = Session::open_with(cli, defaults, timer)
+        .await?
+        .prepare()
+        .await?;
+
+    if let Some((folder, op)) = folder_op(cli) {
+        let engine = session.engine();
+        for account in &session.accounts {
+            if cli.safe_mode || account.settings.safe_mode {
+                warn!(account = %account.id, "Safe mode enabled; skipping folder operation");
+                continue;
+            }
+            let folder = account.settings.server_folder(&folder);
+            match engine.run_folder_op(account, &folder, &op).await {
+                Ok(n) => println!(
+                    "{}: {:?} applied to {} message(s) in {}",
+                    account.email, op, n, folder
+                ),
+                Err(e) => {
+                    warn!(account = %account.id, folder = %folder, error = %e, "Folder operation failed")
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if cli.tui || cli.triage {
+        let startup = TuiStartup::Ready(session.db.clone(), session.accounts.clone());
+        return launch_tui(
+            cli,
+            &session.defaults,
+            startup,
+            session.offline(),
+            session.timer,
+        )
+        .await;
+    }
+
+    if session.offline() {
+        info!("Offline: skipping sync and server checks");
+        for account in &session.accounts {
+            let queued = session.db.list_pending_ops(&account.id).await?.len();
+            if queued > 0 {
+                println!(
+                    "⏸ Offline: {} change(s) for {} queued until you are back online.",
+                    queued, account.email
+                );
+            }
+        }
+    } else if !cli.no_sync {
+        let engine = session.engine();
+        let interrupt = tokio::spawn(cancel_on_ctrl_c(engine.cancel_token()));
+        let progress = progress::spawn(engine.subscribe(), &session.accounts);
+        let mut queued_before = HashMap::new();
+        for account in &session.accounts {
+            queued_before.insert(
+                account.id.clone(),
+                session.db.list_pending_ops(&account.id).await?.len(),
+            );
+        }
+        let report = engine
+            .sync_all(&session.accounts, session.sync_options())
+            .await;
+        interrupt.abort();
+        // Dropping the engine closes the progress stream so the bars can finish.
+        drop(engine);
+        let _ = progress.await;
+        if !report.is_ok() {
+            println!("⚠ Sync finished with problems ({}):", report.summary());
+            for problem in report.problems() {
+                println!("  {}", problem);
+            }
+        }
+        for account in &session.accounts {
+            let before = queued_before.get(&account.id).copied().unwrap_or(0);
+            if before > 0 {
+                let after = session.db.list_pending_ops(&account.id).await?.len();
+                println!("{}: {}", account.email, flush_summary(before, after));
+            }
+        }
+    } else {
+        info!("Skipping sync; using cached data only");
+        let stale = check_cache_freshness(session.db.clone(), &session.accounts).await;
+        if stale.is_empty() {
+            println!("Cached folders match the server.");
+        } else {
+            println!("⚠ Cached data may be out of date:");
+            for line in &stale {
+                println!("  {}", line);
+            }
+            println!("  Run without --no-sync to refresh.");
+        }
+    }
+
+    // Display latest 10 emails
+    println!("\n{}", "=".repeat(80));
+    println!("📬 Latest 10 Emails");
+    println!("{}\n", "=".repeat(80));
+
+    for account in &session.accounts {
+        let messages = session.db.load_messages(&account.id, 10).await?;
+        let summaries = session.db.load_message_summaries(&account.id).await?;
+
+        if messages.is_empty() {
+            println!("No messages found for {}\n", account.email);
+            continue;
+        }
+
+        for (i, (msg, body)) in messages.iter().enumerate() {
+            let date = format_timestamp(msg.internal_date, session.defaults.display_tz);
+
+            let from = friendly_from(msg.from_name.as_deref(), msg.from.as_deref());
+            let subject = msg.subject.as_deref().unwrap_or("(No Subject)");
+
+            // Decode MIME-encoded subjects for display
+            let subject = decode_mime_words(subject);
+
+            let is_read = msg.flags.iter().any(|f| f.eq("Seen") || f.eq("\\Seen"));
+            let status = if is_read { "R" } else { "U" };
+
+            println!("{}. [{}] [{}] {}", i + 1, date, status, subject);
+            println!("   From: {}", from);
+            println!("   Folder: {}", msg.folder);
+
+            if let Some(body_record) = body
+                && let Some(text) = &body_record.sanitized_text
+            {
+                let preview = preview::preview_text(
+                    preview::source_for(Some(&account.settings), &msg.folder),
+                    text,
+                    summaries.get(&msg.id).map(String::as_str),
+                    2,
+                );
+
+                let preview = if preview.chars().count() > 100 {
+                    let truncated: String = preview.chars().take(100).collect();
+                    format!("{}...", truncated)
+                } else {
+                    preview
+                };
+
+                if !preview.is_empty() {
+                    println!("   Preview: {}", preview);
+                }
+            } else if msg.body_status == BodyStatus::TooLarge {
+                println!(
+                    "   Body not fetched (too large); `otto fetch-bodies {}`",
+                    msg.id
+                );
+            }
+
+            println!();
+        }
+    }
+
+    println!("{}", "=".repeat(80));
+
+    Ok(())
+}
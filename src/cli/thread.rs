@@ -0,0 +1,51 @@
+use anyhow::{Result, bail};
+use clap::Args;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::thread_graph::{ThreadGraph, ThreadMessage};
+
+#[derive(Args, Debug)]
+pub struct ThreadArgs {
+    /// Message id or thread id.
+    pub id: String,
+
+    /// Account id/email to search (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Print Graphviz DOT instead of a tree.
+    #[arg(long)]
+    pub dot: bool,
+}
+
+/// Prints a cached thread's reply graph as a tree or Graphviz DOT.
+pub(crate) async fn run(cli: &Cli, args: &ThreadArgs) -> Result<()> {
+    let ThreadArgs { id, account, dot } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    let mut found = false;
+    for account in selected {
+        let messages = session.db.load_thread(&account.id, id).await?;
+        if messages.is_empty() {
+            continue;
+        }
+        found = true;
+        let graph = ThreadGraph::new(
+            messages
+                .iter()
+                .map(|(message, body)| ThreadMessage::from_record(message, body.as_ref())),
+        );
+        if *dot {
+            print!("{}", graph.to_dot(session.defaults.display_tz));
+        } else {
+            println!("{}: {} message(s)", account.email, graph.len());
+            print!("{}", graph.to_tree(session.defaults.display_tz));
+        }
+        break;
+    }
+    if !found {
+        bail!("no cached message or thread {}", id);
+    }
+    Ok(())
+}
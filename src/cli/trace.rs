@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::imap::ProtocolTrace;
+
+#[derive(Args, Debug)]
+pub struct TraceArgs {
+    /// Folder to sync.
+    pub folder: String,
+
+    /// Account id/email (required with several accounts).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Trace file to write (default: otto-imap-trace.log in the current directory).
+    #[arg(long, value_name = "FILE", default_value = "otto-imap-trace.log")]
+    pub out: PathBuf,
+}
+
+/// Syncs one folder while writing the IMAP conversation to a file.
+pub(crate) async fn run(cli: &Cli, args: &TraceArgs) -> Result<()> {
+    let TraceArgs {
+        folder,
+        account,
+        out,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    let [account] = selected.as_slice() else {
+        bail!(
+            "otto trace needs exactly one account; {} match (use --account)",
+            selected.len()
+        );
+    };
+    let folder = &account.settings.server_folder(folder);
+    let engine = session.engine();
+    let trace = Arc::new(ProtocolTrace::create(out)?);
+    let result = engine
+        .sync_folder_traced(account, folder, session.sync_options(), trace)
+        .await;
+    println!("IMAP trace written to {}", out.display());
+    result.with_context(|| format!("traced sync of {}", folder))
+}
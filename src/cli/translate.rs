@@ -0,0 +1,73 @@
+use anyhow::{Result, bail};
+use clap::Args;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::translate::{self, Translator};
+
+#[derive(Args, Debug)]
+pub struct TranslateArgs {
+    /// Message id.
+    pub id: String,
+
+    /// Account id/email to search (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Target language, e.g. de, fr, pt-BR (default: OTTO_TRANSLATE_TO or the locale).
+    #[arg(long, value_name = "LANG")]
+    pub to: Option<String>,
+
+    /// Translate again even when a cached translation exists.
+    #[arg(long)]
+    pub refresh: bool,
+}
+
+/// Translates a cached message (or reuses its cached translation) and prints it.
+pub(crate) async fn run(cli: &Cli, args: &TranslateArgs) -> Result<()> {
+    let TranslateArgs {
+        id,
+        account,
+        to,
+        refresh,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let config = session.defaults.translate.as_ref();
+    let target = match (to, config) {
+        (Some(to), _) => translate::parse_language(to)?,
+        (None, Some(config)) => config.target.clone(),
+        (None, None) => bail!("no translation backend configured (set OTTO_TRANSLATE_URL)"),
+    };
+    let translator = config.cloned().map(Translator::new);
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    for account in selected {
+        if session.db.load_thread(&account.id, id).await?.is_empty() {
+            continue;
+        }
+        let translation = translate::translate_message(
+            session.db.as_ref(),
+            translator.as_ref(),
+            &account.id,
+            id,
+            &target,
+            *refresh,
+            session.offline(),
+        )
+        .await?;
+        println!(
+            "{}: {} translated to {}{}",
+            account.email,
+            id,
+            translation.target,
+            translation
+                .source_lang
+                .as_deref()
+                .map(|lang| format!(" from {}", lang))
+                .unwrap_or_default()
+        );
+        println!();
+        println!("{}", translation.text);
+        return Ok(());
+    }
+    bail!("no cached message {}", id)
+}
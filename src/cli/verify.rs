@@ -0,0 +1,123 @@
+use anyhow::Result;
+use clap::Args;
+use tracing::warn;
+
+use crate::app::{Session, select_accounts};
+use crate::cli::Cli;
+use crate::imap::build_uid_sequence;
+use crate::sync::{FolderDrift, VerifyOptions};
+
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    /// Account id/email to verify (default: every account).
+    #[arg(long)]
+    pub account: Option<String>,
+
+    /// Only verify this folder (default: every enabled folder).
+    #[arg(long)]
+    pub folder: Option<String>,
+
+    /// Check at most N UIDs per folder, spread evenly over the folder.
+    #[arg(long, value_name = "N")]
+    pub sample: Option<usize>,
+
+    /// Also download up to N cached messages per folder and compare their content hash
+    /// with the server copy (catches truncated or corrupted bodies).
+    #[arg(long, value_name = "N")]
+    pub hash_sample: Option<usize>,
+
+    /// Fix the cache: fetch missing messages, drop extra ones, take the server's flags and
+    /// re-download bodies whose hash differs.
+    #[arg(long)]
+    pub repair: bool,
+}
+
+/// Compares the cache with the server and prints the drift per folder.
+pub(crate) async fn run(cli: &Cli, args: &VerifyArgs) -> Result<()> {
+    let VerifyArgs {
+        account,
+        folder,
+        sample,
+        hash_sample,
+        repair,
+    } = args;
+    let session = Session::ready(cli).await?;
+    let engine = session.engine();
+    let selected = select_accounts(&session.accounts, account.as_deref());
+    if selected.is_empty() {
+        warn!(account = ?account, "No matching account to verify");
+    }
+    let options = VerifyOptions {
+        sample: *sample,
+        hash_sample: *hash_sample,
+        repair: *repair,
+    };
+    for account in selected {
+        let folder = folder.as_deref().map(|f| account.settings.server_folder(f));
+        let reports = match engine.verify(account, folder.as_deref(), options).await {
+            Ok(reports) => reports,
+            Err(e) => {
+                warn!(account = %account.id, error = %e, "Verify failed");
+                continue;
+            }
+        };
+        for drift in reports {
+            print_drift(&account.email, &drift);
+        }
+    }
+    Ok(())
+}
+
+fn print_drift(email: &str, drift: &FolderDrift) {
+    if drift.uidvalidity_changed {
+        println!(
+            "{} {}: UIDVALIDITY changed; the next sync rebuilds this folder",
+            email, drift.folder
+        );
+        return;
+    }
+    if drift.is_clean() {
+        println!(
+            "{} {}: {} checked{}, no drift",
+            email,
+            drift.folder,
+            drift.checked,
+            if drift.hashed > 0 {
+                format!(" ({} hashed)", drift.hashed)
+            } else {
+                String::new()
+            }
+        );
+        return;
+    }
+    let hashed = if drift.hashed > 0 {
+        format!(
+            ", {} of {} hash(es) differ",
+            drift.corrupt.len(),
+            drift.hashed
+        )
+    } else {
+        String::new()
+    };
+    println!(
+        "{} {}: {} checked, {} missing, {} extra, {} flag mismatch(es){}{}",
+        email,
+        drift.folder,
+        drift.checked,
+        drift.missing.len(),
+        drift.extra.len(),
+        drift.mismatched.len(),
+        hashed,
+        if drift.repaired { " (repaired)" } else { "" }
+    );
+    for (kind, uids) in [
+        ("missing", &drift.missing),
+        ("extra", &drift.extra),
+        ("flags", &drift.mismatched),
+        ("content", &drift.corrupt),
+    ] {
+        if !uids.is_empty() {
+            println!("  {}: {}", kind, build_uid_sequence(uids));
+        }
+    }
+}
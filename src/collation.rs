@@ -0,0 +1,143 @@
+//! Ordering for the sender and subject sorts of the TUI list. Names and subjects are compared
+//! with ICU collation for the configured locale (`OTTO_COLLATION`, default from
+//! `LC_ALL`/`LC_COLLATE`/`LANG`), so "Émile" sorts next to "Emile" rather than after "Zoe", and
+//! Swedish "Ö" after "Z" only where Swedish order is asked for. Punctuation is ignored and digit
+//! runs compare as numbers ("Build 9" before "Build 10").
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use anyhow::{Result, anyhow};
+use icu_collator::{AlternateHandling, Collator, CollatorOptions, Numeric};
+use icu_locid::Locale;
+use icu_provider::DataLocale;
+use tracing::warn;
+
+/// How list sorts compare text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Collation {
+    /// ICU collation for a BCP 47 locale (`de`, `sv`, `de-u-co-phonebk`); `None` is the CLDR
+    /// root order, a sensible default for mixed-language mail.
+    Locale(Option<String>),
+    /// Case-insensitive code point order, as the `C`/`POSIX` locale sorts.
+    Codepoint,
+}
+
+impl Default for Collation {
+    fn default() -> Self {
+        Self::Locale(None)
+    }
+}
+
+impl Collation {
+    /// `root`, `codepoint` (or `c`/`posix`), or a BCP 47 locale tag; POSIX-style names such as
+    /// `de_DE.UTF-8` are accepted too.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let raw = raw.trim();
+        let name = raw
+            .split(['.', '@'])
+            .next()
+            .unwrap_or_default()
+            .replace('_', "-");
+        match name.to_ascii_lowercase().as_str() {
+            "" | "root" | "und" => Ok(Self::Locale(None)),
+            "codepoint" | "c" | "posix" => Ok(Self::Codepoint),
+            _ => {
+                let locale = Locale::from_str(&name)
+                    .map_err(|e| anyhow!("invalid collation locale {:?}: {}", raw, e))?;
+                Ok(Self::Locale(Some(locale.to_string())))
+            }
+        }
+    }
+
+    /// The collation the environment asks for: `OTTO_COLLATION`, else the first of
+    /// `LC_ALL`, `LC_COLLATE` and `LANG` that is set, else the root order.
+    pub fn from_env(get: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(raw) = get("OTTO_COLLATION") {
+            return Self::parse(&raw).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring OTTO_COLLATION; using the root collation");
+                Self::default()
+            });
+        }
+        ["LC_ALL", "LC_COLLATE", "LANG"]
+            .into_iter()
+            .filter_map(|var| get(var).filter(|v| !v.trim().is_empty()))
+            .next()
+            .and_then(|raw| Self::parse(&raw).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Process-wide, set from `AppDefaults` at startup and on settings reloads.
+static COLLATION: RwLock<Collation> = RwLock::new(Collation::Locale(None));
+
+pub fn set_collation(collation: Collation) {
+    *COLLATION.write().unwrap_or_else(|e| e.into_inner()) = collation;
+}
+
+pub fn collation() -> Collation {
+    COLLATION.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// A comparator for one sort; build it once per sort, not per comparison.
+pub struct Sorter {
+    collator: Option<Collator>,
+}
+
+impl Sorter {
+    pub fn new(collation: &Collation) -> Self {
+        let Collation::Locale(tag) = collation else {
+            return Self { collator: None };
+        };
+        let locale = tag
+            .as_deref()
+            .and_then(|tag| Locale::from_str(tag).ok())
+            .unwrap_or_default();
+        let mut options = CollatorOptions::new();
+        options.alternate_handling = Some(AlternateHandling::Shifted);
+        options.numeric = Some(Numeric::On);
+        let collator = Collator::try_new(&DataLocale::from(&locale), options)
+            .map_err(
+                |e| warn!(locale = %locale, error = %e, "No collation data; sorting by code point"),
+            )
+            .ok();
+        Self { collator }
+    }
+
+    /// The sorter for the process-wide collation.
+    pub fn current() -> Self {
+        Self::new(&collation())
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match &self.collator {
+            Some(collator) => collator.compare(a, b),
+            None => a
+                .chars()
+                .flat_map(char::to_lowercase)
+                .cmp(b.chars().flat_map(char::to_lowercase))
+                .then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// Reply and forward prefixes (in the languages mail clients commonly write them) left out
+/// of subject sorts, so a thread's messages sort together.
+const REPLY_PREFIXES: &[&str] = &[
+    "re", "fw", "fwd", "aw", "wg", "sv", "vs", "antw", "rif", "r", "tr", "enc", "res",
+];
+
+/// `subject` without leading reply/forward prefixes (`Re:`, `Fwd:`, `AW:`, `Re[2]:`, ...).
+pub fn subject_sort_key(subject: &str) -> &str {
+    let mut rest = subject.trim_start();
+    loop {
+        let Some((head, tail)) = rest.split_once(':') else {
+            return rest;
+        };
+        let word = head.split_once('[').map_or(head, |(word, _)| word).trim();
+        if !REPLY_PREFIXES.iter().any(|p| p.eq_ignore_ascii_case(word)) {
+            return rest;
+        }
+        rest = tail.trim_start();
+    }
+}
@@ -0,0 +1,164 @@
+//! Files attached to a draft: reading them (with a size cap, since the whole message is built
+//! in memory), guessing their MIME type from the extension, and completing typed paths for the
+//! compose view's attach prompt.
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+/// Largest message Gmail accepts; a single file over it can never be sent, so it is refused
+/// before being read.
+pub const MAX_MESSAGE_BYTES: u64 = 25 * 1024 * 1024;
+
+/// One attached file, held in memory until the message is built.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl Attachment {
+    /// Reads `path` (`~/` is the home directory). Directories and files over
+    /// `MAX_MESSAGE_BYTES` are refused.
+    pub fn from_path(path: &str) -> Result<Self> {
+        let resolved = expand_home(path.trim());
+        let meta = std::fs::metadata(&resolved)
+            .with_context(|| format!("reading {}", resolved.display()))?;
+        if meta.is_dir() {
+            bail!("{} is a directory", resolved.display());
+        }
+        if meta.len() > MAX_MESSAGE_BYTES {
+            bail!(
+                "{} is {}; messages are limited to {}",
+                resolved.display(),
+                human_size(meta.len()),
+                human_size(MAX_MESSAGE_BYTES)
+            );
+        }
+        let data =
+            std::fs::read(&resolved).with_context(|| format!("reading {}", resolved.display()))?;
+        let filename = resolved
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "attachment".to_string());
+        Ok(Self {
+            content_type: content_type_for(&filename).to_string(),
+            filename,
+            data,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.data.len() as u64
+    }
+}
+
+/// MIME type by file extension; anything unknown is `application/octet-stream`.
+pub fn content_type_for(filename: &str) -> &'static str {
+    let ext = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "ics" => "text/calendar",
+        "eml" => "message/rfc822",
+        "json" => "application/json",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "doc" => "application/msword",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xls" => "application/vnd.ms-excel",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        _ => "application/octet-stream",
+    }
+}
+
+/// What Tab does to a typed path: `input` extended as far as every candidate agrees, and the
+/// candidates themselves (directories end in `/`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathCompletion {
+    pub input: String,
+    pub candidates: Vec<String>,
+}
+
+/// Completes the last component of `input` against its directory's entries (the current
+/// directory for a bare name). Hidden entries only show once the component starts with `.`.
+pub fn complete_path(input: &str) -> PathCompletion {
+    let (dir_part, prefix) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let dir = if dir_part.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_home(dir_part)
+    };
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return PathCompletion {
+            input: input.to_string(),
+            candidates: Vec::new(),
+        };
+    };
+    let mut candidates: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!(
+                "{}{}{}",
+                dir_part,
+                name,
+                if is_dir { "/" } else { "" }
+            ))
+        })
+        .collect();
+    candidates.sort();
+    let input = match candidates.as_slice() {
+        [] => input.to_string(),
+        [only] => only.clone(),
+        [first, rest @ ..] => rest.iter().fold(first.clone(), |common, c| {
+            let len = common
+                .char_indices()
+                .zip(c.chars())
+                .take_while(|((_, a), b)| a == b)
+                .last()
+                .map_or(0, |((i, a), _)| i + a.len_utf8());
+            common[..len].to_string()
+        }),
+    };
+    PathCompletion { input, candidates }
+}
+
+/// `~` or `~/...` under `$HOME`; other paths as typed.
+fn expand_home(path: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Binary units, as file managers show file sizes.
+pub fn human_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
@@ -0,0 +1,284 @@
+//! Mail merge for `otto send --merge`: one personalized message per CSV row, rendered from a
+//! markdown template. Every row is rendered and validated before the first send, sends are
+//! spaced by a delay, and a progress log next to the CSV lets an interrupted run resume
+//! without mailing anyone twice.
+//!
+//! The template starts with a `Subject:` line and a blank line, then the markdown body.
+//! `{{column}}` placeholders (case-insensitive CSV header names) work in both; the CSV needs an
+//! `email` column, which is also the recipient.
+use std::collections::{BTreeMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::{Draft, apply_signature, build_message};
+use crate::address::parse_mailbox;
+use crate::smtp::{SmtpClient, SmtpRejected};
+use crate::types::{Signature, now_ts};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeTemplate {
+    pub subject: String,
+    pub body: String,
+}
+
+impl MergeTemplate {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let raw = raw.replace("\r\n", "\n");
+        let (first, body) = raw.split_once('\n').unwrap_or((&raw, ""));
+        let subject = first
+            .strip_prefix("Subject:")
+            .ok_or_else(|| anyhow!("template must start with a \"Subject:\" line"))?
+            .trim()
+            .to_string();
+        Ok(Self {
+            subject,
+            body: body.strip_prefix('\n').unwrap_or(body).to_string(),
+        })
+    }
+
+    /// Subject and body with every placeholder filled from `fields`.
+    pub fn render(&self, fields: &BTreeMap<String, String>) -> Result<(String, String)> {
+        Ok((fill(&self.subject, fields)?, fill(&self.body, fields)?))
+    }
+}
+
+fn fill(text: &str, fields: &BTreeMap<String, String>) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| anyhow!("unclosed {{{{ in template"))?;
+        let key = after[..end].trim().to_lowercase();
+        let value = fields
+            .get(&key)
+            .ok_or_else(|| anyhow!("no CSV column {:?} for placeholder", key))?;
+        out.push_str(value);
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// One CSV data row; `line` is its 1-based position among the data rows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeRow {
+    pub line: usize,
+    pub fields: BTreeMap<String, String>,
+}
+
+impl MergeRow {
+    pub fn email(&self) -> &str {
+        self.fields.get("email").map(String::as_str).unwrap_or("")
+    }
+}
+
+/// Reads contacts; header names are trimmed and lowercased, and an `email` column is required.
+pub fn read_contacts(path: &Path) -> Result<Vec<MergeRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    let headers: Vec<String> = reader
+        .headers()
+        .context("reading CSV header")?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    if !headers.iter().any(|h| h == "email") {
+        bail!("{} has no \"email\" column", path.display());
+    }
+    reader
+        .records()
+        .enumerate()
+        .map(|(i, record)| {
+            let record = record.with_context(|| format!("reading CSV row {}", i + 1))?;
+            Ok(MergeRow {
+                line: i + 1,
+                fields: headers
+                    .iter()
+                    .cloned()
+                    .zip(record.iter().map(str::to_string))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// One rendered message ready to send.
+#[derive(Clone, Debug)]
+pub struct MergeMessage {
+    pub row: MergeRow,
+    pub draft: Draft,
+    pub raw: Vec<u8>,
+}
+
+/// Renders and builds every row, with `signature` below each body; fails listing each bad row,
+/// before anything is sent.
+pub fn render_all(
+    template: &MergeTemplate,
+    rows: &[MergeRow],
+    from: &str,
+    signature: Option<&Signature>,
+) -> Result<Vec<MergeMessage>> {
+    let mut messages = Vec::new();
+    let mut problems = Vec::new();
+    for row in rows {
+        let built = template.render(&row.fields).and_then(|(subject, body)| {
+            let draft = Draft {
+                from: from.to_string(),
+                to: vec![row.email().to_string()],
+                cc: Vec::new(),
+                subject,
+                body_markdown: match signature {
+                    Some(signature) => apply_signature(&body, None, signature),
+                    None => body,
+                },
+                attachments: Vec::new(),
+            };
+            let raw = build_message(&draft)?;
+            Ok((draft, raw))
+        });
+        match built {
+            Ok((draft, raw)) => messages.push(MergeMessage {
+                row: row.clone(),
+                draft,
+                raw,
+            }),
+            Err(e) => problems.push(format!("row {}: {:#}", row.line, e)),
+        }
+    }
+    if !problems.is_empty() {
+        bail!("{}", problems.join("\n"));
+    }
+    Ok(messages)
+}
+
+/// Append-only progress log (`<csv>.sent.log` by default): one tab-separated line per attempt,
+/// `<unix time>\t<sent|failed>\t<email>\t<detail>`.
+pub struct MergeLog {
+    path: PathBuf,
+}
+
+impl MergeLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn default_path(contacts: &Path) -> PathBuf {
+        let mut name = contacts.as_os_str().to_owned();
+        name.push(".sent.log");
+        PathBuf::from(name)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Lowercased addresses already sent to; a missing log means none.
+    pub fn sent(&self) -> Result<HashSet<String>> {
+        let raw = match std::fs::read_to_string(&self.path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashSet::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading {}", self.path.display()));
+            }
+        };
+        Ok(raw
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split('\t');
+                let _ts = parts.next()?;
+                (parts.next()? == "sent").then(|| parts.next().map(str::to_lowercase))?
+            })
+            .collect())
+    }
+
+    pub fn record(&self, email: &str, sent: bool, detail: &str) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("opening {}", self.path.display()))?;
+        let detail = detail.replace(['\t', '\r', '\n'], " ");
+        writeln!(
+            file,
+            "{}\t{}\t{}\t{}",
+            now_ts(),
+            if sent { "sent" } else { "failed" },
+            email,
+            detail
+        )
+        .with_context(|| format!("writing {}", self.path.display()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    pub sent: usize,
+    pub failed: usize,
+}
+
+/// Sends `messages` in order, waiting `delay` between sends, and logs each outcome. A
+/// permanent (5xx) rejection is logged as failed and the run moves on; any other error is
+/// logged and ends the run, which a rerun resumes. Cancelling stops before the next send.
+pub async fn send_all<S: AsyncRead + AsyncWrite + Unpin>(
+    client: &mut SmtpClient<S>,
+    messages: &[MergeMessage],
+    log: &MergeLog,
+    delay: Duration,
+    cancel: &CancellationToken,
+) -> Result<MergeSummary> {
+    let mut summary = MergeSummary::default();
+    for (i, message) in messages.iter().enumerate() {
+        if i > 0 {
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = cancel.cancelled() => break,
+            }
+        }
+        if cancel.is_cancelled() {
+            break;
+        }
+        let from = envelope_address(&message.draft.from);
+        let to = envelope_address(message.row.email());
+        match client.send(&from, &[to], &message.raw).await {
+            Ok(()) => {
+                log.record(message.row.email(), true, &message.draft.subject)?;
+                summary.sent += 1;
+                info!(row = message.row.line, to = %message.row.email(), "Sent merge message");
+            }
+            Err(e) => {
+                log.record(message.row.email(), false, &format!("{:#}", e))?;
+                summary.failed += 1;
+                let permanent = e
+                    .downcast_ref::<SmtpRejected>()
+                    .is_some_and(SmtpRejected::is_permanent);
+                if !permanent {
+                    return Err(e.context(format!(
+                        "sending row {} to {}",
+                        message.row.line,
+                        message.row.email()
+                    )));
+                }
+                warn!(row = message.row.line, to = %message.row.email(), error = %e, "Merge message rejected");
+                client.reset().await?;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+/// The bare address SMTP envelopes need (`Name <addr>` → `addr`).
+fn envelope_address(raw: &str) -> String {
+    parse_mailbox(raw).map_or_else(|| raw.trim().to_string(), |m| m.addr)
+}
@@ -0,0 +1,485 @@
+//! Outgoing message construction: markdown bodies rendered into multipart/alternative RFC822,
+//! with attached files as multipart/mixed parts.
+//! Addresses are validated before anything is built, and the headers are the ones DKIM signers
+//! and receiving servers expect (Date, Message-ID, MIME-Version), with CRLF line endings and
+//! long header lines folded.
+pub mod attachments;
+pub mod merge;
+
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use pulldown_cmark::{Options, Parser, html};
+use thiserror::Error;
+
+use self::attachments::Attachment;
+use crate::address::{Mailbox, parse_mailbox};
+use crate::types::{Signature, now_ts};
+
+/// Header lines are folded before this length (RFC 5322 recommends 78).
+const MAX_LINE: usize = 78;
+/// Longest RFC 2047 encoded word we emit, wrapper included: under RFC 2047's 75 and short
+/// enough to follow `Subject: ` within `MAX_LINE`.
+const MAX_ENCODED_WORD: usize = MAX_LINE - "Subject: ".len();
+/// Base64 body line length (RFC 2045).
+const BASE64_LINE: usize = 76;
+
+/// Why a draft cannot be turned into a message.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ComposeError {
+    #[error("the message has no recipients")]
+    NoRecipients,
+    #[error("invalid {field} address {address:?}")]
+    InvalidAddress {
+        field: &'static str,
+        address: String,
+    },
+    #[error("{field} contains a line break")]
+    LineBreak { field: &'static str },
+}
+
+/// A message authored in Otto. `body_markdown` is sent as the text/plain alternative verbatim
+/// and rendered to HTML for the text/html alternative; `attachments` follow as their own parts.
+#[derive(Clone, Debug)]
+pub struct Draft {
+    pub from: String,
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub subject: String,
+    pub body_markdown: String,
+    pub attachments: Vec<Attachment>,
+}
+
+/// Both renderings of a markdown body.
+#[derive(Clone, Debug)]
+pub struct RenderedBody {
+    pub text: String,
+    pub html: String,
+}
+
+pub fn render_markdown(markdown: &str) -> RenderedBody {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut body_html = String::new();
+    html::push_html(&mut body_html, Parser::new_ext(markdown, options));
+
+    RenderedBody {
+        text: markdown.to_string(),
+        html: format!(
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"></head><body>\n{}</body></html>\n",
+            body_html
+        ),
+    }
+}
+
+/// Standard signature delimiter line ("dash dash space").
+pub const SIGNATURE_DELIMITER: &str = "-- ";
+
+/// Joins a body, optional reply quote, and signature. The signature goes after the `-- `
+/// delimiter at the bottom, or between body and quote when `above_quote` is set.
+pub fn apply_signature(body: &str, quoted: Option<&str>, signature: &Signature) -> String {
+    let body = body.trim_end();
+    let signature_block = format!(
+        "{}\n{}",
+        SIGNATURE_DELIMITER,
+        signature.body.trim_end_matches(['\r', '\n'])
+    );
+
+    // Blank lines around the delimiter keep markdown from reading "-- " as a setext underline.
+    match quoted {
+        Some(quote) if signature.above_quote => {
+            format!("{}\n\n{}\n\n{}\n", body, signature_block, quote.trim_end())
+        }
+        Some(quote) => format!("{}\n\n{}\n\n{}\n", body, quote.trim_end(), signature_block),
+        None => format!("{}\n\n{}\n", body, signature_block),
+    }
+}
+
+/// Builds the full RFC822 bytes for a draft as multipart/alternative (text + generated HTML),
+/// wrapped in multipart/mixed with base64 parts when files are attached, after checking that
+/// every address parses and the subject is a single line.
+pub fn build_message(draft: &Draft) -> Result<Vec<u8>, ComposeError> {
+    let from = parse_address("From", &draft.from)?;
+    let to = parse_addresses("To", &draft.to)?;
+    let cc = parse_addresses("Cc", &draft.cc)?;
+    if to.is_empty() && cc.is_empty() {
+        return Err(ComposeError::NoRecipients);
+    }
+    if draft.subject.contains(['\r', '\n']) {
+        return Err(ComposeError::LineBreak { field: "Subject" });
+    }
+    let (message, _) = assemble(draft, &from, &to, &cc, true);
+    Ok(message.into_bytes())
+}
+
+/// Bytes `build_message` would produce, without base64-encoding the attachments; shown before
+/// sending, so addresses that don't parse yet count as typed instead of failing.
+pub fn message_size(draft: &Draft) -> u64 {
+    let lenient = |raw: &String| {
+        parse_mailbox(raw).unwrap_or_else(|| Mailbox {
+            name: None,
+            addr: raw.clone(),
+        })
+    };
+    let from = lenient(&draft.from);
+    let to: Vec<Mailbox> = draft.to.iter().map(lenient).collect();
+    let cc: Vec<Mailbox> = draft.cc.iter().map(lenient).collect();
+    let (message, encoded_attachments) = assemble(draft, &from, &to, &cc, false);
+    message.len() as u64 + encoded_attachments
+}
+
+/// The message text. Without `encode`, attachment bodies are left out and their encoded size
+/// is returned instead.
+fn assemble(
+    draft: &Draft,
+    from: &Mailbox,
+    to: &[Mailbox],
+    cc: &[Mailbox],
+    encode: bool,
+) -> (String, u64) {
+    let rendered = render_markdown(&draft.body_markdown);
+    let boundary = make_boundary(&draft.subject, &draft.body_markdown);
+
+    let mut out = String::new();
+    push_header(&mut out, "Date", &chrono::Local::now().to_rfc2822());
+    push_header(&mut out, "From", &format_mailbox(from));
+    if !to.is_empty() {
+        push_address_header(&mut out, "To", to);
+    }
+    if !cc.is_empty() {
+        push_address_header(&mut out, "Cc", cc);
+    }
+    push_header(&mut out, "Subject", &encode_header_value(&draft.subject));
+    push_header(&mut out, "Message-ID", &make_message_id(&from.addr));
+    push_header(&mut out, "MIME-Version", "1.0");
+    push_header(
+        &mut out,
+        "User-Agent",
+        concat!("otto/", env!("CARGO_PKG_VERSION")),
+    );
+
+    let mut skipped = 0;
+    let mixed_boundary = format!("{}-mixed", boundary);
+    if !draft.attachments.is_empty() {
+        push_header(
+            &mut out,
+            "Content-Type",
+            &format!("multipart/mixed; boundary=\"{}\"", mixed_boundary),
+        );
+        out.push_str("\r\n");
+        out.push_str(&format!("--{}\r\n", mixed_boundary));
+    }
+    push_header(
+        &mut out,
+        "Content-Type",
+        &format!("multipart/alternative; boundary=\"{}\"", boundary),
+    );
+    out.push_str("\r\n");
+
+    push_text_part(&mut out, &boundary, "text/plain", &rendered.text);
+    push_text_part(&mut out, &boundary, "text/html", &rendered.html);
+    out.push_str(&format!("--{}--\r\n", boundary));
+
+    if !draft.attachments.is_empty() {
+        for attachment in &draft.attachments {
+            skipped += push_attachment_part(&mut out, &mixed_boundary, attachment, encode);
+        }
+        out.push_str(&format!("--{}--\r\n", mixed_boundary));
+    }
+    (out, skipped)
+}
+
+/// Splits a typed recipient list on the commas between addresses (not those inside a quoted
+/// display name), dropping empty entries.
+pub fn split_recipients(raw: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in raw.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                out.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    out.push(current);
+    out.into_iter()
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+/// Byte offset where the last recipient of a typed list starts (after the last comma outside a
+/// quoted name).
+fn last_recipient_start(raw: &str) -> usize {
+    let mut quoted = false;
+    let mut start = 0;
+    for (i, c) in raw.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => start = i + 1,
+            _ => {}
+        }
+    }
+    start
+}
+
+/// The recipient still being typed: whatever follows the last separating comma, trimmed.
+pub fn current_recipient(raw: &str) -> &str {
+    raw[last_recipient_start(raw)..].trim()
+}
+
+/// Replaces the recipient being typed with `recipient` and starts the next one.
+pub fn complete_recipient(raw: &str, recipient: &str) -> String {
+    let head = raw[..last_recipient_start(raw)].trim_end();
+    if head.is_empty() {
+        format!("{}, ", recipient)
+    } else {
+        format!("{} {}, ", head, recipient)
+    }
+}
+
+fn parse_addresses(field: &'static str, raw: &[String]) -> Result<Vec<Mailbox>, ComposeError> {
+    raw.iter().map(|a| parse_address(field, a)).collect()
+}
+
+/// One `addr` or `Name <addr>`; the address must be a plain `local@domain`.
+fn parse_address(field: &'static str, raw: &str) -> Result<Mailbox, ComposeError> {
+    let invalid = || ComposeError::InvalidAddress {
+        field,
+        address: raw.to_string(),
+    };
+    if raw.contains(['\r', '\n']) {
+        return Err(invalid());
+    }
+    let mailbox = parse_mailbox(raw).ok_or_else(invalid)?;
+    if !is_valid_addr_spec(&mailbox.addr) {
+        return Err(invalid());
+    }
+    Ok(mailbox)
+}
+
+/// `local@domain` with a dot-atom local part and a DNS name of at least two labels.
+pub fn is_valid_addr_spec(addr: &str) -> bool {
+    let Some((local, domain)) = addr.rsplit_once('@') else {
+        return false;
+    };
+    let atom_char = |c: char| c.is_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c);
+    let local_ok = !local.is_empty()
+        && local.len() <= 64
+        && local
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(atom_char));
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = domain.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+        });
+    local_ok && domain_ok
+}
+
+fn format_mailbox(mailbox: &Mailbox) -> String {
+    match &mailbox.name {
+        Some(name) if !name.is_ascii() => {
+            format!("{} <{}>", encode_header_value(name), mailbox.addr)
+        }
+        Some(name) if name.contains(|c: char| "()<>[]:;@\\,.\"".contains(c)) => format!(
+            "\"{}\" <{}>",
+            name.replace('\\', "\\\\").replace('"', "\\\""),
+            mailbox.addr
+        ),
+        Some(name) => format!("{} <{}>", name, mailbox.addr),
+        None => mailbox.addr.clone(),
+    }
+}
+
+/// Address list header, folded between addresses once a line would pass `MAX_LINE`.
+fn push_address_header(out: &mut String, name: &str, mailboxes: &[Mailbox]) {
+    let mut line_len = name.len() + 2;
+    out.push_str(name);
+    out.push_str(": ");
+    for (i, mailbox) in mailboxes.iter().enumerate() {
+        let formatted = format_mailbox(mailbox);
+        if i > 0 {
+            out.push(',');
+            line_len += 1;
+            if line_len + 1 + formatted.len() > MAX_LINE {
+                out.push_str("\r\n");
+                line_len = 0;
+            }
+            out.push(' ');
+            line_len += 1;
+        }
+        line_len += formatted.len();
+        out.push_str(&formatted);
+    }
+    out.push_str("\r\n");
+}
+
+/// `<time.random@domain>`, with the sender's domain as the right-hand side.
+fn make_message_id(from_addr: &str) -> String {
+    let domain = from_addr.rsplit_once('@').map_or("localhost", |(_, d)| d);
+    let mut random = [0u8; 12];
+    OsRng.fill_bytes(&mut random);
+    let random: String = random.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("<{:x}.{}@{}>", now_ts(), random, domain)
+}
+
+fn push_header(out: &mut String, name: &str, value: &str) {
+    out.push_str(name);
+    out.push_str(": ");
+    out.push_str(value);
+    out.push_str("\r\n");
+}
+
+fn push_text_part(out: &mut String, boundary: &str, mimetype: &str, content: &str) {
+    out.push_str(&format!("--{}\r\n", boundary));
+    push_header(out, "Content-Type", &format!("{}; charset=utf-8", mimetype));
+    push_header(out, "Content-Transfer-Encoding", "quoted-printable");
+    out.push_str("\r\n");
+    out.push_str(&quoted_printable::encode_to_str(to_crlf(content)));
+    out.push_str("\r\n");
+}
+
+/// Appends a base64 attachment part; without `encode` the body is left out and its length
+/// returned.
+fn push_attachment_part(
+    out: &mut String,
+    boundary: &str,
+    attachment: &Attachment,
+    encode: bool,
+) -> u64 {
+    use base64::Engine;
+
+    out.push_str(&format!("--{}\r\n", boundary));
+    push_header(
+        out,
+        "Content-Type",
+        &format!(
+            "{};\r\n {}",
+            attachment.content_type,
+            filename_param("name", &attachment.filename)
+        ),
+    );
+    push_header(
+        out,
+        "Content-Disposition",
+        &format!(
+            "attachment;\r\n {}",
+            filename_param("filename", &attachment.filename)
+        ),
+    );
+    push_header(out, "Content-Transfer-Encoding", "base64");
+    out.push_str("\r\n");
+    // Base64 lines of 76 characters, each ending in CRLF.
+    let encoded_len = attachment.data.len().div_ceil(3) * 4;
+    let body_len = encoded_len + encoded_len.div_ceil(BASE64_LINE) * 2;
+    if !encode {
+        return body_len as u64;
+    }
+    let encoded = base64::engine::general_purpose::STANDARD.encode(&attachment.data);
+    for start in (0..encoded.len()).step_by(BASE64_LINE) {
+        out.push_str(&encoded[start..(start + BASE64_LINE).min(encoded.len())]);
+        out.push_str("\r\n");
+    }
+    0
+}
+
+/// `name="file.pdf"`, or the RFC 2231 `name*=utf-8''...` form for names that cannot be quoted
+/// as ASCII.
+fn filename_param(param: &str, filename: &str) -> String {
+    if filename
+        .chars()
+        .all(|c| c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\')
+    {
+        return format!("{}=\"{}\"", param, filename);
+    }
+    let encoded: String = filename
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect();
+    format!("{}*=utf-8''{}", param, encoded)
+}
+
+fn to_crlf(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\n', "\r\n")
+}
+
+/// RFC 2047 encodes non-ASCII header values as UTF-8 base64 words of at most
+/// `MAX_ENCODED_WORD` characters (split on character boundaries), one per folded line. Long
+/// ASCII values fold at spaces.
+fn encode_header_value(value: &str) -> String {
+    if value.is_ascii() {
+        return fold_ascii(value);
+    }
+    use base64::Engine;
+    // Base64 of `max_bytes` input fills what the wrapper leaves of an encoded word.
+    let max_bytes = (MAX_ENCODED_WORD - "=?UTF-8?B??=".len()) / 4 * 3;
+    let mut words = Vec::new();
+    let mut chunk = String::new();
+    for ch in value.chars() {
+        if chunk.len() + ch.len_utf8() > max_bytes {
+            words.push(std::mem::take(&mut chunk));
+        }
+        chunk.push(ch);
+    }
+    words.push(chunk);
+    words
+        .iter()
+        .map(|word| {
+            format!(
+                "=?UTF-8?B?{}?=",
+                base64::engine::general_purpose::STANDARD.encode(word.as_bytes())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n ")
+}
+
+/// Folds an unstructured ASCII value at spaces so lines stay near `MAX_LINE` (the first line
+/// leaves room for a `Subject: ` prefix).
+fn fold_ascii(value: &str) -> String {
+    let mut out = String::new();
+    let mut line_len = "Subject: ".len();
+    for (i, word) in value.split(' ').enumerate() {
+        if i > 0 {
+            if line_len + 1 + word.len() > MAX_LINE && line_len > 0 {
+                out.push_str("\r\n");
+                line_len = 0;
+            }
+            out.push(' ');
+            line_len += 1;
+        }
+        out.push_str(word);
+        line_len += word.len();
+    }
+    out
+}
+
+fn make_boundary(subject: &str, body: &str) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    subject.hash(&mut hasher);
+    body.hash(&mut hasher);
+    now_ts().hash(&mut hasher);
+    format!("otto-alt-{:016x}", hasher.finish())
+}
@@ -1,6 +1,21 @@
 use anyhow::Result;
 use chrono::NaiveDate;
 use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::collation::Collation;
+use crate::imap::ImapTimeouts;
+use crate::learn::LearnConfig;
+use crate::sanitize;
+use crate::smtp::SmtpEndpoint;
+use crate::storage::BodyStorage;
+use crate::storage::ops::FlagConflictPolicy;
+use crate::sync::DEFAULT_MAX_IDLE_PER_ACCOUNT;
+use crate::timefmt::DisplayTz;
+use crate::translate::TranslateConfig;
+use crate::types::{ClientCert, FolderRole, ImapEndpoint, ListSort, PreviewSource, TlsMode};
 
 /// Application-wide defaults. These can be overridden by env vars but do not
 /// require any user-authored config files.
@@ -10,7 +25,74 @@ pub struct AppDefaults {
     pub poll_interval_minutes: u32,
     pub prefetch_recent: u32,
     pub safe_mode: bool,
+    /// Newly onboarded accounts sync unread mail only (`OTTO_UNREAD_ONLY`).
+    pub unread_only: bool,
+    /// Newly onboarded accounts sync Gmail's All Mail once instead of each folder
+    /// (`OTTO_ALL_MAIL`).
+    pub all_mail_mode: bool,
+    /// Newly onboarded accounts store Trash and Spam as metadata only
+    /// (`OTTO_METADATA_ONLY_TRASH_SPAM`, default on).
+    pub metadata_only_trash_spam: bool,
+    /// Start in offline (travel) mode, as with `--offline` (`OTTO_OFFLINE`).
+    pub offline: bool,
     pub folders: Vec<String>,
+    /// Upper bound on folders synced in parallel (one IMAP connection each).
+    pub max_concurrent_folders: usize,
+    /// Idle IMAP sessions kept per account between passes (`OTTO_MAX_POOLED_CONNECTIONS`,
+    /// default 4; 0 logs out after every folder).
+    pub max_pooled_connections: usize,
+    /// Approximate cap on fetched message bytes held in memory across folders
+    /// (`OTTO_MEMORY_BUDGET_MB`, default unlimited).
+    pub memory_budget_bytes: Option<u64>,
+    /// Limits on IMAP connect, SELECT, SEARCH and silent reads (`OTTO_IMAP_*_TIMEOUT_SECS`,
+    /// `OTTO_IMAP_FETCH_IDLE_SECS`).
+    pub imap_timeouts: ImapTimeouts,
+    /// html2text time per HTML part before a body is tag-stripped and marked degraded
+    /// (`OTTO_HTML_RENDER_BUDGET_MS`, default 2000; 0 = no limit).
+    pub html_render_budget: Option<Duration>,
+    /// Size at which the shared IMAP trace log rotates, when `OTTO_IMAP_TRACE` is on
+    /// (`OTTO_IMAP_TRACE_MAX_MB`, default 10); `None` means no tracing.
+    pub imap_trace_max_bytes: Option<u64>,
+    /// Default per-account download cap (bytes/sec) for newly onboarded accounts.
+    pub max_download_bytes_per_sec: Option<u64>,
+    /// Default size above which newly onboarded accounts skip message bodies
+    /// (`OTTO_MAX_MESSAGE_KB`).
+    pub max_message_bytes: Option<u64>,
+    /// Timezone used for message dates in the CLI/TUI (`OTTO_TIMEZONE`, default local).
+    pub display_tz: DisplayTz,
+    /// Storage backend URL (`OTTO_DATABASE_URL`); unset means the local SQLite file.
+    pub database_url: Option<String>,
+    /// How sync settles server flag changes against queued local flag ops
+    /// (`OTTO_FLAG_CONFLICT_POLICY`, default flag).
+    pub flag_conflicts: FlagConflictPolicy,
+    /// Layout for newly stored raw bodies (`OTTO_BODY_STORAGE`, default inline; hybrid
+    /// offloads blobs from `OTTO_BLOB_OFFLOAD_KB` up to files).
+    pub body_storage: BodyStorage,
+    /// IMAP server for newly onboarded accounts (`OTTO_IMAP_HOST`, `OTTO_IMAP_PORT`,
+    /// `OTTO_IMAP_TLS`, `OTTO_IMAP_CERT_SHA256`; default Gmail over TLS).
+    pub imap: ImapEndpoint,
+    /// Submission server for `otto send` (`OTTO_SMTP_HOST`, `OTTO_SMTP_PORT`; default
+    /// smtp.gmail.com:465, implicit TLS).
+    pub smtp: SmtpEndpoint,
+    /// Sender colors and label badges in the TUI list (`OTTO_TUI_BADGES`, default on).
+    pub tui_badges: bool,
+    /// Fold repeated automated messages into one row in the TUI list
+    /// (`OTTO_TUI_COLLAPSE_REPEATS`, default off).
+    pub tui_collapse_repeats: bool,
+    /// Where list previews come from unless a folder policy says otherwise
+    /// (`OTTO_PREVIEW_SOURCE`: clean, summary or raw; default clean).
+    pub preview_source: PreviewSource,
+    /// Initial order of the TUI list (`OTTO_TUI_SORT`: date, sender or subject; default date).
+    pub tui_sort: ListSort,
+    /// How sender and subject sorts compare text (`OTTO_COLLATION`: a locale such as `de` or
+    /// `sv`, `root` or `codepoint`; default from `LC_ALL`/`LC_COLLATE`/`LANG`).
+    pub collation: Collation,
+    /// Translation backend for `otto translate` and `T` in the TUI (`OTTO_TRANSLATE_*`; unset
+    /// `OTTO_TRANSLATE_URL` means none).
+    pub translate: Option<TranslateConfig>,
+    /// When repeated TUI actions on a sender's mail suggest a rule (`OTTO_LEARN_THRESHOLD`,
+    /// default 5, 0 = off; `OTTO_LEARN_AUTO` creates them without asking).
+    pub learn: LearnConfig,
 }
 
 impl AppDefaults {
@@ -29,12 +111,147 @@ impl AppDefaults {
             .ok()
             .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
             .unwrap_or(false);
+        let unread_only = env::var("OTTO_UNREAD_ONLY")
+            .ok()
+            .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let all_mail_mode = env::var("OTTO_ALL_MAIL")
+            .ok()
+            .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let metadata_only_trash_spam = env::var("OTTO_METADATA_ONLY_TRASH_SPAM")
+            .ok()
+            .map(|s| !(s == "0" || s.eq_ignore_ascii_case("false")))
+            .unwrap_or(true);
+        let tui_badges = env::var("OTTO_TUI_BADGES")
+            .ok()
+            .map(|s| !(s == "0" || s.eq_ignore_ascii_case("false")))
+            .unwrap_or(true);
+        let tui_collapse_repeats = env::var("OTTO_TUI_COLLAPSE_REPEATS")
+            .ok()
+            .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let preview_source = match env::var("OTTO_PREVIEW_SOURCE") {
+            Ok(raw) => PreviewSource::parse(&raw).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring OTTO_PREVIEW_SOURCE; using clean");
+                PreviewSource::Clean
+            }),
+            Err(_) => PreviewSource::Clean,
+        };
+        let tui_sort = match env::var("OTTO_TUI_SORT") {
+            Ok(raw) => ListSort::parse(&raw).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring OTTO_TUI_SORT; sorting by date");
+                ListSort::Date
+            }),
+            Err(_) => ListSort::Date,
+        };
+        let collation = Collation::from_env(|var| env::var(var).ok());
+        let translate = TranslateConfig::from_env(|var| env::var(var).ok()).unwrap_or_else(|e| {
+            warn!(error = %e, "Ignoring the OTTO_TRANSLATE_* settings; translation is off");
+            None
+        });
+        let learn = LearnConfig::from_env(|var| env::var(var).ok()).unwrap_or_else(|e| {
+            warn!(error = %e, "Ignoring OTTO_LEARN_THRESHOLD; using the default");
+            LearnConfig::default()
+        });
+        let offline = env::var("OTTO_OFFLINE")
+            .ok()
+            .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+
+        let imap_trace_max_bytes = env::var("OTTO_IMAP_TRACE")
+            .ok()
+            .filter(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+            .map(|_| {
+                env::var("OTTO_IMAP_TRACE_MAX_MB")
+                    .ok()
+                    .and_then(|s| s.parse::<u64>().ok())
+                    .filter(|mb| *mb > 0)
+                    .unwrap_or(10)
+                    * 1024
+                    * 1024
+            });
+
+        let max_pooled_connections = env::var("OTTO_MAX_POOLED_CONNECTIONS")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_MAX_IDLE_PER_ACCOUNT);
+        let memory_budget_bytes = env::var("OTTO_MEMORY_BUDGET_MB")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|mb| *mb > 0)
+            .map(|mb| mb * 1024 * 1024);
+        let html_render_budget = match env::var("OTTO_HTML_RENDER_BUDGET_MS")
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+        {
+            Some(0) => None,
+            Some(ms) => Some(Duration::from_millis(ms)),
+            None => Some(sanitize::DEFAULT_RENDER_BUDGET),
+        };
+        let max_concurrent_folders = env::var("OTTO_MAX_CONCURRENT_FOLDERS")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(4);
+
+        let max_download_bytes_per_sec = env::var("OTTO_MAX_DOWNLOAD_BPS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|n| *n > 0);
+
+        let max_message_bytes = env::var("OTTO_MAX_MESSAGE_KB")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .filter(|n| *n > 0)
+            .map(|kb| kb * 1024);
+
+        let display_tz = match env::var("OTTO_TIMEZONE") {
+            Ok(raw) => DisplayTz::parse(&raw).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring OTTO_TIMEZONE; using local time");
+                DisplayTz::Local
+            }),
+            Err(_) => DisplayTz::Local,
+        };
+
+        let database_url = env::var("OTTO_DATABASE_URL")
+            .ok()
+            .filter(|s| !s.trim().is_empty());
+
+        let flag_conflicts = match env::var("OTTO_FLAG_CONFLICT_POLICY") {
+            Ok(raw) => FlagConflictPolicy::parse(&raw).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring OTTO_FLAG_CONFLICT_POLICY; using flag");
+                FlagConflictPolicy::Flag
+            }),
+            Err(_) => FlagConflictPolicy::Flag,
+        };
+
+        let body_storage = match env::var("OTTO_BODY_STORAGE") {
+            Ok(raw) => BodyStorage::parse(&raw).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring OTTO_BODY_STORAGE; storing bodies inline");
+                BodyStorage::Inline
+            }),
+            Err(_) => BodyStorage::Inline,
+        };
+        let body_storage = match body_storage {
+            BodyStorage::Hybrid { .. } => BodyStorage::Hybrid {
+                min_file_bytes: env::var("OTTO_BLOB_OFFLOAD_KB")
+                    .ok()
+                    .and_then(|s| s.parse::<u64>().ok())
+                    .unwrap_or(BodyStorage::DEFAULT_OFFLOAD_KB)
+                    * 1024,
+            },
+            other => other,
+        };
 
         let folders = vec![
             env::var("OTTO_FOLDER_INBOX").unwrap_or_else(|_| "INBOX".to_string()),
-            env::var("OTTO_FOLDER_SENT").unwrap_or_else(|_| "[Gmail]/Sent Mail".to_string()),
-            env::var("OTTO_FOLDER_TRASH").unwrap_or_else(|_| "[Gmail]/Trash".to_string()),
-            env::var("OTTO_FOLDER_SPAM").unwrap_or_else(|_| "[Gmail]/Spam".to_string()),
+            env::var("OTTO_FOLDER_SENT")
+                .unwrap_or_else(|_| FolderRole::Sent.gmail_default().to_string()),
+            env::var("OTTO_FOLDER_TRASH")
+                .unwrap_or_else(|_| FolderRole::Trash.gmail_default().to_string()),
+            env::var("OTTO_FOLDER_SPAM")
+                .unwrap_or_else(|_| FolderRole::Junk.gmail_default().to_string()),
         ];
 
         Ok(Self {
@@ -42,11 +259,128 @@ impl AppDefaults {
             poll_interval_minutes,
             prefetch_recent,
             safe_mode,
+            unread_only,
+            all_mail_mode,
+            metadata_only_trash_spam,
+            offline,
             folders,
+            max_concurrent_folders,
+            max_pooled_connections,
+            memory_budget_bytes,
+            imap_timeouts: imap_timeouts_from_env(),
+            html_render_budget,
+            imap_trace_max_bytes,
+            max_download_bytes_per_sec,
+            max_message_bytes,
+            display_tz,
+            database_url,
+            flag_conflicts,
+            body_storage,
+            imap: imap_from_env(),
+            smtp: smtp_from_env(),
+            tui_badges,
+            tui_collapse_repeats,
+            preview_source,
+            tui_sort,
+            collation,
+            translate,
+            learn,
         })
     }
 }
 
+fn imap_from_env() -> ImapEndpoint {
+    let mut imap = ImapEndpoint::default();
+    if let Ok(raw) = env::var("OTTO_IMAP_TLS") {
+        match TlsMode::parse(&raw) {
+            Ok(tls) => {
+                imap.tls = tls;
+                imap.port = tls.default_port();
+            }
+            Err(e) => warn!(error = %e, "Ignoring OTTO_IMAP_TLS; using tls"),
+        }
+    }
+    if let Some(host) = env::var("OTTO_IMAP_HOST")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+    {
+        imap.host = host.trim().to_string();
+    }
+    if let Some(port) = env::var("OTTO_IMAP_PORT")
+        .ok()
+        .and_then(|s| s.parse::<u16>().ok())
+        .filter(|n| *n > 0)
+    {
+        imap.port = port;
+    }
+    if let Ok(raw) = env::var("OTTO_IMAP_CERT_SHA256") {
+        match ImapEndpoint::parse_fingerprint(&raw) {
+            Ok(hex) => imap.cert_sha256 = Some(hex),
+            Err(e) => warn!(error = %e, "Ignoring OTTO_IMAP_CERT_SHA256"),
+        }
+    }
+    if let Some(path) = env::var("OTTO_IMAP_CA_FILE")
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+    {
+        imap.ca_file = Some(PathBuf::from(path.trim()));
+    }
+    let client_var = |name| {
+        env::var(name)
+            .ok()
+            .filter(|s: &String| !s.trim().is_empty())
+    };
+    match (
+        client_var("OTTO_IMAP_CLIENT_CERT"),
+        client_var("OTTO_IMAP_CLIENT_KEY"),
+    ) {
+        (Some(cert), Some(key)) => {
+            imap.client_cert = Some(ClientCert {
+                cert: PathBuf::from(cert.trim()),
+                key: PathBuf::from(key.trim()),
+            })
+        }
+        (None, None) => {}
+        _ => warn!("Ignoring OTTO_IMAP_CLIENT_CERT/OTTO_IMAP_CLIENT_KEY: both must be set"),
+    }
+    imap
+}
+
+fn imap_timeouts_from_env() -> ImapTimeouts {
+    let secs = |name, default: Duration| {
+        env::var(name)
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .filter(|n| *n > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(default)
+    };
+    let defaults = ImapTimeouts::DEFAULT;
+    ImapTimeouts {
+        connect: secs("OTTO_IMAP_CONNECT_TIMEOUT_SECS", defaults.connect),
+        select: secs("OTTO_IMAP_SELECT_TIMEOUT_SECS", defaults.select),
+        search: secs("OTTO_IMAP_SEARCH_TIMEOUT_SECS", defaults.search),
+        fetch_idle: secs("OTTO_IMAP_FETCH_IDLE_SECS", defaults.fetch_idle),
+    }
+}
+
+fn smtp_from_env() -> SmtpEndpoint {
+    let mut endpoint = SmtpEndpoint::default();
+    if let Some(host) = env::var("OTTO_SMTP_HOST")
+        .ok()
+        .filter(|h| !h.trim().is_empty())
+    {
+        endpoint.host = host.trim().to_string();
+    }
+    if let Some(port) = env::var("OTTO_SMTP_PORT")
+        .ok()
+        .and_then(|s| s.parse::<u16>().ok())
+    {
+        endpoint.port = port;
+    }
+    endpoint
+}
+
 fn cutoff_from_env() -> Option<NaiveDate> {
     let raw = env::var("OTTO_CUTOFF_SINCE").ok()?;
     NaiveDate::parse_from_str(&raw, "%Y-%m-%d").ok()
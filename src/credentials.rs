@@ -0,0 +1,130 @@
+//! The secret an account authenticates IMAP with, by its `Credential`: an OAuth access token
+//! from the account's provider (XOAUTH2), or for servers without XOAUTH2 a password, kept in the OS keyring or
+//! printed by a command (`pass show mail/work`, `op read op://Mail/work/password`), which
+//! `ImapClient::connect` sends with `AUTHENTICATE PLAIN` or `LOGIN`.
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+
+use crate::oauth::{authorize_with_scopes, mail_scopes};
+use crate::profile;
+use crate::types::{Account, Credential};
+
+const KEYRING_SERVICE: &str = "otto-imap-password";
+
+/// How long a password command may run. Long enough to answer a pinentry prompt, short enough
+/// that a command stuck on a locked store can't hang a daemon pass for good.
+pub const PASSWORD_CMD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The account's IMAP secret. Keyring passwords and password commands are read on every call,
+/// so a rotated password is picked up by the next sync without touching the account.
+pub async fn imap_secret(account: &Account) -> Result<String> {
+    match &account.settings.credential {
+        Credential::OAuth => {
+            let scopes = mail_scopes(&account.provider);
+            Ok(
+                authorize_with_scopes(&account.provider, &scopes, &account.id)
+                    .await?
+                    .access_token,
+            )
+        }
+        Credential::Password => {
+            let id = account.id.clone();
+            tokio::task::spawn_blocking(move || load_password(&id))
+                .await
+                .context("keyring task")?
+        }
+        Credential::PasswordCommand { command } => run_password_cmd(command).await,
+    }
+}
+
+/// Runs `cmd` through `sh -c` and returns the first line of its output, the convention of
+/// `pass` and friends (later lines hold metadata). Gives up after `PASSWORD_CMD_TIMEOUT`.
+pub async fn run_password_cmd(cmd: &str) -> Result<String> {
+    run_password_cmd_within(cmd, PASSWORD_CMD_TIMEOUT).await
+}
+
+/// `run_password_cmd` with its own time limit. A command still running at `limit` is killed,
+/// and the error carries what it printed to stderr so far (often the reason it is stuck).
+pub async fn run_password_cmd_within(cmd: &str, limit: Duration) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("running password command `{}`", cmd))?;
+    let mut stdout_pipe = child.stdout.take().context("password command stdout")?;
+    let mut stderr_pipe = child.stderr.take().context("password command stderr")?;
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let run = async {
+        let (status, _, _) = tokio::try_join!(
+            child.wait(),
+            stdout_pipe.read_to_end(&mut stdout),
+            stderr_pipe.read_to_end(&mut stderr)
+        )?;
+        Ok::<_, std::io::Error>(status)
+    };
+    let status = match tokio::time::timeout(limit, run).await {
+        Ok(status) => status.with_context(|| format!("running password command `{}`", cmd))?,
+        Err(_) => {
+            let _ = child.kill().await;
+            bail!(
+                "password command `{}` timed out after {}s: {}",
+                cmd,
+                limit.as_secs_f32(),
+                String::from_utf8_lossy(&stderr).trim()
+            );
+        }
+    };
+    if !status.success() {
+        bail!(
+            "password command `{}` failed ({}): {}",
+            cmd,
+            status,
+            String::from_utf8_lossy(&stderr).trim()
+        );
+    }
+    let stdout = String::from_utf8(stdout).context("password command printed non-UTF-8")?;
+    let password = stdout.lines().next().unwrap_or_default();
+    if password.is_empty() {
+        bail!(
+            "password command `{}` printed nothing: {}",
+            cmd,
+            String::from_utf8_lossy(&stderr).trim()
+        );
+    }
+    Ok(password.to_string())
+}
+
+/// Stores the account's IMAP password in the keyring. Like the column keys, there is no file
+/// fallback: a password on disk would defeat the point of keeping it out of the config.
+pub fn store_password(account_id: &str, password: &str) -> Result<()> {
+    keyring::Entry::new(&profile::keyring_service(KEYRING_SERVICE), account_id)
+        .context("opening password keyring entry")?
+        .set_password(password)
+        .with_context(|| format!("storing IMAP password for {}", account_id))
+}
+
+pub fn load_password(account_id: &str) -> Result<String> {
+    keyring::Entry::new(&profile::keyring_service(KEYRING_SERVICE), account_id)
+        .context("opening password keyring entry")?
+        .get_password()
+        .with_context(|| format!("reading IMAP password for {} from the keyring", account_id))
+}
+
+/// Removes a stored password; missing entries are fine.
+pub fn delete_password(account_id: &str) -> Result<()> {
+    let entry = keyring::Entry::new(&profile::keyring_service(KEYRING_SERVICE), account_id)
+        .context("opening password keyring entry")?;
+    match entry.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("deleting IMAP password for {}", account_id)),
+    }
+}
@@ -0,0 +1,170 @@
+//! `otto daemon` and the TUI's `--watch`: keep syncing each account on its own
+//! `poll_interval_minutes`. The daemon refreshes OAuth tokens itself before every pass and never
+//! opens a browser; an account whose refresh token stopped working is skipped until it is
+//! re-authorized interactively. Accounts with cleanup rules get them run (`cleanup::run_rules`)
+//! before their pass at most once per `CLEANUP_INTERVAL_SECS`, so the pass sends the result.
+//! After a pass, accounts with notification rules are notified about mail cached within
+//! `notify::RETRY_WINDOW_SECS` that no earlier pass announced (`notify::dispatch`).
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::app::{cancel_on_ctrl_c, register_ciphers};
+use crate::cleanup::{self, CLEANUP_INTERVAL_SECS};
+use crate::config::AppDefaults;
+use crate::notify;
+use crate::oauth::refresh_stored;
+use crate::storage::MailStore;
+use crate::sync::{self, SyncEngine, SyncOptions};
+use crate::types::{Account, now_ts};
+
+/// How long the daemon waits before looking again when there is no account to sync.
+const IDLE_RECHECK: Duration = Duration::from_secs(60);
+
+/// When each account last started a sync pass.
+#[derive(Debug, Default)]
+pub struct Schedule {
+    last_started: HashMap<String, i64>,
+}
+
+impl Schedule {
+    fn interval_secs(account: &Account) -> i64 {
+        60 * i64::from(account.settings.poll_interval_minutes.max(1))
+    }
+
+    fn due_at(&self, account: &Account) -> i64 {
+        self.last_started
+            .get(&account.id)
+            .map_or(i64::MIN, |started| started + Self::interval_secs(account))
+    }
+
+    /// Accounts whose interval has elapsed at `now` (accounts not seen before are due at once),
+    /// marked as started. Accounts missing from `accounts` or paused are forgotten, so a resumed
+    /// account is due at once.
+    pub fn take_due(&mut self, accounts: &[Account], now: i64) -> Vec<Account> {
+        self.last_started
+            .retain(|id, _| accounts.iter().any(|a| &a.id == id && a.settings.enabled));
+        let due: Vec<Account> = accounts
+            .iter()
+            .filter(|account| account.settings.enabled && self.due_at(account) <= now)
+            .cloned()
+            .collect();
+        self.mark_started(&due, now);
+        due
+    }
+
+    /// Restarts the interval of accounts synced outside the schedule (a manual or reload pass).
+    pub fn mark_started(&mut self, accounts: &[Account], now: i64) {
+        for account in accounts {
+            self.last_started.insert(account.id.clone(), now);
+        }
+    }
+
+    /// Time until the next of `accounts` is due (zero if one already is); `None` without
+    /// enabled accounts.
+    pub fn next_due_in(&self, accounts: &[Account], now: i64) -> Option<Duration> {
+        accounts
+            .iter()
+            .filter(|account| account.settings.enabled)
+            .map(|account| self.due_at(account))
+            .min()
+            .map(|due| Duration::from_secs(due.saturating_sub(now).max(0) as u64))
+    }
+}
+
+/// Runs scheduled passes until Ctrl-C, which lets the running pass stop cleanly first.
+/// Accounts are re-read before every pass, so accounts added or edited meanwhile (interval,
+/// folders, policies) are picked up without a restart.
+pub async fn run(
+    db: Arc<dyn MailStore>,
+    defaults: &AppDefaults,
+    options: SyncOptions,
+) -> Result<()> {
+    let engine = SyncEngine::new(db.clone(), defaults.max_concurrent_folders);
+    let cancel = engine.cancel_token();
+    tokio::spawn(cancel_on_ctrl_c(cancel.clone()));
+    tokio::spawn(sync::keep_pooled_connections_alive(cancel.clone()));
+    let mut schedule = Schedule::default();
+    let mut last_cleanup: HashMap<String, i64> = HashMap::new();
+    info!("Sync daemon started");
+
+    while !cancel.is_cancelled() {
+        let accounts = db.list_accounts().await?;
+        register_ciphers(db.as_ref(), &accounts)?;
+        let due = schedule.take_due(&accounts, now_ts());
+
+        // Ctrl-C stops the pass at the next batch boundary: committed batches carry their
+        // checkpoint, so the next run resumes where this one stopped.
+        for account in &due {
+            if cancel.is_cancelled() {
+                break;
+            }
+            // Password accounts have no token; their password is read when the pass connects.
+            if account.settings.credential.is_oauth() {
+                match refresh_stored(&account.provider, &account.id).await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => {
+                        warn!(account = %account.id, "No usable refresh token; run otto interactively to re-authorize");
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!(account = %account.id, error = %e, "Token refresh failed; retrying next interval");
+                        continue;
+                    }
+                }
+            }
+            let now = now_ts();
+            let has_rules = !account.settings.cleanup_rules.is_empty();
+            let safe_mode = options.safe_mode || account.settings.safe_mode;
+            if has_rules
+                && !safe_mode
+                && last_cleanup
+                    .get(&account.id)
+                    .is_none_or(|at| now - at >= CLEANUP_INTERVAL_SECS)
+            {
+                last_cleanup.insert(account.id.clone(), now);
+                if let Err(e) =
+                    cleanup::run_rules(db.as_ref(), account, None, now, defaults.display_tz, false)
+                        .await
+                {
+                    warn!(account = %account.id, error = %e, "Cleanup rules failed");
+                }
+            }
+            let pass_started = now_ts();
+            let report = engine
+                .sync_all(std::slice::from_ref(account), options)
+                .await;
+            if let Err(e) = notify::dispatch(
+                db.as_ref(),
+                account,
+                pass_started - notify::RETRY_WINDOW_SECS,
+                now_ts(),
+                defaults.display_tz,
+                &defaults.smtp,
+            )
+            .await
+            {
+                warn!(account = %account.id, error = %e, "Notifications failed");
+            }
+            if report.is_ok() {
+                info!(account = %account.id, summary = %report.summary(), "Scheduled sync finished");
+            } else {
+                warn!(account = %account.id, summary = %report.summary(), "Scheduled sync had failures");
+            }
+        }
+
+        let wait = schedule
+            .next_due_in(&accounts, now_ts())
+            .unwrap_or(IDLE_RECHECK);
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = cancel.cancelled() => break,
+        }
+    }
+
+    info!("Sync daemon stopped");
+    Ok(())
+}
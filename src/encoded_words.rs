@@ -0,0 +1,120 @@
+//! RFC 2047 encoded words (`=?charset?Q?...?=` / `=?charset?B?...?=`) in header values otto
+//! shows as-is: cached subjects in the CLI list and attachment filenames in MIME summaries.
+//! Anything that does not decode is kept verbatim, never dropped.
+use base64::Engine;
+
+/// Decodes every encoded word in `text`. Whitespace between two adjacent encoded words is
+/// dropped, as RFC 2047 requires; words that fail to decode stay as they were.
+pub fn decode_mime_words(text: &str) -> String {
+    if !text.contains("=?") {
+        return text.to_string();
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut remaining = text;
+    let mut last_was_encoded = false;
+
+    while let Some(start) = remaining.find("=?") {
+        let before = &remaining[..start];
+        let Some(len) = encoded_word_len(&remaining[start..]) else {
+            // A stray "=?" (e.g. "1=?"); keep it and look for a real word after it.
+            result.push_str(&remaining[..start + 2]);
+            remaining = &remaining[start + 2..];
+            last_was_encoded = false;
+            continue;
+        };
+
+        let word = &remaining[start..start + len];
+        match decode_mime_word(word) {
+            Some(decoded) => {
+                if !(last_was_encoded && before.trim().is_empty()) {
+                    result.push_str(before);
+                }
+                result.push_str(&decoded);
+                last_was_encoded = true;
+            }
+            None => {
+                result.push_str(before);
+                result.push_str(word);
+                last_was_encoded = false;
+            }
+        }
+        remaining = &remaining[start + len..];
+    }
+
+    result.push_str(remaining);
+    result
+}
+
+/// Length of the encoded word `s` starts with (`s` begins with "=?"), if it is one: charset,
+/// encoding and text are separated by '?', none of them holds whitespace, and "?=" closes it.
+fn encoded_word_len(s: &str) -> Option<usize> {
+    let body = &s[2..];
+    let charset_end = body.find('?')?;
+    let encoding_end = charset_end + 1 + body[charset_end + 1..].find('?')?;
+    let text_start = encoding_end + 1;
+    let text_len = body[text_start..].find("?=")?;
+    if body[..text_start + text_len].contains(char::is_whitespace) {
+        return None;
+    }
+    Some(2 + text_start + text_len + 2)
+}
+
+fn decode_mime_word(word: &str) -> Option<String> {
+    // Format: =?charset?encoding?encoded-text?=
+    let inner = word.strip_prefix("=?")?.strip_suffix("?=")?;
+    let mut parts = inner.splitn(3, '?');
+    let (_charset, encoding, encoded_text) = (parts.next()?, parts.next()?, parts.next()?);
+
+    match encoding.to_ascii_uppercase().as_str() {
+        "Q" => decode_quoted_printable_rfc2047(encoded_text),
+        "B" => decode_base64(encoded_text),
+        _ => None,
+    }
+}
+
+/// The "Q" encoding: `=XX` hex escapes and `_` for space. An `=` not followed by two hex
+/// digits is kept literally; `None` if the bytes are not UTF-8.
+pub fn decode_quoted_printable_rfc2047(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' => match bytes.get(i + 1..i + 3).and_then(hex_byte) {
+                Some(byte) => {
+                    result.push(byte);
+                    i += 3;
+                }
+                None => {
+                    result.push(b'=');
+                    i += 1;
+                }
+            },
+            b'_' => {
+                result.push(b' ');
+                i += 1;
+            }
+            b => {
+                result.push(b);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(result).ok()
+}
+
+fn hex_byte(pair: &[u8]) -> Option<u8> {
+    let hi = char::from(pair[0]).to_digit(16)?;
+    let lo = char::from(pair[1]).to_digit(16)?;
+    Some((hi * 16 + lo) as u8)
+}
+
+fn decode_base64(text: &str) -> Option<String> {
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(text.as_bytes())
+        .ok()?;
+    String::from_utf8(decoded).ok()
+}
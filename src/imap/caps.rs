@@ -0,0 +1,103 @@
+//! What an IMAP server supports, from the CAPABILITY response sent after login (servers often
+//! advertise more once authenticated). Sync and replay consult it before using an extension
+//! instead of assuming Gmail: CONDSTORE for MODSEQ change tracking, X-GM-EXT-1 for the Gmail
+//! message/thread ids and labels, MOVE and UIDPLUS for moves and targeted expunges, and
+//! COMPRESS=DEFLATE, which `ImapClient::connect` turns on right after the probe. QUOTA gates
+//! the per-sync storage usage check, and LIST-STATUS lets the folder counts come back in one
+//! command instead of a STATUS per folder. With ID the client names itself right after login.
+use async_imap::types::{Capabilities, Capability};
+
+/// Gmail's extra FETCH items, requested only from servers advertising X-GM-EXT-1.
+const GMAIL_FETCH_ITEMS: &str = "X-GM-MSGID X-GM-THRID X-GM-LABELS";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServerCaps {
+    /// RFC 7162 CONDSTORE: `SELECT (CONDSTORE)`, HIGHESTMODSEQ and `SEARCH MODSEQ`.
+    pub condstore: bool,
+    /// RFC 7162 QRESYNC (implies CONDSTORE); recorded for quick resync, not used yet.
+    pub qresync: bool,
+    /// X-GM-EXT-1: `X-GM-MSGID`, `X-GM-THRID` and `X-GM-LABELS`.
+    pub gmail: bool,
+    /// RFC 6851 `UID MOVE`.
+    pub move_ext: bool,
+    /// RFC 4315 UIDPLUS: `UID EXPUNGE` of just the given UIDs.
+    pub uidplus: bool,
+    /// RFC 4978 COMPRESS=DEFLATE.
+    pub compress_deflate: bool,
+    /// RFC 2087/9208 QUOTA: `GETQUOTAROOT`.
+    pub quota: bool,
+    /// RFC 2342 NAMESPACE.
+    pub namespace: bool,
+    /// RFC 5819 LIST-STATUS: `LIST ... RETURN (STATUS (...))`.
+    pub list_status: bool,
+    /// RFC 2971 ID.
+    pub id: bool,
+}
+
+impl ServerCaps {
+    /// From capability names as advertised (case-insensitive; `AUTH=` entries are ignored).
+    pub fn from_names<'a>(names: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut caps = Self::default();
+        for name in names {
+            match name.to_ascii_uppercase().as_str() {
+                "CONDSTORE" => caps.condstore = true,
+                "QRESYNC" => {
+                    caps.qresync = true;
+                    caps.condstore = true;
+                }
+                "X-GM-EXT-1" => caps.gmail = true,
+                "MOVE" => caps.move_ext = true,
+                "UIDPLUS" => caps.uidplus = true,
+                "COMPRESS=DEFLATE" => caps.compress_deflate = true,
+                "NAMESPACE" => caps.namespace = true,
+                "LIST-STATUS" => caps.list_status = true,
+                "ID" => caps.id = true,
+                // RFC 9208 servers list the resources they track as `QUOTA=RES-*`.
+                name if name == "QUOTA" || name.starts_with("QUOTA=") => caps.quota = true,
+                _ => {}
+            }
+        }
+        caps
+    }
+
+    pub fn from_capabilities(capabilities: &Capabilities) -> Self {
+        Self::from_names(capabilities.iter().filter_map(|cap| match cap {
+            Capability::Atom(name) => Some(name.as_str()),
+            _ => None,
+        }))
+    }
+
+    /// A parenthesized FETCH item list: `items`, plus the Gmail ids and labels when the
+    /// server has them.
+    pub fn fetch_query(&self, items: &str) -> String {
+        if self.gmail {
+            format!("({} {})", items, GMAIL_FETCH_ITEMS)
+        } else {
+            format!("({})", items)
+        }
+    }
+
+    /// Space-separated names of the extensions found, for logs (`none` when there are none).
+    pub fn describe(&self) -> String {
+        let names: Vec<&str> = [
+            (self.condstore, "CONDSTORE"),
+            (self.qresync, "QRESYNC"),
+            (self.gmail, "X-GM-EXT-1"),
+            (self.move_ext, "MOVE"),
+            (self.uidplus, "UIDPLUS"),
+            (self.compress_deflate, "COMPRESS=DEFLATE"),
+            (self.quota, "QUOTA"),
+            (self.namespace, "NAMESPACE"),
+            (self.list_status, "LIST-STATUS"),
+            (self.id, "ID"),
+        ]
+        .into_iter()
+        .filter_map(|(has, name)| has.then_some(name))
+        .collect();
+        if names.is_empty() {
+            "none".to_string()
+        } else {
+            names.join(" ")
+        }
+    }
+}
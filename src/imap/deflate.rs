@@ -0,0 +1,134 @@
+//! RFC 4978 COMPRESS=DEFLATE. Once `COMPRESS DEFLATE` succeeds, both directions of the
+//! connection are one raw DEFLATE stream each. `MailStream` runs this layer between the
+//! transport and the protocol trace, so traces still show plain IMAP. Every flush ends the
+//! pending output with a sync flush, so the server can inflate each command as soon as it is sent.
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Bytes read from the transport (and reserved for output) at a time.
+const CHUNK: usize = 16 * 1024;
+
+#[derive(Debug)]
+pub(super) struct Deflate {
+    compress: Compress,
+    decompress: Decompress,
+    /// Compressed bytes read from the transport and not inflated yet.
+    input: Vec<u8>,
+    /// Compressed bytes not written to the transport yet.
+    output: Vec<u8>,
+    /// Data was compressed since the last sync flush.
+    unflushed: bool,
+}
+
+impl Deflate {
+    pub(super) fn new() -> Self {
+        Self {
+            // Raw DEFLATE: no zlib header or trailer.
+            compress: Compress::new(Compression::default(), false),
+            decompress: Decompress::new(false),
+            input: Vec::new(),
+            output: Vec::new(),
+            unflushed: false,
+        }
+    }
+
+    pub(super) fn poll_read<S: AsyncRead + Unpin>(
+        &mut self,
+        inner: &mut S,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            if !self.input.is_empty() {
+                let (in_before, out_before) =
+                    (self.decompress.total_in(), self.decompress.total_out());
+                let status = self
+                    .decompress
+                    .decompress(
+                        &self.input,
+                        buf.initialize_unfilled(),
+                        FlushDecompress::None,
+                    )
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let consumed = (self.decompress.total_in() - in_before) as usize;
+                let produced = (self.decompress.total_out() - out_before) as usize;
+                self.input.drain(..consumed);
+                buf.advance(produced);
+                if produced > 0 || status == Status::StreamEnd {
+                    return Poll::Ready(Ok(()));
+                }
+            }
+            // Nothing to hand out until more of the stream arrives.
+            let mut chunk = [0u8; CHUNK];
+            let mut raw = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut *inner).poll_read(cx, &mut raw))?;
+            if raw.filled().is_empty() {
+                return Poll::Ready(Ok(()));
+            }
+            self.input.extend_from_slice(raw.filled());
+        }
+    }
+
+    /// Accepts all of `data` once earlier output has reached the transport.
+    pub(super) fn poll_write<S: AsyncWrite + Unpin>(
+        &mut self,
+        inner: &mut S,
+        cx: &mut Context<'_>,
+        data: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        ready!(self.poll_drain(inner, cx))?;
+        self.deflate(data, FlushCompress::None)?;
+        self.unflushed = true;
+        Poll::Ready(Ok(data.len()))
+    }
+
+    pub(super) fn poll_flush<S: AsyncWrite + Unpin>(
+        &mut self,
+        inner: &mut S,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.unflushed {
+            self.deflate(&[], FlushCompress::Sync)?;
+            self.unflushed = false;
+        }
+        ready!(self.poll_drain(inner, cx))?;
+        Pin::new(inner).poll_flush(cx)
+    }
+
+    fn deflate(&mut self, mut data: &[u8], flush: FlushCompress) -> io::Result<()> {
+        loop {
+            self.output.reserve(CHUNK);
+            let before = self.compress.total_in();
+            self.compress
+                .compress_vec(data, &mut self.output, flush)
+                .map_err(io::Error::other)?;
+            data = &data[(self.compress.total_in() - before) as usize..];
+            // Done once the input is taken and the compressor stopped short of a full buffer.
+            if data.is_empty() && self.output.len() < self.output.capacity() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn poll_drain<S: AsyncWrite + Unpin>(
+        &mut self,
+        inner: &mut S,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        while !self.output.is_empty() {
+            let written = ready!(Pin::new(&mut *inner).poll_write(cx, &self.output))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.output.drain(..written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
@@ -1,76 +1,948 @@
-//! IMAP connector (XOAUTH2) using async-imap 0.11 with tokio-rustls.
-use anyhow::{Context, Result};
+//! IMAP connector (XOAUTH2) using async-imap 0.11 with tokio-rustls. The account's
+//! `ImapEndpoint` picks the server and transport: implicit TLS, STARTTLS, or plain TCP for
+//! loopback bridges, with an optional pinned certificate fingerprint instead of CA checks.
+use anyhow::{Context, Result, anyhow, bail};
+use async_imap::error::Result as ImapResult;
+use async_imap::types::{Mailbox, NameAttribute, QuotaResourceName};
 use async_imap::{Authenticator, Client, Session};
+use futures::TryStreamExt;
+use mailparse::MailHeaderMap;
 use rustls_native_certs::load_native_certs;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::TcpStream;
+use tokio::time::Sleep;
 use tokio_rustls::TlsConnector;
-use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerName};
-use tokio_util::compat::TokioAsyncReadCompatExt;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::{ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::version::{TLS12, TLS13};
+use tokio_rustls::rustls::{
+    Certificate, ClientConfig, ConfigBuilder, DEFAULT_CIPHER_SUITES, PrivateKey, RootCertStore,
+    ServerName, SupportedProtocolVersion, WantsVerifier,
+};
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+use tracing::{debug, warn};
 
-use crate::types::Account;
+use crate::types::{
+    Account, AccountQuota, ClientCert, FolderCounts, ImapEndpoint, MailboxInfo, MailboxNamespace,
+    TlsMode, TlsPolicy, TlsVersion,
+};
+
+pub mod caps;
+mod deflate;
+mod moves;
+pub mod reconnect;
+pub mod timeout;
+pub mod trace;
+
+pub use async_imap::extensions::uidplus::CopyUid;
+pub use caps::ServerCaps;
+use deflate::Deflate;
+pub use reconnect::{SelectedMailbox, is_connection_lost, is_session_lost};
+pub use timeout::{ImapTimeout, ImapTimeouts, is_timeout, set_timeouts};
+pub use trace::{ProtocolTrace, TraceLog};
+
+/// An authenticated IMAP session over whichever transport the account uses, with the
+/// capabilities the server advertised after login. Derefs to the async-imap `Session`;
+/// `select`, `select_condstore`, `examine` and `uid_search` are shadowed by versions that give
+/// up after the configured [`ImapTimeouts`] and remember the mailbox they opened.
+#[derive(Debug)]
+pub struct ImapSession {
+    session: Session<Compat<MailStream>>,
+    caps: ServerCaps,
+    server_id: Option<BTreeMap<String, String>>,
+    selected: Option<SelectedMailbox>,
+}
+
+impl ImapSession {
+    /// The extensions this connection may use, probed once at login.
+    pub fn caps(&self) -> &ServerCaps {
+        &self.caps
+    }
+
+    /// What the server said about itself in answer to the ID sent at login; `None` when it
+    /// has no ID extension or answered `NIL`.
+    pub fn server_id(&self) -> Option<&BTreeMap<String, String>> {
+        self.server_id.as_ref()
+    }
+
+    /// Whether an operation on this connection timed out; it must not be reused.
+    pub fn timed_out(&self) -> bool {
+        self.session.get_ref().get_ref().timed_out
+    }
+
+    /// The mailbox the last successful SELECT/EXAMINE opened (what
+    /// [`ImapClient::reconnect`] reopens).
+    pub fn selected(&self) -> Option<&SelectedMailbox> {
+        self.selected.as_ref()
+    }
+
+    pub async fn select<S: AsRef<str>>(&mut self, mailbox: S) -> ImapResult<Mailbox> {
+        let limit = timeout::timeouts().select;
+        let result = tokio::time::timeout(limit, self.session.select(mailbox.as_ref())).await;
+        let result = self.settle("SELECT", limit, result);
+        self.track_selected(mailbox.as_ref(), false, false, result)
+    }
+
+    pub async fn select_condstore<S: AsRef<str>>(&mut self, mailbox: S) -> ImapResult<Mailbox> {
+        let limit = timeout::timeouts().select;
+        let result =
+            tokio::time::timeout(limit, self.session.select_condstore(mailbox.as_ref())).await;
+        let result = self.settle("SELECT", limit, result);
+        self.track_selected(mailbox.as_ref(), false, true, result)
+    }
+
+    pub async fn examine<S: AsRef<str>>(&mut self, mailbox: S) -> ImapResult<Mailbox> {
+        let limit = timeout::timeouts().select;
+        let result = tokio::time::timeout(limit, self.session.examine(mailbox.as_ref())).await;
+        let result = self.settle("EXAMINE", limit, result);
+        self.track_selected(mailbox.as_ref(), true, false, result)
+    }
+
+    /// Records a successfully opened mailbox; a failed SELECT leaves none open.
+    fn track_selected(
+        &mut self,
+        name: &str,
+        read_only: bool,
+        condstore: bool,
+        result: ImapResult<Mailbox>,
+    ) -> ImapResult<Mailbox> {
+        self.selected = result.as_ref().ok().map(|mailbox| SelectedMailbox {
+            name: name.to_string(),
+            uid_validity: mailbox.uid_validity,
+            read_only,
+            condstore,
+        });
+        result
+    }
+
+    pub async fn uid_search<S: AsRef<str>>(&mut self, query: S) -> ImapResult<HashSet<u32>> {
+        let limit = timeout::timeouts().search;
+        let result = tokio::time::timeout(limit, self.session.uid_search(query)).await;
+        self.settle("UID SEARCH", limit, result)
+    }
+
+    /// Unwraps a time-limited command. On expiry the command was abandoned half-way, so the
+    /// connection is marked timed out and fails every later read or write.
+    fn settle<T>(
+        &mut self,
+        operation: &'static str,
+        limit: Duration,
+        result: Result<ImapResult<T>, tokio::time::error::Elapsed>,
+    ) -> ImapResult<T> {
+        result.unwrap_or_else(|_| {
+            self.session.get_mut().get_mut().timed_out = true;
+            Err(ImapTimeout {
+                operation,
+                after: limit,
+            }
+            .into_io()
+            .into())
+        })
+    }
+}
+
+impl Deref for ImapSession {
+    type Target = Session<Compat<MailStream>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.session
+    }
+}
+
+impl DerefMut for ImapSession {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.session
+    }
+}
+
+/// The byte stream under an IMAP session: the transport, optionally DEFLATE-compressed
+/// (`COMPRESS=DEFLATE`) and copied to a protocol trace (always as plain IMAP). A read that
+/// waits longer than `ImapTimeouts::fetch_idle` for the server fails with `TimedOut`, as does
+/// all later I/O.
+#[derive(Debug)]
+pub struct MailStream {
+    transport: Transport,
+    deflate: Option<Box<Deflate>>,
+    trace: Option<Arc<ProtocolTrace>>,
+    /// Armed while a read waits for the server; cleared by incoming data and by each write
+    /// (a new command).
+    read_deadline: Option<Pin<Box<Sleep>>>,
+    timed_out: bool,
+}
+
+#[derive(Debug)]
+enum Transport {
+    Tls(Box<TlsStream<TcpStream>>),
+    /// Unencrypted TCP (`TlsMode::Plain`, loopback hosts only).
+    Plain(TcpStream),
+}
+
+impl MailStream {
+    fn new(transport: Transport, trace: Option<Arc<ProtocolTrace>>) -> Self {
+        Self {
+            transport,
+            deflate: None,
+            trace,
+            read_deadline: None,
+            timed_out: false,
+        }
+    }
+
+    /// Compresses everything from here on; call right after the server accepted
+    /// `COMPRESS DEFLATE`.
+    fn start_deflate(&mut self) {
+        self.deflate = Some(Box::new(Deflate::new()));
+    }
+
+    /// Whether `COMPRESS DEFLATE` is in effect.
+    pub fn is_compressed(&self) -> bool {
+        self.deflate.is_some()
+    }
+}
+
+impl AsyncRead for MailStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.timed_out {
+            return Poll::Ready(Err(stalled()));
+        }
+        let before = buf.filled().len();
+        let poll = match &mut this.deflate {
+            Some(deflate) => deflate.poll_read(&mut this.transport, cx, buf),
+            None => Pin::new(&mut this.transport).poll_read(cx, buf),
+        };
+        match &poll {
+            Poll::Ready(Ok(())) => {
+                this.read_deadline = None;
+                if let Some(trace) = &this.trace {
+                    trace.server(&buf.filled()[before..]);
+                }
+            }
+            Poll::Ready(Err(_)) => {}
+            Poll::Pending => {
+                let deadline = this.read_deadline.get_or_insert_with(|| {
+                    Box::pin(tokio::time::sleep(timeout::timeouts().fetch_idle))
+                });
+                if deadline.as_mut().poll(cx).is_ready() {
+                    this.timed_out = true;
+                    return Poll::Ready(Err(stalled()));
+                }
+            }
+        }
+        poll
+    }
+}
+
+impl AsyncWrite for MailStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.timed_out {
+            return Poll::Ready(Err(stalled()));
+        }
+        this.read_deadline = None;
+        let poll = match &mut this.deflate {
+            Some(deflate) => deflate.poll_write(&mut this.transport, cx, buf),
+            None => Pin::new(&mut this.transport).poll_write(cx, buf),
+        };
+        if let (Poll::Ready(Ok(written)), Some(trace)) = (&poll, &this.trace) {
+            trace.client(&buf[..*written]);
+        }
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        match &mut this.deflate {
+            Some(deflate) => deflate.poll_flush(&mut this.transport, cx),
+            None => Pin::new(&mut this.transport).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if let Some(deflate) = &mut this.deflate {
+            std::task::ready!(deflate.poll_flush(&mut this.transport, cx))?;
+        }
+        Pin::new(&mut this.transport).poll_shutdown(cx)
+    }
+}
+
+/// The error for I/O on a stream whose server went quiet for too long.
+fn stalled() -> io::Error {
+    ImapTimeout {
+        operation: "read",
+        after: timeout::timeouts().fetch_idle,
+    }
+    .into_io()
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+            Transport::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+            Transport::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+            Transport::Plain(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+            Transport::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// What the client sends in its ID command (RFC 2971).
+const CLIENT_ID: [(&str, Option<&str>); 2] = [
+    ("name", Some("otto")),
+    ("version", Some(env!("CARGO_PKG_VERSION"))),
+];
 
 pub struct ImapClient;
 
 impl ImapClient {
-    pub async fn connect(
+    /// Connects and authenticates with `secret` (see `credentials::imap_secret`): an OAuth
+    /// access token for XOAUTH2, or the account's password for `AUTHENTICATE PLAIN` / `LOGIN`.
+    /// Traced to the shared log when `OTTO_IMAP_TRACE` turned it on.
+    pub async fn connect(account: &Account, secret: &str) -> Result<ImapSession> {
+        let trace = trace::shared_log().map(|log| {
+            let endpoint = &account.settings.imap;
+            let server = format!("{}:{}", endpoint.host, endpoint.port);
+            Arc::new(ProtocolTrace::connection(log, &account.id, &server))
+        });
+        Self::connect_traced(account, secret, trace).await
+    }
+
+    /// Like [`ImapClient::connect`], copying the conversation to `trace` when given (from the
+    /// greeting on; with STARTTLS, from the TLS handshake on). Everything up to the logged-in
+    /// session has to finish within `ImapTimeouts::connect`.
+    pub async fn connect_traced(
         account: &Account,
-        access_token: &str,
-    ) -> Result<Session<tokio_util::compat::Compat<tokio_rustls::client::TlsStream<TcpStream>>>>
-    {
-        // Create TLS config with native root certificates
-        let mut root_store = RootCertStore::empty();
-        for cert in load_native_certs().context("failed to load native certs")? {
-            root_store
-                .add(&tokio_rustls::rustls::Certificate(cert.0))
-                .context("failed to add cert to root store")?;
+        secret: &str,
+        trace: Option<Arc<ProtocolTrace>>,
+    ) -> Result<ImapSession> {
+        let limit = timeout::timeouts().connect;
+        tokio::time::timeout(limit, Self::open(account, secret, trace))
+            .await
+            .map_err(|_| ImapTimeout {
+                operation: "connect",
+                after: limit,
+            })
+            .with_context(|| {
+                let endpoint = &account.settings.imap;
+                format!("connecting to {}:{}", endpoint.host, endpoint.port)
+            })?
+    }
+
+    async fn open(
+        account: &Account,
+        secret: &str,
+        trace: Option<Arc<ProtocolTrace>>,
+    ) -> Result<ImapSession> {
+        let endpoint = &account.settings.imap;
+        if endpoint.tls == TlsMode::Plain && !endpoint.is_loopback() {
+            bail!(
+                "refusing unencrypted IMAP to {}: TLS mode plain is only allowed for localhost",
+                endpoint.host
+            );
         }
+        let address = format!("{}:{}", endpoint.host, endpoint.port);
 
-        let config = ClientConfig::builder()
-            .with_safe_defaults()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+        // Connect via TCP
+        let tcp = TcpStream::connect((endpoint.host.as_str(), endpoint.port))
+            .await
+            .with_context(|| format!("connecting to {}", address))?;
 
-        let connector = TlsConnector::from(Arc::new(config));
+        // Convert tokio AsyncRead/AsyncWrite to futures AsyncRead/AsyncWrite and read the
+        // server greeting (sent in the clear before STARTTLS, inside TLS otherwise)
+        let client = match endpoint.tls {
+            TlsMode::Tls => {
+                let tls = start_tls(endpoint, &account.settings.tls_policy, tcp).await?;
+                let stream = MailStream::new(Transport::Tls(Box::new(tls)), trace);
+                let mut client = Client::new(stream.compat());
+                read_greeting(&mut client).await?;
+                client
+            }
+            TlsMode::Starttls => {
+                let mut plain = Client::new(tcp.compat());
+                read_greeting(&mut plain).await?;
+                plain
+                    .run_command_and_check_ok("STARTTLS", None)
+                    .await
+                    .with_context(|| format!("STARTTLS on {}", address))?;
+                // Anything the server sent before the handshake is dropped with the client.
+                let tls = start_tls(
+                    endpoint,
+                    &account.settings.tls_policy,
+                    plain.into_inner().into_inner(),
+                )
+                .await?;
+                Client::new(MailStream::new(Transport::Tls(Box::new(tls)), trace).compat())
+            }
+            TlsMode::Plain => {
+                let mut client =
+                    Client::new(MailStream::new(Transport::Plain(tcp), trace).compat());
+                read_greeting(&mut client).await?;
+                client
+            }
+        };
 
-        // Connect via TCP
-        let tcp = TcpStream::connect(("imap.gmail.com", 993))
+        let mut session = if account.settings.credential.is_oauth() {
+            let xoauth = Xoauth2 {
+                user: account.email.clone(),
+                access_token: secret.to_string(),
+            };
+            client
+                .authenticate("XOAUTH2", xoauth)
+                .await
+                .map_err(|(err, _client)| err)
+                .context("XOAUTH2 authenticate")?
+        } else {
+            password_login(client, &account.email, secret).await?
+        };
+
+        let caps = ServerCaps::from_capabilities(
+            &session
+                .capabilities()
+                .await
+                .context("CAPABILITY after login")?,
+        );
+        debug!(account = %account.id, capabilities = %caps.describe(), "IMAP capabilities");
+        // Some servers refuse to SELECT until the client has named itself; the answer is only
+        // informational, so a failed ID doesn't fail the connection.
+        let server_id = if caps.id {
+            match session.id(CLIENT_ID).await {
+                Ok(server_id) => {
+                    let server_id: Option<BTreeMap<String, String>> =
+                        server_id.map(|fields| fields.into_iter().collect());
+                    debug!(account = %account.id, server_id = ?server_id, "IMAP server ID");
+                    server_id
+                }
+                Err(e) => {
+                    warn!(account = %account.id, error = %e, "IMAP ID failed");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if caps.compress_deflate {
+            session
+                .run_command_and_check_ok("COMPRESS DEFLATE")
+                .await
+                .context("COMPRESS DEFLATE")?;
+            // The server compresses from its next response on; nothing is buffered before it.
+            session.get_mut().get_mut().start_deflate();
+            debug!(account = %account.id, "IMAP compression enabled");
+        }
+        Ok(ImapSession {
+            session,
+            caps,
+            server_id,
+            selected: None,
+        })
+    }
+
+    /// Uploads `message` to `folder` with APPEND (bare LF line endings become CRLF). The server
+    /// assigns the UID; the message reaches the cache on the folder's next sync.
+    pub async fn append(
+        session: &mut ImapSession,
+        folder: &str,
+        message: &AppendMessage,
+    ) -> Result<()> {
+        // async-imap quotes the name as is, so anything needing an escape is refused here.
+        if folder.contains(['"', '\\', '\r', '\n']) {
+            bail!(
+                "cannot APPEND to {:?}: unsupported characters in the folder name",
+                folder
+            );
+        }
+        let flags = (!message.flags.is_empty()).then(|| format!("({})", message.flags.join(" ")));
+        let internal_date = message
+            .internal_date
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|date| format!("\"{}\"", date.format("%d-%b-%Y %H:%M:%S +0000")));
+        session
+            .append(
+                folder,
+                flags.as_deref(),
+                internal_date.as_deref(),
+                crlf_lines(&message.raw),
+            )
             .await
-            .context("connecting to imap.gmail.com:993")?;
+            .with_context(|| format!("APPEND to {}", folder))
+    }
 
-        // Upgrade to TLS
-        let server_name = ServerName::try_from("imap.gmail.com").context("invalid DNS name")?;
-        let tls_stream = connector
-            .connect(server_name, tcp)
+    /// Every mailbox the server reports for `LIST "" "*"`, attributes included.
+    pub async fn list_folders(session: &mut ImapSession) -> Result<Vec<MailboxInfo>> {
+        let names: Vec<_> = session
+            .list(Some(""), Some("*"))
+            .await
+            .context("LIST")?
+            .try_collect()
             .await
-            .context("starting TLS for IMAP")?;
+            .context("reading LIST response")?;
+        Ok(names
+            .iter()
+            .map(|name| MailboxInfo {
+                name: name.name().to_string(),
+                delimiter: name.delimiter().map(str::to_string),
+                attributes: name.attributes().iter().map(attribute_label).collect(),
+            })
+            .collect())
+    }
 
-        // Convert tokio AsyncRead/AsyncWrite to futures AsyncRead/AsyncWrite
-        let compat_stream = tls_stream.compat();
+    /// The personal namespace from `NAMESPACE` (the first personal entry), or on servers
+    /// without it the empty prefix with the delimiter `LIST "" ""` reports.
+    pub async fn namespace(session: &mut ImapSession) -> Result<MailboxNamespace> {
+        if session.caps().namespace
+            && let Some(namespaces) = session.namespace().await.context("NAMESPACE")?
+            && let Some(personal) = namespaces.personal.first()
+        {
+            return Ok(MailboxNamespace {
+                prefix: personal.prefix.to_string(),
+                delimiter: personal.delimiter.as_deref().map(str::to_string),
+            });
+        }
+        let names: Vec<_> = session
+            .list(Some(""), None)
+            .await
+            .context("LIST \"\" \"\"")?
+            .try_collect()
+            .await
+            .context("reading LIST response")?;
+        Ok(MailboxNamespace {
+            prefix: String::new(),
+            delimiter: names
+                .first()
+                .and_then(|name| name.delimiter())
+                .map(str::to_string),
+        })
+    }
 
-        // Create IMAP client
-        let mut client = Client::new(compat_stream);
+    /// Unseen and total counts of `folders`: one `LIST "" "*" RETURN (STATUS ...)` on servers
+    /// with LIST-STATUS, else a `STATUS` per folder. Folders the server doesn't report on (gone,
+    /// not selectable, STATUS refused) are left out.
+    pub async fn folder_counts(
+        session: &mut ImapSession,
+        folders: &[String],
+        checked_at: i64,
+    ) -> Result<Vec<FolderCounts>> {
+        const ITEMS: &str = "(MESSAGES UNSEEN)";
+        let counts = |folder: &str, mailbox: &Mailbox| FolderCounts {
+            folder: folder.to_string(),
+            unseen: mailbox.unseen.unwrap_or(0),
+            messages: mailbox.exists,
+            checked_at,
+        };
+        if session.caps().list_status {
+            let statuses = session
+                .list_status(Some(""), Some("*"), ITEMS)
+                .await
+                .context("LIST-STATUS")?;
+            return Ok(folders
+                .iter()
+                .filter_map(|folder| {
+                    statuses
+                        .iter()
+                        .find(|(name, _)| name == folder)
+                        .map(|(_, mailbox)| counts(folder, mailbox))
+                })
+                .collect());
+        }
+        let mut out = Vec::new();
+        for folder in folders {
+            match session.status(folder, ITEMS).await {
+                Ok(mailbox) => out.push(counts(folder, &mailbox)),
+                Err(e) => debug!(folder = %folder, error = %e, "STATUS failed; no counts for it"),
+            }
+        }
+        Ok(out)
+    }
 
-        // Read server greeting
-        let _greeting = client
-            .read_response()
+    /// `GETQUOTAROOT INBOX`: the STORAGE and MESSAGE limits of the INBOX's quota roots (the
+    /// first root reporting each wins). `None` when no root has either.
+    pub async fn quota(
+        session: &mut ImapSession,
+        account_id: &str,
+        fetched_at: i64,
+    ) -> Result<Option<AccountQuota>> {
+        let (_, quotas) = session
+            .get_quota_root("INBOX")
             .await
-            .context("reading IMAP greeting")?
-            .ok_or_else(|| anyhow::anyhow!("unexpected end of stream, expected greeting"))?;
-
-        // Authenticate using XOAUTH2
-        let xoauth = Xoauth2 {
-            user: account.email.clone(),
-            access_token: access_token.to_string(),
+            .context("GETQUOTAROOT INBOX")?;
+        let resource = |name: QuotaResourceName| {
+            quotas
+                .iter()
+                .flat_map(|quota| &quota.resources)
+                .find(|resource| resource.name == name)
         };
+        let storage = resource(QuotaResourceName::Storage);
+        let messages = resource(QuotaResourceName::Message);
+        if storage.is_none() && messages.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(AccountQuota {
+            account_id: account_id.to_string(),
+            storage_used: storage.map(|r| r.usage.saturating_mul(1024)),
+            storage_limit: storage.map(|r| r.limit.saturating_mul(1024)),
+            messages_used: messages.map(|r| r.usage),
+            messages_limit: messages.map(|r| r.limit),
+            fetched_at,
+        }))
+    }
+}
+
+/// A message to upload with APPEND: the raw RFC 5322 bytes, the flags to give it (`\Seen`,
+/// ...) and its internal date (the server's arrival time; now when unset).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AppendMessage {
+    pub raw: Vec<u8>,
+    pub flags: Vec<String>,
+    pub internal_date: Option<i64>,
+}
+
+impl AppendMessage {
+    /// `raw` dated by its own `Date:` header, so uploaded mail sorts where it was sent (none
+    /// when the header is missing or unreadable).
+    pub fn from_raw(raw: Vec<u8>, flags: Vec<String>) -> Self {
+        let internal_date = mailparse::parse_headers(&raw)
+            .ok()
+            .and_then(|(headers, _)| headers.get_first_value("Date"))
+            .and_then(|date| mailparse::dateparse(&date).ok());
+        Self {
+            raw,
+            flags,
+            internal_date,
+        }
+    }
+}
+
+/// `raw` with every bare LF turned into CRLF, as IMAP literals require (`.eml` files saved on
+/// Unix usually have LF endings).
+fn crlf_lines(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len() + raw.len() / 32);
+    for (idx, byte) in raw.iter().enumerate() {
+        if *byte == b'\n' && (idx == 0 || raw[idx - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(*byte);
+    }
+    out
+}
+
+async fn read_greeting<T>(client: &mut Client<T>) -> Result<()>
+where
+    T: futures::AsyncRead + futures::AsyncWrite + Unpin + std::fmt::Debug + Send,
+{
+    client
+        .read_response()
+        .await
+        .context("reading IMAP greeting")?
+        .ok_or_else(|| anyhow::anyhow!("unexpected end of stream, expected greeting"))?;
+    Ok(())
+}
 
-        let session = client
-            .authenticate("XOAUTH2", xoauth)
+/// TLS handshake with the account's IMAP server.
+async fn start_tls(
+    endpoint: &ImapEndpoint,
+    tls_policy: &TlsPolicy,
+    tcp: TcpStream,
+) -> Result<TlsStream<TcpStream>> {
+    tls_handshake(
+        &endpoint.host,
+        endpoint.cert_sha256.as_deref(),
+        endpoint.ca_file.as_deref(),
+        endpoint.client_cert.as_ref(),
+        tls_policy,
+        tcp,
+    )
+    .await
+    .context("starting TLS for IMAP")
+}
+
+/// TLS handshake with `host` over `tcp`: against the native root certificates plus the CAs
+/// in `ca_file`, or against the pinned fingerprint alone when there is one. `client_cert` is
+/// presented when the server asks for one, and only `tls_policy`'s versions and cipher suites
+/// are offered. Shared with the SMTP client.
+pub(crate) async fn tls_handshake(
+    host: &str,
+    cert_sha256: Option<&str>,
+    ca_file: Option<&Path>,
+    client_cert: Option<&ClientCert>,
+    tls_policy: &TlsPolicy,
+    tcp: TcpStream,
+) -> Result<TlsStream<TcpStream>> {
+    let identity = client_cert.map(load_client_cert).transpose()?;
+    let builder = policy_builder(tls_policy)?;
+    let config = match cert_sha256 {
+        Some(sha256) => {
+            let builder = builder.with_custom_certificate_verifier(Arc::new(PinnedCertificate {
+                sha256: sha256.to_string(),
+            }));
+            match identity {
+                Some((chain, key)) => builder
+                    .with_client_auth_cert(chain, key)
+                    .context("invalid client certificate or key")?,
+                None => builder.with_no_client_auth(),
+            }
+        }
+        None => {
+            let mut root_store = RootCertStore::empty();
+            for cert in load_native_certs().context("failed to load native certs")? {
+                root_store
+                    .add(&Certificate(cert.0))
+                    .context("failed to add cert to root store")?;
+            }
+            if let Some(path) = ca_file {
+                for cert in load_ca_file(path)? {
+                    root_store
+                        .add(&cert)
+                        .with_context(|| format!("adding a CA from {}", path.display()))?;
+                }
+            }
+            let builder = builder.with_root_certificates(root_store);
+            match identity {
+                Some((chain, key)) => builder
+                    .with_client_auth_cert(chain, key)
+                    .context("invalid client certificate or key")?,
+                None => builder.with_no_client_auth(),
+            }
+        }
+    };
+
+    let connector = TlsConnector::from(Arc::new(config));
+    let server_name =
+        ServerName::try_from(host).with_context(|| format!("invalid server name {:?}", host))?;
+    Ok(connector.connect(server_name, tcp).await?)
+}
+
+/// A rustls config builder offering only `policy`'s protocol versions and cipher suites.
+fn policy_builder(policy: &TlsPolicy) -> Result<ConfigBuilder<ClientConfig, WantsVerifier>> {
+    let suites = if policy.cipher_suites.is_empty() {
+        DEFAULT_CIPHER_SUITES.to_vec()
+    } else {
+        policy
+            .cipher_suites
+            .iter()
+            .map(|name| {
+                DEFAULT_CIPHER_SUITES
+                    .iter()
+                    .find(|suite| format!("{:?}", suite.suite()).eq_ignore_ascii_case(name))
+                    .copied()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "unknown cipher suite {:?} (supported: {})",
+                            name,
+                            supported_cipher_suites().join(" ")
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?
+    };
+    let versions: &[&SupportedProtocolVersion] = match policy.min_version {
+        Some(TlsVersion::Tls13) => &[&TLS13],
+        Some(TlsVersion::Tls12) | None => &[&TLS13, &TLS12],
+    };
+    ClientConfig::builder()
+        .with_cipher_suites(&suites)
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(versions)
+        .with_context(|| {
+            format!(
+                "TLS policy {} leaves nothing to negotiate",
+                policy.describe()
+            )
+        })
+}
+
+/// Checks that `policy` names known cipher suites usable with its minimum version, so a bad
+/// policy is refused when it is set rather than on the next connection.
+pub fn check_tls_policy(policy: &TlsPolicy) -> Result<()> {
+    policy_builder(policy).map(|_| ())
+}
+
+/// IANA names of the cipher suites a [`TlsPolicy`] may list.
+pub fn supported_cipher_suites() -> Vec<String> {
+    DEFAULT_CIPHER_SUITES
+        .iter()
+        .map(|suite| format!("{:?}", suite.suite()))
+        .collect()
+}
+
+/// The certificates of a PEM bundle (`ImapEndpoint::ca_file`); an error when it holds none.
+pub fn load_ca_file(path: &Path) -> Result<Vec<Certificate>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("opening CA bundle {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut io::BufReader::new(file))
+        .with_context(|| format!("reading CA bundle {}", path.display()))?;
+    if certs.is_empty() {
+        bail!("{} holds no PEM certificates", path.display());
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+/// The certificate chain and private key of `client`; an error when either file holds none.
+pub fn load_client_cert(client: &ClientCert) -> Result<(Vec<Certificate>, PrivateKey)> {
+    let file = std::fs::File::open(&client.cert)
+        .with_context(|| format!("opening client certificate {}", client.cert.display()))?;
+    let chain = rustls_pemfile::certs(&mut io::BufReader::new(file))
+        .with_context(|| format!("reading client certificate {}", client.cert.display()))?;
+    if chain.is_empty() {
+        bail!("{} holds no PEM certificates", client.cert.display());
+    }
+    let file = std::fs::File::open(&client.key)
+        .with_context(|| format!("opening client key {}", client.key.display()))?;
+    let key = rustls_pemfile::read_all(&mut io::BufReader::new(file))
+        .with_context(|| format!("reading client key {}", client.key.display()))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .with_context(|| format!("{} holds no PEM private key", client.key.display()))?;
+    Ok((chain.into_iter().map(Certificate).collect(), key))
+}
+
+/// Lowercase hex SHA-256 of a DER certificate, the form `ImapEndpoint::cert_sha256` stores.
+pub fn certificate_sha256(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Accepts exactly the server certificate with the pinned fingerprint (typically the
+/// self-signed certificate of a local bridge); handshake signatures are still verified.
+struct PinnedCertificate {
+    sha256: String,
+}
+
+impl ServerCertVerifier for PinnedCertificate {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        let actual = certificate_sha256(&end_entity.0);
+        if actual == self.sha256 {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(tokio_rustls::rustls::Error::General(format!(
+                "server certificate SHA-256 {} does not match the pinned {}",
+                actual, self.sha256
+            )))
+        }
+    }
+}
+
+/// Wire spelling of a LIST attribute (`\Noselect`, `\Sent`, ...).
+fn attribute_label(attr: &NameAttribute<'_>) -> String {
+    match attr {
+        NameAttribute::NoInferiors => "\\Noinferiors".into(),
+        NameAttribute::NoSelect => "\\Noselect".into(),
+        NameAttribute::Marked => "\\Marked".into(),
+        NameAttribute::Unmarked => "\\Unmarked".into(),
+        NameAttribute::All => "\\All".into(),
+        NameAttribute::Archive => "\\Archive".into(),
+        NameAttribute::Drafts => "\\Drafts".into(),
+        NameAttribute::Flagged => "\\Flagged".into(),
+        NameAttribute::Junk => "\\Junk".into(),
+        NameAttribute::Sent => "\\Sent".into(),
+        NameAttribute::Trash => "\\Trash".into(),
+        NameAttribute::Extension(other) => other.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// Signs in with a password: `AUTHENTICATE PLAIN` when the server advertises `AUTH=PLAIN`,
+/// else the `LOGIN` command, unless the server has disabled it (`LOGINDISABLED`).
+async fn password_login(
+    mut client: Client<Compat<MailStream>>,
+    user: &str,
+    password: &str,
+) -> Result<Session<Compat<MailStream>>> {
+    let capabilities = client.capabilities().await.context("CAPABILITY")?;
+    if capabilities.has_str("AUTH=PLAIN") {
+        let plain = SaslPlain {
+            user: user.to_string(),
+            password: password.to_string(),
+        };
+        client
+            .authenticate("PLAIN", plain)
+            .await
+            .map_err(|(err, _client)| err)
+            .context("PLAIN authenticate")
+    } else if capabilities.has_str("LOGINDISABLED") {
+        bail!("the server offers neither AUTH=PLAIN nor LOGIN (LOGINDISABLED)");
+    } else {
+        client
+            .login(user, password)
             .await
             .map_err(|(err, _client)| err)
-            .context("XOAUTH2 authenticate")?;
+            .context("LOGIN")
+    }
+}
 
-        Ok(session)
+/// SASL PLAIN (RFC 4616): no authorization identity, then the user and password.
+struct SaslPlain {
+    user: String,
+    password: String,
+}
+
+impl Authenticator for SaslPlain {
+    type Response = String;
+
+    fn process(&mut self, _challenge: &[u8]) -> String {
+        format!("\0{}\0{}", self.user, self.password)
     }
 }
 
@@ -89,3 +961,38 @@ impl Authenticator for Xoauth2 {
         )
     }
 }
+
+/// Builds an IMAP sequence-set from UIDs, compressing consecutive runs into ranges
+/// (`1:5,7,10:15`). Input may be unsorted or contain duplicates.
+pub fn build_uid_sequence(uids: &[u32]) -> String {
+    if uids.is_empty() {
+        return "1".to_string();
+    }
+
+    let mut sorted = uids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut parts = Vec::new();
+    let mut start = sorted[0];
+    let mut end = start;
+    for &uid in &sorted[1..] {
+        if uid == end + 1 {
+            end = uid;
+            continue;
+        }
+        parts.push(format_uid_range(start, end));
+        start = uid;
+        end = uid;
+    }
+    parts.push(format_uid_range(start, end));
+    parts.join(",")
+}
+
+fn format_uid_range(start: u32, end: u32) -> String {
+    if start == end {
+        start.to_string()
+    } else {
+        format!("{}:{}", start, end)
+    }
+}
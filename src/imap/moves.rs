@@ -0,0 +1,93 @@
+//! Moving and expunging messages on the server. `UID MOVE` (RFC 6851) moves atomically and
+//! `UID EXPUNGE` (RFC 4315 UIDPLUS) removes just the given messages; both are used when the
+//! server advertises them. Otherwise a move falls back to `UID COPY` plus `\Deleted` and an
+//! expunge, and the expunge to a plain `EXPUNGE` with any other `\Deleted` messages in the
+//! mailbox unflagged around it (RFC 4315 section 1) so they survive it. A UIDPLUS server
+//! answers the copy or move with `COPYUID`, the UIDs the messages got in the destination.
+use std::collections::HashSet;
+
+use async_imap::error::Result as ImapResult;
+use async_imap::extensions::uidplus::CopyUid;
+use futures::StreamExt;
+use tracing::debug;
+
+use super::{ImapSession, build_uid_sequence};
+
+impl ImapSession {
+    /// Moves `uids` of the selected mailbox to `destination`, returning the server's `COPYUID`
+    /// when it sent one.
+    pub async fn move_uids(
+        &mut self,
+        uids: &[u32],
+        destination: &str,
+    ) -> ImapResult<Option<CopyUid>> {
+        let uid_seq = build_uid_sequence(uids);
+        if self.caps().move_ext {
+            return self.uid_mv_mapped(&uid_seq, destination).await;
+        }
+        debug!(destination = %destination, "Server has no MOVE; copying, then expunging");
+        let copied = self.uid_copy_mapped(&uid_seq, destination).await?;
+        self.store_uids(&uid_seq, "+FLAGS.SILENT (\\Deleted)")
+            .await?;
+        self.expunge_uids(uids).await?;
+        Ok(copied)
+    }
+
+    /// Flags `uids` of the selected mailbox `\Deleted` and expunges them, leaving any other
+    /// `\Deleted` messages in place.
+    pub async fn delete_uids(&mut self, uids: &[u32]) -> ImapResult<()> {
+        self.store_uids(&build_uid_sequence(uids), "+FLAGS.SILENT (\\Deleted)")
+            .await?;
+        self.expunge_uids(uids).await
+    }
+
+    /// Expunges `uids` (already flagged `\Deleted`) and nothing else.
+    async fn expunge_uids(&mut self, uids: &[u32]) -> ImapResult<()> {
+        if self.caps().uidplus {
+            let expunged: Vec<_> = self
+                .uid_expunge(build_uid_sequence(uids))
+                .await?
+                .collect()
+                .await;
+            return expunged
+                .into_iter()
+                .find(|r| r.is_err())
+                .unwrap_or(Ok(0))
+                .map(|_| ());
+        }
+
+        let ours: HashSet<u32> = uids.iter().copied().collect();
+        let mut others: Vec<u32> = self
+            .uid_search("DELETED")
+            .await?
+            .into_iter()
+            .filter(|uid| !ours.contains(uid))
+            .collect();
+        others.sort_unstable();
+        let others_seq = build_uid_sequence(&others);
+        if !others.is_empty() {
+            debug!(
+                others = others.len(),
+                "Unflagging other \\Deleted messages around EXPUNGE"
+            );
+            self.store_uids(&others_seq, "-FLAGS.SILENT (\\Deleted)")
+                .await?;
+        }
+        let expunged: Vec<_> = self.expunge().await?.collect().await;
+        let result = expunged.into_iter().find(|r| r.is_err()).unwrap_or(Ok(0));
+        if !others.is_empty() {
+            self.store_uids(&others_seq, "+FLAGS.SILENT (\\Deleted)")
+                .await?;
+        }
+        result.map(|_| ())
+    }
+
+    /// Runs a `UID STORE`, stopping at the first error response.
+    async fn store_uids(&mut self, uid_seq: &str, item: &str) -> ImapResult<()> {
+        let stored: Vec<_> = self.uid_store(uid_seq, item).await?.collect().await;
+        match stored.into_iter().find(|r| r.is_err()) {
+            Some(Err(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Replacing a session that died mid-command. `ImapSession` remembers the mailbox its last
+//! successful SELECT/EXAMINE opened ([`SelectedMailbox`]); [`ImapClient::reconnect`] logs in
+//! again and reopens it the same way, refusing when UIDVALIDITY changed meanwhile (the UIDs the
+//! caller still has to fetch would no longer name the same messages).
+use std::io;
+
+use anyhow::{Context, Result, bail};
+
+use super::{ImapClient, ImapSession};
+use crate::types::Account;
+
+/// The mailbox a session has open and how it was opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectedMailbox {
+    pub name: String,
+    pub uid_validity: Option<u32>,
+    /// Opened with EXAMINE.
+    pub read_only: bool,
+    /// Opened with `SELECT ... (CONDSTORE)`.
+    pub condstore: bool,
+}
+
+/// Whether `error` means the connection itself is gone (reset, closed by the server, or
+/// silent past its deadline) rather than the server refusing a command: the session is dead
+/// and only a new connection can carry on.
+pub fn is_connection_lost(error: &async_imap::error::Error) -> bool {
+    match error {
+        async_imap::error::Error::ConnectionLost => true,
+        async_imap::error::Error::Io(e) => is_lost_io(e),
+        _ => false,
+    }
+}
+
+/// [`is_connection_lost`] for an error somewhere in an `anyhow` chain.
+pub fn is_session_lost(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<async_imap::error::Error>()
+            .is_some_and(is_connection_lost)
+            || cause.downcast_ref::<io::Error>().is_some_and(is_lost_io)
+    })
+}
+
+fn is_lost_io(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::TimedOut
+    )
+}
+
+impl ImapClient {
+    /// Replaces `session` with a new connection that has the same mailbox open. The old
+    /// session is dropped without LOGOUT (its connection is gone).
+    pub async fn reconnect(
+        account: &Account,
+        secret: &str,
+        session: &mut ImapSession,
+    ) -> Result<()> {
+        let Some(selected) = session.selected().cloned() else {
+            bail!("no mailbox was open on the lost connection");
+        };
+        let mut fresh = Self::connect(account, secret).await?;
+        let mailbox = if selected.read_only {
+            fresh.examine(&selected.name).await
+        } else if selected.condstore {
+            fresh.select_condstore(&selected.name).await
+        } else {
+            fresh.select(&selected.name).await
+        }
+        .with_context(|| format!("reopening {} after reconnecting", selected.name))?;
+        if mailbox.uid_validity != selected.uid_validity {
+            bail!(
+                "UIDVALIDITY of {} changed from {:?} to {:?} while reconnecting",
+                selected.name,
+                selected.uid_validity,
+                mailbox.uid_validity
+            );
+        }
+        *session = fresh;
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! Time limits on IMAP round trips, so a server that stops answering fails the folder task
+//! (retried next pass) instead of stalling it forever. Connecting (TCP, TLS, greeting, login),
+//! SELECT/EXAMINE and UID SEARCH each get a deadline; everything else, FETCH streams included,
+//! fails once the server has been silent for `fetch_idle` while a response is awaited.
+use std::io;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use thiserror::Error;
+
+/// Process-wide, set from `AppDefaults` at startup.
+static TIMEOUTS: RwLock<ImapTimeouts> = RwLock::new(ImapTimeouts::DEFAULT);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImapTimeouts {
+    /// TCP connect through authentication (`OTTO_IMAP_CONNECT_TIMEOUT_SECS`, default 30).
+    pub connect: Duration,
+    /// SELECT / EXAMINE (`OTTO_IMAP_SELECT_TIMEOUT_SECS`, default 60).
+    pub select: Duration,
+    /// UID SEARCH (`OTTO_IMAP_SEARCH_TIMEOUT_SECS`, default 120).
+    pub search: Duration,
+    /// Longest silence while a response is awaited, e.g. between two FETCH responses
+    /// (`OTTO_IMAP_FETCH_IDLE_SECS`, default 120).
+    pub fetch_idle: Duration,
+}
+
+impl ImapTimeouts {
+    pub const DEFAULT: Self = Self {
+        connect: Duration::from_secs(30),
+        select: Duration::from_secs(60),
+        search: Duration::from_secs(120),
+        fetch_idle: Duration::from_secs(120),
+    };
+}
+
+impl Default for ImapTimeouts {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Replaces the process-wide limits; connections already open pick them up on their next
+/// command.
+pub fn set_timeouts(timeouts: ImapTimeouts) {
+    *TIMEOUTS.write().unwrap_or_else(|e| e.into_inner()) = timeouts;
+}
+
+pub fn timeouts() -> ImapTimeouts {
+    *TIMEOUTS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// An IMAP operation ran out of time. The connection it happened on is unusable afterwards
+/// (a response may still be on its way), so it is never pooled again.
+#[derive(Debug, Error)]
+#[error("IMAP {operation} timed out after {after:?}")]
+pub struct ImapTimeout {
+    pub operation: &'static str,
+    pub after: Duration,
+}
+
+impl ImapTimeout {
+    /// As the `io::Error` (`TimedOut`) async-imap carries in `Error::Io`.
+    pub(crate) fn into_io(self) -> io::Error {
+        io::Error::new(io::ErrorKind::TimedOut, self)
+    }
+}
+
+/// Whether `error` comes from a timed-out IMAP operation (rather than a server refusal or a
+/// dropped connection): such failures are worth retrying on a fresh connection.
+pub fn is_timeout(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<ImapTimeout>()
+            || cause
+                .downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::TimedOut)
+    })
+}
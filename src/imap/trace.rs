@@ -0,0 +1,287 @@
+//! IMAP protocol trace: a copy of the raw conversation on one connection, written line by line
+//! as `C: ` (client) and `S: ` (server) with a millisecond offset, for server-compatibility bug
+//! reports. AUTHENTICATE payloads and LOGIN passwords are replaced with `<redacted>`; message
+//! content is kept, so a trace is as private as the mail it fetched.
+//!
+//! A trace goes to its own file (`otto trace`), or, with `OTTO_IMAP_TRACE=1`, every connection
+//! of the process writes to one shared [`TraceLog`] that rotates by size, each line tagged with
+//! its connection.
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+
+/// Rotated files kept next to the shared log (`imap-trace.log.1` is the newest).
+pub const TRACE_LOG_KEEP: usize = 3;
+
+/// The shared log every new connection traces to, when enabled.
+static SHARED_LOG: RwLock<Option<Arc<TraceLog>>> = RwLock::new(None);
+/// Numbers connections in the shared log.
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(1);
+
+/// Makes every connection opened from now on trace to `log` (`None` stops tracing new ones).
+pub fn set_shared_log(log: Option<Arc<TraceLog>>) {
+    *SHARED_LOG.write().unwrap_or_else(|e| e.into_inner()) = log;
+}
+
+pub fn shared_log() -> Option<Arc<TraceLog>> {
+    SHARED_LOG.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// A trace file. A rotating log moves to `<path>.1` (shifting older ones up to
+/// [`TRACE_LOG_KEEP`]) once it reaches its size limit, and starts afresh.
+pub struct TraceLog {
+    path: PathBuf,
+    max_bytes: Option<u64>,
+    file: Mutex<LogFile>,
+}
+
+struct LogFile {
+    out: BufWriter<File>,
+    written: u64,
+}
+
+impl TraceLog {
+    /// A new file at `path` (truncated), never rotated.
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("creating IMAP trace {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes: None,
+            file: Mutex::new(LogFile {
+                out: BufWriter::new(file),
+                written: 0,
+            }),
+        })
+    }
+
+    /// Appends to `path`, rotating once it holds `max_bytes`.
+    pub fn rotating(path: &Path, max_bytes: u64) -> Result<Self> {
+        let file = open_append(path)?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes: Some(max_bytes),
+            file: Mutex::new(LogFile {
+                out: BufWriter::new(file),
+                written,
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn max_bytes(&self) -> Option<u64> {
+        self.max_bytes
+    }
+
+    fn write_line(&self, line: &str) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if writeln!(file.out, "{}", line).is_ok() {
+            file.written += line.len() as u64 + 1;
+        }
+        if let Some(max) = self.max_bytes
+            && file.written >= max
+        {
+            self.rotate(&mut file);
+        }
+    }
+
+    fn flush(&self) {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = file.out.flush();
+    }
+
+    /// Shifts `<path>.N` up by one (dropping the oldest), moves the log to `<path>.1` and
+    /// reopens it empty. On failure the log just keeps growing.
+    fn rotate(&self, file: &mut LogFile) {
+        let _ = file.out.flush();
+        let numbered = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        for n in (1..TRACE_LOG_KEEP).rev() {
+            let _ = fs::rename(numbered(n), numbered(n + 1));
+        }
+        if fs::rename(&self.path, numbered(1)).is_err() {
+            return;
+        }
+        if let Ok(fresh) = open_append(&self.path) {
+            file.out = BufWriter::new(fresh);
+            file.written = 0;
+        }
+    }
+}
+
+impl std::fmt::Debug for TraceLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TraceLog")
+            .field("path", &self.path)
+            .field("max_bytes", &self.max_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening IMAP trace log {}", path.display()))
+}
+
+pub struct ProtocolTrace {
+    started: Instant,
+    log: Arc<TraceLog>,
+    /// Connection tag in a shared log (`#3 work`); none in a file of its own.
+    label: Option<String>,
+    state: Mutex<TraceState>,
+}
+
+struct TraceState {
+    client: Vec<u8>,
+    server: Vec<u8>,
+    /// The last command was an AUTHENTICATE without an initial response, so the client's next
+    /// line is the credential.
+    credential_next: bool,
+}
+
+impl ProtocolTrace {
+    /// Traces one connection to a file of its own at `path`.
+    pub fn create(path: &Path) -> Result<Self> {
+        Ok(Self::with_log(Arc::new(TraceLog::create(path)?), None))
+    }
+
+    /// Traces a new connection of `account_id` to the shared `log`, announcing it with a
+    /// timestamped header line.
+    pub fn connection(log: Arc<TraceLog>, account_id: &str, server: &str) -> Self {
+        let label = format!(
+            "#{} {}",
+            NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed),
+            account_id
+        );
+        log.write_line(&format!(
+            "-------- {} connecting to {} at {}",
+            label,
+            server,
+            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+        ));
+        Self::with_log(log, Some(label))
+    }
+
+    fn with_log(log: Arc<TraceLog>, label: Option<String>) -> Self {
+        Self {
+            started: Instant::now(),
+            log,
+            label,
+            state: Mutex::new(TraceState {
+                client: Vec::new(),
+                server: Vec::new(),
+                credential_next: false,
+            }),
+        }
+    }
+
+    /// Bytes written to the server.
+    pub fn client(&self, bytes: &[u8]) {
+        self.record(bytes, true);
+    }
+
+    /// Bytes read from the server.
+    pub fn server(&self, bytes: &[u8]) {
+        self.record(bytes, false);
+    }
+
+    /// Writes out buffered partial lines and flushes the file.
+    pub fn flush(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for client in [true, false] {
+            let rest = std::mem::take(if client {
+                &mut state.client
+            } else {
+                &mut state.server
+            });
+            if !rest.is_empty() {
+                self.write_line(&mut state, &rest, client);
+            }
+        }
+        self.log.flush();
+    }
+
+    fn record(&self, bytes: &[u8], client: bool) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let buffer = if client {
+            &mut state.client
+        } else {
+            &mut state.server
+        };
+        buffer.extend_from_slice(bytes);
+        let mut lines = Vec::new();
+        while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+            lines.push(buffer.drain(..=end).collect::<Vec<u8>>());
+        }
+        for line in lines {
+            self.write_line(&mut state, &line, client);
+        }
+        // A trace is read after a failure, possibly a crash: keep the file current.
+        self.log.flush();
+    }
+
+    fn write_line(&self, state: &mut TraceState, line: &[u8], client: bool) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (prefix, text) = if client {
+            ("C", redact_client_line(line, &mut state.credential_next))
+        } else {
+            ("S", line.to_string())
+        };
+        let elapsed = self.started.elapsed().as_millis();
+        self.log.write_line(&match &self.label {
+            Some(label) => format!("{:>8} {} {}: {}", elapsed, label, prefix, text),
+            None => format!("{:>8} {}: {}", elapsed, prefix, text),
+        });
+    }
+}
+
+impl std::fmt::Debug for ProtocolTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProtocolTrace").finish_non_exhaustive()
+    }
+}
+
+impl Drop for ProtocolTrace {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// Masks credentials in one client line: the initial response of `AUTHENTICATE`, the line
+/// answering its continuation, and the password of `LOGIN`.
+fn redact_client_line(line: &str, credential_next: &mut bool) -> String {
+    if std::mem::take(credential_next) {
+        return if line.is_empty() || line == "*" {
+            line.to_string()
+        } else {
+            "<redacted>".to_string()
+        };
+    }
+    let words: Vec<&str> = line.splitn(4, ' ').collect();
+    let command = words.get(1).map(|c| c.to_ascii_uppercase());
+    match command.as_deref() {
+        Some("AUTHENTICATE") if words.len() >= 4 => {
+            format!("{} {} {} <redacted>", words[0], words[1], words[2])
+        }
+        Some("AUTHENTICATE") => {
+            *credential_next = true;
+            line.to_string()
+        }
+        Some("LOGIN") if words.len() >= 3 => {
+            format!("{} {} {} <redacted>", words[0], words[1], words[2])
+        }
+        _ => line.to_string(),
+    }
+}
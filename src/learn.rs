@@ -0,0 +1,301 @@
+//! Learned rules: the TUI counts the user's manual archive, delete and label actions per sender
+//! (`sender_actions`). Once a sender/action pair reaches `OTTO_LEARN_THRESHOLD` messages
+//! (default 5; 0 turns learning off) it becomes a suggestion: a cleanup rule on
+//! `from:<sender>` with no age that keeps doing it. The TUI asks for approval, `otto
+//! suggestions` lists and settles pending ones, and with `OTTO_LEARN_AUTO=1` the rule is
+//! created without asking. Accepted rules are ordinary cleanup rules, so `otto daemon` runs them
+//! and `otto cleanup` shows, changes or removes them. A rejected pair is not suggested again.
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result, anyhow};
+use tracing::{info, warn};
+
+use crate::address::parse_mailbox;
+use crate::cleanup::{CleanupAction, CleanupRule};
+use crate::storage::MailStore;
+use crate::storage::learning::{SenderAction, SuggestionStatus};
+use crate::storage::ops::MessageOp;
+use crate::tui::{SNOOZE_LABEL, TASK_LABEL};
+use crate::types::now_ts;
+
+/// Manual actions on a sender's mail before a rule is suggested.
+pub const DEFAULT_LEARN_THRESHOLD: u32 = 5;
+
+/// When to suggest rules, from `OTTO_LEARN_*`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LearnConfig {
+    /// Messages per sender and action before a suggestion (`OTTO_LEARN_THRESHOLD`); 0 = off.
+    pub threshold: u32,
+    /// Create suggested rules without asking (`OTTO_LEARN_AUTO`).
+    pub auto_accept: bool,
+}
+
+impl Default for LearnConfig {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_LEARN_THRESHOLD,
+            auto_accept: false,
+        }
+    }
+}
+
+impl LearnConfig {
+    /// Reads `OTTO_LEARN_THRESHOLD` and `OTTO_LEARN_AUTO` through `get`; an unreadable
+    /// threshold is an error.
+    pub fn from_env(get: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let threshold = match get("OTTO_LEARN_THRESHOLD") {
+            Some(raw) => raw.trim().parse::<u32>().map_err(|_| {
+                anyhow!(
+                    "invalid OTTO_LEARN_THRESHOLD {:?}; expected a number of messages",
+                    raw
+                )
+            })?,
+            None => DEFAULT_LEARN_THRESHOLD,
+        };
+        let auto_accept = get("OTTO_LEARN_AUTO")
+            .map(|s| s == "1" || s.eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        Ok(Self {
+            threshold,
+            auto_accept,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.threshold > 0
+    }
+}
+
+/// A manual action worth learning.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LearnedAction {
+    Archive,
+    Delete,
+    Label(String),
+}
+
+impl LearnedAction {
+    /// The learnable part of a message op. Triage's snooze and task labels are bookkeeping,
+    /// not a judgement about the sender, so they are left out.
+    pub fn from_op(op: &MessageOp) -> Option<Self> {
+        match op {
+            MessageOp::Archive => Some(LearnedAction::Archive),
+            MessageOp::Delete => Some(LearnedAction::Delete),
+            MessageOp::AddLabel(label) if label != SNOOZE_LABEL && label != TASK_LABEL => {
+                Some(LearnedAction::Label(label.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Value stored in `sender_actions.action`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LearnedAction::Archive => "archive",
+            LearnedAction::Delete => "delete",
+            LearnedAction::Label(_) => "label",
+        }
+    }
+
+    /// Value stored in `sender_actions.label` (empty unless labeling).
+    fn label(&self) -> &str {
+        match self {
+            LearnedAction::Label(label) => label,
+            _ => "",
+        }
+    }
+
+    fn from_parts(kind: &str, label: &str) -> Option<Self> {
+        match kind {
+            "archive" => Some(LearnedAction::Archive),
+            "delete" => Some(LearnedAction::Delete),
+            "label" if !label.is_empty() => Some(LearnedAction::Label(label.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// A rule the user's actions suggest.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleSuggestion {
+    /// Lowercased bare address.
+    pub sender: String,
+    pub action: LearnedAction,
+    /// Messages the action was applied to so far.
+    pub count: i64,
+}
+
+impl RuleSuggestion {
+    fn from_sender_action(row: &SenderAction) -> Option<Self> {
+        Some(Self {
+            sender: row.sender.clone(),
+            action: LearnedAction::from_parts(&row.action, &row.label)?,
+            count: row.count,
+        })
+    }
+
+    /// Name of the cleanup rule accepting it creates (`auto-archive-news@example.com`).
+    pub fn rule_name(&self) -> String {
+        match &self.action {
+            LearnedAction::Label(label) => format!("auto-label-{}-{}", label, self.sender),
+            action => format!("auto-{}-{}", action.kind(), self.sender),
+        }
+    }
+
+    /// The cleanup rule: every message from the sender, whatever its age.
+    pub fn rule(&self) -> CleanupRule {
+        let (action, label) = match &self.action {
+            LearnedAction::Archive => (CleanupAction::Archive, None),
+            LearnedAction::Delete => (CleanupAction::Delete, None),
+            LearnedAction::Label(label) => (CleanupAction::Label, Some(label.clone())),
+        };
+        CleanupRule {
+            name: self.rule_name(),
+            query: format!("from:{}", self.sender),
+            older_than_days: 0,
+            action,
+            label,
+        }
+    }
+
+    /// The approval question, e.g. "Always archive mail from news@example.com (5 so far)?".
+    pub fn question(&self) -> String {
+        let verb = match &self.action {
+            LearnedAction::Archive => "archive".to_string(),
+            LearnedAction::Delete => "delete".to_string(),
+            LearnedAction::Label(label) => format!("label as {}", label),
+        };
+        format!(
+            "Always {} mail from {} ({} so far)?",
+            verb, self.sender, self.count
+        )
+    }
+}
+
+/// The bare lowercased sender of a cached message, if it has one.
+fn sender_of(from: Option<&str>) -> Option<String> {
+    let from = from?.trim();
+    let addr = parse_mailbox(from).map_or_else(|| from.to_string(), |mailbox| mailbox.addr);
+    let addr = addr.trim().to_ascii_lowercase();
+    addr.contains('@').then_some(addr)
+}
+
+/// Counts a manual `op` on `message_ids` per sender. Returns the suggestions it produced;
+/// with `auto_accept` they are already turned into rules.
+pub async fn observe(
+    db: &dyn MailStore,
+    account_id: &str,
+    op: &MessageOp,
+    message_ids: &[String],
+    config: LearnConfig,
+) -> Result<Vec<RuleSuggestion>> {
+    if !config.enabled() {
+        return Ok(Vec::new());
+    }
+    let Some(action) = LearnedAction::from_op(op) else {
+        return Ok(Vec::new());
+    };
+    let messages = db.load_messages_by_ids(account_id, message_ids).await?;
+    let mut per_sender: BTreeMap<String, i64> = BTreeMap::new();
+    for msg in &messages {
+        if let Some(sender) = sender_of(msg.from.as_deref()) {
+            *per_sender.entry(sender).or_default() += 1;
+        }
+    }
+
+    let mut suggestions = Vec::new();
+    for (sender, count) in per_sender {
+        let reached = db
+            .record_sender_action(
+                account_id,
+                &sender,
+                action.kind(),
+                action.label(),
+                count,
+                i64::from(config.threshold),
+            )
+            .await?;
+        if let Some(count) = reached {
+            suggestions.push(RuleSuggestion {
+                sender,
+                action: action.clone(),
+                count,
+            });
+        }
+    }
+    if config.auto_accept {
+        for suggestion in &suggestions {
+            accept(db, account_id, suggestion).await?;
+        }
+    }
+    Ok(suggestions)
+}
+
+/// Suggestions waiting for approval, most recent first.
+pub async fn pending(db: &dyn MailStore, account_id: &str) -> Result<Vec<RuleSuggestion>> {
+    let rows = db
+        .load_sender_actions(account_id, Some(SuggestionStatus::Pending))
+        .await?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let suggestion = RuleSuggestion::from_sender_action(row);
+            if suggestion.is_none() {
+                warn!(account = %account_id, action = %row.action, "Ignoring unreadable learned action");
+            }
+            suggestion
+        })
+        .collect())
+}
+
+/// Adds the suggested rule to the account (replacing a rule of the same name) and marks the
+/// suggestion accepted.
+pub async fn accept(
+    db: &dyn MailStore,
+    account_id: &str,
+    suggestion: &RuleSuggestion,
+) -> Result<()> {
+    let mut account = db
+        .list_accounts()
+        .await?
+        .into_iter()
+        .find(|a| a.id == account_id)
+        .with_context(|| format!("no account {}", account_id))?;
+    let rule = suggestion.rule();
+    let rules = &mut account.settings.cleanup_rules;
+    match rules.iter_mut().find(|r| r.name == rule.name) {
+        Some(existing) => *existing = rule.clone(),
+        None => rules.push(rule.clone()),
+    }
+    account.updated_at = now_ts();
+    db.save_account(&account).await?;
+    settle(db, account_id, suggestion, SuggestionStatus::Accepted).await?;
+    info!(account = %account_id, rule = %rule.name, query = %rule.query, "Created learned cleanup rule");
+    Ok(())
+}
+
+/// Declines the suggestion; the pair keeps counting but is not suggested again.
+pub async fn reject(
+    db: &dyn MailStore,
+    account_id: &str,
+    suggestion: &RuleSuggestion,
+) -> Result<()> {
+    settle(db, account_id, suggestion, SuggestionStatus::Rejected).await
+}
+
+async fn settle(
+    db: &dyn MailStore,
+    account_id: &str,
+    suggestion: &RuleSuggestion,
+    status: SuggestionStatus,
+) -> Result<()> {
+    db.set_sender_action_status(
+        account_id,
+        &suggestion.sender,
+        suggestion.action.kind(),
+        suggestion.action.label(),
+        status,
+    )
+    .await?;
+    Ok(())
+}
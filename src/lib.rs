@@ -1,12 +1,34 @@
+pub mod address;
 pub mod app;
+pub mod cleanup;
 pub mod cli;
+pub mod collation;
+pub mod compose;
 pub mod config;
+pub mod credentials;
+pub mod daemon;
+pub mod encoded_words;
 pub mod errors;
 pub mod imap;
+pub mod learn;
+pub mod notify;
 pub mod oauth;
 pub mod onboarding;
+pub mod presets;
+pub mod preview;
+pub mod profile;
+pub mod progress;
+pub mod responses;
 pub mod sanitize;
+pub mod share;
+pub mod smart_folders;
+pub mod smtp;
+pub mod status;
 pub mod storage;
 pub mod sync;
+pub mod thread_graph;
+pub mod threading;
+pub mod timefmt;
+pub mod translate;
 pub mod tui;
 pub mod types;
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
-use otto::app;
 use otto::cli::Cli;
+use otto::{app, sync};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -10,7 +10,10 @@ async fn main() -> Result<()> {
     init_tracing();
 
     let cli = Cli::parse();
-    app::run(cli).await
+    let result = app::run(cli).await;
+    // Every command path ends here, so pooled IMAP sessions are always logged out.
+    sync::close_pooled_connections().await;
+    result
 }
 
 fn init_tracing() {
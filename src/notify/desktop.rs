@@ -0,0 +1,31 @@
+//! Desktop popups through `notify-send` (libnotify), so they show up in whatever notification
+//! daemon the session runs.
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+
+use super::{Notification, NotificationChannel};
+
+pub struct Desktop;
+
+#[async_trait]
+impl NotificationChannel for Desktop {
+    async fn send(&self, note: &Notification) -> Result<()> {
+        let (title, body) = (note.title.clone(), note.body.clone());
+        let status = tokio::task::spawn_blocking(move || {
+            Command::new("notify-send")
+                .args(["--app-name=otto", "--category=email.arrived", "--"])
+                .arg(title)
+                .arg(body)
+                .status()
+        })
+        .await
+        .context("notify-send task")?
+        .context("running notify-send")?;
+        if !status.success() {
+            bail!("notify-send failed ({})", status);
+        }
+        Ok(())
+    }
+}
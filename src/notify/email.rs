@@ -0,0 +1,42 @@
+//! Email-to-self: the notification as a short mail sent through the account's own SMTP
+//! submission (`otto send`'s client), so it reaches phones that only run a mail app.
+use anyhow::Result;
+use async_trait::async_trait;
+
+use super::{Notification, NotificationChannel};
+use crate::compose::{Draft, build_message};
+use crate::smtp::{SmtpEndpoint, send_from_account};
+use crate::types::Account;
+
+pub struct EmailToSelf {
+    account: Account,
+    to: String,
+    smtp: SmtpEndpoint,
+}
+
+impl EmailToSelf {
+    pub fn new(account: Account, to: String, smtp: SmtpEndpoint) -> Self {
+        Self { account, to, smtp }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for EmailToSelf {
+    async fn send(&self, note: &Notification) -> Result<()> {
+        let message = build_message(&Draft {
+            from: self.account.email.clone(),
+            to: vec![self.to.clone()],
+            cc: Vec::new(),
+            subject: note.title.clone(),
+            body_markdown: note.body.replace('\n', "  \n"),
+            attachments: Vec::new(),
+        })?;
+        send_from_account(
+            &self.smtp,
+            &self.account,
+            std::slice::from_ref(&self.to),
+            &message,
+        )
+        .await
+    }
+}
@@ -0,0 +1,64 @@
+//! HTTP channels: a JSON webhook and an ntfy topic (`POST <server>/<topic>`, the body as the
+//! message and the title in the `Title` header).
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use super::{HTTP_TIMEOUT, Notification, NotificationChannel};
+
+pub struct Webhook {
+    url: String,
+}
+
+impl Webhook {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for Webhook {
+    async fn send(&self, note: &Notification) -> Result<()> {
+        reqwest::Client::new()
+            .post(&self.url)
+            .timeout(HTTP_TIMEOUT)
+            .json(&note.to_json())
+            .send()
+            .await
+            .with_context(|| format!("posting to {}", self.url))?
+            .error_for_status()
+            .with_context(|| format!("webhook {}", self.url))?;
+        Ok(())
+    }
+}
+
+pub struct Ntfy {
+    url: String,
+}
+
+impl Ntfy {
+    pub fn new(server: &str, topic: &str) -> Self {
+        Self {
+            url: format!("{}/{}", server.trim_end_matches('/'), topic),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for Ntfy {
+    async fn send(&self, note: &Notification) -> Result<()> {
+        reqwest::Client::new()
+            .post(&self.url)
+            .timeout(HTTP_TIMEOUT)
+            .header("Title", &note.title)
+            .header("Tags", "email")
+            .body(note.body.clone())
+            .send()
+            .await
+            .with_context(|| format!("posting to {}", self.url))?
+            .error_for_status()
+            .with_context(|| format!("ntfy {}", self.url))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! New-mail notifications. Each account keeps a list of rules: which new mail (a smart-folder
+//! query, or by default unread INBOX mail) goes to which channel. Channels implement
+//! [`NotificationChannel`]: a desktop popup (`notify-send`), a webhook POST, an ntfy.sh topic,
+//! or an email to yourself over the account's SMTP. `otto daemon` dispatches after each pass,
+//! at most one notification per rule and pass. Each rule claims the messages it looks at
+//! through the store's processed-message cursor, so a message is announced once per rule even
+//! with several daemons on one store, and one a channel failed to deliver is retried.
+mod desktop;
+mod email;
+mod http;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::address::{friendly_from, parse_mailbox};
+use crate::smart_folders::SmartQuery;
+use crate::smtp::SmtpEndpoint;
+use crate::storage::MailStore;
+use crate::timefmt::DisplayTz;
+use crate::types::{Account, FolderRole, MessageRecord};
+
+pub use desktop::Desktop;
+pub use email::EmailToSelf;
+pub use http::{Ntfy, Webhook};
+
+/// How far back of a pass `otto daemon` still picks up unannounced messages, so ones whose
+/// channel failed (or that were cached while no daemon ran) go out on a later pass.
+pub const RETRY_WINDOW_SECS: i64 = 24 * 60 * 60;
+
+/// Messages claimed per round trip while collecting a rule's new mail.
+const CLAIM_BATCH: usize = 500;
+
+/// ntfy server used when a rule names none.
+pub const DEFAULT_NTFY_SERVER: &str = "https://ntfy.sh";
+/// Messages listed in a notification body; the rest are summed up.
+const MAX_LISTED: usize = 5;
+/// Webhook and ntfy requests give up after this long.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Where a rule's notifications go (stored as JSON in the account's rules).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelConfig {
+    /// `notify-send` on the machine running otto.
+    Desktop,
+    /// JSON POST of the notification and its messages.
+    Webhook { url: String },
+    /// Plain-text POST to an ntfy topic.
+    Ntfy {
+        topic: String,
+        #[serde(default = "default_ntfy_server")]
+        server: String,
+    },
+    /// A summary mail sent through the account's SMTP server (OAuth accounts only).
+    Email {
+        /// Recipient; the account's own address when unset.
+        to: Option<String>,
+    },
+}
+
+fn default_ntfy_server() -> String {
+    DEFAULT_NTFY_SERVER.to_string()
+}
+
+impl ChannelConfig {
+    pub fn describe(&self) -> String {
+        match self {
+            ChannelConfig::Desktop => "desktop".to_string(),
+            ChannelConfig::Webhook { url } => format!("webhook {}", url),
+            ChannelConfig::Ntfy { topic, server } => {
+                format!("ntfy {}/{}", server.trim_end_matches('/'), topic)
+            }
+            ChannelConfig::Email { to: Some(to) } => format!("email to {}", to),
+            ChannelConfig::Email { to: None } => "email to self".to_string(),
+        }
+    }
+}
+
+/// A named rule (stored on the account): new mail matching `query` goes to `channel`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NotifyRule {
+    pub name: String,
+    /// Smart-folder query (see `crate::smart_folders`); unset means new unread INBOX mail.
+    pub query: Option<String>,
+    pub channel: ChannelConfig,
+}
+
+/// One message as listed in a notification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NoteMessage {
+    pub id: String,
+    pub folder: String,
+    pub from: String,
+    pub subject: String,
+}
+
+/// What a channel delivers.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Notification {
+    pub account: String,
+    pub rule: String,
+    pub title: String,
+    /// One `from — subject` line per listed message.
+    pub body: String,
+    pub messages: Vec<NoteMessage>,
+}
+
+impl NoteMessage {
+    pub fn from_record(msg: &MessageRecord) -> Self {
+        Self {
+            id: msg.id.clone(),
+            folder: msg.folder.clone(),
+            from: friendly_from(msg.from_name.as_deref(), msg.from.as_deref()),
+            subject: msg
+                .subject
+                .clone()
+                .unwrap_or_else(|| "(no subject)".to_string()),
+        }
+    }
+}
+
+impl Notification {
+    pub fn new(account: &str, rule: &str, messages: Vec<NoteMessage>) -> Self {
+        let mut lines: Vec<String> = messages
+            .iter()
+            .take(MAX_LISTED)
+            .map(|m| format!("{} — {}", m.from, m.subject))
+            .collect();
+        if messages.len() > MAX_LISTED {
+            lines.push(format!("and {} more", messages.len() - MAX_LISTED));
+        }
+        Self {
+            account: account.to_string(),
+            rule: rule.to_string(),
+            title: format!(
+                "{} new message(s) for {} ({})",
+                messages.len(),
+                account,
+                rule
+            ),
+            body: lines.join("\n"),
+            messages,
+        }
+    }
+
+    /// The webhook payload.
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "account": self.account,
+            "rule": self.rule,
+            "title": self.title,
+            "body": self.body,
+            "messages": self
+                .messages
+                .iter()
+                .map(|m| json!({
+                    "id": m.id,
+                    "folder": m.folder,
+                    "from": m.from,
+                    "subject": m.subject,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// A way of delivering notifications.
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    async fn send(&self, note: &Notification) -> Result<()>;
+}
+
+/// The channel `config` describes, for `account`'s rules.
+pub fn channel(
+    config: &ChannelConfig,
+    account: &Account,
+    smtp: &SmtpEndpoint,
+) -> Box<dyn NotificationChannel> {
+    match config {
+        ChannelConfig::Desktop => Box::new(Desktop),
+        ChannelConfig::Webhook { url } => Box::new(Webhook::new(url)),
+        ChannelConfig::Ntfy { topic, server } => Box::new(Ntfy::new(server, topic)),
+        ChannelConfig::Email { to } => Box::new(EmailToSelf::new(
+            account.clone(),
+            to.clone().unwrap_or_else(|| account.email.clone()),
+            smtp.clone(),
+        )),
+    }
+}
+
+/// Folders whose new mail never notifies, and the Sent folder (mail from yourself is skipped).
+#[derive(Clone, Debug)]
+pub struct Quiet {
+    pub sent: String,
+    /// Drafts, Trash and Spam.
+    pub ignored: Vec<String>,
+}
+
+/// What each rule would send for `new` (messages just cached): unread mail not from `me`,
+/// outside `quiet` folders, matching the rule's query (default: in INBOX, or labeled `\Inbox`).
+/// Rules without matches, or whose query no longer parses, are left out.
+pub fn select<'a, 'r>(
+    rules: &'r [NotifyRule],
+    new: &'a [MessageRecord],
+    me: &str,
+    quiet: &Quiet,
+    now: i64,
+    tz: DisplayTz,
+) -> Vec<(&'r NotifyRule, Vec<&'a MessageRecord>)> {
+    let candidates: Vec<&MessageRecord> = new
+        .iter()
+        .filter(|msg| !msg.flags.iter().any(|f| f.eq_ignore_ascii_case("\\Seen")))
+        .filter(|msg| msg.folder != quiet.sent && !quiet.ignored.contains(&msg.folder))
+        .filter(|msg| {
+            !msg.from.as_deref().is_some_and(|from| {
+                parse_mailbox(from)
+                    .map(|mailbox| mailbox.addr)
+                    .unwrap_or_else(|| from.to_string())
+                    .eq_ignore_ascii_case(me)
+            })
+        })
+        .collect();
+    let mut out = Vec::new();
+    for rule in rules {
+        let query = match rule.query.as_deref().map(SmartQuery::parse).transpose() {
+            Ok(query) => query,
+            Err(e) => {
+                warn!(rule = %rule.name, error = %e, "Skipping notification rule with an invalid query");
+                continue;
+            }
+        };
+        let matched: Vec<&MessageRecord> = candidates
+            .iter()
+            .copied()
+            .filter(|msg| match &query {
+                Some(query) => query.matches(msg, now, tz),
+                None => {
+                    msg.folder.eq_ignore_ascii_case("INBOX")
+                        || msg.labels.iter().any(|l| l.eq_ignore_ascii_case("\\Inbox"))
+                }
+            })
+            .collect();
+        if !matched.is_empty() {
+            out.push((rule, matched));
+        }
+    }
+    out
+}
+
+/// Notifies about the account's messages first cached since `since` (unix secs) that no
+/// earlier dispatch handled; returns how many notifications went out. Each rule claims them as
+/// consumer `notify:<rule>` (`MailStore::claim_unprocessed_messages`). A failing channel is
+/// logged, hands its messages back for the next dispatch and does not stop the other rules.
+pub async fn dispatch(
+    db: &dyn MailStore,
+    account: &Account,
+    since: i64,
+    now: i64,
+    tz: DisplayTz,
+    smtp: &SmtpEndpoint,
+) -> Result<usize> {
+    let rules = &account.settings.notify_rules;
+    if rules.is_empty() {
+        return Ok(0);
+    }
+    let mut ignored = Vec::new();
+    for role in [FolderRole::Drafts, FolderRole::Trash, FolderRole::Junk] {
+        ignored.push(db.role_folder(&account.id, role).await?);
+    }
+    let quiet = Quiet {
+        sent: db.role_folder(&account.id, FolderRole::Sent).await?,
+        ignored,
+    };
+    let mut sent = 0;
+    for rule in rules {
+        let consumer = format!("notify:{}", rule.name);
+        let mut new = Vec::new();
+        loop {
+            let batch = db
+                .claim_unprocessed_messages(&consumer, &account.id, since, CLAIM_BATCH)
+                .await?;
+            let done = batch.len() < CLAIM_BATCH;
+            new.extend(batch);
+            if done {
+                break;
+            }
+        }
+        let selected = select(
+            std::slice::from_ref(rule),
+            &new,
+            &account.email,
+            &quiet,
+            now,
+            tz,
+        );
+        let Some((_, messages)) = selected.into_iter().next() else {
+            continue;
+        };
+        let note = Notification::new(
+            &account.email,
+            &rule.name,
+            messages
+                .iter()
+                .map(|msg| NoteMessage::from_record(msg))
+                .collect(),
+        );
+        match channel(&rule.channel, account, smtp).send(&note).await {
+            Ok(()) => {
+                sent += 1;
+                info!(account = %account.id, rule = %rule.name, count = messages.len(), "Sent notification");
+            }
+            Err(e) => {
+                warn!(account = %account.id, rule = %rule.name, error = %e, "Notification failed");
+                let ids: Vec<String> = messages.iter().map(|msg| msg.id.clone()).collect();
+                db.release_processed_messages(&consumer, &ids).await?;
+            }
+        }
+    }
+    Ok(sent)
+}
@@ -1,12 +1,22 @@
+//! OAuth 2.0 authorization code flow (PKCE, loopback redirect) for the providers signing in to
+//! IMAP with XOAUTH2: Google for Gmail, the Microsoft identity platform for Microsoft 365 and
+//! Outlook.com. Refresh tokens live in the OS keyring, one service per provider.
 use crate::errors::{AppError, AppResult};
+use crate::profile;
+use crate::types::Provider;
+use base64::Engine;
 use chrono::{DateTime, Duration, Utc};
-use oauth2::basic::BasicClient;
+use oauth2::basic::{
+    BasicErrorResponse, BasicRevocationErrorResponse, BasicTokenIntrospectionResponse,
+    BasicTokenType,
+};
 use oauth2::reqwest::async_http_client;
 use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge,
-    PkceCodeVerifier, RedirectUrl, RefreshToken, Scope, TokenResponse, TokenUrl,
+    AuthUrl, AuthorizationCode, Client, ClientId, ClientSecret, CsrfToken, ExtraTokenFields,
+    PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, RefreshToken, Scope, StandardRevocableToken,
+    StandardTokenResponse, TokenResponse, TokenUrl,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
 use std::io::Write;
@@ -14,15 +24,21 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
 use tracing::{info, warn};
 
-const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
-const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
-const SERVICE_NAME: &str = "otto-google-oauth";
+const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const GOOGLE_SERVICE_NAME: &str = "otto-google-oauth";
+const MICROSOFT_LOGIN_URL: &str = "https://login.microsoftonline.com";
+const MICROSOFT_SERVICE_NAME: &str = "otto-microsoft-oauth";
+/// Tenant used without `MICROSOFT_TENANT`: work, school and personal accounts alike.
+const MICROSOFT_DEFAULT_TENANT: &str = "common";
 
 #[derive(Clone, Debug)]
 pub struct TokenBundle {
     pub access_token: String,
     pub expires_at: Option<DateTime<Utc>>,
     pub refresh_token: Option<String>,
+    /// OpenID Connect ID token, returned when the `openid` scope was requested.
+    pub id_token: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,13 +46,70 @@ struct UserInfo {
     email: String,
 }
 
-pub async fn authorize_with_scopes(scopes: &[Scope], token_key: &str) -> AppResult<TokenBundle> {
-    let creds = load_credentials()?;
-    let token_store = TokenStore::from_key(token_key);
+/// The `id_token` of OpenID Connect token responses, next to the standard fields.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct IdTokenFields {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id_token: Option<String>,
+}
+
+impl ExtraTokenFields for IdTokenFields {}
+
+type TokenResult = StandardTokenResponse<IdTokenFields, BasicTokenType>;
+
+type OAuthClient = Client<
+    BasicErrorResponse,
+    TokenResult,
+    BasicTokenType,
+    BasicTokenIntrospectionResponse,
+    StandardRevocableToken,
+    BasicRevocationErrorResponse,
+>;
+
+/// Scopes of an access token for the provider's mail servers: IMAP, and SMTP where one token
+/// covers both (Gmail). Microsoft only hands out refresh tokens with `offline_access`.
+pub fn mail_scopes(provider: &Provider) -> Vec<Scope> {
+    match provider {
+        Provider::GmailImap => vec![Scope::new("https://mail.google.com/".into())],
+        Provider::OutlookImap => vec![
+            Scope::new("https://outlook.office.com/IMAP.AccessAsUser.All".into()),
+            Scope::new("offline_access".into()),
+        ],
+        Provider::Imap => Vec::new(),
+    }
+}
+
+/// `mail_scopes` plus what onboarding needs to learn the account's address.
+pub fn onboarding_scopes(provider: &Provider) -> Vec<Scope> {
+    let mut scopes = mail_scopes(provider);
+    match provider {
+        Provider::GmailImap => scopes.push(Scope::new(
+            "https://www.googleapis.com/auth/userinfo.email".into(),
+        )),
+        Provider::OutlookImap => {
+            scopes.push(Scope::new("openid".into()));
+            scopes.push(Scope::new("email".into()));
+        }
+        Provider::Imap => {}
+    }
+    scopes
+}
+
+pub async fn authorize_with_scopes(
+    provider: &Provider,
+    scopes: &[Scope],
+    token_key: &str,
+) -> AppResult<TokenBundle> {
+    let creds = load_credentials(provider)?;
+    let token_store = TokenStore::new(provider, token_key)?;
 
     if let Some(refresh) = token_store.load()? {
-        if let Some(bundle) =
-            try_refresh(&build_client(&creds, &pick_redirect_uri()?)?, refresh).await?
+        if let Some(bundle) = try_refresh(
+            &build_client(&creds, &pick_redirect_uri()?)?,
+            &token_store,
+            refresh,
+        )
+        .await?
         {
             return Ok(bundle);
         }
@@ -56,8 +129,8 @@ pub async fn authorize_with_scopes(scopes: &[Scope], token_key: &str) -> AppResu
     let redirect = build_redirect_url(&base_redirect, local_port)?;
     let client = build_client(&creds, &redirect)?;
 
-    let (auth_url, verifier, csrf) = build_auth_url(&client, scopes)?;
-    info!(account = %token_key, redirect = %redirect, "Opening browser for Google OAuth consent");
+    let (auth_url, verifier, csrf) = build_auth_url(provider, &client, scopes)?;
+    info!(account = %token_key, redirect = %redirect, provider = ?provider, "Opening browser for OAuth consent");
     open_in_browser(&auth_url);
 
     let code = listen_for_code(listener).await?;
@@ -77,16 +150,80 @@ pub async fn authorize_with_scopes(scopes: &[Scope], token_key: &str) -> AppResu
         token_store.save(ref_token)?;
     }
 
-    Ok(TokenBundle {
+    Ok(bundle_from(&token_res, refresh))
+}
+
+fn bundle_from(token_res: &TokenResult, refresh_token: Option<String>) -> TokenBundle {
+    TokenBundle {
         access_token: token_res.access_token().secret().to_string(),
         expires_at: token_res
             .expires_in()
             .map(|d| Utc::now() + Duration::from_std(d).unwrap_or_else(|_| Duration::seconds(0))),
-        refresh_token: refresh,
-    })
+        refresh_token,
+        id_token: token_res.extra_fields().id_token.clone(),
+    }
+}
+
+/// Refreshes the account's stored token without ever falling back to browser consent; `None`
+/// when there is no refresh token or the provider rejects it. For unattended callers like the
+/// daemon.
+pub async fn refresh_stored(
+    provider: &Provider,
+    token_key: &str,
+) -> AppResult<Option<TokenBundle>> {
+    let creds = load_credentials(provider)?;
+    let token_store = TokenStore::new(provider, token_key)?;
+    let Some(refresh) = token_store.load()? else {
+        return Ok(None);
+    };
+    try_refresh(
+        &build_client(&creds, &pick_redirect_uri()?)?,
+        &token_store,
+        refresh,
+    )
+    .await
 }
 
-pub async fn fetch_user_email(access_token: &str) -> AppResult<String> {
+/// The address the token was issued to: Google's userinfo endpoint, or for Microsoft the
+/// `email` (else `preferred_username`) claim of the ID token.
+pub async fn fetch_user_email(provider: &Provider, token: &TokenBundle) -> AppResult<String> {
+    match provider {
+        Provider::GmailImap => fetch_google_email(&token.access_token).await,
+        Provider::OutlookImap => {
+            let id_token = token.id_token.as_deref().ok_or_else(|| {
+                AppError::Unexpected("Microsoft token response has no id_token".into())
+            })?;
+            email_from_id_token(id_token)
+        }
+        Provider::Imap => Err(no_oauth()),
+    }
+}
+
+/// The `email` or `preferred_username` claim of a JWT ID token. The token came straight from
+/// the token endpoint over TLS, so its signature isn't checked.
+pub fn email_from_id_token(id_token: &str) -> AppResult<String> {
+    #[derive(Deserialize)]
+    struct Claims {
+        email: Option<String>,
+        preferred_username: Option<String>,
+    }
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| AppError::Unexpected("malformed id_token".into()))?;
+    let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|e| AppError::Unexpected(format!("decode id_token: {e}")))?;
+    let claims: Claims = serde_json::from_slice(&json)
+        .map_err(|e| AppError::Unexpected(format!("parse id_token: {e}")))?;
+    claims
+        .email
+        .or(claims.preferred_username)
+        .filter(|e| e.contains('@'))
+        .ok_or_else(|| AppError::Unexpected("id_token carries no email address".into()))
+}
+
+async fn fetch_google_email(access_token: &str) -> AppResult<String> {
     let client = reqwest::Client::new();
     let res = client
         .get("https://www.googleapis.com/oauth2/v2/userinfo")
@@ -107,26 +244,60 @@ pub async fn fetch_user_email(access_token: &str) -> AppResult<String> {
     Ok(parsed.email)
 }
 
-fn load_credentials() -> AppResult<InstalledCreds> {
-    let id = env::var("GOOGLE_CLIENT_ID")
-        .map_err(|_| AppError::Config("GOOGLE_CLIENT_ID missing".into()))?;
-    let secret = env::var("GOOGLE_CLIENT_SECRET")
-        .map_err(|_| AppError::Config("GOOGLE_CLIENT_SECRET missing".into()))?;
-    Ok(InstalledCreds {
-        client_id: id,
-        client_secret: secret,
-        redirect_uris: vec![
-            "http://localhost:8000".into(),
-            "http://127.0.0.1:8000".into(),
-            "urn:ietf:wg:oauth:2.0:oob".into(),
-        ],
-    })
+/// Generic IMAP accounts have no OAuth client; they sign in with a password.
+fn no_oauth() -> AppError {
+    AppError::Config("generic IMAP accounts sign in with a password, not OAuth".into())
+}
+
+fn load_credentials(provider: &Provider) -> AppResult<InstalledCreds> {
+    match provider {
+        Provider::Imap => Err(no_oauth()),
+        Provider::GmailImap => {
+            let id = env::var("GOOGLE_CLIENT_ID")
+                .map_err(|_| AppError::Config("GOOGLE_CLIENT_ID missing".into()))?;
+            let secret = env::var("GOOGLE_CLIENT_SECRET")
+                .map_err(|_| AppError::Config("GOOGLE_CLIENT_SECRET missing".into()))?;
+            Ok(InstalledCreds {
+                client_id: id,
+                client_secret: Some(secret),
+                auth_url: GOOGLE_AUTH_URL.to_string(),
+                token_url: GOOGLE_TOKEN_URL.to_string(),
+                redirect_uris: vec![
+                    "http://localhost:8000".into(),
+                    "http://127.0.0.1:8000".into(),
+                    "urn:ietf:wg:oauth:2.0:oob".into(),
+                ],
+            })
+        }
+        Provider::OutlookImap => {
+            let id = env::var("MICROSOFT_CLIENT_ID")
+                .map_err(|_| AppError::Config("MICROSOFT_CLIENT_ID missing".into()))?;
+            // Public client registrations (the usual for desktop apps) have no secret.
+            let secret = env::var("MICROSOFT_CLIENT_SECRET")
+                .ok()
+                .filter(|s| !s.trim().is_empty());
+            let tenant = env::var("MICROSOFT_TENANT")
+                .ok()
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| MICROSOFT_DEFAULT_TENANT.to_string());
+            Ok(InstalledCreds {
+                client_id: id,
+                client_secret: secret,
+                auth_url: format!("{MICROSOFT_LOGIN_URL}/{tenant}/oauth2/v2.0/authorize"),
+                token_url: format!("{MICROSOFT_LOGIN_URL}/{tenant}/oauth2/v2.0/token"),
+                redirect_uris: vec!["http://localhost".into()],
+            })
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 struct InstalledCreds {
     client_id: String,
-    client_secret: String,
+    client_secret: Option<String>,
+    auth_url: String,
+    token_url: String,
     #[allow(dead_code)]
     redirect_uris: Vec<String>,
 }
@@ -143,12 +314,15 @@ fn build_redirect_url(base: &str, port: u16) -> AppResult<String> {
     Ok(url.to_string())
 }
 
-fn build_client(creds: &InstalledCreds, redirect: &str) -> AppResult<BasicClient> {
-    let client = BasicClient::new(
+fn build_client(creds: &InstalledCreds, redirect: &str) -> AppResult<OAuthClient> {
+    let client = OAuthClient::new(
         ClientId::new(creds.client_id.clone()),
-        Some(ClientSecret::new(creds.client_secret.clone())),
-        AuthUrl::new(AUTH_URL.to_string()).unwrap(),
-        Some(TokenUrl::new(TOKEN_URL.to_string()).unwrap()),
+        creds.client_secret.clone().map(ClientSecret::new),
+        AuthUrl::new(creds.auth_url.clone())
+            .map_err(|e| AppError::Config(format!("invalid auth url {}: {e}", creds.auth_url)))?,
+        Some(TokenUrl::new(creds.token_url.clone()).map_err(|e| {
+            AppError::Config(format!("invalid token url {}: {e}", creds.token_url))
+        })?),
     )
     .set_redirect_uri(
         RedirectUrl::new(redirect.to_string())
@@ -160,15 +334,22 @@ fn build_client(creds: &InstalledCreds, redirect: &str) -> AppResult<BasicClient
 }
 
 fn build_auth_url(
-    client: &BasicClient,
+    provider: &Provider,
+    client: &OAuthClient,
     scopes: &[Scope],
 ) -> AppResult<(String, PkceCodeVerifier, CsrfToken)> {
     let (challenge, verifier) = PkceCodeChallenge::new_random_sha256();
     let mut req = client
         .authorize_url(CsrfToken::new_random)
-        .add_extra_param("access_type", "offline")
-        .add_extra_param("prompt", "consent")
         .set_pkce_challenge(challenge);
+    req = match provider {
+        // Google only returns a refresh token for offline access, and only on consent.
+        Provider::GmailImap => req
+            .add_extra_param("access_type", "offline")
+            .add_extra_param("prompt", "consent"),
+        Provider::OutlookImap => req.add_extra_param("prompt", "select_account"),
+        Provider::Imap => return Err(no_oauth()),
+    };
     for scope in scopes {
         req = req.add_scope(scope.clone());
     }
@@ -176,20 +357,27 @@ fn build_auth_url(
     Ok((url.to_string(), verifier, csrf))
 }
 
-async fn try_refresh(client: &BasicClient, token: StoredToken) -> AppResult<Option<TokenBundle>> {
+/// Microsoft rotates refresh tokens: a new one in the response replaces the stored one.
+async fn try_refresh(
+    client: &OAuthClient,
+    store: &TokenStore,
+    token: StoredToken,
+) -> AppResult<Option<TokenBundle>> {
     let refresh = RefreshToken::new(token.refresh_token);
     let res = client
         .exchange_refresh_token(&refresh)
         .request_async(async_http_client)
         .await;
     match res {
-        Ok(token_res) => Ok(Some(TokenBundle {
-            access_token: token_res.access_token().secret().to_string(),
-            expires_at: token_res.expires_in().map(|d| {
-                Utc::now() + Duration::from_std(d).unwrap_or_else(|_| Duration::seconds(0))
-            }),
-            refresh_token: None,
-        })),
+        Ok(token_res) => {
+            let rotated = token_res.refresh_token().map(|r| r.secret().to_string());
+            if let Some(rotated) = &rotated
+                && rotated != refresh.secret()
+            {
+                store.save(rotated)?;
+            }
+            Ok(Some(bundle_from(&token_res, rotated)))
+        }
         Err(err) => {
             warn!("Refresh token invalid or expired: {err}");
             Ok(None)
@@ -264,14 +452,20 @@ struct StoredToken {
 
 #[derive(Clone)]
 struct TokenStore {
+    service: &'static str,
     account_id: String,
 }
 
 impl TokenStore {
-    fn from_key(key: &str) -> Self {
-        Self {
+    fn new(provider: &Provider, key: &str) -> AppResult<Self> {
+        Ok(Self {
+            service: match provider {
+                Provider::GmailImap => GOOGLE_SERVICE_NAME,
+                Provider::OutlookImap => MICROSOFT_SERVICE_NAME,
+                Provider::Imap => return Err(no_oauth()),
+            },
             account_id: key.to_string(),
-        }
+        })
     }
 
     fn load(&self) -> AppResult<Option<StoredToken>> {
@@ -299,14 +493,16 @@ impl TokenStore {
     }
 
     fn delete(&self) -> AppResult<()> {
-        if let Ok(entry) = keyring::Entry::new(SERVICE_NAME, &self.account_id) {
+        if let Ok(entry) =
+            keyring::Entry::new(&profile::keyring_service(self.service), &self.account_id)
+        {
             let _ = entry.delete_password();
         }
         Ok(())
     }
 
     fn load_keyring(&self) -> Result<Option<StoredToken>, String> {
-        let entry = keyring::Entry::new(SERVICE_NAME, &self.account_id)
+        let entry = keyring::Entry::new(&profile::keyring_service(self.service), &self.account_id)
             .map_err(|e| format!("keyring entry error: {e}"))?;
         match entry.get_password() {
             Ok(pwd) => serde_json::from_str(&pwd)
@@ -318,7 +514,7 @@ impl TokenStore {
     }
 
     fn save_keyring(&self, serialized: &str) -> Result<(), String> {
-        let entry = keyring::Entry::new(SERVICE_NAME, &self.account_id)
+        let entry = keyring::Entry::new(&profile::keyring_service(self.service), &self.account_id)
             .map_err(|e| format!("keyring entry error: {e}"))?;
         entry
             .set_password(serialized)
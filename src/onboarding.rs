@@ -1,33 +1,331 @@
 use crate::config::AppDefaults;
-use crate::oauth::{TokenBundle, authorize_with_scopes, fetch_user_email};
-use crate::types::{Account, AccountSettings, Provider, now_ts};
-use anyhow::Result;
-use oauth2::Scope;
-use tracing::info;
-
-/// Run OAuth flow, fetch the user's email, and return an Account + token bundle.
-pub async fn onboard_account(defaults: &AppDefaults) -> Result<(Account, TokenBundle)> {
-    let scopes = vec![
-        Scope::new("https://mail.google.com/".into()),
-        Scope::new("https://www.googleapis.com/auth/userinfo.email".into()),
-    ];
-    let token = authorize_with_scopes(&scopes, "default").await?;
-    let email = fetch_user_email(&token.access_token).await?;
+use crate::credentials;
+use crate::imap::ImapClient;
+use crate::oauth::{TokenBundle, authorize_with_scopes, fetch_user_email, onboarding_scopes};
+use crate::presets::{self, PresetAuth, ProviderPreset};
+use crate::types::{
+    Account, AccountSettings, BodyFetch, Credential, FolderPolicy, FolderRole, ImapEndpoint,
+    MailboxInfo, MailboxNamespace, Provider, TlsMode, now_ts, special_use_folder,
+};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+/// Run OAuth flow, fetch the user's email, and return an Account + token bundle, plus the
+/// server's folder list (empty when `LIST` failed and the configured folders were kept).
+/// The provider's own IMAP server is used unless `OTTO_IMAP_HOST` names another one.
+pub async fn onboard_account(
+    defaults: &AppDefaults,
+    provider: &Provider,
+) -> Result<(Account, TokenBundle, Vec<MailboxInfo>)> {
+    let token = authorize_with_scopes(provider, &onboarding_scopes(provider), "default").await?;
+    let email = fetch_user_email(provider, &token).await?;
+    let imap = if defaults.imap == ImapEndpoint::default() {
+        provider.imap_endpoint()
+    } else {
+        defaults.imap.clone()
+    };
+    let mut account = new_account(defaults, provider.clone(), email, imap, Credential::OAuth);
+    if let Some(preset) = presets::for_provider(provider) {
+        account.settings.folders = preset.rename_defaults(&defaults.folders);
+    }
+    let discovered = discover_and_select(
+        defaults,
+        &mut account,
+        &token.access_token,
+        presets::for_provider(provider),
+    )
+    .await;
+    info!(account = %account.id, folders = ?account.settings.folders, "Onboarded account via OAuth");
+    Ok((account, token, discovered))
+}
+
+/// A password account to add without OAuth, from `otto accounts add` or one `[[account]]`
+/// entry of an `otto accounts import` file. Without `password_cmd`, the password is the one
+/// stored in the keyring (`otto accounts password --stdin`). A `preset` (`crate::presets`)
+/// supplies the server and special folder names; `host` and `port` still override it.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PasswordAccountSpec {
+    pub email: String,
+    pub host: Option<String>,
+    pub preset: Option<String>,
+    /// Defaults to the preset's port, else the TLS mode's usual port.
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: TlsMode,
+    pub password_cmd: Option<String>,
+}
+
+impl PasswordAccountSpec {
+    /// The app-password preset named by `preset`; OAuth presets are refused, since their
+    /// accounts are added with `otto --add-account --provider`.
+    pub fn preset(&self) -> Result<Option<&'static ProviderPreset>> {
+        let Some(name) = &self.preset else {
+            return Ok(None);
+        };
+        let preset = presets::find(name)?;
+        if let PresetAuth::OAuth(_) = preset.auth {
+            bail!(
+                "{}: {} signs in with OAuth; use `otto --add-account --provider {}`",
+                self.email,
+                preset.label,
+                preset.name
+            );
+        }
+        Ok(Some(preset))
+    }
+
+    pub fn endpoint(&self) -> Result<ImapEndpoint> {
+        let endpoint = match (&self.host, self.preset()?) {
+            (Some(host), _) => ImapEndpoint {
+                host: host.clone(),
+                port: self.port.unwrap_or(self.tls.default_port()),
+                tls: self.tls,
+                ..ImapEndpoint::default()
+            },
+            (None, Some(preset)) => ImapEndpoint {
+                port: self.port.unwrap_or(preset.port),
+                ..preset.endpoint()
+            },
+            (None, None) => bail!("{}: needs a host or a provider preset", self.email),
+        };
+        if endpoint.tls == TlsMode::Plain && !endpoint.is_loopback() {
+            bail!(
+                "{}: TLS mode plain is only allowed for localhost, not {}",
+                self.email,
+                endpoint.host
+            );
+        }
+        Ok(endpoint)
+    }
+
+    pub fn credential(&self) -> Credential {
+        match &self.password_cmd {
+            Some(command) => Credential::PasswordCommand {
+                command: command.clone(),
+            },
+            None => Credential::Password,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountsFile {
+    #[serde(default)]
+    account: Vec<PasswordAccountSpec>,
+}
+
+/// Parses an accounts file: a TOML list of `[[account]]` tables with `email`, `host` or
+/// `preset`, and optionally `port`, `tls` and `password_cmd`.
+pub fn parse_accounts_file(text: &str) -> Result<Vec<PasswordAccountSpec>> {
+    let file: AccountsFile = toml::from_str(text).context("parsing accounts file")?;
+    for spec in &file.account {
+        spec.endpoint()?;
+    }
+    Ok(file.account)
+}
+
+/// Builds an account that logs in with a password, then lists the server's folders with it
+/// like OAuth onboarding does. Discovery failing (wrong or not yet stored password, server
+/// down) only keeps the configured folders, so provisioning scripts can run offline from the
+/// mail server.
+pub async fn onboard_password_account(
+    defaults: &AppDefaults,
+    spec: &PasswordAccountSpec,
+) -> Result<(Account, Vec<MailboxInfo>)> {
+    let endpoint = spec.endpoint()?;
+    let mut account = new_account(
+        defaults,
+        Provider::for_password_account(&endpoint),
+        spec.email.clone(),
+        endpoint,
+        spec.credential(),
+    );
+    if let Some(preset) = spec.preset()? {
+        account.settings.folders = preset.rename_defaults(&defaults.folders);
+    } else if account.provider == Provider::Imap {
+        account.settings.folders = imap_default_folders(&defaults.folders);
+    }
+    let discovered = match credentials::imap_secret(&account).await {
+        Ok(secret) => discover_and_select(defaults, &mut account, &secret, spec.preset()?).await,
+        Err(e) => {
+            warn!(account = %account.id, error = %e, "Reading the password failed; keeping configured folders");
+            Vec::new()
+        }
+    };
+    info!(account = %account.id, folders = ?account.settings.folders, "Onboarded password account");
+    Ok((account, discovered))
+}
+
+fn new_account(
+    defaults: &AppDefaults,
+    provider: Provider,
+    email: String,
+    imap: ImapEndpoint,
+    credential: Credential,
+) -> Account {
     let now = now_ts();
-    let account = Account {
+    Account {
         id: email.clone(),
         email,
-        provider: Provider::GmailImap,
         settings: AccountSettings {
             folders: defaults.folders.clone(),
             cutoff_since: defaults.cutoff_since,
             poll_interval_minutes: defaults.poll_interval_minutes,
             prefetch_recent: defaults.prefetch_recent,
             safe_mode: defaults.safe_mode,
+            max_download_bytes_per_sec: defaults.max_download_bytes_per_sec,
+            folder_policies: Default::default(),
+            encrypt_columns: false,
+            unread_only: defaults.unread_only,
+            max_message_bytes: defaults.max_message_bytes,
+            smart_folders: Vec::new(),
+            all_mail_mode: defaults.all_mail_mode && provider == Provider::GmailImap,
+            imap,
+            enabled: true,
+            credential,
+            cleanup_rules: Vec::new(),
+            notify_rules: Vec::new(),
+            namespace: None,
+            tls_policy: Default::default(),
         },
+        provider,
         created_at: now,
         updated_at: now,
+    }
+}
+
+/// Lists the server's folders and narrows the account's configured ones to those that exist;
+/// returns the listing (empty when `LIST` failed). A preset's folder names stand in for
+/// special-use roles the server doesn't advertise, and for the listing when `LIST` failed.
+async fn discover_and_select(
+    defaults: &AppDefaults,
+    account: &mut Account,
+    secret: &str,
+    preset: Option<&ProviderPreset>,
+) -> Vec<MailboxInfo> {
+    let discovered = match discover(account, secret).await {
+        Ok((mut mailboxes, namespace)) => {
+            if let Some(preset) = preset {
+                preset.apply_roles(&mut mailboxes);
+            }
+            if let Some(namespace) = namespace {
+                account.settings.apply_namespace(namespace);
+            }
+            mailboxes
+        }
+        Err(e) => {
+            warn!(account = %account.id, error = %e, "Folder discovery failed; keeping configured folders");
+            Vec::new()
+        }
     };
-    info!(account = %account.id, "Onboarded account via OAuth");
-    Ok((account, token))
+    if !discovered.is_empty() {
+        // Gmail defaults stay as they are: missing ones are found by special-use role.
+        let configured: Vec<String> = defaults
+            .folders
+            .iter()
+            .map(|folder| match FolderRole::for_gmail_default(folder) {
+                Some(_) => folder.clone(),
+                None => account.settings.server_folder(folder),
+            })
+            .collect();
+        account.settings.folders = select_folders(&configured, &discovered);
+    }
+    if defaults.metadata_only_trash_spam {
+        let known = match preset {
+            Some(preset) if discovered.is_empty() => preset.mailboxes(),
+            _ => discovered.clone(),
+        };
+        for folder in trash_and_spam(&account.settings.folders, &known) {
+            account.settings.folder_policies.insert(
+                folder,
+                FolderPolicy {
+                    body_fetch: BodyFetch::MetadataOnly,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+    discovered
+}
+
+/// The server's mailboxes, and its namespace unless reading that failed.
+async fn discover(
+    account: &Account,
+    secret: &str,
+) -> Result<(Vec<MailboxInfo>, Option<MailboxNamespace>)> {
+    let mut session = ImapClient::connect(account, secret).await?;
+    let mailboxes = ImapClient::list_folders(&mut session).await?;
+    let namespace = ImapClient::namespace(&mut session)
+        .await
+        .map_err(
+            |e| warn!(account = %account.id, error = %e, "Reading the folder namespace failed"),
+        )
+        .ok();
+    let _ = session.logout().await;
+    Ok((mailboxes, namespace))
+}
+
+/// `configured` with the built-in Gmail defaults renamed to the names most other servers use
+/// (`Sent`, `Junk`, ...), for generic IMAP accounts before (or without) discovery.
+pub fn imap_default_folders(configured: &[String]) -> Vec<String> {
+    configured
+        .iter()
+        .filter_map(|folder| match FolderRole::for_gmail_default(folder) {
+            Some(role) => role.imap_default().map(str::to_string),
+            None => Some(folder.clone()),
+        })
+        .collect()
+}
+
+/// The configured folders that exist on the server and can be selected, in configured order.
+/// A built-in Gmail default that is missing (localized servers name it "[Gmail]/Gesendet"
+/// etc.) is replaced by the mailbox advertising the same special-use role, else by a listed
+/// mailbox with the role's common name (`Sent`, `Junk`, ...).
+pub fn select_folders(configured: &[String], discovered: &[MailboxInfo]) -> Vec<String> {
+    let mut selected: Vec<String> = Vec::new();
+    for folder in configured {
+        let found = discovered
+            .iter()
+            .any(|m| same_folder(&m.name, folder) && m.selectable());
+        let resolved = if found {
+            Some(folder.as_str())
+        } else {
+            FolderRole::for_gmail_default(folder).and_then(|role| {
+                special_use_folder(discovered, role).or_else(|| {
+                    let name = role.imap_default()?;
+                    discovered
+                        .iter()
+                        .find(|m| m.name == name && m.selectable())
+                        .map(|m| m.name.as_str())
+                })
+            })
+        };
+        match resolved {
+            Some(name) if !selected.iter().any(|s| s == name) => selected.push(name.to_string()),
+            Some(_) => {}
+            None => {
+                warn!(folder = %folder, "Configured folder not found on the server; not syncing it")
+            }
+        }
+    }
+    selected
+}
+
+/// The synced folders holding Trash and Spam: the special-use mailboxes when the server listed
+/// them, otherwise the Gmail default names.
+pub fn trash_and_spam(folders: &[String], discovered: &[MailboxInfo]) -> Vec<String> {
+    [FolderRole::Trash, FolderRole::Junk]
+        .into_iter()
+        .map(|role| special_use_folder(discovered, role).unwrap_or(role.gmail_default()))
+        .filter(|name| folders.iter().any(|f| f == name))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Folder names compare exactly, except INBOX, which IMAP treats case-insensitively.
+pub fn same_folder(a: &str, b: &str) -> bool {
+    a == b || (a.eq_ignore_ascii_case("INBOX") && b.eq_ignore_ascii_case("INBOX"))
 }
@@ -0,0 +1,191 @@
+//! Built-in provider presets: the IMAP server, how to sign in and the special folder names of
+//! the big providers, so `otto accounts add --preset fastmail --email ...` works without knowing
+//! any IMAP settings. Special folder names fill in for servers that don't advertise SPECIAL-USE
+//! and for onboarding without a reachable server.
+use anyhow::{Result, bail};
+
+use crate::types::{FolderRole, ImapEndpoint, MailboxInfo, Provider, TlsMode};
+
+/// How accounts of a preset sign in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PresetAuth {
+    /// OAuth through `otto --add-account --provider <provider>`.
+    OAuth(Provider),
+    /// An app password generated in the provider's account settings (the normal password
+    /// is refused over IMAP); `where_to_get` says where.
+    AppPassword { where_to_get: &'static str },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProviderPreset {
+    /// Name used on the command line and in accounts files.
+    pub name: &'static str,
+    pub label: &'static str,
+    pub host: &'static str,
+    pub port: u16,
+    pub tls: TlsMode,
+    pub auth: PresetAuth,
+    /// The provider's name for each special folder.
+    pub folders: &'static [(FolderRole, &'static str)],
+}
+
+pub const PRESETS: &[ProviderPreset] = &[
+    ProviderPreset {
+        name: "gmail",
+        label: "Gmail / Google Workspace",
+        host: "imap.gmail.com",
+        port: 993,
+        tls: TlsMode::Tls,
+        auth: PresetAuth::OAuth(Provider::GmailImap),
+        folders: &[
+            (FolderRole::Sent, "[Gmail]/Sent Mail"),
+            (FolderRole::Trash, "[Gmail]/Trash"),
+            (FolderRole::Junk, "[Gmail]/Spam"),
+            (FolderRole::Drafts, "[Gmail]/Drafts"),
+        ],
+    },
+    ProviderPreset {
+        name: "outlook",
+        label: "Microsoft 365 / Outlook.com",
+        host: "outlook.office365.com",
+        port: 993,
+        tls: TlsMode::Tls,
+        auth: PresetAuth::OAuth(Provider::OutlookImap),
+        folders: &[
+            (FolderRole::Sent, "Sent Items"),
+            (FolderRole::Trash, "Deleted Items"),
+            (FolderRole::Junk, "Junk Email"),
+            (FolderRole::Drafts, "Drafts"),
+        ],
+    },
+    ProviderPreset {
+        name: "fastmail",
+        label: "Fastmail",
+        host: "imap.fastmail.com",
+        port: 993,
+        tls: TlsMode::Tls,
+        auth: PresetAuth::AppPassword {
+            where_to_get: "Settings > Privacy & Security > Manage app passwords (access: IMAP)",
+        },
+        folders: &[
+            (FolderRole::Sent, "Sent"),
+            (FolderRole::Trash, "Trash"),
+            (FolderRole::Junk, "Spam"),
+            (FolderRole::Drafts, "Drafts"),
+        ],
+    },
+    ProviderPreset {
+        name: "yahoo",
+        label: "Yahoo Mail",
+        host: "imap.mail.yahoo.com",
+        port: 993,
+        tls: TlsMode::Tls,
+        auth: PresetAuth::AppPassword {
+            where_to_get: "Account info > Account security > Generate app password",
+        },
+        folders: &[
+            (FolderRole::Sent, "Sent"),
+            (FolderRole::Trash, "Trash"),
+            (FolderRole::Junk, "Bulk"),
+            (FolderRole::Drafts, "Draft"),
+        ],
+    },
+    ProviderPreset {
+        name: "icloud",
+        label: "iCloud Mail",
+        host: "imap.mail.me.com",
+        port: 993,
+        tls: TlsMode::Tls,
+        auth: PresetAuth::AppPassword {
+            where_to_get: "account.apple.com > Sign-In and Security > App-Specific Passwords",
+        },
+        folders: &[
+            (FolderRole::Sent, "Sent Messages"),
+            (FolderRole::Trash, "Deleted Messages"),
+            (FolderRole::Junk, "Junk"),
+            (FolderRole::Drafts, "Drafts"),
+        ],
+    },
+];
+
+/// The preset called `name` (case-insensitive).
+pub fn find(name: &str) -> Result<&'static ProviderPreset> {
+    match PRESETS
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+    {
+        Some(preset) => Ok(preset),
+        None => bail!(
+            "unknown provider preset {:?}; known: {}",
+            name,
+            PRESETS
+                .iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// The OAuth preset of `provider`.
+pub fn for_provider(provider: &Provider) -> Option<&'static ProviderPreset> {
+    PRESETS
+        .iter()
+        .find(|p| p.auth == PresetAuth::OAuth(provider.clone()))
+}
+
+impl ProviderPreset {
+    pub fn endpoint(&self) -> ImapEndpoint {
+        ImapEndpoint {
+            host: self.host.to_string(),
+            port: self.port,
+            tls: self.tls,
+            ..ImapEndpoint::default()
+        }
+    }
+
+    /// The provider's name for `role`, if the preset knows it.
+    pub fn folder(&self, role: FolderRole) -> Option<&'static str> {
+        self.folders
+            .iter()
+            .find(|(r, _)| *r == role)
+            .map(|(_, name)| *name)
+    }
+
+    /// `configured` with the built-in Gmail defaults renamed to this provider's folders.
+    pub fn rename_defaults(&self, configured: &[String]) -> Vec<String> {
+        configured
+            .iter()
+            .map(|folder| {
+                FolderRole::for_gmail_default(folder)
+                    .and_then(|role| self.folder(role))
+                    .map_or_else(|| folder.clone(), str::to_string)
+            })
+            .collect()
+    }
+
+    /// The preset's special folders as a folder listing, for onboarding without a server.
+    pub fn mailboxes(&self) -> Vec<MailboxInfo> {
+        self.folders
+            .iter()
+            .map(|(role, name)| MailboxInfo {
+                name: name.to_string(),
+                delimiter: None,
+                attributes: vec![role.attribute().to_string()],
+            })
+            .collect()
+    }
+
+    /// Marks the listed mailboxes carrying a preset folder name with that folder's special-use
+    /// attribute, for roles the server didn't advertise itself.
+    pub fn apply_roles(&self, mailboxes: &mut [MailboxInfo]) {
+        for (role, name) in self.folders {
+            if mailboxes.iter().any(|m| m.role() == Some(*role)) {
+                continue;
+            }
+            if let Some(mailbox) = mailboxes.iter_mut().find(|m| m.name == *name) {
+                mailbox.attributes.push(role.attribute().to_string());
+            }
+        }
+    }
+}
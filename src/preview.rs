@@ -0,0 +1,109 @@
+//! The one-line preview under each message in the TUI list and the plain CLI list. The source
+//! is chosen per folder (`FolderPolicy::preview`, `otto folder-policy --preview`) or globally
+//! (`OTTO_PREVIEW_SOURCE`, default `clean`): newsletters often open with "View in browser" and
+//! replies with a quote, neither of which says what the message is about.
+use std::sync::RwLock;
+
+use crate::types::{AccountSettings, PreviewSource};
+
+/// Process-wide, set from `AppDefaults` at startup and on settings reloads.
+static DEFAULT_SOURCE: RwLock<PreviewSource> = RwLock::new(PreviewSource::Clean);
+
+pub fn set_default_source(source: PreviewSource) {
+    *DEFAULT_SOURCE.write().unwrap_or_else(|e| e.into_inner()) = source;
+}
+
+pub fn default_source() -> PreviewSource {
+    *DEFAULT_SOURCE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The folder's own preview source, falling back to the global one.
+pub fn source_for(settings: Option<&AccountSettings>, folder: &str) -> PreviewSource {
+    settings
+        .and_then(|s| s.folder_policy(folder).preview)
+        .unwrap_or_else(default_source)
+}
+
+/// Up to `max_lines` preview lines from `text` (a sanitized body) joined with spaces.
+/// `summary` is the message's stored summary, used by `PreviewSource::Summary`.
+pub fn preview_text(
+    source: PreviewSource,
+    text: &str,
+    summary: Option<&str>,
+    max_lines: usize,
+) -> String {
+    let non_empty = |text: &'_ str| -> Vec<String> {
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .take(max_lines)
+            .map(str::to_string)
+            .collect()
+    };
+    let lines = match (source, summary) {
+        (PreviewSource::Raw, _) => non_empty(text),
+        (PreviewSource::Summary, Some(summary)) if !summary.trim().is_empty() => non_empty(summary),
+        _ => clean_lines(text).take(max_lines).collect(),
+    };
+    lines.join(" ")
+}
+
+/// The body's lines up to the first reply header, skipping blank lines, quoted lines and
+/// newsletter boilerplate; falls back to nothing rather than to the skipped lines.
+pub fn clean_lines(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.lines()
+        .map(str::trim)
+        .take_while(|line| !is_reply_header(line))
+        .filter(|line| !line.is_empty() && !line.starts_with('>') && !is_boilerplate(line))
+        .map(str::to_string)
+}
+
+/// "On Mon, 3 Mar 2025 at 10:00, Ana <ana@example.com> wrote:" and Outlook's separators.
+fn is_reply_header(line: &str) -> bool {
+    (line.starts_with("On ") && line.ends_with("wrote:"))
+        || line.starts_with("-----Original Message-----")
+        || line.starts_with("________________________________")
+}
+
+const BOILERPLATE: &[&str] = &[
+    "view in browser",
+    "view in your browser",
+    "view this email in your browser",
+    "view this email in a browser",
+    "view it in your browser",
+    "view online",
+    "view as a web page",
+    "view as webpage",
+    "open in browser",
+    "having trouble viewing",
+    "trouble viewing this email",
+    "email not displaying correctly",
+    "if you cannot see this email",
+    "web version",
+];
+
+/// Short lines that only point elsewhere: "View in browser", a bare link, an image's alt text
+/// (`[Logo]`), a link footnote (`[1]: https://...`) or a divider.
+fn is_boilerplate(line: &str) -> bool {
+    if !line.chars().any(char::is_alphanumeric) {
+        return true;
+    }
+    if line.starts_with("http://") || line.starts_with("https://") {
+        return !line.contains(char::is_whitespace);
+    }
+    if line.starts_with('[')
+        && line.ends_with(']')
+        && !line[1..].contains('[')
+        && line.chars().count() <= 40
+    {
+        return true;
+    }
+    if let Some((marker, rest)) = line.split_once("]: ")
+        && marker.starts_with('[')
+        && !rest.contains(char::is_whitespace)
+    {
+        return true;
+    }
+    let lower = line.to_lowercase();
+    line.chars().count() <= 100 && BOILERPLATE.iter().any(|phrase| lower.contains(phrase))
+}
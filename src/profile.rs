@@ -0,0 +1,124 @@
+//! Named profiles (`--profile work`, `OTTO_PROFILE`, `otto profile switch`): each one has its own
+//! data directory (database, blobs, logs) and its own keyring entries, so personal and work mail
+//! never share a cache or a credential. The default profile is the plain data directory, as
+//! before profiles existed; named ones live under `<data dir>/profiles/<name>`.
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use anyhow::{Context, Result, bail};
+
+/// The profile used when none is selected; it maps to the data directory itself.
+pub const DEFAULT_PROFILE: &str = "default";
+/// Subdirectory of the base data directory holding the named profiles.
+const PROFILES_DIR: &str = "profiles";
+/// File in the base data directory naming the profile `otto profile switch` selected.
+const CURRENT_FILE: &str = "current-profile";
+const MAX_NAME_CHARS: usize = 32;
+
+/// Process-wide, set once at startup from the flag, the environment or the current-profile file.
+static ACTIVE: RwLock<Option<String>> = RwLock::new(None);
+
+pub fn set_active(profile: Option<String>) {
+    *ACTIVE.write().unwrap_or_else(|e| e.into_inner()) = profile.filter(|p| p != DEFAULT_PROFILE);
+}
+
+/// The active named profile; `None` is the default profile.
+pub fn active() -> Option<String> {
+    ACTIVE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Profile names are short and path- and keyring-safe: ASCII letters, digits, `-` and `_`.
+pub fn validate(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME_CHARS
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!(
+            "invalid profile name {:?}: use up to {} letters, digits, '-' or '_'",
+            name,
+            MAX_NAME_CHARS
+        );
+    }
+    Ok(())
+}
+
+/// The profile to run with: `--profile`, else `OTTO_PROFILE`, else the one last switched to
+/// under `base`, else the default profile.
+pub fn resolve(flag: Option<&str>, env: Option<&str>, base: &Path) -> Result<Option<String>> {
+    let chosen = match flag.or(env).map(str::trim).filter(|p| !p.is_empty()) {
+        Some(name) => Some(name.to_string()),
+        None => current(base)?,
+    };
+    match chosen {
+        Some(name) if name != DEFAULT_PROFILE => {
+            validate(&name)?;
+            Ok(Some(name))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Data directory of `profile` under the base data directory.
+pub fn data_dir(base: &Path, profile: Option<&str>) -> PathBuf {
+    match profile {
+        Some(name) => base.join(PROFILES_DIR).join(name),
+        None => base.to_path_buf(),
+    }
+}
+
+/// Keyring service for the active profile: `service` itself for the default profile,
+/// `service@name` otherwise, so the same account id in two profiles keeps separate secrets.
+pub fn keyring_service(service: &str) -> String {
+    match active() {
+        Some(name) => format!("{}@{}", service, name),
+        None => service.to_string(),
+    }
+}
+
+/// The default profile followed by the named profiles under `base`, sorted.
+pub fn list(base: &Path) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let dir = base.join(PROFILES_DIR);
+    if dir.is_dir() {
+        for entry in
+            std::fs::read_dir(&dir).with_context(|| format!("reading {}", dir.display()))?
+        {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.path().is_dir() && validate(&name).is_ok() && name != DEFAULT_PROFILE {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(names)
+}
+
+/// The profile `otto profile switch` last selected under `base`, if any.
+pub fn current(base: &Path) -> Result<Option<String>> {
+    let path = base.join(CURRENT_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => Ok(Some(text.trim().to_string()).filter(|p| !p.is_empty())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+    }
+}
+
+/// Makes `name` the profile later runs use without `--profile`, creating its data directory.
+pub fn switch(base: &Path, name: &str) -> Result<PathBuf> {
+    let profile = if name == DEFAULT_PROFILE {
+        None
+    } else {
+        validate(name)?;
+        Some(name)
+    };
+    let dir = data_dir(base, profile);
+    std::fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    let path = base.join(CURRENT_FILE);
+    std::fs::write(&path, format!("{}\n", name))
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(dir)
+}
@@ -0,0 +1,186 @@
+//! Terminal progress for CLI syncs, driven by `SyncProgress` events: one bar per folder
+//! (messages fetched / planned, transfer rate, ETA) when stderr is a terminal, otherwise one
+//! summary line per finished folder.
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
+
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::task::JoinHandle;
+
+use crate::types::{Account, SyncProgress};
+
+const BAR_TEMPLATE: &str = "{prefix:28!} [{bar:30.cyan/blue}] {pos:>6}/{len:6} {msg} ETA {eta}";
+
+#[derive(Default)]
+struct FolderProgress {
+    planned: u64,
+    fetched: u64,
+    bytes: u64,
+    started: Option<Instant>,
+    bar: Option<ProgressBar>,
+}
+
+impl FolderProgress {
+    fn rate(&self) -> u64 {
+        let elapsed = self
+            .started
+            .map(|s| s.elapsed())
+            .unwrap_or_default()
+            .max(Duration::from_millis(1));
+        (self.bytes as f64 / elapsed.as_secs_f64()) as u64
+    }
+
+    fn summary(&self, status: &str) -> String {
+        let elapsed = self.started.map(|s| s.elapsed()).unwrap_or_default();
+        format!(
+            "{}/{} message(s), {} in {:.1}s, {}",
+            self.fetched,
+            self.planned.max(self.fetched),
+            HumanBytes(self.bytes),
+            elapsed.as_secs_f64(),
+            status
+        )
+    }
+
+    /// Closes the bar with `summary`, or prints it as a plain line without one.
+    fn finish(mut self, label: &str, status: &str) {
+        self.refresh();
+        let summary = self.summary(status);
+        match self.bar.take() {
+            Some(bar) => bar.finish_with_message(summary),
+            None => eprintln!("{}: {}", label, summary),
+        }
+    }
+
+    fn refresh(&self) {
+        if let Some(bar) = &self.bar {
+            bar.set_length(self.planned.max(self.fetched));
+            bar.set_position(self.fetched);
+            bar.set_message(format!(
+                "{} @ {}/s",
+                HumanBytes(self.bytes),
+                HumanBytes(self.rate())
+            ));
+        }
+    }
+}
+
+struct Renderer {
+    /// `None` when stderr is not a terminal.
+    multi: Option<MultiProgress>,
+    style: ProgressStyle,
+    emails: HashMap<String, String>,
+    folders: HashMap<(String, String), FolderProgress>,
+}
+
+impl Renderer {
+    fn label(&self, account_id: &str, folder: &str) -> String {
+        if self.emails.len() > 1 {
+            let email = self
+                .emails
+                .get(account_id)
+                .map_or(account_id, String::as_str);
+            format!("{} {}", email, folder)
+        } else {
+            folder.to_string()
+        }
+    }
+
+    fn folder(&mut self, account_id: &str, folder: &str) -> &mut FolderProgress {
+        let label = self.label(account_id, folder);
+        let multi = self.multi.clone();
+        let style = self.style.clone();
+        self.folders
+            .entry((account_id.to_string(), folder.to_string()))
+            .or_insert_with(|| FolderProgress {
+                started: Some(Instant::now()),
+                bar: multi.map(|multi| {
+                    let bar = multi.add(ProgressBar::new(0));
+                    bar.set_style(style);
+                    bar.set_prefix(label);
+                    bar
+                }),
+                ..Default::default()
+            })
+    }
+
+    fn handle(&mut self, event: SyncProgress) {
+        match event {
+            SyncProgress::FolderStarted { account_id, folder } => {
+                let progress = self.folder(&account_id, &folder);
+                progress.started = Some(Instant::now());
+                progress.refresh();
+            }
+            SyncProgress::UidsPlanned {
+                account_id,
+                folder,
+                count,
+            } => {
+                let progress = self.folder(&account_id, &folder);
+                progress.planned += count as u64;
+                progress.refresh();
+            }
+            SyncProgress::MessagesFetched {
+                account_id,
+                folder,
+                count,
+                bytes,
+            } => {
+                let progress = self.folder(&account_id, &folder);
+                progress.fetched += count as u64;
+                progress.bytes += bytes;
+                progress.refresh();
+            }
+            SyncProgress::FolderFinished {
+                account_id,
+                folder,
+                ok,
+            } => {
+                let label = self.label(&account_id, &folder);
+                // The body phase reports under the same folder names; it starts afresh.
+                if let Some(progress) = self.folders.remove(&(account_id, folder)) {
+                    progress.finish(&label, if ok { "done" } else { "failed" });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Closes whatever is still open once the engine is gone (body-phase fetches).
+    fn finish(&mut self) {
+        let folders: Vec<_> = self.folders.drain().collect();
+        for ((account_id, folder), progress) in folders {
+            progress.finish(&self.label(&account_id, &folder), "bodies done");
+        }
+    }
+}
+
+/// Renders `events` until the engine that produced them is dropped.
+pub fn spawn(
+    mut events: broadcast::Receiver<SyncProgress>,
+    accounts: &[Account],
+) -> JoinHandle<()> {
+    let mut renderer = Renderer {
+        multi: std::io::stderr().is_terminal().then(MultiProgress::new),
+        style: ProgressStyle::with_template(BAR_TEMPLATE)
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("=> "),
+        emails: accounts
+            .iter()
+            .map(|a| (a.id.clone(), a.email.clone()))
+            .collect(),
+        folders: HashMap::new(),
+    };
+    tokio::spawn(async move {
+        loop {
+            match events.recv().await {
+                Ok(event) => renderer.handle(event),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+        renderer.finish();
+    })
+}
@@ -0,0 +1,140 @@
+//! Response tracking for sent mail (`otto responses`): which sent messages got a reply and how
+//! quickly. Messages are grouped by thread (X-GM-THRID, or the JWZ thread id) and walked in
+//! date order. A sent message answered by the next message in its thread, when that one is
+//! from someone else, counts with the gap as its response time. A sent message that is the
+//! newest of its thread is awaiting a reply. A sent message followed by another one of yours
+//! is neither. Only cached mail counts, so the account must sync its Sent folder (or All Mail).
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::Result;
+
+use crate::address::parse_mailbox;
+use crate::storage::MailStore;
+use crate::types::{Account, FolderRole, MessageRecord};
+
+/// A sent message and the first reply to it.
+#[derive(Clone, Debug)]
+pub struct Answered {
+    pub sent: MessageRecord,
+    pub reply: MessageRecord,
+}
+
+impl Answered {
+    /// Seconds from sending to the reply.
+    pub fn response_secs(&self) -> i64 {
+        let sent = self.sent.internal_date.unwrap_or_default();
+        (self.reply.internal_date.unwrap_or(sent) - sent).max(0)
+    }
+}
+
+/// What happened to the sent messages of a window.
+#[derive(Clone, Debug, Default)]
+pub struct ResponseStats {
+    /// Sent messages tracked (threaded, outside Drafts/Trash/Spam).
+    pub sent: usize,
+    /// Oldest first.
+    pub answered: Vec<Answered>,
+    /// Oldest first.
+    pub awaiting: Vec<MessageRecord>,
+}
+
+impl ResponseStats {
+    pub fn average_response_secs(&self) -> Option<i64> {
+        if self.answered.is_empty() {
+            return None;
+        }
+        let total: i64 = self.answered.iter().map(Answered::response_secs).sum();
+        Some(total / self.answered.len() as i64)
+    }
+}
+
+/// Where an account keeps its mail, for telling sent messages from received ones.
+#[derive(Clone, Debug)]
+pub struct Folders {
+    pub sent: String,
+    /// Drafts, Trash and Spam: nothing in them counts.
+    pub ignored: Vec<String>,
+}
+
+/// Tracks `messages` (any order) of the account whose address is `me`.
+pub fn track(messages: &[MessageRecord], me: &str, folders: &Folders) -> ResponseStats {
+    // The same message can be cached in several folders (Sent and INBOX when mailing
+    // yourself, or a label folder); one row per Message-ID is enough.
+    let mut seen = HashSet::new();
+    let mut threads: BTreeMap<&str, Vec<&MessageRecord>> = BTreeMap::new();
+    for msg in messages {
+        let Some(thread) = msg.thread_id.as_deref() else {
+            continue;
+        };
+        if msg.internal_date.is_none()
+            || folders.ignored.contains(&msg.folder)
+            || msg.flags.iter().any(|f| f.eq_ignore_ascii_case("\\Draft"))
+        {
+            continue;
+        }
+        if !seen.insert(msg.message_id_header.as_deref().unwrap_or(&msg.id)) {
+            continue;
+        }
+        threads.entry(thread).or_default().push(msg);
+    }
+
+    let mut stats = ResponseStats::default();
+    for messages in threads.values_mut() {
+        messages.sort_by_key(|msg| (msg.internal_date, msg.id.as_str()));
+        for (idx, msg) in messages.iter().enumerate() {
+            if !is_sent(msg, me, folders) {
+                continue;
+            }
+            stats.sent += 1;
+            match messages.get(idx + 1) {
+                None => stats.awaiting.push((*msg).clone()),
+                Some(next) if !is_sent(next, me, folders) => stats.answered.push(Answered {
+                    sent: (*msg).clone(),
+                    reply: (*next).clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+    }
+    stats.answered.sort_by_key(|a| a.sent.internal_date);
+    stats.awaiting.sort_by_key(|msg| msg.internal_date);
+    stats
+}
+
+/// Tracks the account's messages received from `since` (unix secs) on.
+pub async fn collect(db: &dyn MailStore, account: &Account, since: i64) -> Result<ResponseStats> {
+    let messages = db.load_messages_since(&account.id, since).await?;
+    let mut ignored = Vec::new();
+    for role in [FolderRole::Drafts, FolderRole::Trash, FolderRole::Junk] {
+        ignored.push(db.role_folder(&account.id, role).await?);
+    }
+    let folders = Folders {
+        sent: db.role_folder(&account.id, FolderRole::Sent).await?,
+        ignored,
+    };
+    Ok(track(&messages, &account.email, &folders))
+}
+
+/// Cached in Sent, labeled `\Sent` (All Mail mode), or from the account's own address.
+fn is_sent(msg: &MessageRecord, me: &str, folders: &Folders) -> bool {
+    msg.folder == folders.sent
+        || msg.labels.iter().any(|l| l.eq_ignore_ascii_case("\\Sent"))
+        || msg.from.as_deref().is_some_and(|from| {
+            parse_mailbox(from)
+                .map(|mailbox| mailbox.addr)
+                .unwrap_or_else(|| from.to_string())
+                .eq_ignore_ascii_case(me)
+        })
+}
+
+/// `3d 4h`, `5h 12m`, `12m` or `<1m`.
+pub fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => "<1m".to_string(),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
@@ -1,3 +1,19 @@
+//! Turns a fetched message into the stored, readable body: the preferred text part (HTML
+//! through html2text), tracking parameters cleaned from URLs, and a MIME summary with attachment
+//! metadata. html2text gets a time budget per HTML part (`OTTO_HTML_RENDER_BUDGET_MS`, default
+//! 2000); pathological HTML (huge nested tables) that runs past it is tag-stripped with
+//! [`strip_tags`] instead and the body is marked `degraded`, so the TUI can re-render it in full
+//! on request. Budgeted renders run on at most [`MAX_RENDER_THREADS`] threads at once, counting
+//! abandoned ones still running; past that, HTML is tag-stripped straight away.
+pub mod resanitize;
+
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use crate::encoded_words::decode_mime_words;
 use crate::types::BodyRecord;
 use anyhow::Result;
 use html2text::from_read;
@@ -8,9 +24,58 @@ use mailparse::body::Body;
 use once_cell::sync::Lazy;
 use regex::Regex;
 use serde::Serialize;
+use tracing::warn;
 use url::Url;
 use url::form_urlencoded;
 
+/// Default html2text time per HTML part before falling back to [`strip_tags`].
+pub const DEFAULT_RENDER_BUDGET: Duration = Duration::from_secs(2);
+
+/// HTML up to this size is rendered on the calling thread: html2text finishes it quickly
+/// whatever its structure, so the budget isn't worth a thread.
+const INLINE_RENDER_BYTES: usize = 4 * 1024;
+
+/// Budgeted html2text threads alive at once, including ones abandoned past their budget.
+pub const MAX_RENDER_THREADS: usize = 16;
+
+static RENDER_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// A slot in [`RENDER_THREADS`], released when the render thread finishes.
+struct RenderSlot;
+
+impl RenderSlot {
+    fn acquire() -> Option<Self> {
+        RENDER_THREADS
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < MAX_RENDER_THREADS).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| RenderSlot)
+    }
+}
+
+impl Drop for RenderSlot {
+    fn drop(&mut self) {
+        RENDER_THREADS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Process-wide, set from `AppDefaults` at startup.
+static RENDER_BUDGET: RwLock<Option<Duration>> = RwLock::new(Some(DEFAULT_RENDER_BUDGET));
+
+/// Replaces the html2text budget for bodies sanitized from now on (`None`: no limit).
+pub fn set_render_budget(budget: Option<Duration>) {
+    *RENDER_BUDGET.write().unwrap_or_else(|e| e.into_inner()) = budget;
+}
+
+pub fn render_budget() -> Option<Duration> {
+    *RENDER_BUDGET.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Bump whenever `sanitize` output changes (URL cleaning, reply stripping, MIME summary, ...);
+/// `otto resanitize` rebuilds stored bodies produced by older versions.
+pub const SANITIZER_VERSION: i64 = 1;
+
 #[derive(Debug)]
 pub struct SanitizedBody {
     pub sanitized_text: String,
@@ -18,6 +83,8 @@ pub struct SanitizedBody {
     pub attachments_json: Option<String>,
     pub raw_hash: String,
     pub has_attachments: bool,
+    /// An HTML part ran past the html2text budget and was only tag-stripped.
+    pub degraded: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -29,8 +96,22 @@ struct AttachmentMeta {
     encoded_bytes: usize,
 }
 
+/// Sanitizes with the process-wide html2text budget ([`render_budget`]).
 pub fn sanitize(parsed: &ParsedMail, raw_bytes: &[u8]) -> Result<SanitizedBody> {
-    let text = extract_text(parsed, raw_bytes);
+    sanitize_with_budget(parsed, raw_bytes, render_budget())
+}
+
+/// Sanitizes, giving html2text `budget` per HTML part (`None`: as long as it takes).
+pub fn sanitize_with_budget(
+    parsed: &ParsedMail,
+    raw_bytes: &[u8],
+    budget: Option<Duration>,
+) -> Result<SanitizedBody> {
+    let mut renderer = Renderer {
+        budget,
+        degraded: false,
+    };
+    let text = renderer.extract_text(parsed, raw_bytes);
     let raw_hash = compute_hash(raw_bytes);
     let (mime_summary, attachments) = summarize_mime(parsed);
     let has_attachments = !attachments.is_empty();
@@ -41,21 +122,34 @@ pub fn sanitize(parsed: &ParsedMail, raw_bytes: &[u8]) -> Result<SanitizedBody>
         attachments_json: serde_json::to_string(&attachments).ok(),
         raw_hash,
         has_attachments,
+        degraded: renderer.degraded,
     })
 }
 
 /// Public wrapper for sanitize that's imported by sync module
 pub fn sanitize_message(parsed: &ParsedMail, raw_bytes: &[u8]) -> SanitizedBody {
-    sanitize(parsed, raw_bytes).unwrap_or_else(|_| SanitizedBody {
+    sanitize_message_with_budget(parsed, raw_bytes, render_budget())
+}
+
+/// [`sanitize_message`] with an explicit html2text budget; `None` renders in full, as when a
+/// degraded body is re-rendered on request.
+pub fn sanitize_message_with_budget(
+    parsed: &ParsedMail,
+    raw_bytes: &[u8],
+    budget: Option<Duration>,
+) -> SanitizedBody {
+    sanitize_with_budget(parsed, raw_bytes, budget).unwrap_or_else(|_| SanitizedBody {
         sanitized_text: String::from_utf8_lossy(raw_bytes).to_string(),
         mime_summary: None,
         attachments_json: None,
         raw_hash: compute_hash(raw_bytes),
         has_attachments: false,
+        degraded: false,
     })
 }
 
-fn compute_hash(data: &[u8]) -> String {
+/// The `raw_hash` stored for a raw RFC822 message.
+pub fn compute_hash(data: &[u8]) -> String {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
 
@@ -64,6 +158,49 @@ fn compute_hash(data: &[u8]) -> String {
     format!("{:x}", hasher.finish())
 }
 
+/// A decoded attachment part, for exports that carry the files along (`otto share`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    pub filename: Option<String>,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// The message's attachment parts (same rules as the stored attachment metadata), decoded.
+pub fn attachments(parsed: &ParsedMail) -> Vec<Attachment> {
+    let mut out = Vec::new();
+    collect_attachments(parsed, 0, &mut out);
+    out
+}
+
+fn collect_attachments(part: &ParsedMail, depth: usize, out: &mut Vec<Attachment>) {
+    if depth > 20 {
+        return;
+    }
+    let mimetype = &part.ctype.mimetype;
+    if mimetype.starts_with("multipart/") && !part.subparts.is_empty() {
+        for child in &part.subparts {
+            collect_attachments(child, depth + 1, out);
+        }
+        return;
+    }
+    let filename = extract_filename(part);
+    let content_id = part.headers.get_first_value("Content-ID");
+    if is_attachment_part(
+        mimetype,
+        &part.get_content_disposition().disposition,
+        filename.as_deref(),
+        content_id.as_deref(),
+    ) && let Ok(data) = part.get_body_raw()
+    {
+        out.push(Attachment {
+            filename,
+            mime_type: mimetype.clone(),
+            data,
+        });
+    }
+}
+
 fn summarize_mime(parsed: &ParsedMail) -> (String, Vec<AttachmentMeta>) {
     let mut lines = Vec::new();
     let mut attachments = Vec::new();
@@ -162,8 +299,10 @@ fn extract_filename(part: &ParsedMail) -> Option<String> {
         .or_else(|| part.ctype.params.get("filename"))
         .cloned();
 
+    // Some mailers put RFC 2047 words in quoted filename parameters instead of RFC 2231.
     disp_name.or(ctype_name).and_then(|v| {
-        let trimmed = v.trim();
+        let decoded = decode_mime_words(&v);
+        let trimmed = decoded.trim();
         if trimmed.is_empty() {
             None
         } else {
@@ -199,32 +338,125 @@ fn is_attachment_part(
     !mimetype.starts_with("text/") && !mimetype.starts_with("multipart/")
 }
 
-fn extract_text(parsed: &ParsedMail, raw_bytes: &[u8]) -> String {
-    if let Some(text) = extract_preferred_text(parsed) {
-        return text;
-    }
-    // As last resort, render the whole raw message body.
-    let raw_lossy = String::from_utf8_lossy(raw_bytes);
-    render_text_part(raw_lossy.as_ref())
+/// Converts text and HTML parts, giving html2text `budget` per part; `degraded` is set once a
+/// part fell back to [`strip_tags`].
+struct Renderer {
+    budget: Option<Duration>,
+    degraded: bool,
 }
 
-fn html_to_text(html: &[u8]) -> String {
-    from_read(html, 80).unwrap_or_default()
-}
+impl Renderer {
+    fn extract_text(&mut self, parsed: &ParsedMail, raw_bytes: &[u8]) -> String {
+        if let Some(text) = self.extract_preferred_text(parsed) {
+            return text;
+        }
+        // As last resort, render the whole raw message body.
+        let raw_lossy = String::from_utf8_lossy(raw_bytes);
+        self.render_text_part(raw_lossy.as_ref())
+    }
 
-fn render_text_part(body: &str) -> String {
-    let cleaned = clean_urls_in_text(body);
-    if looks_like_html(&cleaned) {
-        html_to_text(cleaned.as_bytes())
-    } else {
-        cleaned
+    /// html2text, abandoned for [`strip_tags`] once it runs past the budget. Larger inputs run
+    /// on their own thread, which is left to finish in the background (html2text can't be
+    /// interrupted); with [`MAX_RENDER_THREADS`] busy, the HTML is tag-stripped right away.
+    fn html_to_text(&mut self, html: &[u8]) -> String {
+        let Some(budget) = self.budget.filter(|_| html.len() > INLINE_RENDER_BYTES) else {
+            return from_read(html, 80).unwrap_or_default();
+        };
+        let Some(slot) = RenderSlot::acquire() else {
+            warn!(
+                bytes = html.len(),
+                threads = MAX_RENDER_THREADS,
+                "html2text threads all busy; falling back to stripping tags"
+            );
+            self.degraded = true;
+            return strip_tags(&String::from_utf8_lossy(html));
+        };
+        let (tx, rx) = mpsc::sync_channel(1);
+        let owned = html.to_vec();
+        let spawned = thread::Builder::new()
+            .name("html2text".to_string())
+            .spawn(move || {
+                let _slot = slot;
+                let _ = tx.send(from_read(owned.as_slice(), 80).unwrap_or_default());
+            });
+        if spawned.is_err() {
+            return from_read(html, 80).unwrap_or_default();
+        }
+        match rx.recv_timeout(budget) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!(
+                    bytes = html.len(),
+                    budget_ms = budget.as_millis() as u64,
+                    timed_out = matches!(e, RecvTimeoutError::Timeout),
+                    "html2text did not finish; falling back to stripping tags"
+                );
+                self.degraded = true;
+                strip_tags(&String::from_utf8_lossy(html))
+            }
+        }
     }
-}
 
-fn render_html_part(html: &[u8]) -> String {
-    let lossless = String::from_utf8_lossy(html);
-    let cleaned = clean_urls_in_text(lossless.as_ref());
-    html_to_text(cleaned.as_bytes())
+    fn render_text_part(&mut self, body: &str) -> String {
+        let cleaned = clean_urls_in_text(body);
+        if looks_like_html(&cleaned) {
+            self.html_to_text(cleaned.as_bytes())
+        } else {
+            cleaned
+        }
+    }
+
+    fn render_html_part(&mut self, html: &[u8]) -> String {
+        let lossless = String::from_utf8_lossy(html);
+        let cleaned = clean_urls_in_text(lossless.as_ref());
+        self.html_to_text(cleaned.as_bytes())
+    }
+
+    fn extract_preferred_text(&mut self, part: &ParsedMail) -> Option<String> {
+        let mimetype = part.ctype.mimetype.to_ascii_lowercase();
+        if part.subparts.is_empty() {
+            if mimetype == "text/plain" {
+                let body =
+                    String::from_utf8_lossy(part.get_body_raw().unwrap_or_default().as_ref())
+                        .to_string();
+                return Some(self.render_text_part(&body));
+            }
+            if mimetype == "text/html" {
+                let html = part.get_body_raw().unwrap_or_default();
+                return Some(self.render_html_part(&html));
+            }
+            return None;
+        }
+
+        // Handle multipart/alternative with preference: text/plain then text/html then others.
+        if mimetype.starts_with("multipart/alternative") {
+            if let Some(plain) = part
+                .subparts
+                .iter()
+                .find(|p| p.ctype.mimetype.eq_ignore_ascii_case("text/plain"))
+                && let Some(text) = self.extract_preferred_text(plain)
+            {
+                return Some(text);
+            }
+            if let Some(html) = part
+                .subparts
+                .iter()
+                .find(|p| p.ctype.mimetype.eq_ignore_ascii_case("text/html"))
+                && let Some(text) = self.extract_preferred_text(html)
+            {
+                return Some(text);
+            }
+        }
+
+        // For other multiparts, walk children and return the first successful extraction.
+        for child in &part.subparts {
+            if let Some(text) = self.extract_preferred_text(child) {
+                return Some(text);
+            }
+        }
+
+        None
+    }
 }
 
 fn looks_like_html(body: &str) -> bool {
@@ -245,49 +477,34 @@ fn looks_like_html(body: &str) -> bool {
     angle_count > 5
 }
 
-fn extract_preferred_text(part: &ParsedMail) -> Option<String> {
-    let mimetype = part.ctype.mimetype.to_ascii_lowercase();
-    if part.subparts.is_empty() {
-        if mimetype == "text/plain" {
-            let body = String::from_utf8_lossy(part.get_body_raw().unwrap_or_default().as_ref())
-                .to_string();
-            return Some(render_text_part(&body));
-        }
-        if mimetype == "text/html" {
-            let html = part.get_body_raw().unwrap_or_default();
-            return Some(render_html_part(&html));
-        }
-        return None;
-    }
-
-    // Handle multipart/alternative with preference: text/plain then text/html then others.
-    if mimetype.starts_with("multipart/alternative") {
-        if let Some(text) = part
-            .subparts
-            .iter()
-            .find(|p| p.ctype.mimetype.eq_ignore_ascii_case("text/plain"))
-            .and_then(|p| extract_preferred_text(p))
-        {
-            return Some(text);
-        }
-        if let Some(text) = part
-            .subparts
-            .iter()
-            .find(|p| p.ctype.mimetype.eq_ignore_ascii_case("text/html"))
-            .and_then(|p| extract_preferred_text(p))
-        {
-            return Some(text);
-        }
-    }
-
-    // For other multiparts, walk children and return the first successful extraction.
-    for child in &part.subparts {
-        if let Some(text) = extract_preferred_text(child) {
-            return Some(text);
-        }
-    }
-
-    None
+/// Plain text from HTML with regexes alone (linear time, whatever the nesting): scripts,
+/// styles and comments dropped, block ends turned into line breaks, other tags removed, common
+/// entities decoded and blank runs squeezed. Rougher than html2text: no tables, lists or link
+/// footnotes.
+pub fn strip_tags(html: &str) -> String {
+    static HIDDEN_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?is)<!--.*?-->|<script\b.*?</script\s*>|<style\b.*?</style\s*>").unwrap()
+    });
+    static BREAK_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"(?i)<br\s*/?>|</(p|div|tr|li|h[1-6]|table|blockquote)\s*>").unwrap()
+    });
+    static TAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+    static BLANKS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n[ \t]*(\n[ \t]*)+").unwrap());
+    static SPACES_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[ \t]+").unwrap());
+
+    let text = HIDDEN_RE.replace_all(html, "");
+    let text = BREAK_RE.replace_all(&text, "\n");
+    let text = TAG_RE.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let text = SPACES_RE.replace_all(&text, " ");
+    let text = BLANKS_RE.replace_all(&text, "\n\n");
+    text.trim().to_string()
 }
 
 fn clean_urls_in_text(body: &str) -> String {
@@ -302,7 +519,9 @@ fn clean_urls_in_text(body: &str) -> String {
         .into_owned()
 }
 
-fn clean_url(raw: &str) -> String {
+/// Drops tracking query parameters and unwraps known redirectors. URLs with nothing to drop
+/// come back byte-for-byte, and cleaning is idempotent.
+pub fn clean_url(raw: &str) -> String {
     // Exact matches to strip quickly.
     const DROP_EXACT: &[&str] = &[
         "gclid",
@@ -370,6 +589,10 @@ fn clean_url(raw: &str) -> String {
         })
         .map(|(k, v)| (k.into_owned(), v.into_owned()))
         .collect();
+    // Re-serializing would still normalize the URL (percent-encoding, trailing '/').
+    if kept.len() == parsed.query_pairs().count() {
+        return raw.to_string();
+    }
 
     if kept.is_empty() {
         parsed.set_query(None);
@@ -438,5 +661,7 @@ pub fn build_body_record(
         mime_summary: sanitized.mime_summary,
         attachments_json: sanitized.attachments_json,
         sanitized_at: Some(crate::types::now_ts()),
+        sanitizer_version: Some(SANITIZER_VERSION),
+        degraded: sanitized.degraded,
     }
 }
@@ -0,0 +1,94 @@
+//! `otto resanitize`: re-runs `sanitize_message` over stored raw messages so bodies sanitized by an
+//! older `SANITIZER_VERSION` pick up sanitizer improvements without a re-download. Works in
+//! batches (parsed in parallel with rayon, like the sync body phase) and pages by message id, so
+//! an interrupted run simply continues with the rows still behind on the next one.
+use anyhow::{Context, Result};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use super::{SANITIZER_VERSION, build_body_record, sanitize_message, sanitize_message_with_budget};
+use crate::storage::MailStore;
+use crate::types::BodyRecord;
+
+const BATCH_SIZE: usize = 200;
+
+/// Rebuilds the account's bodies sanitized before the current version (every stored body with
+/// `all`). Messages that no longer parse keep their old text. Returns bodies rewritten.
+pub async fn resanitize_account(
+    db: &dyn MailStore,
+    account_id: &str,
+    all: bool,
+    cancel: &CancellationToken,
+) -> Result<usize> {
+    let below_version = if all { i64::MAX } else { SANITIZER_VERSION };
+    let mut after = String::new();
+    let mut rewritten = 0;
+    loop {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let batch = db
+            .load_bodies_to_resanitize(account_id, below_version, &after, BATCH_SIZE)
+            .await?;
+        let Some((last, _)) = batch.last() else {
+            break;
+        };
+        after = last.clone();
+        let loaded = batch.len();
+
+        let bodies: Vec<(bool, BodyRecord)> = tokio::task::spawn_blocking(move || {
+            use rayon::prelude::*;
+            batch
+                .into_par_iter()
+                .filter_map(|(message_id, raw)| {
+                    let parsed = match mailparse::parse_mail(&raw) {
+                        Ok(parsed) => parsed,
+                        Err(e) => {
+                            warn!(message_id = %message_id, error = %e, "Stored message no longer parses");
+                            return None;
+                        }
+                    };
+                    let sanitized = sanitize_message(&parsed, &raw);
+                    let has_attachments = sanitized.has_attachments;
+                    Some((
+                        has_attachments,
+                        build_body_record(&message_id, None, sanitized),
+                    ))
+                })
+                .collect()
+        })
+        .await
+        .context("parallel resanitize task panicked")?;
+
+        db.store_resanitized_bodies(account_id, &bodies).await?;
+        rewritten += bodies.len();
+        info!(account = %account_id, loaded, rewritten, "Resanitized body batch");
+    }
+    Ok(rewritten)
+}
+
+/// Re-renders one message's body with no html2text time budget, replacing a degraded
+/// (tag-stripped) rendering. Returns false when its raw message isn't stored.
+pub async fn rerender_message(
+    db: &dyn MailStore,
+    account_id: &str,
+    message_id: &str,
+) -> Result<bool> {
+    let raw = db.load_raw_message(account_id, message_id).await?;
+    let Some(raw) = raw else {
+        return Ok(false);
+    };
+    let id = message_id.to_string();
+    let body = tokio::task::spawn_blocking(move || -> Result<(bool, BodyRecord)> {
+        let parsed = mailparse::parse_mail(&raw).context("parsing stored message")?;
+        let sanitized = sanitize_message_with_budget(&parsed, &raw, None);
+        Ok((
+            sanitized.has_attachments,
+            build_body_record(&id, None, sanitized),
+        ))
+    })
+    .await
+    .context("re-render task panicked")??;
+    db.store_resanitized_bodies(account_id, &[body]).await?;
+    Ok(true)
+}
@@ -0,0 +1,174 @@
+//! `otto share`: one cached thread as a single self-contained, read-only HTML page, for handing
+//! a conversation to someone outside email. Everything comes from the cache; bodies are the
+//! sanitized text (never the sender's HTML), every header and body is escaped, the page carries
+//! no scripts and a CSP that forbids loading anything, and Bcc is left out. Attachments are
+//! listed by name and size, and embedded as `data:` downloads only when asked for.
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use base64::Engine;
+
+use crate::address::friendly_from;
+use crate::encoded_words::decode_mime_words;
+use crate::sanitize;
+use crate::timefmt::{DisplayTz, format_absolute};
+use crate::types::{BodyRecord, MessageRecord};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShareOptions {
+    /// Embed the attachments (decoded from the cached raw message) as `data:` links.
+    pub attachments: bool,
+    pub tz: DisplayTz,
+}
+
+const STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:46rem;margin:2rem auto;\
+padding:0 1rem;color:#222;background:#fff}h1{font-size:1.3rem}\
+article{border-top:1px solid #ddd;padding:1rem 0}dl{display:grid;\
+grid-template-columns:max-content 1fr;gap:.1rem .8rem;margin:0 0 .8rem;color:#555;\
+font-size:.9rem}dt{font-weight:600}dd{margin:0;overflow-wrap:anywhere}\
+.body{white-space:pre-wrap;overflow-wrap:anywhere}.missing{color:#888;font-style:italic}\
+ul.files{font-size:.9rem}footer{color:#888;font-size:.8rem;border-top:1px solid #ddd;\
+padding-top:.5rem}";
+
+/// Renders the thread's messages (any order, duplicates from several folders allowed) oldest
+/// first as a complete HTML document.
+pub fn render_thread_html(
+    messages: &[(MessageRecord, Option<BodyRecord>)],
+    options: ShareOptions,
+) -> String {
+    let mut seen = HashSet::new();
+    let mut thread: Vec<&(MessageRecord, Option<BodyRecord>)> = messages
+        .iter()
+        .filter(|(message, _)| {
+            seen.insert(
+                message
+                    .message_id_header
+                    .clone()
+                    .unwrap_or_else(|| message.id.clone()),
+            )
+        })
+        .collect();
+    thread.sort_by_key(|(message, _)| (message.internal_date, message.id.clone()));
+
+    let title = thread
+        .first()
+        .and_then(|(message, _)| message.subject.as_deref())
+        .map(decode_mime_words)
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "(no subject)".to_string());
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta http-equiv=\"Content-Security-Policy\" content=\"default-src 'none'; \
+         style-src 'unsafe-inline'; img-src data:\">\n\
+         <meta name=\"referrer\" content=\"no-referrer\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape(&title),
+        STYLE,
+        escape(&title)
+    );
+    for (message, body) in &thread {
+        render_message(&mut out, message, body.as_ref(), options);
+    }
+    let _ = write!(
+        out,
+        "<footer>{} message(s), exported read-only by otto.</footer>\n</body>\n</html>\n",
+        thread.len()
+    );
+    out
+}
+
+fn render_message(
+    out: &mut String,
+    message: &MessageRecord,
+    body: Option<&BodyRecord>,
+    options: ShareOptions,
+) {
+    out.push_str("<article>\n<dl>\n");
+    let from = friendly_from(message.from_name.as_deref(), message.from.as_deref());
+    header_row(out, "From", Some(&from));
+    header_row(out, "To", message.to.as_deref());
+    header_row(out, "Cc", message.cc.as_deref());
+    let date = message
+        .internal_date
+        .map(|ts| format_absolute(ts, options.tz));
+    header_row(out, "Date", date.as_deref());
+    let subject = message.subject.as_deref().map(decode_mime_words);
+    header_row(out, "Subject", subject.as_deref());
+    out.push_str("</dl>\n");
+
+    match body.and_then(|b| b.sanitized_text.as_deref()) {
+        Some(text) => {
+            let _ = writeln!(out, "<div class=\"body\">{}</div>", escape(text.trim_end()));
+        }
+        None => out.push_str("<p class=\"missing\">(body not downloaded)</p>\n"),
+    }
+
+    let parsed_raw = body
+        .and_then(|b| b.raw_rfc822.as_deref())
+        .and_then(|raw| mailparse::parse_mail(raw).ok());
+    let files = parsed_raw
+        .as_ref()
+        .map(sanitize::attachments)
+        .unwrap_or_default();
+    if !files.is_empty() {
+        out.push_str("<ul class=\"files\">\n");
+        for file in &files {
+            let name = file.filename.as_deref().unwrap_or("(unnamed)");
+            let label = format!(
+                "{} ({}, {})",
+                name,
+                file.mime_type,
+                human_size(file.data.len())
+            );
+            if options.attachments {
+                let _ = writeln!(
+                    out,
+                    "<li><a download=\"{}\" href=\"data:{};base64,{}\">{}</a></li>",
+                    escape(name),
+                    escape(&file.mime_type),
+                    base64::engine::general_purpose::STANDARD.encode(&file.data),
+                    escape(&label)
+                );
+            } else {
+                let _ = writeln!(out, "<li>{}</li>", escape(&label));
+            }
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</article>\n");
+}
+
+fn header_row(out: &mut String, name: &str, value: Option<&str>) {
+    if let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) {
+        let _ = writeln!(out, "<dt>{}</dt><dd>{}</dd>", name, escape(value));
+    }
+}
+
+fn human_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// Escapes text for HTML element content and quoted attribute values.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,269 @@
+//! Smart folders: saved queries over the local cache, listed after the real folders in the TUI
+//! sidebar. Queries run in Rust against loaded (decrypted) records, so they also work for
+//! accounts with column encryption, and membership follows every list refresh.
+//!
+//! A query is a list of terms that must all match; `-term` negates one:
+//! `is:unread|read|starred`, `has:attachment`, `from:`, `to:`, `cc:`, `bcc:`, `subject:`,
+//! `folder:`, `label:`, `after:YYYY-MM-DD`, `before:YYYY-MM-DD`, `newer:<N>d`,
+//! `date:today|this-week|this-month`, `note:` / `has:note` (the message's private note, see
+//! `storage::notes`), or a bare word matched against subject, sender and note.
+//! Text values are case-insensitive substrings; commas list alternatives
+//! (`from:alice@example.com,bob@example.com`), and double quotes keep spaces.
+//!
+//! `to:` covers To and Cc like Gmail's; `cc:` and `bcc:` match their own list only, against each
+//! parsed recipient's name and address, so `to:me@example.com -cc:me@example.com` finds mail
+//! where that address is in To but not Cc.
+use anyhow::{Result, anyhow, bail};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::address::parse_address_list;
+use crate::timefmt::{DisplayTz, local_date};
+use crate::types::MessageRecord;
+
+/// A named saved query (stored on the account, in sidebar order).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SmartFolder {
+    pub name: String,
+    pub query: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SmartQuery {
+    terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Term {
+    negated: bool,
+    kind: TermKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum TermKind {
+    Unread,
+    Read,
+    Starred,
+    Attachment,
+    From(Vec<String>),
+    To(Vec<String>),
+    Cc(Vec<String>),
+    Bcc(Vec<String>),
+    Subject(Vec<String>),
+    Folder(Vec<String>),
+    Label(Vec<String>),
+    After(NaiveDate),
+    Before(NaiveDate),
+    NewerDays(i64),
+    Today,
+    ThisWeek,
+    ThisMonth,
+    Note(Vec<String>),
+    HasNote,
+    Text(Vec<String>),
+}
+
+impl SmartQuery {
+    pub fn parse(raw: &str) -> Result<Self> {
+        let terms = tokenize(raw)?
+            .into_iter()
+            .map(|token| parse_term(&token))
+            .collect::<Result<Vec<_>>>()?;
+        if terms.is_empty() {
+            bail!("empty query");
+        }
+        Ok(Self { terms })
+    }
+
+    /// Whether `msg` matches every term; `now` (unix seconds) and `tz` anchor relative dates.
+    pub fn matches(&self, msg: &MessageRecord, now: i64, tz: DisplayTz) -> bool {
+        self.matches_with_note(msg, None, now, tz)
+    }
+
+    /// [`Self::matches`] for a message carrying the private note `note`.
+    pub fn matches_with_note(
+        &self,
+        msg: &MessageRecord,
+        note: Option<&str>,
+        now: i64,
+        tz: DisplayTz,
+    ) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term.kind.matches(msg, note, now, tz) != term.negated)
+    }
+}
+
+impl TermKind {
+    fn matches(&self, msg: &MessageRecord, note: Option<&str>, now: i64, tz: DisplayTz) -> bool {
+        let has_flag = |name: &str| {
+            msg.flags
+                .iter()
+                .any(|f| f.trim_start_matches('\\').eq_ignore_ascii_case(name))
+        };
+        let date = || msg.internal_date.and_then(|ts| local_date(ts, tz));
+        let today = || local_date(now, tz);
+
+        match self {
+            TermKind::Unread => !has_flag("Seen"),
+            TermKind::Read => has_flag("Seen"),
+            TermKind::Starred => has_flag("Flagged"),
+            TermKind::Attachment => msg.has_attachments,
+            TermKind::From(needles) => {
+                any_contains(needles, msg.from.as_deref())
+                    || any_contains(needles, msg.from_name.as_deref())
+            }
+            TermKind::To(needles) => {
+                any_recipient(needles, msg.to.as_deref())
+                    || any_recipient(needles, msg.cc.as_deref())
+            }
+            TermKind::Cc(needles) => any_recipient(needles, msg.cc.as_deref()),
+            TermKind::Bcc(needles) => any_recipient(needles, msg.bcc.as_deref()),
+            TermKind::Subject(needles) => any_contains(needles, msg.subject.as_deref()),
+            // Gmail labels count as folders too (`folder:inbox` matches `\Inbox` in All Mail).
+            TermKind::Folder(names) => names.iter().any(|n| {
+                n.eq_ignore_ascii_case(&msg.folder)
+                    || msg
+                        .labels
+                        .iter()
+                        .any(|l| n.eq_ignore_ascii_case(l.trim_start_matches('\\')))
+            }),
+            TermKind::Label(names) => msg.labels.iter().any(|label| {
+                let label = label.trim_start_matches('\\');
+                names.iter().any(|n| n.eq_ignore_ascii_case(label))
+            }),
+            TermKind::After(day) => date().is_some_and(|d| d >= *day),
+            TermKind::Before(day) => date().is_some_and(|d| d < *day),
+            TermKind::NewerDays(days) => msg
+                .internal_date
+                .is_some_and(|ts| now.saturating_sub(ts) <= days * 86_400),
+            TermKind::Today => date().is_some_and(|d| Some(d) == today()),
+            TermKind::ThisWeek => date()
+                .zip(today())
+                .is_some_and(|(d, t)| d.iso_week() == t.iso_week()),
+            TermKind::ThisMonth => date()
+                .zip(today())
+                .is_some_and(|(d, t)| (d.year(), d.month()) == (t.year(), t.month())),
+            TermKind::Note(needles) => any_contains(needles, note),
+            TermKind::HasNote => note.is_some(),
+            TermKind::Text(needles) => {
+                any_contains(needles, msg.subject.as_deref())
+                    || any_contains(needles, msg.from.as_deref())
+                    || any_contains(needles, msg.from_name.as_deref())
+                    || any_contains(needles, note)
+            }
+        }
+    }
+}
+
+fn any_contains(needles: &[String], haystack: Option<&str>) -> bool {
+    let Some(haystack) = haystack else {
+        return false;
+    };
+    let haystack = haystack.to_lowercase();
+    needles.iter().any(|needle| haystack.contains(needle))
+}
+
+/// Matches against each mailbox of a recipient list, falling back to the raw value when it
+/// does not parse.
+fn any_recipient(needles: &[String], list: Option<&str>) -> bool {
+    let Some(list) = list else {
+        return false;
+    };
+    let mailboxes = parse_address_list(list);
+    if mailboxes.is_empty() {
+        return any_contains(needles, Some(list));
+    }
+    mailboxes.iter().any(|mailbox| {
+        any_contains(needles, Some(&mailbox.addr)) || any_contains(needles, mailbox.name.as_deref())
+    })
+}
+
+/// Splits on whitespace outside double quotes; quotes are dropped.
+fn tokenize(raw: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for ch in raw.chars() {
+        match ch {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if quoted {
+        bail!("unclosed quote in query");
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+fn parse_term(token: &str) -> Result<Term> {
+    let (negated, token) = match token.strip_prefix('-') {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+    let values = |raw: &str| -> Result<Vec<String>> {
+        let values: Vec<String> = raw
+            .split(',')
+            .map(|v| v.trim().to_lowercase())
+            .filter(|v| !v.is_empty())
+            .collect();
+        if values.is_empty() {
+            bail!("{:?} needs a value", token);
+        }
+        Ok(values)
+    };
+    let day = |raw: &str| {
+        NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .map_err(|_| anyhow!("{:?}: expected a YYYY-MM-DD date", token))
+    };
+
+    let kind = match token.split_once(':') {
+        None => TermKind::Text(values(token)?),
+        Some((key, value)) => match key.to_ascii_lowercase().as_str() {
+            "is" => match value.to_ascii_lowercase().as_str() {
+                "unread" => TermKind::Unread,
+                "read" => TermKind::Read,
+                "starred" | "flagged" => TermKind::Starred,
+                _ => bail!("{:?}: expected is:unread, is:read or is:starred", token),
+            },
+            "has" if value.eq_ignore_ascii_case("attachment") => TermKind::Attachment,
+            "has" if value.eq_ignore_ascii_case("note") => TermKind::HasNote,
+            "note" => TermKind::Note(values(value)?),
+            "from" => TermKind::From(values(value)?),
+            "to" => TermKind::To(values(value)?),
+            "cc" => TermKind::Cc(values(value)?),
+            "bcc" => TermKind::Bcc(values(value)?),
+            "subject" => TermKind::Subject(values(value)?),
+            "folder" => TermKind::Folder(values(value)?),
+            "label" => TermKind::Label(values(value)?),
+            "after" => TermKind::After(day(value)?),
+            "before" => TermKind::Before(day(value)?),
+            "newer" => TermKind::NewerDays(
+                value
+                    .strip_suffix('d')
+                    .and_then(|n| n.parse::<i64>().ok())
+                    .filter(|n| *n >= 0)
+                    .ok_or_else(|| anyhow!("{:?}: expected newer:<N>d", token))?,
+            ),
+            "date" => match value.to_ascii_lowercase().as_str() {
+                "today" => TermKind::Today,
+                "this-week" => TermKind::ThisWeek,
+                "this-month" => TermKind::ThisMonth,
+                _ => bail!(
+                    "{:?}: expected date:today, date:this-week or date:this-month",
+                    token
+                ),
+            },
+            _ => bail!("unknown query term {:?}", token),
+        },
+    };
+    Ok(Term { negated, kind })
+}
@@ -0,0 +1,248 @@
+//! Minimal SMTP submission client (implicit TLS, AUTH XOAUTH2) for sending composed mail with
+//! the same OAuth token IMAP uses. Gmail files messages submitted this way in Sent itself.
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+
+use crate::credentials::imap_secret;
+use crate::imap::tls_handshake;
+use crate::types::{Account, TlsPolicy};
+
+/// Submission server (implicit TLS only, usually port 465).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SmtpEndpoint {
+    pub host: String,
+    pub port: u16,
+}
+
+impl Default for SmtpEndpoint {
+    fn default() -> Self {
+        Self {
+            host: "smtp.gmail.com".to_string(),
+            port: 465,
+        }
+    }
+}
+
+/// A negative server reply. 5xx replies are permanent: retrying the same message will not help.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("SMTP {command} rejected: {code} {text}")]
+pub struct SmtpRejected {
+    pub command: String,
+    pub code: u16,
+    pub text: String,
+}
+
+impl SmtpRejected {
+    pub fn is_permanent(&self) -> bool {
+        self.code >= 500
+    }
+}
+
+pub struct SmtpClient<S> {
+    stream: BufReader<S>,
+}
+
+impl SmtpClient<TlsStream<TcpStream>> {
+    /// Connects over implicit TLS (within the account's `tls_policy`) and authenticates `user`
+    /// with an OAuth access token.
+    pub async fn connect(
+        endpoint: &SmtpEndpoint,
+        tls_policy: &TlsPolicy,
+        user: &str,
+        access_token: &str,
+    ) -> Result<Self> {
+        let address = format!("{}:{}", endpoint.host, endpoint.port);
+        let tcp = TcpStream::connect((endpoint.host.as_str(), endpoint.port))
+            .await
+            .with_context(|| format!("connecting to {}", address))?;
+        let tls = tls_handshake(&endpoint.host, None, None, None, tls_policy, tcp)
+            .await
+            .context("starting TLS for SMTP")?;
+        Self::start(tls, user, access_token).await
+    }
+}
+
+/// Sends one message from `account`'s own address over a fresh connection. The client signs in
+/// with XOAUTH2, so accounts using another credential are refused.
+pub async fn send_from_account(
+    endpoint: &SmtpEndpoint,
+    account: &Account,
+    recipients: &[String],
+    message: &[u8],
+) -> Result<()> {
+    if !account.settings.credential.is_oauth() {
+        bail!(
+            "SMTP sign-in uses OAuth; {} uses a {}",
+            account.email,
+            account.settings.credential.describe()
+        );
+    }
+    let token = imap_secret(account).await?;
+    let mut client = SmtpClient::connect(
+        endpoint,
+        &account.settings.tls_policy,
+        &account.email,
+        &token,
+    )
+    .await?;
+    let result = client.send(&account.email, recipients, message).await;
+    let _ = client.quit().await;
+    result
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SmtpClient<S> {
+    /// Reads the greeting, sends EHLO and authenticates over an established stream.
+    pub async fn start(stream: S, user: &str, access_token: &str) -> Result<Self> {
+        let mut client = Self {
+            stream: BufReader::new(stream),
+        };
+        client.expect("greeting", &[220]).await?;
+        client.command("EHLO otto", &[250]).await?;
+
+        let token = format!("user={}\x01auth=Bearer {}\x01\x01", user, access_token);
+        let encoded = base64::engine::general_purpose::STANDARD.encode(token);
+        let (code, text) = client
+            .roundtrip(&format!("AUTH XOAUTH2 {}", encoded))
+            .await?;
+        match code {
+            235 => Ok(client),
+            // The server explains the failure in a 334 challenge, then answers an empty line.
+            334 => {
+                let (code, _) = client.roundtrip("").await?;
+                let detail = base64::engine::general_purpose::STANDARD
+                    .decode(text.trim())
+                    .ok()
+                    .and_then(|raw| String::from_utf8(raw).ok())
+                    .unwrap_or(text);
+                Err(SmtpRejected {
+                    command: "AUTH XOAUTH2".to_string(),
+                    code,
+                    text: detail,
+                }
+                .into())
+            }
+            code => Err(SmtpRejected {
+                command: "AUTH XOAUTH2".to_string(),
+                code,
+                text,
+            }
+            .into()),
+        }
+    }
+
+    /// Submits `message` (CRLF RFC822) from `from` to every address in `recipients`.
+    pub async fn send(&mut self, from: &str, recipients: &[String], message: &[u8]) -> Result<()> {
+        self.command(&format!("MAIL FROM:<{}>", from), &[250])
+            .await?;
+        for recipient in recipients {
+            self.command(&format!("RCPT TO:<{}>", recipient), &[250, 251])
+                .await?;
+        }
+        self.command("DATA", &[354]).await?;
+
+        let mut data = dot_stuff(message);
+        if !data.ends_with(b"\r\n") {
+            data.extend_from_slice(b"\r\n");
+        }
+        data.extend_from_slice(b".\r\n");
+        self.stream
+            .get_mut()
+            .write_all(&data)
+            .await
+            .context("writing message data")?;
+        self.expect("message data", &[250]).await?;
+        Ok(())
+    }
+
+    /// Abandons a half-done transaction (after a rejected MAIL or RCPT).
+    pub async fn reset(&mut self) -> Result<()> {
+        self.command("RSET", &[250]).await?;
+        Ok(())
+    }
+
+    pub async fn quit(mut self) -> Result<()> {
+        self.command("QUIT", &[221]).await?;
+        Ok(())
+    }
+
+    async fn command(&mut self, line: &str, ok: &[u16]) -> Result<String> {
+        let (code, text) = self.roundtrip(line).await?;
+        if ok.contains(&code) {
+            return Ok(text);
+        }
+        // Keep tokens out of errors.
+        let command = line.split(' ').next().unwrap_or(line).to_string();
+        Err(SmtpRejected {
+            command,
+            code,
+            text,
+        }
+        .into())
+    }
+
+    async fn roundtrip(&mut self, line: &str) -> Result<(u16, String)> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .await
+            .context("writing SMTP command")?;
+        stream.flush().await.context("flushing SMTP command")?;
+        self.read_reply().await
+    }
+
+    async fn expect(&mut self, what: &str, ok: &[u16]) -> Result<()> {
+        let (code, text) = self.read_reply().await?;
+        if ok.contains(&code) {
+            return Ok(());
+        }
+        Err(SmtpRejected {
+            command: what.to_string(),
+            code,
+            text,
+        }
+        .into())
+    }
+
+    /// One reply, joining the text of multi-line (`250-...`) replies.
+    async fn read_reply(&mut self) -> Result<(u16, String)> {
+        let mut text = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = self
+                .stream
+                .read_line(&mut line)
+                .await
+                .context("reading SMTP reply")?;
+            if read == 0 {
+                return Err(anyhow!("SMTP server closed the connection"));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let code = line
+                .get(..3)
+                .and_then(|c| c.parse::<u16>().ok())
+                .ok_or_else(|| anyhow!("malformed SMTP reply {:?}", line))?;
+            text.push(line.get(4..).unwrap_or("").to_string());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, text.join("\n")));
+            }
+        }
+    }
+}
+
+/// Doubles a leading `.` on every line so the message body cannot end DATA early.
+fn dot_stuff(message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(message.len() + 16);
+    let mut line_start = true;
+    for &byte in message {
+        if line_start && byte == b'.' {
+            out.push(b'.');
+        }
+        out.push(byte);
+        line_start = byte == b'\n';
+    }
+    out
+}
@@ -0,0 +1,249 @@
+//! `otto status`: unread counts, sync freshness and storage quota for desktop status bars
+//! (waybar, i3blocks) or scripts (JSON). Everything comes from the local cache, with no IMAP or OAuth, so a bar
+//! can poll it every few seconds. Counts are the ones the server reported at the last sync
+//! (`STATUS`), or a scan of the cached messages for folders without them and while local
+//! changes are queued that the server hasn't seen.
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde_json::json;
+
+use crate::storage::MailStore;
+use crate::sync::{FolderLabels, synced_folders};
+use crate::timefmt::{DisplayTz, format_relative};
+use crate::types::{Account, AccountQuota, FolderRole};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum StatusFormat {
+    /// One JSON object for a waybar `custom` module with `"return-type": "json"`.
+    Waybar,
+    /// `full_text`, `short_text` and (when stale) `color` lines for an i3blocks block.
+    I3blocks,
+    /// Per-account, per-folder counts for scripts.
+    Json,
+}
+
+/// What a status bar shows for one account.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountStatus {
+    pub email: String,
+    /// Unread messages per enabled folder (folders without unread mail included as 0).
+    pub unread: BTreeMap<String, u32>,
+    /// Messages per enabled folder, for folders with server-reported counts.
+    pub total: BTreeMap<String, u32>,
+    /// Oldest last-sync time over the synced folders; `None` if one never synced.
+    pub last_sync_ts: Option<i64>,
+    /// No full sync within two poll intervals (never set for paused accounts).
+    pub stale: bool,
+    /// Storage usage from the last sync that read it.
+    pub quota: Option<AccountQuota>,
+}
+
+impl AccountStatus {
+    pub fn inbox_unread(&self) -> u32 {
+        self.unread
+            .iter()
+            .filter(|(folder, _)| folder.eq_ignore_ascii_case("INBOX"))
+            .map(|(_, n)| n)
+            .sum()
+    }
+}
+
+/// Reads each account's status from the cache.
+pub async fn collect(
+    db: &dyn MailStore,
+    accounts: &[Account],
+    now: i64,
+) -> Result<Vec<AccountStatus>> {
+    let mut out = Vec::new();
+    for account in accounts {
+        let folders = db.list_folders(&account.id).await?;
+        let enabled: Vec<&String> = account.settings.enabled_folders().collect();
+        let server = db.load_folder_counts(&account.id).await?;
+        let total = enabled
+            .iter()
+            .filter_map(|folder| server.get(*folder).map(|c| ((*folder).clone(), c.messages)))
+            .collect();
+        let server_unread: Option<BTreeMap<String, u32>> =
+            if db.list_pending_ops(&account.id).await?.is_empty() {
+                enabled
+                    .iter()
+                    .map(|folder| server.get(*folder).map(|c| ((*folder).clone(), c.unseen)))
+                    .collect()
+            } else {
+                None
+            };
+        let unread = match server_unread {
+            Some(unread) => unread,
+            None => scan_unread(db, account, &enabled).await?,
+        };
+        let synced: Option<Vec<i64>> = synced_folders(db, account)
+            .await?
+            .iter()
+            .map(|folder| {
+                folders
+                    .iter()
+                    .find(|f| f.name == *folder)
+                    .and_then(|f| f.last_sync_ts)
+            })
+            .collect();
+        let last_sync_ts = synced.and_then(|ts| ts.into_iter().min());
+        let max_age = 2 * 60 * i64::from(account.settings.poll_interval_minutes.max(1));
+        out.push(AccountStatus {
+            email: account.email.clone(),
+            unread,
+            total,
+            last_sync_ts,
+            stale: account.settings.enabled && last_sync_ts.is_none_or(|ts| now - ts > max_age),
+            quota: db.load_account_quota(&account.id).await?,
+        });
+    }
+    Ok(out)
+}
+
+/// Unread counts of `enabled` from the cached messages.
+async fn scan_unread(
+    db: &dyn MailStore,
+    account: &Account,
+    enabled: &[&String],
+) -> Result<BTreeMap<String, u32>> {
+    let counts = db.unread_counts(&account.id).await?;
+    // In All Mail mode most rows sit in All Mail; their labels say which folder they're in.
+    let (label_counts, labels) = if account.settings.all_mail_mode {
+        let all_mail = db.role_folder(&account.id, FolderRole::All).await?;
+        (
+            db.unread_label_counts(&account.id, &all_mail).await?,
+            Some(FolderLabels::load(db, &account.id).await?),
+        )
+    } else {
+        (HashMap::new(), None)
+    };
+    Ok(enabled
+        .iter()
+        .map(|folder| {
+            let by_label = labels
+                .as_ref()
+                .and_then(|l| label_counts.get(&l.label_for(folder)))
+                .copied()
+                .unwrap_or(0);
+            (
+                (*folder).clone(),
+                counts.get(*folder).copied().unwrap_or(0) + by_label,
+            )
+        })
+        .collect())
+}
+
+/// `1.2 GB of 15 GB (8%)`, or `1.2 GB used` without a storage limit; `None` when the server
+/// reported no storage figure.
+pub fn quota_summary(quota: &AccountQuota) -> Option<String> {
+    let used = format_bytes(quota.storage_used?);
+    Some(match (quota.storage_limit, quota.storage_percent()) {
+        (Some(limit), Some(percent)) => {
+            format!("{} of {} ({}%)", used, format_bytes(limit), percent)
+        }
+        _ => format!("{} used", used),
+    })
+}
+
+/// Decimal units, as providers quote plan sizes.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit + 1 < UNITS.len() {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if value >= 100.0 || value.fract() < 0.05 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Renders `statuses` for `format`; `now` and `tz` only affect the human-readable ages.
+pub fn render(
+    format: StatusFormat,
+    statuses: &[AccountStatus],
+    now: DateTime<Utc>,
+    tz: DisplayTz,
+) -> String {
+    let inbox_unread: u32 = statuses.iter().map(AccountStatus::inbox_unread).sum();
+    let stale = statuses.iter().any(|s| s.stale);
+    match format {
+        StatusFormat::Json => json!({
+            "unread": inbox_unread,
+            "stale": stale,
+            "accounts": statuses
+                .iter()
+                .map(|s| json!({
+                    "email": s.email,
+                    "inbox_unread": s.inbox_unread(),
+                    "unread": s.unread,
+                    "total": s.total,
+                    "last_sync": s.last_sync_ts,
+                    "stale": s.stale,
+                    "quota": s.quota.as_ref().map(|q| json!({
+                        "storage_used": q.storage_used,
+                        "storage_limit": q.storage_limit,
+                        "messages_used": q.messages_used,
+                        "messages_limit": q.messages_limit,
+                        "fetched_at": q.fetched_at,
+                    })),
+                }))
+                .collect::<Vec<_>>(),
+        })
+        .to_string(),
+        StatusFormat::Waybar => {
+            let tooltip = statuses
+                .iter()
+                .map(|s| {
+                    format!(
+                        "{}: {} unread, synced {}{}{}",
+                        s.email,
+                        s.inbox_unread(),
+                        match s.last_sync_ts {
+                            Some(_) => format_relative(s.last_sync_ts, now, tz),
+                            None => "never".to_string(),
+                        },
+                        if s.stale { " (stale)" } else { "" },
+                        s.quota
+                            .as_ref()
+                            .and_then(quota_summary)
+                            .map(|q| format!(", {}", q))
+                            .unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut class = vec![if inbox_unread > 0 { "unread" } else { "read" }];
+            if stale {
+                class.push("stale");
+            }
+            json!({
+                "text": inbox_unread.to_string(),
+                "alt": class[0],
+                "tooltip": tooltip,
+                "class": class,
+            })
+            .to_string()
+        }
+        StatusFormat::I3blocks => {
+            let marker = if stale { " (stale)" } else { "" };
+            let mut out = format!(
+                "mail {}{}\n{}{}\n",
+                inbox_unread, marker, inbox_unread, marker
+            );
+            if stale {
+                out.push_str("#999999\n");
+            }
+            out
+        }
+    }
+}
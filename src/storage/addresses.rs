@@ -0,0 +1,331 @@
+//! Parsed recipients (`message_addresses` table): one row per mailbox of a message's To, Cc and
+//! Bcc lists, so filters can ask which field an address appeared in ("in To but not Cc")
+//! instead of substring-matching the raw header strings kept on `messages`. Recipient columns
+//! are not covered by column encryption, so neither is this table.
+use anyhow::{Context, Result};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+
+use crate::address::parse_address_list;
+use crate::types::MessageRecord;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AddressField {
+    To,
+    Cc,
+    Bcc,
+}
+
+impl AddressField {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AddressField::To => "to",
+            AddressField::Cc => "cc",
+            AddressField::Bcc => "bcc",
+        }
+    }
+
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "to" => Some(AddressField::To),
+            "cc" => Some(AddressField::Cc),
+            "bcc" => Some(AddressField::Bcc),
+            _ => None,
+        }
+    }
+}
+
+/// One mailbox from a recipient list; `addr` is stored lowercased.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recipient {
+    pub field: AddressField,
+    pub name: Option<String>,
+    pub addr: String,
+}
+
+/// An address offered while typing a recipient, from the account's cached recipient lists.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddressSuggestion {
+    /// Display name from the address's most recent appearance.
+    pub name: Option<String>,
+    pub addr: String,
+    /// Messages listing the address.
+    pub uses: i64,
+    /// Newest of those messages (internal date, else when it was cached).
+    pub last_seen: i64,
+}
+
+impl AddressSuggestion {
+    /// The address as it goes into a To or Cc field; names with list punctuation are quoted.
+    pub fn recipient(&self) -> String {
+        match self
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+        {
+            Some(name) if name.contains(|c: char| ",;<>@\"".contains(c)) => {
+                format!("\"{}\" <{}>", name.replace('"', ""), self.addr)
+            }
+            Some(name) => format!("{} <{}>", name, self.addr),
+            None => self.addr.clone(),
+        }
+    }
+}
+
+/// Parses the message's To, Cc and Bcc values in header order.
+pub fn recipients_of(message: &MessageRecord) -> Vec<Recipient> {
+    parse_recipients(
+        message.to.as_deref(),
+        message.cc.as_deref(),
+        message.bcc.as_deref(),
+    )
+}
+
+fn parse_recipients(to: Option<&str>, cc: Option<&str>, bcc: Option<&str>) -> Vec<Recipient> {
+    [
+        (AddressField::To, to),
+        (AddressField::Cc, cc),
+        (AddressField::Bcc, bcc),
+    ]
+    .into_iter()
+    .flat_map(|(field, raw)| {
+        raw.map(parse_address_list)
+            .unwrap_or_default()
+            .into_iter()
+            .map(move |mailbox| Recipient {
+                field,
+                name: mailbox.name,
+                addr: mailbox.addr.trim().to_lowercase(),
+            })
+    })
+    .collect()
+}
+
+/// Creates the table; the first time, also indexes the recipients of messages already cached.
+pub(crate) async fn ensure_addresses_table(pool: &SqlitePool) -> Result<()> {
+    let exists: Option<String> = sqlx::query_scalar(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'message_addresses'",
+    )
+    .fetch_optional(pool)
+    .await
+    .context("checking for message_addresses table")?;
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS message_addresses (
+            message_id TEXT NOT NULL,
+            field TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            name TEXT,
+            address TEXT NOT NULL,
+            PRIMARY KEY (message_id, field, position),
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_message_addresses_address ON message_addresses(address);
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating message_addresses table")?;
+    if exists.is_none() {
+        backfill(pool).await?;
+    }
+    Ok(())
+}
+
+async fn backfill(pool: &SqlitePool) -> Result<()> {
+    const PAGE: i64 = 500;
+    let mut conn = pool.acquire().await.context("acquiring connection")?;
+    let mut after = String::new();
+    loop {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, to_addrs, cc_addrs, bcc_addrs FROM messages
+            WHERE id > ?1 AND (to_addrs IS NOT NULL OR cc_addrs IS NOT NULL OR bcc_addrs IS NOT NULL)
+            ORDER BY id
+            LIMIT ?2;
+            "#,
+        )
+        .bind(&after)
+        .bind(PAGE)
+        .fetch_all(&mut *conn)
+        .await
+        .context("loading recipients to index")?;
+        let Some(last) = rows.last() else {
+            break;
+        };
+        after = last.get(0);
+        for row in rows {
+            let recipients = parse_recipients(row.get(1), row.get(2), row.get(3));
+            replace_in(&mut conn, &row.get::<String, _>(0), &recipients).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the message's rows with `recipients` (see [`recipients_of`]).
+pub(crate) async fn replace_in(
+    conn: &mut SqliteConnection,
+    message_id: &str,
+    recipients: &[Recipient],
+) -> Result<()> {
+    sqlx::query("DELETE FROM message_addresses WHERE message_id = ?1")
+        .bind(message_id)
+        .execute(&mut *conn)
+        .await
+        .context("clearing message recipients")?;
+    if recipients.is_empty() {
+        return Ok(());
+    }
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "INSERT OR IGNORE INTO message_addresses (message_id, field, position, name, address) ",
+    );
+    qb.push_values(
+        recipients.iter().enumerate(),
+        |mut row, (position, recipient)| {
+            row.push_bind(message_id)
+                .push_bind(recipient.field.as_str())
+                .push_bind(position as i64)
+                .push_bind(&recipient.name)
+                .push_bind(&recipient.addr);
+        },
+    );
+    qb.build()
+        .execute(&mut *conn)
+        .await
+        .context("storing message recipients")?;
+    Ok(())
+}
+
+/// The message's recipients, To first, each list in header order.
+pub(crate) async fn list(pool: &SqlitePool, message_id: &str) -> Result<Vec<Recipient>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT field, name, address FROM message_addresses
+        WHERE message_id = ?1
+        ORDER BY position;
+        "#,
+    )
+    .bind(message_id)
+    .fetch_all(pool)
+    .await
+    .context("loading message recipients")?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(Recipient {
+                field: AddressField::parse(row.get::<String, _>(0).as_str())?,
+                name: row.get(1),
+                addr: row.get(2),
+            })
+        })
+        .collect())
+}
+
+/// Ids of the account's messages that list `address` in one of `fields` and in none of
+/// `not_fields` (e.g. To but not Cc), newest first.
+pub(crate) async fn find(
+    pool: &SqlitePool,
+    account_id: &str,
+    address: &str,
+    fields: &[AddressField],
+    not_fields: &[AddressField],
+) -> Result<Vec<String>> {
+    if fields.is_empty() {
+        return Ok(Vec::new());
+    }
+    let address = address.trim().to_lowercase();
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT DISTINCT m.id, m.internal_date FROM messages m \
+         JOIN message_addresses a ON a.message_id = m.id WHERE m.account_id = ",
+    );
+    qb.push_bind(account_id);
+    qb.push(" AND a.address = ");
+    qb.push_bind(address.clone());
+    qb.push(" AND a.field IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for field in fields {
+            separated.push_bind(field.as_str());
+        }
+    }
+    qb.push(")");
+    if !not_fields.is_empty() {
+        qb.push(
+            " AND NOT EXISTS (SELECT 1 FROM message_addresses x \
+             WHERE x.message_id = m.id AND x.address = ",
+        );
+        qb.push_bind(address);
+        qb.push(" AND x.field IN (");
+        {
+            let mut separated = qb.separated(", ");
+            for field in not_fields {
+                separated.push_bind(field.as_str());
+            }
+        }
+        qb.push("))");
+    }
+    qb.push(" ORDER BY m.internal_date DESC, m.id");
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("finding messages by recipient")?;
+    Ok(rows.into_iter().map(|row| row.get(0)).collect())
+}
+
+/// Recipient suggestions: addresses from the account's To/Cc/Bcc lists whose address, or a word
+/// of whose name, starts with `prefix`, ranked by frecency. Each message listing an address adds
+/// `1 / (1 + age / 30 days)`, so frequent contacts lead and recent ones outrank stale ones. The
+/// account's own address is left out; senders are not included since `from_addr` may be sealed
+/// by column encryption.
+pub(crate) async fn suggest(
+    pool: &SqlitePool,
+    account_id: &str,
+    prefix: &str,
+    limit: usize,
+    now: i64,
+) -> Result<Vec<AddressSuggestion>> {
+    let prefix = prefix.trim().to_lowercase();
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+    let escaped = prefix
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    // With a single MAX() aggregate, SQLite takes the bare `name` from the newest row.
+    let rows = sqlx::query(
+        r#"
+        SELECT a.address, a.name, MAX(COALESCE(m.internal_date, m.created_at)) AS last_seen,
+               COUNT(DISTINCT a.message_id) AS uses,
+               SUM(1.0 / (1.0 + MAX(?3 - COALESCE(m.internal_date, m.created_at), 0) / 2592000.0))
+                   AS score
+        FROM message_addresses a
+        JOIN messages m ON m.id = a.message_id
+        WHERE m.account_id = ?1
+          AND a.address <> (SELECT lower(email) FROM accounts WHERE id = ?1)
+          AND (a.address LIKE ?2 || '%' ESCAPE '\'
+               OR lower(a.name) LIKE ?2 || '%' ESCAPE '\'
+               OR lower(a.name) LIKE '% ' || ?2 || '%' ESCAPE '\')
+        GROUP BY a.address
+        ORDER BY score DESC, last_seen DESC, a.address
+        LIMIT ?4;
+        "#,
+    )
+    .bind(account_id)
+    .bind(&escaped)
+    .bind(now)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+    .context("ranking recipient suggestions")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| AddressSuggestion {
+            addr: row.get(0),
+            name: row.get(1),
+            last_seen: row.get(2),
+            uses: row.get(3),
+        })
+        .collect())
+}
@@ -0,0 +1,118 @@
+//! Append-only `audit_log` of destructive commands otto sent to a server (moves, deletes,
+//! expunges), for `otto audit`. Triggers reject UPDATE and DELETE, so rows only ever accrue.
+use anyhow::{Context, Result};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+
+/// One server command and its outcome.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub account_id: String,
+    pub ts: i64,
+    /// Which part of otto issued it: `ops-replay` (queued TUI/CLI actions) or `folder-op`.
+    pub actor: String,
+    /// `archive`, `move`, `delete` (to Trash) or `expunge`.
+    pub op: String,
+    pub folder: String,
+    /// Where messages were moved, if anywhere.
+    pub destination: Option<String>,
+    pub uids: Vec<u32>,
+    /// `pending_ops` ids the command settled (empty for folder ops).
+    pub op_ids: Vec<i64>,
+    /// `ok`, or `rejected: <server reason>`.
+    pub outcome: String,
+}
+
+pub(crate) async fn ensure_audit_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id TEXT NOT NULL,
+            ts INTEGER NOT NULL,
+            actor TEXT NOT NULL,
+            op TEXT NOT NULL,
+            folder TEXT NOT NULL,
+            destination TEXT,
+            uids TEXT NOT NULL,
+            op_ids TEXT NOT NULL,
+            outcome TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_account_ts ON audit_log(account_id, ts);
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log is append-only');
+        END;
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log is append-only');
+        END;
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating audit_log table")?;
+    Ok(())
+}
+
+pub(crate) async fn append(pool: &SqlitePool, record: &AuditRecord) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (account_id, ts, actor, op, folder, destination, uids, op_ids, outcome)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9);
+        "#,
+    )
+    .bind(&record.account_id)
+    .bind(record.ts)
+    .bind(&record.actor)
+    .bind(&record.op)
+    .bind(&record.folder)
+    .bind(&record.destination)
+    .bind(serde_json::to_string(&record.uids).context("encoding audit uids")?)
+    .bind(serde_json::to_string(&record.op_ids).context("encoding audit op ids")?)
+    .bind(&record.outcome)
+    .execute(pool)
+    .await
+    .context("appending audit record")?;
+    Ok(())
+}
+
+/// Newest first; `account_id` and `since` (unix seconds) narrow the result.
+pub(crate) async fn list(
+    pool: &SqlitePool,
+    account_id: Option<&str>,
+    since: Option<i64>,
+    limit: usize,
+) -> Result<Vec<AuditRecord>> {
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT account_id, ts, actor, op, folder, destination, uids, op_ids, outcome \
+         FROM audit_log WHERE 1 = 1",
+    );
+    if let Some(account_id) = account_id {
+        qb.push(" AND account_id = ").push_bind(account_id);
+    }
+    if let Some(since) = since {
+        qb.push(" AND ts >= ").push_bind(since);
+    }
+    qb.push(" ORDER BY ts DESC, id DESC LIMIT ")
+        .push_bind(limit as i64);
+
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("loading audit log")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditRecord {
+            account_id: row.get(0),
+            ts: row.get(1),
+            actor: row.get(2),
+            op: row.get(3),
+            folder: row.get(4),
+            destination: row.get(5),
+            uids: serde_json::from_str(&row.get::<String, _>(6)).unwrap_or_default(),
+            op_ids: serde_json::from_str(&row.get::<String, _>(7)).unwrap_or_default(),
+            outcome: row.get(8),
+        })
+        .collect())
+}
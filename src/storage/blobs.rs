@@ -0,0 +1,298 @@
+//! Content-addressed raw bodies (`OTTO_BODY_STORAGE`). Raw RFC822 bytes are stored once per
+//! content hash: in the `blobs` table, or in hybrid mode above a size threshold as files under
+//! `<db>.blobs/`, which keeps the database small and snapshot-friendly.
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqliteConnection, SqlitePool};
+
+use crate::storage::compression::RawFormat;
+use crate::storage::crypto::ColumnCipher;
+use crate::storage::store::BodyStorage;
+use crate::types::now_ts;
+
+/// Files younger than this are left to the writer that may still be committing their row.
+const STRAY_FILE_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Creates the blob tables and the triggers that release a blob with its last body reference.
+pub(crate) async fn ensure_blob_tables(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS blobs (
+            hash TEXT PRIMARY KEY,
+            data BLOB NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            format INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS blob_trash (
+            hash TEXT PRIMARY KEY
+        );
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating blob tables")?;
+
+    // Migration: Add blob_hash column (content-addressed raw bodies)
+    let _ = sqlx::query("ALTER TABLE bodies ADD COLUMN blob_hash TEXT;")
+        .execute(pool)
+        .await;
+    // Migration: Add offloaded column (hybrid mode keeps `data` empty and the bytes in a file)
+    let _ = sqlx::query("ALTER TABLE blobs ADD COLUMN offloaded INTEGER NOT NULL DEFAULT 0;")
+        .execute(pool)
+        .await;
+    // Migration: Add format column (see `compression::RawFormat`)
+    let _ = sqlx::query("ALTER TABLE blobs ADD COLUMN format INTEGER NOT NULL DEFAULT 0;")
+        .execute(pool)
+        .await;
+    // Ignore errors (columns might already exist)
+
+    // Files can't be removed from SQL, so dropped offloaded blobs are queued for
+    // `BlobStore::purge_files`.
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_bodies_blob_hash ON bodies(blob_hash);
+        CREATE TRIGGER IF NOT EXISTS bodies_release_blob_on_delete
+        AFTER DELETE ON bodies
+        WHEN old.blob_hash IS NOT NULL
+        BEGIN
+            DELETE FROM blobs WHERE hash = old.blob_hash
+                AND NOT EXISTS (SELECT 1 FROM bodies WHERE blob_hash = old.blob_hash);
+        END;
+        CREATE TRIGGER IF NOT EXISTS bodies_release_blob_on_update
+        AFTER UPDATE OF blob_hash ON bodies
+        WHEN old.blob_hash IS NOT NULL AND old.blob_hash IS NOT new.blob_hash
+        BEGIN
+            DELETE FROM blobs WHERE hash = old.blob_hash
+                AND NOT EXISTS (SELECT 1 FROM bodies WHERE blob_hash = old.blob_hash);
+        END;
+        CREATE TRIGGER IF NOT EXISTS blobs_trash_offloaded
+        AFTER DELETE ON blobs
+        WHEN old.offloaded = 1
+        BEGIN
+            INSERT OR IGNORE INTO blob_trash (hash) VALUES (old.hash);
+        END;
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating blob triggers")?;
+    Ok(())
+}
+
+/// SHA-256 of the raw message, keyed per account when its columns are encrypted (a sealed
+/// blob is only readable by the account that wrote it).
+pub(crate) fn content_hash(cipher: Option<&ColumnCipher>, plain: &[u8]) -> String {
+    match cipher {
+        Some(cipher) => cipher.content_hash(plain),
+        None => format!("{:x}", Sha256::digest(plain)),
+    }
+}
+
+/// The storage mode in effect for a write, plus the directory offloaded blobs live in.
+#[derive(Clone, Debug)]
+pub(crate) struct BlobStore {
+    mode: BodyStorage,
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(mode: BodyStorage, db_path: &Path) -> Self {
+        Self {
+            mode,
+            dir: db_path.with_extension("blobs"),
+        }
+    }
+
+    /// Whether raw bodies go to `blobs` instead of `bodies.raw_rfc822`.
+    pub fn content_addressed(&self) -> bool {
+        self.mode != BodyStorage::Inline
+    }
+
+    fn offloads(&self, len: usize) -> bool {
+        match self.mode {
+            BodyStorage::Hybrid { min_file_bytes } => len as u64 >= min_file_bytes,
+            _ => false,
+        }
+    }
+
+    fn file_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(hash.get(..2).unwrap_or("00")).join(hash)
+    }
+
+    /// Stores `data` (already compressed as `format` and sealed) under `hash` unless a blob
+    /// with that hash exists.
+    pub async fn put(
+        &self,
+        conn: &mut SqliteConnection,
+        hash: &str,
+        data: &[u8],
+        format: RawFormat,
+    ) -> Result<()> {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM blobs WHERE hash = ?1")
+            .bind(hash)
+            .fetch_optional(&mut *conn)
+            .await
+            .context("checking blob")?;
+        if exists.is_some() {
+            return Ok(());
+        }
+
+        let offload = self.offloads(data.len());
+        if offload {
+            self.write_file(hash, data)?;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO blobs (hash, data, size_bytes, offloaded, created_at, format)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT(hash) DO NOTHING;
+            "#,
+        )
+        .bind(hash)
+        .bind(if offload { &[][..] } else { data })
+        .bind(data.len() as i64)
+        .bind(if offload { 1 } else { 0 })
+        .bind(now_ts())
+        .bind(format.as_i64())
+        .execute(&mut *conn)
+        .await
+        .context("storing blob")?;
+        Ok(())
+    }
+
+    /// Rewrites the bytes of an inline blob (same content, new encoding). Offloaded files are
+    /// left alone: a reader could pair the new file with the old row's format.
+    pub async fn replace_inline(
+        conn: &mut SqliteConnection,
+        hash: &str,
+        data: &[u8],
+        format: RawFormat,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE blobs SET data = ?1, size_bytes = ?2, format = ?3 WHERE hash = ?4 AND offloaded = 0",
+        )
+            .bind(data)
+            .bind(data.len() as i64)
+            .bind(format.as_i64())
+            .bind(hash)
+            .execute(&mut *conn)
+            .await
+            .context("rewriting blob")?;
+        Ok(())
+    }
+
+    /// Writes via a temp file and rename, so a crash never leaves a truncated blob behind. A
+    /// file left by a dropped blob is overwritten: it may hold the content in another format.
+    fn write_file(&self, hash: &str, data: &[u8]) -> Result<()> {
+        let path = self.file_path(hash);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating blob directory {}", parent.display()))?;
+        }
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&tmp, data)
+            .with_context(|| format!("writing blob file {}", tmp.display()))?;
+        std::fs::rename(&tmp, &path)
+            .with_context(|| format!("moving blob file into {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Raw bytes of a blob row: its inline `data`, or the offloaded file.
+    pub fn read(&self, hash: &str, data: Vec<u8>, offloaded: bool) -> Result<Vec<u8>> {
+        if !offloaded {
+            return Ok(data);
+        }
+        let path = self.file_path(hash);
+        std::fs::read(&path).with_context(|| format!("reading blob file {}", path.display()))
+    }
+
+    /// Whether the file of an offloaded blob is on disk.
+    pub fn has_file(&self, hash: &str) -> bool {
+        self.file_path(hash).is_file()
+    }
+
+    /// Deletes files under the blob directory that no offloaded blob row names (a write that
+    /// crashed before its row committed, a purge that never ran, a leftover temp file); returns
+    /// files removed. Files younger than an hour are kept: another process may be about to
+    /// commit their row.
+    pub fn remove_stray_files(&self, offloaded: &HashSet<String>) -> Result<u64> {
+        let shards = match std::fs::read_dir(&self.dir) {
+            Ok(shards) => shards,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("listing blob directory {}", self.dir.display()));
+            }
+        };
+        let cutoff = SystemTime::now() - STRAY_FILE_MIN_AGE;
+        let mut removed = 0;
+        for shard in shards {
+            let shard = shard.context("listing blob directory")?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&shard)
+                .with_context(|| format!("listing blob directory {}", shard.display()))?
+            {
+                let entry = entry.context("listing blob directory")?;
+                let name = entry.file_name();
+                if offloaded.contains(name.to_string_lossy().as_ref()) {
+                    continue;
+                }
+                let metadata = entry.metadata().context("reading blob file metadata")?;
+                if !metadata.is_file() || metadata.modified().is_ok_and(|m| m > cutoff) {
+                    continue;
+                }
+                let path = entry.path();
+                match std::fs::remove_file(&path) {
+                    Ok(()) => removed += 1,
+                    Err(e) if e.kind() == ErrorKind::NotFound => {}
+                    Err(e) => {
+                        return Err(e)
+                            .with_context(|| format!("removing blob file {}", path.display()));
+                    }
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Deletes the files of offloaded blobs dropped since the last purge; returns files
+    /// removed. Run it while nothing else writes (startup): a concurrent write of the same
+    /// content could otherwise lose its file.
+    pub async fn purge_files(&self, pool: &SqlitePool) -> Result<usize> {
+        let rows = sqlx::query(
+            r#"
+            SELECT t.hash FROM blob_trash t
+            WHERE NOT EXISTS (SELECT 1 FROM blobs b WHERE b.hash = t.hash AND b.offloaded = 1);
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+        .context("loading dropped blobs")?;
+
+        let mut removed = 0;
+        for row in rows {
+            let path = self.file_path(&row.get::<String, _>(0));
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("removing blob file {}", path.display()));
+                }
+            }
+        }
+        sqlx::query("DELETE FROM blob_trash")
+            .execute(pool)
+            .await
+            .context("clearing blob trash")?;
+        Ok(removed)
+    }
+}
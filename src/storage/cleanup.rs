@@ -0,0 +1,89 @@
+//! `cleanup_runs`: what each cleanup rule run queued, for `otto cleanup --report`. Only message
+//! ids are kept; the report looks subjects up in the cache, so sealed columns stay sealed.
+use anyhow::{Context, Result};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+
+/// One rule run that archived or deleted something.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CleanupRun {
+    pub account_id: String,
+    pub ts: i64,
+    pub rule: String,
+    /// `archive` or `delete`.
+    pub action: String,
+    pub message_ids: Vec<String>,
+}
+
+pub(crate) async fn ensure_cleanup_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS cleanup_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account_id TEXT NOT NULL,
+            ts INTEGER NOT NULL,
+            rule TEXT NOT NULL,
+            action TEXT NOT NULL,
+            message_ids TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_cleanup_runs_account_ts ON cleanup_runs(account_id, ts);
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating cleanup_runs table")?;
+    Ok(())
+}
+
+pub(crate) async fn append(pool: &SqlitePool, run: &CleanupRun) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO cleanup_runs (account_id, ts, rule, action, message_ids)
+        VALUES (?1, ?2, ?3, ?4, ?5);
+        "#,
+    )
+    .bind(&run.account_id)
+    .bind(run.ts)
+    .bind(&run.rule)
+    .bind(&run.action)
+    .bind(serde_json::to_string(&run.message_ids).context("encoding cleanup message ids")?)
+    .execute(pool)
+    .await
+    .context("recording cleanup run")?;
+    Ok(())
+}
+
+/// Newest first; `account_id` and `since` (unix seconds) narrow the result.
+pub(crate) async fn list(
+    pool: &SqlitePool,
+    account_id: Option<&str>,
+    since: Option<i64>,
+    limit: usize,
+) -> Result<Vec<CleanupRun>> {
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT account_id, ts, rule, action, message_ids FROM cleanup_runs WHERE 1 = 1",
+    );
+    if let Some(account_id) = account_id {
+        qb.push(" AND account_id = ").push_bind(account_id);
+    }
+    if let Some(since) = since {
+        qb.push(" AND ts >= ").push_bind(since);
+    }
+    qb.push(" ORDER BY ts DESC, id DESC LIMIT ")
+        .push_bind(limit as i64);
+
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("loading cleanup runs")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| CleanupRun {
+            account_id: row.get(0),
+            ts: row.get(1),
+            rule: row.get(2),
+            action: row.get(3),
+            message_ids: serde_json::from_str(&row.get::<String, _>(4)).unwrap_or_default(),
+        })
+        .collect())
+}
@@ -0,0 +1,61 @@
+//! zstd compression of raw RFC822 bytes. A message is compressed before it is sealed (sealed
+//! bytes don't compress) and the format is recorded next to the bytes (`bodies.raw_format`,
+//! `blobs.format`), so rows written before compression, or ones it didn't shrink, stay
+//! readable as they are.
+use anyhow::{Context, Result, bail};
+
+/// Level 3 is zstd's default: most of the size win on HTML mail at a fraction of the CPU of
+/// the higher levels.
+const LEVEL: i32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawFormat {
+    Plain,
+    Zstd,
+}
+
+impl RawFormat {
+    pub fn as_i64(self) -> i64 {
+        match self {
+            RawFormat::Plain => 0,
+            RawFormat::Zstd => 1,
+        }
+    }
+
+    pub fn from_i64(raw: i64) -> Result<Self> {
+        match raw {
+            0 => Ok(RawFormat::Plain),
+            1 => Ok(RawFormat::Zstd),
+            other => bail!("unknown raw body format {}", other),
+        }
+    }
+}
+
+/// Compresses `plain`, or keeps it as is when zstd doesn't make it smaller.
+pub fn compress(plain: &[u8]) -> Result<(Vec<u8>, RawFormat)> {
+    let packed = zstd::bulk::compress(plain, LEVEL).context("compressing raw body")?;
+    if packed.len() < plain.len() {
+        Ok((packed, RawFormat::Zstd))
+    } else {
+        Ok((plain.to_vec(), RawFormat::Plain))
+    }
+}
+
+pub fn decompress(data: Vec<u8>, format: RawFormat) -> Result<Vec<u8>> {
+    match format {
+        RawFormat::Plain => Ok(data),
+        RawFormat::Zstd => {
+            zstd::stream::decode_all(data.as_slice()).context("decompressing raw body")
+        }
+    }
+}
+
+/// Totals of an `otto compress-bodies` run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompressStats {
+    /// Raw bodies and blobs rewritten compressed.
+    pub compressed: usize,
+    /// Stored size of those rows before and after.
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
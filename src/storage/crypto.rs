@@ -0,0 +1,186 @@
+//! Per-account column encryption for identifying message fields (subject, sender, body text and
+//! raw RFC822). Values are sealed with XChaCha20-Poly1305 under a random 256-bit key kept in the
+//! OS keyring, so a copied `otto.db` is unreadable without the account's keyring entry.
+use anyhow::{Context, Result, anyhow, bail};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use sha2::{Digest, Sha256};
+
+use crate::profile;
+use crate::types::{BodyRecord, MessageRecord};
+
+const KEYRING_SERVICE: &str = "otto-column-key";
+/// Marks sealed TEXT columns; anything without it is legacy plaintext.
+const TEXT_PREFIX: &str = "enc1:";
+/// Marks sealed BLOB columns (raw RFC822 never starts with a NUL byte).
+const BLOB_MAGIC: &[u8] = b"\0otto-enc1\0";
+const NONCE_LEN: usize = 24;
+
+pub struct ColumnCipher {
+    cipher: XChaCha20Poly1305,
+    /// Derived from the column key; keys content hashes of sealed blobs.
+    hash_key: [u8; 32],
+}
+
+impl ColumnCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+            hash_key: Sha256::new()
+                .chain_update(b"otto-blob-hash\0")
+                .chain_update(key)
+                .finalize()
+                .into(),
+        }
+    }
+
+    /// Content address for a sealed blob. Equal plaintexts collide only under the same key, so
+    /// the hash can't be matched against known messages or across accounts.
+    pub fn content_hash(&self, plain: &[u8]) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.hash_key)
+            .chain_update(plain)
+            .finalize();
+        format!("{:x}", digest)
+    }
+
+    /// Loads the account's key from the keyring.
+    pub fn load(account_id: &str) -> Result<Self> {
+        let entry = keyring::Entry::new(&profile::keyring_service(KEYRING_SERVICE), account_id)
+            .context("opening column key keyring entry")?;
+        let encoded = entry
+            .get_password()
+            .with_context(|| format!("reading column key for {}", account_id))?;
+        let key: [u8; 32] = STANDARD
+            .decode(encoded.trim())
+            .context("decoding column key")?
+            .try_into()
+            .map_err(|_| anyhow!("column key for {} is not 32 bytes", account_id))?;
+        Ok(Self::new(&key))
+    }
+
+    /// Loads the account's key, generating and storing one on first use. There is no file
+    /// fallback: without a keyring the key would sit next to the data it protects.
+    pub fn load_or_create(account_id: &str) -> Result<Self> {
+        let entry = keyring::Entry::new(&profile::keyring_service(KEYRING_SERVICE), account_id)
+            .context("opening column key keyring entry")?;
+        match entry.get_password() {
+            Ok(_) => Self::load(account_id),
+            Err(keyring::Error::NoEntry) => {
+                let key: [u8; 32] = XChaCha20Poly1305::generate_key(&mut OsRng).into();
+                entry
+                    .set_password(&STANDARD.encode(key))
+                    .with_context(|| format!("storing column key for {}", account_id))?;
+                Ok(Self::new(&key))
+            }
+            Err(e) => Err(e).with_context(|| format!("reading column key for {}", account_id)),
+        }
+    }
+
+    fn seal(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plain)
+            .map_err(|_| anyhow!("encrypting column value"))?;
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            bail!("sealed column value is truncated");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("decrypting column value (wrong key or corrupted data)"))
+    }
+
+    pub fn seal_text(&self, plain: &str) -> Result<String> {
+        Ok(format!(
+            "{}{}",
+            TEXT_PREFIX,
+            STANDARD.encode(self.seal(plain.as_bytes())?)
+        ))
+    }
+
+    /// Decrypts a sealed value; plaintext written before encryption was enabled passes through.
+    pub fn open_text(&self, stored: &str) -> Result<String> {
+        let Some(encoded) = stored.strip_prefix(TEXT_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let sealed = STANDARD
+            .decode(encoded)
+            .context("decoding sealed column value")?;
+        String::from_utf8(self.open(&sealed)?).context("sealed column value is not UTF-8")
+    }
+
+    pub fn seal_bytes(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let mut out = BLOB_MAGIC.to_vec();
+        out.extend(self.seal(plain)?);
+        Ok(out)
+    }
+
+    pub fn open_bytes(&self, stored: &[u8]) -> Result<Vec<u8>> {
+        match stored.strip_prefix(BLOB_MAGIC) {
+            Some(sealed) => self.open(sealed),
+            None => Ok(stored.to_vec()),
+        }
+    }
+
+    fn seal_opt(&self, value: &Option<String>) -> Result<Option<String>> {
+        value.as_deref().map(|v| self.seal_text(v)).transpose()
+    }
+
+    fn open_opt(&self, value: &Option<String>) -> Result<Option<String>> {
+        value.as_deref().map(|v| self.open_text(v)).transpose()
+    }
+
+    /// Copy of `message` with its encrypted columns sealed.
+    pub fn seal_message(&self, message: &MessageRecord) -> Result<MessageRecord> {
+        Ok(MessageRecord {
+            subject: self.seal_opt(&message.subject)?,
+            from: self.seal_opt(&message.from)?,
+            from_name: self.seal_opt(&message.from_name)?,
+            ..message.clone()
+        })
+    }
+
+    pub fn open_message(&self, message: &mut MessageRecord) -> Result<()> {
+        message.subject = self.open_opt(&message.subject)?;
+        message.from = self.open_opt(&message.from)?;
+        message.from_name = self.open_opt(&message.from_name)?;
+        Ok(())
+    }
+
+    /// Copy of `body` with `raw_rfc822` and `sanitized_text` sealed.
+    pub fn seal_body(&self, body: &BodyRecord) -> Result<BodyRecord> {
+        Ok(BodyRecord {
+            message_id: body.message_id.clone(),
+            raw_rfc822: body
+                .raw_rfc822
+                .as_deref()
+                .map(|raw| self.seal_bytes(raw))
+                .transpose()?,
+            sanitized_text: self.seal_opt(&body.sanitized_text)?,
+            mime_summary: body.mime_summary.clone(),
+            attachments_json: body.attachments_json.clone(),
+            sanitized_at: body.sanitized_at,
+            sanitizer_version: body.sanitizer_version,
+            degraded: body.degraded,
+        })
+    }
+
+    pub fn open_body(&self, body: &mut BodyRecord) -> Result<()> {
+        if let Some(raw) = body.raw_rfc822.as_deref() {
+            body.raw_rfc822 = Some(self.open_bytes(raw)?);
+        }
+        body.sanitized_text = self.open_opt(&body.sanitized_text)?;
+        Ok(())
+    }
+}
@@ -1,17 +1,62 @@
+use crate::profile;
+use crate::storage::addresses::{self, AddressField, AddressSuggestion, Recipient};
+use crate::storage::audit::{self, AuditRecord};
+use crate::storage::blobs::{self, BlobStore};
+use crate::storage::cleanup::{self, CleanupRun};
+use crate::storage::compression::{self, CompressStats, RawFormat};
+use crate::storage::crypto::ColumnCipher;
+use crate::storage::identity;
+use crate::storage::learning::{self, SenderAction, SuggestionStatus};
+use crate::storage::notes::{self, MessageNote};
+use crate::storage::ops::{self, MessageOp};
+use crate::storage::quota;
+use crate::storage::recovery::{self, RecoveryReport};
+use crate::storage::reply_later::{self, ReplyLater};
+use crate::storage::store::BodyStorage;
+use crate::storage::summaries;
+use crate::storage::threads;
+use crate::storage::translations::{self, MessageTranslation};
 use crate::types::{
-    Account, AccountSettings, BodyRecord, FolderState, MessageRecord, Provider, now_ts,
+    Account, AccountQuota, AccountSettings, BodyRecord, BodyStatus, Credential, FetchRetry,
+    FolderCounts, FolderRole, FolderState, MailboxInfo, MessageRecord, Provider, ServerIdentity,
+    Signature, SyncRunRecord, now_ts, special_use_folder,
 };
 use anyhow::{Context, Result};
 use chrono::NaiveDate;
 use dirs::home_dir;
 
-use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool, Transaction};
+use sqlx::{
+    ColumnIndex, Decode, QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool, Transaction, Type,
+};
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use tracing::warn;
 
 const DB_FILE_NAME: &str = "otto.db";
 
+/// Column list `message_from_row` maps into a `MessageRecord`.
+pub(crate) const MESSAGE_RECORD_SELECT: &str = "SELECT id, folder, uid, thread_id, internal_date, subject, from_addr, to_addrs, cc_addrs, bcc_addrs, \
+     flags, labels, has_attachments, size_bytes, raw_hash, created_at, updated_at, body_status, from_name, \
+     message_id_header FROM messages";
+
+/// Column list `account_from_row` maps into an `Account` (the columns `save_account` writes).
+pub(crate) const ACCOUNT_SELECT: &str = "SELECT id, email, provider, cutoff_since, poll_interval_minutes, prefetch_recent, safe_mode, folders, \
+     created_at, updated_at, max_download_bps, folder_policies, encrypt_columns, unread_only, max_message_bytes, \
+     smart_folders, all_mail_mode, imap_endpoint, enabled, credential, cleanup_rules, notify_rules, namespace, \
+     tls_policy FROM accounts";
+
+/// How long `sync_runs` rows are kept (90 days).
+pub(crate) const SYNC_RUN_RETENTION_SECS: i64 = 90 * 24 * 60 * 60;
+
+/// Where archived messages live locally until the All Mail sync re-links their uid. Servers
+/// that advertise `\All` (see [`Database::role_folder`]) override it.
+pub const ARCHIVE_FOLDER: &str = FolderRole::All.gmail_default();
+
+/// Where deleted messages go; deleting from here expunges them. Overridden by `\Trash`.
+pub const TRASH_FOLDER: &str = FolderRole::Trash.gmail_default();
+
 #[derive(Clone, Debug, Default)]
 pub struct FolderStateUpdate {
     pub uidvalidity: Option<u32>,
@@ -20,8 +65,19 @@ pub struct FolderStateUpdate {
     pub exists_count: Option<u32>,
     pub last_sync_ts: Option<i64>,
     pub last_uid_scan_ts: Option<i64>,
+    pub baseline_scan_uid: Option<u32>,
+    /// Incremental-pass checkpoint: new UIDs up to `resume_uid` are committed for the MODSEQ
+    /// window starting at `resume_modseq`. Both `None` once the pass completes.
+    pub resume_modseq: Option<u64>,
+    pub resume_uid: Option<u32>,
+    /// Lowest UID of the batch an in-progress checkpoint (`baseline_scan_uid`/`resume_uid`)
+    /// covers, so startup recovery can tell whether that batch landed. `None` otherwise.
+    pub checkpoint_from_uid: Option<u32>,
 }
 
+/// Body downloaded for a previously header-only message: `(has_attachments, raw_hash, body)`.
+pub type FetchedBodyUpdate = (bool, Option<String>, BodyRecord);
+
 pub type MessageLocationUpdate = (
     String,
     String,
@@ -37,6 +93,11 @@ pub type MessageLocationUpdate = (
 pub struct Database {
     pool: SqlitePool,
     path: PathBuf,
+    /// Column ciphers for accounts with `encrypt_columns` set (see `register_cipher`); shared
+    /// by clones.
+    ciphers: Arc<RwLock<HashMap<String, Arc<ColumnCipher>>>>,
+    /// Layout for raw bodies on write (see `BodyStorage`); shared by clones.
+    body_storage: Arc<RwLock<BodyStorage>>,
 }
 
 #[derive(Clone, Debug)]
@@ -53,9 +114,17 @@ impl Database {
         Self::new_named(DB_FILE_NAME).await
     }
 
+    /// Where `new_default` keeps the database.
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(default_data_dir()?.join(DB_FILE_NAME))
+    }
+
     pub async fn new_named(file_name: &str) -> Result<Self> {
-        let base = default_data_dir()?;
-        let db_path = base.join(file_name);
+        Self::open_at(default_data_dir()?.join(file_name)).await
+    }
+
+    /// Opens (creating if needed) the database file at `db_path`.
+    pub async fn open_at(db_path: PathBuf) -> Result<Self> {
         let url = format!("sqlite://{}?mode=rwc", db_path.display());
 
         if let Some(parent) = db_path.parent() {
@@ -70,6 +139,8 @@ impl Database {
         let db = Database {
             pool,
             path: db_path,
+            ciphers: Arc::new(RwLock::new(HashMap::new())),
+            body_storage: Arc::new(RwLock::new(BodyStorage::Inline)),
         };
         db.migrate().await?;
         Ok(db)
@@ -79,6 +150,46 @@ impl Database {
         &self.path
     }
 
+    /// Seals/opens the account's encrypted columns with `cipher` from now on (`None` stops).
+    pub fn register_cipher(&self, account_id: &str, cipher: Option<ColumnCipher>) {
+        let mut ciphers = self.ciphers.write().unwrap_or_else(|e| e.into_inner());
+        match cipher {
+            Some(cipher) => {
+                ciphers.insert(account_id.to_string(), Arc::new(cipher));
+            }
+            None => {
+                ciphers.remove(account_id);
+            }
+        }
+    }
+
+    pub fn set_body_storage(&self, mode: BodyStorage) {
+        *self.body_storage.write().unwrap_or_else(|e| e.into_inner()) = mode;
+    }
+
+    fn blob_store(&self) -> BlobStore {
+        let mode = *self.body_storage.read().unwrap_or_else(|e| e.into_inner());
+        BlobStore::new(mode, &self.path)
+    }
+
+    /// Deletes files of offloaded blobs no longer referenced; call before syncing starts.
+    pub async fn purge_blob_files(&self) -> Result<usize> {
+        self.blob_store().purge_files(&self.pool).await
+    }
+
+    /// Repairs what interrupted writes left behind (see `storage::recovery`).
+    pub async fn recover_interrupted_writes(&self) -> Result<RecoveryReport> {
+        recovery::recover(&self.pool, &self.blob_store()).await
+    }
+
+    fn cipher_for(&self, account_id: &str) -> Option<Arc<ColumnCipher>> {
+        self.ciphers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(account_id)
+            .cloned()
+    }
+
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
@@ -171,6 +282,366 @@ impl Database {
         Ok(())
     }
 
+    pub async fn record_audit(&self, record: &AuditRecord) -> Result<()> {
+        audit::append(&self.pool, record).await
+    }
+
+    /// Audit records, newest first, optionally for one account and from `since` (unix secs).
+    pub async fn load_audit_log(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>> {
+        audit::list(&self.pool, account_id, since, limit).await
+    }
+
+    pub async fn record_cleanup_run(&self, run: &CleanupRun) -> Result<()> {
+        cleanup::append(&self.pool, run).await
+    }
+
+    /// Cleanup runs, newest first, optionally for one account and from `since` (unix secs).
+    pub async fn load_cleanup_runs(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<CleanupRun>> {
+        cleanup::list(&self.pool, account_id, since, limit).await
+    }
+
+    /// Replaces the account's stored quota.
+    pub async fn save_account_quota(&self, quota: &AccountQuota) -> Result<()> {
+        quota::save(&self.pool, quota).await
+    }
+
+    /// The quota last read from the account's server, if any.
+    pub async fn load_account_quota(&self, account_id: &str) -> Result<Option<AccountQuota>> {
+        quota::load(&self.pool, account_id).await
+    }
+
+    /// Replaces the account's stored server ID answer.
+    pub async fn save_server_identity(&self, identity: &ServerIdentity) -> Result<()> {
+        identity::save(&self.pool, identity).await
+    }
+
+    /// What the account's server last said in answer to ID, if it supports ID.
+    pub async fn load_server_identity(&self, account_id: &str) -> Result<Option<ServerIdentity>> {
+        identity::load(&self.pool, account_id).await
+    }
+
+    /// Adds messages to the reply-later queue, or changes their due date.
+    pub async fn set_reply_later(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+        due: Option<NaiveDate>,
+    ) -> Result<u64> {
+        reply_later::set(&self.pool, account_id, message_ids, due).await
+    }
+
+    /// The message's parsed To/Cc/Bcc recipients, in header order.
+    pub async fn load_recipients(&self, message_id: &str) -> Result<Vec<Recipient>> {
+        addresses::list(&self.pool, message_id).await
+    }
+
+    /// Messages listing `address` in one of `fields` and in none of `not_fields`, newest first.
+    pub async fn find_messages_by_recipient(
+        &self,
+        account_id: &str,
+        address: &str,
+        fields: &[AddressField],
+        not_fields: &[AddressField],
+    ) -> Result<Vec<String>> {
+        addresses::find(&self.pool, account_id, address, fields, not_fields).await
+    }
+
+    /// Addresses starting with `prefix` (or with a name word that does), best match first.
+    pub async fn suggest_addresses(
+        &self,
+        account_id: &str,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<AddressSuggestion>> {
+        addresses::suggest(&self.pool, account_id, prefix, limit, now_ts()).await
+    }
+
+    pub async fn clear_reply_later(&self, account_id: &str, message_ids: &[String]) -> Result<u64> {
+        reply_later::clear(&self.pool, account_id, message_ids).await
+    }
+
+    /// The account's reply-later queue, soonest due first, undated entries last.
+    pub async fn load_reply_later(&self, account_id: &str) -> Result<Vec<ReplyLater>> {
+        let mut queue = reply_later::list(&self.pool, account_id).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for entry in &mut queue {
+                entry.subject = entry
+                    .subject
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+                entry.from = entry
+                    .from
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+            }
+        }
+        Ok(queue)
+    }
+
+    /// Sets the private note of the account's message; false when there is no such message.
+    pub async fn set_message_note(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        note: &str,
+    ) -> Result<bool> {
+        let note = match self.cipher_for(account_id) {
+            Some(cipher) => cipher.seal_text(note)?,
+            None => note.to_string(),
+        };
+        notes::set(&self.pool, account_id, message_id, &note).await
+    }
+
+    pub async fn clear_message_note(&self, account_id: &str, message_id: &str) -> Result<bool> {
+        notes::clear(&self.pool, account_id, message_id).await
+    }
+
+    /// The account's notes, most recently edited first.
+    pub async fn load_message_notes(&self, account_id: &str) -> Result<Vec<MessageNote>> {
+        let mut list = notes::list(&self.pool, account_id).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for entry in &mut list {
+                entry.note = cipher.open_text(&entry.note)?;
+                entry.subject = entry
+                    .subject
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+                entry.from = entry
+                    .from
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+            }
+        }
+        Ok(list)
+    }
+
+    /// Stores a summarizer's summary of the account's message; false when there is no such
+    /// message.
+    pub async fn set_message_summary(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        summary: &str,
+    ) -> Result<bool> {
+        let summary = match self.cipher_for(account_id) {
+            Some(cipher) => cipher.seal_text(summary)?,
+            None => summary.to_string(),
+        };
+        summaries::set(&self.pool, account_id, message_id, &summary).await
+    }
+
+    /// The account's message summaries, keyed by message id.
+    pub async fn load_message_summaries(
+        &self,
+        account_id: &str,
+    ) -> Result<HashMap<String, String>> {
+        let cipher = self.cipher_for(account_id);
+        summaries::list(&self.pool, account_id)
+            .await?
+            .into_iter()
+            .map(|(id, summary)| {
+                let summary = match cipher.as_deref() {
+                    Some(cipher) => cipher.open_text(&summary)?,
+                    None => summary,
+                };
+                Ok((id, summary))
+            })
+            .collect()
+    }
+
+    /// Caches a translation of the account's message; false when there is no such message.
+    pub async fn set_message_translation(
+        &self,
+        account_id: &str,
+        translation: &MessageTranslation,
+    ) -> Result<bool> {
+        let sealed;
+        let translation = match self.cipher_for(account_id) {
+            Some(cipher) => {
+                sealed = MessageTranslation {
+                    text: cipher.seal_text(&translation.text)?,
+                    ..translation.clone()
+                };
+                &sealed
+            }
+            None => translation,
+        };
+        translations::set(&self.pool, account_id, translation).await
+    }
+
+    /// The cached translation of the account's message into `target`, if any.
+    pub async fn load_message_translation(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        target: &str,
+    ) -> Result<Option<MessageTranslation>> {
+        let Some(mut translation) =
+            translations::get(&self.pool, account_id, message_id, target).await?
+        else {
+            return Ok(None);
+        };
+        if let Some(cipher) = self.cipher_for(account_id) {
+            translation.text = cipher.open_text(&translation.text)?;
+        }
+        Ok(Some(translation))
+    }
+
+    /// Counts `count` messages from `sender` (a lowercased address) that the user applied
+    /// `action` (and `label`) to; the pair's count when that brought it to `threshold`.
+    pub async fn record_sender_action(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        count: i64,
+        threshold: i64,
+    ) -> Result<Option<i64>> {
+        let cipher = self.cipher_for(account_id);
+        let key = blobs::content_hash(cipher.as_deref(), sender.as_bytes());
+        let stored = match cipher.as_deref() {
+            Some(cipher) => cipher.seal_text(sender)?,
+            None => sender.to_string(),
+        };
+        learning::record(
+            &self.pool, account_id, &key, &stored, action, label, count, threshold,
+        )
+        .await
+    }
+
+    /// Settles a sender/action pair; false when it was never recorded.
+    pub async fn set_sender_action_status(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        status: SuggestionStatus,
+    ) -> Result<bool> {
+        let cipher = self.cipher_for(account_id);
+        let key = blobs::content_hash(cipher.as_deref(), sender.as_bytes());
+        learning::set_status(&self.pool, account_id, &key, action, label, status).await
+    }
+
+    /// The account's sender/action pairs, optionally with one status, most recent first.
+    pub async fn load_sender_actions(
+        &self,
+        account_id: &str,
+        status: Option<SuggestionStatus>,
+    ) -> Result<Vec<SenderAction>> {
+        let mut actions = learning::list(&self.pool, account_id, status).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for action in &mut actions {
+                action.sender = cipher.open_text(&action.sender)?;
+            }
+        }
+        Ok(actions)
+    }
+
+    /// Appends one account pass's folder rows and prunes history older than
+    /// `SYNC_RUN_RETENTION_SECS`.
+    pub async fn record_sync_runs(&self, runs: &[SyncRunRecord]) -> Result<()> {
+        if runs.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await.context("beginning sync run tx")?;
+        for run in runs {
+            sqlx::query(
+                r#"
+                INSERT INTO sync_runs (account_id, folder, run_started_at, duration_ms, added, updated, deleted, bytes, status, error)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10);
+                "#,
+            )
+            .bind(&run.account_id)
+            .bind(&run.folder)
+            .bind(run.run_started_at)
+            .bind(run.duration_ms)
+            .bind(run.added as i64)
+            .bind(run.updated as i64)
+            .bind(run.deleted as i64)
+            .bind(run.bytes as i64)
+            .bind(if run.ok { "ok" } else { "failed" })
+            .bind(&run.error)
+            .execute(&mut *tx)
+            .await
+            .context("inserting sync run")?;
+        }
+
+        sqlx::query("DELETE FROM sync_runs WHERE run_started_at < ?1")
+            .bind(now_ts() - SYNC_RUN_RETENTION_SECS)
+            .execute(&mut *tx)
+            .await
+            .context("pruning sync runs")?;
+
+        tx.commit().await.context("committing sync run tx")?;
+        Ok(())
+    }
+
+    /// Folder rows of the account's most recent `limit` sync passes, newest pass first.
+    pub async fn load_recent_sync_runs(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SyncRunRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT folder, run_started_at, duration_ms, added, updated, deleted, bytes, status, error
+            FROM sync_runs
+            WHERE account_id = ?1
+              AND run_started_at IN (
+                  SELECT DISTINCT run_started_at FROM sync_runs
+                  WHERE account_id = ?1
+                  ORDER BY run_started_at DESC
+                  LIMIT ?2
+              )
+            ORDER BY run_started_at DESC, folder ASC;
+            "#,
+        )
+        .bind(account_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading sync runs")?;
+        Ok(sync_runs_from_rows(account_id, rows))
+    }
+
+    /// Each folder's latest sync run for the account, by folder name.
+    pub async fn load_latest_sync_runs(&self, account_id: &str) -> Result<Vec<SyncRunRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT folder, run_started_at, duration_ms, added, updated, deleted, bytes, status, error
+            FROM sync_runs s
+            WHERE account_id = ?1
+              AND run_started_at = (
+                  SELECT MAX(run_started_at) FROM sync_runs
+                  WHERE account_id = ?1 AND folder = s.folder
+              )
+            ORDER BY folder ASC;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading latest sync runs")?;
+        Ok(sync_runs_from_rows(account_id, rows))
+    }
+
     #[allow(clippy::too_many_arguments)] // transaction aggregator for all per-folder writes
     /// Atomically apply per-folder writes (new messages/bodies, location/flag updates), update folder state, and record sync end in one transaction.
     pub async fn commit_folder_batch(
@@ -186,121 +657,25 @@ impl Database {
         last_modseq: Option<u64>,
         last_uid: Option<u32>,
     ) -> Result<()> {
-        if messages.len() != bodies.len() {
-            anyhow::bail!("messages and bodies length mismatch");
+        // Header-only (pending) messages carry no body row, so bodies may be a subset.
+        if bodies.len() > messages.len() {
+            anyhow::bail!("more bodies than messages in folder batch");
         }
 
         let mut tx: Transaction<'_, Sqlite> = self.pool.begin().await.context("begin tx")?;
         let now = now_ts();
 
-        for (message, body) in messages.iter().zip(bodies.iter()) {
-            sqlx::query(
-                r#"
-                INSERT INTO messages (
-                    id, account_id, folder, uid, thread_id, internal_date,
-                    subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
-                    flags, labels, has_attachments, size_bytes, raw_hash,
-                    created_at, updated_at
-                )
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
-                ON CONFLICT(id) DO UPDATE SET
-                    account_id = excluded.account_id,
-                    folder = excluded.folder,
-                    uid = excluded.uid,
-                    thread_id = excluded.thread_id,
-                    internal_date = excluded.internal_date,
-                    subject = excluded.subject,
-                    from_addr = excluded.from_addr,
-                    to_addrs = excluded.to_addrs,
-                    cc_addrs = excluded.cc_addrs,
-                    bcc_addrs = excluded.bcc_addrs,
-                    flags = excluded.flags,
-                    labels = excluded.labels,
-                    has_attachments = excluded.has_attachments,
-                    size_bytes = excluded.size_bytes,
-                    raw_hash = excluded.raw_hash,
-                    created_at = excluded.created_at,
-                    updated_at = excluded.updated_at;
-                "#,
-            )
-            .bind(&message.id)
-            .bind(&message.account_id)
-            .bind(&message.folder)
-            .bind(message.uid.map(|v| v as i64))
-            .bind(&message.thread_id)
-            .bind(message.internal_date)
-            .bind(&message.subject)
-            .bind(&message.from)
-            .bind(&message.to)
-            .bind(&message.cc)
-            .bind(&message.bcc)
-            .bind(serde_json::to_string(&message.flags).unwrap_or_else(|_| "[]".into()))
-            .bind(serde_json::to_string(&message.labels).unwrap_or_else(|_| "[]".into()))
-            .bind(if message.has_attachments { 1 } else { 0 })
-            .bind(message.size_bytes.map(|v| v as i64))
-            .bind(&message.raw_hash)
-            .bind(message.created_at)
-            .bind(message.updated_at)
-            .execute(&mut *tx)
-            .await
-            .context("upserting message in tx")?;
-
-            sqlx::query(
-                r#"
-                INSERT INTO bodies (message_id, raw_rfc822, sanitized_text, mime_summary, attachments_json, sanitized_at)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-                ON CONFLICT(message_id) DO UPDATE SET
-                    raw_rfc822 = excluded.raw_rfc822,
-                    sanitized_text = excluded.sanitized_text,
-                    mime_summary = excluded.mime_summary,
-                    attachments_json = excluded.attachments_json,
-                    sanitized_at = excluded.sanitized_at;
-                "#,
-            )
-            .bind(&body.message_id)
-            .bind(&body.raw_rfc822)
-            .bind(&body.sanitized_text)
-            .bind(&body.mime_summary)
-            .bind(&body.attachments_json)
-            .bind(body.sanitized_at)
-            .execute(&mut *tx)
-            .await
-            .context("upserting body in tx")?;
-        }
-
-        if !location_updates.is_empty() {
-            for (message_id, folder, uid, flags, labels, thread_id, internal_date, size_bytes) in
-                location_updates
-            {
-                sqlx::query(
-                    r#"
-                    UPDATE messages
-                    SET folder = ?1,
-                        uid = ?2,
-                        flags = ?3,
-                        labels = ?4,
-                        thread_id = ?5,
-                        internal_date = ?6,
-                        size_bytes = ?7,
-                        updated_at = ?8
-                    WHERE account_id = ?9 AND id = ?10;
-                    "#,
-                )
-                .bind(folder)
-                .bind(*uid as i64)
-                .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
-                .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
-                .bind(thread_id)
-                .bind(internal_date)
-                .bind(size_bytes.map(|v| v as i64))
-                .bind(now)
-                .bind(account_id)
-                .bind(message_id)
-                .execute(&mut *tx)
-                .await
-                .context("updating message location in tx")?;
-            }
-        }
+        let cipher = self.cipher_for(account_id);
+        write_messages_in_tx(
+            &mut tx,
+            account_id,
+            cipher.as_deref(),
+            &self.blob_store(),
+            messages,
+            bodies,
+            location_updates,
+        )
+        .await?;
 
         if !flag_updates.is_empty() {
             for (uid, flags, labels) in flag_updates {
@@ -328,9 +703,10 @@ impl Database {
             r#"
             INSERT INTO folders (
                 account_id, name, uidvalidity, highest_uid, highestmodseq,
-                exists_count, last_sync_ts, last_uid_scan_ts, created_at, updated_at
+                exists_count, last_sync_ts, last_uid_scan_ts, baseline_scan_uid,
+                created_at, updated_at, resume_modseq, resume_uid, checkpoint_from_uid
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
             ON CONFLICT(account_id, name) DO UPDATE SET
                 uidvalidity = excluded.uidvalidity,
                 highest_uid = excluded.highest_uid,
@@ -338,7 +714,11 @@ impl Database {
                 exists_count = excluded.exists_count,
                 last_sync_ts = excluded.last_sync_ts,
                 last_uid_scan_ts = excluded.last_uid_scan_ts,
-                updated_at = excluded.updated_at;
+                baseline_scan_uid = excluded.baseline_scan_uid,
+                updated_at = excluded.updated_at,
+                resume_modseq = excluded.resume_modseq,
+                resume_uid = excluded.resume_uid,
+                checkpoint_from_uid = excluded.checkpoint_from_uid;
             "#,
         )
         .bind(account_id)
@@ -349,8 +729,12 @@ impl Database {
         .bind(folder_update.exists_count.map(|v| v as i64))
         .bind(folder_update.last_sync_ts)
         .bind(folder_update.last_uid_scan_ts)
+        .bind(folder_update.baseline_scan_uid.map(|v| v as i64))
         .bind(now)
         .bind(now)
+        .bind(folder_update.resume_modseq.map(|v| v as i64))
+        .bind(folder_update.resume_uid.map(|v| v as i64))
+        .bind(folder_update.checkpoint_from_uid.map(|v| v as i64))
         .execute(&mut *tx)
         .await
         .context("upserting folder state in tx")?;
@@ -399,7 +783,10 @@ impl Database {
                 safe_mode INTEGER NOT NULL,
                 folders TEXT NOT NULL,
                 created_at INTEGER NOT NULL,
-                updated_at INTEGER NOT NULL
+                updated_at INTEGER NOT NULL,
+                max_download_bps INTEGER,
+                folder_policies TEXT NOT NULL DEFAULT '{}',
+                encrypt_columns INTEGER NOT NULL DEFAULT 0
             );
 
             CREATE TABLE IF NOT EXISTS folders (
@@ -412,6 +799,11 @@ impl Database {
                 exists_count INTEGER,
                 last_sync_ts INTEGER,
                 last_uid_scan_ts INTEGER,
+                baseline_scan_uid INTEGER,
+                resume_modseq INTEGER,
+                resume_uid INTEGER,
+                checkpoint_from_uid INTEGER,
+                backfill_since TEXT,
                 created_at INTEGER NOT NULL,
                 updated_at INTEGER NOT NULL,
                 UNIQUE(account_id, name),
@@ -438,6 +830,8 @@ impl Database {
                 raw_hash TEXT,
                 created_at INTEGER NOT NULL,
                 updated_at INTEGER NOT NULL,
+                body_status TEXT NOT NULL DEFAULT 'full',
+                from_name TEXT,
                 FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
             );
             CREATE INDEX IF NOT EXISTS idx_messages_account_folder ON messages(account_id, folder);
@@ -451,6 +845,9 @@ impl Database {
                 mime_summary TEXT,
                 attachments_json TEXT,
                 sanitized_at INTEGER,
+                sanitizer_version INTEGER,
+                raw_format INTEGER NOT NULL DEFAULT 0,
+                degraded INTEGER NOT NULL DEFAULT 0,
                 FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
             );
 
@@ -465,22 +862,82 @@ impl Database {
                 PRIMARY KEY (account_id, folder),
                 FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
             );
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .context("running migrations")?;
-
-        // Migration: Add highestmodseq column to folders table if it doesn't exist
-        // This is for existing databases that were created before this column was added
-        let _ = sqlx::query(
-            r#"
-            ALTER TABLE folders ADD COLUMN highestmodseq INTEGER;
-            "#,
-        )
-        .execute(&self.pool)
-        .await;
-        // Ignore errors (column might already exist)
+
+            CREATE TABLE IF NOT EXISTS signatures (
+                account_id TEXT NOT NULL,
+                alias TEXT NOT NULL DEFAULT '',
+                body TEXT NOT NULL,
+                above_quote INTEGER NOT NULL DEFAULT 0,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (account_id, alias),
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS processed_messages (
+                consumer TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                processed_at INTEGER NOT NULL,
+                PRIMARY KEY (consumer, message_id),
+                FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_processed_messages_message ON processed_messages(message_id);
+
+            CREATE TABLE IF NOT EXISTS sync_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                account_id TEXT NOT NULL,
+                folder TEXT NOT NULL,
+                run_started_at INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                added INTEGER NOT NULL,
+                updated INTEGER NOT NULL,
+                deleted INTEGER NOT NULL,
+                bytes INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                error TEXT,
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            );
+            CREATE INDEX IF NOT EXISTS idx_sync_runs_account_started ON sync_runs(account_id, run_started_at);
+
+            CREATE TABLE IF NOT EXISTS fetch_retries (
+                account_id TEXT NOT NULL,
+                folder TEXT NOT NULL,
+                uid INTEGER NOT NULL,
+                attempts INTEGER NOT NULL,
+                last_error TEXT,
+                first_failed_at INTEGER NOT NULL,
+                last_attempt_at INTEGER NOT NULL,
+                PRIMARY KEY (account_id, folder, uid),
+                FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("running migrations")?;
+
+        ops::ensure_ops_table(&self.pool).await?;
+        threads::ensure_threads_table(&self.pool).await?;
+        audit::ensure_audit_table(&self.pool).await?;
+        cleanup::ensure_cleanup_table(&self.pool).await?;
+        quota::ensure_quota_table(&self.pool).await?;
+        identity::ensure_identity_table(&self.pool).await?;
+        reply_later::ensure_reply_later_table(&self.pool).await?;
+        notes::ensure_notes_table(&self.pool).await?;
+        summaries::ensure_summaries_table(&self.pool).await?;
+        translations::ensure_translations_table(&self.pool).await?;
+        learning::ensure_learning_table(&self.pool).await?;
+        addresses::ensure_addresses_table(&self.pool).await?;
+
+        // Migration: Add highestmodseq column to folders table if it doesn't exist
+        // This is for existing databases that were created before this column was added
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE folders ADD COLUMN highestmodseq INTEGER;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
 
         // Migration: Add last_uid_scan_ts column to folders table if it doesn't exist
         let _ = sqlx::query(
@@ -502,14 +959,299 @@ impl Database {
         .await;
         // Ignore errors (column might already exist)
 
+        // Migration: Add backfill_since column (oldest date covered by `otto backfill`)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE folders ADD COLUMN backfill_since TEXT;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add max_download_bps column (per-account FETCH throttle)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN max_download_bps INTEGER;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add folder_policies column (per-folder cutoff/body/enabled overrides)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN folder_policies TEXT NOT NULL DEFAULT '{}';
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add encrypt_columns column (per-account column encryption)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN encrypt_columns INTEGER NOT NULL DEFAULT 0;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add unread_only column (per-account unread-only sync)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN unread_only INTEGER NOT NULL DEFAULT 0;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add max_message_bytes column (per-account body size cap)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN max_message_bytes INTEGER;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add smart_folders column (saved queries shown as virtual folders)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN smart_folders TEXT NOT NULL DEFAULT '[]';
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add all_mail_mode column (Gmail All Mail single-pass sync)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN all_mail_mode INTEGER NOT NULL DEFAULT 0;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add imap_endpoint column (host, port, TLS mode, pinned certificate)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN imap_endpoint TEXT NOT NULL DEFAULT '{}';
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add enabled column (`otto pause`)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN enabled INTEGER NOT NULL DEFAULT 1;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add credential column (password accounts; NULL = OAuth)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN credential TEXT;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Password commands used to live in the endpoint JSON; move them to `credential`.
+        sqlx::query(
+            r#"
+            UPDATE accounts
+            SET credential = json_object('kind', 'password_command', 'command', json_extract(imap_endpoint, '$.password_cmd')),
+                imap_endpoint = json_remove(imap_endpoint, '$.password_cmd')
+            WHERE credential IS NULL
+              AND json_valid(imap_endpoint)
+              AND json_extract(imap_endpoint, '$.password_cmd') IS NOT NULL;
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("moving password commands to accounts.credential")?;
+
+        // Password accounts off Gmail's server were stored as Gmail; they are generic IMAP.
+        sqlx::query(
+            r#"
+            UPDATE accounts
+            SET provider = 'imap'
+            WHERE provider = 'gmail-imap'
+              AND credential IS NOT NULL
+              AND json_valid(imap_endpoint)
+              AND lower(json_extract(imap_endpoint, '$.host')) <> 'imap.gmail.com';
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .context("marking password accounts as generic IMAP")?;
+
+        // Migration: Add cleanup_rules column (age-based rules run by the daemon)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN cleanup_rules TEXT NOT NULL DEFAULT '[]';
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add namespace column (folder prefix + hierarchy delimiter; NULL = unknown)
+        let _ = sqlx::query("ALTER TABLE accounts ADD COLUMN namespace TEXT;")
+            .execute(&self.pool)
+            .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add notify_rules column (new-mail notification channels)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN notify_rules TEXT NOT NULL DEFAULT '[]';
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add tls_policy column (minimum TLS version, allowed cipher suites)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE accounts ADD COLUMN tls_policy TEXT NOT NULL DEFAULT '{}';
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add from_name column (display name split out of From)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE messages ADD COLUMN from_name TEXT;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add body_status column (headers-first sync leaves bodies pending)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE messages ADD COLUMN body_status TEXT NOT NULL DEFAULT 'full';
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add resume_modseq/resume_uid columns (incremental pass checkpoint)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE folders ADD COLUMN resume_modseq INTEGER;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE folders ADD COLUMN resume_uid INTEGER;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (columns might already exist)
+
+        // Migration: Add checkpoint_from_uid column (first UID of a checkpointed batch)
+        let _ = sqlx::query("ALTER TABLE folders ADD COLUMN checkpoint_from_uid INTEGER;")
+            .execute(&self.pool)
+            .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add baseline_scan_uid column (windowed baseline scan checkpoint)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE folders ADD COLUMN baseline_scan_uid INTEGER;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add LIST discovery columns (NULL attributes = not seen in the last LIST)
+        let _ = sqlx::query("ALTER TABLE folders ADD COLUMN attributes TEXT;")
+            .execute(&self.pool)
+            .await;
+        let _ = sqlx::query("ALTER TABLE folders ADD COLUMN delimiter TEXT;")
+            .execute(&self.pool)
+            .await;
+        // Ignore errors (columns might already exist)
+
+        // Migration: Add server-reported counts (STATUS MESSAGES/UNSEEN) for badges
+        for column in ["server_unseen", "server_messages", "counts_checked_at"] {
+            let _ = sqlx::query(&format!(
+                "ALTER TABLE folders ADD COLUMN {} INTEGER;",
+                column
+            ))
+            .execute(&self.pool)
+            .await;
+        }
+        // Ignore errors (columns might already exist)
+
+        // Migration: Add message_id_header column (cross-folder dedup without X-GM-MSGID)
+        let _ = sqlx::query(
+            r#"
+            ALTER TABLE messages ADD COLUMN message_id_header TEXT;
+            "#,
+        )
+        .execute(&self.pool)
+        .await;
+        // Ignore errors (column might already exist)
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_messages_account_message_id_header ON messages(account_id, message_id_header);",
+        )
+        .execute(&self.pool)
+        .await
+        .context("creating message_id_header index")?;
+
+        // Migration: Add sanitizer_version (NULL = sanitized before versioning; `otto resanitize`)
+        let _ = sqlx::query("ALTER TABLE bodies ADD COLUMN sanitizer_version INTEGER;")
+            .execute(&self.pool)
+            .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add raw_format (0 = as received, 1 = zstd; `otto compress-bodies`)
+        let _ = sqlx::query("ALTER TABLE bodies ADD COLUMN raw_format INTEGER NOT NULL DEFAULT 0;")
+            .execute(&self.pool)
+            .await;
+        // Ignore errors (column might already exist)
+
+        // Migration: Add degraded (1 = HTML tag-stripped after html2text ran out of time)
+        let _ = sqlx::query("ALTER TABLE bodies ADD COLUMN degraded INTEGER NOT NULL DEFAULT 0;")
+            .execute(&self.pool)
+            .await;
+        // Ignore errors (column might already exist)
+
+        blobs::ensure_blob_tables(&self.pool).await?;
+
         Ok(())
     }
 
     pub async fn save_account(&self, account: &Account) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO accounts (id, email, provider, cutoff_since, poll_interval_minutes, prefetch_recent, safe_mode, folders, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            INSERT INTO accounts (id, email, provider, cutoff_since, poll_interval_minutes, prefetch_recent, safe_mode, folders, created_at, updated_at, max_download_bps, folder_policies, encrypt_columns, unread_only, max_message_bytes, smart_folders, all_mail_mode, imap_endpoint, enabled, credential, cleanup_rules, notify_rules, namespace, tls_policy)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
             ON CONFLICT(id) DO UPDATE SET
                 email = excluded.email,
                 provider = excluded.provider,
@@ -518,7 +1260,21 @@ impl Database {
                 prefetch_recent = excluded.prefetch_recent,
                 safe_mode = excluded.safe_mode,
                 folders = excluded.folders,
-                updated_at = excluded.updated_at;
+                updated_at = excluded.updated_at,
+                max_download_bps = excluded.max_download_bps,
+                folder_policies = excluded.folder_policies,
+                encrypt_columns = excluded.encrypt_columns,
+                unread_only = excluded.unread_only,
+                max_message_bytes = excluded.max_message_bytes,
+                smart_folders = excluded.smart_folders,
+                all_mail_mode = excluded.all_mail_mode,
+                imap_endpoint = excluded.imap_endpoint,
+                enabled = excluded.enabled,
+                credential = excluded.credential,
+                cleanup_rules = excluded.cleanup_rules,
+                notify_rules = excluded.notify_rules,
+                namespace = excluded.namespace,
+                tls_policy = excluded.tls_policy;
             "#,
         )
         .bind(&account.id)
@@ -531,6 +1287,56 @@ impl Database {
         .bind(serde_json::to_string(&account.settings.folders).unwrap_or_else(|_| "[]".into()))
         .bind(account.created_at)
         .bind(account.updated_at)
+        .bind(
+            account
+                .settings
+                .max_download_bytes_per_sec
+                .map(|bps| bps as i64),
+        )
+        .bind(
+            serde_json::to_string(&account.settings.folder_policies)
+                .unwrap_or_else(|_| "{}".into()),
+        )
+        .bind(if account.settings.encrypt_columns {
+            1
+        } else {
+            0
+        })
+        .bind(if account.settings.unread_only { 1 } else { 0 })
+        .bind(account.settings.max_message_bytes.map(|bytes| bytes as i64))
+        .bind(
+            serde_json::to_string(&account.settings.smart_folders)
+                .unwrap_or_else(|_| "[]".into()),
+        )
+        .bind(if account.settings.all_mail_mode {
+            1
+        } else {
+            0
+        })
+        .bind(serde_json::to_string(&account.settings.imap).unwrap_or_else(|_| "{}".into()))
+        .bind(if account.settings.enabled { 1 } else { 0 })
+        .bind(match &account.settings.credential {
+            Credential::OAuth => None,
+            credential => serde_json::to_string(credential).ok(),
+        })
+        .bind(
+            serde_json::to_string(&account.settings.cleanup_rules)
+                .unwrap_or_else(|_| "[]".into()),
+        )
+        .bind(
+            serde_json::to_string(&account.settings.notify_rules)
+                .unwrap_or_else(|_| "[]".into()),
+        )
+        .bind(
+            account
+                .settings
+                .namespace
+                .as_ref()
+                .and_then(|namespace| serde_json::to_string(namespace).ok()),
+        )
+        .bind(
+            serde_json::to_string(&account.settings.tls_policy).unwrap_or_else(|_| "{}".into()),
+        )
         .execute(&self.pool)
         .await
         .context("upserting account")?;
@@ -538,530 +1344,1950 @@ impl Database {
     }
 
     pub async fn list_accounts(&self) -> Result<Vec<Account>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT id, email, provider, cutoff_since, poll_interval_minutes, prefetch_recent, safe_mode, folders, created_at, updated_at
-            FROM accounts;
+        let rows = sqlx::query(ACCOUNT_SELECT)
+            .fetch_all(&self.pool)
+            .await
+            .context("loading accounts")?;
+
+        Ok(rows.iter().map(account_from_row).collect())
+    }
+
+    /// Rewrites the account's encrypted columns from `from` to `to` (`None` = plaintext) and sets
+    /// `accounts.encrypt_columns` to match, in one transaction so the flag never disagrees with
+    /// the rows. Pages by id so raw bodies are never all in memory. Returns rows rewritten.
+    pub async fn reseal_account(
+        &self,
+        account_id: &str,
+        from: Option<&ColumnCipher>,
+        to: Option<&ColumnCipher>,
+    ) -> Result<usize> {
+        const PAGE: i64 = 200;
+        let blob_store = self.blob_store();
+        let mut tx = self.pool.begin().await.context("beginning reseal tx")?;
+        let mut resealed = 0;
+
+        let mut after = String::new();
+        loop {
+            let rows = sqlx::query(
+                r#"
+                SELECT id, subject, from_addr, from_name
+                FROM messages
+                WHERE account_id = ?1 AND id > ?2
+                ORDER BY id
+                LIMIT ?3;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading messages to reseal")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+
+            for row in rows {
+                sqlx::query(
+                    "UPDATE messages SET subject = ?1, from_addr = ?2, from_name = ?3 WHERE id = ?4",
+                )
+                .bind(reseal_text(from, to, row.get(1))?)
+                .bind(reseal_text(from, to, row.get(2))?)
+                .bind(reseal_text(from, to, row.get(3))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing message")?;
+                resealed += 1;
+            }
+        }
+
+        let mut after = String::new();
+        loop {
+            let rows = sqlx::query(
+                r#"
+                SELECT b.message_id, b.raw_rfc822, b.sanitized_text, b.blob_hash, bl.data,
+                       bl.offloaded, bl.format
+                FROM bodies b
+                JOIN messages m ON m.id = b.message_id
+                LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+                WHERE m.account_id = ?1 AND b.message_id > ?2
+                ORDER BY b.message_id
+                LIMIT ?3;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading bodies to reseal")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+
+            for row in rows {
+                let reseal = |raw: Vec<u8>| -> Result<(Vec<u8>, Vec<u8>)> {
+                    let plain = match from {
+                        Some(cipher) => cipher.open_bytes(&raw)?,
+                        None => raw,
+                    };
+                    let sealed = match to {
+                        Some(cipher) => cipher.seal_bytes(&plain)?,
+                        None => plain.clone(),
+                    };
+                    Ok((plain, sealed))
+                };
+                let raw = row
+                    .get::<Option<Vec<u8>>, _>(1)
+                    .map(|raw| reseal(raw).map(|(_, sealed)| sealed))
+                    .transpose()?;
+                // Blobs are addressed under the account's key, so a resealed blob moves to a
+                // new hash (of the uncompressed message); the old one is released with its last
+                // reference.
+                let blob_hash = row.get::<Option<String>, _>(3);
+                let blob_hash = match (blob_hash, row.get::<Option<Vec<u8>>, _>(4)) {
+                    (Some(hash), Some(data)) => {
+                        let offloaded = row.get::<Option<i64>, _>(5) == Some(1);
+                        let format = RawFormat::from_i64(row.get(6))?;
+                        let (packed, sealed) = reseal(blob_store.read(&hash, data, offloaded)?)?;
+                        let plain = compression::decompress(packed, format)?;
+                        let hash = blobs::content_hash(to, &plain);
+                        blob_store.put(&mut tx, &hash, &sealed, format).await?;
+                        Some(hash)
+                    }
+                    (hash, _) => hash,
+                };
+                sqlx::query(
+                    "UPDATE bodies SET raw_rfc822 = ?1, blob_hash = ?2, sanitized_text = ?3 WHERE message_id = ?4",
+                )
+                .bind(raw)
+                .bind(blob_hash)
+                .bind(reseal_text(from, to, row.get(2))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing body")?;
+                resealed += 1;
+            }
+        }
+
+        let rows = sqlx::query("SELECT message_id, note FROM message_notes WHERE account_id = ?1")
+            .bind(account_id)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading notes to reseal")?;
+        for row in rows {
+            sqlx::query("UPDATE message_notes SET note = ?1 WHERE message_id = ?2")
+                .bind(reseal_text(from, to, row.get(1))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing note")?;
+        }
+
+        let rows =
+            sqlx::query("SELECT message_id, summary FROM message_summaries WHERE account_id = ?1")
+                .bind(account_id)
+                .fetch_all(&mut *tx)
+                .await
+                .context("loading summaries to reseal")?;
+        for row in rows {
+            sqlx::query("UPDATE message_summaries SET summary = ?1 WHERE message_id = ?2")
+                .bind(reseal_text(from, to, row.get(1))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing summary")?;
+        }
+
+        let rows = sqlx::query(
+            "SELECT message_id, target, text FROM message_translations WHERE account_id = ?1",
+        )
+        .bind(account_id)
+        .fetch_all(&mut *tx)
+        .await
+        .context("loading translations to reseal")?;
+        for row in rows {
+            sqlx::query(
+                "UPDATE message_translations SET text = ?1 WHERE message_id = ?2 AND target = ?3",
+            )
+            .bind(reseal_text(from, to, row.get(2))?)
+            .bind(row.get::<String, _>(0))
+            .bind(row.get::<String, _>(1))
+            .execute(&mut *tx)
+            .await
+            .context("resealing translation")?;
+        }
+
+        // Learned sender counts are keyed by a hash under the column key: rekey them too.
+        let rows = sqlx::query(
+            "SELECT sender_key, action, label, sender FROM sender_actions WHERE account_id = ?1",
+        )
+        .bind(account_id)
+        .fetch_all(&mut *tx)
+        .await
+        .context("loading sender actions to reseal")?;
+        for row in rows {
+            let sender = match from {
+                Some(cipher) => cipher.open_text(&row.get::<String, _>(3))?,
+                None => row.get(3),
+            };
+            let stored = match to {
+                Some(cipher) => cipher.seal_text(&sender)?,
+                None => sender.clone(),
+            };
+            sqlx::query(
+                r#"
+                UPDATE sender_actions SET sender_key = ?1, sender = ?2
+                WHERE account_id = ?3 AND sender_key = ?4 AND action = ?5 AND label = ?6;
+                "#,
+            )
+            .bind(blobs::content_hash(to, sender.as_bytes()))
+            .bind(stored)
+            .bind(account_id)
+            .bind(row.get::<String, _>(0))
+            .bind(row.get::<String, _>(1))
+            .bind(row.get::<String, _>(2))
+            .execute(&mut *tx)
+            .await
+            .context("resealing sender action")?;
+        }
+
+        sqlx::query("UPDATE accounts SET encrypt_columns = ?1, updated_at = ?2 WHERE id = ?3")
+            .bind(if to.is_some() { 1 } else { 0 })
+            .bind(now_ts())
+            .bind(account_id)
+            .execute(&mut *tx)
+            .await
+            .context("updating encrypt_columns")?;
+
+        tx.commit().await.context("committing reseal tx")?;
+        Ok(resealed)
+    }
+
+    /// Sets the signature of `signature.alias` (the account default for `None`).
+    pub async fn save_signature(&self, signature: &Signature) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO signatures (account_id, alias, body, above_quote, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT(account_id, alias) DO UPDATE SET
+                body = excluded.body,
+                above_quote = excluded.above_quote,
+                updated_at = excluded.updated_at;
+            "#,
+        )
+        .bind(&signature.account_id)
+        .bind(signature.alias.as_deref().unwrap_or(""))
+        .bind(&signature.body)
+        .bind(if signature.above_quote { 1 } else { 0 })
+        .bind(now_ts())
+        .execute(&self.pool)
+        .await
+        .context("upserting signature")?;
+        Ok(())
+    }
+
+    /// Loads the signature for a send-as alias, falling back to the account default.
+    pub async fn load_signature(
+        &self,
+        account_id: &str,
+        alias: Option<&str>,
+    ) -> Result<Option<Signature>> {
+        let row = sqlx::query(
+            r#"
+            SELECT alias, body, above_quote
+            FROM signatures
+            WHERE account_id = ?1 AND (alias = ?2 OR alias = '')
+            ORDER BY alias = ?2 DESC
+            LIMIT 1;
+            "#,
+        )
+        .bind(account_id)
+        .bind(alias.unwrap_or(""))
+        .fetch_optional(&self.pool)
+        .await
+        .context("loading signature")?;
+
+        Ok(row.map(|r| {
+            let alias: String = r.get(0);
+            Signature {
+                account_id: account_id.to_string(),
+                alias: (!alias.is_empty()).then_some(alias),
+                body: r.get(1),
+                above_quote: r.get::<i64, _>(2) == 1,
+            }
+        }))
+    }
+
+    /// Removes the signature of `alias` (the account default for `None`); false when none was set.
+    pub async fn clear_signature(&self, account_id: &str, alias: Option<&str>) -> Result<bool> {
+        let removed = sqlx::query("DELETE FROM signatures WHERE account_id = ?1 AND alias = ?2")
+            .bind(account_id)
+            .bind(alias.unwrap_or(""))
+            .execute(&self.pool)
+            .await
+            .context("deleting signature")?
+            .rows_affected();
+        Ok(removed > 0)
+    }
+
+    pub async fn upsert_folder_state(
+        &self,
+        account_id: &str,
+        name: &str,
+        update: &FolderStateUpdate,
+    ) -> Result<FolderState> {
+        let now = now_ts();
+        sqlx::query(
+            r#"
+            INSERT INTO folders (account_id, name, uidvalidity, highest_uid, highestmodseq, exists_count, last_sync_ts, last_uid_scan_ts, baseline_scan_uid, created_at, updated_at, resume_modseq, resume_uid, checkpoint_from_uid)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+            ON CONFLICT(account_id, name) DO UPDATE SET
+                uidvalidity = excluded.uidvalidity,
+                highest_uid = excluded.highest_uid,
+                highestmodseq = excluded.highestmodseq,
+                exists_count = excluded.exists_count,
+                last_sync_ts = excluded.last_sync_ts,
+                last_uid_scan_ts = excluded.last_uid_scan_ts,
+                baseline_scan_uid = excluded.baseline_scan_uid,
+                updated_at = excluded.updated_at,
+                resume_modseq = excluded.resume_modseq,
+                resume_uid = excluded.resume_uid,
+                checkpoint_from_uid = excluded.checkpoint_from_uid;
+            "#,
+        )
+        .bind(account_id)
+        .bind(name)
+        .bind(update.uidvalidity.map(|v| v as i64))
+        .bind(update.highest_uid.map(|v| v as i64))
+        .bind(update.highestmodseq.map(|v| v as i64))
+        .bind(update.exists_count.map(|v| v as i64))
+        .bind(update.last_sync_ts)
+        .bind(update.last_uid_scan_ts)
+        .bind(update.baseline_scan_uid.map(|v| v as i64))
+        .bind(now)
+        .bind(now)
+        .bind(update.resume_modseq.map(|v| v as i64))
+        .bind(update.resume_uid.map(|v| v as i64))
+        .bind(update.checkpoint_from_uid.map(|v| v as i64))
+        .execute(&self.pool)
+        .await
+        .context("upserting folder")?;
+
+        let row = sqlx::query(
+            r#"
+            SELECT id, uidvalidity, highest_uid, highestmodseq, exists_count, last_sync_ts, last_uid_scan_ts, baseline_scan_uid, resume_modseq, resume_uid, backfill_since
+            FROM folders
+            WHERE account_id = ?1 AND name = ?2
+            "#,
+        )
+        .bind(account_id)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+        .context("reloading folder")?;
+
+        Ok(FolderState {
+            id: row.get::<i64, _>(0),
+            account_id: account_id.to_string(),
+            name: name.to_string(),
+            uidvalidity: row.get::<Option<i64>, _>(1).map(|v| v as u32),
+            highest_uid: row.get::<Option<i64>, _>(2).map(|v| v as u32),
+            highestmodseq: row.get::<Option<i64>, _>(3).map(|v| v as u64),
+            exists_count: row.get::<Option<i64>, _>(4).map(|v| v as u32),
+            last_sync_ts: row.get::<Option<i64>, _>(5),
+            last_uid_scan_ts: row.get::<Option<i64>, _>(6),
+            baseline_scan_uid: row.get::<Option<i64>, _>(7).map(|v| v as u32),
+            resume_modseq: row.get::<Option<i64>, _>(8).map(|v| v as u64),
+            resume_uid: row.get::<Option<i64>, _>(9).map(|v| v as u32),
+            backfill_since: parse_optional_date(row.get::<Option<String>, _>(10)),
+        })
+    }
+
+    pub async fn list_folders(&self, account_id: &str) -> Result<Vec<FolderState>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, name, uidvalidity, highest_uid, highestmodseq, exists_count, last_sync_ts, last_uid_scan_ts, baseline_scan_uid, resume_modseq, resume_uid, backfill_since
+            FROM folders
+            WHERE account_id = ?1
+            ORDER BY name ASC;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading folders")?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            out.push(FolderState {
+                id: row.get(0),
+                account_id: account_id.to_string(),
+                name: row.get(1),
+                uidvalidity: row.get::<Option<i64>, _>(2).map(|v| v as u32),
+                highest_uid: row.get::<Option<i64>, _>(3).map(|v| v as u32),
+                highestmodseq: row.get::<Option<i64>, _>(4).map(|v| v as u64),
+                exists_count: row.get::<Option<i64>, _>(5).map(|v| v as u32),
+                last_sync_ts: row.get(6),
+                last_uid_scan_ts: row.get(7),
+                baseline_scan_uid: row.get::<Option<i64>, _>(8).map(|v| v as u32),
+                resume_modseq: row.get::<Option<i64>, _>(9).map(|v| v as u64),
+                resume_uid: row.get::<Option<i64>, _>(10).map(|v| v as u32),
+                backfill_since: parse_optional_date(row.get::<Option<String>, _>(11)),
+            });
+        }
+        Ok(out)
+    }
+
+    /// Replaces the account's discovered-folder list with a fresh `LIST` result. Sync state
+    /// is left alone; folders that disappeared from the server keep it but lose their attributes.
+    pub async fn record_discovered_folders(
+        &self,
+        account_id: &str,
+        mailboxes: &[MailboxInfo],
+    ) -> Result<()> {
+        let now = now_ts();
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning folder discovery tx")?;
+        sqlx::query("UPDATE folders SET attributes = NULL WHERE account_id = ?1")
+            .bind(account_id)
+            .execute(&mut *tx)
+            .await
+            .context("clearing discovered folders")?;
+        for mailbox in mailboxes {
+            sqlx::query(
+                r#"
+                INSERT INTO folders (account_id, name, attributes, delimiter, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ON CONFLICT(account_id, name) DO UPDATE SET
+                    attributes = excluded.attributes,
+                    delimiter = excluded.delimiter,
+                    updated_at = excluded.updated_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&mailbox.name)
+            .bind(serde_json::to_string(&mailbox.attributes).unwrap_or_else(|_| "[]".into()))
+            .bind(&mailbox.delimiter)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .context("storing discovered folder")?;
+        }
+        tx.commit().await.context("committing folder discovery")?;
+        Ok(())
+    }
+
+    /// Folders seen in the last `LIST`, by name.
+    pub async fn list_discovered_folders(&self, account_id: &str) -> Result<Vec<MailboxInfo>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT name, delimiter, attributes FROM folders
+            WHERE account_id = ?1 AND attributes IS NOT NULL
+            ORDER BY name ASC;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading discovered folders")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| MailboxInfo {
+                name: row.get(0),
+                delimiter: row.get(1),
+                attributes: serde_json::from_str(&row.get::<String, _>(2)).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Stores the counts the server reported for each folder, creating rows for folders not
+    /// seen before. Folders missing from `counts` keep what they had.
+    pub async fn save_folder_counts(
+        &self,
+        account_id: &str,
+        counts: &[FolderCounts],
+    ) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning folder counts tx")?;
+        for entry in counts {
+            sqlx::query(
+                r#"
+                INSERT INTO folders (account_id, name, server_unseen, server_messages, counts_checked_at, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?5)
+                ON CONFLICT(account_id, name) DO UPDATE SET
+                    server_unseen = excluded.server_unseen,
+                    server_messages = excluded.server_messages,
+                    counts_checked_at = excluded.counts_checked_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&entry.folder)
+            .bind(i64::from(entry.unseen))
+            .bind(i64::from(entry.messages))
+            .bind(entry.checked_at)
+            .execute(&mut *tx)
+            .await
+            .context("storing folder counts")?;
+        }
+        tx.commit().await.context("committing folder counts")?;
+        Ok(())
+    }
+
+    /// The last server-reported counts per folder name.
+    pub async fn load_folder_counts(
+        &self,
+        account_id: &str,
+    ) -> Result<HashMap<String, FolderCounts>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT name, server_unseen, server_messages, counts_checked_at FROM folders
+            WHERE account_id = ?1 AND counts_checked_at IS NOT NULL;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading folder counts")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let counts = FolderCounts {
+                    folder: row.get(0),
+                    unseen: row.get::<Option<i64>, _>(1).unwrap_or(0) as u32,
+                    messages: row.get::<Option<i64>, _>(2).unwrap_or(0) as u32,
+                    checked_at: row.get(3),
+                };
+                (counts.folder.clone(), counts)
+            })
+            .collect())
+    }
+
+    /// The account's folder for `role` per the last discovery, else the English Gmail name.
+    pub async fn role_folder(&self, account_id: &str, role: FolderRole) -> Result<String> {
+        let mut conn = self.pool.acquire().await.context("acquiring connection")?;
+        role_folder_in(&mut conn, account_id, role).await
+    }
+
+    /// Writes one backfill batch without touching the incremental sync state (MODSEQ, highest
+    /// UID, checkpoints); `backfill_since` advances once a whole date chunk is stored.
+    pub async fn commit_backfill_batch(
+        &self,
+        account_id: &str,
+        folder: &str,
+        messages: &[MessageRecord],
+        bodies: &[BodyRecord],
+        location_updates: &[MessageLocationUpdate],
+        backfill_since: Option<NaiveDate>,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("beginning backfill tx")?;
+        let cipher = self.cipher_for(account_id);
+        write_messages_in_tx(
+            &mut tx,
+            account_id,
+            cipher.as_deref(),
+            &self.blob_store(),
+            messages,
+            bodies,
+            location_updates,
+        )
+        .await?;
+
+        if let Some(since) = backfill_since {
+            let now = now_ts();
+            sqlx::query(
+                r#"
+                INSERT INTO folders (account_id, name, backfill_since, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT(account_id, name) DO UPDATE SET
+                    backfill_since = excluded.backfill_since,
+                    updated_at = excluded.updated_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(folder)
+            .bind(since.to_string())
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .context("recording backfill progress")?;
+        }
+
+        tx.commit().await.context("committing backfill tx")?;
+        Ok(())
+    }
+
+    /// Forgets backfill progress (after a UIDVALIDITY reset cleared the folder).
+    pub async fn clear_folder_backfill(&self, account_id: &str, folder: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE folders SET backfill_since = NULL, updated_at = ?1 WHERE account_id = ?2 AND name = ?3",
+        )
+        .bind(now_ts())
+        .bind(account_id)
+        .bind(folder)
+        .execute(&self.pool)
+        .await
+        .context("clearing folder backfill")?;
+        Ok(())
+    }
+
+    /// Counts one more failed attempt for each `(uid, error)` of `folder`.
+    pub async fn record_fetch_failures(
+        &self,
+        account_id: &str,
+        folder: &str,
+        failures: &[(u32, String)],
+    ) -> Result<()> {
+        if failures.is_empty() {
+            return Ok(());
+        }
+        let now = now_ts();
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning fetch retry tx")?;
+        for (uid, error) in failures {
+            sqlx::query(
+                r#"
+                INSERT INTO fetch_retries (account_id, folder, uid, attempts, last_error, first_failed_at, last_attempt_at)
+                VALUES (?1, ?2, ?3, 1, ?4, ?5, ?5)
+                ON CONFLICT(account_id, folder, uid) DO UPDATE SET
+                    attempts = attempts + 1,
+                    last_error = excluded.last_error,
+                    last_attempt_at = excluded.last_attempt_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(folder)
+            .bind(*uid as i64)
+            .bind(error)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .context("recording fetch failure")?;
+        }
+        tx.commit().await.context("committing fetch retry tx")?;
+        Ok(())
+    }
+
+    /// The folder's failed fetches, lowest UID first (including ones past the attempt limit).
+    pub async fn load_fetch_retries(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<Vec<FetchRetry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT uid, attempts, last_error, first_failed_at, last_attempt_at
+            FROM fetch_retries
+            WHERE account_id = ?1 AND folder = ?2
+            ORDER BY uid ASC;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading fetch retries")?;
+        Ok(rows
+            .iter()
+            .map(|row| FetchRetry {
+                uid: row.get::<i64, _>(0) as u32,
+                attempts: row.get::<i64, _>(1) as u32,
+                last_error: row.get(2),
+                first_failed_at: row.get(3),
+                last_attempt_at: row.get(4),
+            })
+            .collect())
+    }
+
+    /// Drops `uids` of `folder` from the retry queue (fetched, expunged, or UIDs invalidated).
+    pub async fn clear_fetch_retries(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<u64> {
+        if uids.is_empty() {
+            return Ok(0);
+        }
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("DELETE FROM fetch_retries WHERE account_id = ");
+        qb.push_bind(account_id);
+        qb.push(" AND folder = ");
+        qb.push_bind(folder);
+        qb.push(" AND uid IN (");
+        {
+            let mut separated = qb.separated(", ");
+            for uid in uids {
+                separated.push_bind(*uid as i64);
+            }
+        }
+        qb.push(")");
+        let res = qb
+            .build()
+            .execute(&self.pool)
+            .await
+            .context("clearing fetch retries")?;
+        Ok(res.rows_affected())
+    }
+
+    pub async fn load_message_ids_by_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<std::collections::HashMap<u32, String>> {
+        use std::collections::HashMap;
+
+        if uids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT uid, id FROM messages WHERE account_id = ");
+        qb.push_bind(account_id);
+        qb.push(" AND folder = ");
+        qb.push_bind(folder);
+        qb.push(" AND uid IN (");
+        {
+            let mut separated = qb.separated(", ");
+            for uid in uids {
+                separated.push_bind(*uid as i64);
+            }
+        }
+        qb.push(")");
+
+        let rows = qb
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("loading message ids by uid list")?;
+
+        let mut out = HashMap::new();
+        for row in rows {
+            let uid = row.get::<Option<i64>, _>(0).map(|v| v as u32).unwrap_or(0);
+            let id: String = row.get(1);
+            if uid > 0 {
+                out.insert(uid, id);
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Unread (no `Seen` flag), non-deleted messages per folder.
+    pub async fn unread_counts(&self, account_id: &str) -> Result<HashMap<String, u32>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT folder, COUNT(*)
+            FROM messages
+            WHERE account_id = ?1
+              AND flags NOT LIKE '%"Seen"%'
+              AND flags NOT LIKE '%"Deleted"%'
+            GROUP BY folder;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("counting unread messages")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get::<i64, _>(1) as u32))
+            .collect())
+    }
+
+    /// Unread messages of `folder` per label (for All Mail mode, where labels give membership).
+    pub async fn unread_label_counts(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<String, u32>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT label.value, COUNT(*)
+            FROM messages, json_each(messages.labels) AS label
+            WHERE account_id = ?1
+              AND folder = ?2
+              AND flags NOT LIKE '%"Seen"%'
+              AND flags NOT LIKE '%"Deleted"%'
+            GROUP BY label.value;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("counting unread messages per label")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get::<i64, _>(1) as u32))
+            .collect())
+    }
+
+    pub async fn load_uid_to_message_id_map_by_folder(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<std::collections::HashMap<u32, String>> {
+        use std::collections::HashMap;
+
+        let rows = sqlx::query(
+            r#"
+            SELECT uid, id
+            FROM messages
+            WHERE account_id = ?1 AND folder = ?2 AND uid IS NOT NULL;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading message uid map by folder")?;
+
+        let mut out = HashMap::new();
+        for row in rows {
+            let uid = row.get::<Option<i64>, _>(0).map(|v| v as u32).unwrap_or(0);
+            let id: String = row.get(1);
+            if uid > 0 {
+                out.insert(uid, id);
+            }
+        }
+        Ok(out)
+    }
+
+    /// Cached `(flags, labels)` of every UID-linked message in `folder`.
+    pub async fn load_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (Vec<String>, Vec<String>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT uid, flags, labels
+            FROM messages
+            WHERE account_id = ?1 AND folder = ?2 AND uid IS NOT NULL;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading cached flags by uid")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let flags = serde_json::from_str(&row.get::<String, _>(1)).unwrap_or_default();
+                let labels = serde_json::from_str(&row.get::<String, _>(2)).unwrap_or_default();
+                (row.get::<i64, _>(0) as u32, (flags, labels))
+            })
+            .collect())
+    }
+
+    pub async fn load_raw_hashes_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (String, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT uid, id, raw_hash
+            FROM messages
+            WHERE account_id = ?1 AND folder = ?2 AND uid IS NOT NULL
+              AND raw_hash IS NOT NULL AND body_status = 'full';
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading raw hashes by uid")?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get::<i64, _>(0) as u32, (row.get(1), row.get(2))))
+            .collect())
+    }
+
+    pub async fn batch_update_message_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+        updates: &[(u32, Vec<String>, Vec<String>)],
+    ) -> Result<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        let now = now_ts();
+        let mut tx = self.pool.begin().await.context("beginning transaction")?;
+
+        for (uid, flags, labels) in updates {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET flags = ?1, labels = ?2, updated_at = ?3
+                WHERE account_id = ?4 AND folder = ?5 AND uid = ?6;
+                "#,
+            )
+            .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
+            .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
+            .bind(now)
+            .bind(account_id)
+            .bind(folder)
+            .bind(*uid as i64)
+            .execute(&mut *tx)
+            .await
+            .context("updating message flags/labels")?;
+        }
+
+        tx.commit().await.context("committing flag update tx")?;
+        Ok(())
+    }
+
+    pub async fn load_existing_message_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<std::collections::HashSet<String>> {
+        use std::collections::HashSet;
+
+        if ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT id FROM messages WHERE account_id = ");
+        qb.push_bind(account_id);
+        qb.push(" AND id IN (");
+        {
+            let mut separated = qb.separated(", ");
+            for id in ids {
+                separated.push_bind(id);
+            }
+        }
+        qb.push(")");
+
+        let rows = qb
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("loading existing message ids")?;
+
+        let mut out = HashSet::new();
+        for row in rows {
+            let id: String = row.get(0);
+            out.insert(id);
+        }
+        Ok(out)
+    }
+
+    /// Maps each `Message-ID` in `headers` to a message already cached outside `folder`.
+    pub async fn load_message_ids_by_header(
+        &self,
+        account_id: &str,
+        folder: &str,
+        headers: &[String],
+    ) -> Result<HashMap<String, String>> {
+        if headers.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "SELECT message_id_header, MIN(id) FROM messages WHERE account_id = ",
+        );
+        qb.push_bind(account_id);
+        qb.push(" AND folder != ");
+        qb.push_bind(folder);
+        qb.push(" AND message_id_header IN (");
+        {
+            let mut separated = qb.separated(", ");
+            for header in headers {
+                separated.push_bind(header);
+            }
+        }
+        qb.push(") GROUP BY message_id_header");
+
+        let rows = qb
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("loading message ids by Message-ID")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get::<String, _>(0), row.get::<String, _>(1)))
+            .collect())
+    }
+
+    pub async fn batch_update_message_location_by_id(
+        &self,
+        account_id: &str,
+        updates: &[MessageLocationUpdate],
+    ) -> Result<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        let now = now_ts();
+        let mut tx = self.pool.begin().await.context("beginning transaction")?;
+
+        for (message_id, folder, uid, flags, labels, thread_id, internal_date, size_bytes) in
+            updates
+        {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET folder = ?1,
+                    uid = ?2,
+                    flags = ?3,
+                    labels = ?4,
+                    thread_id = COALESCE(?5, thread_id),
+                    internal_date = ?6,
+                    size_bytes = ?7,
+                    updated_at = ?8
+                WHERE account_id = ?9 AND id = ?10;
+                "#,
+            )
+            .bind(folder)
+            .bind(*uid as i64)
+            .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
+            .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
+            .bind(thread_id)
+            .bind(internal_date)
+            .bind(size_bytes.map(|v| v as i64))
+            .bind(now)
+            .bind(account_id)
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await
+            .context("updating message location")?;
+        }
+
+        tx.commit().await.context("committing location update tx")?;
+        Ok(())
+    }
+
+    /// Deletes "fallback-id" duplicates (e.g. `account:folder:uid`) when a stable Gmail id row
+    /// already exists for the same raw message bytes (`raw_hash`).
+    ///
+    /// This is a local-only cleanup (no IMAP calls) that helps migrate older DBs that were created
+    /// before `X-GM-MSGID` was extracted.
+    pub async fn dedupe_fallback_messages_by_raw_hash(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<usize> {
+        // Find duplicates where:
+        // - one row has a numeric id (Gmail X-GM-MSGID stored as string)
+        // - another row has a fallback id containing ':' (legacy format)
+        // - both share the same raw_hash
+        let rows = sqlx::query(
+            r#"
+            SELECT legacy.id
+            FROM messages AS stable
+            JOIN messages AS legacy
+              ON legacy.account_id = stable.account_id
+             AND legacy.raw_hash = stable.raw_hash
+            WHERE stable.account_id = ?1
+              AND stable.raw_hash IS NOT NULL
+              AND stable.id GLOB '[0-9]*'
+              AND legacy.id LIKE '%:%'
+              AND legacy.id != stable.id
+            LIMIT ?2;
+            "#,
+        )
+        .bind(account_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("finding fallback duplicates")?;
+
+        let mut deleted = 0usize;
+        for row in rows {
+            let legacy_id: String = row.get(0);
+            self.delete_message(&legacy_id).await?;
+            deleted += 1;
+        }
+
+        Ok(deleted)
+    }
+
+    pub async fn upsert_message(
+        &self,
+        message: &MessageRecord,
+        body: Option<&BodyRecord>,
+    ) -> Result<()> {
+        let cipher = self.cipher_for(&message.account_id);
+        let sealed;
+        let stored = match cipher.as_deref() {
+            Some(cipher) => {
+                sealed = cipher.seal_message(message)?;
+                &sealed
+            }
+            None => message,
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO messages (
+                id, account_id, folder, uid, thread_id, internal_date,
+                subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
+                flags, labels, has_attachments, size_bytes, raw_hash,
+                created_at, updated_at, message_id_header
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
+            ON CONFLICT(id) DO UPDATE SET
+                account_id = excluded.account_id,
+                folder = excluded.folder,
+                uid = excluded.uid,
+                thread_id = excluded.thread_id,
+                internal_date = excluded.internal_date,
+                subject = excluded.subject,
+                from_addr = excluded.from_addr,
+                to_addrs = excluded.to_addrs,
+                cc_addrs = excluded.cc_addrs,
+                bcc_addrs = excluded.bcc_addrs,
+                flags = excluded.flags,
+                labels = excluded.labels,
+                has_attachments = excluded.has_attachments,
+                size_bytes = excluded.size_bytes,
+                raw_hash = excluded.raw_hash,
+                updated_at = excluded.updated_at,
+                message_id_header = excluded.message_id_header;
             "#,
         )
-        .fetch_all(&self.pool)
+        .bind(&stored.id)
+        .bind(&stored.account_id)
+        .bind(&stored.folder)
+        .bind(stored.uid.map(|v| v as i64))
+        .bind(&stored.thread_id)
+        .bind(stored.internal_date)
+        .bind(&stored.subject)
+        .bind(&stored.from)
+        .bind(&stored.to)
+        .bind(&stored.cc)
+        .bind(&stored.bcc)
+        .bind(serde_json::to_string(&stored.flags).unwrap_or_else(|_| "[]".into()))
+        .bind(serde_json::to_string(&stored.labels).unwrap_or_else(|_| "[]".into()))
+        .bind(if stored.has_attachments { 1 } else { 0 })
+        .bind(stored.size_bytes.map(|v| v as i64))
+        .bind(&stored.raw_hash)
+        .bind(stored.created_at)
+        .bind(stored.updated_at)
+        .bind(&stored.message_id_header)
+        .execute(&self.pool)
         .await
-        .context("loading accounts")?;
+        .context("upserting message")?;
+        let mut conn = self.pool.acquire().await.context("acquiring connection")?;
+        addresses::replace_in(&mut conn, &message.id, &addresses::recipients_of(message)).await?;
 
-        let mut out = Vec::new();
-        for row in rows {
-            let cutoff_raw: String = row.get(3);
-            let cutoff = NaiveDate::parse_from_str(&cutoff_raw, "%Y-%m-%d")
-                .unwrap_or_else(|_| NaiveDate::from_ymd_opt(2025, 12, 1).unwrap());
-            let folders_json: String = row.get(7);
-            let folders: Vec<String> =
-                serde_json::from_str(&folders_json).unwrap_or_else(|_| vec!["INBOX".into()]);
-            out.push(Account {
-                id: row.get(0),
-                email: row.get(1),
-                provider: provider_from_str(&row.get::<String, _>(2)),
-                settings: AccountSettings {
-                    cutoff_since: cutoff,
-                    poll_interval_minutes: row.get::<i64, _>(4) as u32,
-                    prefetch_recent: row.get::<i64, _>(5) as u32,
-                    safe_mode: row.get::<i64, _>(6) == 1,
-                    folders,
-                },
-                created_at: row.get(8),
-                updated_at: row.get(9),
-            });
+        if let Some(body) = body {
+            upsert_body_in(&mut conn, cipher.as_deref(), body, &self.blob_store()).await?;
         }
-        Ok(out)
+
+        Ok(())
     }
 
-    pub async fn upsert_folder_state(
+    pub async fn load_messages(
         &self,
         account_id: &str,
-        name: &str,
-        update: &FolderStateUpdate,
-    ) -> Result<FolderState> {
-        let now = now_ts();
-        sqlx::query(
-            r#"
-            INSERT INTO folders (account_id, name, uidvalidity, highest_uid, highestmodseq, exists_count, last_sync_ts, last_uid_scan_ts, created_at, updated_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
-            ON CONFLICT(account_id, name) DO UPDATE SET
-                uidvalidity = excluded.uidvalidity,
-                highest_uid = excluded.highest_uid,
-                highestmodseq = excluded.highestmodseq,
-                exists_count = excluded.exists_count,
-                last_sync_ts = excluded.last_sync_ts,
-                last_uid_scan_ts = excluded.last_uid_scan_ts,
-                updated_at = excluded.updated_at;
-            "#,
-        )
-        .bind(account_id)
-        .bind(name)
-        .bind(update.uidvalidity.map(|v| v as i64))
-        .bind(update.highest_uid.map(|v| v as i64))
-        .bind(update.highestmodseq.map(|v| v as i64))
-        .bind(update.exists_count.map(|v| v as i64))
-        .bind(update.last_sync_ts)
-        .bind(update.last_uid_scan_ts)
-        .bind(now)
-        .bind(now)
-        .execute(&self.pool)
-        .await
-        .context("upserting folder")?;
+        limit: usize,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
+        self.load_messages_in(account_id, None, limit).await
+    }
 
-        let row = sqlx::query(
+    /// Every message of a thread, newest first, with bodies; `id` is a message id or a thread
+    /// id. Empty when neither matches.
+    pub async fn load_thread(
+        &self,
+        account_id: &str,
+        id: &str,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
+        let thread_id: Option<String> = sqlx::query_scalar(
             r#"
-            SELECT id, uidvalidity, highest_uid, highestmodseq, exists_count, last_sync_ts, last_uid_scan_ts, updated_at
-            FROM folders
-            WHERE account_id = ?1 AND name = ?2
+            SELECT COALESCE(
+                (SELECT thread_id FROM messages WHERE account_id = ?1 AND id = ?2),
+                (SELECT thread_id FROM messages WHERE account_id = ?1 AND thread_id = ?2 LIMIT 1)
+            );
             "#,
         )
         .bind(account_id)
-        .bind(name)
+        .bind(id)
         .fetch_one(&self.pool)
         .await
-        .context("reloading folder")?;
-
-        Ok(FolderState {
-            id: row.get::<i64, _>(0),
-            account_id: account_id.to_string(),
-            name: name.to_string(),
-            uidvalidity: row.get::<Option<i64>, _>(1).map(|v| v as u32),
-            highest_uid: row.get::<Option<i64>, _>(2).map(|v| v as u32),
-            highestmodseq: row.get::<Option<i64>, _>(3).map(|v| v as u64),
-            exists_count: row.get::<Option<i64>, _>(4).map(|v| v as u32),
-            last_sync_ts: row.get::<Option<i64>, _>(5),
-            last_uid_scan_ts: row.get::<Option<i64>, _>(6),
-        })
+        .context("resolving thread id")?;
+        match thread_id {
+            Some(thread_id) => {
+                self.load_messages_in(account_id, Some(&thread_id), usize::MAX)
+                    .await
+            }
+            None => Ok(Vec::new()),
+        }
     }
 
-    pub async fn list_folders(&self, account_id: &str) -> Result<Vec<FolderState>> {
+    /// The newest `limit` undeleted messages, optionally only those of one thread.
+    async fn load_messages_in(
+        &self,
+        account_id: &str,
+        thread_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
         let rows = sqlx::query(
             r#"
-            SELECT id, name, uidvalidity, highest_uid, highestmodseq, exists_count, last_sync_ts, last_uid_scan_ts
-            FROM folders
-            WHERE account_id = ?1
-            ORDER BY name ASC;
+            SELECT id, folder, uid, thread_id, internal_date, subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
+                   flags, labels, has_attachments, size_bytes, raw_hash, created_at, updated_at, body_status, from_name,
+                   message_id_header
+            FROM messages
+            WHERE account_id = ?1 AND flags NOT LIKE '%"Deleted"%'
+              AND (?3 IS NULL OR thread_id = ?3)
+            ORDER BY internal_date DESC NULLS LAST
+            LIMIT ?2;
             "#,
         )
         .bind(account_id)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .bind(thread_id)
         .fetch_all(&self.pool)
         .await
-        .context("loading folders")?;
+        .context("loading messages")?;
 
+        let cipher = self.cipher_for(account_id);
+        let blob_store = self.blob_store();
         let mut out = Vec::new();
         for row in rows {
-            out.push(FolderState {
-                id: row.get(0),
-                account_id: account_id.to_string(),
-                name: row.get(1),
-                uidvalidity: row.get::<Option<i64>, _>(2).map(|v| v as u32),
-                highest_uid: row.get::<Option<i64>, _>(3).map(|v| v as u32),
-                highestmodseq: row.get::<Option<i64>, _>(4).map(|v| v as u64),
-                exists_count: row.get::<Option<i64>, _>(5).map(|v| v as u32),
-                last_sync_ts: row.get(6),
-                last_uid_scan_ts: row.get(7),
-            });
+            let msg_id: String = row.get(0);
+            let mut raw_format = RawFormat::Plain;
+            let mut body = sqlx::query(
+                r#"
+                SELECT b.raw_rfc822, b.sanitized_text, b.mime_summary, b.attachments_json,
+                       b.sanitized_at, b.blob_hash, bl.data, bl.offloaded, b.sanitizer_version,
+                       CASE WHEN bl.hash IS NULL THEN b.raw_format ELSE bl.format END,
+                       b.degraded
+                FROM bodies b
+                LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+                WHERE b.message_id = ?1
+                "#,
+            )
+            .bind(&msg_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("loading body")?
+            .map(|brow| -> Result<BodyRecord> {
+                let raw_rfc822 = match (brow.get::<Option<String>, _>(5), brow.get(6)) {
+                    (Some(hash), Some(data)) => {
+                        let offloaded = brow.get::<Option<i64>, _>(7) == Some(1);
+                        Some(blob_store.read(&hash, data, offloaded)?)
+                    }
+                    _ => brow.get::<Option<Vec<u8>>, _>(0),
+                };
+                raw_format = RawFormat::from_i64(brow.get(9))?;
+                Ok(BodyRecord {
+                    message_id: msg_id.clone(),
+                    raw_rfc822,
+                    sanitized_text: brow.get::<Option<String>, _>(1),
+                    mime_summary: brow.get::<Option<String>, _>(2),
+                    attachments_json: brow.get::<Option<String>, _>(3),
+                    sanitized_at: brow.get::<Option<i64>, _>(4),
+                    sanitizer_version: brow.get::<Option<i64>, _>(8),
+                    degraded: brow.get::<i64, _>(10) != 0,
+                })
+            })
+            .transpose()?;
+
+            let mut message = message_from_row(account_id, &row);
+            if let Some(cipher) = cipher.as_deref() {
+                cipher.open_message(&mut message)?;
+                if let Some(body) = body.as_mut() {
+                    cipher.open_body(body)?;
+                }
+            }
+            if let Some(body) = body.as_mut() {
+                body.raw_rfc822 = body
+                    .raw_rfc822
+                    .take()
+                    .map(|raw| compression::decompress(raw, raw_format))
+                    .transpose()?;
+            }
+            out.push((message, body));
         }
+
         Ok(out)
     }
 
-    pub async fn load_message_ids_by_uids(
+    /// Claims up to `limit` messages first cached at or after `cached_since` (unix secs; 0 = all)
+    /// that `consumer` (notifier, summarizer, rules engine, MCP client, ...) has not seen yet,
+    /// oldest first, and records them as processed in the same statement so concurrent
+    /// consumers sharing a name never receive the same message twice. Rows marked `Deleted`
+    /// are left out.
+    pub async fn claim_unprocessed_messages(
         &self,
+        consumer: &str,
         account_id: &str,
-        folder: &str,
-        uids: &[u32],
-    ) -> Result<std::collections::HashMap<u32, String>> {
-        use std::collections::HashMap;
+        cached_since: i64,
+        limit: usize,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut tx = self.pool.begin().await.context("beginning claim tx")?;
 
-        if uids.is_empty() {
-            return Ok(HashMap::new());
+        let claimed: Vec<String> = sqlx::query(
+            r#"
+            INSERT INTO processed_messages (consumer, message_id, processed_at)
+            SELECT ?1, m.id, ?3
+            FROM messages m
+            WHERE m.account_id = ?2
+              AND m.created_at >= ?4
+              AND m.flags NOT LIKE '%"Deleted"%'
+              AND NOT EXISTS (
+                  SELECT 1 FROM processed_messages p
+                  WHERE p.consumer = ?1 AND p.message_id = m.id
+              )
+            ORDER BY m.internal_date ASC NULLS FIRST, m.id ASC
+            LIMIT ?5
+            RETURNING message_id;
+            "#,
+        )
+        .bind(consumer)
+        .bind(account_id)
+        .bind(now_ts())
+        .bind(cached_since)
+        .bind(limit as i64)
+        .fetch_all(&mut *tx)
+        .await
+        .context("claiming unprocessed messages")?
+        .into_iter()
+        .map(|row| row.get::<String, _>(0))
+        .collect();
+
+        if claimed.is_empty() {
+            tx.commit().await.context("committing claim tx")?;
+            return Ok(Vec::new());
         }
 
-        let mut qb: QueryBuilder<Sqlite> =
-            QueryBuilder::new("SELECT uid, id FROM messages WHERE account_id = ");
-        qb.push_bind(account_id);
-        qb.push(" AND folder = ");
-        qb.push_bind(folder);
-        qb.push(" AND uid IN (");
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+            r#"
+            SELECT id, folder, uid, thread_id, internal_date, subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
+                   flags, labels, has_attachments, size_bytes, raw_hash, created_at, updated_at, body_status, from_name,
+                   message_id_header
+            FROM messages
+            WHERE id IN ("#,
+        );
         {
             let mut separated = qb.separated(", ");
-            for uid in uids {
-                separated.push_bind(*uid as i64);
+            for id in &claimed {
+                separated.push_bind(id);
             }
         }
-        qb.push(")");
+        qb.push(") ORDER BY internal_date ASC NULLS FIRST, id ASC");
 
         let rows = qb
             .build()
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *tx)
             .await
-            .context("loading message ids by uid list")?;
-
-        let mut out = HashMap::new();
-        for row in rows {
-            let uid = row.get::<Option<i64>, _>(0).map(|v| v as u32).unwrap_or(0);
-            let id: String = row.get(1);
-            if uid > 0 {
-                out.insert(uid, id);
-            }
-        }
+            .context("loading claimed messages")?;
+        tx.commit().await.context("committing claim tx")?;
 
-        Ok(out)
+        let cipher = self.cipher_for(account_id);
+        rows.into_iter()
+            .map(|row| {
+                let mut message = message_from_row(account_id, &row);
+                if let Some(cipher) = cipher.as_deref() {
+                    cipher.open_message(&mut message)?;
+                }
+                Ok(message)
+            })
+            .collect()
     }
 
-    pub async fn load_uid_to_message_id_map_by_folder(
+    /// Returns claimed messages to `consumer`'s unprocessed set (e.g. after a failed run).
+    pub async fn release_processed_messages(
         &self,
-        account_id: &str,
-        folder: &str,
-    ) -> Result<std::collections::HashMap<u32, String>> {
-        use std::collections::HashMap;
-
-        let rows = sqlx::query(
-            r#"
-            SELECT uid, id
-            FROM messages
-            WHERE account_id = ?1 AND folder = ?2 AND uid IS NOT NULL;
-            "#,
-        )
-        .bind(account_id)
-        .bind(folder)
-        .fetch_all(&self.pool)
-        .await
-        .context("loading message uid map by folder")?;
+        consumer: &str,
+        message_ids: &[String],
+    ) -> Result<u64> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
 
-        let mut out = HashMap::new();
-        for row in rows {
-            let uid = row.get::<Option<i64>, _>(0).map(|v| v as u32).unwrap_or(0);
-            let id: String = row.get(1);
-            if uid > 0 {
-                out.insert(uid, id);
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("DELETE FROM processed_messages WHERE consumer = ");
+        qb.push_bind(consumer);
+        qb.push(" AND message_id IN (");
+        {
+            let mut separated = qb.separated(", ");
+            for id in message_ids {
+                separated.push_bind(id);
             }
         }
-        Ok(out)
+        qb.push(")");
+
+        let result = qb
+            .build()
+            .execute(&self.pool)
+            .await
+            .context("releasing processed messages")?;
+        Ok(result.rows_affected())
     }
 
-    pub async fn batch_update_message_flags_by_uid(
+    /// Header-only messages awaiting a body download, newest first, outside `skip_folders`.
+    pub async fn load_pending_body_targets(
         &self,
         account_id: &str,
-        folder: &str,
-        updates: &[(u32, Vec<String>, Vec<String>)],
-    ) -> Result<()> {
-        if updates.is_empty() {
-            return Ok(());
+        skip_folders: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, String, u32)>> {
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT id, folder, uid FROM messages WHERE account_id = ");
+        qb.push_bind(account_id);
+        qb.push(" AND body_status = 'pending' AND uid IS NOT NULL");
+        if !skip_folders.is_empty() {
+            qb.push(" AND folder NOT IN (");
+            {
+                let mut separated = qb.separated(", ");
+                for folder in skip_folders {
+                    separated.push_bind(folder);
+                }
+            }
+            qb.push(")");
         }
+        qb.push(" ORDER BY internal_date DESC NULLS LAST LIMIT ");
+        qb.push_bind(limit as i64);
 
-        let now = now_ts();
-        let mut tx = self.pool.begin().await.context("beginning transaction")?;
-
-        for (uid, flags, labels) in updates {
-            sqlx::query(
-                r#"
-                UPDATE messages
-                SET flags = ?1, labels = ?2, updated_at = ?3
-                WHERE account_id = ?4 AND folder = ?5 AND uid = ?6;
-                "#,
-            )
-            .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
-            .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
-            .bind(now)
-            .bind(account_id)
-            .bind(folder)
-            .bind(*uid as i64)
-            .execute(&mut *tx)
+        let rows = qb
+            .build()
+            .fetch_all(&self.pool)
             .await
-            .context("updating message flags/labels")?;
-        }
+            .context("loading pending body targets")?;
 
-        tx.commit().await.context("committing flag update tx")?;
-        Ok(())
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get::<String, _>(0),
+                    row.get::<String, _>(1),
+                    row.get::<i64, _>(2) as u32,
+                )
+            })
+            .collect())
     }
 
-    pub async fn load_existing_message_ids(
+    /// Queues bodies skipped for size (`too_large`) for download by marking them `pending`; an
+    /// empty `message_ids` selects every such message of the account. Returns the
+    /// `(message_id, folder, uid)` fetch targets, newest first.
+    pub async fn request_oversized_bodies(
         &self,
         account_id: &str,
-        ids: &[String],
-    ) -> Result<std::collections::HashSet<String>> {
-        use std::collections::HashSet;
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>> {
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+            "UPDATE messages SET body_status = 'pending' WHERE body_status = 'too_large' AND account_id = ",
+        );
+        qb.push_bind(account_id);
+        if !message_ids.is_empty() {
+            qb.push(" AND id IN (");
+            {
+                let mut separated = qb.separated(", ");
+                for id in message_ids {
+                    separated.push_bind(id);
+                }
+            }
+            qb.push(")");
+        }
+        qb.push(" RETURNING id, folder, uid, internal_date");
 
-        if ids.is_empty() {
-            return Ok(HashSet::new());
+        let mut rows: Vec<(String, String, Option<i64>, Option<i64>)> = qb
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("requesting oversized bodies")?
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+            .collect();
+        rows.sort_by_key(|row| std::cmp::Reverse(row.3));
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, folder, uid, _)| Some((id, folder, uid? as u32)))
+            .collect())
+    }
+
+    /// `(message_id, folder, uid)` fetch targets for `message_ids` of the account, whatever their
+    /// body status, newest first. Messages without a server UID are left out.
+    pub async fn load_body_targets(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>> {
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
         }
 
         let mut qb: QueryBuilder<Sqlite> =
-            QueryBuilder::new("SELECT id FROM messages WHERE account_id = ");
+            QueryBuilder::new("SELECT id, folder, uid FROM messages WHERE account_id = ");
         qb.push_bind(account_id);
-        qb.push(" AND id IN (");
+        qb.push(" AND uid IS NOT NULL AND id IN (");
         {
             let mut separated = qb.separated(", ");
-            for id in ids {
+            for id in message_ids {
                 separated.push_bind(id);
             }
         }
-        qb.push(")");
+        qb.push(") ORDER BY internal_date DESC NULLS LAST");
 
         let rows = qb
             .build()
             .fetch_all(&self.pool)
             .await
-            .context("loading existing message ids")?;
+            .context("loading body targets")?;
 
-        let mut out = HashSet::new();
-        for row in rows {
-            let id: String = row.get(0);
-            out.insert(id);
-        }
-        Ok(out)
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get::<String, _>(0),
+                    row.get::<String, _>(1),
+                    row.get::<i64, _>(2) as u32,
+                )
+            })
+            .collect())
     }
 
-    pub async fn batch_update_message_location_by_id(
+    /// Stores lazily fetched bodies and marks their messages as fully downloaded.
+    pub async fn store_fetched_bodies(
         &self,
         account_id: &str,
-        updates: &[MessageLocationUpdate],
+        updates: &[FetchedBodyUpdate],
     ) -> Result<()> {
         if updates.is_empty() {
             return Ok(());
         }
 
         let now = now_ts();
-        let mut tx = self.pool.begin().await.context("beginning transaction")?;
+        let cipher = self.cipher_for(account_id);
+        let mut tx = self.pool.begin().await.context("beginning body fetch tx")?;
 
-        for (message_id, folder, uid, flags, labels, thread_id, internal_date, size_bytes) in
-            updates
-        {
+        for (has_attachments, raw_hash, body) in updates {
             sqlx::query(
                 r#"
                 UPDATE messages
-                SET folder = ?1,
-                    uid = ?2,
-                    flags = ?3,
-                    labels = ?4,
-                    thread_id = ?5,
-                    internal_date = ?6,
-                    size_bytes = ?7,
-                    updated_at = ?8
-                WHERE account_id = ?9 AND id = ?10;
+                SET has_attachments = ?1, raw_hash = ?2, body_status = 'full', updated_at = ?3
+                WHERE account_id = ?4 AND id = ?5;
                 "#,
             )
-            .bind(folder)
-            .bind(*uid as i64)
-            .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
-            .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
-            .bind(thread_id)
-            .bind(internal_date)
-            .bind(size_bytes.map(|v| v as i64))
+            .bind(if *has_attachments { 1 } else { 0 })
+            .bind(raw_hash)
             .bind(now)
             .bind(account_id)
-            .bind(message_id)
+            .bind(&body.message_id)
             .execute(&mut *tx)
             .await
-            .context("updating message location")?;
+            .context("marking message body fetched")?;
+
+            upsert_body_in(&mut tx, cipher.as_deref(), body, &self.blob_store()).await?;
         }
 
-        tx.commit().await.context("committing location update tx")?;
+        tx.commit().await.context("committing body fetch tx")?;
         Ok(())
     }
 
-    /// Deletes "fallback-id" duplicates (e.g. `account:folder:uid`) when a stable Gmail id row
-    /// already exists for the same raw message bytes (`raw_hash`).
-    ///
-    /// This is a local-only cleanup (no IMAP calls) that helps migrate older DBs that were created
-    /// before `X-GM-MSGID` was extracted.
-    pub async fn dedupe_fallback_messages_by_raw_hash(
+    /// Deletes the stored bodies (raw message and sanitized text) of a folder's messages and
+    /// marks downloaded ones `pending`, for metadata-only folders. Returns the bodies removed.
+    pub async fn drop_folder_bodies(&self, account_id: &str, folder: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await.context("beginning body drop tx")?;
+
+        let dropped = sqlx::query(
+            r#"
+            DELETE FROM bodies
+            WHERE message_id IN (
+                SELECT id FROM messages WHERE account_id = ?1 AND folder = ?2
+            );
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .execute(&mut *tx)
+        .await
+        .context("deleting folder bodies")?
+        .rows_affected();
+
+        sqlx::query(
+            r#"
+            UPDATE messages SET body_status = 'pending', updated_at = ?3
+            WHERE account_id = ?1 AND folder = ?2 AND body_status = 'full';
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(now_ts())
+        .execute(&mut *tx)
+        .await
+        .context("marking folder bodies pending")?;
+
+        tx.commit().await.context("committing body drop tx")?;
+        Ok(dropped)
+    }
+
+    /// Stored raw messages (decrypted, blobs read) whose sanitized text predates
+    /// `below_version`, ordered by message id after `after`, for `otto resanitize`.
+    pub async fn load_bodies_to_resanitize(
         &self,
         account_id: &str,
+        below_version: i64,
+        after: &str,
         limit: usize,
-    ) -> Result<usize> {
-        // Find duplicates where:
-        // - one row has a numeric id (Gmail X-GM-MSGID stored as string)
-        // - another row has a fallback id containing ':' (legacy format)
-        // - both share the same raw_hash
+    ) -> Result<Vec<(String, Vec<u8>)>> {
         let rows = sqlx::query(
             r#"
-            SELECT legacy.id
-            FROM messages AS stable
-            JOIN messages AS legacy
-              ON legacy.account_id = stable.account_id
-             AND legacy.raw_hash = stable.raw_hash
-            WHERE stable.account_id = ?1
-              AND stable.raw_hash IS NOT NULL
-              AND stable.id GLOB '[0-9]*'
-              AND legacy.id LIKE '%:%'
-              AND legacy.id != stable.id
-            LIMIT ?2;
+            SELECT b.message_id, b.raw_rfc822, b.blob_hash, bl.data, bl.offloaded,
+                   CASE WHEN bl.hash IS NULL THEN b.raw_format ELSE bl.format END
+            FROM bodies b
+            JOIN messages m ON m.id = b.message_id
+            LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+            WHERE m.account_id = ?1 AND b.message_id > ?2
+              AND COALESCE(b.sanitizer_version, 0) < ?3
+              AND (b.raw_rfc822 IS NOT NULL OR bl.data IS NOT NULL)
+            ORDER BY b.message_id
+            LIMIT ?4;
             "#,
         )
         .bind(account_id)
+        .bind(after)
+        .bind(below_version)
         .bind(limit as i64)
         .fetch_all(&self.pool)
         .await
-        .context("finding fallback duplicates")?;
-
-        let mut deleted = 0usize;
-        for row in rows {
-            let legacy_id: String = row.get(0);
-            self.delete_message(&legacy_id).await?;
-            deleted += 1;
-        }
+        .context("loading bodies to resanitize")?;
 
-        Ok(deleted)
+        let cipher = self.cipher_for(account_id);
+        let blob_store = self.blob_store();
+        rows.into_iter()
+            .map(|row| {
+                let raw = match (row.get::<Option<String>, _>(2), row.get(3)) {
+                    (Some(hash), Some(data)) => {
+                        let offloaded = row.get::<Option<i64>, _>(4) == Some(1);
+                        blob_store.read(&hash, data, offloaded)?
+                    }
+                    _ => row.get::<Vec<u8>, _>(1),
+                };
+                let format = RawFormat::from_i64(row.get(5))?;
+                Ok((row.get(0), open_raw(cipher.as_deref(), raw, format)?))
+            })
+            .collect()
     }
 
-    pub async fn upsert_message(
+    /// One message's stored raw RFC822, if it has one.
+    pub async fn load_raw_message(
         &self,
-        message: &MessageRecord,
-        body: Option<&BodyRecord>,
-    ) -> Result<()> {
-        sqlx::query(
+        account_id: &str,
+        message_id: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        let row = sqlx::query(
             r#"
-            INSERT INTO messages (
-                id, account_id, folder, uid, thread_id, internal_date,
-                subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
-                flags, labels, has_attachments, size_bytes, raw_hash,
-                created_at, updated_at
-            )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
-            ON CONFLICT(id) DO UPDATE SET
-                account_id = excluded.account_id,
-                folder = excluded.folder,
-                uid = excluded.uid,
-                thread_id = excluded.thread_id,
-                internal_date = excluded.internal_date,
-                subject = excluded.subject,
-                from_addr = excluded.from_addr,
-                to_addrs = excluded.to_addrs,
-                cc_addrs = excluded.cc_addrs,
-                bcc_addrs = excluded.bcc_addrs,
-                flags = excluded.flags,
-                labels = excluded.labels,
-                has_attachments = excluded.has_attachments,
-                size_bytes = excluded.size_bytes,
-                raw_hash = excluded.raw_hash,
-                updated_at = excluded.updated_at;
+            SELECT b.raw_rfc822, b.blob_hash, bl.data, bl.offloaded,
+                   CASE WHEN bl.hash IS NULL THEN b.raw_format ELSE bl.format END
+            FROM bodies b
+            JOIN messages m ON m.id = b.message_id
+            LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+            WHERE m.account_id = ?1 AND b.message_id = ?2
+              AND (b.raw_rfc822 IS NOT NULL OR bl.data IS NOT NULL);
             "#,
         )
-        .bind(&message.id)
-        .bind(&message.account_id)
-        .bind(&message.folder)
-        .bind(message.uid.map(|v| v as i64))
-        .bind(&message.thread_id)
-        .bind(message.internal_date)
-        .bind(&message.subject)
-        .bind(&message.from)
-        .bind(&message.to)
-        .bind(&message.cc)
-        .bind(&message.bcc)
-        .bind(serde_json::to_string(&message.flags).unwrap_or_else(|_| "[]".into()))
-        .bind(serde_json::to_string(&message.labels).unwrap_or_else(|_| "[]".into()))
-        .bind(if message.has_attachments { 1 } else { 0 })
-        .bind(message.size_bytes.map(|v| v as i64))
-        .bind(&message.raw_hash)
-        .bind(message.created_at)
-        .bind(message.updated_at)
-        .execute(&self.pool)
+        .bind(account_id)
+        .bind(message_id)
+        .fetch_optional(&self.pool)
         .await
-        .context("upserting message")?;
+        .context("loading raw message")?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
 
-        if let Some(body) = body {
+        let raw = match (row.get::<Option<String>, _>(1), row.get(2)) {
+            (Some(hash), Some(data)) => {
+                let offloaded = row.get::<Option<i64>, _>(3) == Some(1);
+                self.blob_store().read(&hash, data, offloaded)?
+            }
+            _ => row.get::<Vec<u8>, _>(0),
+        };
+        let format = RawFormat::from_i64(row.get(4))?;
+        let cipher = self.cipher_for(account_id);
+        open_raw(cipher.as_deref(), raw, format).map(Some)
+    }
+
+    /// Replaces the sanitized columns of already stored bodies (the raw message is left as is)
+    /// and each message's `has_attachments`. `bodies` pairs that flag with the new record.
+    pub async fn store_resanitized_bodies(
+        &self,
+        account_id: &str,
+        bodies: &[(bool, BodyRecord)],
+    ) -> Result<()> {
+        if bodies.is_empty() {
+            return Ok(());
+        }
+
+        let cipher = self.cipher_for(account_id);
+        let mut tx = self.pool.begin().await.context("beginning resanitize tx")?;
+        for (has_attachments, body) in bodies {
+            let sanitized_text = match (cipher.as_deref(), body.sanitized_text.as_deref()) {
+                (Some(cipher), Some(text)) => Some(cipher.seal_text(text)?),
+                (_, text) => text.map(str::to_string),
+            };
             sqlx::query(
                 r#"
-                INSERT INTO bodies (message_id, raw_rfc822, sanitized_text, mime_summary, attachments_json, sanitized_at)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-                ON CONFLICT(message_id) DO UPDATE SET
-                    raw_rfc822 = excluded.raw_rfc822,
-                    sanitized_text = excluded.sanitized_text,
-                    mime_summary = excluded.mime_summary,
-                    attachments_json = excluded.attachments_json,
-                    sanitized_at = excluded.sanitized_at;
+                UPDATE bodies
+                SET sanitized_text = ?1, mime_summary = ?2, attachments_json = ?3,
+                    sanitized_at = ?4, sanitizer_version = ?5, degraded = ?6
+                WHERE message_id = ?7;
                 "#,
             )
-            .bind(&body.message_id)
-            .bind(&body.raw_rfc822)
-            .bind(&body.sanitized_text)
+            .bind(sanitized_text)
             .bind(&body.mime_summary)
             .bind(&body.attachments_json)
             .bind(body.sanitized_at)
-            .execute(&self.pool)
+            .bind(body.sanitizer_version)
+            .bind(body.degraded)
+            .bind(&body.message_id)
+            .execute(&mut *tx)
             .await
-            .context("upserting body")?;
-        }
+            .context("updating resanitized body")?;
 
+            sqlx::query(
+                "UPDATE messages SET has_attachments = ?1 WHERE account_id = ?2 AND id = ?3",
+            )
+            .bind(if *has_attachments { 1 } else { 0 })
+            .bind(account_id)
+            .bind(&body.message_id)
+            .execute(&mut *tx)
+            .await
+            .context("updating message attachment flag")?;
+        }
+        tx.commit().await.context("committing resanitize tx")?;
         Ok(())
     }
 
-    pub async fn load_messages(
-        &self,
-        account_id: &str,
-        limit: usize,
-    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
-        let rows = sqlx::query(
-            r#"
-            SELECT id, folder, uid, thread_id, internal_date, subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
-                   flags, labels, has_attachments, size_bytes, raw_hash, created_at, updated_at
-            FROM messages
-            WHERE account_id = ?1
-            ORDER BY internal_date DESC NULLS LAST
-            LIMIT ?2;
-            "#,
-        )
-        .bind(account_id)
-        .bind(limit as i64)
-        .fetch_all(&self.pool)
-        .await
-        .context("loading messages")?;
+    /// Compresses the account's raw messages stored before compression: inline bodies and
+    /// inline blobs (offloaded blob files are left as written). Each page commits on its own,
+    /// so an interrupted run keeps its progress; rows zstd doesn't shrink stay as they are.
+    pub async fn compress_raw_bodies(&self, account_id: &str) -> Result<CompressStats> {
+        const PAGE: i64 = 200;
+        let cipher = self.cipher_for(account_id);
+        let cipher = cipher.as_deref();
+        let mut stats = CompressStats::default();
+        // Sealed bytes in, sealed compressed bytes out; `None` when compression doesn't pay.
+        let pack = |stored: Vec<u8>| -> Result<Option<Vec<u8>>> {
+            let plain = open_raw(cipher, stored, RawFormat::Plain)?;
+            match compression::compress(&plain)? {
+                (packed, RawFormat::Zstd) => Ok(Some(match cipher {
+                    Some(cipher) => cipher.seal_bytes(&packed)?,
+                    None => packed,
+                })),
+                (_, RawFormat::Plain) => Ok(None),
+            }
+        };
 
-        let mut out = Vec::new();
-        for row in rows {
-            let flags: Vec<String> =
-                serde_json::from_str(&row.get::<String, _>(10)).unwrap_or_default();
-            let labels: Vec<String> =
-                serde_json::from_str(&row.get::<String, _>(11)).unwrap_or_default();
-            let msg_id: String = row.get(0);
-            let body = sqlx::query(
+        let mut after = String::new();
+        loop {
+            let mut tx = self.pool.begin().await.context("beginning compress tx")?;
+            let rows = sqlx::query(
                 r#"
-                SELECT raw_rfc822, sanitized_text, mime_summary, attachments_json, sanitized_at
-                FROM bodies
-                WHERE message_id = ?1
+                SELECT b.message_id, b.raw_rfc822
+                FROM bodies b
+                JOIN messages m ON m.id = b.message_id
+                WHERE m.account_id = ?1 AND b.message_id > ?2
+                  AND b.raw_rfc822 IS NOT NULL AND b.raw_format = 0
+                ORDER BY b.message_id
+                LIMIT ?3;
                 "#,
             )
-            .bind(&msg_id)
-            .fetch_optional(&self.pool)
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
             .await
-            .context("loading body")?
-            .map(|brow| BodyRecord {
-                message_id: msg_id.clone(),
-                raw_rfc822: brow.get::<Option<Vec<u8>>, _>(0),
-                sanitized_text: brow.get::<Option<String>, _>(1),
-                mime_summary: brow.get::<Option<String>, _>(2),
-                attachments_json: brow.get::<Option<String>, _>(3),
-                sanitized_at: brow.get::<Option<i64>, _>(4),
-            });
+            .context("loading bodies to compress")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+            for row in rows {
+                let stored: Vec<u8> = row.get(1);
+                let before = stored.len() as u64;
+                let Some(packed) = pack(stored)? else {
+                    continue;
+                };
+                sqlx::query(
+                    "UPDATE bodies SET raw_rfc822 = ?1, raw_format = ?2 WHERE message_id = ?3",
+                )
+                .bind(&packed)
+                .bind(RawFormat::Zstd.as_i64())
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("compressing body")?;
+                stats.compressed += 1;
+                stats.bytes_before += before;
+                stats.bytes_after += packed.len() as u64;
+            }
+            tx.commit().await.context("committing compress tx")?;
+        }
+
+        let mut after = String::new();
+        loop {
+            let mut tx = self.pool.begin().await.context("beginning compress tx")?;
+            let rows = sqlx::query(
+                r#"
+                SELECT DISTINCT bl.hash, bl.data
+                FROM blobs bl
+                JOIN bodies b ON b.blob_hash = bl.hash
+                JOIN messages m ON m.id = b.message_id
+                WHERE m.account_id = ?1 AND bl.hash > ?2
+                  AND bl.format = 0 AND bl.offloaded = 0
+                ORDER BY bl.hash
+                LIMIT ?3;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading blobs to compress")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+            for row in rows {
+                let stored: Vec<u8> = row.get(1);
+                let before = stored.len() as u64;
+                let Some(packed) = pack(stored)? else {
+                    continue;
+                };
+                BlobStore::replace_inline(
+                    &mut tx,
+                    &row.get::<String, _>(0),
+                    &packed,
+                    RawFormat::Zstd,
+                )
+                .await?;
+                stats.compressed += 1;
+                stats.bytes_before += before;
+                stats.bytes_after += packed.len() as u64;
+            }
+            tx.commit().await.context("committing compress tx")?;
+        }
+        Ok(stats)
+    }
+
+    /// Rebuilds the database file so pages freed by deletes or compression go back to the
+    /// filesystem (SQLite otherwise only reuses them).
+    pub async fn vacuum(&self) -> Result<()> {
+        sqlx::query("VACUUM")
+            .execute(&self.pool)
+            .await
+            .context("vacuuming database")?;
+        Ok(())
+    }
+
+    /// Messages (no bodies) received before `before` (unix secs), oldest first; rows already
+    /// marked `Deleted` are left out.
+    pub async fn load_messages_before(
+        &self,
+        account_id: &str,
+        before: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND internal_date < ")
+            .push_bind(before)
+            .push(" AND flags NOT LIKE '%\"Deleted\"%' ORDER BY internal_date ASC, id ASC");
+        self.load_message_records(account_id, qb).await
+    }
+
+    /// Messages (no bodies) received at or after `since` (unix secs), oldest first; rows
+    /// already marked `Deleted` are left out.
+    pub async fn load_messages_since(
+        &self,
+        account_id: &str,
+        since: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND internal_date >= ")
+            .push_bind(since)
+            .push(" AND flags NOT LIKE '%\"Deleted\"%' ORDER BY internal_date ASC, id ASC");
+        self.load_message_records(account_id, qb).await
+    }
+
+    /// Messages (no bodies) first cached at or after `since` (unix secs), oldest first; rows
+    /// already marked `Deleted` are left out.
+    pub async fn load_messages_cached_since(
+        &self,
+        account_id: &str,
+        since: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND created_at >= ")
+            .push_bind(since)
+            .push(" AND flags NOT LIKE '%\"Deleted\"%' ORDER BY internal_date ASC, id ASC");
+        self.load_message_records(account_id, qb).await
+    }
+
+    /// The cached messages among `ids` (no bodies), in no particular order.
+    pub async fn load_messages_by_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<MessageRecord>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND id IN (");
+        {
+            let mut separated = qb.separated(", ");
+            for id in ids {
+                separated.push_bind(id);
+            }
+        }
+        qb.push(")");
+        self.load_message_records(account_id, qb).await
+    }
 
-            out.push((
-                MessageRecord {
-                    id: msg_id,
-                    account_id: account_id.to_string(),
-                    folder: row.get(1),
-                    uid: row.get::<Option<i64>, _>(2).map(|v| v as u32),
-                    thread_id: row.get(3),
-                    internal_date: row.get(4),
-                    subject: row.get(5),
-                    from: row.get(6),
-                    to: row.get(7),
-                    cc: row.get(8),
-                    bcc: row.get(9),
-                    flags,
-                    labels,
-                    has_attachments: row.get::<i64, _>(12) == 1,
-                    size_bytes: row.get::<Option<i64>, _>(13).map(|v| v as u32),
-                    raw_hash: row.get(14),
-                    created_at: row.get(15),
-                    updated_at: row.get(16),
-                },
-                body,
-            ));
+    async fn load_message_records(
+        &self,
+        account_id: &str,
+        mut qb: QueryBuilder<'_, Sqlite>,
+    ) -> Result<Vec<MessageRecord>> {
+        let rows = qb
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("loading messages")?;
+        let cipher = self.cipher_for(account_id);
+        let mut out = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut message = message_from_row(account_id, &row);
+            if let Some(cipher) = cipher.as_deref() {
+                cipher.open_message(&mut message)?;
+            }
+            out.push(message);
         }
-
         Ok(out)
     }
 
@@ -1074,7 +3300,8 @@ impl Database {
         let rows = sqlx::query(
             r#"
             SELECT id, folder, uid, thread_id, internal_date, subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
-                   flags, labels, has_attachments, size_bytes, raw_hash, created_at, updated_at
+                   flags, labels, has_attachments, size_bytes, raw_hash, created_at, updated_at, body_status, from_name,
+                   message_id_header
             FROM messages
             WHERE account_id = ?1 AND folder = ?2
             ORDER BY internal_date DESC NULLS LAST
@@ -1088,61 +3315,29 @@ impl Database {
         .await
         .context("loading messages by folder")?;
 
+        let cipher = self.cipher_for(account_id);
         let mut out = Vec::new();
         for row in rows {
-            let flags: Vec<String> =
-                serde_json::from_str(&row.get::<String, _>(10)).unwrap_or_default();
-            let labels: Vec<String> =
-                serde_json::from_str(&row.get::<String, _>(11)).unwrap_or_default();
-
-            out.push(MessageRecord {
-                id: row.get(0),
-                account_id: account_id.to_string(),
-                folder: row.get(1),
-                uid: row.get::<Option<i64>, _>(2).map(|v| v as u32),
-                thread_id: row.get(3),
-                internal_date: row.get(4),
-                subject: row.get(5),
-                from: row.get(6),
-                to: row.get(7),
-                cc: row.get(8),
-                bcc: row.get(9),
-                flags,
-                labels,
-                has_attachments: row.get::<i64, _>(12) == 1,
-                size_bytes: row.get::<Option<i64>, _>(13).map(|v| v as u32),
-                raw_hash: row.get(14),
-                created_at: row.get(15),
-                updated_at: row.get(16),
-            });
+            let mut message = message_from_row(account_id, &row);
+            if let Some(cipher) = cipher.as_deref() {
+                cipher.open_message(&mut message)?;
+            }
+            out.push(message);
         }
 
         Ok(out)
     }
 
     pub async fn upsert_body(&self, body: &BodyRecord) -> Result<()> {
-        sqlx::query(
-            r#"
-            INSERT INTO bodies (message_id, raw_rfc822, sanitized_text, mime_summary, attachments_json, sanitized_at)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-            ON CONFLICT(message_id) DO UPDATE SET
-                raw_rfc822 = excluded.raw_rfc822,
-                sanitized_text = excluded.sanitized_text,
-                mime_summary = excluded.mime_summary,
-                attachments_json = excluded.attachments_json,
-                sanitized_at = excluded.sanitized_at;
-            "#,
-        )
-        .bind(&body.message_id)
-        .bind(&body.raw_rfc822)
-        .bind(&body.sanitized_text)
-        .bind(&body.mime_summary)
-        .bind(&body.attachments_json)
-        .bind(body.sanitized_at)
-        .execute(&self.pool)
-        .await
-        .context("upserting body")?;
-        Ok(())
+        let account_id: Option<String> =
+            sqlx::query_scalar("SELECT account_id FROM messages WHERE id = ?1")
+                .bind(&body.message_id)
+                .fetch_optional(&self.pool)
+                .await
+                .context("resolving body account")?;
+        let cipher = account_id.and_then(|id| self.cipher_for(&id));
+        let mut conn = self.pool.acquire().await.context("acquiring connection")?;
+        upsert_body_in(&mut conn, cipher.as_deref(), body, &self.blob_store()).await
     }
 
     /// Batch upsert messages and bodies in a single transaction for maximum performance
@@ -1163,6 +3358,16 @@ impl Database {
         let mut tx = self.pool.begin().await.context("beginning transaction")?;
 
         for (message, body) in messages.iter().zip(bodies.iter()) {
+            let cipher = self.cipher_for(&message.account_id);
+            let recipients = addresses::recipients_of(message);
+            let sealed;
+            let message = match cipher.as_deref() {
+                Some(cipher) => {
+                    sealed = cipher.seal_message(message)?;
+                    &sealed
+                }
+                None => message,
+            };
             // Insert/update message
             sqlx::query(
                 r#"
@@ -1170,9 +3375,9 @@ impl Database {
                     id, account_id, folder, uid, thread_id, internal_date,
                     subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
                     flags, labels, has_attachments, size_bytes, raw_hash,
-                    created_at, updated_at
+                    created_at, updated_at, message_id_header
                 )
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
                 ON CONFLICT(id) DO UPDATE SET
                     account_id = excluded.account_id,
                     folder = excluded.folder,
@@ -1189,7 +3394,8 @@ impl Database {
                     has_attachments = excluded.has_attachments,
                     size_bytes = excluded.size_bytes,
                     raw_hash = excluded.raw_hash,
-                    updated_at = excluded.updated_at;
+                    updated_at = excluded.updated_at,
+                    message_id_header = excluded.message_id_header;
                 "#,
             )
             .bind(&message.id)
@@ -1210,32 +3416,13 @@ impl Database {
             .bind(&message.raw_hash)
             .bind(message.created_at)
             .bind(message.updated_at)
+            .bind(&message.message_id_header)
             .execute(&mut *tx)
             .await
             .context("batch upserting message")?;
+            addresses::replace_in(&mut tx, &message.id, &recipients).await?;
 
-            // Insert/update body
-            sqlx::query(
-                r#"
-                INSERT INTO bodies (message_id, raw_rfc822, sanitized_text, mime_summary, attachments_json, sanitized_at)
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
-                ON CONFLICT(message_id) DO UPDATE SET
-                    raw_rfc822 = excluded.raw_rfc822,
-                    sanitized_text = excluded.sanitized_text,
-                    mime_summary = excluded.mime_summary,
-                    attachments_json = excluded.attachments_json,
-                    sanitized_at = excluded.sanitized_at;
-                "#,
-            )
-            .bind(&body.message_id)
-            .bind(&body.raw_rfc822)
-            .bind(&body.sanitized_text)
-            .bind(&body.mime_summary)
-            .bind(&body.attachments_json)
-            .bind(body.sanitized_at)
-            .execute(&mut *tx)
-            .await
-            .context("batch upserting body")?;
+            upsert_body_in(&mut tx, cipher.as_deref(), body, &self.blob_store()).await?;
         }
 
         // Commit the entire batch atomically
@@ -1244,6 +3431,153 @@ impl Database {
         Ok(())
     }
 
+    /// Applies `op` to the local cache and queues it in `pending_ops` (one row per message, with
+    /// the pre-op folder/uid as payload) in a single transaction. Returns messages affected.
+    pub async fn apply_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await.context("beginning message op tx")?;
+        let queued = apply_message_op_in_tx(&mut tx, account_id, op, message_ids).await?;
+        ops::enqueue_ops(&mut tx, account_id, op.kind(), &queued).await?;
+        tx.commit().await.context("committing message op tx")?;
+
+        Ok(queued.len())
+    }
+
+    /// Mirrors an op the server has already executed (e.g. a folder-wide UID STORE/MOVE) into
+    /// the local cache without queueing it.
+    pub async fn record_applied_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await.context("beginning message op tx")?;
+        let applied = apply_message_op_in_tx(&mut tx, account_id, op, message_ids).await?;
+        tx.commit().await.context("committing message op tx")?;
+
+        Ok(applied.len())
+    }
+
+    /// Queued flag ops (mark read, add label) for `uids` of `folder`.
+    pub async fn load_pending_flag_ops(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<Vec<ops::PendingFlagOp>> {
+        ops::load_pending_flag_ops(&self.pool, account_id, folder, uids).await
+    }
+
+    /// Every queued flag op for the account with its message's current location.
+    pub async fn load_replayable_flag_ops(&self, account_id: &str) -> Result<Vec<ops::ReplayOp>> {
+        ops::load_replayable_flag_ops(&self.pool, account_id).await
+    }
+
+    /// Every queued archive/move/copy/delete op for the account at its pre-op location.
+    pub async fn load_replayable_location_ops(
+        &self,
+        account_id: &str,
+    ) -> Result<Vec<ops::ReplayOp>> {
+        ops::load_replayable_location_ops(&self.pool, account_id).await
+    }
+
+    /// Restores the messages touched by rejected ops and drops the ops, atomically.
+    pub async fn rollback_pending_ops(&self, ids: &[i64]) -> Result<u64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning op rollback tx")?;
+        let restored = ops::rollback_ops(&mut tx, ids).await?;
+        tx.commit().await.context("committing op rollback tx")?;
+        Ok(restored)
+    }
+
+    pub async fn clear_pending_ops(&self, ids: &[i64]) -> Result<u64> {
+        ops::clear_ops(&self.pool, ids).await
+    }
+
+    /// Sets the destination uids a `COPYUID` gave messages moved by queued ops.
+    pub async fn link_moved_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uid_validity: u32,
+        links: &[(i64, u32)],
+    ) -> Result<u64> {
+        ops::link_moved(&self.pool, account_id, folder, uid_validity, links).await
+    }
+
+    /// Every op still queued for the account, oldest first.
+    pub async fn list_pending_ops(&self, account_id: &str) -> Result<Vec<ops::PendingOp>> {
+        ops::list_ops(&self.pool, account_id).await
+    }
+
+    /// Counts a server rejection of queued ops; returns how many of them are dead-lettered.
+    pub async fn record_op_failures(&self, ids: &[i64], error: &str) -> Result<u64> {
+        ops::record_failures(&self.pool, ids, error).await
+    }
+
+    /// Releases dead-lettered ops for the next replay.
+    pub async fn retry_dead_ops(&self, ids: &[i64]) -> Result<u64> {
+        ops::retry_dead(&self.pool, ids).await
+    }
+
+    /// Holds queued ops back from replay because the server changed their message.
+    pub async fn mark_op_conflicts(
+        &self,
+        ids: &[i64],
+        server_flags: &[String],
+        server_labels: &[String],
+    ) -> Result<u64> {
+        ops::mark_conflicts(&self.pool, ids, server_flags, server_labels).await
+    }
+
+    /// Queued ops held back by a conflict, oldest first, with decrypted subjects.
+    pub async fn list_op_conflicts(&self, account_id: &str) -> Result<Vec<ops::OpConflict>> {
+        let mut conflicts = ops::list_conflicts(&self.pool, account_id).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for conflict in &mut conflicts {
+                conflict.subject = conflict
+                    .subject
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Settles held ops (see `ops::ConflictResolution`), atomically.
+    pub async fn resolve_op_conflicts(
+        &self,
+        ids: &[i64],
+        resolution: ops::ConflictResolution,
+    ) -> Result<u64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning conflict resolution tx")?;
+        let settled = ops::resolve_conflicts(&mut tx, ids, resolution).await?;
+        tx.commit()
+            .await
+            .context("committing conflict resolution tx")?;
+        Ok(settled)
+    }
+
     pub async fn delete_message(&self, message_id: &str) -> Result<()> {
         // Delete body first (foreign key constraint)
         sqlx::query("DELETE FROM bodies WHERE message_id = ?1")
@@ -1348,7 +3682,21 @@ impl Database {
     }
 }
 
+/// Data directory of the active profile (`crate::profile`), created if needed.
 pub(crate) fn default_data_dir() -> Result<PathBuf> {
+    let base = base_data_dir()?;
+    let Some(profile) = profile::active() else {
+        return Ok(base);
+    };
+    let path = profile::data_dir(&base, Some(&profile));
+    std::fs::create_dir_all(&path)
+        .with_context(|| format!("creating profile directory {}", path.display()))?;
+    Ok(path)
+}
+
+/// `OTTO_DATA_DIR`, else `~/otto`, else `./otto-data`: the default profile's data directory,
+/// which also holds the named profiles.
+pub(crate) fn base_data_dir() -> Result<PathBuf> {
     if let Ok(custom) = env::var("OTTO_DATA_DIR") {
         let path = PathBuf::from(custom);
         std::fs::create_dir_all(&path)
@@ -1375,15 +3723,653 @@ pub(crate) fn default_data_dir() -> Result<PathBuf> {
     Ok(path)
 }
 
-fn provider_to_str(provider: &Provider) -> String {
+pub(crate) fn provider_to_str(provider: &Provider) -> String {
     match provider {
         Provider::GmailImap => "gmail-imap".to_string(),
+        Provider::OutlookImap => "outlook-imap".to_string(),
+        Provider::Imap => "imap".to_string(),
     }
 }
 
-fn provider_from_str(raw: &str) -> Provider {
+pub(crate) fn provider_from_str(raw: &str) -> Provider {
     match raw {
         "gmail-imap" => Provider::GmailImap,
+        "outlook-imap" => Provider::OutlookImap,
+        "imap" => Provider::Imap,
         _ => Provider::GmailImap,
     }
 }
+
+/// Id of a row in another folder sharing `message`'s `Message-ID`, for messages keyed by the
+/// `account:folder:uid` fallback (no X-GM-MSGID).
+async fn find_cross_folder_copy(
+    conn: &mut SqliteConnection,
+    account_id: &str,
+    message: &MessageRecord,
+) -> Result<Option<String>> {
+    let Some(header) = message.message_id_header.as_deref() else {
+        return Ok(None);
+    };
+    if !message.id.contains(':') {
+        return Ok(None);
+    }
+    sqlx::query_scalar(
+        r#"
+        SELECT id FROM messages
+        WHERE account_id = ?1 AND message_id_header = ?2 AND folder != ?3 AND id != ?4
+        LIMIT 1;
+        "#,
+    )
+    .bind(account_id)
+    .bind(header)
+    .bind(&message.folder)
+    .bind(&message.id)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("looking up message by Message-ID")
+}
+
+/// Maps a row of [`ACCOUNT_SELECT`]'s columns. Generic over the backend so the SQLite and
+/// Postgres stores read accounts the same way.
+pub(crate) fn account_from_row<R: Row>(row: &R) -> Account
+where
+    usize: ColumnIndex<R>,
+    String: for<'r> Decode<'r, R::Database> + Type<R::Database>,
+    i64: for<'r> Decode<'r, R::Database> + Type<R::Database>,
+{
+    let cutoff_raw: String = row.get(3);
+    let cutoff = NaiveDate::parse_from_str(&cutoff_raw, "%Y-%m-%d")
+        .unwrap_or_else(|_| NaiveDate::from_ymd_opt(2025, 12, 1).unwrap());
+    let folders_json: String = row.get(7);
+    let folders: Vec<String> =
+        serde_json::from_str(&folders_json).unwrap_or_else(|_| vec!["INBOX".into()]);
+    let policies_json: String = row.get(11);
+    let folder_policies = serde_json::from_str(&policies_json).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring unreadable folder_policies");
+        Default::default()
+    });
+    let smart_json: String = row.get(15);
+    let smart_folders = serde_json::from_str(&smart_json).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring unreadable smart_folders");
+        Vec::new()
+    });
+    let imap_json: String = row.get(17);
+    let imap = serde_json::from_str(&imap_json).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring unreadable imap_endpoint");
+        Default::default()
+    });
+    let credential = row
+        .get::<Option<String>, _>(19)
+        .map(|json| {
+            serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring unreadable credential; using OAuth");
+                Credential::OAuth
+            })
+        })
+        .unwrap_or_default();
+    let cleanup_json: String = row.get(20);
+    let cleanup_rules = serde_json::from_str(&cleanup_json).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring unreadable cleanup_rules");
+        Vec::new()
+    });
+    let notify_json: String = row.get(21);
+    let notify_rules = serde_json::from_str(&notify_json).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring unreadable notify_rules");
+        Vec::new()
+    });
+    let namespace = row.get::<Option<String>, _>(22).and_then(|json| {
+        serde_json::from_str(&json)
+            .map_err(|e| warn!(error = %e, "Ignoring unreadable namespace"))
+            .ok()
+    });
+    let tls_json: String = row.get(23);
+    let tls_policy = serde_json::from_str(&tls_json).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring unreadable tls_policy");
+        Default::default()
+    });
+    Account {
+        id: row.get(0),
+        email: row.get(1),
+        provider: provider_from_str(&row.get::<String, _>(2)),
+        settings: AccountSettings {
+            cutoff_since: cutoff,
+            poll_interval_minutes: row.get::<i64, _>(4) as u32,
+            prefetch_recent: row.get::<i64, _>(5) as u32,
+            safe_mode: row.get::<i64, _>(6) == 1,
+            folders,
+            max_download_bytes_per_sec: row
+                .get::<Option<i64>, _>(10)
+                .filter(|bps| *bps > 0)
+                .map(|bps| bps as u64),
+            folder_policies,
+            encrypt_columns: row.get::<i64, _>(12) == 1,
+            unread_only: row.get::<i64, _>(13) == 1,
+            max_message_bytes: row
+                .get::<Option<i64>, _>(14)
+                .filter(|bytes| *bytes > 0)
+                .map(|bytes| bytes as u64),
+            smart_folders,
+            all_mail_mode: row.get::<i64, _>(16) == 1,
+            imap,
+            enabled: row.get::<i64, _>(18) == 1,
+            credential,
+            cleanup_rules,
+            notify_rules,
+            namespace,
+            tls_policy,
+        },
+        created_at: row.get(8),
+        updated_at: row.get(9),
+    }
+}
+
+/// Maps a row of [`MESSAGE_RECORD_SELECT`]'s columns, as stored (sealed columns stay sealed).
+pub(crate) fn message_from_row<R: Row>(account_id: &str, row: &R) -> MessageRecord
+where
+    usize: ColumnIndex<R>,
+    String: for<'r> Decode<'r, R::Database> + Type<R::Database>,
+    i64: for<'r> Decode<'r, R::Database> + Type<R::Database>,
+{
+    MessageRecord {
+        id: row.get(0),
+        account_id: account_id.to_string(),
+        folder: row.get(1),
+        uid: row.get::<Option<i64>, _>(2).map(|v| v as u32),
+        thread_id: row.get(3),
+        internal_date: row.get(4),
+        subject: row.get(5),
+        from: row.get(6),
+        from_name: row.get(18),
+        to: row.get(7),
+        cc: row.get(8),
+        bcc: row.get(9),
+        flags: serde_json::from_str(&row.get::<String, _>(10)).unwrap_or_default(),
+        labels: serde_json::from_str(&row.get::<String, _>(11)).unwrap_or_default(),
+        has_attachments: row.get::<i64, _>(12) == 1,
+        size_bytes: row.get::<Option<i64>, _>(13).map(|v| v as u32),
+        raw_hash: row.get(14),
+        message_id_header: row.get(19),
+        references: Vec::new(),
+        body_status: body_status_from_str(&row.get::<String, _>(17)),
+        created_at: row.get(15),
+        updated_at: row.get(16),
+    }
+}
+
+pub(crate) fn sync_runs_from_rows<R: Row>(account_id: &str, rows: Vec<R>) -> Vec<SyncRunRecord>
+where
+    usize: ColumnIndex<R>,
+    String: for<'r> Decode<'r, R::Database> + Type<R::Database>,
+    i64: for<'r> Decode<'r, R::Database> + Type<R::Database>,
+{
+    rows.into_iter()
+        .map(|row| SyncRunRecord {
+            account_id: account_id.to_string(),
+            folder: row.get(0),
+            run_started_at: row.get(1),
+            duration_ms: row.get(2),
+            added: row.get::<i64, _>(3) as usize,
+            updated: row.get::<i64, _>(4) as usize,
+            deleted: row.get::<i64, _>(5) as usize,
+            bytes: row.get::<i64, _>(6) as u64,
+            ok: row.get::<String, _>(7) == "ok",
+            error: row.get(8),
+        })
+        .collect()
+}
+
+/// Plaintext of stored raw bytes: opened under `cipher`, then decompressed.
+pub(crate) fn open_raw(
+    cipher: Option<&ColumnCipher>,
+    stored: Vec<u8>,
+    format: RawFormat,
+) -> Result<Vec<u8>> {
+    let packed = match cipher {
+        Some(cipher) => cipher.open_bytes(&stored)?,
+        None => stored,
+    };
+    compression::decompress(packed, format)
+}
+
+/// Upserts `body` (plaintext), compressed and then sealed under `cipher`. In a
+/// content-addressed mode the raw message is stored once under its content hash and the body
+/// row only references it.
+async fn upsert_body_in(
+    conn: &mut SqliteConnection,
+    cipher: Option<&ColumnCipher>,
+    body: &BodyRecord,
+    blobs: &BlobStore,
+) -> Result<()> {
+    let (packed, format) = match body.raw_rfc822.as_deref() {
+        Some(plain) => {
+            let (packed, format) = compression::compress(plain)?;
+            (Some(packed), format)
+        }
+        None => (None, RawFormat::Plain),
+    };
+    let packed = BodyRecord {
+        message_id: body.message_id.clone(),
+        raw_rfc822: packed,
+        sanitized_text: body.sanitized_text.clone(),
+        mime_summary: body.mime_summary.clone(),
+        attachments_json: body.attachments_json.clone(),
+        sanitized_at: body.sanitized_at,
+        sanitizer_version: body.sanitizer_version,
+        degraded: body.degraded,
+    };
+    let sealed;
+    let stored = match cipher {
+        Some(cipher) => {
+            sealed = cipher.seal_body(&packed)?;
+            &sealed
+        }
+        None => &packed,
+    };
+    let blob_hash = match (body.raw_rfc822.as_deref(), stored.raw_rfc822.as_deref()) {
+        (Some(plain), Some(data)) if blobs.content_addressed() => {
+            let hash = blobs::content_hash(cipher, plain);
+            blobs.put(&mut *conn, &hash, data, format).await?;
+            Some(hash)
+        }
+        _ => None,
+    };
+    let (raw, raw_format) = match blob_hash {
+        Some(_) => (None, RawFormat::Plain),
+        None => (stored.raw_rfc822.as_deref(), format),
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO bodies (message_id, raw_rfc822, blob_hash, sanitized_text, mime_summary, attachments_json, sanitized_at, sanitizer_version, raw_format, degraded)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        ON CONFLICT(message_id) DO UPDATE SET
+            raw_rfc822 = excluded.raw_rfc822,
+            blob_hash = excluded.blob_hash,
+            sanitized_text = excluded.sanitized_text,
+            mime_summary = excluded.mime_summary,
+            attachments_json = excluded.attachments_json,
+            sanitized_at = excluded.sanitized_at,
+            sanitizer_version = excluded.sanitizer_version,
+            raw_format = excluded.raw_format,
+            degraded = excluded.degraded;
+        "#,
+    )
+    .bind(&stored.message_id)
+    .bind(raw)
+    .bind(&blob_hash)
+    .bind(&stored.sanitized_text)
+    .bind(&stored.mime_summary)
+    .bind(&stored.attachments_json)
+    .bind(stored.sanitized_at)
+    .bind(stored.sanitizer_version)
+    .bind(raw_format.as_i64())
+    .bind(stored.degraded)
+    .execute(&mut *conn)
+    .await
+    .context("upserting body")?;
+    Ok(())
+}
+
+/// Shared by folder and backfill commits: upserts messages + bodies and applies location
+/// updates for messages already cached under another folder.
+async fn write_messages_in_tx(
+    conn: &mut SqliteConnection,
+    account_id: &str,
+    cipher: Option<&ColumnCipher>,
+    blobs: &BlobStore,
+    messages: &[MessageRecord],
+    bodies: &[BodyRecord],
+    location_updates: &[MessageLocationUpdate],
+) -> Result<()> {
+    let now = now_ts();
+    let mut relinked = HashSet::new();
+
+    for message in messages {
+        // Without a server-stable id, a copy already cached from another folder is relinked
+        // here rather than stored twice (covers folders synced in parallel).
+        if let Some(existing) = find_cross_folder_copy(&mut *conn, account_id, message).await? {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET folder = ?1, uid = ?2, flags = ?3, labels = ?4, updated_at = ?5
+                WHERE account_id = ?6 AND id = ?7;
+                "#,
+            )
+            .bind(&message.folder)
+            .bind(message.uid.map(|v| v as i64))
+            .bind(serde_json::to_string(&message.flags).unwrap_or_else(|_| "[]".into()))
+            .bind(serde_json::to_string(&message.labels).unwrap_or_else(|_| "[]".into()))
+            .bind(now)
+            .bind(account_id)
+            .bind(&existing)
+            .execute(&mut *conn)
+            .await
+            .context("relinking duplicate message")?;
+            relinked.insert(message.id.as_str());
+            continue;
+        }
+
+        // X-GM-THRID wins; otherwise thread on Message-ID/References.
+        let thread_id = match &message.thread_id {
+            Some(thread_id) => Some(thread_id.clone()),
+            None => threads::assign_thread(&mut *conn, account_id, message).await?,
+        };
+
+        let recipients = addresses::recipients_of(message);
+        let sealed;
+        let message = match cipher {
+            Some(cipher) => {
+                sealed = cipher.seal_message(message)?;
+                &sealed
+            }
+            None => message,
+        };
+        // A header-only insert never downgrades a row whose body is already stored.
+        sqlx::query(
+            r#"
+            INSERT INTO messages (
+                id, account_id, folder, uid, thread_id, internal_date,
+                subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
+                flags, labels, has_attachments, size_bytes, raw_hash,
+                created_at, updated_at, body_status, from_name, message_id_header
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)
+            ON CONFLICT(id) DO UPDATE SET
+                account_id = excluded.account_id,
+                folder = excluded.folder,
+                uid = excluded.uid,
+                thread_id = excluded.thread_id,
+                internal_date = excluded.internal_date,
+                subject = excluded.subject,
+                from_addr = excluded.from_addr,
+                to_addrs = excluded.to_addrs,
+                cc_addrs = excluded.cc_addrs,
+                bcc_addrs = excluded.bcc_addrs,
+                flags = excluded.flags,
+                labels = excluded.labels,
+                has_attachments = CASE WHEN excluded.body_status = 'pending'
+                    OR (excluded.body_status = 'too_large' AND messages.body_status = 'full')
+                    THEN messages.has_attachments ELSE excluded.has_attachments END,
+                size_bytes = excluded.size_bytes,
+                raw_hash = COALESCE(excluded.raw_hash, messages.raw_hash),
+                created_at = messages.created_at,
+                updated_at = excluded.updated_at,
+                body_status = CASE WHEN excluded.body_status = 'pending'
+                    OR (excluded.body_status = 'too_large' AND messages.body_status = 'full')
+                    THEN messages.body_status ELSE excluded.body_status END,
+                from_name = excluded.from_name,
+                message_id_header = COALESCE(excluded.message_id_header, messages.message_id_header);
+            "#,
+        )
+        .bind(&message.id)
+        .bind(&message.account_id)
+        .bind(&message.folder)
+        .bind(message.uid.map(|v| v as i64))
+        .bind(&thread_id)
+        .bind(message.internal_date)
+        .bind(&message.subject)
+        .bind(&message.from)
+        .bind(&message.to)
+        .bind(&message.cc)
+        .bind(&message.bcc)
+        .bind(serde_json::to_string(&message.flags).unwrap_or_else(|_| "[]".into()))
+        .bind(serde_json::to_string(&message.labels).unwrap_or_else(|_| "[]".into()))
+        .bind(if message.has_attachments { 1 } else { 0 })
+        .bind(message.size_bytes.map(|v| v as i64))
+        .bind(&message.raw_hash)
+        .bind(message.created_at)
+        .bind(message.updated_at)
+        .bind(body_status_to_str(message.body_status))
+        .bind(&message.from_name)
+        .bind(&message.message_id_header)
+        .execute(&mut *conn)
+        .await
+        .context("upserting message in tx")?;
+        addresses::replace_in(&mut *conn, &message.id, &recipients).await?;
+    }
+
+    for body in bodies {
+        if relinked.contains(body.message_id.as_str()) {
+            continue;
+        }
+        upsert_body_in(&mut *conn, cipher, body, blobs).await?;
+    }
+
+    if !location_updates.is_empty() {
+        for (message_id, folder, uid, flags, labels, thread_id, internal_date, size_bytes) in
+            location_updates
+        {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET folder = ?1,
+                    uid = ?2,
+                    flags = ?3,
+                    labels = ?4,
+                    thread_id = COALESCE(?5, thread_id),
+                    internal_date = ?6,
+                    size_bytes = ?7,
+                    updated_at = ?8
+                WHERE account_id = ?9 AND id = ?10;
+                "#,
+            )
+            .bind(folder)
+            .bind(*uid as i64)
+            .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
+            .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
+            .bind(thread_id)
+            .bind(internal_date)
+            .bind(size_bytes.map(|v| v as i64))
+            .bind(now)
+            .bind(account_id)
+            .bind(message_id)
+            .execute(&mut *conn)
+            .await
+            .context("updating message location in tx")?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn role_folder_in(
+    conn: &mut SqliteConnection,
+    account_id: &str,
+    role: FolderRole,
+) -> Result<String> {
+    let rows = sqlx::query(
+        "SELECT name, attributes FROM folders WHERE account_id = ?1 AND attributes IS NOT NULL",
+    )
+    .bind(account_id)
+    .fetch_all(&mut *conn)
+    .await
+    .context("loading special-use folders")?;
+    let mailboxes: Vec<MailboxInfo> = rows
+        .into_iter()
+        .map(|row| MailboxInfo {
+            name: row.get(0),
+            delimiter: None,
+            attributes: serde_json::from_str(&row.get::<String, _>(1)).unwrap_or_default(),
+        })
+        .collect();
+    Ok(special_use_folder(&mailboxes, role)
+        .unwrap_or(role.gmail_default())
+        .to_string())
+}
+
+/// Local half of a `MessageOp`: updates/deletes the cached rows and returns one
+/// `(message_id, payload)` per message found, payload holding the pre-op folder/uid/label.
+async fn apply_message_op_in_tx(
+    conn: &mut SqliteConnection,
+    account_id: &str,
+    op: &MessageOp,
+    message_ids: &[String],
+) -> Result<Vec<(String, Option<String>)>> {
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, folder, uid, flags, labels FROM messages WHERE account_id = ",
+    );
+    qb.push_bind(account_id);
+    qb.push(" AND id IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in message_ids {
+            separated.push_bind(id);
+        }
+    }
+    qb.push(")");
+    let rows = qb
+        .build()
+        .fetch_all(&mut *conn)
+        .await
+        .context("loading messages for op")?;
+
+    let archive = role_folder_in(&mut *conn, account_id, FolderRole::All).await?;
+    let trash = role_folder_in(&mut *conn, account_id, FolderRole::Trash).await?;
+    let now = now_ts();
+    let mut queued = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let id: String = row.get(0);
+        let (payload, local) = plan_local_op(
+            op,
+            &archive,
+            &trash,
+            LocalState {
+                folder: row.get(1),
+                uid: row.get(2),
+                flags: serde_json::from_str(&row.get::<String, _>(3)).unwrap_or_default(),
+                labels: serde_json::from_str(&row.get::<String, _>(4)).unwrap_or_default(),
+            },
+        );
+        queued.push((id.clone(), Some(payload)));
+
+        sqlx::query(
+            r#"
+            UPDATE messages
+            SET folder = ?1, uid = ?2, flags = ?3, labels = ?4, updated_at = ?5
+            WHERE id = ?6;
+            "#,
+        )
+        .bind(&local.folder)
+        .bind(local.uid)
+        .bind(serde_json::to_string(&local.flags).unwrap_or_else(|_| "[]".into()))
+        .bind(serde_json::to_string(&local.labels).unwrap_or_else(|_| "[]".into()))
+        .bind(now)
+        .bind(&id)
+        .execute(&mut *conn)
+        .await
+        .context("updating message for op")?;
+    }
+
+    Ok(queued)
+}
+
+/// A cached message's location, flags and labels, before or after a `MessageOp`.
+pub(crate) struct LocalState {
+    pub folder: String,
+    pub uid: Option<i64>,
+    pub flags: Vec<String>,
+    pub labels: Vec<String>,
+}
+
+/// What a `MessageOp` does to one cached row, given the account's All Mail and Trash folders:
+/// the queued op's payload (the pre-op snapshot) and the row's state afterwards.
+pub(crate) fn plan_local_op(
+    op: &MessageOp,
+    archive: &str,
+    trash: &str,
+    before: LocalState,
+) -> (String, LocalState) {
+    let LocalState {
+        folder,
+        uid,
+        mut flags,
+        mut labels,
+    } = before;
+
+    // Pre-op snapshot: where to replay the op, and what to restore if the server rejects it.
+    let payload = serde_json::json!({
+        "folder": folder,
+        "uid": uid,
+        "label": op.label(),
+        "dest": op.destination(),
+        "flags": flags,
+        "labels": labels,
+    });
+
+    op.apply_to_flags(&mut flags, &mut labels);
+
+    // Moved copies lose their source-folder uid; the destination's sync re-links them by id.
+    // Deleting from Trash only marks the row; it is removed once the server expunges it.
+    // Rows in All Mail stay put: a move there swaps the Inbox label for the destination's.
+    let destination = match op {
+        MessageOp::Archive => Some(archive),
+        MessageOp::Move(dest) if folder == archive => {
+            labels.retain(|l| !l.eq_ignore_ascii_case("\\Inbox"));
+            if !labels.iter().any(|l| l == dest) {
+                labels.push(dest.clone());
+            }
+            None
+        }
+        MessageOp::Move(dest) => Some(dest.as_str()),
+        MessageOp::Delete if folder == trash => {
+            if !flags.iter().any(|f| f == "Deleted") {
+                flags.push("Deleted".to_string());
+            }
+            None
+        }
+        MessageOp::Delete => Some(trash),
+        _ => None,
+    };
+    let (folder, uid) = match destination {
+        Some(dest) if dest != folder => (dest.to_string(), None),
+        _ => (folder, uid),
+    };
+    (
+        payload.to_string(),
+        LocalState {
+            folder,
+            uid,
+            flags,
+            labels,
+        },
+    )
+}
+
+/// Opens `value` with `from` (if sealed) and seals it again with `to`.
+pub(crate) fn reseal_text(
+    from: Option<&ColumnCipher>,
+    to: Option<&ColumnCipher>,
+    value: Option<String>,
+) -> Result<Option<String>> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    let plain = match from {
+        Some(cipher) => cipher.open_text(&value)?,
+        None => value,
+    };
+    match to {
+        Some(cipher) => cipher.seal_text(&plain).map(Some),
+        None => Ok(Some(plain)),
+    }
+}
+
+pub(crate) fn parse_optional_date(raw: Option<String>) -> Option<NaiveDate> {
+    raw.and_then(|s| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok())
+}
+
+pub(crate) fn body_status_to_str(status: BodyStatus) -> &'static str {
+    match status {
+        BodyStatus::Full => "full",
+        BodyStatus::Pending => "pending",
+        BodyStatus::TooLarge => "too_large",
+    }
+}
+
+pub(crate) fn body_status_from_str(raw: &str) -> BodyStatus {
+    match raw {
+        "pending" => BodyStatus::Pending,
+        "too_large" => BodyStatus::TooLarge,
+        _ => BodyStatus::Full,
+    }
+}
@@ -0,0 +1,63 @@
+//! `server_identity`: what each account's server said about itself in answer to IMAP ID
+//! (RFC 2971), one row per account, replaced on every sync that connects to a server
+//! advertising ID. Kept for diagnostics (`otto imap-server`).
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use sqlx::{Row, SqlitePool};
+
+use crate::types::ServerIdentity;
+
+pub(crate) async fn ensure_identity_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS server_identity (
+            account_id TEXT PRIMARY KEY,
+            fields TEXT NOT NULL,
+            fetched_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating server_identity table")?;
+    Ok(())
+}
+
+pub(crate) async fn save(pool: &SqlitePool, identity: &ServerIdentity) -> Result<()> {
+    let fields = serde_json::to_string(&identity.fields).context("serializing server identity")?;
+    sqlx::query(
+        r#"
+        INSERT INTO server_identity (account_id, fields, fetched_at)
+        VALUES (?1, ?2, ?3)
+        ON CONFLICT(account_id) DO UPDATE SET
+            fields = excluded.fields,
+            fetched_at = excluded.fetched_at;
+        "#,
+    )
+    .bind(&identity.account_id)
+    .bind(fields)
+    .bind(identity.fetched_at)
+    .execute(pool)
+    .await
+    .context("saving server identity")?;
+    Ok(())
+}
+
+pub(crate) async fn load(pool: &SqlitePool, account_id: &str) -> Result<Option<ServerIdentity>> {
+    let row = sqlx::query("SELECT fields, fetched_at FROM server_identity WHERE account_id = ?1;")
+        .bind(account_id)
+        .fetch_optional(pool)
+        .await
+        .context("loading server identity")?;
+    row.map(|row| {
+        let fields: BTreeMap<String, String> =
+            serde_json::from_str(&row.get::<String, _>(0)).context("parsing server identity")?;
+        Ok(ServerIdentity {
+            account_id: account_id.to_string(),
+            fields,
+            fetched_at: row.get(1),
+        })
+    })
+    .transpose()
+}
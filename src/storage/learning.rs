@@ -0,0 +1,198 @@
+//! `sender_actions`: how often the user archived, deleted or labeled a sender's mail by hand,
+//! for learned rule suggestions (`crate::learn`). Rows are keyed by a hash of the sender
+//! address, keyed with the column key on encrypted accounts, and keep the address itself
+//! sealed like notes (`Database` seals, opens and rekeys it).
+use anyhow::{Context, Result, anyhow};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+
+use crate::types::now_ts;
+
+/// Where a sender/action pair stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuggestionStatus {
+    /// Below the threshold.
+    Counting,
+    /// Reached the threshold; waiting for the user to accept or reject the rule.
+    Pending,
+    Accepted,
+    /// Declined; not suggested again.
+    Rejected,
+}
+
+impl SuggestionStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SuggestionStatus::Counting => "counting",
+            SuggestionStatus::Pending => "pending",
+            SuggestionStatus::Accepted => "accepted",
+            SuggestionStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw {
+            "counting" => Ok(SuggestionStatus::Counting),
+            "pending" => Ok(SuggestionStatus::Pending),
+            "accepted" => Ok(SuggestionStatus::Accepted),
+            "rejected" => Ok(SuggestionStatus::Rejected),
+            other => Err(anyhow!("unknown suggestion status {:?}", other)),
+        }
+    }
+}
+
+/// One sender/action pair and how often the user did it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SenderAction {
+    /// Lowercased bare address.
+    pub sender: String,
+    /// `archive`, `delete` or `label`.
+    pub action: String,
+    /// The label for `label`, otherwise empty.
+    pub label: String,
+    /// Messages the action was applied to.
+    pub count: i64,
+    pub status: SuggestionStatus,
+    pub first_at: i64,
+    pub last_at: i64,
+}
+
+pub(crate) async fn ensure_learning_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sender_actions (
+            account_id TEXT NOT NULL,
+            sender_key TEXT NOT NULL,
+            action TEXT NOT NULL,
+            label TEXT NOT NULL DEFAULT '',
+            sender TEXT NOT NULL,
+            count INTEGER NOT NULL,
+            status TEXT NOT NULL DEFAULT 'counting',
+            first_at INTEGER NOT NULL,
+            last_at INTEGER NOT NULL,
+            PRIMARY KEY (account_id, sender_key, action, label),
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating sender_actions table")?;
+    Ok(())
+}
+
+/// Adds `count` to the pair (`sender` as stored). When that brought a counting pair to
+/// `threshold`, it becomes pending and its new count is returned.
+#[allow(clippy::too_many_arguments)] // the row key, its stored sender and the increment
+pub(crate) async fn record(
+    pool: &SqlitePool,
+    account_id: &str,
+    sender_key: &str,
+    sender: &str,
+    action: &str,
+    label: &str,
+    count: i64,
+    threshold: i64,
+) -> Result<Option<i64>> {
+    let now = now_ts();
+    let mut tx = pool.begin().await.context("beginning sender action tx")?;
+    sqlx::query(
+        r#"
+        INSERT INTO sender_actions
+            (account_id, sender_key, action, label, sender, count, status, first_at, last_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'counting', ?7, ?7)
+        ON CONFLICT(account_id, sender_key, action, label) DO UPDATE SET
+            count = count + excluded.count,
+            sender = excluded.sender,
+            last_at = excluded.last_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(sender_key)
+    .bind(action)
+    .bind(label)
+    .bind(sender)
+    .bind(count)
+    .bind(now)
+    .execute(&mut *tx)
+    .await
+    .context("recording sender action")?;
+    let promoted = sqlx::query(
+        r#"
+        UPDATE sender_actions SET status = 'pending'
+        WHERE account_id = ?1 AND sender_key = ?2 AND action = ?3 AND label = ?4
+          AND status = 'counting' AND count >= ?5
+        RETURNING count;
+        "#,
+    )
+    .bind(account_id)
+    .bind(sender_key)
+    .bind(action)
+    .bind(label)
+    .bind(threshold)
+    .fetch_optional(&mut *tx)
+    .await
+    .context("promoting sender action")?
+    .map(|row| row.get(0));
+    tx.commit().await.context("committing sender action tx")?;
+    Ok(promoted)
+}
+
+/// Sets the pair's status; false when it was never recorded.
+pub(crate) async fn set_status(
+    pool: &SqlitePool,
+    account_id: &str,
+    sender_key: &str,
+    action: &str,
+    label: &str,
+    status: SuggestionStatus,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sender_actions SET status = ?5
+        WHERE account_id = ?1 AND sender_key = ?2 AND action = ?3 AND label = ?4;
+        "#,
+    )
+    .bind(account_id)
+    .bind(sender_key)
+    .bind(action)
+    .bind(label)
+    .bind(status.as_str())
+    .execute(pool)
+    .await
+    .context("updating sender action status")?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The account's pairs (sender as stored), optionally with one status, most recent first.
+pub(crate) async fn list(
+    pool: &SqlitePool,
+    account_id: &str,
+    status: Option<SuggestionStatus>,
+) -> Result<Vec<SenderAction>> {
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT sender, action, label, count, status, first_at, last_at FROM sender_actions WHERE account_id = ",
+    );
+    qb.push_bind(account_id);
+    if let Some(status) = status {
+        qb.push(" AND status = ").push_bind(status.as_str());
+    }
+    qb.push(" ORDER BY last_at DESC, sender_key, action, label");
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("loading sender actions")?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(SenderAction {
+                sender: row.get(0),
+                action: row.get(1),
+                label: row.get(2),
+                count: row.get(3),
+                status: SuggestionStatus::parse(&row.get::<String, _>(4))?,
+                first_at: row.get(5),
+                last_at: row.get(6),
+            })
+        })
+        .collect()
+}
@@ -1,3 +1,22 @@
+pub mod addresses;
+pub mod audit;
+mod blobs;
+pub mod cleanup;
+pub mod compression;
+pub mod crypto;
 pub mod db;
+pub mod identity;
+pub mod learning;
+pub mod notes;
+pub mod ops;
+pub mod postgres;
+pub mod quota;
+pub mod recovery;
+pub mod reply_later;
+pub mod store;
+pub mod summaries;
+mod threads;
+pub mod translations;
 
 pub use db::Database;
+pub use store::{BodyStorage, MailStore, StorageBackend, open_store};
@@ -0,0 +1,102 @@
+//! Private notes on messages ("called them back 3/12"): free text kept in the local cache
+//! only, never sent to the server. One note per message; rows go away with their message.
+//! Accounts with column encryption store the text sealed (`Database` seals and opens it).
+use anyhow::{Context, Result};
+use sqlx::{Row, SqlitePool};
+
+use crate::types::now_ts;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageNote {
+    pub message_id: String,
+    pub note: String,
+    pub updated_at: i64,
+    /// The message's subject and sender, for listings.
+    pub subject: Option<String>,
+    pub from: Option<String>,
+}
+
+pub(crate) async fn ensure_notes_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS message_notes (
+            message_id TEXT PRIMARY KEY,
+            account_id TEXT NOT NULL,
+            note TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_message_notes_account ON message_notes(account_id);
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating message_notes table")?;
+    Ok(())
+}
+
+/// Sets (or replaces) the note of the account's message; false when the account has no such
+/// message.
+pub(crate) async fn set(
+    pool: &SqlitePool,
+    account_id: &str,
+    message_id: &str,
+    note: &str,
+) -> Result<bool> {
+    let written = sqlx::query(
+        r#"
+        INSERT INTO message_notes (message_id, account_id, note, updated_at)
+        SELECT id, account_id, ?3, ?4 FROM messages WHERE account_id = ?1 AND id = ?2
+        ON CONFLICT(message_id) DO UPDATE SET
+            note = excluded.note,
+            updated_at = excluded.updated_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(message_id)
+    .bind(note)
+    .bind(now_ts())
+    .execute(pool)
+    .await
+    .context("saving message note")?
+    .rows_affected();
+    Ok(written > 0)
+}
+
+/// Removes the note; false when there was none.
+pub(crate) async fn clear(pool: &SqlitePool, account_id: &str, message_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM message_notes WHERE account_id = ?1 AND message_id = ?2")
+        .bind(account_id)
+        .bind(message_id)
+        .execute(pool)
+        .await
+        .context("clearing message note")?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// The account's notes, most recently edited first (text as stored).
+pub(crate) async fn list(pool: &SqlitePool, account_id: &str) -> Result<Vec<MessageNote>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT n.message_id, n.note, n.updated_at, m.subject, m.from_addr
+        FROM message_notes n
+        JOIN messages m ON m.id = n.message_id
+        WHERE n.account_id = ?1
+        ORDER BY n.updated_at DESC, n.message_id;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("loading message notes")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| MessageNote {
+            message_id: row.get(0),
+            note: row.get(1),
+            updated_at: row.get(2),
+            subject: row.get(3),
+            from: row.get(4),
+        })
+        .collect())
+}
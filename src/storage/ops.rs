@@ -1,6 +1,252 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use chrono::Utc;
-use sqlx::{Row, SqlitePool};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+use std::collections::BTreeSet;
+
+/// A user action on one or more messages, applied locally right away and queued in
+/// `pending_ops` for replay against the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageOp {
+    Archive,
+    Delete,
+    MarkRead,
+    MarkUnread,
+    Star,
+    Unstar,
+    AddLabel(String),
+    RemoveLabel(String),
+    /// Move to another folder (`UID MOVE`).
+    Move(String),
+    /// Copy into another folder; on Gmail this adds the folder's label (`UID COPY`).
+    Copy(String),
+}
+
+/// `pending_ops.kind` values that only change flags/labels (replayed with `UID STORE`).
+pub const FLAG_OP_KINDS: &[&str] = &[
+    "mark_read",
+    "mark_unread",
+    "star",
+    "unstar",
+    "add_label",
+    "remove_label",
+];
+
+/// `pending_ops.kind` values that move, copy or delete messages (replayed in queue order).
+pub const LOCATION_OP_KINDS: &[&str] = &["archive", "move", "copy", "delete"];
+
+/// Server rejections (one per replay pass) before an op is dead-lettered: held back from
+/// replay with its last error until `otto ops --retry` or `--drop` settles it.
+pub const MAX_OP_ATTEMPTS: i64 = 5;
+
+impl MessageOp {
+    /// Value stored in `pending_ops.kind`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MessageOp::Archive => "archive",
+            MessageOp::Delete => "delete",
+            MessageOp::MarkRead => "mark_read",
+            MessageOp::MarkUnread => "mark_unread",
+            MessageOp::Star => "star",
+            MessageOp::Unstar => "unstar",
+            MessageOp::AddLabel(_) => "add_label",
+            MessageOp::RemoveLabel(_) => "remove_label",
+            MessageOp::Move(_) => "move",
+            MessageOp::Copy(_) => "copy",
+        }
+    }
+
+    /// Destination folder of a move/copy, carried in the payload as `dest`.
+    pub fn destination(&self) -> Option<&str> {
+        match self {
+            MessageOp::Move(dest) | MessageOp::Copy(dest) => Some(dest),
+            _ => None,
+        }
+    }
+
+    /// Label carried in the op's payload.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            MessageOp::AddLabel(label) | MessageOp::RemoveLabel(label) => Some(label),
+            _ => None,
+        }
+    }
+
+    /// Rebuilds an op from a `pending_ops` row; `arg` is the payload's `label` or `dest`.
+    pub fn from_kind(kind: &str, arg: Option<String>) -> Option<Self> {
+        match kind {
+            "archive" => Some(MessageOp::Archive),
+            "delete" => Some(MessageOp::Delete),
+            "mark_read" => Some(MessageOp::MarkRead),
+            "mark_unread" => Some(MessageOp::MarkUnread),
+            "star" => Some(MessageOp::Star),
+            "unstar" => Some(MessageOp::Unstar),
+            "add_label" => arg.map(MessageOp::AddLabel),
+            "remove_label" => arg.map(MessageOp::RemoveLabel),
+            "move" => arg.map(MessageOp::Move),
+            "copy" => arg.map(MessageOp::Copy),
+            _ => None,
+        }
+    }
+
+    /// Applies the op's effect on a message's flags and labels. Folder changes (archive, move,
+    /// delete) are applied by the caller.
+    pub fn apply_to_flags(&self, flags: &mut Vec<String>, labels: &mut Vec<String>) {
+        match self {
+            MessageOp::MarkRead => add_flag(flags, "Seen"),
+            MessageOp::MarkUnread => remove_flag(flags, "Seen"),
+            MessageOp::Star => add_flag(flags, "Flagged"),
+            MessageOp::Unstar => remove_flag(flags, "Flagged"),
+            MessageOp::AddLabel(label) => {
+                if !labels.iter().any(|l| l == label) {
+                    labels.push(label.clone());
+                }
+            }
+            MessageOp::RemoveLabel(label) => labels.retain(|l| l != label),
+            MessageOp::Copy(dest) => {
+                if !labels.iter().any(|l| l == dest) {
+                    labels.push(dest.clone());
+                }
+            }
+            MessageOp::Archive => {
+                labels.retain(|l| !l.eq_ignore_ascii_case("\\Inbox"));
+            }
+            MessageOp::Move(_) | MessageOp::Delete => {}
+        }
+    }
+}
+
+/// Stored flags use the IMAP name without the backslash (`Seen`); older rows may carry it.
+fn add_flag(flags: &mut Vec<String>, name: &str) {
+    if !flags.iter().any(|f| f.trim_start_matches('\\') == name) {
+        flags.push(name.to_string());
+    }
+}
+
+fn remove_flag(flags: &mut Vec<String>, name: &str) {
+    flags.retain(|f| f.trim_start_matches('\\') != name);
+}
+
+/// What sync stores when the server changed a message's flags/labels while local flag ops for
+/// it are still queued in `pending_ops`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlagConflictPolicy {
+    /// Take the server's flags and drop the queued local ops.
+    ServerWins,
+    /// Keep the local flags; the queued ops stay for replay.
+    LocalWins,
+    /// Take the server's flags and re-apply the queued local ops on top, in queue order.
+    Merge,
+    /// Like `Merge`, except when the server changed the message since the ops were queued
+    /// (`is_remote_conflict`): then the local row is kept and the ops are held back from replay
+    /// with the server's values recorded in `pending_ops.conflict` until `otto conflicts`
+    /// settles them.
+    #[default]
+    Flag,
+}
+
+impl FlagConflictPolicy {
+    /// Parses `server-wins` / `local-wins` / `merge` (underscores also accepted).
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "server-wins" | "server" => Ok(Self::ServerWins),
+            "local-wins" | "local" => Ok(Self::LocalWins),
+            "merge" => Ok(Self::Merge),
+            "flag" => Ok(Self::Flag),
+            other => bail!(
+                "unknown flag conflict policy {:?} (expected flag, server-wins, local-wins or merge)",
+                other
+            ),
+        }
+    }
+
+    /// Flags and labels to store given the server's values and the message's queued ops;
+    /// `None` leaves the local row untouched.
+    pub fn resolve(
+        self,
+        server_flags: &[String],
+        server_labels: &[String],
+        pending: &[MessageOp],
+    ) -> Option<(Vec<String>, Vec<String>)> {
+        match self {
+            Self::ServerWins => Some((server_flags.to_vec(), server_labels.to_vec())),
+            Self::LocalWins => None,
+            Self::Merge | Self::Flag => {
+                let mut flags = server_flags.to_vec();
+                let mut labels = server_labels.to_vec();
+                for op in pending {
+                    op.apply_to_flags(&mut flags, &mut labels);
+                }
+                Some((flags, labels))
+            }
+        }
+    }
+}
+
+/// Whether the server changed a message in a way the queued ops would overwrite: its flags or
+/// labels differ from the pre-op `snapshot` and also from the snapshot with `pending` applied
+/// (the server already agreeing with the local change is not a conflict).
+pub fn is_remote_conflict(
+    snapshot: (&[String], &[String]),
+    server: (&[String], &[String]),
+    pending: &[MessageOp],
+) -> bool {
+    let normalized = |flags: &[String], labels: &[String]| {
+        let flags: BTreeSet<String> = flags
+            .iter()
+            .map(|f| f.trim_start_matches('\\').to_string())
+            .collect();
+        let labels: BTreeSet<String> = labels.iter().cloned().collect();
+        (flags, labels)
+    };
+    let server = normalized(server.0, server.1);
+    if server == normalized(snapshot.0, snapshot.1) {
+        return false;
+    }
+    let mut flags = snapshot.0.to_vec();
+    let mut labels = snapshot.1.to_vec();
+    for op in pending {
+        op.apply_to_flags(&mut flags, &mut labels);
+    }
+    server != normalized(&flags, &labels)
+}
+
+/// A queued op that changes flags/labels, located by the folder/uid it was queued against.
+#[derive(Debug, Clone)]
+pub struct PendingFlagOp {
+    pub id: i64,
+    pub uid: u32,
+    pub op: MessageOp,
+    /// The message's flags and labels before the op, when the payload recorded them.
+    pub snapshot: Option<(Vec<String>, Vec<String>)>,
+}
+
+/// A queued op held back because the server changed its message (`FlagConflictPolicy::Flag`).
+#[derive(Debug, Clone)]
+pub struct OpConflict {
+    pub id: i64,
+    pub message_id: String,
+    pub op: MessageOp,
+    /// The message's current location and subject; `None` when the row is gone.
+    pub folder: Option<String>,
+    pub uid: Option<u32>,
+    pub subject: Option<String>,
+    /// Local flags and labels (with the queued ops applied).
+    pub local_flags: Vec<String>,
+    pub local_labels: Vec<String>,
+    /// What the server reported when the conflict was detected.
+    pub server_flags: Vec<String>,
+    pub server_labels: Vec<String>,
+    pub detected_at: i64,
+}
+
+/// How `otto conflicts` settles held ops.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Release the ops for replay; the local state overwrites the server on the next pass.
+    KeepLocal,
+    /// Store the server's flags and labels locally and drop the ops.
+    KeepServer,
+}
 
 #[derive(Debug, Clone)]
 pub struct PendingOp {
@@ -10,6 +256,11 @@ pub struct PendingOp {
     pub target: String,
     pub payload: Option<String>,
     pub created_at: i64,
+    /// Replays the server rejected so far.
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    /// When the op was dead-lettered (see `MAX_OP_ATTEMPTS`).
+    pub dead_at: Option<i64>,
 }
 
 pub async fn ensure_ops_table(pool: &SqlitePool) -> Result<()> {
@@ -29,6 +280,28 @@ pub async fn ensure_ops_table(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await
     .context("creating pending_ops table")?;
+
+    // Migration: Add conflict column (server flags/labels held against queued ops)
+    let _ = sqlx::query(
+        r#"
+        ALTER TABLE pending_ops ADD COLUMN conflict TEXT;
+        "#,
+    )
+    .execute(pool)
+    .await;
+    // Ignore errors (column might already exist)
+
+    // Migration: Add retry bookkeeping (rejected replays, last error, dead-letter time)
+    for column in [
+        "attempts INTEGER NOT NULL DEFAULT 0",
+        "last_error TEXT",
+        "dead_at INTEGER",
+    ] {
+        let _ = sqlx::query(&format!("ALTER TABLE pending_ops ADD COLUMN {};", column))
+            .execute(pool)
+            .await;
+        // Ignore errors (column might already exist)
+    }
     Ok(())
 }
 
@@ -56,13 +329,41 @@ pub async fn enqueue_op(
     Ok(())
 }
 
+/// Queues one op per `(target, payload)` on an existing connection/transaction so callers can
+/// pair the queue insert with the optimistic local update.
+pub async fn enqueue_ops(
+    conn: &mut SqliteConnection,
+    account_id: &str,
+    kind: &str,
+    targets: &[(String, Option<String>)],
+) -> Result<()> {
+    let now = Utc::now().timestamp();
+    for (target, payload) in targets {
+        sqlx::query(
+            r#"
+            INSERT INTO pending_ops (account_id, kind, target, payload, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5);
+            "#,
+        )
+        .bind(account_id)
+        .bind(kind)
+        .bind(target)
+        .bind(payload)
+        .bind(now)
+        .execute(&mut *conn)
+        .await
+        .context("enqueue pending op")?;
+    }
+    Ok(())
+}
+
 pub async fn list_ops(pool: &SqlitePool, account_id: &str) -> Result<Vec<PendingOp>> {
     let rows = sqlx::query(
         r#"
-        SELECT id, account_id, kind, target, payload, created_at
+        SELECT id, account_id, kind, target, payload, created_at, attempts, last_error, dead_at
         FROM pending_ops
         WHERE account_id = ?1
-        ORDER BY created_at ASC;
+        ORDER BY created_at ASC, id ASC;
         "#,
     )
     .bind(account_id)
@@ -79,6 +380,9 @@ pub async fn list_ops(pool: &SqlitePool, account_id: &str) -> Result<Vec<Pending
             target: row.get(3),
             payload: row.get(4),
             created_at: row.get(5),
+            attempts: row.get(6),
+            last_error: row.get(7),
+            dead_at: row.get(8),
         });
     }
     Ok(ops)
@@ -101,3 +405,496 @@ pub async fn clear_op(pool: &SqlitePool, id: i64) -> Result<()> {
         .context("clear pending op")?;
     Ok(())
 }
+
+/// Queued flag ops whose payload points at `folder` and one of `uids`.
+pub async fn load_pending_flag_ops(
+    pool: &SqlitePool,
+    account_id: &str,
+    folder: &str,
+    uids: &[u32],
+) -> Result<Vec<PendingFlagOp>> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, kind, json_extract(payload, '$.uid'), json_extract(payload, '$.label'), \
+         json_extract(payload, '$.flags'), json_extract(payload, '$.labels') \
+         FROM pending_ops WHERE account_id = ",
+    );
+    qb.push_bind(account_id);
+    push_kinds(&mut qb, "kind", FLAG_OP_KINDS);
+    qb.push(" AND json_extract(payload, '$.folder') = ");
+    qb.push_bind(folder);
+    qb.push(" AND json_extract(payload, '$.uid') IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for uid in uids {
+            separated.push_bind(*uid as i64);
+        }
+    }
+    qb.push(") ORDER BY id ASC");
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("loading pending flag ops")?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let kind: String = row.get(1);
+            let uid: i64 = row.get(2);
+            let op = MessageOp::from_kind(&kind, row.get(3))?;
+            let list = |idx: usize| -> Option<Vec<String>> {
+                serde_json::from_str(&row.get::<Option<String>, _>(idx)?).ok()
+            };
+            Some(PendingFlagOp {
+                id: row.get(0),
+                uid: uid as u32,
+                op,
+                snapshot: list(4).zip(list(5)),
+            })
+        })
+        .collect())
+}
+
+pub async fn clear_ops(pool: &SqlitePool, ids: &[i64]) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new("DELETE FROM pending_ops WHERE id IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+    }
+    qb.push(")");
+    let res = qb
+        .build()
+        .execute(pool)
+        .await
+        .context("clearing pending ops")?;
+    Ok(res.rows_affected())
+}
+
+/// Gives the messages moved by ops `links` (op id, destination uid) the uids the server's
+/// `COPYUID` named in `folder`, so later ops on them replay without waiting for sync. Skipped
+/// when the cached UIDVALIDITY of `folder` differs from `uid_validity`, for rows that already
+/// have a uid, and where another cached message holds the uid. Returns the rows linked.
+pub async fn link_moved(
+    pool: &SqlitePool,
+    account_id: &str,
+    folder: &str,
+    uid_validity: u32,
+    links: &[(i64, u32)],
+) -> Result<u64> {
+    let now = Utc::now().timestamp();
+    let mut tx = pool.begin().await.context("beginning link tx")?;
+    let mut linked = 0;
+    for (op_id, uid) in links {
+        let res = sqlx::query(
+            r#"
+            UPDATE messages SET uid = ?1, updated_at = ?2
+            WHERE account_id = ?3 AND folder = ?4 AND uid IS NULL
+              AND id = (SELECT target FROM pending_ops WHERE id = ?5 AND account_id = ?3)
+              AND EXISTS (
+                  SELECT 1 FROM folders
+                  WHERE account_id = ?3 AND name = ?4 AND uidvalidity = ?6
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM messages WHERE account_id = ?3 AND folder = ?4 AND uid = ?1
+              );
+            "#,
+        )
+        .bind(i64::from(*uid))
+        .bind(now)
+        .bind(account_id)
+        .bind(folder)
+        .bind(op_id)
+        .bind(i64::from(uid_validity))
+        .execute(&mut *tx)
+        .await
+        .context("linking moved message")?;
+        linked += res.rows_affected();
+    }
+    tx.commit().await.context("committing link tx")?;
+    Ok(linked)
+}
+
+/// Appends ` AND <column> IN (<kinds>)`.
+fn push_kinds(qb: &mut QueryBuilder<'_, Sqlite>, column: &str, kinds: &[&'static str]) {
+    qb.push(format!(" AND {} IN (", column));
+    {
+        let mut separated = qb.separated(", ");
+        for kind in kinds {
+            separated.push_bind(*kind);
+        }
+    }
+    qb.push(")");
+}
+
+/// A queued op with the location to replay it at. `uid` is `None` while a moved or archived
+/// copy waits for sync to re-link it.
+#[derive(Debug, Clone)]
+pub struct ReplayOp {
+    pub id: i64,
+    pub op: MessageOp,
+    pub folder: Option<String>,
+    pub uid: Option<u32>,
+}
+
+/// Every queued flag op for the account, oldest first, at the message's current location (the
+/// payload's pre-op folder/uid when the row is gone).
+pub async fn load_replayable_flag_ops(
+    pool: &SqlitePool,
+    account_id: &str,
+) -> Result<Vec<ReplayOp>> {
+    load_replay_ops(
+        pool,
+        account_id,
+        FLAG_OP_KINDS,
+        "CASE WHEN m.id IS NULL THEN json_extract(p.payload, '$.folder') ELSE m.folder END, \
+         CASE WHEN m.id IS NULL THEN json_extract(p.payload, '$.uid') ELSE m.uid END",
+    )
+    .await
+}
+
+/// Every queued archive/move/copy/delete op for the account, oldest first, at the folder/uid
+/// the message had when it was queued. An op queued while the message had no uid uses the
+/// row's current location once sync has re-linked it.
+pub async fn load_replayable_location_ops(
+    pool: &SqlitePool,
+    account_id: &str,
+) -> Result<Vec<ReplayOp>> {
+    load_replay_ops(
+        pool,
+        account_id,
+        LOCATION_OP_KINDS,
+        "CASE WHEN json_extract(p.payload, '$.uid') IS NULL AND m.uid IS NOT NULL \
+              THEN m.folder ELSE json_extract(p.payload, '$.folder') END, \
+         COALESCE(json_extract(p.payload, '$.uid'), m.uid)",
+    )
+    .await
+}
+
+/// `location_columns` selects the folder and uid to replay at.
+async fn load_replay_ops(
+    pool: &SqlitePool,
+    account_id: &str,
+    kinds: &[&'static str],
+    location_columns: &str,
+) -> Result<Vec<ReplayOp>> {
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(format!(
+        "SELECT p.id, p.kind, \
+         COALESCE(json_extract(p.payload, '$.label'), json_extract(p.payload, '$.dest')), {} \
+         FROM pending_ops p \
+         LEFT JOIN messages m ON m.id = p.target AND m.account_id = p.account_id \
+         WHERE p.account_id = ",
+        location_columns
+    ));
+    qb.push_bind(account_id);
+    push_kinds(&mut qb, "p.kind", kinds);
+    qb.push(" AND p.conflict IS NULL AND p.dead_at IS NULL ORDER BY p.id ASC");
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("loading replayable ops")?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let kind: String = row.get(1);
+            let op = MessageOp::from_kind(&kind, row.get(2))?;
+            let uid: Option<i64> = row.get(4);
+            Some(ReplayOp {
+                id: row.get(0),
+                op,
+                folder: row.get(3),
+                uid: uid.map(|u| u as u32),
+            })
+        })
+        .collect())
+}
+
+/// Undoes the optimistic local effect of ops the server rejected by restoring each message's
+/// pre-op folder, uid, flags and labels from the payload, then drops the ops.
+pub async fn rollback_ops(conn: &mut SqliteConnection, ids: &[i64]) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let mut qb: QueryBuilder<Sqlite> =
+        QueryBuilder::new("SELECT id, account_id, target, payload FROM pending_ops WHERE id IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+    }
+    qb.push(")");
+    let rows = qb
+        .build()
+        .fetch_all(&mut *conn)
+        .await
+        .context("loading ops to roll back")?;
+
+    let mut restored = 0;
+    for row in &rows {
+        let id: i64 = row.get(0);
+        let account_id: String = row.get(1);
+        let target: String = row.get(2);
+        let payload: serde_json::Value = row
+            .get::<Option<String>, _>(3)
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or_default();
+        // Ops queued before snapshots were recorded only restore the location.
+        let json = |key: &str| {
+            payload
+                .get(key)
+                .filter(|v| !v.is_null())
+                .map(|v| v.to_string())
+        };
+        if let Some(folder) = payload.get("folder").and_then(|v| v.as_str()) {
+            let res = sqlx::query(
+                r#"
+                UPDATE messages
+                SET folder = ?1, uid = ?2, flags = COALESCE(?3, flags),
+                    labels = COALESCE(?4, labels), updated_at = ?5
+                WHERE id = ?6 AND account_id = ?7;
+                "#,
+            )
+            .bind(folder)
+            .bind(payload.get("uid").and_then(|v| v.as_i64()))
+            .bind(json("flags"))
+            .bind(json("labels"))
+            .bind(Utc::now().timestamp())
+            .bind(&target)
+            .bind(&account_id)
+            .execute(&mut *conn)
+            .await
+            .context("restoring message after rejected op")?;
+            restored += res.rows_affected();
+        }
+        sqlx::query("DELETE FROM pending_ops WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .context("clearing rolled back op")?;
+    }
+    Ok(restored)
+}
+
+/// Counts a server rejection of `ids` with its `error`; ops reaching `MAX_OP_ATTEMPTS` are
+/// dead-lettered. Returns how many of `ids` are dead now.
+pub async fn record_failures(pool: &SqlitePool, ids: &[i64], error: &str) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let mut qb: QueryBuilder<Sqlite> =
+        QueryBuilder::new("UPDATE pending_ops SET attempts = attempts + 1, last_error = ");
+    qb.push_bind(error);
+    qb.push(", dead_at = CASE WHEN attempts + 1 >= ");
+    qb.push_bind(MAX_OP_ATTEMPTS);
+    qb.push(" THEN ");
+    qb.push_bind(Utc::now().timestamp());
+    qb.push(" ELSE NULL END WHERE dead_at IS NULL AND id IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+    }
+    qb.push(")");
+    qb.build()
+        .execute(pool)
+        .await
+        .context("recording op failures")?;
+
+    let mut qb: QueryBuilder<Sqlite> =
+        QueryBuilder::new("SELECT COUNT(*) FROM pending_ops WHERE dead_at IS NOT NULL AND id IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+    }
+    qb.push(")");
+    let row = qb
+        .build()
+        .fetch_one(pool)
+        .await
+        .context("counting dead ops")?;
+    Ok(row.get::<i64, _>(0) as u64)
+}
+
+/// Releases dead-lettered `ids` for the next replay with a fresh attempt count.
+pub async fn retry_dead(pool: &SqlitePool, ids: &[i64]) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "UPDATE pending_ops SET attempts = 0, last_error = NULL, dead_at = NULL \
+         WHERE dead_at IS NOT NULL AND id IN (",
+    );
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+    }
+    qb.push(")");
+    let res = qb
+        .build()
+        .execute(pool)
+        .await
+        .context("releasing dead ops")?;
+    Ok(res.rows_affected())
+}
+
+/// Holds `ids` back from replay, recording the server's flags and labels for the message.
+pub async fn mark_conflicts(
+    pool: &SqlitePool,
+    ids: &[i64],
+    server_flags: &[String],
+    server_labels: &[String],
+) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let conflict = serde_json::json!({
+        "flags": server_flags,
+        "labels": server_labels,
+        "detected_at": Utc::now().timestamp(),
+    })
+    .to_string();
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new("UPDATE pending_ops SET conflict = ");
+    qb.push_bind(conflict);
+    qb.push(" WHERE id IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+    }
+    qb.push(")");
+    let res = qb
+        .build()
+        .execute(pool)
+        .await
+        .context("marking op conflicts")?;
+    Ok(res.rows_affected())
+}
+
+/// Every held op for the account, oldest first, with its message's current state. `subject`
+/// is returned as stored (sealed when the account encrypts columns).
+pub async fn list_conflicts(pool: &SqlitePool, account_id: &str) -> Result<Vec<OpConflict>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT p.id, p.target, p.kind,
+               COALESCE(json_extract(p.payload, '$.label'), json_extract(p.payload, '$.dest')),
+               p.conflict, m.folder, m.uid, m.subject, m.flags, m.labels
+        FROM pending_ops p
+        LEFT JOIN messages m ON m.id = p.target AND m.account_id = p.account_id
+        WHERE p.account_id = ?1 AND p.conflict IS NOT NULL
+        ORDER BY p.id ASC;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("listing op conflicts")?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let kind: String = row.get(2);
+            let op = MessageOp::from_kind(&kind, row.get(3))?;
+            let conflict: serde_json::Value =
+                serde_json::from_str(&row.get::<String, _>(4)).unwrap_or_default();
+            let conflict_list = |key: &str| -> Vec<String> {
+                serde_json::from_value(conflict[key].clone()).unwrap_or_default()
+            };
+            let local_list = |idx: usize| -> Vec<String> {
+                row.get::<Option<String>, _>(idx)
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default()
+            };
+            Some(OpConflict {
+                id: row.get(0),
+                message_id: row.get(1),
+                op,
+                folder: row.get(5),
+                uid: row.get::<Option<i64>, _>(6).map(|uid| uid as u32),
+                subject: row.get(7),
+                local_flags: local_list(8),
+                local_labels: local_list(9),
+                server_flags: conflict_list("flags"),
+                server_labels: conflict_list("labels"),
+                detected_at: conflict["detected_at"].as_i64().unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// Settles held ops: `KeepLocal` releases them for the next replay, `KeepServer` stores the
+/// recorded server flags and labels on their messages and drops them. Returns ops settled.
+pub async fn resolve_conflicts(
+    conn: &mut SqliteConnection,
+    ids: &[i64],
+    resolution: ConflictResolution,
+) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, account_id, target, conflict FROM pending_ops \
+         WHERE conflict IS NOT NULL AND id IN (",
+    );
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+    }
+    qb.push(")");
+    let rows = qb
+        .build()
+        .fetch_all(&mut *conn)
+        .await
+        .context("loading op conflicts")?;
+
+    for row in &rows {
+        let id: i64 = row.get(0);
+        if resolution == ConflictResolution::KeepLocal {
+            sqlx::query("UPDATE pending_ops SET conflict = NULL WHERE id = ?1")
+                .bind(id)
+                .execute(&mut *conn)
+                .await
+                .context("releasing held op")?;
+            continue;
+        }
+        let conflict: serde_json::Value =
+            serde_json::from_str(&row.get::<String, _>(3)).unwrap_or_default();
+        sqlx::query(
+            r#"
+            UPDATE messages SET flags = ?1, labels = ?2, updated_at = ?3
+            WHERE id = ?4 AND account_id = ?5;
+            "#,
+        )
+        .bind(conflict["flags"].to_string())
+        .bind(conflict["labels"].to_string())
+        .bind(Utc::now().timestamp())
+        .bind(row.get::<String, _>(2))
+        .bind(row.get::<String, _>(1))
+        .execute(&mut *conn)
+        .await
+        .context("storing server flags for conflict")?;
+        sqlx::query("DELETE FROM pending_ops WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .context("dropping held op")?;
+    }
+    Ok(rows.len() as u64)
+}
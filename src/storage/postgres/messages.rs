@@ -0,0 +1,528 @@
+//! Write paths of the Postgres store that run inside a caller's transaction: message and body
+//! upserts, JWZ thread assignment, recipients and the local half of message ops. The SQL
+//! follows the SQLite versions in `storage::db`, `storage::threads` and `storage::addresses`.
+use std::collections::{BTreeSet, HashSet};
+
+use anyhow::{Context, Result};
+use sqlx::{PgConnection, Row};
+
+use crate::storage::addresses;
+use crate::storage::blobs;
+use crate::storage::compression::{self, RawFormat};
+use crate::storage::crypto::ColumnCipher;
+use crate::storage::db::{
+    FolderStateUpdate, LocalState, MessageLocationUpdate, body_status_to_str, plan_local_op,
+};
+use crate::storage::ops::MessageOp;
+use crate::threading::{Threader, thread_id_for_root};
+use crate::types::{
+    BodyRecord, FolderRole, MailboxInfo, MessageRecord, now_ts, special_use_folder,
+};
+
+use super::records;
+
+/// Upper bound on ancestor lookups per message; real reply chains are far shorter.
+const MAX_ANCESTOR_ROUNDS: usize = 64;
+
+/// Upserts `body` (plaintext), compressed and then sealed under `cipher`. Content-addressed
+/// bodies keep their raw bytes once per hash in `blobs`.
+pub(super) async fn upsert_body_in(
+    conn: &mut PgConnection,
+    cipher: Option<&ColumnCipher>,
+    body: &BodyRecord,
+    content_addressed: bool,
+) -> Result<()> {
+    let (packed, format) = match body.raw_rfc822.as_deref() {
+        Some(plain) => {
+            let (packed, format) = compression::compress(plain)?;
+            (Some(packed), format)
+        }
+        None => (None, RawFormat::Plain),
+    };
+    let packed = BodyRecord {
+        raw_rfc822: packed,
+        ..body.clone()
+    };
+    let sealed;
+    let stored = match cipher {
+        Some(cipher) => {
+            sealed = cipher.seal_body(&packed)?;
+            &sealed
+        }
+        None => &packed,
+    };
+    let blob_hash = match (body.raw_rfc822.as_deref(), stored.raw_rfc822.as_deref()) {
+        (Some(plain), Some(data)) if content_addressed => {
+            let hash = blobs::content_hash(cipher, plain);
+            put_blob(&mut *conn, &hash, data, format).await?;
+            Some(hash)
+        }
+        _ => None,
+    };
+    let (raw, raw_format) = match blob_hash {
+        Some(_) => (None, RawFormat::Plain),
+        None => (stored.raw_rfc822.as_deref(), format),
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO bodies (message_id, raw_rfc822, blob_hash, sanitized_text, mime_summary, attachments_json, sanitized_at, sanitizer_version, raw_format, degraded)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (message_id) DO UPDATE SET
+            raw_rfc822 = excluded.raw_rfc822,
+            blob_hash = excluded.blob_hash,
+            sanitized_text = excluded.sanitized_text,
+            mime_summary = excluded.mime_summary,
+            attachments_json = excluded.attachments_json,
+            sanitized_at = excluded.sanitized_at,
+            sanitizer_version = excluded.sanitizer_version,
+            raw_format = excluded.raw_format,
+            degraded = excluded.degraded;
+        "#,
+    )
+    .bind(&stored.message_id)
+    .bind(raw)
+    .bind(&blob_hash)
+    .bind(&stored.sanitized_text)
+    .bind(&stored.mime_summary)
+    .bind(&stored.attachments_json)
+    .bind(stored.sanitized_at)
+    .bind(stored.sanitizer_version)
+    .bind(raw_format.as_i64())
+    .bind(i64::from(stored.degraded))
+    .execute(&mut *conn)
+    .await
+    .context("upserting body")?;
+    Ok(())
+}
+
+/// Stores `data` (compressed as `format`, then sealed) under `hash` unless that blob exists.
+pub(super) async fn put_blob(
+    conn: &mut PgConnection,
+    hash: &str,
+    data: &[u8],
+    format: RawFormat,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO blobs (hash, data, size_bytes, created_at, format)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (hash) DO NOTHING;
+        "#,
+    )
+    .bind(hash)
+    .bind(data)
+    .bind(data.len() as i64)
+    .bind(now_ts())
+    .bind(format.as_i64())
+    .execute(&mut *conn)
+    .await
+    .context("storing blob")?;
+    Ok(())
+}
+
+/// Shared by folder and backfill commits: upserts messages + bodies and applies location
+/// updates for messages already cached under another folder.
+pub(super) async fn write_messages_in_tx(
+    conn: &mut PgConnection,
+    account_id: &str,
+    cipher: Option<&ColumnCipher>,
+    content_addressed: bool,
+    messages: &[MessageRecord],
+    bodies: &[BodyRecord],
+    location_updates: &[MessageLocationUpdate],
+) -> Result<()> {
+    let now = now_ts();
+    let mut relinked = HashSet::new();
+
+    for message in messages {
+        // Without a server-stable id, a copy already cached from another folder is relinked
+        // here rather than stored twice.
+        if let Some(existing) = find_cross_folder_copy(&mut *conn, account_id, message).await? {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET folder = $1, uid = $2, flags = $3, labels = $4, updated_at = $5
+                WHERE account_id = $6 AND id = $7;
+                "#,
+            )
+            .bind(&message.folder)
+            .bind(message.uid.map(i64::from))
+            .bind(serde_json::to_string(&message.flags).unwrap_or_else(|_| "[]".into()))
+            .bind(serde_json::to_string(&message.labels).unwrap_or_else(|_| "[]".into()))
+            .bind(now)
+            .bind(account_id)
+            .bind(&existing)
+            .execute(&mut *conn)
+            .await
+            .context("relinking duplicate message")?;
+            relinked.insert(message.id.as_str());
+            continue;
+        }
+
+        // X-GM-THRID wins; otherwise thread on Message-ID/References.
+        let thread_id = match &message.thread_id {
+            Some(thread_id) => Some(thread_id.clone()),
+            None => assign_thread(&mut *conn, account_id, message).await?,
+        };
+
+        let recipients = addresses::recipients_of(message);
+        let sealed;
+        let message = match cipher {
+            Some(cipher) => {
+                sealed = cipher.seal_message(message)?;
+                &sealed
+            }
+            None => message,
+        };
+        // A header-only insert never downgrades a row whose body is already stored.
+        sqlx::query(
+            r#"
+            INSERT INTO messages (
+                id, account_id, folder, uid, thread_id, internal_date,
+                subject, from_addr, to_addrs, cc_addrs, bcc_addrs,
+                flags, labels, has_attachments, size_bytes, raw_hash,
+                created_at, updated_at, body_status, from_name, message_id_header
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21)
+            ON CONFLICT (id) DO UPDATE SET
+                account_id = excluded.account_id,
+                folder = excluded.folder,
+                uid = excluded.uid,
+                thread_id = excluded.thread_id,
+                internal_date = excluded.internal_date,
+                subject = excluded.subject,
+                from_addr = excluded.from_addr,
+                to_addrs = excluded.to_addrs,
+                cc_addrs = excluded.cc_addrs,
+                bcc_addrs = excluded.bcc_addrs,
+                flags = excluded.flags,
+                labels = excluded.labels,
+                has_attachments = CASE WHEN excluded.body_status = 'pending'
+                    OR (excluded.body_status = 'too_large' AND messages.body_status = 'full')
+                    THEN messages.has_attachments ELSE excluded.has_attachments END,
+                size_bytes = excluded.size_bytes,
+                raw_hash = COALESCE(excluded.raw_hash, messages.raw_hash),
+                created_at = messages.created_at,
+                updated_at = excluded.updated_at,
+                body_status = CASE WHEN excluded.body_status = 'pending'
+                    OR (excluded.body_status = 'too_large' AND messages.body_status = 'full')
+                    THEN messages.body_status ELSE excluded.body_status END,
+                from_name = excluded.from_name,
+                message_id_header = COALESCE(excluded.message_id_header, messages.message_id_header);
+            "#,
+        )
+        .bind(&message.id)
+        .bind(&message.account_id)
+        .bind(&message.folder)
+        .bind(message.uid.map(i64::from))
+        .bind(&thread_id)
+        .bind(message.internal_date)
+        .bind(&message.subject)
+        .bind(&message.from)
+        .bind(&message.to)
+        .bind(&message.cc)
+        .bind(&message.bcc)
+        .bind(serde_json::to_string(&message.flags).unwrap_or_else(|_| "[]".into()))
+        .bind(serde_json::to_string(&message.labels).unwrap_or_else(|_| "[]".into()))
+        .bind(i64::from(message.has_attachments))
+        .bind(message.size_bytes.map(i64::from))
+        .bind(&message.raw_hash)
+        .bind(message.created_at)
+        .bind(message.updated_at)
+        .bind(body_status_to_str(message.body_status))
+        .bind(&message.from_name)
+        .bind(&message.message_id_header)
+        .execute(&mut *conn)
+        .await
+        .context("upserting message in tx")?;
+        records::replace_recipients(&mut *conn, &message.id, &recipients).await?;
+    }
+
+    for body in bodies {
+        if relinked.contains(body.message_id.as_str()) {
+            continue;
+        }
+        upsert_body_in(&mut *conn, cipher, body, content_addressed).await?;
+    }
+
+    for (message_id, folder, uid, flags, labels, thread_id, internal_date, size_bytes) in
+        location_updates
+    {
+        sqlx::query(
+            r#"
+            UPDATE messages
+            SET folder = $1,
+                uid = $2,
+                flags = $3,
+                labels = $4,
+                thread_id = COALESCE($5, thread_id),
+                internal_date = $6,
+                size_bytes = $7,
+                updated_at = $8
+            WHERE account_id = $9 AND id = $10;
+            "#,
+        )
+        .bind(folder)
+        .bind(i64::from(*uid))
+        .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
+        .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
+        .bind(thread_id)
+        .bind(internal_date)
+        .bind(size_bytes.map(i64::from))
+        .bind(now)
+        .bind(account_id)
+        .bind(message_id)
+        .execute(&mut *conn)
+        .await
+        .context("updating message location in tx")?;
+    }
+
+    Ok(())
+}
+
+/// Id of a row in another folder sharing `message`'s `Message-ID`, for messages keyed by the
+/// `account:folder:uid` fallback (no X-GM-MSGID).
+async fn find_cross_folder_copy(
+    conn: &mut PgConnection,
+    account_id: &str,
+    message: &MessageRecord,
+) -> Result<Option<String>> {
+    let Some(header) = message.message_id_header.as_deref() else {
+        return Ok(None);
+    };
+    if !message.id.contains(':') {
+        return Ok(None);
+    }
+    sqlx::query_scalar(
+        r#"
+        SELECT id FROM messages
+        WHERE account_id = $1 AND message_id_header = $2 AND folder <> $3 AND id <> $4
+        LIMIT 1;
+        "#,
+    )
+    .bind(account_id)
+    .bind(header)
+    .bind(&message.folder)
+    .bind(&message.id)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("looking up message by Message-ID")
+}
+
+/// Upserts the folder's sync state columns (everything `FolderStateUpdate` carries).
+pub(super) async fn upsert_folder_row(
+    conn: &mut PgConnection,
+    account_id: &str,
+    name: &str,
+    update: &FolderStateUpdate,
+) -> Result<()> {
+    let now = now_ts();
+    sqlx::query(
+        r#"
+        INSERT INTO folders (
+            account_id, name, uidvalidity, highest_uid, highestmodseq,
+            exists_count, last_sync_ts, last_uid_scan_ts, baseline_scan_uid,
+            created_at, updated_at, resume_modseq, resume_uid, checkpoint_from_uid
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+        ON CONFLICT (account_id, name) DO UPDATE SET
+            uidvalidity = excluded.uidvalidity,
+            highest_uid = excluded.highest_uid,
+            highestmodseq = excluded.highestmodseq,
+            exists_count = excluded.exists_count,
+            last_sync_ts = excluded.last_sync_ts,
+            last_uid_scan_ts = excluded.last_uid_scan_ts,
+            baseline_scan_uid = excluded.baseline_scan_uid,
+            updated_at = excluded.updated_at,
+            resume_modseq = excluded.resume_modseq,
+            resume_uid = excluded.resume_uid,
+            checkpoint_from_uid = excluded.checkpoint_from_uid;
+        "#,
+    )
+    .bind(account_id)
+    .bind(name)
+    .bind(update.uidvalidity.map(i64::from))
+    .bind(update.highest_uid.map(i64::from))
+    .bind(update.highestmodseq.map(|v| v as i64))
+    .bind(update.exists_count.map(i64::from))
+    .bind(update.last_sync_ts)
+    .bind(update.last_uid_scan_ts)
+    .bind(update.baseline_scan_uid.map(i64::from))
+    .bind(now)
+    .bind(now)
+    .bind(update.resume_modseq.map(|v| v as i64))
+    .bind(update.resume_uid.map(i64::from))
+    .bind(update.checkpoint_from_uid.map(i64::from))
+    .execute(&mut *conn)
+    .await
+    .context("upserting folder state")?;
+    Ok(())
+}
+
+pub(super) async fn role_folder_in(
+    conn: &mut PgConnection,
+    account_id: &str,
+    role: FolderRole,
+) -> Result<String> {
+    let rows = sqlx::query(
+        "SELECT name, attributes FROM folders WHERE account_id = $1 AND attributes IS NOT NULL",
+    )
+    .bind(account_id)
+    .fetch_all(&mut *conn)
+    .await
+    .context("loading special-use folders")?;
+    let mailboxes: Vec<MailboxInfo> = rows
+        .into_iter()
+        .map(|row| MailboxInfo {
+            name: row.get(0),
+            delimiter: None,
+            attributes: serde_json::from_str(&row.get::<String, _>(1)).unwrap_or_default(),
+        })
+        .collect();
+    Ok(special_use_folder(&mailboxes, role)
+        .unwrap_or(role.gmail_default())
+        .to_string())
+}
+
+/// Local half of a `MessageOp` (see `db::plan_local_op`); returns one `(message_id, payload)`
+/// per message found.
+pub(super) async fn apply_message_op_in_tx(
+    conn: &mut PgConnection,
+    account_id: &str,
+    op: &MessageOp,
+    message_ids: &[String],
+) -> Result<Vec<(String, Option<String>)>> {
+    let rows = sqlx::query(
+        "SELECT id, folder, uid, flags, labels FROM messages WHERE account_id = $1 AND id = ANY($2)",
+    )
+    .bind(account_id)
+    .bind(message_ids)
+    .fetch_all(&mut *conn)
+    .await
+    .context("loading messages for op")?;
+
+    let archive = role_folder_in(&mut *conn, account_id, FolderRole::All).await?;
+    let trash = role_folder_in(&mut *conn, account_id, FolderRole::Trash).await?;
+    let now = now_ts();
+    let mut queued = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let id: String = row.get(0);
+        let (payload, local) = plan_local_op(
+            op,
+            &archive,
+            &trash,
+            LocalState {
+                folder: row.get(1),
+                uid: row.get(2),
+                flags: serde_json::from_str(&row.get::<String, _>(3)).unwrap_or_default(),
+                labels: serde_json::from_str(&row.get::<String, _>(4)).unwrap_or_default(),
+            },
+        );
+        queued.push((id.clone(), Some(payload)));
+
+        sqlx::query(
+            r#"
+            UPDATE messages
+            SET folder = $1, uid = $2, flags = $3, labels = $4, updated_at = $5
+            WHERE id = $6;
+            "#,
+        )
+        .bind(&local.folder)
+        .bind(local.uid)
+        .bind(serde_json::to_string(&local.flags).unwrap_or_else(|_| "[]".into()))
+        .bind(serde_json::to_string(&local.labels).unwrap_or_else(|_| "[]".into()))
+        .bind(now)
+        .bind(&id)
+        .execute(&mut *conn)
+        .await
+        .context("updating message for op")?;
+    }
+
+    Ok(queued)
+}
+
+/// Links `message` into the account's container graph and returns its thread id (see
+/// `storage::threads::assign_thread`).
+async fn assign_thread(
+    conn: &mut PgConnection,
+    account_id: &str,
+    message: &MessageRecord,
+) -> Result<Option<String>> {
+    let Some(own) = message.message_id_header.as_deref() else {
+        return Ok(None);
+    };
+
+    let mut threader = Threader::default();
+    let mut thread_ids = BTreeSet::new();
+    let mut wanted: Vec<String> = message.references.clone();
+    wanted.push(own.to_string());
+    let mut loaded = BTreeSet::new();
+    for _ in 0..MAX_ANCESTOR_ROUNDS {
+        wanted.retain(|id| !loaded.contains(id));
+        if wanted.is_empty() {
+            break;
+        }
+        let rows = sqlx::query(
+            r#"
+            SELECT message_id_header, parent, thread_id FROM threads
+            WHERE account_id = $1 AND message_id_header = ANY($2);
+            "#,
+        )
+        .bind(account_id)
+        .bind(&wanted)
+        .fetch_all(&mut *conn)
+        .await
+        .context("loading thread containers")?;
+        loaded.extend(wanted.drain(..));
+        for row in rows {
+            let parent: Option<String> = row.get(1);
+            threader.insert(&row.get::<String, _>(0), parent.as_deref());
+            thread_ids.insert(row.get::<String, _>(2));
+            wanted.extend(parent);
+        }
+    }
+
+    threader.add_message(own, &message.references);
+    let thread_id = match thread_ids.pop_first() {
+        Some(kept) => kept,
+        None => thread_id_for_root(threader.root(own)),
+    };
+
+    // The message bridged threads that were separate until now; fold them into one.
+    for merged in &thread_ids {
+        for table in ["threads", "messages"] {
+            sqlx::query(&format!(
+                "UPDATE {} SET thread_id = $1 WHERE account_id = $2 AND thread_id = $3",
+                table
+            ))
+            .bind(&thread_id)
+            .bind(account_id)
+            .bind(merged)
+            .execute(&mut *conn)
+            .await
+            .context("merging threads")?;
+        }
+    }
+
+    for (id, parent) in threader.containers() {
+        sqlx::query(
+            r#"
+            INSERT INTO threads (account_id, message_id_header, parent, thread_id)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (account_id, message_id_header) DO UPDATE SET
+                parent = excluded.parent,
+                thread_id = excluded.thread_id;
+            "#,
+        )
+        .bind(account_id)
+        .bind(id)
+        .bind(parent)
+        .bind(&thread_id)
+        .execute(&mut *conn)
+        .await
+        .context("storing thread container")?;
+    }
+
+    Ok(Some(thread_id))
+}
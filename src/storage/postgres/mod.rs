@@ -0,0 +1,2271 @@
+//! Postgres backend (`OTTO_DATABASE_URL=postgres://…`): one cache shared by several clients,
+//! e.g. a household or team behind the HTTP API. Same tables and semantics as the SQLite store;
+//! blobs always stay in the database, so `hybrid` body storage behaves like `content` here.
+mod messages;
+mod ops;
+mod records;
+mod recovery;
+mod schema;
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{PgPool, Postgres, QueryBuilder, Row};
+
+use crate::storage::addresses::{AddressField, AddressSuggestion, Recipient};
+use crate::storage::audit::AuditRecord;
+use crate::storage::blobs;
+use crate::storage::cleanup::CleanupRun;
+use crate::storage::compression::{self, CompressStats, RawFormat};
+use crate::storage::crypto::ColumnCipher;
+use crate::storage::db::{
+    ACCOUNT_SELECT, FetchedBodyUpdate, FolderStateUpdate, MESSAGE_RECORD_SELECT,
+    MessageLocationUpdate, SYNC_RUN_RETENTION_SECS, account_from_row, message_from_row, open_raw,
+    parse_optional_date, provider_to_str, reseal_text, sync_runs_from_rows,
+};
+use crate::storage::learning::{SenderAction, SuggestionStatus};
+use crate::storage::notes::MessageNote;
+use crate::storage::ops::{
+    ConflictResolution, MessageOp, OpConflict, PendingFlagOp, PendingOp, ReplayOp,
+};
+use crate::storage::recovery::RecoveryReport;
+use crate::storage::reply_later::ReplyLater;
+use crate::storage::store::{BodyStorage, MailStore};
+use crate::storage::translations::MessageTranslation;
+use crate::types::{
+    Account, AccountQuota, BodyRecord, Credential, FetchRetry, FolderCounts, FolderRole,
+    FolderState, MailboxInfo, MessageRecord, ServerIdentity, Signature, SyncRunRecord, now_ts,
+};
+
+#[derive(Clone)]
+pub struct PgStore {
+    pool: PgPool,
+    /// `postgres user@host:port/db` for logs; never the password.
+    location: String,
+    /// Column ciphers for accounts with `encrypt_columns` set; shared by clones.
+    ciphers: Arc<RwLock<HashMap<String, Arc<ColumnCipher>>>>,
+    /// Layout for raw bodies on write; shared by clones.
+    body_storage: Arc<RwLock<BodyStorage>>,
+}
+
+impl PgStore {
+    /// Connects to the server at `url` and creates the schema if missing.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let options = PgConnectOptions::from_str(url).context("parsing Postgres URL")?;
+        let location = format!(
+            "postgres {}@{}:{}/{}",
+            options.get_username(),
+            options.get_host(),
+            options.get_port(),
+            options.get_database().unwrap_or(options.get_username()),
+        );
+        let pool = PgPoolOptions::new()
+            .connect_with(options)
+            .await
+            .with_context(|| format!("connecting to {}", location))?;
+        schema::migrate(&pool).await?;
+        Ok(Self {
+            pool,
+            location,
+            ciphers: Arc::new(RwLock::new(HashMap::new())),
+            body_storage: Arc::new(RwLock::new(BodyStorage::Inline)),
+        })
+    }
+
+    fn cipher_for(&self, account_id: &str) -> Option<Arc<ColumnCipher>> {
+        self.ciphers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(account_id)
+            .cloned()
+    }
+
+    /// Whether new raw bodies go to `blobs` (`content` and `hybrid`).
+    fn content_addressed(&self) -> bool {
+        *self.body_storage.read().unwrap_or_else(|e| e.into_inner()) != BodyStorage::Inline
+    }
+
+    /// The newest `limit` undeleted messages, optionally only those of one thread.
+    async fn load_messages_in(
+        &self,
+        account_id: &str,
+        thread_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
+        let rows = sqlx::query(&format!(
+            "{} WHERE account_id = $1 AND flags NOT LIKE '%\"Deleted\"%' \
+             AND ($3::text IS NULL OR thread_id = $3) \
+             ORDER BY internal_date DESC NULLS LAST LIMIT $2",
+            MESSAGE_RECORD_SELECT
+        ))
+        .bind(account_id)
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .bind(thread_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading messages")?;
+
+        let cipher = self.cipher_for(account_id);
+        let mut out = Vec::new();
+        for row in rows {
+            let msg_id: String = row.get(0);
+            let mut raw_format = RawFormat::Plain;
+            let mut body = sqlx::query(
+                r#"
+                SELECT b.raw_rfc822, b.sanitized_text, b.mime_summary, b.attachments_json,
+                       b.sanitized_at, b.blob_hash, bl.data, b.sanitizer_version,
+                       CASE WHEN bl.hash IS NULL THEN b.raw_format ELSE bl.format END,
+                       b.degraded
+                FROM bodies b
+                LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+                WHERE b.message_id = $1
+                "#,
+            )
+            .bind(&msg_id)
+            .fetch_optional(&self.pool)
+            .await
+            .context("loading body")?
+            .map(|brow| -> Result<BodyRecord> {
+                let raw_rfc822 = match (brow.get::<Option<String>, _>(5), brow.get(6)) {
+                    (Some(_), Some(data)) => Some(data),
+                    _ => brow.get::<Option<Vec<u8>>, _>(0),
+                };
+                raw_format = RawFormat::from_i64(brow.get(8))?;
+                Ok(BodyRecord {
+                    message_id: msg_id.clone(),
+                    raw_rfc822,
+                    sanitized_text: brow.get(1),
+                    mime_summary: brow.get(2),
+                    attachments_json: brow.get(3),
+                    sanitized_at: brow.get(4),
+                    sanitizer_version: brow.get(7),
+                    degraded: brow.get::<i64, _>(9) != 0,
+                })
+            })
+            .transpose()?;
+
+            let mut message = message_from_row(account_id, &row);
+            if let Some(cipher) = cipher.as_deref() {
+                cipher.open_message(&mut message)?;
+                if let Some(body) = body.as_mut() {
+                    cipher.open_body(body)?;
+                }
+            }
+            if let Some(body) = body.as_mut() {
+                body.raw_rfc822 = body
+                    .raw_rfc822
+                    .take()
+                    .map(|raw| compression::decompress(raw, raw_format))
+                    .transpose()?;
+            }
+            out.push((message, body));
+        }
+        Ok(out)
+    }
+
+    async fn load_message_records(
+        &self,
+        account_id: &str,
+        mut qb: QueryBuilder<'_, Postgres>,
+    ) -> Result<Vec<MessageRecord>> {
+        let rows = qb
+            .build()
+            .fetch_all(&self.pool)
+            .await
+            .context("loading messages")?;
+        let cipher = self.cipher_for(account_id);
+        let mut out = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut message = message_from_row(account_id, &row);
+            if let Some(cipher) = cipher.as_deref() {
+                cipher.open_message(&mut message)?;
+            }
+            out.push(message);
+        }
+        Ok(out)
+    }
+
+    async fn delete_message(&self, message_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("beginning delete tx")?;
+        sqlx::query("DELETE FROM bodies WHERE message_id = $1")
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await
+            .context("deleting body")?;
+        sqlx::query("DELETE FROM messages WHERE id = $1")
+            .bind(message_id)
+            .execute(&mut *tx)
+            .await
+            .context("deleting message")?;
+        tx.commit().await.context("committing delete tx")?;
+        Ok(())
+    }
+
+    async fn record_folder_sync_end(
+        conn: &mut sqlx::PgConnection,
+        account_id: &str,
+        folder: &str,
+        status: &str,
+        last_modseq: Option<u64>,
+        last_uid: Option<u32>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO folder_sync_state (account_id, folder, status, started_at, finished_at, last_modseq, last_uid)
+            VALUES ($1, $2, $3, NULL, $4, $5, $6)
+            ON CONFLICT (account_id, folder) DO UPDATE SET
+                status = excluded.status,
+                finished_at = excluded.finished_at,
+                last_modseq = excluded.last_modseq,
+                last_uid = excluded.last_uid;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(status)
+        .bind(now_ts())
+        .bind(last_modseq.map(|v| v as i64))
+        .bind(last_uid.map(i64::from))
+        .execute(&mut *conn)
+        .await
+        .context("recording folder sync end in tx")?;
+        Ok(())
+    }
+}
+
+/// Decodes a `folders` row selected as `id, name, uidvalidity, …, backfill_since`.
+fn folder_from_row(account_id: &str, row: &sqlx::postgres::PgRow) -> FolderState {
+    FolderState {
+        id: row.get(0),
+        account_id: account_id.to_string(),
+        name: row.get(1),
+        uidvalidity: row.get::<Option<i64>, _>(2).map(|v| v as u32),
+        highest_uid: row.get::<Option<i64>, _>(3).map(|v| v as u32),
+        highestmodseq: row.get::<Option<i64>, _>(4).map(|v| v as u64),
+        exists_count: row.get::<Option<i64>, _>(5).map(|v| v as u32),
+        last_sync_ts: row.get(6),
+        last_uid_scan_ts: row.get(7),
+        baseline_scan_uid: row.get::<Option<i64>, _>(8).map(|v| v as u32),
+        resume_modseq: row.get::<Option<i64>, _>(9).map(|v| v as u64),
+        resume_uid: row.get::<Option<i64>, _>(10).map(|v| v as u32),
+        backfill_since: parse_optional_date(row.get(11)),
+    }
+}
+
+const FOLDER_SELECT: &str = "SELECT id, name, uidvalidity, highest_uid, highestmodseq, exists_count, \
+     last_sync_ts, last_uid_scan_ts, baseline_scan_uid, resume_modseq, resume_uid, backfill_since \
+     FROM folders";
+
+fn uids_to_i64(uids: &[u32]) -> Vec<i64> {
+    uids.iter().map(|uid| i64::from(*uid)).collect()
+}
+
+#[async_trait]
+impl MailStore for PgStore {
+    fn describe(&self) -> String {
+        self.location.clone()
+    }
+
+    fn register_cipher(&self, account_id: &str, cipher: Option<ColumnCipher>) {
+        let mut ciphers = self.ciphers.write().unwrap_or_else(|e| e.into_inner());
+        match cipher {
+            Some(cipher) => {
+                ciphers.insert(account_id.to_string(), Arc::new(cipher));
+            }
+            None => {
+                ciphers.remove(account_id);
+            }
+        }
+    }
+
+    fn set_body_storage(&self, mode: BodyStorage) {
+        *self.body_storage.write().unwrap_or_else(|e| e.into_inner()) = mode;
+    }
+
+    async fn purge_blob_files(&self) -> Result<usize> {
+        // Blobs are never offloaded to files here.
+        Ok(0)
+    }
+
+    async fn recover_interrupted_writes(&self) -> Result<RecoveryReport> {
+        recovery::recover(&self.pool).await
+    }
+
+    async fn list_accounts(&self) -> Result<Vec<Account>> {
+        let rows = sqlx::query(ACCOUNT_SELECT)
+            .fetch_all(&self.pool)
+            .await
+            .context("loading accounts")?;
+        Ok(rows.iter().map(account_from_row).collect())
+    }
+
+    async fn save_account(&self, account: &Account) -> Result<()> {
+        let settings = &account.settings;
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, email, provider, cutoff_since, poll_interval_minutes, prefetch_recent, safe_mode, folders, created_at, updated_at, max_download_bps, folder_policies, encrypt_columns, unread_only, max_message_bytes, smart_folders, all_mail_mode, imap_endpoint, enabled, credential, cleanup_rules, notify_rules, namespace, tls_policy)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24)
+            ON CONFLICT (id) DO UPDATE SET
+                email = excluded.email,
+                provider = excluded.provider,
+                cutoff_since = excluded.cutoff_since,
+                poll_interval_minutes = excluded.poll_interval_minutes,
+                prefetch_recent = excluded.prefetch_recent,
+                safe_mode = excluded.safe_mode,
+                folders = excluded.folders,
+                updated_at = excluded.updated_at,
+                max_download_bps = excluded.max_download_bps,
+                folder_policies = excluded.folder_policies,
+                encrypt_columns = excluded.encrypt_columns,
+                unread_only = excluded.unread_only,
+                max_message_bytes = excluded.max_message_bytes,
+                smart_folders = excluded.smart_folders,
+                all_mail_mode = excluded.all_mail_mode,
+                imap_endpoint = excluded.imap_endpoint,
+                enabled = excluded.enabled,
+                credential = excluded.credential,
+                cleanup_rules = excluded.cleanup_rules,
+                notify_rules = excluded.notify_rules,
+                namespace = excluded.namespace,
+                tls_policy = excluded.tls_policy;
+            "#,
+        )
+        .bind(&account.id)
+        .bind(&account.email)
+        .bind(provider_to_str(&account.provider))
+        .bind(settings.cutoff_since.to_string())
+        .bind(i64::from(settings.poll_interval_minutes))
+        .bind(i64::from(settings.prefetch_recent))
+        .bind(i64::from(settings.safe_mode))
+        .bind(serde_json::to_string(&settings.folders).unwrap_or_else(|_| "[]".into()))
+        .bind(account.created_at)
+        .bind(account.updated_at)
+        .bind(settings.max_download_bytes_per_sec.map(|bps| bps as i64))
+        .bind(serde_json::to_string(&settings.folder_policies).unwrap_or_else(|_| "{}".into()))
+        .bind(i64::from(settings.encrypt_columns))
+        .bind(i64::from(settings.unread_only))
+        .bind(settings.max_message_bytes.map(|bytes| bytes as i64))
+        .bind(serde_json::to_string(&settings.smart_folders).unwrap_or_else(|_| "[]".into()))
+        .bind(i64::from(settings.all_mail_mode))
+        .bind(serde_json::to_string(&settings.imap).unwrap_or_else(|_| "{}".into()))
+        .bind(i64::from(settings.enabled))
+        .bind(match &settings.credential {
+            Credential::OAuth => None,
+            credential => serde_json::to_string(credential).ok(),
+        })
+        .bind(serde_json::to_string(&settings.cleanup_rules).unwrap_or_else(|_| "[]".into()))
+        .bind(serde_json::to_string(&settings.notify_rules).unwrap_or_else(|_| "[]".into()))
+        .bind(
+            settings
+                .namespace
+                .as_ref()
+                .and_then(|namespace| serde_json::to_string(namespace).ok()),
+        )
+        .bind(serde_json::to_string(&settings.tls_policy).unwrap_or_else(|_| "{}".into()))
+        .execute(&self.pool)
+        .await
+        .context("upserting account")?;
+        Ok(())
+    }
+
+    async fn reseal_account(
+        &self,
+        account_id: &str,
+        from: Option<&ColumnCipher>,
+        to: Option<&ColumnCipher>,
+    ) -> Result<usize> {
+        const PAGE: i64 = 200;
+        let mut tx = self.pool.begin().await.context("beginning reseal tx")?;
+        let mut resealed = 0;
+
+        let mut after = String::new();
+        loop {
+            let rows = sqlx::query(
+                r#"
+                SELECT id, subject, from_addr, from_name
+                FROM messages
+                WHERE account_id = $1 AND id > $2
+                ORDER BY id
+                LIMIT $3;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading messages to reseal")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+
+            for row in rows {
+                sqlx::query(
+                    "UPDATE messages SET subject = $1, from_addr = $2, from_name = $3 WHERE id = $4",
+                )
+                .bind(reseal_text(from, to, row.get(1))?)
+                .bind(reseal_text(from, to, row.get(2))?)
+                .bind(reseal_text(from, to, row.get(3))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing message")?;
+                resealed += 1;
+            }
+        }
+
+        let mut after = String::new();
+        loop {
+            let rows = sqlx::query(
+                r#"
+                SELECT b.message_id, b.raw_rfc822, b.sanitized_text, b.blob_hash, bl.data, bl.format
+                FROM bodies b
+                JOIN messages m ON m.id = b.message_id
+                LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+                WHERE m.account_id = $1 AND b.message_id > $2
+                ORDER BY b.message_id
+                LIMIT $3;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading bodies to reseal")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+
+            for row in rows {
+                let reseal = |raw: Vec<u8>| -> Result<(Vec<u8>, Vec<u8>)> {
+                    let plain = match from {
+                        Some(cipher) => cipher.open_bytes(&raw)?,
+                        None => raw,
+                    };
+                    let sealed = match to {
+                        Some(cipher) => cipher.seal_bytes(&plain)?,
+                        None => plain.clone(),
+                    };
+                    Ok((plain, sealed))
+                };
+                let raw = row
+                    .get::<Option<Vec<u8>>, _>(1)
+                    .map(|raw| reseal(raw).map(|(_, sealed)| sealed))
+                    .transpose()?;
+                // Blobs are addressed under the account's key, so a resealed blob moves to a
+                // new hash; the old one is released with its last reference.
+                let blob_hash = row.get::<Option<String>, _>(3);
+                let blob_hash = match (blob_hash, row.get::<Option<Vec<u8>>, _>(4)) {
+                    (Some(_), Some(data)) => {
+                        let format = RawFormat::from_i64(row.get(5))?;
+                        let (packed, sealed) = reseal(data)?;
+                        let plain = compression::decompress(packed, format)?;
+                        let hash = blobs::content_hash(to, &plain);
+                        messages::put_blob(&mut tx, &hash, &sealed, format).await?;
+                        Some(hash)
+                    }
+                    (hash, _) => hash,
+                };
+                sqlx::query(
+                    "UPDATE bodies SET raw_rfc822 = $1, blob_hash = $2, sanitized_text = $3 WHERE message_id = $4",
+                )
+                .bind(raw)
+                .bind(blob_hash)
+                .bind(reseal_text(from, to, row.get(2))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing body")?;
+                resealed += 1;
+            }
+        }
+
+        let rows = sqlx::query("SELECT message_id, note FROM message_notes WHERE account_id = $1")
+            .bind(account_id)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading notes to reseal")?;
+        for row in rows {
+            sqlx::query("UPDATE message_notes SET note = $1 WHERE message_id = $2")
+                .bind(reseal_text(from, to, row.get(1))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing note")?;
+        }
+
+        let rows =
+            sqlx::query("SELECT message_id, summary FROM message_summaries WHERE account_id = $1")
+                .bind(account_id)
+                .fetch_all(&mut *tx)
+                .await
+                .context("loading summaries to reseal")?;
+        for row in rows {
+            sqlx::query("UPDATE message_summaries SET summary = $1 WHERE message_id = $2")
+                .bind(reseal_text(from, to, row.get(1))?)
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("resealing summary")?;
+        }
+
+        let rows = sqlx::query(
+            "SELECT message_id, target, text FROM message_translations WHERE account_id = $1",
+        )
+        .bind(account_id)
+        .fetch_all(&mut *tx)
+        .await
+        .context("loading translations to reseal")?;
+        for row in rows {
+            sqlx::query(
+                "UPDATE message_translations SET text = $1 WHERE message_id = $2 AND target = $3",
+            )
+            .bind(reseal_text(from, to, row.get(2))?)
+            .bind(row.get::<String, _>(0))
+            .bind(row.get::<String, _>(1))
+            .execute(&mut *tx)
+            .await
+            .context("resealing translation")?;
+        }
+
+        // Learned sender counts are keyed by a hash under the column key: rekey them too.
+        let rows = sqlx::query(
+            "SELECT sender_key, action, label, sender FROM sender_actions WHERE account_id = $1",
+        )
+        .bind(account_id)
+        .fetch_all(&mut *tx)
+        .await
+        .context("loading sender actions to reseal")?;
+        for row in rows {
+            let sender = match from {
+                Some(cipher) => cipher.open_text(&row.get::<String, _>(3))?,
+                None => row.get(3),
+            };
+            let stored = match to {
+                Some(cipher) => cipher.seal_text(&sender)?,
+                None => sender.clone(),
+            };
+            sqlx::query(
+                r#"
+                UPDATE sender_actions SET sender_key = $1, sender = $2
+                WHERE account_id = $3 AND sender_key = $4 AND action = $5 AND label = $6;
+                "#,
+            )
+            .bind(blobs::content_hash(to, sender.as_bytes()))
+            .bind(stored)
+            .bind(account_id)
+            .bind(row.get::<String, _>(0))
+            .bind(row.get::<String, _>(1))
+            .bind(row.get::<String, _>(2))
+            .execute(&mut *tx)
+            .await
+            .context("resealing sender action")?;
+        }
+
+        sqlx::query("UPDATE accounts SET encrypt_columns = $1, updated_at = $2 WHERE id = $3")
+            .bind(i64::from(to.is_some()))
+            .bind(now_ts())
+            .bind(account_id)
+            .execute(&mut *tx)
+            .await
+            .context("updating encrypt_columns")?;
+
+        tx.commit().await.context("committing reseal tx")?;
+        Ok(resealed)
+    }
+
+    async fn list_folders(&self, account_id: &str) -> Result<Vec<FolderState>> {
+        let rows = sqlx::query(&format!(
+            "{} WHERE account_id = $1 ORDER BY name ASC",
+            FOLDER_SELECT
+        ))
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading folders")?;
+        Ok(rows
+            .iter()
+            .map(|row| folder_from_row(account_id, row))
+            .collect())
+    }
+
+    async fn record_discovered_folders(
+        &self,
+        account_id: &str,
+        mailboxes: &[MailboxInfo],
+    ) -> Result<()> {
+        let now = now_ts();
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning folder discovery tx")?;
+        sqlx::query("UPDATE folders SET attributes = NULL WHERE account_id = $1")
+            .bind(account_id)
+            .execute(&mut *tx)
+            .await
+            .context("clearing discovered folders")?;
+        for mailbox in mailboxes {
+            sqlx::query(
+                r#"
+                INSERT INTO folders (account_id, name, attributes, delimiter, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $5)
+                ON CONFLICT (account_id, name) DO UPDATE SET
+                    attributes = excluded.attributes,
+                    delimiter = excluded.delimiter,
+                    updated_at = excluded.updated_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&mailbox.name)
+            .bind(serde_json::to_string(&mailbox.attributes).unwrap_or_else(|_| "[]".into()))
+            .bind(&mailbox.delimiter)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .context("storing discovered folder")?;
+        }
+        tx.commit().await.context("committing folder discovery")?;
+        Ok(())
+    }
+
+    async fn list_discovered_folders(&self, account_id: &str) -> Result<Vec<MailboxInfo>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT name, delimiter, attributes FROM folders
+            WHERE account_id = $1 AND attributes IS NOT NULL
+            ORDER BY name ASC;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading discovered folders")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| MailboxInfo {
+                name: row.get(0),
+                delimiter: row.get(1),
+                attributes: serde_json::from_str(&row.get::<String, _>(2)).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    async fn save_folder_counts(&self, account_id: &str, counts: &[FolderCounts]) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning folder counts tx")?;
+        for entry in counts {
+            sqlx::query(
+                r#"
+                INSERT INTO folders (account_id, name, server_unseen, server_messages, counts_checked_at, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $5, $5)
+                ON CONFLICT (account_id, name) DO UPDATE SET
+                    server_unseen = excluded.server_unseen,
+                    server_messages = excluded.server_messages,
+                    counts_checked_at = excluded.counts_checked_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&entry.folder)
+            .bind(i64::from(entry.unseen))
+            .bind(i64::from(entry.messages))
+            .bind(entry.checked_at)
+            .execute(&mut *tx)
+            .await
+            .context("storing folder counts")?;
+        }
+        tx.commit().await.context("committing folder counts")?;
+        Ok(())
+    }
+
+    async fn load_folder_counts(&self, account_id: &str) -> Result<HashMap<String, FolderCounts>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT name, server_unseen, server_messages, counts_checked_at FROM folders
+            WHERE account_id = $1 AND counts_checked_at IS NOT NULL;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading folder counts")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let counts = FolderCounts {
+                    folder: row.get(0),
+                    unseen: row.get::<Option<i64>, _>(1).unwrap_or(0) as u32,
+                    messages: row.get::<Option<i64>, _>(2).unwrap_or(0) as u32,
+                    checked_at: row.get(3),
+                };
+                (counts.folder.clone(), counts)
+            })
+            .collect())
+    }
+
+    async fn role_folder(&self, account_id: &str, role: FolderRole) -> Result<String> {
+        let mut conn = self.pool.acquire().await.context("acquiring connection")?;
+        messages::role_folder_in(&mut conn, account_id, role).await
+    }
+
+    async fn upsert_folder_state(
+        &self,
+        account_id: &str,
+        name: &str,
+        update: &FolderStateUpdate,
+    ) -> Result<FolderState> {
+        let mut conn = self.pool.acquire().await.context("acquiring connection")?;
+        messages::upsert_folder_row(&mut conn, account_id, name, update).await?;
+        let row = sqlx::query(&format!(
+            "{} WHERE account_id = $1 AND name = $2",
+            FOLDER_SELECT
+        ))
+        .bind(account_id)
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await
+        .context("reloading folder")?;
+        Ok(folder_from_row(account_id, &row))
+    }
+
+    async fn record_folder_sync_start(
+        &self,
+        account_id: &str,
+        folder: &str,
+        baseline_modseq: Option<u64>,
+        baseline_uid: Option<u32>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO folder_sync_state (account_id, folder, status, started_at, finished_at, last_modseq, last_uid)
+            VALUES ($1, $2, 'in_progress', $3, NULL, $4, $5)
+            ON CONFLICT (account_id, folder) DO UPDATE SET
+                status = 'in_progress',
+                started_at = excluded.started_at,
+                finished_at = NULL,
+                last_modseq = excluded.last_modseq,
+                last_uid = excluded.last_uid;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(now_ts())
+        .bind(baseline_modseq.map(|v| v as i64))
+        .bind(baseline_uid.map(i64::from))
+        .execute(&self.pool)
+        .await
+        .context("recording folder sync start")?;
+        Ok(())
+    }
+
+    async fn commit_folder_batch(
+        &self,
+        account_id: &str,
+        folder: &str,
+        messages: &[MessageRecord],
+        bodies: &[BodyRecord],
+        location_updates: &[MessageLocationUpdate],
+        flag_updates: &[(u32, Vec<String>, Vec<String>)],
+        folder_update: &FolderStateUpdate,
+        sync_status: &str,
+        last_modseq: Option<u64>,
+        last_uid: Option<u32>,
+    ) -> Result<()> {
+        // Header-only (pending) messages carry no body row, so bodies may be a subset.
+        if bodies.len() > messages.len() {
+            anyhow::bail!("more bodies than messages in folder batch");
+        }
+
+        let mut tx = self.pool.begin().await.context("begin tx")?;
+        let now = now_ts();
+        let cipher = self.cipher_for(account_id);
+        messages::write_messages_in_tx(
+            &mut tx,
+            account_id,
+            cipher.as_deref(),
+            self.content_addressed(),
+            messages,
+            bodies,
+            location_updates,
+        )
+        .await?;
+
+        for (uid, flags, labels) in flag_updates {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET flags = $1, labels = $2, updated_at = $3
+                WHERE account_id = $4 AND folder = $5 AND uid = $6;
+                "#,
+            )
+            .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
+            .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
+            .bind(now)
+            .bind(account_id)
+            .bind(folder)
+            .bind(i64::from(*uid))
+            .execute(&mut *tx)
+            .await
+            .context("updating message flags/labels in tx")?;
+        }
+
+        messages::upsert_folder_row(&mut tx, account_id, folder, folder_update).await?;
+        Self::record_folder_sync_end(
+            &mut tx,
+            account_id,
+            folder,
+            sync_status,
+            last_modseq,
+            last_uid,
+        )
+        .await?;
+
+        tx.commit().await.context("commit folder batch tx")?;
+        Ok(())
+    }
+
+    async fn commit_backfill_batch(
+        &self,
+        account_id: &str,
+        folder: &str,
+        messages: &[MessageRecord],
+        bodies: &[BodyRecord],
+        location_updates: &[MessageLocationUpdate],
+        backfill_since: Option<NaiveDate>,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await.context("beginning backfill tx")?;
+        let cipher = self.cipher_for(account_id);
+        messages::write_messages_in_tx(
+            &mut tx,
+            account_id,
+            cipher.as_deref(),
+            self.content_addressed(),
+            messages,
+            bodies,
+            location_updates,
+        )
+        .await?;
+
+        if let Some(since) = backfill_since {
+            sqlx::query(
+                r#"
+                INSERT INTO folders (account_id, name, backfill_since, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, $4)
+                ON CONFLICT (account_id, name) DO UPDATE SET
+                    backfill_since = excluded.backfill_since,
+                    updated_at = excluded.updated_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(folder)
+            .bind(since.to_string())
+            .bind(now_ts())
+            .execute(&mut *tx)
+            .await
+            .context("recording backfill progress")?;
+        }
+
+        tx.commit().await.context("committing backfill tx")?;
+        Ok(())
+    }
+
+    async fn clear_folder_backfill(&self, account_id: &str, folder: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE folders SET backfill_since = NULL, updated_at = $1 WHERE account_id = $2 AND name = $3",
+        )
+        .bind(now_ts())
+        .bind(account_id)
+        .bind(folder)
+        .execute(&self.pool)
+        .await
+        .context("clearing folder backfill")?;
+        Ok(())
+    }
+
+    async fn record_sync_runs(&self, runs: &[SyncRunRecord]) -> Result<()> {
+        if runs.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await.context("beginning sync run tx")?;
+        for run in runs {
+            sqlx::query(
+                r#"
+                INSERT INTO sync_runs (account_id, folder, run_started_at, duration_ms, added, updated, deleted, bytes, status, error)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10);
+                "#,
+            )
+            .bind(&run.account_id)
+            .bind(&run.folder)
+            .bind(run.run_started_at)
+            .bind(run.duration_ms)
+            .bind(run.added as i64)
+            .bind(run.updated as i64)
+            .bind(run.deleted as i64)
+            .bind(run.bytes as i64)
+            .bind(if run.ok { "ok" } else { "failed" })
+            .bind(&run.error)
+            .execute(&mut *tx)
+            .await
+            .context("inserting sync run")?;
+        }
+
+        sqlx::query("DELETE FROM sync_runs WHERE run_started_at < $1")
+            .bind(now_ts() - SYNC_RUN_RETENTION_SECS)
+            .execute(&mut *tx)
+            .await
+            .context("pruning sync runs")?;
+
+        tx.commit().await.context("committing sync run tx")?;
+        Ok(())
+    }
+
+    async fn load_latest_sync_runs(&self, account_id: &str) -> Result<Vec<SyncRunRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT folder, run_started_at, duration_ms, added, updated, deleted, bytes, status, error
+            FROM sync_runs s
+            WHERE account_id = $1
+              AND run_started_at = (
+                  SELECT MAX(run_started_at) FROM sync_runs
+                  WHERE account_id = $1 AND folder = s.folder
+              )
+            ORDER BY folder ASC;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading latest sync runs")?;
+        Ok(sync_runs_from_rows(account_id, rows))
+    }
+
+    async fn record_audit(&self, record: &AuditRecord) -> Result<()> {
+        records::append_audit(&self.pool, record).await
+    }
+
+    async fn load_audit_log(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>> {
+        records::list_audit(&self.pool, account_id, since, limit).await
+    }
+
+    async fn record_cleanup_run(&self, run: &CleanupRun) -> Result<()> {
+        records::append_cleanup_run(&self.pool, run).await
+    }
+
+    async fn load_cleanup_runs(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<CleanupRun>> {
+        records::list_cleanup_runs(&self.pool, account_id, since, limit).await
+    }
+
+    async fn save_account_quota(&self, quota: &AccountQuota) -> Result<()> {
+        records::save_quota(&self.pool, quota).await
+    }
+
+    async fn load_account_quota(&self, account_id: &str) -> Result<Option<AccountQuota>> {
+        records::load_quota(&self.pool, account_id).await
+    }
+
+    async fn save_server_identity(&self, identity: &ServerIdentity) -> Result<()> {
+        records::save_identity(&self.pool, identity).await
+    }
+
+    async fn load_server_identity(&self, account_id: &str) -> Result<Option<ServerIdentity>> {
+        records::load_identity(&self.pool, account_id).await
+    }
+
+    async fn load_messages_before(
+        &self,
+        account_id: &str,
+        before: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND internal_date < ")
+            .push_bind(before)
+            .push(" AND flags NOT LIKE '%\"Deleted\"%' ORDER BY internal_date ASC, id ASC");
+        self.load_message_records(account_id, qb).await
+    }
+
+    async fn load_messages_since(
+        &self,
+        account_id: &str,
+        since: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND internal_date >= ")
+            .push_bind(since)
+            .push(" AND flags NOT LIKE '%\"Deleted\"%' ORDER BY internal_date ASC, id ASC");
+        self.load_message_records(account_id, qb).await
+    }
+
+    async fn load_messages_cached_since(
+        &self,
+        account_id: &str,
+        since: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND created_at >= ")
+            .push_bind(since)
+            .push(" AND flags NOT LIKE '%\"Deleted\"%' ORDER BY internal_date ASC, id ASC");
+        self.load_message_records(account_id, qb).await
+    }
+
+    async fn load_messages_by_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<MessageRecord>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND id = ANY(")
+            .push_bind(ids)
+            .push(")");
+        self.load_message_records(account_id, qb).await
+    }
+
+    async fn set_reply_later(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+        due: Option<NaiveDate>,
+    ) -> Result<u64> {
+        records::set_reply_later(&self.pool, account_id, message_ids, due).await
+    }
+
+    async fn clear_reply_later(&self, account_id: &str, message_ids: &[String]) -> Result<u64> {
+        records::clear_reply_later(&self.pool, account_id, message_ids).await
+    }
+
+    async fn load_reply_later(&self, account_id: &str) -> Result<Vec<ReplyLater>> {
+        let mut queue = records::list_reply_later(&self.pool, account_id).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for entry in &mut queue {
+                entry.subject = entry
+                    .subject
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+                entry.from = entry
+                    .from
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+            }
+        }
+        Ok(queue)
+    }
+
+    async fn set_message_note(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        note: &str,
+    ) -> Result<bool> {
+        let note = match self.cipher_for(account_id) {
+            Some(cipher) => cipher.seal_text(note)?,
+            None => note.to_string(),
+        };
+        records::set_note(&self.pool, account_id, message_id, &note).await
+    }
+
+    async fn clear_message_note(&self, account_id: &str, message_id: &str) -> Result<bool> {
+        records::clear_note(&self.pool, account_id, message_id).await
+    }
+
+    async fn load_message_notes(&self, account_id: &str) -> Result<Vec<MessageNote>> {
+        let mut list = records::list_notes(&self.pool, account_id).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for entry in &mut list {
+                entry.note = cipher.open_text(&entry.note)?;
+                entry.subject = entry
+                    .subject
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+                entry.from = entry
+                    .from
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+            }
+        }
+        Ok(list)
+    }
+
+    async fn set_message_summary(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        summary: &str,
+    ) -> Result<bool> {
+        let summary = match self.cipher_for(account_id) {
+            Some(cipher) => cipher.seal_text(summary)?,
+            None => summary.to_string(),
+        };
+        records::set_summary(&self.pool, account_id, message_id, &summary).await
+    }
+
+    async fn load_message_summaries(&self, account_id: &str) -> Result<HashMap<String, String>> {
+        let cipher = self.cipher_for(account_id);
+        records::list_summaries(&self.pool, account_id)
+            .await?
+            .into_iter()
+            .map(|(id, summary)| {
+                let summary = match cipher.as_deref() {
+                    Some(cipher) => cipher.open_text(&summary)?,
+                    None => summary,
+                };
+                Ok((id, summary))
+            })
+            .collect()
+    }
+
+    /// `FOR UPDATE SKIP LOCKED` lets concurrent claims pass over each other's candidates, and
+    /// the primary key (`ON CONFLICT DO NOTHING`) drops any row another claim already recorded.
+    async fn claim_unprocessed_messages(
+        &self,
+        consumer: &str,
+        account_id: &str,
+        cached_since: i64,
+        limit: usize,
+    ) -> Result<Vec<MessageRecord>> {
+        let claimed: Vec<String> = sqlx::query_scalar(
+            r#"
+            WITH picked AS (
+                SELECT m.id FROM messages m
+                WHERE m.account_id = $2
+                  AND m.created_at >= $4
+                  AND m.flags NOT LIKE '%"Deleted"%'
+                  AND NOT EXISTS (
+                      SELECT 1 FROM processed_messages p
+                      WHERE p.consumer = $1 AND p.message_id = m.id
+                  )
+                ORDER BY m.internal_date ASC NULLS FIRST, m.id ASC
+                LIMIT $5
+                FOR UPDATE OF m SKIP LOCKED
+            )
+            INSERT INTO processed_messages (consumer, message_id, processed_at)
+            SELECT $1, id, $3 FROM picked
+            ON CONFLICT DO NOTHING
+            RETURNING message_id
+            "#,
+        )
+        .bind(consumer)
+        .bind(account_id)
+        .bind(now_ts())
+        .bind(cached_since)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("claiming unprocessed messages")?;
+        if claimed.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(MESSAGE_RECORD_SELECT);
+        qb.push(" WHERE account_id = ")
+            .push_bind(account_id)
+            .push(" AND id = ANY(")
+            .push_bind(claimed)
+            .push(") ORDER BY internal_date ASC NULLS FIRST, id ASC");
+        self.load_message_records(account_id, qb).await
+    }
+
+    async fn release_processed_messages(
+        &self,
+        consumer: &str,
+        message_ids: &[String],
+    ) -> Result<u64> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+        let result = sqlx::query(
+            "DELETE FROM processed_messages WHERE consumer = $1 AND message_id = ANY($2)",
+        )
+        .bind(consumer)
+        .bind(message_ids)
+        .execute(&self.pool)
+        .await
+        .context("releasing processed messages")?;
+        Ok(result.rows_affected())
+    }
+
+    async fn set_message_translation(
+        &self,
+        account_id: &str,
+        translation: &MessageTranslation,
+    ) -> Result<bool> {
+        let sealed;
+        let translation = match self.cipher_for(account_id) {
+            Some(cipher) => {
+                sealed = MessageTranslation {
+                    text: cipher.seal_text(&translation.text)?,
+                    ..translation.clone()
+                };
+                &sealed
+            }
+            None => translation,
+        };
+        records::set_translation(&self.pool, account_id, translation).await
+    }
+
+    async fn load_message_translation(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        target: &str,
+    ) -> Result<Option<MessageTranslation>> {
+        let Some(mut translation) =
+            records::get_translation(&self.pool, account_id, message_id, target).await?
+        else {
+            return Ok(None);
+        };
+        if let Some(cipher) = self.cipher_for(account_id) {
+            translation.text = cipher.open_text(&translation.text)?;
+        }
+        Ok(Some(translation))
+    }
+
+    async fn save_signature(&self, signature: &Signature) -> Result<()> {
+        records::save_signature(&self.pool, signature).await
+    }
+
+    async fn load_signature(
+        &self,
+        account_id: &str,
+        alias: Option<&str>,
+    ) -> Result<Option<Signature>> {
+        records::load_signature(&self.pool, account_id, alias).await
+    }
+
+    async fn clear_signature(&self, account_id: &str, alias: Option<&str>) -> Result<bool> {
+        records::clear_signature(&self.pool, account_id, alias).await
+    }
+
+    async fn record_sender_action(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        count: i64,
+        threshold: i64,
+    ) -> Result<Option<i64>> {
+        let cipher = self.cipher_for(account_id);
+        let key = blobs::content_hash(cipher.as_deref(), sender.as_bytes());
+        let stored = match cipher.as_deref() {
+            Some(cipher) => cipher.seal_text(sender)?,
+            None => sender.to_string(),
+        };
+        records::record_sender_action(
+            &self.pool, account_id, &key, &stored, action, label, count, threshold,
+        )
+        .await
+    }
+
+    async fn set_sender_action_status(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        status: SuggestionStatus,
+    ) -> Result<bool> {
+        let cipher = self.cipher_for(account_id);
+        let key = blobs::content_hash(cipher.as_deref(), sender.as_bytes());
+        records::set_sender_action_status(&self.pool, account_id, &key, action, label, status).await
+    }
+
+    async fn load_sender_actions(
+        &self,
+        account_id: &str,
+        status: Option<SuggestionStatus>,
+    ) -> Result<Vec<SenderAction>> {
+        let mut actions = records::list_sender_actions(&self.pool, account_id, status).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for action in &mut actions {
+                action.sender = cipher.open_text(&action.sender)?;
+            }
+        }
+        Ok(actions)
+    }
+
+    async fn load_recipients(&self, message_id: &str) -> Result<Vec<Recipient>> {
+        records::list_recipients(&self.pool, message_id).await
+    }
+
+    async fn find_messages_by_recipient(
+        &self,
+        account_id: &str,
+        address: &str,
+        fields: &[AddressField],
+        not_fields: &[AddressField],
+    ) -> Result<Vec<String>> {
+        records::find_by_recipient(&self.pool, account_id, address, fields, not_fields).await
+    }
+
+    async fn suggest_addresses(
+        &self,
+        account_id: &str,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<AddressSuggestion>> {
+        records::suggest_addresses(&self.pool, account_id, prefix, limit, now_ts()).await
+    }
+
+    async fn load_message_ids_by_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<HashMap<u32, String>> {
+        if uids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows = sqlx::query(
+            "SELECT uid, id FROM messages WHERE account_id = $1 AND folder = $2 AND uid = ANY($3)",
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(uids_to_i64(uids))
+        .fetch_all(&self.pool)
+        .await
+        .context("loading message ids by uid list")?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let uid = row.get::<Option<i64>, _>(0).map(|v| v as u32).unwrap_or(0);
+                (uid > 0).then(|| (uid, row.get(1)))
+            })
+            .collect())
+    }
+
+    async fn load_uid_to_message_id_map_by_folder(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, String>> {
+        let rows = sqlx::query(
+            "SELECT uid, id FROM messages WHERE account_id = $1 AND folder = $2 AND uid IS NOT NULL",
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading message uid map by folder")?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                let uid = row.get::<Option<i64>, _>(0).map(|v| v as u32).unwrap_or(0);
+                (uid > 0).then(|| (uid, row.get(1)))
+            })
+            .collect())
+    }
+
+    async fn load_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (Vec<String>, Vec<String>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT uid, flags, labels
+            FROM messages
+            WHERE account_id = $1 AND folder = $2 AND uid IS NOT NULL;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading cached flags by uid")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let flags = serde_json::from_str(&row.get::<String, _>(1)).unwrap_or_default();
+                let labels = serde_json::from_str(&row.get::<String, _>(2)).unwrap_or_default();
+                (row.get::<i64, _>(0) as u32, (flags, labels))
+            })
+            .collect())
+    }
+
+    async fn load_raw_hashes_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (String, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT uid, id, raw_hash
+            FROM messages
+            WHERE account_id = $1 AND folder = $2 AND uid IS NOT NULL
+              AND raw_hash IS NOT NULL AND body_status = 'full';
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading raw hashes by uid")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get::<i64, _>(0) as u32, (row.get(1), row.get(2))))
+            .collect())
+    }
+
+    async fn unread_counts(&self, account_id: &str) -> Result<HashMap<String, u32>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT folder, COUNT(*)
+            FROM messages
+            WHERE account_id = $1
+              AND flags NOT LIKE '%"Seen"%'
+              AND flags NOT LIKE '%"Deleted"%'
+            GROUP BY folder;
+            "#,
+        )
+        .bind(account_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("counting unread messages")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get::<i64, _>(1) as u32))
+            .collect())
+    }
+
+    async fn unread_label_counts(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<String, u32>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT label.value, COUNT(*)
+            FROM messages, jsonb_array_elements_text(messages.labels::jsonb) AS label(value)
+            WHERE account_id = $1
+              AND folder = $2
+              AND flags NOT LIKE '%"Seen"%'
+              AND flags NOT LIKE '%"Deleted"%'
+            GROUP BY label.value;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("counting unread messages per label")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get::<i64, _>(1) as u32))
+            .collect())
+    }
+
+    async fn batch_update_message_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+        updates: &[(u32, Vec<String>, Vec<String>)],
+    ) -> Result<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        let now = now_ts();
+        let mut tx = self.pool.begin().await.context("beginning transaction")?;
+        for (uid, flags, labels) in updates {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET flags = $1, labels = $2, updated_at = $3
+                WHERE account_id = $4 AND folder = $5 AND uid = $6;
+                "#,
+            )
+            .bind(serde_json::to_string(flags).unwrap_or_else(|_| "[]".into()))
+            .bind(serde_json::to_string(labels).unwrap_or_else(|_| "[]".into()))
+            .bind(now)
+            .bind(account_id)
+            .bind(folder)
+            .bind(i64::from(*uid))
+            .execute(&mut *tx)
+            .await
+            .context("updating message flags/labels")?;
+        }
+        tx.commit().await.context("committing flag update tx")?;
+        Ok(())
+    }
+
+    async fn load_existing_message_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<HashSet<String>> {
+        if ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let ids: Vec<String> =
+            sqlx::query_scalar("SELECT id FROM messages WHERE account_id = $1 AND id = ANY($2)")
+                .bind(account_id)
+                .bind(ids)
+                .fetch_all(&self.pool)
+                .await
+                .context("loading existing message ids")?;
+        Ok(ids.into_iter().collect())
+    }
+
+    async fn load_message_ids_by_header(
+        &self,
+        account_id: &str,
+        folder: &str,
+        headers: &[String],
+    ) -> Result<HashMap<String, String>> {
+        if headers.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows = sqlx::query(
+            r#"
+            SELECT message_id_header, MIN(id) FROM messages
+            WHERE account_id = $1 AND folder <> $2 AND message_id_header = ANY($3)
+            GROUP BY message_id_header;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(headers)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading message ids by Message-ID")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get::<String, _>(0), row.get::<String, _>(1)))
+            .collect())
+    }
+
+    async fn dedupe_fallback_messages_by_raw_hash(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<usize> {
+        let legacy_ids: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT legacy.id
+            FROM messages AS stable
+            JOIN messages AS legacy
+              ON legacy.account_id = stable.account_id
+             AND legacy.raw_hash = stable.raw_hash
+            WHERE stable.account_id = $1
+              AND stable.raw_hash IS NOT NULL
+              AND stable.id ~ '^[0-9]'
+              AND legacy.id LIKE '%:%'
+              AND legacy.id <> stable.id
+            LIMIT $2;
+            "#,
+        )
+        .bind(account_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("finding fallback duplicates")?;
+
+        for legacy_id in &legacy_ids {
+            self.delete_message(legacy_id).await?;
+        }
+        Ok(legacy_ids.len())
+    }
+
+    async fn load_messages(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
+        self.load_messages_in(account_id, None, limit).await
+    }
+
+    async fn load_thread(
+        &self,
+        account_id: &str,
+        id: &str,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
+        let thread_id: Option<String> = sqlx::query_scalar(
+            r#"
+            SELECT COALESCE(
+                (SELECT thread_id FROM messages WHERE account_id = $1 AND id = $2),
+                (SELECT thread_id FROM messages WHERE account_id = $1 AND thread_id = $2 LIMIT 1)
+            );
+            "#,
+        )
+        .bind(account_id)
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .context("resolving thread id")?;
+        match thread_id {
+            Some(thread_id) => {
+                self.load_messages_in(account_id, Some(&thread_id), usize::MAX)
+                    .await
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn load_pending_body_targets(
+        &self,
+        account_id: &str,
+        skip_folders: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, String, u32)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, folder, uid FROM messages
+            WHERE account_id = $1 AND body_status = 'pending' AND uid IS NOT NULL
+              AND folder <> ALL($2)
+            ORDER BY internal_date DESC NULLS LAST
+            LIMIT $3;
+            "#,
+        )
+        .bind(account_id)
+        .bind(skip_folders)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading pending body targets")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get::<i64, _>(2) as u32))
+            .collect())
+    }
+
+    async fn request_oversized_bodies(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>> {
+        let mut rows: Vec<(String, String, Option<i64>, Option<i64>)> = sqlx::query(
+            r#"
+            UPDATE messages SET body_status = 'pending'
+            WHERE body_status = 'too_large' AND account_id = $1
+              AND (cardinality($2::text[]) = 0 OR id = ANY($2))
+            RETURNING id, folder, uid, internal_date;
+            "#,
+        )
+        .bind(account_id)
+        .bind(message_ids)
+        .fetch_all(&self.pool)
+        .await
+        .context("requesting oversized bodies")?
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2), row.get(3)))
+        .collect();
+        rows.sort_by_key(|row| std::cmp::Reverse(row.3));
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, folder, uid, _)| Some((id, folder, uid? as u32)))
+            .collect())
+    }
+
+    async fn load_body_targets(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>> {
+        if message_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = sqlx::query(
+            r#"
+            SELECT id, folder, uid FROM messages
+            WHERE account_id = $1 AND uid IS NOT NULL AND id = ANY($2)
+            ORDER BY internal_date DESC NULLS LAST;
+            "#,
+        )
+        .bind(account_id)
+        .bind(message_ids)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading body targets")?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get::<i64, _>(2) as u32))
+            .collect())
+    }
+
+    async fn store_fetched_bodies(
+        &self,
+        account_id: &str,
+        updates: &[FetchedBodyUpdate],
+    ) -> Result<()> {
+        if updates.is_empty() {
+            return Ok(());
+        }
+
+        let now = now_ts();
+        let cipher = self.cipher_for(account_id);
+        let content_addressed = self.content_addressed();
+        let mut tx = self.pool.begin().await.context("beginning body fetch tx")?;
+        for (has_attachments, raw_hash, body) in updates {
+            sqlx::query(
+                r#"
+                UPDATE messages
+                SET has_attachments = $1, raw_hash = $2, body_status = 'full', updated_at = $3
+                WHERE account_id = $4 AND id = $5;
+                "#,
+            )
+            .bind(i64::from(*has_attachments))
+            .bind(raw_hash)
+            .bind(now)
+            .bind(account_id)
+            .bind(&body.message_id)
+            .execute(&mut *tx)
+            .await
+            .context("marking message body fetched")?;
+
+            messages::upsert_body_in(&mut tx, cipher.as_deref(), body, content_addressed).await?;
+        }
+        tx.commit().await.context("committing body fetch tx")?;
+        Ok(())
+    }
+
+    async fn drop_folder_bodies(&self, account_id: &str, folder: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await.context("beginning body drop tx")?;
+        let dropped = sqlx::query(
+            r#"
+            DELETE FROM bodies
+            WHERE message_id IN (
+                SELECT id FROM messages WHERE account_id = $1 AND folder = $2
+            );
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .execute(&mut *tx)
+        .await
+        .context("deleting folder bodies")?
+        .rows_affected();
+
+        sqlx::query(
+            r#"
+            UPDATE messages SET body_status = 'pending', updated_at = $3
+            WHERE account_id = $1 AND folder = $2 AND body_status = 'full';
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(now_ts())
+        .execute(&mut *tx)
+        .await
+        .context("marking folder bodies pending")?;
+
+        tx.commit().await.context("committing body drop tx")?;
+        Ok(dropped)
+    }
+
+    async fn load_bodies_to_resanitize(
+        &self,
+        account_id: &str,
+        below_version: i64,
+        after: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT b.message_id, COALESCE(bl.data, b.raw_rfc822),
+                   CASE WHEN bl.hash IS NULL THEN b.raw_format ELSE bl.format END
+            FROM bodies b
+            JOIN messages m ON m.id = b.message_id
+            LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+            WHERE m.account_id = $1 AND b.message_id > $2
+              AND COALESCE(b.sanitizer_version, 0) < $3
+              AND (b.raw_rfc822 IS NOT NULL OR bl.data IS NOT NULL)
+            ORDER BY b.message_id
+            LIMIT $4;
+            "#,
+        )
+        .bind(account_id)
+        .bind(after)
+        .bind(below_version)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading bodies to resanitize")?;
+
+        let cipher = self.cipher_for(account_id);
+        rows.into_iter()
+            .map(|row| {
+                let format = RawFormat::from_i64(row.get(2))?;
+                Ok((row.get(0), open_raw(cipher.as_deref(), row.get(1), format)?))
+            })
+            .collect()
+    }
+
+    async fn load_raw_message(
+        &self,
+        account_id: &str,
+        message_id: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        let row = sqlx::query(
+            r#"
+            SELECT COALESCE(bl.data, b.raw_rfc822),
+                   CASE WHEN bl.hash IS NULL THEN b.raw_format ELSE bl.format END
+            FROM bodies b
+            JOIN messages m ON m.id = b.message_id
+            LEFT JOIN blobs bl ON bl.hash = b.blob_hash
+            WHERE m.account_id = $1 AND b.message_id = $2
+              AND (b.raw_rfc822 IS NOT NULL OR bl.data IS NOT NULL);
+            "#,
+        )
+        .bind(account_id)
+        .bind(message_id)
+        .fetch_optional(&self.pool)
+        .await
+        .context("loading raw message")?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let format = RawFormat::from_i64(row.get(1))?;
+        let cipher = self.cipher_for(account_id);
+        open_raw(cipher.as_deref(), row.get(0), format).map(Some)
+    }
+
+    async fn store_resanitized_bodies(
+        &self,
+        account_id: &str,
+        bodies: &[(bool, BodyRecord)],
+    ) -> Result<()> {
+        if bodies.is_empty() {
+            return Ok(());
+        }
+
+        let cipher = self.cipher_for(account_id);
+        let mut tx = self.pool.begin().await.context("beginning resanitize tx")?;
+        for (has_attachments, body) in bodies {
+            let sanitized_text = match (cipher.as_deref(), body.sanitized_text.as_deref()) {
+                (Some(cipher), Some(text)) => Some(cipher.seal_text(text)?),
+                (_, text) => text.map(str::to_string),
+            };
+            sqlx::query(
+                r#"
+                UPDATE bodies
+                SET sanitized_text = $1, mime_summary = $2, attachments_json = $3,
+                    sanitized_at = $4, sanitizer_version = $5, degraded = $6
+                WHERE message_id = $7;
+                "#,
+            )
+            .bind(sanitized_text)
+            .bind(&body.mime_summary)
+            .bind(&body.attachments_json)
+            .bind(body.sanitized_at)
+            .bind(body.sanitizer_version)
+            .bind(i64::from(body.degraded))
+            .bind(&body.message_id)
+            .execute(&mut *tx)
+            .await
+            .context("updating resanitized body")?;
+
+            sqlx::query(
+                "UPDATE messages SET has_attachments = $1 WHERE account_id = $2 AND id = $3",
+            )
+            .bind(i64::from(*has_attachments))
+            .bind(account_id)
+            .bind(&body.message_id)
+            .execute(&mut *tx)
+            .await
+            .context("updating message attachment flag")?;
+        }
+        tx.commit().await.context("committing resanitize tx")?;
+        Ok(())
+    }
+
+    async fn compress_raw_bodies(&self, account_id: &str) -> Result<CompressStats> {
+        const PAGE: i64 = 200;
+        let cipher = self.cipher_for(account_id);
+        let cipher = cipher.as_deref();
+        let mut stats = CompressStats::default();
+        // Sealed bytes in, sealed compressed bytes out; `None` when compression doesn't pay.
+        let pack = |stored: Vec<u8>| -> Result<Option<Vec<u8>>> {
+            let plain = open_raw(cipher, stored, RawFormat::Plain)?;
+            match compression::compress(&plain)? {
+                (packed, RawFormat::Zstd) => Ok(Some(match cipher {
+                    Some(cipher) => cipher.seal_bytes(&packed)?,
+                    None => packed,
+                })),
+                (_, RawFormat::Plain) => Ok(None),
+            }
+        };
+
+        let mut after = String::new();
+        loop {
+            let mut tx = self.pool.begin().await.context("beginning compress tx")?;
+            let rows = sqlx::query(
+                r#"
+                SELECT b.message_id, b.raw_rfc822
+                FROM bodies b
+                JOIN messages m ON m.id = b.message_id
+                WHERE m.account_id = $1 AND b.message_id > $2
+                  AND b.raw_rfc822 IS NOT NULL AND b.raw_format = 0
+                ORDER BY b.message_id
+                LIMIT $3;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading bodies to compress")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+            for row in rows {
+                let stored: Vec<u8> = row.get(1);
+                let before = stored.len() as u64;
+                let Some(packed) = pack(stored)? else {
+                    continue;
+                };
+                sqlx::query(
+                    "UPDATE bodies SET raw_rfc822 = $1, raw_format = $2 WHERE message_id = $3",
+                )
+                .bind(&packed)
+                .bind(RawFormat::Zstd.as_i64())
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("compressing body")?;
+                stats.compressed += 1;
+                stats.bytes_before += before;
+                stats.bytes_after += packed.len() as u64;
+            }
+            tx.commit().await.context("committing compress tx")?;
+        }
+
+        let mut after = String::new();
+        loop {
+            let mut tx = self.pool.begin().await.context("beginning compress tx")?;
+            let rows = sqlx::query(
+                r#"
+                SELECT DISTINCT bl.hash, bl.data
+                FROM blobs bl
+                JOIN bodies b ON b.blob_hash = bl.hash
+                JOIN messages m ON m.id = b.message_id
+                WHERE m.account_id = $1 AND bl.hash > $2 AND bl.format = 0
+                ORDER BY bl.hash
+                LIMIT $3;
+                "#,
+            )
+            .bind(account_id)
+            .bind(&after)
+            .bind(PAGE)
+            .fetch_all(&mut *tx)
+            .await
+            .context("loading blobs to compress")?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            after = last.get(0);
+            for row in rows {
+                let stored: Vec<u8> = row.get(1);
+                let before = stored.len() as u64;
+                let Some(packed) = pack(stored)? else {
+                    continue;
+                };
+                sqlx::query(
+                    "UPDATE blobs SET data = $1, size_bytes = $2, format = $3 WHERE hash = $4",
+                )
+                .bind(&packed)
+                .bind(packed.len() as i64)
+                .bind(RawFormat::Zstd.as_i64())
+                .bind(row.get::<String, _>(0))
+                .execute(&mut *tx)
+                .await
+                .context("compressing blob")?;
+                stats.compressed += 1;
+                stats.bytes_before += before;
+                stats.bytes_after += packed.len() as u64;
+            }
+            tx.commit().await.context("committing compress tx")?;
+        }
+        Ok(stats)
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        // VACUUM can't run in a transaction block, so it goes over the simple protocol.
+        sqlx::Executor::execute(&self.pool, "VACUUM")
+            .await
+            .context("vacuuming database")?;
+        Ok(())
+    }
+
+    async fn apply_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await.context("beginning message op tx")?;
+        let queued = messages::apply_message_op_in_tx(&mut tx, account_id, op, message_ids).await?;
+        ops::enqueue_ops(&mut tx, account_id, op.kind(), &queued).await?;
+        tx.commit().await.context("committing message op tx")?;
+        Ok(queued.len())
+    }
+
+    async fn record_applied_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await.context("beginning message op tx")?;
+        let applied =
+            messages::apply_message_op_in_tx(&mut tx, account_id, op, message_ids).await?;
+        tx.commit().await.context("committing message op tx")?;
+        Ok(applied.len())
+    }
+
+    async fn load_pending_flag_ops(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<Vec<PendingFlagOp>> {
+        ops::load_pending_flag_ops(&self.pool, account_id, folder, uids).await
+    }
+
+    async fn load_replayable_flag_ops(&self, account_id: &str) -> Result<Vec<ReplayOp>> {
+        ops::load_replayable_flag_ops(&self.pool, account_id).await
+    }
+
+    async fn load_replayable_location_ops(&self, account_id: &str) -> Result<Vec<ReplayOp>> {
+        ops::load_replayable_location_ops(&self.pool, account_id).await
+    }
+
+    async fn rollback_pending_ops(&self, ids: &[i64]) -> Result<u64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning op rollback tx")?;
+        let restored = ops::rollback_ops(&mut tx, ids).await?;
+        tx.commit().await.context("committing op rollback tx")?;
+        Ok(restored)
+    }
+
+    async fn clear_pending_ops(&self, ids: &[i64]) -> Result<u64> {
+        ops::clear_ops(&self.pool, ids).await
+    }
+
+    async fn link_moved_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uid_validity: u32,
+        links: &[(i64, u32)],
+    ) -> Result<u64> {
+        ops::link_moved(&self.pool, account_id, folder, uid_validity, links).await
+    }
+
+    async fn record_op_failures(&self, ids: &[i64], error: &str) -> Result<u64> {
+        ops::record_failures(&self.pool, ids, error).await
+    }
+
+    async fn retry_dead_ops(&self, ids: &[i64]) -> Result<u64> {
+        ops::retry_dead(&self.pool, ids).await
+    }
+
+    async fn list_pending_ops(&self, account_id: &str) -> Result<Vec<PendingOp>> {
+        ops::list_ops(&self.pool, account_id).await
+    }
+
+    async fn record_fetch_failures(
+        &self,
+        account_id: &str,
+        folder: &str,
+        failures: &[(u32, String)],
+    ) -> Result<()> {
+        if failures.is_empty() {
+            return Ok(());
+        }
+        let now = now_ts();
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning fetch retry tx")?;
+        for (uid, error) in failures {
+            sqlx::query(
+                r#"
+                INSERT INTO fetch_retries (account_id, folder, uid, attempts, last_error, first_failed_at, last_attempt_at)
+                VALUES ($1, $2, $3, 1, $4, $5, $5)
+                ON CONFLICT (account_id, folder, uid) DO UPDATE SET
+                    attempts = fetch_retries.attempts + 1,
+                    last_error = excluded.last_error,
+                    last_attempt_at = excluded.last_attempt_at;
+                "#,
+            )
+            .bind(account_id)
+            .bind(folder)
+            .bind(i64::from(*uid))
+            .bind(error)
+            .bind(now)
+            .execute(&mut *tx)
+            .await
+            .context("recording fetch failure")?;
+        }
+        tx.commit().await.context("committing fetch retry tx")?;
+        Ok(())
+    }
+
+    async fn load_fetch_retries(&self, account_id: &str, folder: &str) -> Result<Vec<FetchRetry>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT uid, attempts, last_error, first_failed_at, last_attempt_at
+            FROM fetch_retries
+            WHERE account_id = $1 AND folder = $2
+            ORDER BY uid ASC;
+            "#,
+        )
+        .bind(account_id)
+        .bind(folder)
+        .fetch_all(&self.pool)
+        .await
+        .context("loading fetch retries")?;
+        Ok(rows
+            .iter()
+            .map(|row| FetchRetry {
+                uid: row.get::<i64, _>(0) as u32,
+                attempts: row.get::<i64, _>(1) as u32,
+                last_error: row.get(2),
+                first_failed_at: row.get(3),
+                last_attempt_at: row.get(4),
+            })
+            .collect())
+    }
+
+    async fn clear_fetch_retries(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<u64> {
+        if uids.is_empty() {
+            return Ok(0);
+        }
+        let res = sqlx::query(
+            "DELETE FROM fetch_retries WHERE account_id = $1 AND folder = $2 AND uid = ANY($3)",
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(uids_to_i64(uids))
+        .execute(&self.pool)
+        .await
+        .context("clearing fetch retries")?;
+        Ok(res.rows_affected())
+    }
+
+    async fn mark_op_conflicts(
+        &self,
+        ids: &[i64],
+        server_flags: &[String],
+        server_labels: &[String],
+    ) -> Result<u64> {
+        ops::mark_conflicts(&self.pool, ids, server_flags, server_labels).await
+    }
+
+    async fn list_op_conflicts(&self, account_id: &str) -> Result<Vec<OpConflict>> {
+        let mut conflicts = ops::list_conflicts(&self.pool, account_id).await?;
+        if let Some(cipher) = self.cipher_for(account_id) {
+            for conflict in &mut conflicts {
+                conflict.subject = conflict
+                    .subject
+                    .as_deref()
+                    .map(|s| cipher.open_text(s))
+                    .transpose()?;
+            }
+        }
+        Ok(conflicts)
+    }
+
+    async fn resolve_op_conflicts(
+        &self,
+        ids: &[i64],
+        resolution: ConflictResolution,
+    ) -> Result<u64> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("beginning conflict resolution tx")?;
+        let settled = ops::resolve_conflicts(&mut tx, ids, resolution).await?;
+        tx.commit()
+            .await
+            .context("committing conflict resolution tx")?;
+        Ok(settled)
+    }
+
+    async fn delete_messages_by_folder(&self, account_id: &str, folder: &str) -> Result<u64> {
+        // Bodies, recipients and notes cascade with their message.
+        let res = sqlx::query("DELETE FROM messages WHERE account_id = $1 AND folder = $2")
+            .bind(account_id)
+            .bind(folder)
+            .execute(&self.pool)
+            .await
+            .context("deleting messages by folder")?;
+        Ok(res.rows_affected())
+    }
+
+    async fn delete_messages_by_folder_and_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<u64> {
+        if uids.is_empty() {
+            return Ok(0);
+        }
+        let res = sqlx::query(
+            "DELETE FROM messages WHERE account_id = $1 AND folder = $2 AND uid = ANY($3)",
+        )
+        .bind(account_id)
+        .bind(folder)
+        .bind(uids_to_i64(uids))
+        .execute(&self.pool)
+        .await
+        .context("deleting messages by uid list")?;
+        Ok(res.rows_affected())
+    }
+}
@@ -0,0 +1,458 @@
+//! `pending_ops` queries for the Postgres store; see `storage::ops` for what each one does.
+//! Payloads stay JSON text, read with `::jsonb` where SQLite uses `json_extract`.
+use anyhow::{Context, Result};
+use sqlx::{PgConnection, PgPool, Row};
+
+use crate::storage::ops::{
+    ConflictResolution, FLAG_OP_KINDS, LOCATION_OP_KINDS, MAX_OP_ATTEMPTS, MessageOp, OpConflict,
+    PendingFlagOp, PendingOp, ReplayOp,
+};
+use crate::types::now_ts;
+
+/// Queues one op per `(target, payload)` next to the optimistic local update.
+pub(super) async fn enqueue_ops(
+    conn: &mut PgConnection,
+    account_id: &str,
+    kind: &str,
+    targets: &[(String, Option<String>)],
+) -> Result<()> {
+    let now = now_ts();
+    for (target, payload) in targets {
+        sqlx::query(
+            r#"
+            INSERT INTO pending_ops (account_id, kind, target, payload, created_at)
+            VALUES ($1, $2, $3, $4, $5);
+            "#,
+        )
+        .bind(account_id)
+        .bind(kind)
+        .bind(target)
+        .bind(payload)
+        .bind(now)
+        .execute(&mut *conn)
+        .await
+        .context("enqueue pending op")?;
+    }
+    Ok(())
+}
+
+pub(super) async fn list_ops(pool: &PgPool, account_id: &str) -> Result<Vec<PendingOp>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, account_id, kind, target, payload, created_at, attempts, last_error, dead_at
+        FROM pending_ops
+        WHERE account_id = $1
+        ORDER BY created_at ASC, id ASC;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("list pending ops")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| PendingOp {
+            id: row.get(0),
+            account_id: row.get(1),
+            kind: row.get(2),
+            target: row.get(3),
+            payload: row.get(4),
+            created_at: row.get(5),
+            attempts: row.get(6),
+            last_error: row.get(7),
+            dead_at: row.get(8),
+        })
+        .collect())
+}
+
+pub(super) async fn load_pending_flag_ops(
+    pool: &PgPool,
+    account_id: &str,
+    folder: &str,
+    uids: &[u32],
+) -> Result<Vec<PendingFlagOp>> {
+    if uids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let uids: Vec<i64> = uids.iter().map(|uid| i64::from(*uid)).collect();
+    let rows = sqlx::query(
+        r#"
+        SELECT id, kind, (payload::jsonb->>'uid')::bigint, payload::jsonb->>'label',
+               (payload::jsonb->'flags')::text, (payload::jsonb->'labels')::text
+        FROM pending_ops
+        WHERE account_id = $1 AND kind = ANY($2)
+          AND payload::jsonb->>'folder' = $3
+          AND (payload::jsonb->>'uid')::bigint = ANY($4)
+        ORDER BY id ASC;
+        "#,
+    )
+    .bind(account_id)
+    .bind(FLAG_OP_KINDS)
+    .bind(folder)
+    .bind(&uids)
+    .fetch_all(pool)
+    .await
+    .context("loading pending flag ops")?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let kind: String = row.get(1);
+            let uid: i64 = row.get(2);
+            let op = MessageOp::from_kind(&kind, row.get(3))?;
+            let list = |idx: usize| -> Option<Vec<String>> {
+                serde_json::from_str(&row.get::<Option<String>, _>(idx)?).ok()
+            };
+            Some(PendingFlagOp {
+                id: row.get(0),
+                uid: uid as u32,
+                op,
+                snapshot: list(4).zip(list(5)),
+            })
+        })
+        .collect())
+}
+
+pub(super) async fn clear_ops(pool: &PgPool, ids: &[i64]) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let res = sqlx::query("DELETE FROM pending_ops WHERE id = ANY($1)")
+        .bind(ids)
+        .execute(pool)
+        .await
+        .context("clearing pending ops")?;
+    Ok(res.rows_affected())
+}
+
+pub(super) async fn link_moved(
+    pool: &PgPool,
+    account_id: &str,
+    folder: &str,
+    uid_validity: u32,
+    links: &[(i64, u32)],
+) -> Result<u64> {
+    let now = now_ts();
+    let mut tx = pool.begin().await.context("beginning link tx")?;
+    let mut linked = 0;
+    for (op_id, uid) in links {
+        let res = sqlx::query(
+            r#"
+            UPDATE messages SET uid = $1, updated_at = $2
+            WHERE account_id = $3 AND folder = $4 AND uid IS NULL
+              AND id = (SELECT target FROM pending_ops WHERE id = $5 AND account_id = $3)
+              AND EXISTS (
+                  SELECT 1 FROM folders
+                  WHERE account_id = $3 AND name = $4 AND uidvalidity = $6
+              )
+              AND NOT EXISTS (
+                  SELECT 1 FROM messages WHERE account_id = $3 AND folder = $4 AND uid = $1
+              );
+            "#,
+        )
+        .bind(i64::from(*uid))
+        .bind(now)
+        .bind(account_id)
+        .bind(folder)
+        .bind(op_id)
+        .bind(i64::from(uid_validity))
+        .execute(&mut *tx)
+        .await
+        .context("linking moved message")?;
+        linked += res.rows_affected();
+    }
+    tx.commit().await.context("committing link tx")?;
+    Ok(linked)
+}
+
+pub(super) async fn load_replayable_flag_ops(
+    pool: &PgPool,
+    account_id: &str,
+) -> Result<Vec<ReplayOp>> {
+    load_replay_ops(
+        pool,
+        account_id,
+        FLAG_OP_KINDS,
+        "CASE WHEN m.id IS NULL THEN p.payload::jsonb->>'folder' ELSE m.folder END, \
+         CASE WHEN m.id IS NULL THEN (p.payload::jsonb->>'uid')::bigint ELSE m.uid END",
+    )
+    .await
+}
+
+pub(super) async fn load_replayable_location_ops(
+    pool: &PgPool,
+    account_id: &str,
+) -> Result<Vec<ReplayOp>> {
+    load_replay_ops(
+        pool,
+        account_id,
+        LOCATION_OP_KINDS,
+        "CASE WHEN p.payload::jsonb->>'uid' IS NULL AND m.uid IS NOT NULL \
+              THEN m.folder ELSE p.payload::jsonb->>'folder' END, \
+         COALESCE((p.payload::jsonb->>'uid')::bigint, m.uid)",
+    )
+    .await
+}
+
+/// `location_columns` selects the folder and uid to replay at.
+async fn load_replay_ops(
+    pool: &PgPool,
+    account_id: &str,
+    kinds: &[&'static str],
+    location_columns: &str,
+) -> Result<Vec<ReplayOp>> {
+    let rows = sqlx::query(&format!(
+        "SELECT p.id, p.kind, \
+         COALESCE(p.payload::jsonb->>'label', p.payload::jsonb->>'dest'), {} \
+         FROM pending_ops p \
+         LEFT JOIN messages m ON m.id = p.target AND m.account_id = p.account_id \
+         WHERE p.account_id = $1 AND p.kind = ANY($2) \
+           AND p.conflict IS NULL AND p.dead_at IS NULL ORDER BY p.id ASC",
+        location_columns
+    ))
+    .bind(account_id)
+    .bind(kinds)
+    .fetch_all(pool)
+    .await
+    .context("loading replayable ops")?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let kind: String = row.get(1);
+            let op = MessageOp::from_kind(&kind, row.get(2))?;
+            let uid: Option<i64> = row.get(4);
+            Some(ReplayOp {
+                id: row.get(0),
+                op,
+                folder: row.get(3),
+                uid: uid.map(|u| u as u32),
+            })
+        })
+        .collect())
+}
+
+pub(super) async fn rollback_ops(conn: &mut PgConnection, ids: &[i64]) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let rows =
+        sqlx::query("SELECT id, account_id, target, payload FROM pending_ops WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(&mut *conn)
+            .await
+            .context("loading ops to roll back")?;
+    let mut restored = 0;
+    for row in &rows {
+        let id: i64 = row.get(0);
+        let account_id: String = row.get(1);
+        let target: String = row.get(2);
+        let payload: serde_json::Value = row
+            .get::<Option<String>, _>(3)
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or_default();
+        // Ops queued before snapshots were recorded only restore the location.
+        let json = |key: &str| {
+            payload
+                .get(key)
+                .filter(|v| !v.is_null())
+                .map(|v| v.to_string())
+        };
+        if let Some(folder) = payload.get("folder").and_then(|v| v.as_str()) {
+            let res = sqlx::query(
+                r#"
+                UPDATE messages
+                SET folder = $1, uid = $2, flags = COALESCE($3, flags),
+                    labels = COALESCE($4, labels), updated_at = $5
+                WHERE id = $6 AND account_id = $7;
+                "#,
+            )
+            .bind(folder)
+            .bind(payload.get("uid").and_then(|v| v.as_i64()))
+            .bind(json("flags"))
+            .bind(json("labels"))
+            .bind(now_ts())
+            .bind(&target)
+            .bind(&account_id)
+            .execute(&mut *conn)
+            .await
+            .context("restoring message after rejected op")?;
+            restored += res.rows_affected();
+        }
+        sqlx::query("DELETE FROM pending_ops WHERE id = $1")
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .context("clearing rolled back op")?;
+    }
+    Ok(restored)
+}
+
+pub(super) async fn record_failures(pool: &PgPool, ids: &[i64], error: &str) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    sqlx::query(
+        r#"
+        UPDATE pending_ops SET attempts = attempts + 1, last_error = $1,
+            dead_at = CASE WHEN attempts + 1 >= $2 THEN $3 ELSE NULL END
+        WHERE dead_at IS NULL AND id = ANY($4);
+        "#,
+    )
+    .bind(error)
+    .bind(MAX_OP_ATTEMPTS)
+    .bind(now_ts())
+    .bind(ids)
+    .execute(pool)
+    .await
+    .context("recording op failures")?;
+    let dead: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM pending_ops WHERE dead_at IS NOT NULL AND id = ANY($1)",
+    )
+    .bind(ids)
+    .fetch_one(pool)
+    .await
+    .context("counting dead ops")?;
+    Ok(dead as u64)
+}
+
+pub(super) async fn retry_dead(pool: &PgPool, ids: &[i64]) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let res = sqlx::query(
+        r#"
+        UPDATE pending_ops SET attempts = 0, last_error = NULL, dead_at = NULL
+        WHERE dead_at IS NOT NULL AND id = ANY($1);
+        "#,
+    )
+    .bind(ids)
+    .execute(pool)
+    .await
+    .context("releasing dead ops")?;
+    Ok(res.rows_affected())
+}
+
+pub(super) async fn mark_conflicts(
+    pool: &PgPool,
+    ids: &[i64],
+    server_flags: &[String],
+    server_labels: &[String],
+) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let conflict = serde_json::json!({
+        "flags": server_flags,
+        "labels": server_labels,
+        "detected_at": now_ts(),
+    })
+    .to_string();
+    let res = sqlx::query("UPDATE pending_ops SET conflict = $1 WHERE id = ANY($2)")
+        .bind(conflict)
+        .bind(ids)
+        .execute(pool)
+        .await
+        .context("marking op conflicts")?;
+    Ok(res.rows_affected())
+}
+
+/// `subject` is returned as stored (sealed when the account encrypts columns).
+pub(super) async fn list_conflicts(pool: &PgPool, account_id: &str) -> Result<Vec<OpConflict>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT p.id, p.target, p.kind,
+               COALESCE(p.payload::jsonb->>'label', p.payload::jsonb->>'dest'),
+               p.conflict, m.folder, m.uid, m.subject, m.flags, m.labels
+        FROM pending_ops p
+        LEFT JOIN messages m ON m.id = p.target AND m.account_id = p.account_id
+        WHERE p.account_id = $1 AND p.conflict IS NOT NULL
+        ORDER BY p.id ASC;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("listing op conflicts")?;
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let kind: String = row.get(2);
+            let op = MessageOp::from_kind(&kind, row.get(3))?;
+            let conflict: serde_json::Value =
+                serde_json::from_str(&row.get::<String, _>(4)).unwrap_or_default();
+            let conflict_list = |key: &str| -> Vec<String> {
+                serde_json::from_value(conflict[key].clone()).unwrap_or_default()
+            };
+            let local_list = |idx: usize| -> Vec<String> {
+                row.get::<Option<String>, _>(idx)
+                    .and_then(|json| serde_json::from_str(&json).ok())
+                    .unwrap_or_default()
+            };
+            Some(OpConflict {
+                id: row.get(0),
+                message_id: row.get(1),
+                op,
+                folder: row.get(5),
+                uid: row.get::<Option<i64>, _>(6).map(|uid| uid as u32),
+                subject: row.get(7),
+                local_flags: local_list(8),
+                local_labels: local_list(9),
+                server_flags: conflict_list("flags"),
+                server_labels: conflict_list("labels"),
+                detected_at: conflict["detected_at"].as_i64().unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+pub(super) async fn resolve_conflicts(
+    conn: &mut PgConnection,
+    ids: &[i64],
+    resolution: ConflictResolution,
+) -> Result<u64> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    let rows = sqlx::query(
+        r#"
+        SELECT id, account_id, target, conflict FROM pending_ops
+        WHERE conflict IS NOT NULL AND id = ANY($1);
+        "#,
+    )
+    .bind(ids)
+    .fetch_all(&mut *conn)
+    .await
+    .context("loading op conflicts")?;
+    for row in &rows {
+        let id: i64 = row.get(0);
+        if resolution == ConflictResolution::KeepLocal {
+            sqlx::query("UPDATE pending_ops SET conflict = NULL WHERE id = $1")
+                .bind(id)
+                .execute(&mut *conn)
+                .await
+                .context("releasing held op")?;
+            continue;
+        }
+        let conflict: serde_json::Value =
+            serde_json::from_str(&row.get::<String, _>(3)).unwrap_or_default();
+        sqlx::query(
+            r#"
+            UPDATE messages SET flags = $1, labels = $2, updated_at = $3
+            WHERE id = $4 AND account_id = $5;
+            "#,
+        )
+        .bind(conflict["flags"].to_string())
+        .bind(conflict["labels"].to_string())
+        .bind(now_ts())
+        .bind(row.get::<String, _>(2))
+        .bind(row.get::<String, _>(1))
+        .execute(&mut *conn)
+        .await
+        .context("storing server flags for conflict")?;
+        sqlx::query("DELETE FROM pending_ops WHERE id = $1")
+            .bind(id)
+            .execute(&mut *conn)
+            .await
+            .context("dropping held op")?;
+    }
+    Ok(rows.len() as u64)
+}
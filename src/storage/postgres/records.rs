@@ -0,0 +1,785 @@
+//! Postgres versions of the small per-account tables: audit log, cleanup runs, quota, server
+//! identity, reply-later, notes, summaries, translations, learned sender actions, signatures
+//! and parsed recipients. Values are read and written as stored; `PgStore` seals and opens them.
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder, Row};
+
+use crate::storage::addresses::{AddressField, AddressSuggestion, Recipient};
+use crate::storage::audit::AuditRecord;
+use crate::storage::cleanup::CleanupRun;
+use crate::storage::learning::{SenderAction, SuggestionStatus};
+use crate::storage::notes::MessageNote;
+use crate::storage::reply_later::ReplyLater;
+use crate::storage::translations::MessageTranslation;
+use crate::types::{AccountQuota, ServerIdentity, Signature, now_ts};
+
+pub(super) async fn append_audit(pool: &PgPool, record: &AuditRecord) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log (account_id, ts, actor, op, folder, destination, uids, op_ids, outcome)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9);
+        "#,
+    )
+    .bind(&record.account_id)
+    .bind(record.ts)
+    .bind(&record.actor)
+    .bind(&record.op)
+    .bind(&record.folder)
+    .bind(&record.destination)
+    .bind(serde_json::to_string(&record.uids).context("encoding audit uids")?)
+    .bind(serde_json::to_string(&record.op_ids).context("encoding audit op ids")?)
+    .bind(&record.outcome)
+    .execute(pool)
+    .await
+    .context("appending audit record")?;
+    Ok(())
+}
+
+pub(super) async fn list_audit(
+    pool: &PgPool,
+    account_id: Option<&str>,
+    since: Option<i64>,
+    limit: usize,
+) -> Result<Vec<AuditRecord>> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT account_id, ts, actor, op, folder, destination, uids, op_ids, outcome \
+         FROM audit_log WHERE 1 = 1",
+    );
+    if let Some(account_id) = account_id {
+        qb.push(" AND account_id = ").push_bind(account_id);
+    }
+    if let Some(since) = since {
+        qb.push(" AND ts >= ").push_bind(since);
+    }
+    qb.push(" ORDER BY ts DESC, id DESC LIMIT ")
+        .push_bind(limit as i64);
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("loading audit log")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditRecord {
+            account_id: row.get(0),
+            ts: row.get(1),
+            actor: row.get(2),
+            op: row.get(3),
+            folder: row.get(4),
+            destination: row.get(5),
+            uids: serde_json::from_str(&row.get::<String, _>(6)).unwrap_or_default(),
+            op_ids: serde_json::from_str(&row.get::<String, _>(7)).unwrap_or_default(),
+            outcome: row.get(8),
+        })
+        .collect())
+}
+
+pub(super) async fn append_cleanup_run(pool: &PgPool, run: &CleanupRun) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO cleanup_runs (account_id, ts, rule, action, message_ids)
+        VALUES ($1, $2, $3, $4, $5);
+        "#,
+    )
+    .bind(&run.account_id)
+    .bind(run.ts)
+    .bind(&run.rule)
+    .bind(&run.action)
+    .bind(serde_json::to_string(&run.message_ids).context("encoding cleanup message ids")?)
+    .execute(pool)
+    .await
+    .context("recording cleanup run")?;
+    Ok(())
+}
+
+pub(super) async fn list_cleanup_runs(
+    pool: &PgPool,
+    account_id: Option<&str>,
+    since: Option<i64>,
+    limit: usize,
+) -> Result<Vec<CleanupRun>> {
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT account_id, ts, rule, action, message_ids FROM cleanup_runs WHERE 1 = 1",
+    );
+    if let Some(account_id) = account_id {
+        qb.push(" AND account_id = ").push_bind(account_id);
+    }
+    if let Some(since) = since {
+        qb.push(" AND ts >= ").push_bind(since);
+    }
+    qb.push(" ORDER BY ts DESC, id DESC LIMIT ")
+        .push_bind(limit as i64);
+    let rows = qb
+        .build()
+        .fetch_all(pool)
+        .await
+        .context("loading cleanup runs")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| CleanupRun {
+            account_id: row.get(0),
+            ts: row.get(1),
+            rule: row.get(2),
+            action: row.get(3),
+            message_ids: serde_json::from_str(&row.get::<String, _>(4)).unwrap_or_default(),
+        })
+        .collect())
+}
+
+pub(super) async fn save_quota(pool: &PgPool, quota: &AccountQuota) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO account_quota (account_id, storage_used, storage_limit, messages_used, messages_limit, fetched_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (account_id) DO UPDATE SET
+            storage_used = excluded.storage_used,
+            storage_limit = excluded.storage_limit,
+            messages_used = excluded.messages_used,
+            messages_limit = excluded.messages_limit,
+            fetched_at = excluded.fetched_at;
+        "#,
+    )
+    .bind(&quota.account_id)
+    .bind(quota.storage_used.map(|n| n as i64))
+    .bind(quota.storage_limit.map(|n| n as i64))
+    .bind(quota.messages_used.map(|n| n as i64))
+    .bind(quota.messages_limit.map(|n| n as i64))
+    .bind(quota.fetched_at)
+    .execute(pool)
+    .await
+    .context("saving account quota")?;
+    Ok(())
+}
+
+pub(super) async fn load_quota(pool: &PgPool, account_id: &str) -> Result<Option<AccountQuota>> {
+    let row = sqlx::query(
+        r#"
+        SELECT storage_used, storage_limit, messages_used, messages_limit, fetched_at
+        FROM account_quota
+        WHERE account_id = $1;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_optional(pool)
+    .await
+    .context("loading account quota")?;
+    let count = |row: &sqlx::postgres::PgRow, idx: usize| {
+        row.get::<Option<i64>, _>(idx).map(|n| n.max(0) as u64)
+    };
+    Ok(row.map(|row| AccountQuota {
+        account_id: account_id.to_string(),
+        storage_used: count(&row, 0),
+        storage_limit: count(&row, 1),
+        messages_used: count(&row, 2),
+        messages_limit: count(&row, 3),
+        fetched_at: row.get(4),
+    }))
+}
+
+pub(super) async fn save_identity(pool: &PgPool, identity: &ServerIdentity) -> Result<()> {
+    let fields = serde_json::to_string(&identity.fields).context("serializing server identity")?;
+    sqlx::query(
+        r#"
+        INSERT INTO server_identity (account_id, fields, fetched_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (account_id) DO UPDATE SET
+            fields = excluded.fields,
+            fetched_at = excluded.fetched_at;
+        "#,
+    )
+    .bind(&identity.account_id)
+    .bind(fields)
+    .bind(identity.fetched_at)
+    .execute(pool)
+    .await
+    .context("saving server identity")?;
+    Ok(())
+}
+
+pub(super) async fn load_identity(
+    pool: &PgPool,
+    account_id: &str,
+) -> Result<Option<ServerIdentity>> {
+    let row = sqlx::query("SELECT fields, fetched_at FROM server_identity WHERE account_id = $1")
+        .bind(account_id)
+        .fetch_optional(pool)
+        .await
+        .context("loading server identity")?;
+    row.map(|row| {
+        let fields: BTreeMap<String, String> =
+            serde_json::from_str(&row.get::<String, _>(0)).context("parsing server identity")?;
+        Ok(ServerIdentity {
+            account_id: account_id.to_string(),
+            fields,
+            fetched_at: row.get(1),
+        })
+    })
+    .transpose()
+}
+
+pub(super) async fn set_reply_later(
+    pool: &PgPool,
+    account_id: &str,
+    message_ids: &[String],
+    due: Option<NaiveDate>,
+) -> Result<u64> {
+    let mut written = 0;
+    let due = due.map(|d| d.format("%Y-%m-%d").to_string());
+    for id in message_ids {
+        written += sqlx::query(
+            r#"
+            INSERT INTO reply_later (message_id, account_id, due_date, added_at)
+            SELECT id, account_id, $3, $4 FROM messages WHERE account_id = $1 AND id = $2
+            ON CONFLICT (message_id) DO UPDATE SET due_date = excluded.due_date;
+            "#,
+        )
+        .bind(account_id)
+        .bind(id)
+        .bind(&due)
+        .bind(now_ts())
+        .execute(pool)
+        .await
+        .context("queueing reply later")?
+        .rows_affected();
+    }
+    Ok(written)
+}
+
+pub(super) async fn clear_reply_later(
+    pool: &PgPool,
+    account_id: &str,
+    message_ids: &[String],
+) -> Result<u64> {
+    if message_ids.is_empty() {
+        return Ok(0);
+    }
+    let result =
+        sqlx::query("DELETE FROM reply_later WHERE account_id = $1 AND message_id = ANY($2)")
+            .bind(account_id)
+            .bind(message_ids)
+            .execute(pool)
+            .await
+            .context("clearing reply later")?;
+    Ok(result.rows_affected())
+}
+
+pub(super) async fn list_reply_later(pool: &PgPool, account_id: &str) -> Result<Vec<ReplyLater>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT r.message_id, r.due_date, r.added_at, m.subject, m.from_addr
+        FROM reply_later r
+        JOIN messages m ON m.id = r.message_id
+        WHERE r.account_id = $1
+        ORDER BY r.due_date IS NULL, r.due_date, r.added_at, r.message_id;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("loading reply later queue")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| ReplyLater {
+            message_id: row.get(0),
+            due: row
+                .get::<Option<String>, _>(1)
+                .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()),
+            added_at: row.get(2),
+            subject: row.get(3),
+            from: row.get(4),
+        })
+        .collect())
+}
+
+pub(super) async fn set_note(
+    pool: &PgPool,
+    account_id: &str,
+    message_id: &str,
+    note: &str,
+) -> Result<bool> {
+    let written = sqlx::query(
+        r#"
+        INSERT INTO message_notes (message_id, account_id, note, updated_at)
+        SELECT id, account_id, $3, $4 FROM messages WHERE account_id = $1 AND id = $2
+        ON CONFLICT (message_id) DO UPDATE SET
+            note = excluded.note,
+            updated_at = excluded.updated_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(message_id)
+    .bind(note)
+    .bind(now_ts())
+    .execute(pool)
+    .await
+    .context("saving message note")?
+    .rows_affected();
+    Ok(written > 0)
+}
+
+pub(super) async fn clear_note(pool: &PgPool, account_id: &str, message_id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM message_notes WHERE account_id = $1 AND message_id = $2")
+        .bind(account_id)
+        .bind(message_id)
+        .execute(pool)
+        .await
+        .context("clearing message note")?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub(super) async fn list_notes(pool: &PgPool, account_id: &str) -> Result<Vec<MessageNote>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT n.message_id, n.note, n.updated_at, m.subject, m.from_addr
+        FROM message_notes n
+        JOIN messages m ON m.id = n.message_id
+        WHERE n.account_id = $1
+        ORDER BY n.updated_at DESC, n.message_id;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("loading message notes")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| MessageNote {
+            message_id: row.get(0),
+            note: row.get(1),
+            updated_at: row.get(2),
+            subject: row.get(3),
+            from: row.get(4),
+        })
+        .collect())
+}
+
+pub(super) async fn set_summary(
+    pool: &PgPool,
+    account_id: &str,
+    message_id: &str,
+    summary: &str,
+) -> Result<bool> {
+    let written = sqlx::query(
+        r#"
+        INSERT INTO message_summaries (message_id, account_id, summary, updated_at)
+        SELECT id, account_id, $3, $4 FROM messages WHERE account_id = $1 AND id = $2
+        ON CONFLICT (message_id) DO UPDATE SET
+            summary = excluded.summary,
+            updated_at = excluded.updated_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(message_id)
+    .bind(summary)
+    .bind(now_ts())
+    .execute(pool)
+    .await
+    .context("saving message summary")?
+    .rows_affected();
+    Ok(written > 0)
+}
+
+pub(super) async fn list_summaries(
+    pool: &PgPool,
+    account_id: &str,
+) -> Result<Vec<(String, String)>> {
+    let rows =
+        sqlx::query("SELECT message_id, summary FROM message_summaries WHERE account_id = $1")
+            .bind(account_id)
+            .fetch_all(pool)
+            .await
+            .context("loading message summaries")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect())
+}
+
+pub(super) async fn set_translation(
+    pool: &PgPool,
+    account_id: &str,
+    translation: &MessageTranslation,
+) -> Result<bool> {
+    let written = sqlx::query(
+        r#"
+        INSERT INTO message_translations
+            (message_id, account_id, target, text, source_lang, source_hash, updated_at)
+        SELECT id, account_id, $3, $4, $5, $6, $7 FROM messages WHERE account_id = $1 AND id = $2
+        ON CONFLICT (message_id, target) DO UPDATE SET
+            text = excluded.text,
+            source_lang = excluded.source_lang,
+            source_hash = excluded.source_hash,
+            updated_at = excluded.updated_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(&translation.message_id)
+    .bind(&translation.target)
+    .bind(&translation.text)
+    .bind(&translation.source_lang)
+    .bind(&translation.source_hash)
+    .bind(translation.updated_at)
+    .execute(pool)
+    .await
+    .context("saving message translation")?
+    .rows_affected();
+    Ok(written > 0)
+}
+
+pub(super) async fn get_translation(
+    pool: &PgPool,
+    account_id: &str,
+    message_id: &str,
+    target: &str,
+) -> Result<Option<MessageTranslation>> {
+    let row = sqlx::query(
+        r#"
+        SELECT message_id, target, text, source_lang, source_hash, updated_at
+        FROM message_translations
+        WHERE account_id = $1 AND message_id = $2 AND target = $3;
+        "#,
+    )
+    .bind(account_id)
+    .bind(message_id)
+    .bind(target)
+    .fetch_optional(pool)
+    .await
+    .context("loading message translation")?;
+    Ok(row.map(|row| MessageTranslation {
+        message_id: row.get(0),
+        target: row.get(1),
+        text: row.get(2),
+        source_lang: row.get(3),
+        source_hash: row.get(4),
+        updated_at: row.get(5),
+    }))
+}
+
+pub(super) async fn save_signature(pool: &PgPool, signature: &Signature) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO signatures (account_id, alias, body, above_quote, updated_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (account_id, alias) DO UPDATE SET
+            body = excluded.body,
+            above_quote = excluded.above_quote,
+            updated_at = excluded.updated_at;
+        "#,
+    )
+    .bind(&signature.account_id)
+    .bind(signature.alias.as_deref().unwrap_or(""))
+    .bind(&signature.body)
+    .bind(i64::from(signature.above_quote))
+    .bind(now_ts())
+    .execute(pool)
+    .await
+    .context("upserting signature")?;
+    Ok(())
+}
+
+pub(super) async fn load_signature(
+    pool: &PgPool,
+    account_id: &str,
+    alias: Option<&str>,
+) -> Result<Option<Signature>> {
+    let row = sqlx::query(
+        r#"
+        SELECT alias, body, above_quote
+        FROM signatures
+        WHERE account_id = $1 AND (alias = $2 OR alias = '')
+        ORDER BY alias = $2 DESC
+        LIMIT 1;
+        "#,
+    )
+    .bind(account_id)
+    .bind(alias.unwrap_or(""))
+    .fetch_optional(pool)
+    .await
+    .context("loading signature")?;
+    Ok(row.map(|row| {
+        let alias: String = row.get(0);
+        Signature {
+            account_id: account_id.to_string(),
+            alias: (!alias.is_empty()).then_some(alias),
+            body: row.get(1),
+            above_quote: row.get::<i64, _>(2) == 1,
+        }
+    }))
+}
+
+pub(super) async fn clear_signature(
+    pool: &PgPool,
+    account_id: &str,
+    alias: Option<&str>,
+) -> Result<bool> {
+    let removed = sqlx::query("DELETE FROM signatures WHERE account_id = $1 AND alias = $2")
+        .bind(account_id)
+        .bind(alias.unwrap_or(""))
+        .execute(pool)
+        .await
+        .context("deleting signature")?
+        .rows_affected();
+    Ok(removed > 0)
+}
+
+#[allow(clippy::too_many_arguments)] // the row key, its stored sender and the increment
+pub(super) async fn record_sender_action(
+    pool: &PgPool,
+    account_id: &str,
+    sender_key: &str,
+    sender: &str,
+    action: &str,
+    label: &str,
+    count: i64,
+    threshold: i64,
+) -> Result<Option<i64>> {
+    let now = now_ts();
+    let mut tx = pool.begin().await.context("beginning sender action tx")?;
+    sqlx::query(
+        r#"
+        INSERT INTO sender_actions
+            (account_id, sender_key, action, label, sender, count, status, first_at, last_at)
+        VALUES ($1, $2, $3, $4, $5, $6, 'counting', $7, $7)
+        ON CONFLICT (account_id, sender_key, action, label) DO UPDATE SET
+            count = sender_actions.count + excluded.count,
+            sender = excluded.sender,
+            last_at = excluded.last_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(sender_key)
+    .bind(action)
+    .bind(label)
+    .bind(sender)
+    .bind(count)
+    .bind(now)
+    .execute(&mut *tx)
+    .await
+    .context("recording sender action")?;
+    let promoted = sqlx::query(
+        r#"
+        UPDATE sender_actions SET status = 'pending'
+        WHERE account_id = $1 AND sender_key = $2 AND action = $3 AND label = $4
+          AND status = 'counting' AND count >= $5
+        RETURNING count;
+        "#,
+    )
+    .bind(account_id)
+    .bind(sender_key)
+    .bind(action)
+    .bind(label)
+    .bind(threshold)
+    .fetch_optional(&mut *tx)
+    .await
+    .context("promoting sender action")?
+    .map(|row| row.get(0));
+    tx.commit().await.context("committing sender action tx")?;
+    Ok(promoted)
+}
+
+pub(super) async fn set_sender_action_status(
+    pool: &PgPool,
+    account_id: &str,
+    sender_key: &str,
+    action: &str,
+    label: &str,
+    status: SuggestionStatus,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sender_actions SET status = $5
+        WHERE account_id = $1 AND sender_key = $2 AND action = $3 AND label = $4;
+        "#,
+    )
+    .bind(account_id)
+    .bind(sender_key)
+    .bind(action)
+    .bind(label)
+    .bind(status.as_str())
+    .execute(pool)
+    .await
+    .context("updating sender action status")?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub(super) async fn list_sender_actions(
+    pool: &PgPool,
+    account_id: &str,
+    status: Option<SuggestionStatus>,
+) -> Result<Vec<SenderAction>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT sender, action, label, count, status, first_at, last_at FROM sender_actions
+        WHERE account_id = $1 AND ($2::text IS NULL OR status = $2)
+        ORDER BY last_at DESC, sender_key, action, label;
+        "#,
+    )
+    .bind(account_id)
+    .bind(status.map(SuggestionStatus::as_str))
+    .fetch_all(pool)
+    .await
+    .context("loading sender actions")?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(SenderAction {
+                sender: row.get(0),
+                action: row.get(1),
+                label: row.get(2),
+                count: row.get(3),
+                status: SuggestionStatus::parse(&row.get::<String, _>(4))?,
+                first_at: row.get(5),
+                last_at: row.get(6),
+            })
+        })
+        .collect()
+}
+
+/// Replaces the message's `message_addresses` rows with `recipients`.
+pub(super) async fn replace_recipients(
+    conn: &mut PgConnection,
+    message_id: &str,
+    recipients: &[Recipient],
+) -> Result<()> {
+    sqlx::query("DELETE FROM message_addresses WHERE message_id = $1")
+        .bind(message_id)
+        .execute(&mut *conn)
+        .await
+        .context("clearing message recipients")?;
+    if recipients.is_empty() {
+        return Ok(());
+    }
+    let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+        "INSERT INTO message_addresses (message_id, field, position, name, address) ",
+    );
+    qb.push_values(
+        recipients.iter().enumerate(),
+        |mut row, (position, recipient)| {
+            row.push_bind(message_id)
+                .push_bind(recipient.field.as_str())
+                .push_bind(position as i64)
+                .push_bind(&recipient.name)
+                .push_bind(&recipient.addr);
+        },
+    );
+    qb.push(" ON CONFLICT DO NOTHING");
+    qb.build()
+        .execute(&mut *conn)
+        .await
+        .context("storing message recipients")?;
+    Ok(())
+}
+
+pub(super) async fn list_recipients(pool: &PgPool, message_id: &str) -> Result<Vec<Recipient>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT field, name, address FROM message_addresses
+        WHERE message_id = $1
+        ORDER BY position;
+        "#,
+    )
+    .bind(message_id)
+    .fetch_all(pool)
+    .await
+    .context("loading message recipients")?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(Recipient {
+                field: AddressField::parse(row.get::<String, _>(0).as_str())?,
+                name: row.get(1),
+                addr: row.get(2),
+            })
+        })
+        .collect())
+}
+
+pub(super) async fn find_by_recipient(
+    pool: &PgPool,
+    account_id: &str,
+    address: &str,
+    fields: &[AddressField],
+    not_fields: &[AddressField],
+) -> Result<Vec<String>> {
+    if fields.is_empty() {
+        return Ok(Vec::new());
+    }
+    let names = |fields: &[AddressField]| -> Vec<&'static str> {
+        fields.iter().map(|field| field.as_str()).collect()
+    };
+    let rows = sqlx::query(
+        r#"
+        SELECT DISTINCT m.id, m.internal_date FROM messages m
+        JOIN message_addresses a ON a.message_id = m.id
+        WHERE m.account_id = $1 AND a.address = $2 AND a.field = ANY($3)
+          AND NOT EXISTS (
+              SELECT 1 FROM message_addresses x
+              WHERE x.message_id = m.id AND x.address = $2 AND x.field = ANY($4))
+        ORDER BY m.internal_date DESC, m.id;
+        "#,
+    )
+    .bind(account_id)
+    .bind(address.trim().to_lowercase())
+    .bind(names(fields))
+    .bind(names(not_fields))
+    .fetch_all(pool)
+    .await
+    .context("finding messages by recipient")?;
+    Ok(rows.into_iter().map(|row| row.get(0)).collect())
+}
+
+/// Ranked as in `addresses::suggest`; the display name comes from the newest message.
+pub(super) async fn suggest_addresses(
+    pool: &PgPool,
+    account_id: &str,
+    prefix: &str,
+    limit: usize,
+    now: i64,
+) -> Result<Vec<AddressSuggestion>> {
+    let prefix = prefix.trim().to_lowercase();
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+    let escaped = prefix
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    let rows = sqlx::query(
+        r#"
+        SELECT a.address,
+               (array_agg(a.name ORDER BY COALESCE(m.internal_date, m.created_at) DESC))[1],
+               MAX(COALESCE(m.internal_date, m.created_at)) AS last_seen,
+               COUNT(DISTINCT a.message_id) AS uses,
+               SUM(1.0 / (1.0 + GREATEST($3 - COALESCE(m.internal_date, m.created_at), 0) / 2592000.0))
+                   AS score
+        FROM message_addresses a
+        JOIN messages m ON m.id = a.message_id
+        WHERE m.account_id = $1
+          AND a.address <> (SELECT lower(email) FROM accounts WHERE id = $1)
+          AND (a.address LIKE $2 || '%' ESCAPE '\'
+               OR lower(a.name) LIKE $2 || '%' ESCAPE '\'
+               OR lower(a.name) LIKE '% ' || $2 || '%' ESCAPE '\')
+        GROUP BY a.address
+        ORDER BY score DESC, last_seen DESC, a.address
+        LIMIT $4;
+        "#,
+    )
+    .bind(account_id)
+    .bind(&escaped)
+    .bind(now)
+    .bind(limit as i64)
+    .fetch_all(pool)
+    .await
+    .context("ranking recipient suggestions")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| AddressSuggestion {
+            addr: row.get(0),
+            name: row.get(1),
+            last_seen: row.get(2),
+            uses: row.get(3),
+        })
+        .collect())
+}
@@ -0,0 +1,96 @@
+//! Startup repair for the Postgres store (see `storage::recovery`). Foreign keys always
+//! cascade here and blobs never leave the database, so only bodies that never landed and
+//! checkpoints committed without their batch need fixing.
+use anyhow::{Context, Result};
+use sqlx::{PgPool, Row};
+
+use crate::storage::recovery::{RecoveryReport, RewoundFolder};
+use crate::types::now_ts;
+
+pub(super) async fn recover(pool: &PgPool) -> Result<RecoveryReport> {
+    let mut tx = pool.begin().await.context("beginning recovery tx")?;
+    let mut report = RecoveryReport::default();
+
+    // A body pointing at a blob that never landed can't be read; drop it so it is refetched.
+    sqlx::query(
+        "DELETE FROM bodies WHERE blob_hash IS NOT NULL AND blob_hash NOT IN (SELECT hash FROM blobs)",
+    )
+    .execute(&mut *tx)
+    .await
+    .context("deleting bodies with missing blobs")?;
+    report.bodies_requeued = sqlx::query(
+        r#"
+        UPDATE messages SET body_status = 'pending', updated_at = $1
+        WHERE body_status = 'full' AND uid IS NOT NULL
+          AND id NOT IN (SELECT message_id FROM bodies);
+        "#,
+    )
+    .bind(now_ts())
+    .execute(&mut *tx)
+    .await
+    .context("requeueing messages without bodies")?
+    .rows_affected();
+
+    let rows = sqlx::query(
+        r#"
+        SELECT f.account_id, f.name, f.checkpoint_from_uid,
+               COALESCE(f.resume_uid, f.baseline_scan_uid) AS checkpoint
+        FROM folders f
+        JOIN folder_sync_state s ON s.account_id = f.account_id AND s.folder = f.name
+        WHERE s.status = 'in_progress'
+          AND f.checkpoint_from_uid IS NOT NULL
+          AND COALESCE(f.resume_uid, f.baseline_scan_uid) IS NOT NULL
+          AND NOT EXISTS (
+              SELECT 1 FROM messages m
+              WHERE m.account_id = f.account_id AND m.folder = f.name
+                AND m.uid BETWEEN f.checkpoint_from_uid
+                              AND COALESCE(f.resume_uid, f.baseline_scan_uid))
+          AND NOT EXISTS (
+              SELECT 1 FROM fetch_retries r
+              WHERE r.account_id = f.account_id AND r.folder = f.name
+                AND r.uid BETWEEN f.checkpoint_from_uid
+                              AND COALESCE(f.resume_uid, f.baseline_scan_uid));
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .context("loading interrupted folders")?;
+    for row in rows {
+        let account_id: String = row.get(0);
+        let folder: String = row.get(1);
+        let rewound_to = row.get::<i64, _>(2).saturating_sub(1).max(0);
+        let checkpoint: i64 = row.get(3);
+        // LEAST skips NULLs where SQLite's MIN returns NULL; an unset column stays unset.
+        sqlx::query(
+            r#"
+            UPDATE folders SET
+                highest_uid = CASE WHEN highest_uid IS NULL THEN NULL
+                                   ELSE LEAST(highest_uid, $3) END,
+                baseline_scan_uid = CASE WHEN baseline_scan_uid IS NULL THEN NULL
+                                         ELSE LEAST(baseline_scan_uid, $3) END,
+                resume_uid = NULL,
+                resume_modseq = NULL,
+                checkpoint_from_uid = NULL,
+                highestmodseq = NULL,
+                updated_at = $4
+            WHERE account_id = $1 AND name = $2;
+            "#,
+        )
+        .bind(&account_id)
+        .bind(&folder)
+        .bind(rewound_to)
+        .bind(now_ts())
+        .execute(&mut *tx)
+        .await
+        .context("rewinding folder checkpoint")?;
+        report.folders_rewound.push(RewoundFolder {
+            account_id,
+            folder,
+            checkpoint_uid: checkpoint as u32,
+            rewound_to: rewound_to as u32,
+        });
+    }
+
+    tx.commit().await.context("committing recovery tx")?;
+    Ok(report)
+}
@@ -0,0 +1,331 @@
+//! Postgres schema: the SQLite tables in their current shape, with `BIGINT` for integer
+//! columns (flags such as `has_attachments` stay 0/1 so both backends read rows alike) and
+//! `BYTEA` for raw bytes. Later columns are added with `ADD COLUMN IF NOT EXISTS`.
+use anyhow::{Context, Result};
+use sqlx::{Executor, PgPool};
+
+/// Serializes schema setup across clients starting at the same time (a shared cache has
+/// several); `CREATE ... IF NOT EXISTS` alone races in Postgres.
+const MIGRATION_LOCK: i64 = 0x6f74_746f_6462;
+
+const SCHEMA: &str = r#"
+CREATE TABLE IF NOT EXISTS accounts (
+    id TEXT PRIMARY KEY,
+    email TEXT NOT NULL,
+    provider TEXT NOT NULL,
+    cutoff_since TEXT NOT NULL,
+    poll_interval_minutes BIGINT NOT NULL,
+    prefetch_recent BIGINT NOT NULL,
+    safe_mode BIGINT NOT NULL,
+    folders TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    max_download_bps BIGINT,
+    folder_policies TEXT NOT NULL DEFAULT '{}',
+    encrypt_columns BIGINT NOT NULL DEFAULT 0,
+    unread_only BIGINT NOT NULL DEFAULT 0,
+    max_message_bytes BIGINT,
+    smart_folders TEXT NOT NULL DEFAULT '[]',
+    all_mail_mode BIGINT NOT NULL DEFAULT 0,
+    imap_endpoint TEXT NOT NULL DEFAULT '{}',
+    enabled BIGINT NOT NULL DEFAULT 1,
+    credential TEXT,
+    cleanup_rules TEXT NOT NULL DEFAULT '[]',
+    namespace TEXT,
+    notify_rules TEXT NOT NULL DEFAULT '[]',
+    tls_policy TEXT NOT NULL DEFAULT '{}'
+);
+
+CREATE TABLE IF NOT EXISTS folders (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    uidvalidity BIGINT,
+    highest_uid BIGINT,
+    highestmodseq BIGINT,
+    exists_count BIGINT,
+    last_sync_ts BIGINT,
+    last_uid_scan_ts BIGINT,
+    baseline_scan_uid BIGINT,
+    resume_modseq BIGINT,
+    resume_uid BIGINT,
+    checkpoint_from_uid BIGINT,
+    backfill_since TEXT,
+    attributes TEXT,
+    delimiter TEXT,
+    server_unseen BIGINT,
+    server_messages BIGINT,
+    counts_checked_at BIGINT,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    UNIQUE (account_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_folders_account ON folders(account_id);
+
+CREATE TABLE IF NOT EXISTS messages (
+    id TEXT PRIMARY KEY,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    folder TEXT NOT NULL,
+    uid BIGINT,
+    thread_id TEXT,
+    internal_date BIGINT,
+    subject TEXT,
+    from_addr TEXT,
+    to_addrs TEXT,
+    cc_addrs TEXT,
+    bcc_addrs TEXT,
+    flags TEXT,
+    labels TEXT,
+    has_attachments BIGINT NOT NULL DEFAULT 0,
+    size_bytes BIGINT,
+    raw_hash TEXT,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    body_status TEXT NOT NULL DEFAULT 'full',
+    from_name TEXT,
+    message_id_header TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_messages_account_folder ON messages(account_id, folder);
+CREATE INDEX IF NOT EXISTS idx_messages_internal_date ON messages(account_id, internal_date DESC);
+CREATE INDEX IF NOT EXISTS idx_messages_account_raw_hash ON messages(account_id, raw_hash);
+CREATE INDEX IF NOT EXISTS idx_messages_account_message_id_header
+    ON messages(account_id, message_id_header);
+
+CREATE TABLE IF NOT EXISTS blobs (
+    hash TEXT PRIMARY KEY,
+    data BYTEA NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    format BIGINT NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS bodies (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    raw_rfc822 BYTEA,
+    blob_hash TEXT,
+    sanitized_text TEXT,
+    mime_summary TEXT,
+    attachments_json TEXT,
+    sanitized_at BIGINT,
+    sanitizer_version BIGINT,
+    raw_format BIGINT NOT NULL DEFAULT 0,
+    degraded BIGINT NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS idx_bodies_blob_hash ON bodies(blob_hash);
+
+CREATE TABLE IF NOT EXISTS folder_sync_state (
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    folder TEXT NOT NULL,
+    status TEXT NOT NULL,
+    started_at BIGINT,
+    finished_at BIGINT,
+    last_modseq BIGINT,
+    last_uid BIGINT,
+    PRIMARY KEY (account_id, folder)
+);
+
+CREATE TABLE IF NOT EXISTS sync_runs (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    folder TEXT NOT NULL,
+    run_started_at BIGINT NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    added BIGINT NOT NULL,
+    updated BIGINT NOT NULL,
+    deleted BIGINT NOT NULL,
+    bytes BIGINT NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_sync_runs_account_started ON sync_runs(account_id, run_started_at);
+
+CREATE TABLE IF NOT EXISTS fetch_retries (
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    folder TEXT NOT NULL,
+    uid BIGINT NOT NULL,
+    attempts BIGINT NOT NULL,
+    last_error TEXT,
+    first_failed_at BIGINT NOT NULL,
+    last_attempt_at BIGINT NOT NULL,
+    PRIMARY KEY (account_id, folder, uid)
+);
+
+CREATE TABLE IF NOT EXISTS pending_ops (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    target TEXT NOT NULL,
+    payload TEXT,
+    created_at BIGINT NOT NULL,
+    conflict TEXT,
+    attempts BIGINT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    dead_at BIGINT
+);
+CREATE INDEX IF NOT EXISTS idx_ops_account ON pending_ops(account_id);
+
+CREATE TABLE IF NOT EXISTS threads (
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    message_id_header TEXT NOT NULL,
+    parent TEXT,
+    thread_id TEXT NOT NULL,
+    PRIMARY KEY (account_id, message_id_header)
+);
+CREATE INDEX IF NOT EXISTS idx_threads_account_thread ON threads(account_id, thread_id);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    ts BIGINT NOT NULL,
+    actor TEXT NOT NULL,
+    op TEXT NOT NULL,
+    folder TEXT NOT NULL,
+    destination TEXT,
+    uids TEXT NOT NULL,
+    op_ids TEXT NOT NULL,
+    outcome TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_audit_account_ts ON audit_log(account_id, ts);
+
+CREATE TABLE IF NOT EXISTS cleanup_runs (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    account_id TEXT NOT NULL,
+    ts BIGINT NOT NULL,
+    rule TEXT NOT NULL,
+    action TEXT NOT NULL,
+    message_ids TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_cleanup_runs_account_ts ON cleanup_runs(account_id, ts);
+
+CREATE TABLE IF NOT EXISTS account_quota (
+    account_id TEXT PRIMARY KEY,
+    storage_used BIGINT,
+    storage_limit BIGINT,
+    messages_used BIGINT,
+    messages_limit BIGINT,
+    fetched_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS server_identity (
+    account_id TEXT PRIMARY KEY,
+    fields TEXT NOT NULL,
+    fetched_at BIGINT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS reply_later (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    account_id TEXT NOT NULL,
+    due_date TEXT,
+    added_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_reply_later_account ON reply_later(account_id, due_date);
+
+CREATE TABLE IF NOT EXISTS message_notes (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    account_id TEXT NOT NULL,
+    note TEXT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_message_notes_account ON message_notes(account_id);
+
+CREATE TABLE IF NOT EXISTS message_summaries (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    account_id TEXT NOT NULL,
+    summary TEXT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_message_summaries_account ON message_summaries(account_id);
+
+CREATE TABLE IF NOT EXISTS processed_messages (
+    consumer TEXT NOT NULL,
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    processed_at BIGINT NOT NULL,
+    PRIMARY KEY (consumer, message_id)
+);
+CREATE INDEX IF NOT EXISTS idx_processed_messages_message ON processed_messages(message_id);
+
+CREATE TABLE IF NOT EXISTS message_translations (
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    account_id TEXT NOT NULL,
+    target TEXT NOT NULL,
+    text TEXT NOT NULL,
+    source_lang TEXT,
+    source_hash TEXT NOT NULL,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (message_id, target)
+);
+CREATE INDEX IF NOT EXISTS idx_message_translations_account ON message_translations(account_id);
+
+CREATE TABLE IF NOT EXISTS sender_actions (
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    sender_key TEXT NOT NULL,
+    action TEXT NOT NULL,
+    label TEXT NOT NULL DEFAULT '',
+    sender TEXT NOT NULL,
+    count BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'counting',
+    first_at BIGINT NOT NULL,
+    last_at BIGINT NOT NULL,
+    PRIMARY KEY (account_id, sender_key, action, label)
+);
+
+CREATE TABLE IF NOT EXISTS signatures (
+    account_id TEXT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    alias TEXT NOT NULL DEFAULT '',
+    body TEXT NOT NULL,
+    above_quote BIGINT NOT NULL DEFAULT 0,
+    updated_at BIGINT NOT NULL,
+    PRIMARY KEY (account_id, alias)
+);
+
+CREATE TABLE IF NOT EXISTS message_addresses (
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    field TEXT NOT NULL,
+    position BIGINT NOT NULL,
+    name TEXT,
+    address TEXT NOT NULL,
+    PRIMARY KEY (message_id, field, position)
+);
+CREATE INDEX IF NOT EXISTS idx_message_addresses_address ON message_addresses(address);
+
+CREATE OR REPLACE FUNCTION otto_audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+DROP TRIGGER IF EXISTS audit_log_append_only ON audit_log;
+CREATE TRIGGER audit_log_append_only BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION otto_audit_log_append_only();
+
+CREATE OR REPLACE FUNCTION otto_release_blob() RETURNS trigger AS $$
+BEGIN
+    IF OLD.blob_hash IS NOT NULL
+        AND (TG_OP = 'DELETE' OR OLD.blob_hash IS DISTINCT FROM NEW.blob_hash) THEN
+        DELETE FROM blobs WHERE hash = OLD.blob_hash
+            AND NOT EXISTS (SELECT 1 FROM bodies WHERE blob_hash = OLD.blob_hash);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+DROP TRIGGER IF EXISTS bodies_release_blob ON bodies;
+CREATE TRIGGER bodies_release_blob AFTER DELETE OR UPDATE OF blob_hash ON bodies
+    FOR EACH ROW EXECUTE FUNCTION otto_release_blob();
+"#;
+
+/// Creates the schema if missing. Runs in one transaction under an advisory lock, so a client
+/// never sees half of it.
+pub(crate) async fn migrate(pool: &PgPool) -> Result<()> {
+    let mut tx = pool.begin().await.context("beginning migration tx")?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MIGRATION_LOCK)
+        .execute(&mut *tx)
+        .await
+        .context("taking migration lock")?;
+    (&mut *tx)
+        .execute(SCHEMA)
+        .await
+        .context("running migrations")?;
+    tx.commit().await.context("committing migrations")?;
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! `account_quota`: the latest storage usage each account's server reported (IMAP QUOTA), one
+//! row per account, replaced on every sync that reads it.
+use anyhow::{Context, Result};
+use sqlx::{Row, SqlitePool};
+
+use crate::types::AccountQuota;
+
+pub(crate) async fn ensure_quota_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS account_quota (
+            account_id TEXT PRIMARY KEY,
+            storage_used INTEGER,
+            storage_limit INTEGER,
+            messages_used INTEGER,
+            messages_limit INTEGER,
+            fetched_at INTEGER NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating account_quota table")?;
+    Ok(())
+}
+
+pub(crate) async fn save(pool: &SqlitePool, quota: &AccountQuota) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO account_quota (account_id, storage_used, storage_limit, messages_used, messages_limit, fetched_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT(account_id) DO UPDATE SET
+            storage_used = excluded.storage_used,
+            storage_limit = excluded.storage_limit,
+            messages_used = excluded.messages_used,
+            messages_limit = excluded.messages_limit,
+            fetched_at = excluded.fetched_at;
+        "#,
+    )
+    .bind(&quota.account_id)
+    .bind(quota.storage_used.map(|n| n as i64))
+    .bind(quota.storage_limit.map(|n| n as i64))
+    .bind(quota.messages_used.map(|n| n as i64))
+    .bind(quota.messages_limit.map(|n| n as i64))
+    .bind(quota.fetched_at)
+    .execute(pool)
+    .await
+    .context("saving account quota")?;
+    Ok(())
+}
+
+pub(crate) async fn load(pool: &SqlitePool, account_id: &str) -> Result<Option<AccountQuota>> {
+    let row = sqlx::query(
+        r#"
+        SELECT storage_used, storage_limit, messages_used, messages_limit, fetched_at
+        FROM account_quota
+        WHERE account_id = ?1;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_optional(pool)
+    .await
+    .context("loading account quota")?;
+    let count = |row: &sqlx::sqlite::SqliteRow, idx: usize| {
+        row.get::<Option<i64>, _>(idx).map(|n| n.max(0) as u64)
+    };
+    Ok(row.map(|row| AccountQuota {
+        account_id: account_id.to_string(),
+        storage_used: count(&row, 0),
+        storage_limit: count(&row, 1),
+        messages_used: count(&row, 2),
+        messages_limit: count(&row, 3),
+        fetched_at: row.get(4),
+    }))
+}
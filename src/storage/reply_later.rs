@@ -0,0 +1,154 @@
+//! Local "reply later" queue: messages set aside to answer in one batch, each with an optional
+//! due date. Unlike triage's snooze label it never touches the server; rows go away with their
+//! message.
+use anyhow::{Context, Result, anyhow};
+use chrono::{Days, NaiveDate};
+use sqlx::{QueryBuilder, Row, Sqlite, SqlitePool};
+
+use crate::types::now_ts;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplyLater {
+    pub message_id: String,
+    pub due: Option<NaiveDate>,
+    pub added_at: i64,
+    /// The message's subject and sender, for listings.
+    pub subject: Option<String>,
+    pub from: Option<String>,
+}
+
+impl ReplyLater {
+    /// Past its due date as of `today` (a message due today is not overdue yet).
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.due.is_some_and(|due| due < today)
+    }
+}
+
+/// Parses a due date: `YYYY-MM-DD`, `+N` / `+Nd` days from `today`, `today`, `tomorrow`, or
+/// an empty string for no due date.
+pub fn parse_due(raw: &str, today: NaiveDate) -> Result<Option<NaiveDate>> {
+    let raw = raw.trim();
+    let days = match raw.to_ascii_lowercase().as_str() {
+        "" => return Ok(None),
+        "today" => Some(0),
+        "tomorrow" => Some(1),
+        other => other
+            .strip_prefix('+')
+            .map(|n| n.strip_suffix('d').unwrap_or(n))
+            .and_then(|n| n.parse::<u64>().ok()),
+    };
+    if let Some(days) = days {
+        return today
+            .checked_add_days(Days::new(days))
+            .map(Some)
+            .ok_or_else(|| anyhow!("due date out of range"));
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .map(Some)
+        .map_err(|_| anyhow!("{:?}: expected YYYY-MM-DD, +N days, today or tomorrow", raw))
+}
+
+pub(crate) async fn ensure_reply_later_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS reply_later (
+            message_id TEXT PRIMARY KEY,
+            account_id TEXT NOT NULL,
+            due_date TEXT,
+            added_at INTEGER NOT NULL,
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_reply_later_account ON reply_later(account_id, due_date);
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating reply_later table")?;
+    Ok(())
+}
+
+/// Queues `message_ids` of the account (ids it does not own are ignored), or updates the due
+/// date of ones already queued. Returns rows written.
+pub(crate) async fn set(
+    pool: &SqlitePool,
+    account_id: &str,
+    message_ids: &[String],
+    due: Option<NaiveDate>,
+) -> Result<u64> {
+    let mut written = 0;
+    let due = due.map(|d| d.format("%Y-%m-%d").to_string());
+    for id in message_ids {
+        written += sqlx::query(
+            r#"
+            INSERT INTO reply_later (message_id, account_id, due_date, added_at)
+            SELECT id, account_id, ?3, ?4 FROM messages WHERE account_id = ?1 AND id = ?2
+            ON CONFLICT(message_id) DO UPDATE SET due_date = excluded.due_date;
+            "#,
+        )
+        .bind(account_id)
+        .bind(id)
+        .bind(&due)
+        .bind(now_ts())
+        .execute(pool)
+        .await
+        .context("queueing reply later")?
+        .rows_affected();
+    }
+    Ok(written)
+}
+
+pub(crate) async fn clear(
+    pool: &SqlitePool,
+    account_id: &str,
+    message_ids: &[String],
+) -> Result<u64> {
+    if message_ids.is_empty() {
+        return Ok(0);
+    }
+    let mut qb: QueryBuilder<Sqlite> =
+        QueryBuilder::new("DELETE FROM reply_later WHERE account_id = ");
+    qb.push_bind(account_id);
+    qb.push(" AND message_id IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in message_ids {
+            separated.push_bind(id);
+        }
+    }
+    qb.push(")");
+    let result = qb
+        .build()
+        .execute(pool)
+        .await
+        .context("clearing reply later")?;
+    Ok(result.rows_affected())
+}
+
+/// The account's queue, soonest due first; undated entries last, oldest first.
+pub(crate) async fn list(pool: &SqlitePool, account_id: &str) -> Result<Vec<ReplyLater>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT r.message_id, r.due_date, r.added_at, m.subject, m.from_addr
+        FROM reply_later r
+        JOIN messages m ON m.id = r.message_id
+        WHERE r.account_id = ?1
+        ORDER BY r.due_date IS NULL, r.due_date, r.added_at, r.message_id;
+        "#,
+    )
+    .bind(account_id)
+    .fetch_all(pool)
+    .await
+    .context("loading reply later queue")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| ReplyLater {
+            message_id: row.get(0),
+            due: row
+                .get::<Option<String>, _>(1)
+                .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()),
+            added_at: row.get(2),
+            subject: row.get(3),
+            from: row.get(4),
+        })
+        .collect())
+}
@@ -0,0 +1,1218 @@
+//! Backend-neutral storage interface. The sync engine and app talk to `dyn MailStore`; the
+//! concrete backend is picked from `OTTO_DATABASE_URL` by `open_store` (SQLite by default).
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use chrono::NaiveDate;
+
+use crate::storage::addresses::{AddressField, AddressSuggestion, Recipient};
+use crate::storage::audit::AuditRecord;
+use crate::storage::cleanup::CleanupRun;
+use crate::storage::compression::CompressStats;
+use crate::storage::crypto::ColumnCipher;
+use crate::storage::db::{Database, FetchedBodyUpdate, FolderStateUpdate, MessageLocationUpdate};
+use crate::storage::learning::{SenderAction, SuggestionStatus};
+use crate::storage::notes::MessageNote;
+use crate::storage::ops::{
+    ConflictResolution, MessageOp, OpConflict, PendingFlagOp, PendingOp, ReplayOp,
+};
+use crate::storage::postgres::PgStore;
+use crate::storage::recovery::RecoveryReport;
+use crate::storage::reply_later::ReplyLater;
+use crate::storage::translations::MessageTranslation;
+use crate::types::{
+    Account, AccountQuota, BodyRecord, FetchRetry, FolderCounts, FolderRole, FolderState,
+    MailboxInfo, MessageRecord, ServerIdentity, Signature, SyncRunRecord,
+};
+
+/// Which backend a database URL selects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageBackend {
+    /// `otto.db` in the default data directory.
+    SqliteDefault,
+    SqliteFile(PathBuf),
+    /// Shared server-side cache (`postgres://user@host/db`).
+    Postgres(String),
+}
+
+impl StorageBackend {
+    /// Parses `OTTO_DATABASE_URL`; `None` or an empty value keeps the default SQLite file.
+    pub fn from_url(url: Option<&str>) -> Result<Self> {
+        let Some(url) = url.map(str::trim).filter(|u| !u.is_empty()) else {
+            return Ok(Self::SqliteDefault);
+        };
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            return Ok(Self::Postgres(url.to_string()));
+        }
+        if let Some(rest) = url.strip_prefix("sqlite:") {
+            let path = rest.trim_start_matches("//");
+            let path = path.split('?').next().unwrap_or_default();
+            if path.is_empty() {
+                bail!("sqlite database URL has no file path: {}", url);
+            }
+            return Ok(Self::SqliteFile(PathBuf::from(path)));
+        }
+        bail!(
+            "unsupported database URL {} (expected sqlite:// or postgres://)",
+            url
+        )
+    }
+
+    /// The file a SQLite backend opens; `None` for a server.
+    pub fn sqlite_path(&self) -> Result<Option<PathBuf>> {
+        match self {
+            Self::SqliteDefault => Database::default_path().map(Some),
+            Self::SqliteFile(path) => Ok(Some(path.clone())),
+            Self::Postgres(_) => Ok(None),
+        }
+    }
+}
+
+/// Where raw RFC822 bodies are written (`OTTO_BODY_STORAGE`). Reads handle both layouts, so
+/// switching modes never strands existing rows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BodyStorage {
+    /// Each body row carries its own raw bytes.
+    #[default]
+    Inline,
+    /// Raw bytes live once per content hash in `blobs`; body rows reference them, so a message
+    /// cached in several folders (or accounts) is stored once.
+    ContentAddressed,
+    /// Content-addressed, but blobs of at least `min_file_bytes` are written to files under
+    /// `<db>.blobs/` instead of into the database.
+    Hybrid { min_file_bytes: u64 },
+}
+
+impl BodyStorage {
+    /// Offload threshold for `hybrid` when `OTTO_BLOB_OFFLOAD_KB` is unset.
+    pub const DEFAULT_OFFLOAD_KB: u64 = 256;
+
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "inline" => Ok(Self::Inline),
+            "content" | "content-addressed" | "cas" => Ok(Self::ContentAddressed),
+            "hybrid" => Ok(Self::Hybrid {
+                min_file_bytes: Self::DEFAULT_OFFLOAD_KB * 1024,
+            }),
+            other => bail!(
+                "unknown body storage mode {} (expected inline, content or hybrid)",
+                other
+            ),
+        }
+    }
+}
+
+/// Opens the store selected by `url` (see `StorageBackend::from_url`).
+pub async fn open_store(url: Option<&str>) -> Result<Arc<dyn MailStore>> {
+    match StorageBackend::from_url(url)? {
+        StorageBackend::SqliteDefault => Ok(Arc::new(Database::new_default().await?)),
+        StorageBackend::SqliteFile(path) => Ok(Arc::new(Database::open_at(path).await?)),
+        StorageBackend::Postgres(url) => Ok(Arc::new(PgStore::connect(&url).await?)),
+    }
+}
+
+/// Everything the sync engine and app need from a mail cache. Implementations own their
+/// transactions: each method is atomic on its own.
+#[async_trait]
+pub trait MailStore: Send + Sync {
+    /// Human-readable location for logs (file path or server).
+    fn describe(&self) -> String;
+
+    /// Seals/opens the account's encrypted columns with `cipher` from now on (`None` stops).
+    fn register_cipher(&self, account_id: &str, cipher: Option<ColumnCipher>);
+
+    /// Layout for raw bodies written from now on.
+    fn set_body_storage(&self, mode: BodyStorage);
+
+    /// Deletes offloaded blob files no longer referenced; returns files removed. Only call
+    /// while no sync is writing.
+    async fn purge_blob_files(&self) -> Result<usize>;
+
+    /// Removes rows orphaned by interrupted writes, requeues bodies that never landed and
+    /// rewinds folders checkpointed past their stored messages. Only call while no sync is
+    /// writing.
+    async fn recover_interrupted_writes(&self) -> Result<RecoveryReport>;
+
+    async fn list_accounts(&self) -> Result<Vec<Account>>;
+    async fn save_account(&self, account: &Account) -> Result<()>;
+    /// Re-encrypts the account's stored columns from `from` to `to`; returns rows rewritten.
+    async fn reseal_account(
+        &self,
+        account_id: &str,
+        from: Option<&ColumnCipher>,
+        to: Option<&ColumnCipher>,
+    ) -> Result<usize>;
+
+    async fn list_folders(&self, account_id: &str) -> Result<Vec<FolderState>>;
+    async fn record_discovered_folders(
+        &self,
+        account_id: &str,
+        mailboxes: &[MailboxInfo],
+    ) -> Result<()>;
+    async fn list_discovered_folders(&self, account_id: &str) -> Result<Vec<MailboxInfo>>;
+    /// Stores server-reported folder counts (STATUS/LIST-STATUS).
+    async fn save_folder_counts(&self, account_id: &str, counts: &[FolderCounts]) -> Result<()>;
+    /// The last server-reported counts per folder name.
+    async fn load_folder_counts(&self, account_id: &str) -> Result<HashMap<String, FolderCounts>>;
+    /// The account's folder for a special-use role (falls back to the English Gmail name).
+    async fn role_folder(&self, account_id: &str, role: FolderRole) -> Result<String>;
+    async fn upsert_folder_state(
+        &self,
+        account_id: &str,
+        name: &str,
+        update: &FolderStateUpdate,
+    ) -> Result<FolderState>;
+    async fn record_folder_sync_start(
+        &self,
+        account_id: &str,
+        folder: &str,
+        baseline_modseq: Option<u64>,
+        baseline_uid: Option<u32>,
+    ) -> Result<()>;
+    #[allow(clippy::too_many_arguments)] // mirrors Database::commit_folder_batch
+    async fn commit_folder_batch(
+        &self,
+        account_id: &str,
+        folder: &str,
+        messages: &[MessageRecord],
+        bodies: &[BodyRecord],
+        location_updates: &[MessageLocationUpdate],
+        flag_updates: &[(u32, Vec<String>, Vec<String>)],
+        folder_update: &FolderStateUpdate,
+        sync_status: &str,
+        last_modseq: Option<u64>,
+        last_uid: Option<u32>,
+    ) -> Result<()>;
+    async fn commit_backfill_batch(
+        &self,
+        account_id: &str,
+        folder: &str,
+        messages: &[MessageRecord],
+        bodies: &[BodyRecord],
+        location_updates: &[MessageLocationUpdate],
+        backfill_since: Option<NaiveDate>,
+    ) -> Result<()>;
+    async fn clear_folder_backfill(&self, account_id: &str, folder: &str) -> Result<()>;
+    async fn record_sync_runs(&self, runs: &[SyncRunRecord]) -> Result<()>;
+    /// Each folder's latest sync run for the account.
+    async fn load_latest_sync_runs(&self, account_id: &str) -> Result<Vec<SyncRunRecord>>;
+    /// Appends to the append-only audit log of destructive server commands.
+    async fn record_audit(&self, record: &AuditRecord) -> Result<()>;
+    /// Audit records, newest first, optionally for one account and from `since` (unix secs).
+    async fn load_audit_log(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>>;
+    async fn record_cleanup_run(&self, run: &CleanupRun) -> Result<()>;
+    /// Cleanup runs, newest first, optionally for one account and from `since` (unix secs).
+    async fn load_cleanup_runs(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<CleanupRun>>;
+    /// Replaces the account's stored quota.
+    async fn save_account_quota(&self, quota: &AccountQuota) -> Result<()>;
+    /// The quota last read from the account's server, if any.
+    async fn load_account_quota(&self, account_id: &str) -> Result<Option<AccountQuota>>;
+    /// Replaces the account's stored server ID answer.
+    async fn save_server_identity(&self, identity: &ServerIdentity) -> Result<()>;
+    /// What the account's server last said in answer to ID, if it supports ID.
+    async fn load_server_identity(&self, account_id: &str) -> Result<Option<ServerIdentity>>;
+    /// Messages (no bodies) received before `before`, oldest first, without `Deleted` rows.
+    async fn load_messages_before(
+        &self,
+        account_id: &str,
+        before: i64,
+    ) -> Result<Vec<MessageRecord>>;
+    /// Messages (no bodies) received at or after `since`, oldest first, without `Deleted` rows.
+    async fn load_messages_since(&self, account_id: &str, since: i64)
+    -> Result<Vec<MessageRecord>>;
+    /// Messages (no bodies) first cached at or after `since`, oldest first, without `Deleted`
+    /// rows.
+    async fn load_messages_cached_since(
+        &self,
+        account_id: &str,
+        since: i64,
+    ) -> Result<Vec<MessageRecord>>;
+    /// The cached messages among `ids` (no bodies).
+    async fn load_messages_by_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<MessageRecord>>;
+    /// Adds messages to the local reply-later queue, or changes their due date.
+    async fn set_reply_later(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+        due: Option<NaiveDate>,
+    ) -> Result<u64>;
+    async fn clear_reply_later(&self, account_id: &str, message_ids: &[String]) -> Result<u64>;
+    /// Soonest due first, undated entries last.
+    async fn load_reply_later(&self, account_id: &str) -> Result<Vec<ReplyLater>>;
+    /// Sets a message's private note; false when the account has no such message.
+    async fn set_message_note(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        note: &str,
+    ) -> Result<bool>;
+    async fn clear_message_note(&self, account_id: &str, message_id: &str) -> Result<bool>;
+    /// Most recently edited first.
+    async fn load_message_notes(&self, account_id: &str) -> Result<Vec<MessageNote>>;
+    /// Stores a summarizer's summary of a message; false when the account has no such message.
+    async fn set_message_summary(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        summary: &str,
+    ) -> Result<bool>;
+    /// Keyed by message id.
+    async fn load_message_summaries(&self, account_id: &str) -> Result<HashMap<String, String>>;
+    /// Atomically claims up to `limit` messages first cached at or after `cached_since` that
+    /// `consumer` has not processed yet, oldest first; concurrent claims under one consumer
+    /// name never return the same message.
+    async fn claim_unprocessed_messages(
+        &self,
+        consumer: &str,
+        account_id: &str,
+        cached_since: i64,
+        limit: usize,
+    ) -> Result<Vec<MessageRecord>>;
+    /// Hands claimed messages back to `consumer`'s unprocessed set, e.g. after a failed run.
+    async fn release_processed_messages(
+        &self,
+        consumer: &str,
+        message_ids: &[String],
+    ) -> Result<u64>;
+    /// Caches a translation of the account's message; false when there is no such message.
+    async fn set_message_translation(
+        &self,
+        account_id: &str,
+        translation: &MessageTranslation,
+    ) -> Result<bool>;
+    /// The cached translation of the account's message into `target`, if any.
+    async fn load_message_translation(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        target: &str,
+    ) -> Result<Option<MessageTranslation>>;
+    /// Sets the signature of `signature.alias` (the account default for `None`).
+    async fn save_signature(&self, signature: &Signature) -> Result<()>;
+    /// The signature for a send-as alias, falling back to the account default.
+    async fn load_signature(
+        &self,
+        account_id: &str,
+        alias: Option<&str>,
+    ) -> Result<Option<Signature>>;
+    /// Removes the signature of `alias` (the account default for `None`); false when none was
+    /// set.
+    async fn clear_signature(&self, account_id: &str, alias: Option<&str>) -> Result<bool>;
+    /// Counts `count` messages from `sender` (lowercased address) the user applied `action`
+    /// (and `label`) to; the pair's count when that brought it to `threshold` (see
+    /// `crate::learn`).
+    async fn record_sender_action(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        count: i64,
+        threshold: i64,
+    ) -> Result<Option<i64>>;
+    /// Settles a sender/action pair; false when it was never recorded.
+    async fn set_sender_action_status(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        status: SuggestionStatus,
+    ) -> Result<bool>;
+    /// The account's sender/action pairs, optionally with one status, most recent first.
+    async fn load_sender_actions(
+        &self,
+        account_id: &str,
+        status: Option<SuggestionStatus>,
+    ) -> Result<Vec<SenderAction>>;
+    async fn load_recipients(&self, message_id: &str) -> Result<Vec<Recipient>>;
+    /// Messages listing `address` in one of `fields` and in none of `not_fields` (e.g. To but
+    /// not Cc), newest first.
+    async fn find_messages_by_recipient(
+        &self,
+        account_id: &str,
+        address: &str,
+        fields: &[AddressField],
+        not_fields: &[AddressField],
+    ) -> Result<Vec<String>>;
+    /// Recipient suggestions for compose: addresses from the account's To/Cc/Bcc lists starting
+    /// with `prefix` (or with a name word that does), ranked by frequency and recency.
+    async fn suggest_addresses(
+        &self,
+        account_id: &str,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<AddressSuggestion>>;
+
+    async fn load_message_ids_by_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<HashMap<u32, String>>;
+    async fn load_uid_to_message_id_map_by_folder(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, String>>;
+    async fn load_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (Vec<String>, Vec<String>)>>;
+    /// `uid -> (message id, raw_hash)` for the folder's rows with a stored body.
+    async fn load_raw_hashes_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (String, String)>>;
+    async fn unread_counts(&self, account_id: &str) -> Result<HashMap<String, u32>>;
+    async fn unread_label_counts(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<String, u32>>;
+    async fn batch_update_message_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+        updates: &[(u32, Vec<String>, Vec<String>)],
+    ) -> Result<()>;
+    async fn load_existing_message_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<HashSet<String>>;
+    /// Maps each `Message-ID` in `headers` to a message already cached outside `folder`.
+    async fn load_message_ids_by_header(
+        &self,
+        account_id: &str,
+        folder: &str,
+        headers: &[String],
+    ) -> Result<HashMap<String, String>>;
+    async fn dedupe_fallback_messages_by_raw_hash(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<usize>;
+    async fn load_messages(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>>;
+    /// Every message of the thread `id` (a message id or thread id) belongs to, with bodies.
+    async fn load_thread(
+        &self,
+        account_id: &str,
+        id: &str,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>>;
+
+    async fn load_pending_body_targets(
+        &self,
+        account_id: &str,
+        skip_folders: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, String, u32)>>;
+    async fn request_oversized_bodies(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>>;
+    /// Fetch targets for specific messages regardless of body status, newest first.
+    async fn load_body_targets(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>>;
+    async fn store_fetched_bodies(
+        &self,
+        account_id: &str,
+        updates: &[FetchedBodyUpdate],
+    ) -> Result<()>;
+    /// Deletes the stored bodies of a folder's messages and marks them `pending` again.
+    async fn drop_folder_bodies(&self, account_id: &str, folder: &str) -> Result<u64>;
+    /// Raw messages whose sanitized text predates `below_version`, by message id after `after`.
+    async fn load_bodies_to_resanitize(
+        &self,
+        account_id: &str,
+        below_version: i64,
+        after: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, Vec<u8>)>>;
+    /// One message's stored raw RFC822, if it has one.
+    async fn load_raw_message(&self, account_id: &str, message_id: &str)
+    -> Result<Option<Vec<u8>>>;
+    /// Replaces stored sanitized text/MIME summary (and `has_attachments`) without touching raw.
+    async fn store_resanitized_bodies(
+        &self,
+        account_id: &str,
+        bodies: &[(bool, BodyRecord)],
+    ) -> Result<()>;
+    /// Compresses raw messages stored before compression (`otto compress-bodies`).
+    async fn compress_raw_bodies(&self, account_id: &str) -> Result<CompressStats>;
+    /// Returns freed pages to the filesystem.
+    async fn vacuum(&self) -> Result<()>;
+
+    async fn apply_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize>;
+    async fn record_applied_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize>;
+
+    /// Queued flag ops (mark read, add label) for `uids` of `folder`.
+    async fn load_pending_flag_ops(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<Vec<PendingFlagOp>>;
+    /// Every queued flag op for the account with its message's current location.
+    async fn load_replayable_flag_ops(&self, account_id: &str) -> Result<Vec<ReplayOp>>;
+    /// Every queued archive/move/copy/delete op for the account at its pre-op location.
+    async fn load_replayable_location_ops(&self, account_id: &str) -> Result<Vec<ReplayOp>>;
+    /// Restores the messages touched by rejected ops and drops the ops.
+    async fn rollback_pending_ops(&self, ids: &[i64]) -> Result<u64>;
+    async fn clear_pending_ops(&self, ids: &[i64]) -> Result<u64>;
+    /// Gives messages moved by ops `links` (op id, uid) their uids in `folder` from the
+    /// server's `COPYUID`, if `uid_validity` matches the cached one. Returns the rows linked.
+    async fn link_moved_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uid_validity: u32,
+        links: &[(i64, u32)],
+    ) -> Result<u64>;
+    /// Counts a server rejection; ops reaching `ops::MAX_OP_ATTEMPTS` are dead-lettered.
+    /// Returns how many of `ids` are dead.
+    async fn record_op_failures(&self, ids: &[i64], error: &str) -> Result<u64>;
+    /// Releases dead-lettered ops for the next replay.
+    async fn retry_dead_ops(&self, ids: &[i64]) -> Result<u64>;
+    /// Every op still queued for the account, oldest first.
+    async fn list_pending_ops(&self, account_id: &str) -> Result<Vec<PendingOp>>;
+    /// Counts one more failed attempt for each `(uid, error)` of `folder`.
+    async fn record_fetch_failures(
+        &self,
+        account_id: &str,
+        folder: &str,
+        failures: &[(u32, String)],
+    ) -> Result<()>;
+    /// The folder's failed fetches, lowest UID first.
+    async fn load_fetch_retries(&self, account_id: &str, folder: &str) -> Result<Vec<FetchRetry>>;
+    async fn clear_fetch_retries(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<u64>;
+    /// Holds queued ops back from replay because the server changed their message.
+    async fn mark_op_conflicts(
+        &self,
+        ids: &[i64],
+        server_flags: &[String],
+        server_labels: &[String],
+    ) -> Result<u64>;
+    /// Queued ops held back by a conflict, oldest first.
+    async fn list_op_conflicts(&self, account_id: &str) -> Result<Vec<OpConflict>>;
+    /// Releases held ops for replay or drops them in favor of the server's state.
+    async fn resolve_op_conflicts(
+        &self,
+        ids: &[i64],
+        resolution: ConflictResolution,
+    ) -> Result<u64>;
+
+    async fn delete_messages_by_folder(&self, account_id: &str, folder: &str) -> Result<u64>;
+    async fn delete_messages_by_folder_and_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<u64>;
+}
+
+#[async_trait]
+impl MailStore for Database {
+    fn describe(&self) -> String {
+        format!("sqlite {}", self.path().display())
+    }
+
+    fn register_cipher(&self, account_id: &str, cipher: Option<ColumnCipher>) {
+        Database::register_cipher(self, account_id, cipher)
+    }
+
+    fn set_body_storage(&self, mode: BodyStorage) {
+        Database::set_body_storage(self, mode)
+    }
+
+    async fn purge_blob_files(&self) -> Result<usize> {
+        Database::purge_blob_files(self).await
+    }
+
+    async fn recover_interrupted_writes(&self) -> Result<RecoveryReport> {
+        Database::recover_interrupted_writes(self).await
+    }
+
+    async fn list_accounts(&self) -> Result<Vec<Account>> {
+        Database::list_accounts(self).await
+    }
+
+    async fn save_account(&self, account: &Account) -> Result<()> {
+        Database::save_account(self, account).await
+    }
+
+    async fn reseal_account(
+        &self,
+        account_id: &str,
+        from: Option<&ColumnCipher>,
+        to: Option<&ColumnCipher>,
+    ) -> Result<usize> {
+        Database::reseal_account(self, account_id, from, to).await
+    }
+
+    async fn list_folders(&self, account_id: &str) -> Result<Vec<FolderState>> {
+        Database::list_folders(self, account_id).await
+    }
+
+    async fn record_discovered_folders(
+        &self,
+        account_id: &str,
+        mailboxes: &[MailboxInfo],
+    ) -> Result<()> {
+        Database::record_discovered_folders(self, account_id, mailboxes).await
+    }
+
+    async fn list_discovered_folders(&self, account_id: &str) -> Result<Vec<MailboxInfo>> {
+        Database::list_discovered_folders(self, account_id).await
+    }
+
+    async fn save_folder_counts(&self, account_id: &str, counts: &[FolderCounts]) -> Result<()> {
+        Database::save_folder_counts(self, account_id, counts).await
+    }
+
+    async fn load_folder_counts(&self, account_id: &str) -> Result<HashMap<String, FolderCounts>> {
+        Database::load_folder_counts(self, account_id).await
+    }
+
+    async fn role_folder(&self, account_id: &str, role: FolderRole) -> Result<String> {
+        Database::role_folder(self, account_id, role).await
+    }
+
+    async fn upsert_folder_state(
+        &self,
+        account_id: &str,
+        name: &str,
+        update: &FolderStateUpdate,
+    ) -> Result<FolderState> {
+        Database::upsert_folder_state(self, account_id, name, update).await
+    }
+
+    async fn record_folder_sync_start(
+        &self,
+        account_id: &str,
+        folder: &str,
+        baseline_modseq: Option<u64>,
+        baseline_uid: Option<u32>,
+    ) -> Result<()> {
+        Database::record_folder_sync_start(self, account_id, folder, baseline_modseq, baseline_uid)
+            .await
+    }
+
+    async fn commit_folder_batch(
+        &self,
+        account_id: &str,
+        folder: &str,
+        messages: &[MessageRecord],
+        bodies: &[BodyRecord],
+        location_updates: &[MessageLocationUpdate],
+        flag_updates: &[(u32, Vec<String>, Vec<String>)],
+        folder_update: &FolderStateUpdate,
+        sync_status: &str,
+        last_modseq: Option<u64>,
+        last_uid: Option<u32>,
+    ) -> Result<()> {
+        Database::commit_folder_batch(
+            self,
+            account_id,
+            folder,
+            messages,
+            bodies,
+            location_updates,
+            flag_updates,
+            folder_update,
+            sync_status,
+            last_modseq,
+            last_uid,
+        )
+        .await
+    }
+
+    async fn commit_backfill_batch(
+        &self,
+        account_id: &str,
+        folder: &str,
+        messages: &[MessageRecord],
+        bodies: &[BodyRecord],
+        location_updates: &[MessageLocationUpdate],
+        backfill_since: Option<NaiveDate>,
+    ) -> Result<()> {
+        Database::commit_backfill_batch(
+            self,
+            account_id,
+            folder,
+            messages,
+            bodies,
+            location_updates,
+            backfill_since,
+        )
+        .await
+    }
+
+    async fn clear_folder_backfill(&self, account_id: &str, folder: &str) -> Result<()> {
+        Database::clear_folder_backfill(self, account_id, folder).await
+    }
+
+    async fn record_sync_runs(&self, runs: &[SyncRunRecord]) -> Result<()> {
+        Database::record_sync_runs(self, runs).await
+    }
+
+    async fn load_latest_sync_runs(&self, account_id: &str) -> Result<Vec<SyncRunRecord>> {
+        Database::load_latest_sync_runs(self, account_id).await
+    }
+
+    async fn record_audit(&self, record: &AuditRecord) -> Result<()> {
+        Database::record_audit(self, record).await
+    }
+
+    async fn load_audit_log(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<AuditRecord>> {
+        Database::load_audit_log(self, account_id, since, limit).await
+    }
+
+    async fn record_cleanup_run(&self, run: &CleanupRun) -> Result<()> {
+        Database::record_cleanup_run(self, run).await
+    }
+
+    async fn load_cleanup_runs(
+        &self,
+        account_id: Option<&str>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<CleanupRun>> {
+        Database::load_cleanup_runs(self, account_id, since, limit).await
+    }
+
+    async fn save_account_quota(&self, quota: &AccountQuota) -> Result<()> {
+        Database::save_account_quota(self, quota).await
+    }
+
+    async fn load_account_quota(&self, account_id: &str) -> Result<Option<AccountQuota>> {
+        Database::load_account_quota(self, account_id).await
+    }
+
+    async fn save_server_identity(&self, identity: &ServerIdentity) -> Result<()> {
+        Database::save_server_identity(self, identity).await
+    }
+
+    async fn load_server_identity(&self, account_id: &str) -> Result<Option<ServerIdentity>> {
+        Database::load_server_identity(self, account_id).await
+    }
+
+    async fn load_messages_before(
+        &self,
+        account_id: &str,
+        before: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        Database::load_messages_before(self, account_id, before).await
+    }
+
+    async fn load_messages_since(
+        &self,
+        account_id: &str,
+        since: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        Database::load_messages_since(self, account_id, since).await
+    }
+
+    async fn load_messages_cached_since(
+        &self,
+        account_id: &str,
+        since: i64,
+    ) -> Result<Vec<MessageRecord>> {
+        Database::load_messages_cached_since(self, account_id, since).await
+    }
+
+    async fn load_messages_by_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<Vec<MessageRecord>> {
+        Database::load_messages_by_ids(self, account_id, ids).await
+    }
+
+    async fn set_reply_later(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+        due: Option<NaiveDate>,
+    ) -> Result<u64> {
+        Database::set_reply_later(self, account_id, message_ids, due).await
+    }
+
+    async fn clear_reply_later(&self, account_id: &str, message_ids: &[String]) -> Result<u64> {
+        Database::clear_reply_later(self, account_id, message_ids).await
+    }
+
+    async fn load_reply_later(&self, account_id: &str) -> Result<Vec<ReplyLater>> {
+        Database::load_reply_later(self, account_id).await
+    }
+
+    async fn set_message_note(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        note: &str,
+    ) -> Result<bool> {
+        Database::set_message_note(self, account_id, message_id, note).await
+    }
+
+    async fn clear_message_note(&self, account_id: &str, message_id: &str) -> Result<bool> {
+        Database::clear_message_note(self, account_id, message_id).await
+    }
+
+    async fn load_message_notes(&self, account_id: &str) -> Result<Vec<MessageNote>> {
+        Database::load_message_notes(self, account_id).await
+    }
+
+    async fn set_message_summary(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        summary: &str,
+    ) -> Result<bool> {
+        Database::set_message_summary(self, account_id, message_id, summary).await
+    }
+
+    async fn load_message_summaries(&self, account_id: &str) -> Result<HashMap<String, String>> {
+        Database::load_message_summaries(self, account_id).await
+    }
+
+    async fn claim_unprocessed_messages(
+        &self,
+        consumer: &str,
+        account_id: &str,
+        cached_since: i64,
+        limit: usize,
+    ) -> Result<Vec<MessageRecord>> {
+        Database::claim_unprocessed_messages(self, consumer, account_id, cached_since, limit).await
+    }
+
+    async fn release_processed_messages(
+        &self,
+        consumer: &str,
+        message_ids: &[String],
+    ) -> Result<u64> {
+        Database::release_processed_messages(self, consumer, message_ids).await
+    }
+
+    async fn set_message_translation(
+        &self,
+        account_id: &str,
+        translation: &MessageTranslation,
+    ) -> Result<bool> {
+        Database::set_message_translation(self, account_id, translation).await
+    }
+
+    async fn load_message_translation(
+        &self,
+        account_id: &str,
+        message_id: &str,
+        target: &str,
+    ) -> Result<Option<MessageTranslation>> {
+        Database::load_message_translation(self, account_id, message_id, target).await
+    }
+
+    async fn save_signature(&self, signature: &Signature) -> Result<()> {
+        Database::save_signature(self, signature).await
+    }
+
+    async fn load_signature(
+        &self,
+        account_id: &str,
+        alias: Option<&str>,
+    ) -> Result<Option<Signature>> {
+        Database::load_signature(self, account_id, alias).await
+    }
+
+    async fn clear_signature(&self, account_id: &str, alias: Option<&str>) -> Result<bool> {
+        Database::clear_signature(self, account_id, alias).await
+    }
+
+    async fn record_sender_action(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        count: i64,
+        threshold: i64,
+    ) -> Result<Option<i64>> {
+        Database::record_sender_action(self, account_id, sender, action, label, count, threshold)
+            .await
+    }
+
+    async fn set_sender_action_status(
+        &self,
+        account_id: &str,
+        sender: &str,
+        action: &str,
+        label: &str,
+        status: SuggestionStatus,
+    ) -> Result<bool> {
+        Database::set_sender_action_status(self, account_id, sender, action, label, status).await
+    }
+
+    async fn load_sender_actions(
+        &self,
+        account_id: &str,
+        status: Option<SuggestionStatus>,
+    ) -> Result<Vec<SenderAction>> {
+        Database::load_sender_actions(self, account_id, status).await
+    }
+
+    async fn load_recipients(&self, message_id: &str) -> Result<Vec<Recipient>> {
+        Database::load_recipients(self, message_id).await
+    }
+
+    async fn find_messages_by_recipient(
+        &self,
+        account_id: &str,
+        address: &str,
+        fields: &[AddressField],
+        not_fields: &[AddressField],
+    ) -> Result<Vec<String>> {
+        Database::find_messages_by_recipient(self, account_id, address, fields, not_fields).await
+    }
+
+    async fn suggest_addresses(
+        &self,
+        account_id: &str,
+        prefix: &str,
+        limit: usize,
+    ) -> Result<Vec<AddressSuggestion>> {
+        Database::suggest_addresses(self, account_id, prefix, limit).await
+    }
+
+    async fn load_message_ids_by_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<HashMap<u32, String>> {
+        Database::load_message_ids_by_uids(self, account_id, folder, uids).await
+    }
+
+    async fn load_uid_to_message_id_map_by_folder(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, String>> {
+        Database::load_uid_to_message_id_map_by_folder(self, account_id, folder).await
+    }
+
+    async fn load_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (Vec<String>, Vec<String>)>> {
+        Database::load_flags_by_uid(self, account_id, folder).await
+    }
+
+    async fn load_raw_hashes_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<u32, (String, String)>> {
+        Database::load_raw_hashes_by_uid(self, account_id, folder).await
+    }
+
+    async fn unread_counts(&self, account_id: &str) -> Result<HashMap<String, u32>> {
+        Database::unread_counts(self, account_id).await
+    }
+
+    async fn unread_label_counts(
+        &self,
+        account_id: &str,
+        folder: &str,
+    ) -> Result<HashMap<String, u32>> {
+        Database::unread_label_counts(self, account_id, folder).await
+    }
+
+    async fn batch_update_message_flags_by_uid(
+        &self,
+        account_id: &str,
+        folder: &str,
+        updates: &[(u32, Vec<String>, Vec<String>)],
+    ) -> Result<()> {
+        Database::batch_update_message_flags_by_uid(self, account_id, folder, updates).await
+    }
+
+    async fn load_existing_message_ids(
+        &self,
+        account_id: &str,
+        ids: &[String],
+    ) -> Result<HashSet<String>> {
+        Database::load_existing_message_ids(self, account_id, ids).await
+    }
+
+    async fn load_message_ids_by_header(
+        &self,
+        account_id: &str,
+        folder: &str,
+        headers: &[String],
+    ) -> Result<HashMap<String, String>> {
+        Database::load_message_ids_by_header(self, account_id, folder, headers).await
+    }
+
+    async fn dedupe_fallback_messages_by_raw_hash(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<usize> {
+        Database::dedupe_fallback_messages_by_raw_hash(self, account_id, limit).await
+    }
+
+    async fn load_messages(
+        &self,
+        account_id: &str,
+        limit: usize,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
+        Database::load_messages(self, account_id, limit).await
+    }
+
+    async fn load_thread(
+        &self,
+        account_id: &str,
+        id: &str,
+    ) -> Result<Vec<(MessageRecord, Option<BodyRecord>)>> {
+        Database::load_thread(self, account_id, id).await
+    }
+
+    async fn load_pending_body_targets(
+        &self,
+        account_id: &str,
+        skip_folders: &[String],
+        limit: usize,
+    ) -> Result<Vec<(String, String, u32)>> {
+        Database::load_pending_body_targets(self, account_id, skip_folders, limit).await
+    }
+
+    async fn request_oversized_bodies(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>> {
+        Database::request_oversized_bodies(self, account_id, message_ids).await
+    }
+
+    async fn load_body_targets(
+        &self,
+        account_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<(String, String, u32)>> {
+        Database::load_body_targets(self, account_id, message_ids).await
+    }
+
+    async fn store_fetched_bodies(
+        &self,
+        account_id: &str,
+        updates: &[FetchedBodyUpdate],
+    ) -> Result<()> {
+        Database::store_fetched_bodies(self, account_id, updates).await
+    }
+
+    async fn drop_folder_bodies(&self, account_id: &str, folder: &str) -> Result<u64> {
+        Database::drop_folder_bodies(self, account_id, folder).await
+    }
+
+    async fn load_bodies_to_resanitize(
+        &self,
+        account_id: &str,
+        below_version: i64,
+        after: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        Database::load_bodies_to_resanitize(self, account_id, below_version, after, limit).await
+    }
+
+    async fn load_raw_message(
+        &self,
+        account_id: &str,
+        message_id: &str,
+    ) -> Result<Option<Vec<u8>>> {
+        Database::load_raw_message(self, account_id, message_id).await
+    }
+
+    async fn store_resanitized_bodies(
+        &self,
+        account_id: &str,
+        bodies: &[(bool, BodyRecord)],
+    ) -> Result<()> {
+        Database::store_resanitized_bodies(self, account_id, bodies).await
+    }
+
+    async fn compress_raw_bodies(&self, account_id: &str) -> Result<CompressStats> {
+        Database::compress_raw_bodies(self, account_id).await
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        Database::vacuum(self).await
+    }
+
+    async fn apply_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize> {
+        Database::apply_message_op(self, account_id, op, message_ids).await
+    }
+
+    async fn record_applied_message_op(
+        &self,
+        account_id: &str,
+        op: &MessageOp,
+        message_ids: &[String],
+    ) -> Result<usize> {
+        Database::record_applied_message_op(self, account_id, op, message_ids).await
+    }
+
+    async fn load_pending_flag_ops(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<Vec<PendingFlagOp>> {
+        Database::load_pending_flag_ops(self, account_id, folder, uids).await
+    }
+
+    async fn load_replayable_flag_ops(&self, account_id: &str) -> Result<Vec<ReplayOp>> {
+        Database::load_replayable_flag_ops(self, account_id).await
+    }
+
+    async fn load_replayable_location_ops(&self, account_id: &str) -> Result<Vec<ReplayOp>> {
+        Database::load_replayable_location_ops(self, account_id).await
+    }
+
+    async fn rollback_pending_ops(&self, ids: &[i64]) -> Result<u64> {
+        Database::rollback_pending_ops(self, ids).await
+    }
+
+    async fn clear_pending_ops(&self, ids: &[i64]) -> Result<u64> {
+        Database::clear_pending_ops(self, ids).await
+    }
+
+    async fn link_moved_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uid_validity: u32,
+        links: &[(i64, u32)],
+    ) -> Result<u64> {
+        Database::link_moved_uids(self, account_id, folder, uid_validity, links).await
+    }
+
+    async fn record_op_failures(&self, ids: &[i64], error: &str) -> Result<u64> {
+        Database::record_op_failures(self, ids, error).await
+    }
+
+    async fn retry_dead_ops(&self, ids: &[i64]) -> Result<u64> {
+        Database::retry_dead_ops(self, ids).await
+    }
+
+    async fn list_pending_ops(&self, account_id: &str) -> Result<Vec<PendingOp>> {
+        Database::list_pending_ops(self, account_id).await
+    }
+
+    async fn record_fetch_failures(
+        &self,
+        account_id: &str,
+        folder: &str,
+        failures: &[(u32, String)],
+    ) -> Result<()> {
+        Database::record_fetch_failures(self, account_id, folder, failures).await
+    }
+
+    async fn load_fetch_retries(&self, account_id: &str, folder: &str) -> Result<Vec<FetchRetry>> {
+        Database::load_fetch_retries(self, account_id, folder).await
+    }
+
+    async fn clear_fetch_retries(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<u64> {
+        Database::clear_fetch_retries(self, account_id, folder, uids).await
+    }
+
+    async fn mark_op_conflicts(
+        &self,
+        ids: &[i64],
+        server_flags: &[String],
+        server_labels: &[String],
+    ) -> Result<u64> {
+        Database::mark_op_conflicts(self, ids, server_flags, server_labels).await
+    }
+
+    async fn list_op_conflicts(&self, account_id: &str) -> Result<Vec<OpConflict>> {
+        Database::list_op_conflicts(self, account_id).await
+    }
+
+    async fn resolve_op_conflicts(
+        &self,
+        ids: &[i64],
+        resolution: ConflictResolution,
+    ) -> Result<u64> {
+        Database::resolve_op_conflicts(self, ids, resolution).await
+    }
+
+    async fn delete_messages_by_folder(&self, account_id: &str, folder: &str) -> Result<u64> {
+        Database::delete_messages_by_folder(self, account_id, folder).await
+    }
+
+    async fn delete_messages_by_folder_and_uids(
+        &self,
+        account_id: &str,
+        folder: &str,
+        uids: &[u32],
+    ) -> Result<u64> {
+        Database::delete_messages_by_folder_and_uids(self, account_id, folder, uids).await
+    }
+}
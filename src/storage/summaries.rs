@@ -0,0 +1,69 @@
+//! Short per-message summaries written by a summarizer (an LLM consumer claiming messages with
+//! `claim_unprocessed_messages`), shown as the list preview where `PreviewSource::Summary`
+//! applies. One summary per message; rows go away with their message. Accounts with column
+//! encryption store the text sealed (`Database` seals and opens it).
+use anyhow::{Context, Result};
+use sqlx::{Row, SqlitePool};
+
+use crate::types::now_ts;
+
+pub(crate) async fn ensure_summaries_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS message_summaries (
+            message_id TEXT PRIMARY KEY,
+            account_id TEXT NOT NULL,
+            summary TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_message_summaries_account ON message_summaries(account_id);
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating message_summaries table")?;
+    Ok(())
+}
+
+/// Sets (or replaces) the summary of the account's message; false when the account has no
+/// such message.
+pub(crate) async fn set(
+    pool: &SqlitePool,
+    account_id: &str,
+    message_id: &str,
+    summary: &str,
+) -> Result<bool> {
+    let written = sqlx::query(
+        r#"
+        INSERT INTO message_summaries (message_id, account_id, summary, updated_at)
+        SELECT id, account_id, ?3, ?4 FROM messages WHERE account_id = ?1 AND id = ?2
+        ON CONFLICT(message_id) DO UPDATE SET
+            summary = excluded.summary,
+            updated_at = excluded.updated_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(message_id)
+    .bind(summary)
+    .bind(now_ts())
+    .execute(pool)
+    .await
+    .context("saving message summary")?
+    .rows_affected();
+    Ok(written > 0)
+}
+
+/// The account's summaries as `(message id, text as stored)`.
+pub(crate) async fn list(pool: &SqlitePool, account_id: &str) -> Result<Vec<(String, String)>> {
+    let rows =
+        sqlx::query("SELECT message_id, summary FROM message_summaries WHERE account_id = ?1")
+            .bind(account_id)
+            .fetch_all(pool)
+            .await
+            .context("loading message summaries")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect())
+}
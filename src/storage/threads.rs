@@ -0,0 +1,135 @@
+//! Persisted JWZ containers (`threads` table) and thread-id assignment for messages that arrive
+//! without X-GM-THRID. See `crate::threading` for the linking rules.
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result};
+use sqlx::{QueryBuilder, Row, Sqlite, SqliteConnection, SqlitePool};
+
+use crate::threading::{Threader, thread_id_for_root};
+use crate::types::MessageRecord;
+
+/// Upper bound on ancestor lookups per message; real reply chains are far shorter.
+const MAX_ANCESTOR_ROUNDS: usize = 64;
+
+pub(crate) async fn ensure_threads_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS threads (
+            account_id TEXT NOT NULL,
+            message_id_header TEXT NOT NULL,
+            parent TEXT,
+            thread_id TEXT NOT NULL,
+            PRIMARY KEY (account_id, message_id_header),
+            FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_threads_account_thread ON threads(account_id, thread_id);
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating threads table")?;
+    Ok(())
+}
+
+/// Links `message` into the account's container graph and returns its thread id, merging
+/// threads the message's references join. `None` when it has no Message-ID to thread on.
+pub(crate) async fn assign_thread(
+    conn: &mut SqliteConnection,
+    account_id: &str,
+    message: &MessageRecord,
+) -> Result<Option<String>> {
+    let Some(own) = message.message_id_header.as_deref() else {
+        return Ok(None);
+    };
+
+    let mut threader = Threader::default();
+    let mut thread_ids = BTreeSet::new();
+    let mut wanted: Vec<String> = message.references.clone();
+    wanted.push(own.to_string());
+    let mut loaded = BTreeSet::new();
+    for _ in 0..MAX_ANCESTOR_ROUNDS {
+        wanted.retain(|id| !loaded.contains(id));
+        if wanted.is_empty() {
+            break;
+        }
+        let rows = load_containers(&mut *conn, account_id, &wanted).await?;
+        loaded.extend(wanted.drain(..));
+        for (id, parent, thread_id) in rows {
+            threader.insert(&id, parent.as_deref());
+            thread_ids.insert(thread_id);
+            wanted.extend(parent);
+        }
+    }
+
+    threader.add_message(own, &message.references);
+    let thread_id = match thread_ids.pop_first() {
+        Some(kept) => kept,
+        None => thread_id_for_root(threader.root(own)),
+    };
+
+    // The message bridged threads that were separate until now; fold them into one.
+    for merged in &thread_ids {
+        for table in ["threads", "messages"] {
+            sqlx::query(&format!(
+                "UPDATE {} SET thread_id = ?1 WHERE account_id = ?2 AND thread_id = ?3",
+                table
+            ))
+            .bind(&thread_id)
+            .bind(account_id)
+            .bind(merged)
+            .execute(&mut *conn)
+            .await
+            .context("merging threads")?;
+        }
+    }
+
+    for (id, parent) in threader.containers() {
+        sqlx::query(
+            r#"
+            INSERT INTO threads (account_id, message_id_header, parent, thread_id)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT(account_id, message_id_header) DO UPDATE SET
+                parent = excluded.parent,
+                thread_id = excluded.thread_id;
+            "#,
+        )
+        .bind(account_id)
+        .bind(id)
+        .bind(parent)
+        .bind(&thread_id)
+        .execute(&mut *conn)
+        .await
+        .context("storing thread container")?;
+    }
+
+    Ok(Some(thread_id))
+}
+
+async fn load_containers(
+    conn: &mut SqliteConnection,
+    account_id: &str,
+    ids: &[String],
+) -> Result<Vec<(String, Option<String>, String)>> {
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT message_id_header, parent, thread_id FROM threads WHERE account_id = ",
+    );
+    qb.push_bind(account_id);
+    qb.push(" AND message_id_header IN (");
+    {
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+    }
+    qb.push(")");
+
+    let rows = qb
+        .build()
+        .fetch_all(&mut *conn)
+        .await
+        .context("loading thread containers")?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.get(0), row.get(1), row.get(2)))
+        .collect())
+}
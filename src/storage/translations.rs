@@ -0,0 +1,111 @@
+//! Translations of message bodies (`otto translate`, `T` in the TUI), cached per message and
+//! target language. `source_hash` is the SHA-256 of the text that was translated, so a body the
+//! sanitizer rebuilt is translated again instead of showing a stale result. Rows go away with
+//! their message. Accounts with column encryption store the text sealed (`Database` seals and
+//! opens it).
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use sqlx::{Row, SqlitePool};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageTranslation {
+    pub message_id: String,
+    /// Language the text was translated into, as configured (`de`, `pt-BR`).
+    pub target: String,
+    pub text: String,
+    /// Language the backend detected in the original, when it says.
+    pub source_lang: Option<String>,
+    pub source_hash: String,
+    pub updated_at: i64,
+}
+
+/// Hex SHA-256 of the text sent for translation.
+pub fn source_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+pub(crate) async fn ensure_translations_table(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS message_translations (
+            message_id TEXT NOT NULL,
+            account_id TEXT NOT NULL,
+            target TEXT NOT NULL,
+            text TEXT NOT NULL,
+            source_lang TEXT,
+            source_hash TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (message_id, target),
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_message_translations_account ON message_translations(account_id);
+        "#,
+    )
+    .execute(pool)
+    .await
+    .context("creating message_translations table")?;
+    Ok(())
+}
+
+/// Stores (or replaces) a translation of the account's message; false when the account has no
+/// such message.
+pub(crate) async fn set(
+    pool: &SqlitePool,
+    account_id: &str,
+    translation: &MessageTranslation,
+) -> Result<bool> {
+    let written = sqlx::query(
+        r#"
+        INSERT INTO message_translations
+            (message_id, account_id, target, text, source_lang, source_hash, updated_at)
+        SELECT id, account_id, ?3, ?4, ?5, ?6, ?7 FROM messages WHERE account_id = ?1 AND id = ?2
+        ON CONFLICT(message_id, target) DO UPDATE SET
+            text = excluded.text,
+            source_lang = excluded.source_lang,
+            source_hash = excluded.source_hash,
+            updated_at = excluded.updated_at;
+        "#,
+    )
+    .bind(account_id)
+    .bind(&translation.message_id)
+    .bind(&translation.target)
+    .bind(&translation.text)
+    .bind(&translation.source_lang)
+    .bind(&translation.source_hash)
+    .bind(translation.updated_at)
+    .execute(pool)
+    .await
+    .context("saving message translation")?
+    .rows_affected();
+    Ok(written > 0)
+}
+
+/// The cached translation of the message into `target` (text as stored).
+pub(crate) async fn get(
+    pool: &SqlitePool,
+    account_id: &str,
+    message_id: &str,
+    target: &str,
+) -> Result<Option<MessageTranslation>> {
+    let row = sqlx::query(
+        r#"
+        SELECT message_id, target, text, source_lang, source_hash, updated_at
+        FROM message_translations
+        WHERE account_id = ?1 AND message_id = ?2 AND target = ?3;
+        "#,
+    )
+    .bind(account_id)
+    .bind(message_id)
+    .bind(target)
+    .fetch_optional(pool)
+    .await
+    .context("loading message translation")?;
+    Ok(row.map(|row| MessageTranslation {
+        message_id: row.get(0),
+        target: row.get(1),
+        text: row.get(2),
+        source_lang: row.get(3),
+        source_hash: row.get(4),
+        updated_at: row.get(5),
+    }))
+}
@@ -0,0 +1,66 @@
+//! Gmail All Mail mode (`AccountSettings::all_mail_mode`): a pass selects `[Gmail]/All Mail`
+//! once instead of INBOX, Sent and every label folder, so each message downloads a single time.
+//! Folder membership then comes from the message's `X-GM-LABELS` (`FolderLabels`). Trash and
+//! Spam are not part of All Mail and are still synced as folders when enabled.
+use anyhow::Result;
+
+use crate::storage::MailStore;
+use crate::types::{Account, FolderRole, Provider};
+
+/// Folders a sync pass (and backfill, verify and the cache check) selects for `account`: its
+/// enabled folders, or in All Mail mode the All Mail folder plus the enabled Trash and Spam.
+/// All Mail mode only applies to Gmail accounts.
+pub async fn synced_folders(db: &dyn MailStore, account: &Account) -> Result<Vec<String>> {
+    let enabled = account.settings.enabled_folders().cloned();
+    if !account.settings.all_mail_mode || account.provider != Provider::GmailImap {
+        return Ok(enabled.collect());
+    }
+    let all_mail = db.role_folder(&account.id, FolderRole::All).await?;
+    let outside = [
+        db.role_folder(&account.id, FolderRole::Trash).await?,
+        db.role_folder(&account.id, FolderRole::Junk).await?,
+    ];
+    Ok(std::iter::once(all_mail)
+        .chain(enabled.filter(|folder| outside.contains(folder)))
+        .collect())
+}
+
+/// Maps folders to the Gmail labels that put a message in them: INBOX is `\Inbox`, the Sent and
+/// Drafts folders are `\Sent` and `\Draft`, and any other folder is the user label of the same
+/// name. Used wherever a message's folder matters, since All Mail rows all share one folder.
+#[derive(Clone, Debug)]
+pub struct FolderLabels {
+    sent: String,
+    drafts: String,
+}
+
+impl FolderLabels {
+    pub async fn load(db: &dyn MailStore, account_id: &str) -> Result<Self> {
+        Ok(Self {
+            sent: db.role_folder(account_id, FolderRole::Sent).await?,
+            drafts: db.role_folder(account_id, FolderRole::Drafts).await?,
+        })
+    }
+
+    /// The `X-GM-LABELS` value meaning "in `folder`".
+    pub fn label_for(&self, folder: &str) -> String {
+        if folder.eq_ignore_ascii_case("INBOX") {
+            "\\Inbox".to_string()
+        } else if folder == self.sent {
+            "\\Sent".to_string()
+        } else if folder == self.drafts {
+            "\\Draft".to_string()
+        } else {
+            folder.to_string()
+        }
+    }
+
+    /// Whether a message stored in `message_folder` with `labels` belongs to `folder`.
+    pub fn contains(&self, folder: &str, message_folder: &str, labels: &[String]) -> bool {
+        if message_folder == folder {
+            return true;
+        }
+        let label = self.label_for(folder);
+        labels.iter().any(|l| l.eq_ignore_ascii_case(&label))
+    }
+}
@@ -0,0 +1,79 @@
+//! Uploading messages to a server folder with APPEND: `.eml` imports, restores from backups,
+//! and copies of mail sent outside the server's own submission path.
+use anyhow::Result;
+use async_imap::error::Error as ImapError;
+use tracing::{debug, info, warn};
+
+use super::{CONNECTION_POOL, SyncEngine};
+use crate::credentials::imap_secret;
+use crate::imap::{AppendMessage, ImapClient};
+use crate::types::Account;
+
+/// What an upload did: how many messages the server stored, and which ones (by position in
+/// the input) it refused, with its reason.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct AppendReport {
+    pub appended: usize,
+    pub rejected: Vec<(usize, String)>,
+}
+
+impl SyncEngine {
+    /// APPENDs `messages` to `folder` in order over the folder's pooled connection. A message
+    /// the server refuses (NO/BAD, e.g. too large) is reported and skipped; connection trouble
+    /// or cancellation stops the upload with an error. Uploaded mail reaches the cache on the
+    /// folder's next sync.
+    pub async fn append_messages(
+        &self,
+        account: &Account,
+        folder: &str,
+        messages: &[AppendMessage],
+    ) -> Result<AppendReport> {
+        let secret = imap_secret(account).await?;
+        let mut session = CONNECTION_POOL
+            .get_or_create(account, folder, &secret)
+            .await?;
+        let mut report = AppendReport::default();
+        let mut result = Ok(());
+        for (idx, message) in messages.iter().enumerate() {
+            if let Err(e) = self.check_cancelled() {
+                result = Err(e);
+                break;
+            }
+            match ImapClient::append(&mut session, folder, message).await {
+                Ok(()) => {
+                    report.appended += 1;
+                    debug!(account = %account.id, folder = %folder, bytes = message.raw.len(), "Appended message");
+                }
+                Err(e) => match e.downcast_ref::<ImapError>() {
+                    Some(ImapError::No(reason) | ImapError::Bad(reason)) => {
+                        warn!(account = %account.id, folder = %folder, message = idx, reason = %reason, "Server refused appended message");
+                        report.rejected.push((idx, reason.clone()));
+                    }
+                    _ => {
+                        result = Err(e);
+                        break;
+                    }
+                },
+            }
+        }
+        CONNECTION_POOL
+            .return_connection(&account.id, folder, session)
+            .await;
+        info!(
+            account = %account.id,
+            folder = %folder,
+            appended = report.appended,
+            rejected = report.rejected.len(),
+            "Appended messages"
+        );
+        match result {
+            Ok(()) => Ok(report),
+            Err(e) => Err(e.context(format!(
+                "upload to {} stopped after {} of {} message(s)",
+                folder,
+                report.appended,
+                messages.len()
+            ))),
+        }
+    }
+}
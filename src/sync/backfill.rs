@@ -0,0 +1,161 @@
+//! `otto backfill`: pages backwards from the account cutoff (or the previous backfill point) to
+//! an older date in fixed date chunks, storing mail outside the incremental MODSEQ window.
+use std::collections::HashSet;
+
+use anyhow::{Context, Result, bail};
+use chrono::{Duration, NaiveDate};
+use tracing::{info, warn};
+
+use super::{CHECKPOINT_BATCH_UIDS, CONNECTION_POOL, ImapSession, SyncEngine};
+use crate::credentials::imap_secret;
+use crate::types::Account;
+
+/// Date span searched per backfill step (`SINCE lo BEFORE hi`).
+const BACKFILL_CHUNK_DAYS: i64 = 30;
+
+impl SyncEngine {
+    /// Fetches every configured folder's mail back to `until`; returns messages stored.
+    pub async fn backfill(&self, account: &Account, until: NaiveDate) -> Result<usize> {
+        let secret = imap_secret(account).await?;
+
+        let mut total = 0;
+        for folder_name in &super::synced_folders(self.db.as_ref(), account).await? {
+            let _permit = self
+                .folder_permits
+                .acquire()
+                .await
+                .context("acquiring backfill permit")?;
+
+            let mut session = CONNECTION_POOL
+                .get_or_create(account, folder_name, &secret)
+                .await?;
+            let result = self
+                .backfill_folder(&mut session, account, folder_name, until)
+                .await;
+            CONNECTION_POOL
+                .return_connection(&account.id, folder_name, session)
+                .await;
+
+            match result {
+                Ok(n) => total += n,
+                Err(e) => warn!(
+                    account = %account.id,
+                    folder = %folder_name,
+                    error = %e,
+                    "Folder backfill failed"
+                ),
+            }
+        }
+
+        Ok(total)
+    }
+
+    async fn backfill_folder(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        until: NaiveDate,
+    ) -> Result<usize> {
+        let mailbox = session
+            .select(folder_name)
+            .await
+            .with_context(|| format!("selecting folder {}", folder_name))?;
+
+        let folder_state = self
+            .db
+            .list_folders(&account.id)
+            .await?
+            .into_iter()
+            .find(|f| f.name == folder_name);
+
+        // Cached UIDs must belong to the current UIDVALIDITY; the regular sync owns that reset.
+        if let Some(stored) = folder_state.as_ref().and_then(|s| s.uidvalidity)
+            && Some(stored) != mailbox.uid_validity
+        {
+            bail!(
+                "UIDVALIDITY changed for {}; run a regular sync before backfilling",
+                folder_name
+            );
+        }
+
+        let cutoff = account.settings.folder_cutoff(folder_name);
+        let mut hi = folder_state
+            .as_ref()
+            .and_then(|s| s.backfill_since)
+            .map_or(cutoff, |since| since.min(cutoff));
+        if hi <= until {
+            info!(
+                account = %account.id,
+                folder = %folder_name,
+                backfilled_to = %hi,
+                "Folder already backfilled"
+            );
+            return Ok(0);
+        }
+
+        let local_uids: HashSet<u32> = self
+            .db
+            .load_uid_to_message_id_map_by_folder(&account.id, folder_name)
+            .await?
+            .into_keys()
+            .collect();
+
+        let mut stored = 0;
+        while hi > until {
+            let lo = (hi - Duration::days(BACKFILL_CHUNK_DAYS)).max(until);
+            let query = format!(
+                "SINCE {} BEFORE {}",
+                lo.format("%d-%b-%Y"),
+                hi.format("%d-%b-%Y")
+            );
+            let mut new_uids: Vec<u32> = session
+                .uid_search(&query)
+                .await
+                .with_context(|| format!("UID SEARCH backfill: {}", query))?
+                .into_iter()
+                .filter(|uid| !local_uids.contains(uid))
+                .collect();
+            new_uids.sort_unstable();
+
+            // `backfill_since` only moves once the whole chunk is stored, so an interrupted
+            // backfill redoes at most one chunk (already-stored UIDs are skipped).
+            let batches: Vec<&[u32]> = new_uids.chunks(CHECKPOINT_BATCH_UIDS).collect();
+            if batches.is_empty() {
+                self.db
+                    .commit_backfill_batch(&account.id, folder_name, &[], &[], &[], Some(lo))
+                    .await?;
+            }
+            for (idx, batch) in batches.iter().enumerate() {
+                let (new, location_updates) = self
+                    .fetch_and_handle_new_uids(session, account, folder_name, batch, false)
+                    .await?;
+                let chunk_done = (idx + 1 == batches.len()).then_some(lo);
+                self.db
+                    .commit_backfill_batch(
+                        &account.id,
+                        folder_name,
+                        &[],
+                        &[],
+                        &location_updates,
+                        chunk_done,
+                    )
+                    .await?;
+                stored += new.len();
+                self.check_cancelled()?;
+            }
+
+            info!(
+                account = %account.id,
+                folder = %folder_name,
+                since = %lo,
+                before = %hi,
+                new = new_uids.len(),
+                "Backfill chunk stored"
+            );
+            hi = lo;
+        }
+
+        Ok(stored)
+    }
+}
@@ -0,0 +1,44 @@
+//! Folder counts: unseen and total messages per folder as the server reports them, read once
+//! per account sync with one `LIST ... RETURN (STATUS (MESSAGES UNSEEN))` (LIST-STATUS) or a
+//! `STATUS` per folder, and kept on the `folders` rows so `otto status` and the TUI sidebar
+//! show badge counts without scanning messages or depending on how much is cached.
+use anyhow::{Context, Result};
+use tracing::debug;
+
+use super::{CONNECTION_POOL, SyncEngine, synced_folders};
+use crate::imap::ImapClient;
+use crate::types::{Account, FolderCounts, now_ts};
+
+impl SyncEngine {
+    /// Reads and stores the counts of the account's enabled folders and the folders it syncs
+    /// (in All Mail mode these differ).
+    pub(super) async fn refresh_folder_counts(
+        &self,
+        account: &Account,
+        secret: &str,
+    ) -> Result<Vec<FolderCounts>> {
+        let mut folders: Vec<String> = account.settings.enabled_folders().cloned().collect();
+        for folder in synced_folders(self.db.as_ref(), account).await? {
+            if !folders.contains(&folder) {
+                folders.push(folder);
+            }
+        }
+
+        let mut session = CONNECTION_POOL
+            .get_or_create(account, "counts", secret)
+            .await
+            .context("connecting for folder counts")?;
+        let result = ImapClient::folder_counts(&mut session, &folders, now_ts()).await;
+        CONNECTION_POOL
+            .return_connection(&account.id, "counts", session)
+            .await;
+        let counts = result?;
+        self.db.save_folder_counts(&account.id, &counts).await?;
+        debug!(
+            account = %account.id,
+            folders = counts.len(),
+            "Stored folder counts"
+        );
+        Ok(counts)
+    }
+}
@@ -0,0 +1,59 @@
+//! Folder discovery: `LIST "" "*"` on demand, stored in the `folders` table with each
+//! mailbox's attributes so users can pick what to sync from what the server actually has.
+//! The same connection asks for the account's namespace (`NAMESPACE`), which is stored on the
+//! account and used to normalize configured folder paths to the server's delimiter.
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+use super::{CONNECTION_POOL, SyncEngine};
+use crate::credentials::imap_secret;
+use crate::imap::ImapClient;
+use crate::types::{Account, MailboxInfo, now_ts};
+
+impl SyncEngine {
+    /// Lists the account's mailboxes on the server and records them; returns the listing.
+    /// A namespace that differs from the stored one is applied to `account` (folders and
+    /// folder policies rewritten to server names), configured folders still at a default name
+    /// the server lacks move to its special-use mailbox, and the account is saved.
+    pub async fn discover_folders(&self, account: &mut Account) -> Result<Vec<MailboxInfo>> {
+        let secret = imap_secret(account).await?;
+        let mut session = CONNECTION_POOL
+            .get_or_create(account, "list", &secret)
+            .await
+            .context("connecting for folder discovery")?;
+        let listed = ImapClient::list_folders(&mut session).await;
+        let namespace = match &listed {
+            Ok(_) => Some(ImapClient::namespace(&mut session).await),
+            Err(_) => None,
+        };
+        CONNECTION_POOL
+            .return_connection(&account.id, "list", session)
+            .await;
+        let mailboxes = listed?;
+
+        self.db
+            .record_discovered_folders(&account.id, &mailboxes)
+            .await?;
+        info!(account = %account.id, folders = mailboxes.len(), "Discovered folders");
+
+        if let Some(Err(e)) = &namespace {
+            warn!(account = %account.id, error = %e, "Reading the folder namespace failed");
+        }
+        let mut changed = false;
+        if let Some(Ok(namespace)) = namespace
+            && account.settings.apply_namespace(namespace)
+        {
+            info!(account = %account.id, namespace = ?account.settings.namespace, folders = ?account.settings.folders, "Stored folder namespace");
+            changed = true;
+        }
+        if account.settings.apply_special_use(&mailboxes) {
+            info!(account = %account.id, folders = ?account.settings.folders, "Mapped default folders to special-use mailboxes");
+            changed = true;
+        }
+        if changed {
+            account.updated_at = now_ts();
+            self.db.save_account(account).await?;
+        }
+        Ok(mailboxes)
+    }
+}
@@ -0,0 +1,165 @@
+//! Folder-wide actions (mark all read, archive older than a date) executed directly on the
+//! server as chunked UID STORE/MOVE commands, then mirrored into the local cache.
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use futures::StreamExt;
+use tracing::{debug, info, warn};
+
+use super::{CONNECTION_POOL, ImapSession, SyncEngine};
+use crate::credentials::imap_secret;
+use crate::imap::build_uid_sequence;
+use crate::storage::audit::AuditRecord;
+use crate::storage::ops::MessageOp;
+use crate::types::{Account, FolderRole, now_ts};
+
+/// UIDs per STORE/MOVE command; keeps command lines and server-side work per round trip bounded.
+const FOLDER_OP_CHUNK: usize = 500;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FolderOp {
+    /// `UID STORE +FLAGS.SILENT (\Seen)` on every unseen message.
+    MarkAllRead,
+    /// `UID MOVE` to All Mail for every message (optionally only those received before a date).
+    Archive { before: Option<NaiveDate> },
+}
+
+impl FolderOp {
+    fn search_query(&self) -> String {
+        match self {
+            FolderOp::MarkAllRead => "UNSEEN".to_string(),
+            FolderOp::Archive { before: Some(date) } => {
+                format!("BEFORE {}", date.format("%d-%b-%Y"))
+            }
+            FolderOp::Archive { before: None } => "ALL".to_string(),
+        }
+    }
+
+    fn message_op(&self) -> MessageOp {
+        match self {
+            FolderOp::MarkAllRead => MessageOp::MarkRead,
+            FolderOp::Archive { .. } => MessageOp::Archive,
+        }
+    }
+}
+
+impl SyncEngine {
+    /// Runs `op` against `folder` and returns how many messages the server acted on.
+    pub async fn run_folder_op(
+        &self,
+        account: &Account,
+        folder_name: &str,
+        op: &FolderOp,
+    ) -> Result<usize> {
+        let archive = self.db.role_folder(&account.id, FolderRole::All).await?;
+        if matches!(op, FolderOp::Archive { .. }) && folder_name == archive {
+            bail!("cannot archive {} into itself", archive);
+        }
+
+        let secret = imap_secret(account).await?;
+
+        let mut session = CONNECTION_POOL
+            .get_or_create(account, folder_name, &secret)
+            .await?;
+        let result = self
+            .run_folder_op_on_session(&mut session, account, folder_name, op, &archive)
+            .await;
+        CONNECTION_POOL
+            .return_connection(&account.id, folder_name, session)
+            .await;
+        result
+    }
+
+    async fn run_folder_op_on_session(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        op: &FolderOp,
+        archive: &str,
+    ) -> Result<usize> {
+        session
+            .select(folder_name)
+            .await
+            .with_context(|| format!("selecting folder {}", folder_name))?;
+
+        let query = op.search_query();
+        let mut uids: Vec<u32> = session
+            .uid_search(&query)
+            .await
+            .with_context(|| format!("UID SEARCH folder op: {}", query))?
+            .into_iter()
+            .collect();
+        uids.sort_unstable();
+
+        info!(
+            account = %account.id,
+            folder = %folder_name,
+            op = ?op,
+            matched = uids.len(),
+            "Running folder operation"
+        );
+
+        let message_op = op.message_op();
+        let mut done = 0;
+        for chunk in uids.chunks(FOLDER_OP_CHUNK) {
+            let uid_seq = build_uid_sequence(chunk);
+            match op {
+                FolderOp::MarkAllRead => {
+                    let responses: Vec<_> = session
+                        .uid_store(&uid_seq, "+FLAGS.SILENT (\\Seen)")
+                        .await
+                        .context("UID STORE \\Seen")?
+                        .collect()
+                        .await;
+                    if let Some(Err(e)) = responses.into_iter().find(|r| r.is_err()) {
+                        return Err(e).context("UID STORE \\Seen response");
+                    }
+                }
+                FolderOp::Archive { .. } => {
+                    let moved = session.move_uids(chunk, archive).await;
+                    let record = AuditRecord {
+                        account_id: account.id.clone(),
+                        ts: now_ts(),
+                        actor: "folder-op".to_string(),
+                        op: "archive".to_string(),
+                        folder: folder_name.to_string(),
+                        destination: Some(archive.to_string()),
+                        uids: chunk.to_vec(),
+                        op_ids: Vec::new(),
+                        outcome: match &moved {
+                            Ok(_) => "ok".to_string(),
+                            Err(e) => format!("error: {}", e),
+                        },
+                    };
+                    if let Err(e) = self.db.record_audit(&record).await {
+                        warn!(account = %account.id, error = %e, "Writing audit record failed");
+                    }
+                    moved.with_context(|| format!("moving to {}", archive))?;
+                }
+            }
+
+            // Mirror each chunk as soon as the server confirms it, so a later failure leaves
+            // the cache consistent with what was actually applied.
+            let ids: Vec<String> = self
+                .db
+                .load_message_ids_by_uids(&account.id, folder_name, chunk)
+                .await?
+                .into_values()
+                .collect();
+            self.db
+                .record_applied_message_op(&account.id, &message_op, &ids)
+                .await?;
+
+            done += chunk.len();
+            debug!(
+                account = %account.id,
+                folder = %folder_name,
+                done = done,
+                total = uids.len(),
+                "Folder operation chunk applied"
+            );
+        }
+
+        Ok(done)
+    }
+}
@@ -0,0 +1,115 @@
+//! Process-wide memory watchdog for sync. FETCH helpers take a `MemoryLease` and grow it by the
+//! raw bytes they hold until the parsed chunk is committed. With a budget set
+//! (`OTTO_MEMORY_BUDGET_MB`), FETCH chunks shrink in proportion to the budget left, and new
+//! folder tasks wait for leases to be released while the budget is exhausted. The count is
+//! approximate: it covers raw message bytes, not parse buffers or sanitized copies.
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+pub(super) static MEMORY: Lazy<MemoryWatchdog> = Lazy::new(MemoryWatchdog::new);
+
+/// Smallest FETCH chunk the watchdog shrinks to, so a folder always makes progress.
+const MIN_BATCH: usize = 5;
+/// Waiting folder tasks recheck this often in case a release raced with their wait.
+const RECHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(super) struct MemoryWatchdog {
+    /// 0 means unlimited.
+    budget: AtomicU64,
+    in_flight: AtomicU64,
+    released: Notify,
+    warned: AtomicBool,
+}
+
+impl MemoryWatchdog {
+    fn new() -> Self {
+        Self {
+            budget: AtomicU64::new(0),
+            in_flight: AtomicU64::new(0),
+            released: Notify::new(),
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    pub(super) fn set_budget(&self, bytes: Option<u64>) {
+        self.budget.store(bytes.unwrap_or(0), Ordering::Relaxed);
+        self.released.notify_waiters();
+    }
+
+    fn over_budget(&self) -> bool {
+        let budget = self.budget.load(Ordering::Relaxed);
+        budget > 0 && self.in_flight.load(Ordering::Relaxed) >= budget
+    }
+
+    pub(super) fn lease(&'static self) -> MemoryLease {
+        MemoryLease {
+            watchdog: self,
+            bytes: 0,
+        }
+    }
+
+    /// `full` scaled by the share of the budget still free, but at least `MIN_BATCH`.
+    pub(super) fn batch_size(&self, full: usize) -> usize {
+        let budget = self.budget.load(Ordering::Relaxed);
+        if budget == 0 {
+            return full;
+        }
+        let free = budget.saturating_sub(self.in_flight.load(Ordering::Relaxed));
+        let scaled = (full as u128 * free as u128 / budget as u128) as usize;
+        scaled.clamp(MIN_BATCH.min(full), full)
+    }
+
+    /// Waits until in-flight batches fit the budget again; returns false if `cancel` fired.
+    pub(super) async fn wait_for_room(&self, cancel: &CancellationToken) -> bool {
+        while self.over_budget() {
+            tokio::select! {
+                _ = cancel.cancelled() => return false,
+                _ = self.released.notified() => {}
+                _ = tokio::time::sleep(RECHECK_INTERVAL) => {}
+            }
+        }
+        true
+    }
+}
+
+/// Bytes one FETCH helper holds; released when dropped.
+pub(super) struct MemoryLease {
+    watchdog: &'static MemoryWatchdog,
+    bytes: u64,
+}
+
+impl MemoryLease {
+    pub(super) fn grow(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        let watchdog = self.watchdog;
+        let total = watchdog.in_flight.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let budget = watchdog.budget.load(Ordering::Relaxed);
+        if budget > 0 && total >= budget && !watchdog.warned.swap(true, Ordering::Relaxed) {
+            warn!(
+                in_flight_bytes = total,
+                budget_bytes = budget,
+                "Sync memory budget reached; shrinking batches and pausing new folders"
+            );
+        }
+    }
+}
+
+impl Drop for MemoryLease {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+        let watchdog = self.watchdog;
+        let before = watchdog.in_flight.fetch_sub(self.bytes, Ordering::Relaxed);
+        let budget = watchdog.budget.load(Ordering::Relaxed);
+        if budget > 0 && before - self.bytes < budget / 2 {
+            watchdog.warned.store(false, Ordering::Relaxed);
+        }
+        watchdog.released.notify_waiters();
+    }
+}
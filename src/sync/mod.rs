@@ -1,72 +1,295 @@
+mod all_mail;
+mod append;
+mod backfill;
+mod counts;
+mod discovery;
+mod folder_ops;
+mod memory;
+mod ops_executor;
+mod pool;
+mod quota;
+mod reconnect;
+mod report;
+mod retry;
+mod runs;
+mod throttle;
+mod unread;
+mod validate;
+mod verify;
+
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 
 use futures::{StreamExt, future::join_all};
-use oauth2::Scope;
-use once_cell::sync::Lazy;
-use tokio::net::TcpStream;
-use tokio::sync::Mutex;
-use tokio_util::compat::Compat;
+use tokio::sync::{Mutex, Semaphore, broadcast};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
-use crate::imap::ImapClient;
-use crate::oauth::authorize_with_scopes;
+use crate::address::{Mailbox, normalize_message_id, parse_mailbox_header};
+use crate::credentials::imap_secret;
+use crate::imap::{
+    ImapClient, ImapSession, ProtocolTrace, build_uid_sequence, is_connection_lost, is_timeout,
+};
 use crate::sanitize::sanitize_message;
-use crate::storage::{Database, db::FolderStateUpdate, db::MessageLocationUpdate};
-use crate::types::{Account, BodyRecord, MessageRecord, now_ts};
+use crate::storage::{
+    MailStore,
+    db::{FetchedBodyUpdate, FolderStateUpdate, MessageLocationUpdate},
+    ops::{FlagConflictPolicy, MessageOp, PendingFlagOp, is_remote_conflict},
+};
+use crate::threading::parent_references;
+use crate::types::{
+    Account, BodyFetch, BodyRecord, BodyStatus, FolderState, MessageRecord, SyncProgress,
+    SyncRunRecord, now_ts,
+};
+use memory::MEMORY;
+use pool::CONNECTION_POOL;
+use reconnect::MAX_RECONNECTS;
+use runs::RunStats;
+use throttle::RateLimiter;
+
+pub use all_mail::{FolderLabels, synced_folders};
+pub use append::AppendReport;
+pub use folder_ops::FolderOp;
+pub use ops_executor::{FolderReplay, LocationBatch, OpsExecutor};
+pub use pool::DEFAULT_MAX_IDLE_PER_ACCOUNT;
+pub use report::{AccountSyncReport, SyncReport};
+pub use retry::MAX_FETCH_ATTEMPTS;
+pub use validate::{CacheFreshness, FolderCheck, FolderStatus};
+pub use verify::{FolderDrift, VerifyOptions, sample_uids};
+
+/// Caps the idle IMAP sessions pooled per account (0 disables pooling); applies to every
+/// engine in the process.
+pub fn set_max_pooled_connections(per_account: usize) {
+    CONNECTION_POOL.set_max_idle_per_account(per_account);
+}
+
+/// NOOPs idle pooled IMAP sessions every minute until `cancel` fires, so long-running
+/// processes (the daemon) reuse warm connections instead of ones the server has dropped.
+pub async fn keep_pooled_connections_alive(cancel: CancellationToken) {
+    let mut tick = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => return,
+            _ = tick.tick() => CONNECTION_POOL.keepalive().await,
+        }
+    }
+}
 
-type ImapSession = async_imap::Session<Compat<tokio_rustls::client::TlsStream<TcpStream>>>;
+/// Caps the approximate bytes of fetched messages held in memory at once (`None` = unlimited);
+/// applies to every engine in the process.
+pub fn set_memory_budget(bytes: Option<u64>) {
+    MEMORY.set_budget(bytes);
+}
 
-// Connection pool: cache IMAP connections to avoid TLS handshake overhead
-struct ConnectionPool {
-    connections: Mutex<HashMap<String, (ImapSession, Instant)>>,
+/// Sends LOGOUT on every pooled IMAP session; call once syncing is over, before exiting.
+pub async fn close_pooled_connections() -> usize {
+    CONNECTION_POOL.close_all().await
 }
 
-impl ConnectionPool {
-    fn new() -> Self {
-        Self {
-            connections: Mutex::new(HashMap::new()),
+/// One fetched UID after parsing: its records, or why it could not be parsed.
+type ParsedFetch = (u32, Result<(MessageRecord, Option<BodyRecord>)>);
+
+/// Raw messages a FETCH reader may queue ahead of the parse stage. Together with the parses in
+/// flight (one per rayon worker) this bounds how many unparsed bodies (and their MIME trees)
+/// are alive at once.
+const PARSE_QUEUE_DEPTH: usize = 4;
+
+/// One new-message FETCH response, copied out of the stream for the parse stage.
+struct RawFetch {
+    uid: u32,
+    /// The full message, or just its header block in headers-only mode.
+    body: Vec<u8>,
+    envelope_subject: Option<String>,
+    envelope_from: Option<Mailbox>,
+    flags: Vec<String>,
+    size: u32,
+    internal_date: Option<i64>,
+    gm_msgid: Option<String>,
+    gm_thrid: Option<String>,
+    labels: Vec<String>,
+}
+
+/// Parse stage of a new-message FETCH, run on a blocking thread: hands each queued message to
+/// rayon, waiting for a parse to finish whenever every worker is busy, so rayon workers never
+/// block on the queue themselves. A parser panic fails just that UID (it goes to the retry
+/// queue). Results come back sorted by UID.
+fn drain_into_parser(
+    mut raw_rx: tokio::sync::mpsc::Receiver<RawFetch>,
+    account_id: String,
+    folder_name: String,
+    headers_only: bool,
+) -> Vec<ParsedFetch> {
+    let account_id = Arc::new(account_id);
+    let folder_name = Arc::new(folder_name);
+    let workers = rayon::current_num_threads().max(1);
+    let (done_tx, done_rx) = std::sync::mpsc::channel::<ParsedFetch>();
+    let mut parsed = Vec::new();
+    let mut in_flight = 0;
+    while let Some(raw) = raw_rx.blocking_recv() {
+        if in_flight == workers {
+            parsed.extend(done_rx.recv());
+            in_flight -= 1;
         }
+        let done_tx = done_tx.clone();
+        let account_id = Arc::clone(&account_id);
+        let folder_name = Arc::clone(&folder_name);
+        rayon::spawn(move || {
+            let uid = raw.uid;
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                parse_raw_fetch(raw, &account_id, &folder_name, headers_only)
+            }))
+            .unwrap_or_else(|_| (uid, Err(anyhow::anyhow!("parser panicked on UID {}", uid))));
+            let _ = done_tx.send(result);
+        });
+        in_flight += 1;
     }
+    drop(done_tx);
+    parsed.extend(done_rx);
+    parsed.sort_by_key(|(uid, _)| *uid);
+    parsed
+}
 
-    async fn get_or_create(
-        &self,
-        key: String,
-        account: &Account,
-        access_token: &str,
-    ) -> Result<ImapSession> {
-        // Quick check for cached connection
-        {
-            let mut pool = self.connections.lock().await;
-            if let Some((session, created_at)) = pool.remove(&key) {
-                // Check if connection is still fresh (< 5 minutes old)
-                if created_at.elapsed() < Duration::from_secs(300) {
-                    debug!("Reusing cached IMAP connection for {}", key);
-                    return Ok(session);
-                } else {
-                    debug!("Cached connection expired for {}", key);
-                }
-            }
-        } // Release lock here!
+/// Parses and sanitizes one new message into its records (CPU-bound; runs on rayon).
+fn parse_raw_fetch(
+    raw: RawFetch,
+    account_id: &str,
+    folder_name: &str,
+    headers_only: bool,
+) -> ParsedFetch {
+    let RawFetch {
+        uid,
+        body,
+        envelope_subject,
+        envelope_from,
+        flags,
+        size,
+        internal_date,
+        gm_msgid,
+        gm_thrid,
+        labels,
+    } = raw;
+    // Parse MIME (CPU-intensive)
+    let parsed = match mailparse::parse_mail(&body) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            let e = anyhow::Error::new(e).context(format!("parsing MIME for UID {}", uid));
+            return (uid, Err(e));
+        }
+    };
 
-        // Create new connection WITHOUT holding the lock (allows parallel creation)
-        debug!("Creating new IMAP connection for {}", key);
-        ImapClient::connect(account, access_token).await
-    }
+    // Sanitize (CPU-intensive); header-only fetches have no body yet
+    let sanitized = (!headers_only).then(|| sanitize_message(&parsed, &body));
 
-    async fn return_connection(&self, key: String, session: ImapSession) {
-        let mut pool = self.connections.lock().await;
-        pool.insert(key, (session, Instant::now()));
-    }
+    // Use pre-extracted envelope data or fallback to headers
+    let subject = envelope_subject
+        .as_ref()
+        .and_then(|s| decode_mime_header(s))
+        .or_else(|| get_header_value(&parsed, "Subject"));
+
+    // Header parsing handles encoded names; the envelope covers messages whose From header is
+    // missing or unparseable.
+    let from_mailbox = parsed
+        .headers
+        .iter()
+        .find(|h| h.get_key().eq_ignore_ascii_case("From"))
+        .and_then(parse_mailbox_header)
+        .or(envelope_from);
+    let (from_name, from) = match from_mailbox {
+        Some(mailbox) => (mailbox.name, Some(mailbox.addr)),
+        None => (None, get_header_value(&parsed, "From")),
+    };
+
+    // Build message record
+    let message_id = gm_msgid.unwrap_or_else(|| format!("{}:{}:{}", account_id, folder_name, uid));
+
+    let mut message = MessageRecord {
+        id: message_id.clone(),
+        account_id: account_id.to_string(),
+        folder: folder_name.to_string(),
+        uid: Some(uid),
+        thread_id: gm_thrid,
+        internal_date,
+        subject,
+        from,
+        from_name,
+        to: get_header_value(&parsed, "To"),
+        cc: get_header_value(&parsed, "Cc"),
+        bcc: get_header_value(&parsed, "Bcc"),
+        flags,
+        labels,
+        has_attachments: false,
+        size_bytes: Some(size),
+        raw_hash: None,
+        message_id_header: get_header_value(&parsed, "Message-ID")
+            .as_deref()
+            .and_then(normalize_message_id),
+        references: parent_references(&parsed),
+        body_status: BodyStatus::Pending,
+        created_at: now_ts(),
+        updated_at: now_ts(),
+    };
+
+    let body_record = sanitized.map(|sanitized| {
+        message.has_attachments = sanitized.has_attachments;
+        message.raw_hash = Some(sanitized.raw_hash.clone());
+        message.body_status = BodyStatus::Full;
+        crate::sanitize::build_body_record(&message_id, Some(body), sanitized)
+    });
+
+    (uid, Ok((message, body_record)))
+}
+
+/// Oldest date a folder's cache covers: its policy cutoff, widened to `backfill_since`.
+fn cache_window_start(
+    account: &Account,
+    folder_name: &str,
+    state: Option<&FolderState>,
+) -> chrono::NaiveDate {
+    let cutoff = account.settings.folder_cutoff(folder_name);
+    state
+        .and_then(|s| s.backfill_since)
+        .map_or(cutoff, |since| since.min(cutoff))
 }
 
-static CONNECTION_POOL: Lazy<ConnectionPool> = Lazy::new(ConnectionPool::new);
+const PROGRESS_CHANNEL_CAPACITY: usize = 256;
 
+#[derive(Clone)]
 pub struct SyncEngine {
-    db: Arc<Database>,
+    db: Arc<dyn MailStore>,
+    /// Caps concurrent folder tasks (and therefore IMAP connections) across all accounts.
+    folder_permits: Arc<Semaphore>,
+    progress: broadcast::Sender<SyncProgress>,
+    /// Per-account download limiters, shared by that account's folder tasks.
+    throttles: Arc<Mutex<HashMap<String, Arc<RateLimiter>>>>,
+    /// Per-folder counters for the current account pass, written to `sync_runs` at its end.
+    run_stats: RunStats,
+    /// Cancelled on Ctrl-C or TUI quit; folder tasks stop at their next batch boundary.
+    cancel: CancellationToken,
+}
+
+/// Per-run sync switches chosen by the caller (CLI/TUI).
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncOptions {
+    /// Bypass the MODSEQ skip and rescan every folder.
+    pub force: bool,
+    /// Baseline scans store envelopes only; bodies are fetched afterwards, newest first.
+    pub headers_first: bool,
+    /// Applied to server flag changes on messages with queued local flag ops.
+    pub flag_conflicts: FlagConflictPolicy,
+    /// Leave queued ops unsent (also honoured per account via `AccountSettings::safe_mode`).
+    pub safe_mode: bool,
+    /// Only fetch new unread mail (`UID SEARCH UNSEEN`); flags, expunges, pending bodies and
+    /// the MODSEQ/UID baseline are left for the next full sync (also `AccountSettings::unread_only`).
+    pub unread_only: bool,
+}
+
+impl SyncOptions {
+    fn unread_only_for(&self, account: &Account) -> bool {
+        self.unread_only || account.settings.unread_only
+    }
 }
 
 #[derive(Debug, Default)]
@@ -75,24 +298,123 @@ struct FolderSyncReport {
     expunged_uids: Vec<u32>,
 }
 
+/// What a folder task hands back to `sync_account`, successful or not.
+struct FolderOutcome {
+    folder: String,
+    duration_ms: i64,
+    result: Result<FolderSyncReport>,
+}
+
 impl SyncEngine {
-    pub fn new(db: Arc<Database>) -> Self {
-        Self { db }
+    pub fn new(db: Arc<dyn MailStore>, max_concurrent_folders: usize) -> Self {
+        let (progress, _) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+        Self {
+            db,
+            folder_permits: Arc::new(Semaphore::new(max_concurrent_folders.max(1))),
+            progress,
+            throttles: Arc::new(Mutex::new(HashMap::new())),
+            run_stats: RunStats::default(),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Uses `cancel` instead of the engine's own token, so one signal can stop several engines.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Token that stops this engine's work. Cancelling it lets running folder tasks commit the
+    /// batch in hand, return their connection to the pool and end with a "sync cancelled"
+    /// error; folders still waiting for a permit and the later phases never start.
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Fails once the engine is cancelled. Only called between committed batches, so the
+    /// session is idle (safe to pool) and the folder's checkpoint is current.
+    fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            bail!("sync cancelled");
+        }
+        Ok(())
     }
 
-    pub async fn sync_all(&self, accounts: &[Account], force: bool) -> Result<()> {
+    /// Subscribe to progress events. The channel closes once the engine (and every folder task
+    /// cloned from it) is dropped; slow receivers see `Lagged` rather than blocking sync.
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncProgress> {
+        self.progress.subscribe()
+    }
+
+    /// Shared download limiter for `account`, rebuilt if its configured rate changed.
+    async fn throttle_for(&self, account: &Account) -> Option<Arc<RateLimiter>> {
+        let bps = account.settings.max_download_bytes_per_sec?;
+        let mut throttles = self.throttles.lock().await;
+        let limiter = throttles
+            .entry(account.id.clone())
+            .or_insert_with(|| Arc::new(RateLimiter::new(bps)));
+        if limiter.bytes_per_sec() != bps {
+            *limiter = Arc::new(RateLimiter::new(bps));
+        }
+        Some(Arc::clone(limiter))
+    }
+
+    fn emit(&self, event: SyncProgress) {
+        self.run_stats.record(&event);
+        // No subscribers is fine; progress is best-effort.
+        let _ = self.progress.send(event);
+    }
+
+    /// Syncs each enabled account in turn; paused accounts are skipped without a report entry.
+    /// Failures do not stop the pass; they are logged and collected in the returned report.
+    pub async fn sync_all(&self, accounts: &[Account], options: SyncOptions) -> SyncReport {
+        let mut report = SyncReport::default();
         for account in accounts {
+            if self.is_cancelled() {
+                info!("Sync cancelled; skipping remaining accounts");
+                break;
+            }
+            if !account.settings.enabled {
+                info!(account = %account.id, "Account paused; skipping sync");
+                continue;
+            }
             info!(account = %account.id, email = %account.email, "Starting IMAP sync");
 
-            if let Err(e) = self.sync_account(account, force).await {
+            let mut account_report = AccountSyncReport {
+                account_id: account.id.clone(),
+                email: account.email.clone(),
+                ..AccountSyncReport::default()
+            };
+            if let Err(e) = self
+                .sync_account(account, options, &mut account_report)
+                .await
+            {
                 warn!(account = %account.id, error = %e, "Account sync failed");
+                account_report.errors.push(format!("{:#}", e));
             }
+            report.accounts.push(account_report);
+            self.emit(SyncProgress::AccountFinished {
+                account_id: account.id.clone(),
+            });
         }
-        Ok(())
+        report.cancelled = self.is_cancelled();
+        report
     }
 
-    async fn sync_account(&self, account: &Account, force: bool) -> Result<()> {
+    async fn sync_account(
+        &self,
+        account: &Account,
+        options: SyncOptions,
+        report: &mut AccountSyncReport,
+    ) -> Result<()> {
         let account_start = Instant::now();
+        let run_started_at = now_ts();
+        // Drop counters left by work outside a sync pass (backfill, folder ops).
+        self.run_stats.take(&account.id);
 
         // Local-only cleanup to remove legacy duplicates created before we extracted X-GM-MSGID.
         // Keeps sync fast while letting existing DBs heal without a wipe.
@@ -103,62 +425,130 @@ impl SyncEngine {
         {
             Ok(0) => {}
             Ok(n) => info!(account = %account.id, deleted = n, "Deduped legacy messages"),
-            Err(e) => warn!(account = %account.id, error = %e, "Deduping legacy messages failed"),
+            Err(e) => {
+                warn!(account = %account.id, error = %e, "Deduping legacy messages failed");
+                report
+                    .errors
+                    .push(format!("deduping legacy messages: {:#}", e));
+            }
+        }
+
+        // Get the OAuth token or password (shared across all connections)
+        let secret_start = Instant::now();
+        let secret = imap_secret(account).await?;
+        info!(account = %account.id, elapsed_ms = ?secret_start.elapsed().as_millis(), "IMAP credentials obtained");
+
+        // Accounts onboarded before discovery existed learn their special-use folders
+        // (localized Trash/All Mail names for ops, and synced folders still at a default name
+        // the server lacks) on their first sync, and accounts without a stored namespace their
+        // folder delimiter.
+        let mut current = account.clone();
+        if (account.settings.namespace.is_none()
+            || self
+                .db
+                .list_discovered_folders(&account.id)
+                .await?
+                .is_empty())
+            && let Err(e) = self.discover_folders(&mut current).await
+        {
+            warn!(account = %account.id, error = %e, "Folder discovery failed");
+            report.errors.push(format!("folder discovery: {:#}", e));
         }
+        let account = &current;
 
-        // Get OAuth token (shared across all connections)
-        let token_start = Instant::now();
-        let scopes = vec![Scope::new("https://mail.google.com/".into())];
-        let token = authorize_with_scopes(&scopes, &account.id).await?;
-        info!(account = %account.id, elapsed_ms = ?token_start.elapsed().as_millis(), "OAuth token obtained");
+        let folders = synced_folders(self.db.as_ref(), account).await?;
+        self.emit(SyncProgress::AccountStarted {
+            account_id: account.id.clone(),
+            folders: folders.len(),
+        });
 
-        // Spawn parallel folder sync tasks (one IMAP connection per folder)
+        // Spawn parallel folder sync tasks (one IMAP connection per folder, bounded by permits)
         let parallel_start = Instant::now();
-        let sync_tasks: Vec<_> = account.settings.folders.iter()
+        let sync_tasks: Vec<_> = folders.iter()
             .map(|folder_name| {
-                let db = Arc::clone(&self.db);
+                let sync_engine = self.clone();
                 let account = account.clone();
                 let folder_name = folder_name.clone();
-                let access_token = token.access_token.clone();
+                let access_token = secret.clone();
 
                 tokio::spawn(async move {
-                    let folder_start = Instant::now();
-                    info!(account = %account.id, folder = %folder_name, "Syncing folder (parallel)");
-
-                    // Get connection from pool (or create new one)
-                    let connect_start = Instant::now();
-                    let pool_key = format!("{}:{}", account.id, folder_name);
-                    let mut session = match CONNECTION_POOL.get_or_create(pool_key.clone(), &account, &access_token).await {
-                        Ok(s) => s,
-                        Err(e) => {
-                            warn!(account = %account.id, folder = %folder_name, error = %e, "IMAP connection failed");
-                            return Err(e);
-                        }
-                    };
-                    debug!(account = %account.id, folder = %folder_name, elapsed_ms = ?connect_start.elapsed().as_millis(), "IMAP connection obtained");
-
-                    // Sync the folder
-                    let sync_engine = SyncEngine { db };
-                    let result = sync_engine.sync_folder(&mut session, &account, &folder_name, force).await;
-
-                    // Return connection to pool (don't logout!)
-                    CONNECTION_POOL.return_connection(pool_key, session).await;
-
-                    match result {
-                        Ok(report) => {
-                            info!(
-                                account = %account.id,
-                                folder = %folder_name,
-                                elapsed_ms = ?folder_start.elapsed().as_millis(),
-                                "Folder sync completed"
-                            );
-                            Ok(report)
+                    let started = Instant::now();
+                    let result: Result<FolderSyncReport> = async {
+                        // Hold a permit for the whole folder sync so connections stay bounded.
+                        let _permit = tokio::select! {
+                            permit = Arc::clone(&sync_engine.folder_permits).acquire_owned() => {
+                                permit.context("acquiring folder sync permit")?
+                            }
+                            _ = sync_engine.cancel.cancelled() => bail!("sync cancelled"),
+                        };
+                        // Over the memory budget, start only once running folders free some.
+                        if !MEMORY.wait_for_room(&sync_engine.cancel).await {
+                            bail!("sync cancelled");
                         }
-                        Err(e) => {
-                            warn!(account = %account.id, folder = %folder_name, error = %e, "Folder sync failed");
-                            Err(e)
+                        let folder_start = Instant::now();
+                        info!(account = %account.id, folder = %folder_name, "Syncing folder (parallel)");
+                        sync_engine.emit(SyncProgress::FolderStarted {
+                            account_id: account.id.clone(),
+                            folder: folder_name.clone(),
+                        });
+
+                        // Get connection from pool (or create new one)
+                        let connect_start = Instant::now();
+                        let mut session = match CONNECTION_POOL.get_or_create(&account, &folder_name, &access_token).await {
+                            Ok(s) => s,
+                            Err(e) => {
+                                warn!(account = %account.id, folder = %folder_name, error = %e, "IMAP connection failed");
+                                sync_engine.emit(SyncProgress::FolderFinished {
+                                    account_id: account.id.clone(),
+                                    folder: folder_name.clone(),
+                                    ok: false,
+                                });
+                                return Err(e);
+                            }
+                        };
+                        debug!(account = %account.id, folder = %folder_name, elapsed_ms = ?connect_start.elapsed().as_millis(), "IMAP connection obtained");
+
+                        // Sync the folder
+                        let result = sync_engine.sync_folder(&mut session, &account, &folder_name, options).await;
+
+                        // Return connection to pool (it logs out what it does not keep)
+                        CONNECTION_POOL.return_connection(&account.id, &folder_name, session).await;
+                        sync_engine.emit(SyncProgress::FolderFinished {
+                            account_id: account.id.clone(),
+                            folder: folder_name.clone(),
+                            ok: result.is_ok(),
+                        });
+
+                        match result {
+                            Ok(report) => {
+                                info!(
+                                    account = %account.id,
+                                    folder = %folder_name,
+                                    elapsed_ms = ?folder_start.elapsed().as_millis(),
+                                    "Folder sync completed"
+                                );
+                                Ok(report)
+                            }
+                            Err(e) if sync_engine.is_cancelled() => {
+                                info!(account = %account.id, folder = %folder_name, "Folder sync stopped (cancelled)");
+                                Err(e)
+                            }
+                            Err(e) if is_timeout(&e) => {
+                                warn!(account = %account.id, folder = %folder_name, error = %e, "Folder sync timed out; retrying next pass");
+                                Err(e)
+                            }
+                            Err(e) => {
+                                warn!(account = %account.id, folder = %folder_name, error = %e, "Folder sync failed");
+                                Err(e)
+                            }
                         }
                     }
+                    .await;
+                    FolderOutcome {
+                        folder: folder_name,
+                        duration_ms: started.elapsed().as_millis() as i64,
+                        result,
+                    }
                 })
             })
             .collect();
@@ -169,16 +559,30 @@ impl SyncEngine {
         // Check for errors
         let mut success_count = 0;
         let mut error_count = 0;
-        let mut reports = Vec::new();
+        let mut folder_reports = Vec::new();
+        let mut folder_runs: Vec<(String, i64, Option<String>)> = Vec::new();
         for result in results {
             match result {
-                Ok(Ok(report)) => {
-                    success_count += 1;
-                    reports.push(report);
-                }
-                Ok(Err(_)) => error_count += 1,
+                Ok(outcome) => match outcome.result {
+                    Ok(report) => {
+                        success_count += 1;
+                        folder_reports.push(report);
+                        folder_runs.push((outcome.folder, outcome.duration_ms, None));
+                    }
+                    Err(e) => {
+                        error_count += 1;
+                        folder_runs.push((
+                            outcome.folder,
+                            outcome.duration_ms,
+                            Some(format!("{:#}", e)),
+                        ));
+                    }
+                },
                 Err(e) => {
                     warn!(account = %account.id, error = %e, "Folder sync task panicked");
+                    report
+                        .errors
+                        .push(format!("folder sync task panicked: {}", e));
                     error_count += 1;
                 }
             }
@@ -186,7 +590,7 @@ impl SyncEngine {
 
         // Apply expunge purges after all folders have synced, so moves across folders
         // don't get deleted before their location updates are processed.
-        for report in reports {
+        for report in folder_reports {
             if report.expunged_uids.is_empty() {
                 continue;
             }
@@ -199,6 +603,11 @@ impl SyncEngine {
                     &report.expunged_uids,
                 )
                 .await?;
+            self.emit(SyncProgress::MessagesExpunged {
+                account_id: account.id.clone(),
+                folder: report.folder.clone(),
+                count: deleted as usize,
+            });
 
             info!(
                 account = %account.id,
@@ -218,38 +627,365 @@ impl SyncEngine {
             "Account sync completed (parallel)"
         );
 
+        // Storage usage is informational: a failure is logged, not reported as a sync error.
+        if !self.is_cancelled()
+            && let Err(e) = self.refresh_quota(account, &secret).await
+        {
+            warn!(account = %account.id, error = %e, "Reading quota failed");
+        }
+
+        // Second phase: download bodies left pending by headers-first scans. Runs on every sync
+        // so an interrupted backlog keeps draining even without --headers-first. Unread-only
+        // runs are meant to stay small and leave the backlog for the next full sync.
+        if self.is_cancelled() {
+            info!(account = %account.id, "Sync cancelled; leaving pending bodies for the next run");
+        } else if options.unread_only_for(account) {
+            debug!(account = %account.id, "Unread-only: leaving pending bodies for a full sync");
+        } else {
+            match self.fetch_pending_bodies(account, &secret).await {
+                Ok(0) => {}
+                Ok(n) => {
+                    info!(account = %account.id, fetched = n, "Fetched pending message bodies");
+                    report.bodies_fetched = n;
+                }
+                Err(e) => {
+                    warn!(account = %account.id, error = %e, "Fetching pending bodies failed");
+                    report
+                        .errors
+                        .push(format!("fetching pending bodies: {:#}", e));
+                }
+            }
+        }
+
+        // Third phase: push queued local flag changes (read, star, labels) to the server.
+        if self.is_cancelled() {
+            debug!(account = %account.id, "Sync cancelled; leaving queued ops unsent");
+        } else if options.safe_mode || account.settings.safe_mode {
+            debug!(account = %account.id, "Safe mode: leaving queued ops unsent");
+        } else {
+            match self.replay_pending_ops(account, &secret).await {
+                Ok(n) => report.ops_settled = n,
+                Err(e) => {
+                    warn!(account = %account.id, error = %e, "Replaying queued ops failed");
+                    report.errors.push(format!("replaying queued ops: {:#}", e));
+                }
+            }
+        }
+
+        // Badge counts, read after the replay so they include the changes just sent. Like the
+        // quota they are informational: a failure is logged, not reported as a sync error.
+        if !self.is_cancelled()
+            && let Err(e) = self.refresh_folder_counts(account, &secret).await
+        {
+            warn!(account = %account.id, error = %e, "Reading folder counts failed");
+        }
+
+        let mut stats = self.run_stats.take(&account.id);
+        let runs: Vec<SyncRunRecord> = folder_runs
+            .into_iter()
+            .map(|(folder, duration_ms, error)| {
+                let folder_stats = stats.remove(&folder).unwrap_or_default();
+                SyncRunRecord {
+                    account_id: account.id.clone(),
+                    folder,
+                    run_started_at,
+                    duration_ms,
+                    added: folder_stats.added,
+                    updated: folder_stats.updated,
+                    deleted: folder_stats.deleted,
+                    bytes: folder_stats.bytes,
+                    ok: error.is_none(),
+                    error,
+                }
+            })
+            .collect();
+        if let Err(e) = self.db.record_sync_runs(&runs).await {
+            warn!(account = %account.id, error = %e, "Recording sync run history failed");
+            report
+                .errors
+                .push(format!("recording sync run history: {:#}", e));
+        }
+        report.folders = runs;
+
         Ok(())
     }
 
-    async fn sync_folder(
+    async fn replay_pending_ops(&self, account: &Account, access_token: &str) -> Result<usize> {
+        let _permit = self
+            .folder_permits
+            .acquire()
+            .await
+            .context("acquiring op replay permit")?;
+        OpsExecutor::new(self.db.clone())
+            .run(account, access_token)
+            .await
+    }
+
+    /// Downloads up to `prefetch_recent` pending bodies, newest first, one folder at a time.
+    async fn fetch_pending_bodies(&self, account: &Account, access_token: &str) -> Result<usize> {
+        let limit = account.settings.prefetch_recent as usize;
+        if limit == 0 {
+            return Ok(0);
+        }
+
+        // Metadata-only and disabled folders keep their bodies pending.
+        let skip_folders: Vec<String> = account
+            .settings
+            .folder_policies
+            .iter()
+            .filter(|(_, policy)| !policy.enabled || policy.body_fetch == BodyFetch::MetadataOnly)
+            .map(|(folder, _)| folder.clone())
+            .collect();
+        let targets = self
+            .db
+            .load_pending_body_targets(&account.id, &skip_folders, limit)
+            .await?;
+        self.fetch_body_targets(account, access_token, targets)
+            .await
+    }
+
+    /// Downloads bodies skipped by `max_message_bytes` regardless of the cap: every such message
+    /// of the account, or only `message_ids`. Bodies that fail to download stay `pending`, so
+    /// the regular body phase retries them. Returns bodies stored.
+    pub async fn fetch_oversized_bodies(
+        &self,
+        account: &Account,
+        message_ids: &[String],
+    ) -> Result<usize> {
+        let targets = self
+            .db
+            .request_oversized_bodies(&account.id, message_ids)
+            .await?;
+        if targets.is_empty() {
+            return Ok(0);
+        }
+        let secret = imap_secret(account).await?;
+        self.fetch_body_targets(account, &secret, targets).await
+    }
+
+    /// Re-downloads the raw bodies of `message_ids` (e.g. after a truncated fetch) whatever their
+    /// body status, overwriting the stored body and re-running the sanitizer. Messages without
+    /// a server UID, or gone from the server, keep what they have. Returns bodies stored.
+    pub async fn refetch(&self, account: &Account, message_ids: &[String]) -> Result<usize> {
+        let targets = self.db.load_body_targets(&account.id, message_ids).await?;
+        if targets.is_empty() {
+            return Ok(0);
+        }
+        let secret = imap_secret(account).await?;
+        self.fetch_body_targets(account, &secret, targets).await
+    }
+
+    /// Syncs one folder over a fresh, unpooled connection whose IMAP conversation is copied to
+    /// `trace` (`otto trace`). Expunges found by the pass are applied like in `sync_account`;
+    /// bodies, queued ops and `sync_runs` are left to regular passes.
+    pub async fn sync_folder_traced(
         &self,
-        session: &mut ImapSession,
         account: &Account,
         folder_name: &str,
-        force: bool,
-    ) -> Result<FolderSyncReport> {
-        const EXPUNGE_SCAN_INTERVAL_SECS: i64 = 6 * 60 * 60;
+        options: SyncOptions,
+        trace: Arc<ProtocolTrace>,
+    ) -> Result<()> {
+        let secret = imap_secret(account).await?;
+        let mut session = ImapClient::connect_traced(account, &secret, Some(trace)).await?;
+        let result = self
+            .sync_folder(&mut session, account, folder_name, options)
+            .await;
+        // Logged out rather than pooled, so the trace ends with the LOGOUT exchange.
+        if let Err(e) = session.logout().await {
+            debug!(account = %account.id, error = %e, "LOGOUT after traced sync failed");
+        }
+        let report = result?;
+        if !report.expunged_uids.is_empty() {
+            self.db
+                .delete_messages_by_folder_and_uids(&account.id, folder_name, &report.expunged_uids)
+                .await?;
+        }
+        Ok(())
+    }
 
-        // Prefer SELECT (CONDSTORE) so we get HIGHESTMODSEQ. If the server doesn't support it,
-        // fall back to a regular SELECT (UID-based sync will be used).
-        let mailbox = match session.select_condstore(folder_name).await {
-            Ok(mbox) => mbox,
-            Err(e) => {
-                warn!(
+    /// Fetches `(message_id, folder, uid)` bodies one folder at a time, in the given order.
+    async fn fetch_body_targets(
+        &self,
+        account: &Account,
+        access_token: &str,
+        targets: Vec<(String, String, u32)>,
+    ) -> Result<usize> {
+        if targets.is_empty() {
+            return Ok(0);
+        }
+
+        // Group by folder, keeping newest-first order within each folder.
+        let mut by_folder: Vec<(String, Vec<(u32, String)>)> = Vec::new();
+        for (message_id, folder, uid) in targets {
+            match by_folder.iter_mut().find(|(name, _)| *name == folder) {
+                Some((_, entries)) => entries.push((uid, message_id)),
+                None => by_folder.push((folder, vec![(uid, message_id)])),
+            }
+        }
+
+        let _permit = self
+            .folder_permits
+            .acquire()
+            .await
+            .context("acquiring body fetch permit")?;
+
+        let mut stored = 0;
+        for (folder_name, entries) in by_folder {
+            if self.is_cancelled() {
+                break;
+            }
+            let mut session = CONNECTION_POOL
+                .get_or_create(account, &folder_name, access_token)
+                .await?;
+            let result = self
+                .fetch_bodies_for_uids(&mut session, account, &folder_name, &entries)
+                .await;
+            CONNECTION_POOL
+                .return_connection(&account.id, &folder_name, session)
+                .await;
+
+            match result {
+                Ok(n) => stored += n,
+                Err(e) => warn!(
                     account = %account.id,
                     folder = %folder_name,
                     error = %e,
-                    "SELECT (CONDSTORE) failed; falling back to SELECT"
-                );
-                session
-                    .select(folder_name)
+                    "Pending body fetch failed"
+                ),
+            }
+        }
+
+        Ok(stored)
+    }
+
+    async fn fetch_bodies_for_uids(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        entries: &[(u32, String)],
+    ) -> Result<usize> {
+        const BATCH_SIZE: usize = 50;
+
+        session
+            .select(folder_name)
+            .await
+            .with_context(|| format!("selecting folder {}", folder_name))?;
+
+        let ids_by_uid: HashMap<u32, String> = entries.iter().cloned().collect();
+        let throttle = self.throttle_for(account).await;
+        let mut stored = 0;
+
+        let mut rest = entries;
+        while !rest.is_empty() {
+            if self.is_cancelled() {
+                break;
+            }
+            let (chunk, tail) = rest.split_at(MEMORY.batch_size(BATCH_SIZE).min(rest.len()));
+            rest = tail;
+            let uids: Vec<u32> = chunk.iter().map(|(uid, _)| *uid).collect();
+            let uid_seq = build_uid_sequence(&uids);
+
+            let mut raw_bodies = Vec::new();
+            {
+                let mut stream = session
+                    .uid_fetch(&uid_seq, "(UID BODY.PEEK[])")
                     .await
-                    .with_context(|| format!("selecting folder {}", folder_name))?
+                    .context("fetching pending bodies")?;
+
+                while let Some(fetch_result) = stream.next().await {
+                    let fetch = match fetch_result {
+                        Ok(f) => f,
+                        Err(e) => {
+                            warn!(error = %e, "Failed to fetch message body");
+                            continue;
+                        }
+                    };
+                    let uid = fetch.uid.unwrap_or(0);
+                    if let (Some(message_id), Some(body)) = (ids_by_uid.get(&uid), fetch.body()) {
+                        if let Some(throttle) = &throttle {
+                            throttle.consume(body.len() as u64).await;
+                        }
+                        raw_bodies.push((message_id.clone(), body.to_vec()));
+                    }
+                }
+            }
+
+            let bytes = raw_bodies.iter().map(|(_, body)| body.len() as u64).sum();
+            let mut lease = MEMORY.lease();
+            lease.grow(bytes);
+            self.emit(SyncProgress::MessagesFetched {
+                account_id: account.id.clone(),
+                folder: folder_name.to_string(),
+                count: raw_bodies.len(),
+                bytes,
+            });
+
+            let updates: Vec<FetchedBodyUpdate> = tokio::task::spawn_blocking(move || {
+                use rayon::prelude::*;
+                raw_bodies
+                    .into_par_iter()
+                    .filter_map(|(message_id, body)| {
+                        let parsed = mailparse::parse_mail(&body).ok()?;
+                        let sanitized = sanitize_message(&parsed, &body);
+                        let has_attachments = sanitized.has_attachments;
+                        let raw_hash = Some(sanitized.raw_hash.clone());
+                        let body_record =
+                            crate::sanitize::build_body_record(&message_id, Some(body), sanitized);
+                        Some((has_attachments, raw_hash, body_record))
+                    })
+                    .collect()
+            })
+            .await
+            .context("parallel body parsing task panicked")?;
+
+            self.db.store_fetched_bodies(&account.id, &updates).await?;
+            self.emit_written(&account.id, folder_name, updates.len());
+            stored += updates.len();
+        }
+
+        Ok(stored)
+    }
+
+    async fn sync_folder(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        options: SyncOptions,
+    ) -> Result<FolderSyncReport> {
+        const EXPUNGE_SCAN_INTERVAL_SECS: i64 = 6 * 60 * 60;
+
+        // Prefer SELECT (CONDSTORE) so we get HIGHESTMODSEQ. Servers that don't advertise
+        // CONDSTORE get a plain SELECT (UID-based sync), as do ones that reject it anyway.
+        let condstore = session.caps().condstore;
+        let mailbox = if condstore {
+            match session.select_condstore(folder_name).await {
+                Ok(mbox) => Some(mbox),
+                Err(e) => {
+                    warn!(
+                        account = %account.id,
+                        folder = %folder_name,
+                        error = %e,
+                        "SELECT (CONDSTORE) failed; falling back to SELECT"
+                    );
+                    None
+                }
             }
+        } else {
+            None
+        };
+        let mailbox = match mailbox {
+            Some(mbox) => mbox,
+            None => session
+                .select(folder_name)
+                .await
+                .with_context(|| format!("selecting folder {}", folder_name))?,
         };
 
         let current_uidvalidity = mailbox.uid_validity.unwrap_or(0);
-        let current_highestmodseq = mailbox.highest_modseq;
+        // Without CONDSTORE a stray HIGHESTMODSEQ can't be followed up with SEARCH MODSEQ.
+        let current_highestmodseq = mailbox.highest_modseq.filter(|_| condstore);
         let current_exists = mailbox.exists;
         let current_highest_uid = mailbox
             .uid_next
@@ -293,6 +1029,19 @@ impl SyncEngine {
                 .db
                 .delete_messages_by_folder(&account.id, folder_name)
                 .await?;
+            self.db
+                .clear_folder_backfill(&account.id, folder_name)
+                .await?;
+            let stale: Vec<u32> = self
+                .db
+                .load_fetch_retries(&account.id, folder_name)
+                .await?
+                .iter()
+                .map(|retry| retry.uid)
+                .collect();
+            self.db
+                .clear_fetch_retries(&account.id, folder_name, &stale)
+                .await?;
 
             warn!(
                 account = %account.id,
@@ -313,6 +1062,10 @@ impl SyncEngine {
                         exists_count: Some(current_exists),
                         last_sync_ts: Some(now),
                         last_uid_scan_ts: None,
+                        baseline_scan_uid: None,
+                        resume_modseq: None,
+                        resume_uid: None,
+                        checkpoint_from_uid: None,
                     },
                 )
                 .await?;
@@ -320,6 +1073,31 @@ impl SyncEngine {
             folder_state = Some(updated);
         }
 
+        let metadata_only =
+            account.settings.folder_policy(folder_name).body_fetch == BodyFetch::MetadataOnly;
+        if metadata_only {
+            // Bodies of messages moved in since the last pass (or from before the policy).
+            let dropped = self.db.drop_folder_bodies(&account.id, folder_name).await?;
+            if dropped > 0 {
+                info!(account = %account.id, folder = %folder_name, dropped, "Dropped bodies in metadata-only folder");
+            }
+        }
+        let headers_only = options.headers_first || metadata_only;
+        self.retry_failed_fetches(session, account, folder_name, headers_only)
+            .await?;
+
+        if options.unread_only_for(account) {
+            return self
+                .sync_folder_unread_only(
+                    session,
+                    account,
+                    folder_name,
+                    &mailbox,
+                    folder_state.as_ref(),
+                )
+                .await;
+        }
+
         // Mark sync start for observability and crash recovery heuristics.
         let baseline_modseq = folder_state.as_ref().and_then(|s| s.highestmodseq);
         let baseline_uid = folder_state.as_ref().and_then(|s| s.highest_uid);
@@ -328,14 +1106,15 @@ impl SyncEngine {
             .await?;
 
         // Build search criteria - use CONDSTORE MODSEQ for change detection
-        let cutoff_str = account.settings.cutoff_since.format("%d-%b-%Y").to_string();
-        let mut pending_messages: Vec<MessageRecord> = Vec::new();
-        let mut pending_bodies: Vec<BodyRecord> = Vec::new();
+        // Backfilled mail older than the account cutoff stays in scope for flag tracking and
+        // expunge detection.
+        let cutoff = cache_window_start(account, folder_name, folder_state.as_ref());
+        let cutoff_str = cutoff.format("%d-%b-%Y").to_string();
         let mut pending_location_updates: Vec<MessageLocationUpdate> = Vec::new();
         let mut pending_flag_updates: Vec<(u32, Vec<String>, Vec<String>)> = Vec::new();
 
         // MODSEQ optimization: Early exit if nothing changed (unless force=true)
-        if !force
+        if !options.force
             && let Some(ref state) = folder_state
             && let (Some(stored_modseq), Some(current_modseq)) =
                 (state.highestmodseq, current_highestmodseq)
@@ -369,6 +1148,10 @@ impl SyncEngine {
                             last_uid_scan_ts: folder_state
                                 .as_ref()
                                 .and_then(|s| s.last_uid_scan_ts),
+                            baseline_scan_uid: None,
+                            resume_modseq: None,
+                            resume_uid: None,
+                            checkpoint_from_uid: None,
                         },
                         "ok",
                         current_highestmodseq,
@@ -400,84 +1183,198 @@ impl SyncEngine {
 
         if stored_modseq == 0 || current_highestmodseq.is_none() {
             // We don't have a usable MODSEQ baseline yet (or server didn't report it).
-            // Fall back to a one-time full scan to establish state.
+            // Fall back to a full scan, windowed by UID range so huge folders don't time out
+            // and an interrupted run resumes from the last committed window.
+            let resume_after = folder_state
+                .as_ref()
+                .and_then(|s| s.baseline_scan_uid)
+                .unwrap_or(0);
             warn!(
                 account = %account.id,
                 folder = %folder_name,
                 stored_modseq = stored_modseq,
                 current_highestmodseq = ?current_highestmodseq,
-                "No MODSEQ baseline available; falling back to full scan"
+                resume_after = resume_after,
+                "No MODSEQ baseline available; falling back to windowed full scan"
             );
 
-            let all_uids_query = format!("SINCE {}", cutoff_str);
-            let uid_set = session
-                .uid_search(&all_uids_query)
-                .await
-                .with_context(|| format!("UID SEARCH baseline: {}", all_uids_query))?;
-            let remote_uids: HashSet<u32> = uid_set.iter().cloned().collect();
-
             let local_uid_map = self
                 .db
                 .load_uid_to_message_id_map_by_folder(&account.id, folder_name)
                 .await?;
             let local_uids: HashSet<u32> = local_uid_map.keys().copied().collect();
 
-            let new_uids: Vec<u32> = remote_uids
-                .iter()
-                .filter(|uid| !local_uids.contains(uid))
-                .copied()
-                .collect();
-
-            if !new_uids.is_empty() {
-                let (messages, bodies) = self
-                    .fetch_and_collect_new_messages(session, account, folder_name, &new_uids)
-                    .await?;
-                pending_messages.extend(messages);
-                pending_bodies.extend(bodies);
-            }
-
-            let expunged_uids: Vec<u32> = local_uids.difference(&remote_uids).copied().collect();
-
-            let highest_uid = current_highest_uid
-                .or_else(|| remote_uids.iter().max().copied())
-                .unwrap_or(stored_highest_uid);
-
-            self.db
-                .commit_folder_batch(
-                    &account.id,
-                    folder_name,
-                    &pending_messages,
-                    &pending_bodies,
-                    &pending_location_updates,
-                    &pending_flag_updates,
-                    &FolderStateUpdate {
-                        uidvalidity: Some(current_uidvalidity),
-                        highest_uid: Some(highest_uid),
-                        highestmodseq: current_highestmodseq,
-                        exists_count: Some(current_exists),
-                        last_sync_ts: Some(now),
-                        last_uid_scan_ts: Some(now),
-                    },
-                    "ok",
-                    current_highestmodseq,
-                    Some(highest_uid),
-                )
-                .await?;
-
-            if !pending_messages.is_empty()
-                && account.provider == crate::types::Provider::GmailImap
-                && let Ok(n) = self
-                    .db
-                    .dedupe_fallback_messages_by_raw_hash(&account.id, 500)
+            let windows = baseline_uid_windows(resume_after, current_highest_uid);
+            let window_count = windows.len();
+            let mut expunged_uids = Vec::new();
+            let mut max_remote_uid: Option<u32> = None;
+
+            for (idx, (lo, hi)) in windows.into_iter().enumerate() {
+                self.check_cancelled()?;
+                let is_last = idx + 1 == window_count;
+                let range = match hi {
+                    Some(hi) => format!("{}:{}", lo, hi),
+                    None => format!("{}:*", lo),
+                };
+                let window_query = format!("UID {} SINCE {}", range, cutoff_str);
+                let uid_set = session
+                    .uid_search(&window_query)
                     .await
-                && n > 0
-            {
+                    .with_context(|| format!("UID SEARCH baseline: {}", window_query))?;
+                // "n:*" always matches the highest UID even when it is below n; clamp to window.
+                let remote_uids: HashSet<u32> =
+                    uid_set.iter().copied().filter(|uid| *uid >= lo).collect();
+                max_remote_uid = max_remote_uid.max(remote_uids.iter().max().copied());
+
+                let in_window = |uid: &u32| *uid >= lo && hi.is_none_or(|hi| *uid <= hi);
+                let mut new_uids: Vec<u32> = remote_uids
+                    .iter()
+                    .filter(|uid| !local_uids.contains(uid))
+                    .copied()
+                    .collect();
+                new_uids.sort_unstable();
+                expunged_uids.extend(
+                    local_uids
+                        .iter()
+                        .filter(|uid| in_window(uid) && !remote_uids.contains(uid))
+                        .copied(),
+                );
+
                 debug!(
                     account = %account.id,
                     folder = %folder_name,
-                    deleted = n,
-                    "Deduped legacy messages after full-scan commit"
+                    window = %range,
+                    window_index = idx + 1,
+                    window_count = window_count,
+                    remote = remote_uids.len(),
+                    new = new_uids.len(),
+                    "Baseline scan window searched"
                 );
+
+                // Commit all but the last batch as they arrive, advancing the checkpoint to the
+                // batch's highest UID, so an interrupted window resumes mid-way.
+                let mut batches: Vec<&[u32]> = new_uids.chunks(CHECKPOINT_BATCH_UIDS).collect();
+                let last_batch = batches.pop().unwrap_or(&[]);
+                for batch in batches {
+                    let (_, location_updates) = self
+                        .fetch_baseline_batch(session, account, folder_name, batch, headers_only)
+                        .await?;
+                    let checkpoint = batch.last().copied().unwrap_or(lo);
+                    self.db
+                        .commit_folder_batch(
+                            &account.id,
+                            folder_name,
+                            &[],
+                            &[],
+                            &location_updates,
+                            &[],
+                            &FolderStateUpdate {
+                                uidvalidity: Some(current_uidvalidity),
+                                highest_uid: Some(checkpoint.max(stored_highest_uid)),
+                                highestmodseq: None,
+                                exists_count: Some(current_exists),
+                                last_sync_ts: Some(now),
+                                last_uid_scan_ts: stored_last_uid_scan_ts,
+                                baseline_scan_uid: Some(checkpoint),
+                                resume_modseq: None,
+                                resume_uid: None,
+                                checkpoint_from_uid: batch.first().copied(),
+                            },
+                            "in_progress",
+                            None,
+                            Some(checkpoint),
+                        )
+                        .await?;
+                    self.emit_updated(&account.id, folder_name, location_updates.len());
+                    self.check_cancelled()?;
+                }
+
+                let (stored, location_updates) = if last_batch.is_empty() {
+                    (Vec::new(), Vec::new())
+                } else {
+                    self.fetch_baseline_batch(
+                        session,
+                        account,
+                        folder_name,
+                        last_batch,
+                        headers_only,
+                    )
+                    .await?
+                };
+
+                // Intermediate windows only advance the checkpoint; the MODSEQ baseline is
+                // recorded once the final window commits so a partial scan never looks complete.
+                let (folder_update, status, checkpoint_uid) = if is_last {
+                    let highest_uid = current_highest_uid
+                        .or(max_remote_uid)
+                        .unwrap_or(stored_highest_uid);
+                    (
+                        FolderStateUpdate {
+                            uidvalidity: Some(current_uidvalidity),
+                            highest_uid: Some(highest_uid),
+                            highestmodseq: current_highestmodseq,
+                            exists_count: Some(current_exists),
+                            last_sync_ts: Some(now),
+                            last_uid_scan_ts: Some(now),
+                            baseline_scan_uid: None,
+                            resume_modseq: None,
+                            resume_uid: None,
+                            checkpoint_from_uid: None,
+                        },
+                        "ok",
+                        highest_uid,
+                    )
+                } else {
+                    let checkpoint = hi.unwrap_or(lo);
+                    (
+                        FolderStateUpdate {
+                            uidvalidity: Some(current_uidvalidity),
+                            highest_uid: Some(checkpoint.max(stored_highest_uid)),
+                            highestmodseq: None,
+                            exists_count: Some(current_exists),
+                            last_sync_ts: Some(now),
+                            last_uid_scan_ts: stored_last_uid_scan_ts,
+                            baseline_scan_uid: Some(checkpoint),
+                            resume_modseq: None,
+                            resume_uid: None,
+                            checkpoint_from_uid: last_batch.first().copied(),
+                        },
+                        "in_progress",
+                        checkpoint,
+                    )
+                };
+
+                self.db
+                    .commit_folder_batch(
+                        &account.id,
+                        folder_name,
+                        &[],
+                        &[],
+                        &location_updates,
+                        &pending_flag_updates,
+                        &folder_update,
+                        status,
+                        folder_update.highestmodseq,
+                        Some(checkpoint_uid),
+                    )
+                    .await?;
+                self.emit_updated(&account.id, folder_name, location_updates.len());
+
+                if !stored.is_empty()
+                    && account.provider == crate::types::Provider::GmailImap
+                    && let Ok(n) = self
+                        .db
+                        .dedupe_fallback_messages_by_raw_hash(&account.id, 500)
+                        .await
+                    && n > 0
+                {
+                    debug!(
+                        account = %account.id,
+                        folder = %folder_name,
+                        deleted = n,
+                        "Deduped legacy messages after full-scan commit"
+                    );
+                }
             }
 
             return Ok(FolderSyncReport {
@@ -536,8 +1433,8 @@ impl SyncEngine {
                 .commit_folder_batch(
                     &account.id,
                     folder_name,
-                    &pending_messages,
-                    &pending_bodies,
+                    &[],
+                    &[],
                     &pending_location_updates,
                     &pending_flag_updates,
                     &FolderStateUpdate {
@@ -547,6 +1444,10 @@ impl SyncEngine {
                         exists_count: Some(current_exists),
                         last_sync_ts: Some(now),
                         last_uid_scan_ts,
+                        baseline_scan_uid: None,
+                        resume_modseq: None,
+                        resume_uid: None,
+                        checkpoint_from_uid: None,
                     },
                     "ok",
                     current_highestmodseq,
@@ -564,15 +1465,28 @@ impl SyncEngine {
             .load_message_ids_by_uids(&account.id, folder_name, &changed_uids)
             .await?;
 
+        // A checkpoint for this same MODSEQ window means an earlier run died mid-pass. Every
+        // checkpoint commit carries the flag updates for the changed UIDs it covers, so UIDs up
+        // to it were committed with fresh flags (new or not) and only the remainder needs work.
+        let resume_uid = folder_state
+            .as_ref()
+            .filter(|s| s.resume_modseq == Some(stored_modseq))
+            .and_then(|s| s.resume_uid)
+            .unwrap_or(0);
+
         let mut new_uids = Vec::new();
         let mut existing_uids = Vec::new();
         for uid in &changed_uids {
             if existing_by_uid.contains_key(uid) {
-                existing_uids.push(*uid);
+                if *uid > resume_uid {
+                    existing_uids.push(*uid);
+                }
             } else {
                 new_uids.push(*uid);
             }
         }
+        new_uids.sort_unstable();
+        existing_uids.sort_unstable();
 
         info!(
             account = %account.id,
@@ -580,25 +1494,88 @@ impl SyncEngine {
             changed = changed_uids.len(),
             new = new_uids.len(),
             existing = existing_uids.len(),
+            resume_uid = resume_uid,
             "Incremental UID diff computed"
         );
 
-        if !new_uids.is_empty() {
-            let (messages, bodies, location_updates) = self
-                .fetch_and_handle_new_uids(session, account, folder_name, &new_uids)
+        // New messages are stored as they are fetched. Every batch but the last then commits a
+        // checkpoint (MODSEQ left at the old value so the next run searches the same window),
+        // together with the flag updates of the existing UIDs below it; the remaining flags
+        // join the final commit below.
+        let mut written = 0;
+        let mut batches: Vec<&[u32]> = new_uids.chunks(CHECKPOINT_BATCH_UIDS).collect();
+        let last_batch = batches.pop().unwrap_or(&[]);
+        let mut flags_pending: &[u32] = &existing_uids;
+        for batch in batches {
+            let (stored, location_updates) = self
+                .fetch_and_handle_new_uids(session, account, folder_name, batch, false)
                 .await?;
-            pending_messages.extend(messages);
-            pending_bodies.extend(bodies);
-            pending_location_updates.extend(location_updates);
+            let checkpoint = batch.last().copied().unwrap_or(resume_uid);
+            let (covered, rest) =
+                flags_pending.split_at(flags_pending.partition_point(|uid| *uid <= checkpoint));
+            flags_pending = rest;
+            let flag_updates = self
+                .changed_flags(
+                    session,
+                    account,
+                    folder_name,
+                    covered,
+                    options.flag_conflicts,
+                )
+                .await?;
+            self.db
+                .commit_folder_batch(
+                    &account.id,
+                    folder_name,
+                    &[],
+                    &[],
+                    &location_updates,
+                    &flag_updates,
+                    &FolderStateUpdate {
+                        uidvalidity: Some(current_uidvalidity),
+                        highest_uid: Some(stored_highest_uid),
+                        highestmodseq: Some(stored_modseq),
+                        exists_count: Some(stored_exists),
+                        last_sync_ts: Some(now),
+                        last_uid_scan_ts: stored_last_uid_scan_ts,
+                        baseline_scan_uid: None,
+                        resume_modseq: Some(stored_modseq),
+                        resume_uid: Some(checkpoint),
+                        checkpoint_from_uid: batch.first().copied(),
+                    },
+                    "in_progress",
+                    Some(stored_modseq),
+                    Some(checkpoint),
+                )
+                .await?;
+            self.emit_updated(
+                &account.id,
+                folder_name,
+                location_updates.len() + flag_updates.len(),
+            );
+            written += stored.len();
+            self.check_cancelled()?;
         }
 
-        if !existing_uids.is_empty() {
-            let mut updates = self
-                .fetch_and_update_flags(session, account, folder_name, &existing_uids)
+        if !last_batch.is_empty() {
+            let (stored, location_updates) = self
+                .fetch_and_handle_new_uids(session, account, folder_name, last_batch, false)
                 .await?;
-            pending_flag_updates.append(&mut updates);
+            written += stored.len();
+            pending_location_updates.extend(location_updates);
         }
 
+        let mut updates = self
+            .changed_flags(
+                session,
+                account,
+                folder_name,
+                flags_pending,
+                options.flag_conflicts,
+            )
+            .await?;
+        pending_flag_updates.append(&mut updates);
+
         let mut expunged_uids = Vec::new();
         let mut last_uid_scan_ts = stored_last_uid_scan_ts;
         let should_uid_scan = (stored_exists > 0 && current_exists < stored_exists)
@@ -631,8 +1608,8 @@ impl SyncEngine {
             .commit_folder_batch(
                 &account.id,
                 folder_name,
-                &pending_messages,
-                &pending_bodies,
+                &[],
+                &[],
                 &pending_location_updates,
                 &pending_flag_updates,
                 &FolderStateUpdate {
@@ -642,14 +1619,23 @@ impl SyncEngine {
                     exists_count: Some(current_exists),
                     last_sync_ts: Some(now),
                     last_uid_scan_ts,
+                    baseline_scan_uid: None,
+                    resume_modseq: None,
+                    resume_uid: None,
+                    checkpoint_from_uid: None,
                 },
                 "ok",
                 current_highestmodseq,
                 Some(highest_uid),
             )
             .await?;
+        self.emit_updated(
+            &account.id,
+            folder_name,
+            pending_location_updates.len() + pending_flag_updates.len(),
+        );
 
-        if !pending_messages.is_empty()
+        if written > 0
             && account.provider == crate::types::Provider::GmailImap
             && let Ok(n) = self
                 .db
@@ -703,16 +1689,120 @@ impl SyncEngine {
         account: &Account,
         folder_name: &str,
         uids: &[u32],
-    ) -> Result<(Vec<MessageRecord>, Vec<BodyRecord>)> {
+        headers_only: bool,
+    ) -> Result<Vec<u32>> {
+        self.emit(SyncProgress::UidsPlanned {
+            account_id: account.id.clone(),
+            folder: folder_name.to_string(),
+            count: uids.len(),
+        });
+
+        let Some(max_bytes) = account.settings.max_message_bytes else {
+            return self
+                .fetch_and_parse_messages(session, account, folder_name, uids, headers_only)
+                .await;
+        };
+
+        // Messages over the account's size cap only get their headers; the body waits for
+        // `otto fetch-bodies`. Header-only fetches carry RFC822.SIZE, so only full fetches
+        // need a cheap size probe first.
+        let oversized = if headers_only {
+            HashSet::new()
+        } else {
+            self.probe_oversized(session, uids, max_bytes).await?
+        };
+        let (large, small): (Vec<u32>, Vec<u32>) =
+            uids.iter().partition(|uid| oversized.contains(uid));
+
+        let mut stored = self
+            .fetch_and_parse_messages(session, account, folder_name, &small, headers_only)
+            .await?;
+        if !large.is_empty() {
+            info!(
+                account = %account.id,
+                folder = %folder_name,
+                count = large.len(),
+                max_bytes = max_bytes,
+                "Skipping bodies of oversized messages"
+            );
+            stored.extend(
+                self.fetch_and_parse_messages(session, account, folder_name, &large, true)
+                    .await?,
+            );
+        }
+        Ok(stored)
+    }
+
+    /// UIDs among `uids` whose RFC822.SIZE exceeds `max_bytes`.
+    async fn probe_oversized(
+        &self,
+        session: &mut ImapSession,
+        uids: &[u32],
+        max_bytes: u64,
+    ) -> Result<HashSet<u32>> {
+        let mut oversized = HashSet::new();
+        if uids.is_empty() {
+            return Ok(oversized);
+        }
+        let mut stream = session
+            .uid_fetch(build_uid_sequence(uids), "(UID RFC822.SIZE)")
+            .await
+            .context("fetching message sizes")?;
+        while let Some(fetch_result) = stream.next().await {
+            let fetch = match fetch_result {
+                Ok(f) => f,
+                Err(e) => {
+                    warn!(error = %e, "Failed to fetch message size");
+                    continue;
+                }
+            };
+            if let (Some(uid), Some(size)) = (fetch.uid, fetch.size)
+                && u64::from(size) > max_bytes
+            {
+                oversized.insert(uid);
+            }
+        }
+        Ok(oversized)
+    }
+
+    /// Fetches, parses and stores `uids` chunk by chunk; returns the UIDs stored. Folder state is
+    /// left to the caller.
+    async fn fetch_and_parse_messages(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        uids: &[u32],
+        headers_only: bool,
+    ) -> Result<Vec<u32>> {
         // Limit batch size to avoid memory issues
         const BATCH_SIZE: usize = 50;
 
-        let mut all_messages = Vec::new();
-        let mut all_bodies = Vec::new();
+        let mut stored = Vec::new();
+        let throttle = self.throttle_for(account).await;
+        let mut reconnects = 0;
 
-        for chunk in uids.chunks(BATCH_SIZE) {
+        // Set when the connection was found dead before a FETCH could start.
+        let mut lost = None;
+
+        let mut rest = uids.to_vec();
+        while !rest.is_empty() {
+            if let Some(e) = lost.take() {
+                reconnects += 1;
+                if !self
+                    .reconnect_session(session, account, folder_name, reconnects)
+                    .await
+                {
+                    return Err(e).context("fetching message metadata and bodies");
+                }
+            }
+            let chunk: Vec<u32> = rest
+                .drain(..MEMORY.batch_size(BATCH_SIZE).min(rest.len()))
+                .collect();
             let batch_start = Instant::now();
-            let uid_seq = Self::build_uid_sequence(chunk);
+            let uid_seq = build_uid_sequence(&chunk);
+            // Held until the chunk is committed.
+            let mut lease = MEMORY.lease();
 
             debug!(
                 account = %account.id,
@@ -722,14 +1812,24 @@ impl SyncEngine {
                 "Fetching batch of new messages"
             );
 
-            // Fetch metadata + bodies
-            let fetch_query = "(UID FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[] ENVELOPE X-GM-MSGID X-GM-THRID X-GM-LABELS)";
+            // Fetch metadata + bodies (or just the header block in headers-first mode)
+            let fetch_query = session.caps().fetch_query(if headers_only {
+                "UID FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[HEADER] ENVELOPE"
+            } else {
+                "UID FLAGS INTERNALDATE RFC822.SIZE BODY.PEEK[] ENVELOPE"
+            });
 
             let fetch_start = Instant::now();
-            let mut stream = session
-                .uid_fetch(&uid_seq, fetch_query)
-                .await
-                .context("fetching message metadata and bodies")?;
+            let mut stream = match session.uid_fetch(&uid_seq, &fetch_query).await {
+                Ok(stream) => stream,
+                Err(e) if is_connection_lost(&e) && reconnects < MAX_RECONNECTS => {
+                    // Reconnected at the top of the loop, then the chunk is fetched again.
+                    rest.splice(0..0, chunk);
+                    lost = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e).context("fetching message metadata and bodies"),
+            };
 
             debug!(
                 account = %account.id,
@@ -738,19 +1838,50 @@ impl SyncEngine {
                 "FETCH command completed, processing stream"
             );
 
-            // Step 1: Collect all raw fetches (fast - just memory copies)
-            let mut raw_fetches = Vec::new();
+            // Step 1: Stream raw fetches into the parse stage through a bounded queue, so only a
+            // few unparsed bodies wait at a time and a full queue slows the FETCH reader (and,
+            // through TCP backpressure, the server) instead of buffering the whole chunk.
+            let parse_start = Instant::now();
+            let (raw_tx, raw_rx) = tokio::sync::mpsc::channel::<RawFetch>(PARSE_QUEUE_DEPTH);
+            // Step 2: Parse and sanitize in parallel (CPU-intensive work) as bodies arrive.
+            let parser = tokio::task::spawn_blocking({
+                let account_id = account.id.clone();
+                let folder_name = folder_name.to_string();
+                move || drain_into_parser(raw_rx, account_id, folder_name, headers_only)
+            });
+
+            let mut returned = HashSet::new();
+            let mut fetched = 0;
+            let mut bytes = 0;
+            let mut stream_error = None;
+            let mut connection_lost = false;
             while let Some(fetch_result) = stream.next().await {
                 let fetch = match fetch_result {
                     Ok(f) => f,
                     Err(e) => {
                         warn!(error = %e, "Failed to fetch message");
+                        stream_error = Some(e.to_string());
+                        // Nothing more arrives on a dead connection.
+                        if is_connection_lost(&e) {
+                            connection_lost = true;
+                            break;
+                        }
                         continue;
                     }
                 };
 
                 let uid = fetch.uid.unwrap_or(0);
-                let body = fetch.body().unwrap_or(&[]).to_vec();
+                let body = if headers_only {
+                    fetch.header()
+                } else {
+                    fetch.body()
+                }
+                .unwrap_or(&[])
+                .to_vec();
+                // Pausing between responses lets TCP backpressure slow the server down.
+                if let Some(throttle) = &throttle {
+                    throttle.consume(body.len() as u64).await;
+                }
                 let flags: Vec<String> = fetch.flags().map(|f| format!("{:?}", f)).collect();
                 let size = fetch.size.unwrap_or(0) as u32;
                 let internal_date = fetch.internal_date().map(|dt| dt.timestamp());
@@ -770,13 +1901,28 @@ impl SyncEngine {
                     .and_then(|e| e.from.as_ref())
                     .and_then(|addrs| addrs.first())
                     .and_then(|addr| {
-                        addr.mailbox
-                            .as_ref()
-                            .and_then(|m| std::str::from_utf8(m).ok())
-                            .map(|m| m.to_string())
+                        let part = |bytes: &Option<std::borrow::Cow<'_, [u8]>>| {
+                            bytes
+                                .as_ref()
+                                .and_then(|b| std::str::from_utf8(b).ok())
+                                .map(|s| s.to_string())
+                        };
+                        let mailbox = part(&addr.mailbox)?;
+                        let addr_spec = match part(&addr.host) {
+                            Some(host) => format!("{}@{}", mailbox, host),
+                            None => mailbox,
+                        };
+                        Some(Mailbox {
+                            name: part(&addr.name).and_then(|n| decode_mime_header(&n)),
+                            addr: addr_spec,
+                        })
                     });
 
-                raw_fetches.push((
+                returned.insert(uid);
+                fetched += 1;
+                bytes += body.len() as u64;
+                lease.grow(body.len() as u64);
+                let raw = RawFetch {
                     uid,
                     body,
                     envelope_subject,
@@ -787,95 +1933,59 @@ impl SyncEngine {
                     gm_msgid,
                     gm_thrid,
                     labels,
-                ));
+                };
+                if raw_tx.send(raw).await.is_err() {
+                    // The parse stage is gone (it panicked); joining it below reports why.
+                    break;
+                }
             }
+            drop(raw_tx);
+            drop(stream);
 
             debug!(
                 account = %account.id,
                 folder = %folder_name,
-                count = raw_fetches.len(),
+                count = fetched,
                 fetch_ms = ?fetch_start.elapsed().as_millis(),
-                "Fetched raw messages, starting parallel parse"
+                "Fetched raw messages, finishing parallel parse"
             );
 
-            // Step 2: Parse and sanitize in parallel (CPU-intensive work)
-            let parse_start = Instant::now();
-            let account_id = account.id.clone();
-            let folder_name_owned = folder_name.to_string();
-
-            let parsed_results: Vec<Result<(MessageRecord, BodyRecord)>> =
-                tokio::task::spawn_blocking(move || {
-                    use rayon::prelude::*;
-                    raw_fetches
-                        .into_par_iter()
-                        .map(
-                            |(
-                                uid,
-                                body,
-                                envelope_subject,
-                                envelope_from,
-                                flags,
-                                size,
-                                internal_date,
-                                gm_msgid,
-                                gm_thrid,
-                                labels,
-                            )| {
-                                // Parse MIME (CPU-intensive)
-                                let parsed = mailparse::parse_mail(&body)
-                                    .with_context(|| format!("parsing MIME for UID {}", uid))?;
-
-                                // Sanitize (CPU-intensive)
-                                let sanitized = sanitize_message(&parsed, &body);
-
-                                // Use pre-extracted envelope data or fallback to headers
-                                let subject = envelope_subject
-                                    .as_ref()
-                                    .and_then(|s| decode_mime_header(s))
-                                    .or_else(|| get_header_value(&parsed, "Subject"));
-
-                                let from =
-                                    envelope_from.or_else(|| get_header_value(&parsed, "From"));
-
-                                // Build message record
-                                let message_id = gm_msgid.unwrap_or_else(|| {
-                                    format!("{}:{}:{}", account_id, folder_name_owned, uid)
-                                });
-
-                                let message = MessageRecord {
-                                    id: message_id.clone(),
-                                    account_id: account_id.clone(),
-                                    folder: folder_name_owned.clone(),
-                                    uid: Some(uid),
-                                    thread_id: gm_thrid,
-                                    internal_date,
-                                    subject,
-                                    from,
-                                    to: get_header_value(&parsed, "To"),
-                                    cc: get_header_value(&parsed, "Cc"),
-                                    bcc: get_header_value(&parsed, "Bcc"),
-                                    flags,
-                                    labels,
-                                    has_attachments: sanitized.has_attachments,
-                                    size_bytes: Some(size),
-                                    raw_hash: Some(sanitized.raw_hash.clone()),
-                                    created_at: now_ts(),
-                                    updated_at: now_ts(),
-                                };
-
-                                let body_record = crate::sanitize::build_body_record(
-                                    &message_id,
-                                    Some(body),
-                                    sanitized,
-                                );
+            // After a lost connection, the UIDs it never answered are fetched again on a new one
+            // (ahead of the rest); what is already in hand is kept.
+            let mut unanswered: Vec<u32> = chunk
+                .iter()
+                .filter(|uid| !returned.contains(uid))
+                .copied()
+                .collect();
+            if connection_lost && !unanswered.is_empty() && reconnects < MAX_RECONNECTS {
+                reconnects += 1;
+                if self
+                    .reconnect_session(session, account, folder_name, reconnects)
+                    .await
+                {
+                    rest.splice(0..0, unanswered.drain(..));
+                }
+            }
 
-                                Ok((message, body_record))
-                            },
-                        )
-                        .collect()
+            // UIDs the server did not answer for go to the retry queue instead of being
+            // skipped for good once the folder checkpoint moves past them.
+            let mut failures: Vec<(u32, String)> = unanswered
+                .iter()
+                .map(|uid| {
+                    let error = stream_error
+                        .clone()
+                        .unwrap_or_else(|| "no FETCH response for UID".to_string());
+                    (*uid, error)
                 })
-                .await
-                .context("parallel parsing task panicked")?;
+                .collect();
+            self.emit(SyncProgress::MessagesFetched {
+                account_id: account.id.clone(),
+                folder: folder_name.to_string(),
+                count: fetched,
+                bytes,
+            });
+
+            let parsed_results = parser.await.context("parallel parsing task panicked")?;
 
             debug!(
                 account = %account.id,
@@ -888,59 +1998,124 @@ impl SyncEngine {
             let mut messages_batch = Vec::new();
             let mut bodies_batch = Vec::new();
 
-            for result in parsed_results {
+            let max_bytes = account.settings.max_message_bytes;
+            for (uid, result) in parsed_results {
                 match result {
-                    Ok((msg, body)) => {
+                    Ok((mut msg, body)) => {
+                        // Headers of messages over the account's size cap; the body waits for
+                        // `otto fetch-bodies`.
+                        if msg.body_status == BodyStatus::Pending
+                            && let (Some(max_bytes), Some(size)) = (max_bytes, msg.size_bytes)
+                            && u64::from(size) > max_bytes
+                        {
+                            msg.body_status = BodyStatus::TooLarge;
+                        }
                         messages_batch.push(msg);
-                        bodies_batch.push(body);
+                        bodies_batch.extend(body);
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Failed to parse message");
+                        failures.push((uid, format!("{:#}", e)));
                     }
-                    Err(e) => warn!(error = %e, "Failed to parse message"),
                 }
             }
+            if !failures.is_empty() {
+                warn!(
+                    account = %account.id,
+                    folder = %folder_name,
+                    count = failures.len(),
+                    "Queued failed message fetches for retry"
+                );
+                self.db
+                    .record_fetch_failures(&account.id, folder_name, &failures)
+                    .await?;
+            }
+
+            self.emit(SyncProgress::MessagesParsed {
+                account_id: account.id.clone(),
+                folder: folder_name.to_string(),
+                count: messages_batch.len(),
+            });
 
+            // Step 4: Store the chunk right away (without moving any checkpoint; the caller
+            // does that once its batch is done), so its bodies are dropped before the next FETCH.
             if !messages_batch.is_empty() {
-                let batch_count = messages_batch.len();
-                all_messages.extend(messages_batch);
-                all_bodies.extend(bodies_batch);
+                self.db
+                    .commit_backfill_batch(
+                        &account.id,
+                        folder_name,
+                        &messages_batch,
+                        &bodies_batch,
+                        &[],
+                        None,
+                    )
+                    .await?;
+                self.emit_written(&account.id, folder_name, messages_batch.len());
+                stored.extend(messages_batch.iter().filter_map(|m| m.uid));
 
                 info!(
                     account = %account.id,
                     folder = %folder_name,
-                    count = batch_count,
+                    count = messages_batch.len(),
                     fetch_ms = ?fetch_start.elapsed().as_millis(),
                     parse_ms = ?parse_start.elapsed().as_millis(),
                     total_ms = ?batch_start.elapsed().as_millis(),
-                    "Batch processed (parallel parse, pending commit)"
+                    "Batch processed and stored"
                 );
             }
+            drop(lease);
         }
 
-        Ok((all_messages, all_bodies))
+        Ok(stored)
     }
 
+    /// New UIDs of a baseline scan. Switching an account to All Mail mode starts a baseline of
+    /// All Mail while most of its messages are cached from the per-folder sync; those are
+    /// re-homed by X-GM-MSGID (`fetch_and_handle_new_uids`) instead of downloaded again.
+    async fn fetch_baseline_batch(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        uids: &[u32],
+        headers_only: bool,
+    ) -> Result<(Vec<u32>, Vec<MessageLocationUpdate>)> {
+        if account.settings.all_mail_mode {
+            return self
+                .fetch_and_handle_new_uids(session, account, folder_name, uids, headers_only)
+                .await;
+        }
+        let stored = self
+            .fetch_and_collect_new_messages(session, account, folder_name, uids, headers_only)
+            .await?;
+        Ok((stored, Vec::new()))
+    }
+
+    /// Stores the new messages among `uids` as they are fetched and returns their UIDs; copies
+    /// already cached from another folder come back as location updates for the caller's commit.
     async fn fetch_and_handle_new_uids(
         &self,
         session: &mut ImapSession,
         account: &Account,
         folder_name: &str,
         uids: &[u32],
-    ) -> Result<(
-        Vec<MessageRecord>,
-        Vec<BodyRecord>,
-        Vec<MessageLocationUpdate>,
-    )> {
+        headers_first: bool,
+    ) -> Result<(Vec<u32>, Vec<MessageLocationUpdate>)> {
         const BATCH_SIZE: usize = 250;
 
+        let headers_only = headers_first
+            || account.settings.folder_policy(folder_name).body_fetch == BodyFetch::MetadataOnly;
         let mut need_body: Vec<u32> = Vec::new();
         let mut location_updates: Vec<MessageLocationUpdate> = Vec::new();
 
         for chunk in uids.chunks(BATCH_SIZE) {
-            let uid_seq = Self::build_uid_sequence(chunk);
-            let fetch_query =
-                "(UID FLAGS INTERNALDATE RFC822.SIZE ENVELOPE X-GM-MSGID X-GM-THRID X-GM-LABELS)";
+            let uid_seq = build_uid_sequence(chunk);
+            let fetch_query = session
+                .caps()
+                .fetch_query("UID FLAGS INTERNALDATE RFC822.SIZE ENVELOPE");
 
             let mut stream = session
-                .uid_fetch(&uid_seq, fetch_query)
+                .uid_fetch(&uid_seq, &fetch_query)
                 .await
                 .context("fetching metadata for new UIDs")?;
 
@@ -966,12 +2141,23 @@ impl SyncEngine {
                 let size = fetch.size;
                 let internal_date = fetch.internal_date().map(|dt| dt.timestamp());
 
+                // Without X-GM-MSGID, the envelope's Message-ID can still match a copy
+                // cached from another folder.
+                let header_id = match gm_msgid {
+                    Some(_) => None,
+                    None => fetch
+                        .envelope()
+                        .and_then(|e| e.message_id.as_ref())
+                        .and_then(|id| std::str::from_utf8(id).ok())
+                        .and_then(normalize_message_id),
+                };
                 let message_id =
                     gm_msgid.unwrap_or_else(|| format!("{}:{}:{}", account.id, folder_name, uid));
 
                 batch.push((
                     uid,
                     message_id,
+                    header_id,
                     flags,
                     labels,
                     gm_thrid,
@@ -984,6 +2170,26 @@ impl SyncEngine {
                 continue;
             }
 
+            let headers: Vec<String> = batch
+                .iter()
+                .filter_map(|(_, _, header_id, ..)| header_id.clone())
+                .collect();
+            let copies = self
+                .db
+                .load_message_ids_by_header(&account.id, folder_name, &headers)
+                .await?;
+            let batch: Vec<_> = batch
+                .into_iter()
+                .map(
+                    |(uid, message_id, header_id, flags, labels, thread_id, date, size)| {
+                        let message_id = header_id
+                            .and_then(|h| copies.get(&h).cloned())
+                            .unwrap_or(message_id);
+                        (uid, message_id, flags, labels, thread_id, date, size)
+                    },
+                )
+                .collect();
+
             let ids: Vec<String> = batch
                 .iter()
                 .map(|(_, id, _, _, _, _, _)| id.clone())
@@ -1008,18 +2214,20 @@ impl SyncEngine {
             }
         }
 
-        let mut messages = Vec::new();
-        let mut bodies = Vec::new();
-
-        if !need_body.is_empty() {
-            let (fetched_messages, fetched_bodies) = self
-                .fetch_and_collect_new_messages(session, account, folder_name, &need_body)
-                .await?;
-            messages.extend(fetched_messages);
-            bodies.extend(fetched_bodies);
-        }
+        let stored = if need_body.is_empty() {
+            Vec::new()
+        } else {
+            self.fetch_and_collect_new_messages(
+                session,
+                account,
+                folder_name,
+                &need_body,
+                headers_only,
+            )
+            .await?
+        };
 
-        Ok((messages, bodies, location_updates))
+        Ok((stored, location_updates))
     }
 
     #[allow(dead_code)]
@@ -1034,7 +2242,7 @@ impl SyncEngine {
         const BATCH_SIZE: usize = 100;
 
         for chunk in uids.chunks(BATCH_SIZE) {
-            let uid_seq = Self::build_uid_sequence(chunk);
+            let uid_seq = build_uid_sequence(chunk);
 
             debug!(
                 account = %account.id,
@@ -1043,10 +2251,10 @@ impl SyncEngine {
                 "Updating flags/labels for existing messages"
             );
 
-            let fetch_query = "(UID FLAGS X-GM-LABELS)";
+            let fetch_query = session.caps().fetch_query("UID FLAGS");
 
             let mut stream = session
-                .uid_fetch(&uid_seq, fetch_query)
+                .uid_fetch(&uid_seq, &fetch_query)
                 .await
                 .context("fetching message flags")?;
 
@@ -1077,17 +2285,24 @@ impl SyncEngine {
         Ok(())
     }
 
-    fn build_uid_sequence(uids: &[u32]) -> String {
-        if uids.is_empty() {
-            return "1".to_string();
+    fn emit_written(&self, account_id: &str, folder_name: &str, count: usize) {
+        if count > 0 {
+            self.emit(SyncProgress::MessagesWritten {
+                account_id: account_id.to_string(),
+                folder: folder_name.to_string(),
+                count,
+            });
         }
+    }
 
-        // Simple comma-separated list
-        // In production, compress to ranges (e.g., "1:5,7,10:15")
-        uids.iter()
-            .map(|u| u.to_string())
-            .collect::<Vec<_>>()
-            .join(",")
+    fn emit_updated(&self, account_id: &str, folder_name: &str, count: usize) {
+        if count > 0 {
+            self.emit(SyncProgress::MessagesUpdated {
+                account_id: account_id.to_string(),
+                folder: folder_name.to_string(),
+                count,
+            });
+        }
     }
 
     fn extract_gm_msgid(fetch: &async_imap::types::Fetch) -> Option<String> {
@@ -1115,9 +2330,10 @@ impl SyncEngine {
 
         let mut updates: Vec<(u32, Vec<String>, Vec<String>)> = Vec::new();
         for chunk in uids.chunks(BATCH_SIZE) {
-            let uid_seq = Self::build_uid_sequence(chunk);
+            let uid_seq = build_uid_sequence(chunk);
+            let fetch_query = session.caps().fetch_query("UID FLAGS");
             let mut stream = session
-                .uid_fetch(&uid_seq, "(UID FLAGS X-GM-LABELS)")
+                .uid_fetch(&uid_seq, &fetch_query)
                 .await
                 .context("fetching flags/labels for changed messages")?;
 
@@ -1154,6 +2370,136 @@ impl SyncEngine {
     }
 }
 
+impl SyncEngine {
+    /// Current flags of `uids` with pending local flag ops resolved, ready to commit.
+    async fn changed_flags(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        uids: &[u32],
+        policy: FlagConflictPolicy,
+    ) -> Result<Vec<(u32, Vec<String>, Vec<String>)>> {
+        if uids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut updates = self
+            .fetch_and_update_flags(session, account, folder_name, uids)
+            .await?;
+        self.resolve_flag_conflicts(account, folder_name, &mut updates, policy)
+            .await?;
+        Ok(updates)
+    }
+
+    /// Settles server flag updates for messages that still have queued local flag ops, per
+    /// `policy`. Updates for other messages pass through unchanged. Under
+    /// `FlagConflictPolicy::Flag`, messages the server changed since their ops were queued keep
+    /// their local row and the ops are held in `pending_ops.conflict`.
+    async fn resolve_flag_conflicts(
+        &self,
+        account: &Account,
+        folder_name: &str,
+        updates: &mut Vec<(u32, Vec<String>, Vec<String>)>,
+        policy: FlagConflictPolicy,
+    ) -> Result<()> {
+        let uids: Vec<u32> = updates.iter().map(|(uid, _, _)| *uid).collect();
+        let pending = self
+            .db
+            .load_pending_flag_ops(&account.id, folder_name, &uids)
+            .await?;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut ops_by_uid: HashMap<u32, Vec<PendingFlagOp>> = HashMap::new();
+        let op_ids: Vec<i64> = pending.iter().map(|p| p.id).collect();
+        for p in pending {
+            ops_by_uid.entry(p.uid).or_default().push(p);
+        }
+
+        let mut held: Vec<(Vec<i64>, Vec<String>, Vec<String>)> = Vec::new();
+        updates.retain_mut(|(uid, flags, labels)| {
+            let Some(queued) = ops_by_uid.get(uid) else {
+                return true;
+            };
+            let ops: Vec<MessageOp> = queued.iter().map(|p| p.op.clone()).collect();
+            // The oldest op's snapshot is the message before any queued local change.
+            if policy == FlagConflictPolicy::Flag
+                && let Some((snapshot_flags, snapshot_labels)) = &queued[0].snapshot
+                && is_remote_conflict((snapshot_flags, snapshot_labels), (flags, labels), &ops)
+            {
+                let ids = queued.iter().map(|p| p.id).collect();
+                held.push((ids, flags.clone(), labels.clone()));
+                return false;
+            }
+            match policy.resolve(flags, labels, &ops) {
+                Some((resolved_flags, resolved_labels)) => {
+                    *flags = resolved_flags;
+                    *labels = resolved_labels;
+                    true
+                }
+                None => false,
+            }
+        });
+
+        // The server state is final; replaying the dropped ops would undo it.
+        if policy == FlagConflictPolicy::ServerWins {
+            self.db.clear_pending_ops(&op_ids).await?;
+        }
+        for (ids, flags, labels) in &held {
+            self.db.mark_op_conflicts(ids, flags, labels).await?;
+        }
+        if !held.is_empty() {
+            warn!(
+                account = %account.id,
+                folder = %folder_name,
+                messages = held.len(),
+                "Server changed messages with queued local changes; holding them until `otto conflicts` settles them"
+            );
+        }
+
+        info!(
+            account = %account.id,
+            folder = %folder_name,
+            conflicts = ops_by_uid.len(),
+            policy = ?policy,
+            "Resolved flag conflicts with queued local ops"
+        );
+        Ok(())
+    }
+}
+
+/// New UIDs fetched per checkpointed commit; bounds the work lost when a sync is interrupted.
+const CHECKPOINT_BATCH_UIDS: usize = 500;
+
+/// UID span covered by one baseline `UID SEARCH` window.
+pub const BASELINE_WINDOW_UIDS: u32 = 10_000;
+
+/// Splits `(resume_after, highest_uid]` into inclusive UID windows for the baseline scan.
+/// The last window is open-ended (`lo:*`) so UIDs allocated mid-scan are still covered.
+pub fn baseline_uid_windows(
+    resume_after: u32,
+    highest_uid: Option<u32>,
+) -> Vec<(u32, Option<u32>)> {
+    let start = resume_after.saturating_add(1);
+    let Some(highest) = highest_uid else {
+        return vec![(start, None)];
+    };
+
+    let mut windows = Vec::new();
+    let mut lo = start;
+    while lo
+        .checked_add(BASELINE_WINDOW_UIDS)
+        .is_some_and(|next| next <= highest)
+    {
+        let hi = lo + BASELINE_WINDOW_UIDS - 1;
+        windows.push((lo, Some(hi)));
+        lo = hi + 1;
+    }
+    windows.push((lo, None));
+    windows
+}
+
 fn get_header_value(parsed: &mailparse::ParsedMail, header_name: &str) -> Option<String> {
     parsed
         .headers
@@ -0,0 +1,473 @@
+//! Pushes queued ops (`pending_ops`) back to the server after a sync pass. Flag ops (read,
+//! star, labels) are collapsed to their net effect and sent with `UID STORE`; archive, move,
+//! copy and delete run in queue order via `UID MOVE`/`UID COPY` (`\Deleted` + expunge inside
+//! Trash), falling back to copy-and-expunge on servers without MOVE (see `imap::moves`). A
+//! move answered with `COPYUID` gives the moved rows their new uids right away, so later ops
+//! on them need not wait for the destination's sync. Accepted ops are cleared. Ops the server
+//! rejects (including label changes the connection's `ServerCaps` say it cannot carry out)
+//! stay queued and are retried on the next pass; after `MAX_OP_ATTEMPTS` rejections they are
+//! dead-lettered, keeping their local effect and last error until `otto ops --retry` or
+//! `--drop` (which rolls them back). Connection trouble does not count as a rejection.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use async_imap::error::Error as ImapError;
+use futures::StreamExt;
+use tracing::{debug, info, warn};
+
+use super::{CONNECTION_POOL, ImapSession};
+use crate::imap::{CopyUid, build_uid_sequence};
+use crate::storage::MailStore;
+use crate::storage::audit::AuditRecord;
+use crate::storage::ops::{MAX_OP_ATTEMPTS, MessageOp, ReplayOp};
+use crate::types::{Account, FolderRole, now_ts};
+
+/// UIDs per STORE/MOVE command, as for folder ops.
+const REPLAY_CHUNK: usize = 500;
+
+/// Consecutive queued ops with the same source folder and action, replayed as one command and
+/// rolled back together if the server rejects it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LocationBatch {
+    pub folder: String,
+    pub op: MessageOp,
+    pub uids: Vec<u32>,
+    pub op_ids: Vec<i64>,
+}
+
+/// Net `UID STORE` work for one folder.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct FolderReplay {
+    pub folder: String,
+    /// STORE item (e.g. `+FLAGS.SILENT (\Seen)`) → sorted UIDs.
+    pub stores: BTreeMap<String, Vec<u32>>,
+    /// Queued ops settled once every store in this folder succeeds.
+    pub op_ids: Vec<i64>,
+}
+
+pub struct OpsExecutor {
+    db: Arc<dyn MailStore>,
+}
+
+impl OpsExecutor {
+    pub fn new(db: Arc<dyn MailStore>) -> Self {
+        Self { db }
+    }
+
+    /// Groups `ops` (oldest first) into per-folder STORE commands. Only the latest op per
+    /// message and flag/label is sent, so read-then-unread pushes just the unread. Ops without
+    /// a server uid yet are left out.
+    pub fn plan(ops: &[ReplayOp]) -> Vec<FolderReplay> {
+        let mut latest: HashMap<(&str, u32, String), String> = HashMap::new();
+        let mut folders: BTreeMap<&str, FolderReplay> = BTreeMap::new();
+        for queued in ops {
+            let (Some(folder), Some(uid)) = (queued.folder.as_deref(), queued.uid) else {
+                continue;
+            };
+            let Some((item, target)) = store_item(&queued.op) else {
+                continue;
+            };
+            latest.insert((folder, uid, target), item);
+            folders
+                .entry(folder)
+                .or_insert_with(|| FolderReplay {
+                    folder: folder.to_string(),
+                    ..Default::default()
+                })
+                .op_ids
+                .push(queued.id);
+        }
+
+        for ((folder, uid, _), item) in latest {
+            if let Some(replay) = folders.get_mut(folder) {
+                replay.stores.entry(item).or_default().push(uid);
+            }
+        }
+        folders
+            .into_values()
+            .map(|mut replay| {
+                for uids in replay.stores.values_mut() {
+                    uids.sort_unstable();
+                }
+                replay
+            })
+            .collect()
+    }
+
+    /// Batches archive/move/copy/delete ops (oldest first) by consecutive runs of the same
+    /// folder and action. Ops whose message has no uid yet wait for a later pass.
+    pub fn plan_locations(ops: &[ReplayOp]) -> Vec<LocationBatch> {
+        let mut batches: Vec<LocationBatch> = Vec::new();
+        for queued in ops {
+            let (Some(folder), Some(uid)) = (queued.folder.as_deref(), queued.uid) else {
+                continue;
+            };
+            match batches.last_mut() {
+                Some(last)
+                    if last.folder == folder
+                        && last.op == queued.op
+                        && last.uids.len() < REPLAY_CHUNK =>
+                {
+                    last.uids.push(uid);
+                    last.op_ids.push(queued.id);
+                }
+                _ => batches.push(LocationBatch {
+                    folder: folder.to_string(),
+                    op: queued.op.clone(),
+                    uids: vec![uid],
+                    op_ids: vec![queued.id],
+                }),
+            }
+        }
+        batches
+    }
+
+    /// Replays the account's queued ops, flags first; returns how many ops were settled
+    /// (cleared or dead-lettered). Failures keep ops queued for the next pass.
+    pub async fn run(&self, account: &Account, access_token: &str) -> Result<usize> {
+        let settled = self.run_flag_ops(account, access_token).await?;
+        Ok(settled + self.run_location_ops(account, access_token).await?)
+    }
+
+    async fn run_flag_ops(&self, account: &Account, access_token: &str) -> Result<usize> {
+        let ops = self.db.load_replayable_flag_ops(&account.id).await?;
+        if ops.is_empty() {
+            return Ok(0);
+        }
+        let replays = Self::plan(&ops);
+
+        let mut cleared = 0;
+        let mut dead = 0;
+        for replay in replays {
+            let mut session = CONNECTION_POOL
+                .get_or_create(account, &replay.folder, access_token)
+                .await?;
+            let result = replay_folder(&mut session, &replay).await;
+            CONNECTION_POOL
+                .return_connection(&account.id, &replay.folder, session)
+                .await;
+
+            match result {
+                Ok(()) => {
+                    cleared += self.db.clear_pending_ops(&replay.op_ids).await? as usize;
+                    debug!(
+                        account = %account.id,
+                        folder = %replay.folder,
+                        ops = replay.op_ids.len(),
+                        "Replayed queued flag ops"
+                    );
+                }
+                Err(e) if rejected(&e) => {
+                    let error = format!("{:#}", e);
+                    let now_dead = self.db.record_op_failures(&replay.op_ids, &error).await?;
+                    dead += now_dead as usize;
+                    warn!(
+                        account = %account.id,
+                        folder = %replay.folder,
+                        error = %error,
+                        dead_lettered = now_dead,
+                        "Server rejected queued flag ops"
+                    );
+                }
+                Err(e) => warn!(
+                    account = %account.id,
+                    folder = %replay.folder,
+                    error = %e,
+                    "Replaying queued flag ops failed; keeping them queued"
+                ),
+            }
+        }
+
+        if cleared > 0 {
+            info!(account = %account.id, cleared = cleared, "Pushed queued flag ops to server");
+        }
+        Ok(cleared + dead)
+    }
+
+    async fn run_location_ops(&self, account: &Account, access_token: &str) -> Result<usize> {
+        let ops = self.db.load_replayable_location_ops(&account.id).await?;
+        let archive = self.db.role_folder(&account.id, FolderRole::All).await?;
+        let trash = self.db.role_folder(&account.id, FolderRole::Trash).await?;
+        let mut cleared = 0;
+        let mut dead = 0;
+        for batch in Self::plan_locations(&ops) {
+            let mut session = CONNECTION_POOL
+                .get_or_create(account, &batch.folder, access_token)
+                .await?;
+            let result = replay_location_batch(&mut session, &batch, &archive, &trash).await;
+            CONNECTION_POOL
+                .return_connection(&account.id, &batch.folder, session)
+                .await;
+            if let Some((op, destination)) = audit_op(&batch, &archive, &trash) {
+                let record = AuditRecord {
+                    account_id: account.id.clone(),
+                    ts: now_ts(),
+                    actor: "ops-replay".to_string(),
+                    op: op.to_string(),
+                    folder: batch.folder.clone(),
+                    destination,
+                    uids: batch.uids.clone(),
+                    op_ids: batch.op_ids.clone(),
+                    outcome: match &result {
+                        Ok(_) => "ok".to_string(),
+                        Err(ImapError::No(reason) | ImapError::Bad(reason)) => {
+                            format!("rejected: {}", reason)
+                        }
+                        Err(e) => format!("error: {}", e),
+                    },
+                };
+                if let Err(e) = self.db.record_audit(&record).await {
+                    warn!(account = %account.id, error = %e, "Writing audit record failed");
+                }
+            }
+
+            match result {
+                Ok(copy_uid) => {
+                    if let (Some(copy_uid), Some((_, Some(destination)))) =
+                        (copy_uid, audit_op(&batch, &archive, &trash))
+                    {
+                        self.link_moved(account, &batch, &destination, &copy_uid)
+                            .await?;
+                    }
+                    // Expunged from Trash: the marked rows can go now.
+                    if batch.op == MessageOp::Delete && batch.folder == trash {
+                        self.db
+                            .delete_messages_by_folder_and_uids(&account.id, &trash, &batch.uids)
+                            .await?;
+                    }
+                    cleared += self.db.clear_pending_ops(&batch.op_ids).await? as usize;
+                }
+                Err(ImapError::No(reason) | ImapError::Bad(reason)) => {
+                    let now_dead = self.db.record_op_failures(&batch.op_ids, &reason).await?;
+                    if now_dead == 0 {
+                        // Retried next pass; later ops wait so the queue order holds.
+                        warn!(
+                            account = %account.id,
+                            folder = %batch.folder,
+                            op = ?batch.op,
+                            reason = %reason,
+                            "Server rejected queued op; retrying next pass"
+                        );
+                        break;
+                    }
+                    warn!(
+                        account = %account.id,
+                        folder = %batch.folder,
+                        op = ?batch.op,
+                        reason = %reason,
+                        attempts = MAX_OP_ATTEMPTS,
+                        "Server kept rejecting queued op; dead-lettered (see `otto ops`)"
+                    );
+                    dead += now_dead as usize;
+                }
+                Err(e) => {
+                    // Connection trouble: stop here so later ops keep their order.
+                    warn!(
+                        account = %account.id,
+                        folder = %batch.folder,
+                        error = %e,
+                        "Replaying queued ops failed; keeping them queued"
+                    );
+                    break;
+                }
+            }
+        }
+
+        if cleared + dead > 0 {
+            info!(
+                account = %account.id,
+                cleared = cleared,
+                dead_lettered = dead,
+                "Replayed queued move/delete ops"
+            );
+        }
+        Ok(cleared + dead)
+    }
+
+    /// Gives the batch's moved rows the uids `COPYUID` named in `destination`.
+    async fn link_moved(
+        &self,
+        account: &Account,
+        batch: &LocationBatch,
+        destination: &str,
+        copy_uid: &CopyUid,
+    ) -> Result<()> {
+        let dest_uids: HashMap<u32, u32> = copy_uid.uids.iter().copied().collect();
+        let links: Vec<(i64, u32)> = batch
+            .uids
+            .iter()
+            .zip(&batch.op_ids)
+            .filter_map(|(uid, op_id)| dest_uids.get(uid).map(|dest_uid| (*op_id, *dest_uid)))
+            .collect();
+        let linked = self
+            .db
+            .link_moved_uids(&account.id, destination, copy_uid.uid_validity, &links)
+            .await?;
+        debug!(
+            account = %account.id,
+            folder = %destination,
+            linked = linked,
+            "Linked moved messages to their COPYUID uids"
+        );
+        Ok(())
+    }
+}
+
+/// How a location batch appears in the audit log (name, destination); copies are not
+/// destructive and are not logged.
+fn audit_op(
+    batch: &LocationBatch,
+    archive: &str,
+    trash: &str,
+) -> Option<(&'static str, Option<String>)> {
+    match &batch.op {
+        MessageOp::Archive => Some(("archive", Some(archive.to_string()))),
+        MessageOp::Move(dest) => Some(("move", Some(dest.clone()))),
+        MessageOp::Delete if batch.folder == trash => Some(("expunge", None)),
+        MessageOp::Delete => Some(("delete", Some(trash.to_string()))),
+        _ => None,
+    }
+}
+
+/// Replays one batch; a move that got a `COPYUID` returns it, for linking the moved rows.
+async fn replay_location_batch(
+    session: &mut ImapSession,
+    batch: &LocationBatch,
+    archive: &str,
+    trash: &str,
+) -> Result<Option<CopyUid>, ImapError> {
+    session.select(&batch.folder).await?;
+    let uid_seq = build_uid_sequence(&batch.uids);
+    let caps = session.caps().clone();
+    match &batch.op {
+        // Messages cached from All Mail (All Mail mode) cannot leave it; archiving drops the
+        // Inbox label and moving swaps it for the destination's label.
+        MessageOp::Archive if batch.folder == archive => {
+            require(caps.gmail, "X-GM-EXT-1")?;
+            store_all(session, &uid_seq, &["-X-GM-LABELS (\\Inbox)".to_string()]).await?;
+            Ok(None)
+        }
+        MessageOp::Move(dest) if batch.folder == archive => {
+            require(caps.gmail, "X-GM-EXT-1")?;
+            let items = [
+                format!("+X-GM-LABELS ({})", quote_label(dest)),
+                "-X-GM-LABELS (\\Inbox)".to_string(),
+            ];
+            store_all(session, &uid_seq, &items).await?;
+            Ok(None)
+        }
+        MessageOp::Archive => session.move_uids(&batch.uids, archive).await,
+        MessageOp::Move(dest) => session.move_uids(&batch.uids, dest).await,
+        MessageOp::Copy(dest) => {
+            session.uid_copy(&uid_seq, dest).await?;
+            Ok(None)
+        }
+        MessageOp::Delete if batch.folder == trash => {
+            session.delete_uids(&batch.uids).await?;
+            Ok(None)
+        }
+        MessageOp::Delete => session.move_uids(&batch.uids, trash).await,
+        _ => Ok(None),
+    }
+}
+
+/// Refuses a command needing an extension the server didn't advertise, as a rejection so the
+/// batch is rolled back rather than retried forever.
+fn require(advertised: bool, extension: &str) -> Result<(), ImapError> {
+    if advertised {
+        Ok(())
+    } else {
+        Err(ImapError::No(format!(
+            "server does not advertise {}",
+            extension
+        )))
+    }
+}
+
+/// Runs each `UID STORE` item in turn, stopping at the first error response.
+async fn store_all(
+    session: &mut ImapSession,
+    uid_seq: &str,
+    items: &[String],
+) -> Result<(), ImapError> {
+    for item in items {
+        let stored: Vec<_> = session.uid_store(uid_seq, item).await?.collect().await;
+        if let Some(Err(e)) = stored.into_iter().find(|r| r.is_err()) {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+async fn replay_folder(session: &mut ImapSession, replay: &FolderReplay) -> Result<()> {
+    session
+        .select(&replay.folder)
+        .await
+        .with_context(|| format!("selecting folder {}", replay.folder))?;
+
+    for (item, uids) in &replay.stores {
+        if item.contains("X-GM-LABELS") && !session.caps().gmail {
+            warn!(
+                folder = %replay.folder,
+                item = %item,
+                "Server has no Gmail labels (X-GM-EXT-1); keeping label change local"
+            );
+            continue;
+        }
+        for chunk in uids.chunks(REPLAY_CHUNK) {
+            let uid_seq = build_uid_sequence(chunk);
+            let responses: Vec<_> = session
+                .uid_store(&uid_seq, item)
+                .await
+                .with_context(|| format!("UID STORE {}", item))?
+                .collect()
+                .await;
+            if let Some(Err(e)) = responses.into_iter().find(|r| r.is_err()) {
+                return Err(e).with_context(|| format!("UID STORE {} response", item));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether a replay error is the server saying NO/BAD, rather than connection trouble.
+fn rejected(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref(),
+            Some(ImapError::No(_) | ImapError::Bad(_))
+        )
+    })
+}
+
+/// STORE item for a flag op plus the flag/label it touches, so a later op on the same flag
+/// or label supersedes an earlier one.
+fn store_item(op: &MessageOp) -> Option<(String, String)> {
+    let flags =
+        |sign: char, flag: &str| (format!("{}FLAGS.SILENT ({})", sign, flag), flag.to_string());
+    let labels = |sign: char, label: &str| {
+        (
+            format!("{}X-GM-LABELS ({})", sign, quote_label(label)),
+            format!("label:{}", label),
+        )
+    };
+    match op {
+        MessageOp::MarkRead => Some(flags('+', "\\Seen")),
+        MessageOp::MarkUnread => Some(flags('-', "\\Seen")),
+        MessageOp::Star => Some(flags('+', "\\Flagged")),
+        MessageOp::Unstar => Some(flags('-', "\\Flagged")),
+        MessageOp::AddLabel(label) => Some(labels('+', label)),
+        MessageOp::RemoveLabel(label) => Some(labels('-', label)),
+        MessageOp::Archive | MessageOp::Delete | MessageOp::Move(_) | MessageOp::Copy(_) => None,
+    }
+}
+
+/// System labels (`\Important`) go bare; user labels are sent as quoted strings.
+fn quote_label(label: &str) -> String {
+    if label.starts_with('\\') {
+        label.to_string()
+    } else {
+        format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
@@ -0,0 +1,185 @@
+//! Process-wide pool of idle IMAP sessions, keyed by account and slot (a
+//! folder name, or `list`/`status`/`verify`/`quota` for account-wide work), so consecutive passes
+//! (and the TUI's background engines) skip the TLS handshake and AUTHENTICATE. Each account
+//! keeps at most `max_idle_per_account` idle sessions; returning one more evicts the account's
+//! least recently returned session. Evicted, expired and replaced sessions are sent LOGOUT
+//! instead of being dropped, and `close_all` drains the pool at shutdown. A cached session must
+//! answer NOOP before it is reused, and `keepalive` NOOPs idle sessions so long-running
+//! processes keep them from timing out on the server.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::future::join_all;
+use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::imap::{ImapClient, ImapSession};
+use crate::types::Account;
+
+/// Idle sessions kept per account unless configured (`OTTO_MAX_POOLED_CONNECTIONS`).
+pub const DEFAULT_MAX_IDLE_PER_ACCOUNT: usize = 4;
+/// Sessions without a server round trip for this long are logged out rather than reused.
+const MAX_IDLE_AGE: Duration = Duration::from_secs(300);
+/// Idle sessions older than this get a NOOP from `keepalive` (well under `MAX_IDLE_AGE`).
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(120);
+/// A cached session that does not answer NOOP in time is considered dead.
+const NOOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// A server that does not answer LOGOUT in time is simply disconnected.
+const LOGOUT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(super) static CONNECTION_POOL: Lazy<ConnectionPool> = Lazy::new(ConnectionPool::new);
+
+struct IdleSession {
+    account_id: String,
+    slot: String,
+    session: ImapSession,
+    /// Last server round trip: when the session was returned or answered a keepalive NOOP.
+    last_used: Instant,
+}
+
+pub(super) struct ConnectionPool {
+    /// Oldest returned first, so eviction takes from the front.
+    idle: Mutex<Vec<IdleSession>>,
+    max_idle_per_account: AtomicUsize,
+}
+
+impl ConnectionPool {
+    fn new() -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+            max_idle_per_account: AtomicUsize::new(DEFAULT_MAX_IDLE_PER_ACCOUNT),
+        }
+    }
+
+    pub(super) fn set_max_idle_per_account(&self, max: usize) {
+        self.max_idle_per_account.store(max, Ordering::Relaxed);
+    }
+
+    pub(super) async fn get_or_create(
+        &self,
+        account: &Account,
+        slot: &str,
+        access_token: &str,
+    ) -> Result<ImapSession> {
+        let cached = {
+            let mut idle = self.idle.lock().await;
+            idle.iter()
+                .position(|s| s.account_id == account.id && s.slot == slot)
+                .map(|pos| idle.remove(pos))
+        }; // Release the lock before any network round trip (allows parallel creation).
+
+        if let Some(mut cached) = cached {
+            if cached.last_used.elapsed() >= MAX_IDLE_AGE {
+                debug!(account = %account.id, slot = %slot, "Cached connection expired");
+                logout(cached).await;
+            } else if noop(&mut cached.session).await {
+                debug!(account = %account.id, slot = %slot, "Reusing cached IMAP connection");
+                return Ok(cached.session);
+            } else {
+                debug!(account = %account.id, slot = %slot, "Cached connection did not answer NOOP");
+            }
+        }
+        debug!(account = %account.id, slot = %slot, "Creating new IMAP connection");
+        ImapClient::connect(account, access_token).await
+    }
+
+    /// Pools an idle `session` (not logged out), then logs out whatever the pool no longer keeps.
+    /// A session that timed out is dropped instead: a late response may still be in flight.
+    pub(super) async fn return_connection(
+        &self,
+        account_id: &str,
+        slot: &str,
+        session: ImapSession,
+    ) {
+        if session.timed_out() {
+            debug!(account = %account_id, slot = %slot, "Dropping timed-out IMAP connection");
+            return;
+        }
+        self.insert(IdleSession {
+            account_id: account_id.to_string(),
+            slot: slot.to_string(),
+            session,
+            last_used: Instant::now(),
+        })
+        .await;
+    }
+
+    async fn insert(&self, new: IdleSession) {
+        let max = self.max_idle_per_account.load(Ordering::Relaxed);
+        let evicted = {
+            let mut idle = self.idle.lock().await;
+            let (mut evicted, mut kept): (Vec<_>, Vec<_>) =
+                std::mem::take(&mut *idle).into_iter().partition(|s| {
+                    (s.account_id == new.account_id && s.slot == new.slot)
+                        || s.last_used.elapsed() >= MAX_IDLE_AGE
+                });
+            let account_id = new.account_id.clone();
+            kept.push(new);
+            while kept.iter().filter(|s| s.account_id == account_id).count() > max {
+                if let Some(pos) = kept.iter().position(|s| s.account_id == account_id) {
+                    evicted.push(kept.remove(pos));
+                }
+            }
+            *idle = kept;
+            evicted
+        };
+        join_all(evicted.into_iter().map(logout)).await;
+    }
+
+    /// NOOPs sessions idle for `KEEPALIVE_INTERVAL`, re-pooling the ones that answer. They are
+    /// out of the pool meanwhile, so a folder task asking for one just connects afresh.
+    pub(super) async fn keepalive(&self) {
+        let due = {
+            let mut idle = self.idle.lock().await;
+            let (due, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut *idle)
+                .into_iter()
+                .partition(|s| s.last_used.elapsed() >= KEEPALIVE_INTERVAL);
+            *idle = kept;
+            due
+        };
+        let checked = join_all(due.into_iter().map(|mut s| async move {
+            let alive = noop(&mut s.session).await;
+            (s, alive)
+        }))
+        .await;
+        for (mut session, alive) in checked {
+            if alive {
+                session.last_used = Instant::now();
+                self.insert(session).await;
+            } else {
+                debug!(account = %session.account_id, slot = %session.slot, "Dropping pooled connection that did not answer NOOP");
+            }
+        }
+    }
+
+    /// Logs out every idle session; returns how many were pooled.
+    pub(super) async fn close_all(&self) -> usize {
+        let drained = std::mem::take(&mut *self.idle.lock().await);
+        let count = drained.len();
+        join_all(drained.into_iter().map(logout)).await;
+        count
+    }
+}
+
+async fn noop(session: &mut ImapSession) -> bool {
+    matches!(
+        tokio::time::timeout(NOOP_TIMEOUT, session.noop()).await,
+        Ok(Ok(()))
+    )
+}
+
+async fn logout(mut idle: IdleSession) {
+    match tokio::time::timeout(LOGOUT_TIMEOUT, idle.session.logout()).await {
+        Ok(Ok(())) => {
+            debug!(account = %idle.account_id, slot = %idle.slot, "Logged out pooled IMAP connection")
+        }
+        Ok(Err(e)) => {
+            debug!(account = %idle.account_id, slot = %idle.slot, error = %e, "LOGOUT of pooled connection failed")
+        }
+        Err(_) => {
+            debug!(account = %idle.account_id, slot = %idle.slot, "LOGOUT of pooled connection timed out")
+        }
+    }
+}
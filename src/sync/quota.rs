@@ -0,0 +1,55 @@
+//! Quota refresh: `GETQUOTAROOT INBOX` once per account sync on servers advertising QUOTA, kept
+//! in `account_quota` for `otto status` and the TUI sidebar. The same connection's answer to the
+//! ID sent at login is stored in `server_identity` for `otto imap-server`.
+use anyhow::{Context, Result};
+use tracing::{debug, warn};
+
+use super::{CONNECTION_POOL, SyncEngine};
+use crate::imap::ImapClient;
+use crate::types::{Account, AccountQuota, ServerIdentity, now_ts};
+
+impl SyncEngine {
+    /// Reads and stores the account's quota; `None` when the server has no QUOTA extension
+    /// or reports no limits (the stored value is then left as it was).
+    pub(super) async fn refresh_quota(
+        &self,
+        account: &Account,
+        secret: &str,
+    ) -> Result<Option<AccountQuota>> {
+        let mut session = CONNECTION_POOL
+            .get_or_create(account, "quota", secret)
+            .await
+            .context("connecting for quota")?;
+        if let Some(fields) = session.server_id() {
+            let identity = ServerIdentity {
+                account_id: account.id.clone(),
+                fields: fields.clone(),
+                fetched_at: now_ts(),
+            };
+            if let Err(e) = self.db.save_server_identity(&identity).await {
+                warn!(account = %account.id, error = %e, "Storing the server ID failed");
+            }
+        }
+        if !session.caps().quota {
+            CONNECTION_POOL
+                .return_connection(&account.id, "quota", session)
+                .await;
+            return Ok(None);
+        }
+        let result = ImapClient::quota(&mut session, &account.id, now_ts()).await;
+        CONNECTION_POOL
+            .return_connection(&account.id, "quota", session)
+            .await;
+        let Some(quota) = result? else {
+            return Ok(None);
+        };
+        self.db.save_account_quota(&quota).await?;
+        debug!(
+            account = %account.id,
+            used = ?quota.storage_used,
+            limit = ?quota.storage_limit,
+            "Stored account quota"
+        );
+        Ok(Some(quota))
+    }
+}
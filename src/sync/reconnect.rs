@@ -0,0 +1,62 @@
+//! Mid-sync reconnects. When the connection dies during a FETCH stream (reset, closed by the
+//! server, or silent past `fetch_idle`), the folder task logs in again, reopens the folder and
+//! fetches only the UIDs the server had not answered yet, instead of sending the rest of the
+//! chunk to the retry queue and failing the folder. Each fetch call gets `MAX_RECONNECTS`
+//! attempts with a growing pause; a changed UIDVALIDITY ends them (the pass starts over next
+//! time).
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use super::SyncEngine;
+use crate::credentials::imap_secret;
+use crate::imap::{ImapClient, ImapSession};
+use crate::types::Account;
+
+/// Reconnects allowed per FETCH loop before its remaining UIDs go to the retry queue.
+pub(super) const MAX_RECONNECTS: usize = 3;
+/// Pause before the first reconnect; doubled for each further one.
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+impl SyncEngine {
+    /// Replaces the dead `session` with a new one that has the same folder open. `attempt`
+    /// counts from 1; false when reconnecting failed, was cancelled, or UIDVALIDITY changed.
+    pub(super) async fn reconnect_session(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        attempt: usize,
+    ) -> bool {
+        let backoff = RECONNECT_BACKOFF * 2u32.pow(attempt.saturating_sub(1) as u32);
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = self.cancel.cancelled() => return false,
+        }
+        let result = match imap_secret(account).await {
+            Ok(secret) => ImapClient::reconnect(account, &secret, session).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                info!(
+                    account = %account.id,
+                    folder = %folder_name,
+                    attempt,
+                    "Reconnected after the IMAP connection was lost"
+                );
+                true
+            }
+            Err(e) => {
+                warn!(
+                    account = %account.id,
+                    folder = %folder_name,
+                    attempt,
+                    error = %format!("{:#}", e),
+                    "Reconnecting after a lost IMAP connection failed"
+                );
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! What a sync pass did, returned by `SyncEngine::sync_all` so callers can show failures instead
+//! of relying on the warnings in the log.
+use crate::types::SyncRunRecord;
+
+#[derive(Clone, Debug, Default)]
+pub struct SyncReport {
+    pub accounts: Vec<AccountSyncReport>,
+    /// The pass was cancelled; accounts after the last one listed were not synced.
+    pub cancelled: bool,
+}
+
+/// One account's pass: a `sync_runs` row per folder plus the account-level phases.
+#[derive(Clone, Debug, Default)]
+pub struct AccountSyncReport {
+    pub account_id: String,
+    pub email: String,
+    pub folders: Vec<SyncRunRecord>,
+    /// Pending bodies downloaded by the body phase.
+    pub bodies_fetched: usize,
+    /// Queued ops the replay phase settled (sent, or rolled back after a rejection).
+    pub ops_settled: usize,
+    /// Failures outside a single folder (token, discovery, body phase, op replay, ...), in the
+    /// order they happened. A token or database failure ends the account's pass early.
+    pub errors: Vec<String>,
+}
+
+impl SyncReport {
+    /// No account or folder failed. Folders stopped by cancellation count as failed.
+    pub fn is_ok(&self) -> bool {
+        self.accounts.iter().all(AccountSyncReport::is_ok)
+    }
+
+    /// One line per failure, prefixed with the account email (and folder).
+    pub fn problems(&self) -> Vec<String> {
+        self.accounts
+            .iter()
+            .flat_map(|account| {
+                let folders = account
+                    .folders
+                    .iter()
+                    .filter_map(|run| {
+                        let error = run.error.as_deref()?;
+                        Some(format!("{} / {}: {}", account.email, run.folder, error))
+                    })
+                    .collect::<Vec<_>>();
+                account
+                    .errors
+                    .iter()
+                    .map(|error| format!("{}: {}", account.email, error))
+                    .chain(folders)
+            })
+            .collect()
+    }
+
+    /// Totals for a status line, e.g. `2 accounts, 9 folders (1 failed): 14 new, 3 updated`.
+    pub fn summary(&self) -> String {
+        let folders: Vec<&SyncRunRecord> = self.accounts.iter().flat_map(|a| &a.folders).collect();
+        let failed = folders.iter().filter(|run| !run.ok).count();
+        let mut out = format!(
+            "{} account(s), {} folder(s)",
+            self.accounts.len(),
+            folders.len()
+        );
+        if failed > 0 {
+            out.push_str(&format!(" ({} failed)", failed));
+        }
+        out.push_str(&format!(
+            ": {} new, {} updated, {} deleted",
+            folders.iter().map(|run| run.added).sum::<usize>(),
+            folders.iter().map(|run| run.updated).sum::<usize>(),
+            folders.iter().map(|run| run.deleted).sum::<usize>(),
+        ));
+        let errors: usize = self.accounts.iter().map(|a| a.errors.len()).sum();
+        if errors > 0 {
+            out.push_str(&format!(", {} account error(s)", errors));
+        }
+        if self.cancelled {
+            out.push_str(" (cancelled)");
+        }
+        out
+    }
+}
+
+impl AccountSyncReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.folders.iter().all(|run| run.ok)
+    }
+}
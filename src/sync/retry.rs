@@ -0,0 +1,85 @@
+//! Retry queue for new-message FETCHes that failed: UIDs the server did not answer for and
+//! messages that did not parse are recorded in `fetch_retries` by `fetch_and_parse_messages`,
+//! and every later folder sync fetches them again, until they succeed, turn out to be expunged,
+//! or reach `MAX_FETCH_ATTEMPTS` (then they stay listed but are no longer retried).
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+use super::{ImapSession, SyncEngine};
+use crate::imap::build_uid_sequence;
+use crate::types::Account;
+
+/// Failed attempts after which a UID is left alone.
+pub const MAX_FETCH_ATTEMPTS: u32 = 5;
+
+impl SyncEngine {
+    /// Re-fetches the folder's queued failures; the folder must be selected.
+    pub(super) async fn retry_failed_fetches(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        headers_only: bool,
+    ) -> Result<()> {
+        let due: Vec<u32> = self
+            .db
+            .load_fetch_retries(&account.id, folder_name)
+            .await?
+            .into_iter()
+            .filter(|retry| retry.attempts < MAX_FETCH_ATTEMPTS)
+            .map(|retry| retry.uid)
+            .collect();
+        if due.is_empty() {
+            return Ok(());
+        }
+
+        // A message expunged since it failed can never be fetched.
+        let uid_seq = build_uid_sequence(&due);
+        let existing: HashSet<u32> = session
+            .uid_search(format!("UID {}", uid_seq))
+            .await
+            .with_context(|| format!("UID SEARCH retry UIDs {}", uid_seq))?
+            .into_iter()
+            .collect();
+        let (retry, gone): (Vec<u32>, Vec<u32>) =
+            due.into_iter().partition(|uid| existing.contains(uid));
+        self.db
+            .clear_fetch_retries(&account.id, folder_name, &gone)
+            .await?;
+        if retry.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            account = %account.id,
+            folder = %folder_name,
+            count = retry.len(),
+            "Retrying failed message fetches"
+        );
+        let fetched = self
+            .fetch_and_collect_new_messages(session, account, folder_name, &retry, headers_only)
+            .await?;
+        self.db
+            .clear_fetch_retries(&account.id, folder_name, &fetched)
+            .await?;
+        let given_up = self
+            .db
+            .load_fetch_retries(&account.id, folder_name)
+            .await?
+            .into_iter()
+            .filter(|r| retry.contains(&r.uid) && r.attempts >= MAX_FETCH_ATTEMPTS)
+            .count();
+        if given_up > 0 {
+            warn!(
+                account = %account.id,
+                folder = %folder_name,
+                count = given_up,
+                attempts = MAX_FETCH_ATTEMPTS,
+                "Giving up on messages that keep failing to fetch"
+            );
+        }
+        Ok(())
+    }
+}
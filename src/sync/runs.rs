@@ -0,0 +1,71 @@
+//! Per-folder counters for the `sync_runs` history, fed from the engine's progress events so the
+//! numbers match what subscribers (TUI, CLI) saw.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::types::SyncProgress;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct FolderRunStats {
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub bytes: u64,
+}
+
+/// Counters keyed by `(account_id, folder)`, shared by an engine and its folder tasks.
+#[derive(Clone, Default)]
+pub(super) struct RunStats {
+    folders: Arc<Mutex<HashMap<(String, String), FolderRunStats>>>,
+}
+
+impl RunStats {
+    pub fn record(&self, event: &SyncProgress) {
+        match event {
+            SyncProgress::MessagesFetched {
+                account_id,
+                folder,
+                bytes,
+                ..
+            } => self.update(account_id, folder, |s| s.bytes += bytes),
+            SyncProgress::MessagesWritten {
+                account_id,
+                folder,
+                count,
+            } => self.update(account_id, folder, |s| s.added += count),
+            SyncProgress::MessagesUpdated {
+                account_id,
+                folder,
+                count,
+            } => self.update(account_id, folder, |s| s.updated += count),
+            SyncProgress::MessagesExpunged {
+                account_id,
+                folder,
+                count,
+            } => self.update(account_id, folder, |s| s.deleted += count),
+            _ => {}
+        }
+    }
+
+    fn update(&self, account_id: &str, folder: &str, apply: impl FnOnce(&mut FolderRunStats)) {
+        let mut folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        apply(
+            folders
+                .entry((account_id.to_string(), folder.to_string()))
+                .or_default(),
+        );
+    }
+
+    /// Removes and returns `account_id`'s counters, keyed by folder.
+    pub fn take(&self, account_id: &str) -> HashMap<String, FolderRunStats> {
+        let mut folders = self.folders.lock().unwrap_or_else(|e| e.into_inner());
+        let keys: Vec<(String, String)> = folders
+            .keys()
+            .filter(|(account, _)| account == account_id)
+            .cloned()
+            .collect();
+        keys.into_iter()
+            .filter_map(|key| folders.remove(&key).map(|stats| (key.1, stats)))
+            .collect()
+    }
+}
@@ -0,0 +1,40 @@
+//! Per-account download rate limiting for FETCH streams.
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::{Instant, sleep_until};
+
+/// Paces consumers to an average of `bytes_per_sec`. Each `consume` call reserves the next slot
+/// on a shared virtual clock and sleeps until that slot ends, so parallel folder streams of one
+/// account share a single budget and the server sees TCP backpressure while we wait.
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    next_free: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            next_free: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bytes_per_sec
+    }
+
+    pub async fn consume(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+
+        let wait_until = {
+            let mut next_free = self.next_free.lock().await;
+            let start = (*next_free).max(Instant::now());
+            *next_free = start + Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+            *next_free
+        };
+        sleep_until(wait_until).await;
+    }
+}
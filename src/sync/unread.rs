@@ -0,0 +1,100 @@
+//! Unread-only passes (`--unread-only` / `AccountSettings::unread_only`): a quick "what's new"
+//! check that downloads only unseen mail. It writes through the backfill path, so the folder's
+//! MODSEQ, highest UID and EXISTS count stay where the last full sync left them and the next
+//! full sync still sees every change since then.
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use tracing::{debug, info};
+
+use super::{CHECKPOINT_BATCH_UIDS, FolderSyncReport, ImapSession, SyncEngine, cache_window_start};
+use crate::storage::db::FolderStateUpdate;
+use crate::types::{Account, FolderState};
+
+impl SyncEngine {
+    /// Fetches unseen messages in the sync window that are not cached yet. `mailbox` is the
+    /// folder as just SELECTed; `folder_state` is its stored state after any UIDVALIDITY reset.
+    pub(super) async fn sync_folder_unread_only(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        mailbox: &async_imap::types::Mailbox,
+        folder_state: Option<&FolderState>,
+    ) -> Result<FolderSyncReport> {
+        let report = FolderSyncReport {
+            folder: folder_name.to_string(),
+            expunged_uids: Vec::new(),
+        };
+
+        if let Some(state) = folder_state
+            && session.caps().condstore
+            && let (Some(stored), Some(current)) = (state.highestmodseq, mailbox.highest_modseq)
+            && stored > 0
+            && stored == current
+            && state.exists_count == Some(mailbox.exists)
+        {
+            debug!(account = %account.id, folder = %folder_name, "Unread-only: no changes (MODSEQ match)");
+            return Ok(report);
+        }
+
+        // Never-synced folders still record UIDVALIDITY, so the UIDs stored here are dropped
+        // if it changes before the first full sync. Everything else stays unset.
+        if folder_state.is_none() {
+            self.db
+                .upsert_folder_state(
+                    &account.id,
+                    folder_name,
+                    &FolderStateUpdate {
+                        uidvalidity: Some(mailbox.uid_validity.unwrap_or(0)),
+                        highest_uid: None,
+                        highestmodseq: None,
+                        exists_count: None,
+                        last_sync_ts: None,
+                        last_uid_scan_ts: None,
+                        baseline_scan_uid: None,
+                        resume_modseq: None,
+                        resume_uid: None,
+                        checkpoint_from_uid: None,
+                    },
+                )
+                .await?;
+        }
+
+        let cutoff = cache_window_start(account, folder_name, folder_state);
+        let query = format!("UNSEEN SINCE {}", cutoff.format("%d-%b-%Y"));
+        let remote = session
+            .uid_search(&query)
+            .await
+            .with_context(|| format!("UID SEARCH unread-only: {}", query))?;
+        let local: HashSet<u32> = self
+            .db
+            .load_uid_to_message_id_map_by_folder(&account.id, folder_name)
+            .await?
+            .into_keys()
+            .collect();
+        let mut new_uids: Vec<u32> = remote
+            .into_iter()
+            .filter(|uid| !local.contains(uid))
+            .collect();
+        new_uids.sort_unstable();
+
+        for batch in new_uids.chunks(CHECKPOINT_BATCH_UIDS) {
+            let (_, location_updates) = self
+                .fetch_and_handle_new_uids(session, account, folder_name, batch, false)
+                .await?;
+            self.db
+                .commit_backfill_batch(&account.id, folder_name, &[], &[], &location_updates, None)
+                .await?;
+            self.check_cancelled()?;
+        }
+
+        info!(
+            account = %account.id,
+            folder = %folder_name,
+            fetched = new_uids.len(),
+            "Unread-only sync completed"
+        );
+        Ok(report)
+    }
+}
@@ -0,0 +1,155 @@
+//! Startup spot-check of the cached folder state against the server: one `STATUS` per folder
+//! (no SELECT, no FETCH), so `--no-sync` runs can say which folders are stale or void before
+//! the cached data is trusted.
+use std::fmt;
+
+use anyhow::{Context, Result};
+use tracing::{debug, warn};
+
+use super::{CONNECTION_POOL, SyncEngine};
+use crate::credentials::imap_secret;
+use crate::types::{Account, FolderState};
+
+/// Server-side counters from `STATUS (UIDVALIDITY UIDNEXT MESSAGES HIGHESTMODSEQ)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FolderStatus {
+    pub uidvalidity: Option<u32>,
+    pub uid_next: Option<u32>,
+    pub messages: u32,
+    pub highestmodseq: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CacheFreshness {
+    Fresh,
+    NeverSynced,
+    /// UIDVALIDITY changed, so every cached UID is void; the next sync refetches the folder.
+    NeedsResync {
+        cached: u32,
+        server: u32,
+    },
+    /// New UIDs, flag/expunge changes, or an interrupted pass since the last sync.
+    Stale {
+        new_uids: u32,
+        changed: bool,
+    },
+}
+
+impl CacheFreshness {
+    pub fn classify(cached: Option<&FolderState>, server: &FolderStatus) -> Self {
+        let Some((state, cached_validity)) = cached.and_then(|s| s.uidvalidity.map(|v| (s, v)))
+        else {
+            return Self::NeverSynced;
+        };
+        if let Some(server_validity) = server.uidvalidity
+            && server_validity != cached_validity
+        {
+            return Self::NeedsResync {
+                cached: cached_validity,
+                server: server_validity,
+            };
+        }
+
+        let new_uids = server
+            .uid_next
+            .map(|next| {
+                next.saturating_sub(1)
+                    .saturating_sub(state.highest_uid.unwrap_or(0))
+            })
+            .unwrap_or(0);
+        // MODSEQ covers flag changes and expunges; without CONDSTORE fall back to the count.
+        let changed = match (server.highestmodseq, state.highestmodseq) {
+            (Some(server_modseq), Some(cached_modseq)) => server_modseq > cached_modseq,
+            _ => state.exists_count != Some(server.messages),
+        };
+        let interrupted = state.baseline_scan_uid.is_some() || state.resume_uid.is_some();
+
+        if new_uids == 0 && !changed && !interrupted {
+            Self::Fresh
+        } else {
+            Self::Stale {
+                new_uids,
+                changed: changed || interrupted,
+            }
+        }
+    }
+}
+
+impl fmt::Display for CacheFreshness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fresh => write!(f, "up to date"),
+            Self::NeverSynced => write!(f, "never synced"),
+            Self::NeedsResync { cached, server } => write!(
+                f,
+                "needs full resync (UIDVALIDITY {} -> {})",
+                cached, server
+            ),
+            Self::Stale {
+                new_uids: 0,
+                changed: _,
+            } => write!(f, "changed on server since last sync"),
+            Self::Stale { new_uids, changed } => {
+                write!(f, "{} new message(s) on server", new_uids)?;
+                if *changed {
+                    write!(f, ", other changes too")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FolderCheck {
+    pub folder: String,
+    pub freshness: CacheFreshness,
+}
+
+impl SyncEngine {
+    /// Compares every enabled folder's cached state with a server `STATUS`. Folders whose
+    /// STATUS fails are skipped with a warning.
+    pub async fn validate_cache(&self, account: &Account) -> Result<Vec<FolderCheck>> {
+        let folders = self.db.list_folders(&account.id).await?;
+
+        let secret = imap_secret(account).await?;
+        let mut session = CONNECTION_POOL
+            .get_or_create(account, "status", &secret)
+            .await
+            .context("connecting for cache validation")?;
+
+        let mut checks = Vec::new();
+        let items = if session.caps().condstore {
+            "(UIDVALIDITY UIDNEXT MESSAGES HIGHESTMODSEQ)"
+        } else {
+            "(UIDVALIDITY UIDNEXT MESSAGES)"
+        };
+        for folder in &super::synced_folders(self.db.as_ref(), account).await? {
+            let mailbox = match session.status(folder, items).await {
+                Ok(mailbox) => mailbox,
+                Err(e) => {
+                    warn!(account = %account.id, folder = %folder, error = %e, "STATUS failed");
+                    continue;
+                }
+            };
+            let server = FolderStatus {
+                uidvalidity: mailbox.uid_validity,
+                uid_next: mailbox.uid_next,
+                messages: mailbox.exists,
+                highestmodseq: mailbox.highest_modseq,
+            };
+            let cached = folders.iter().find(|f| f.name == *folder);
+            let freshness = CacheFreshness::classify(cached, &server);
+            debug!(account = %account.id, folder = %folder, freshness = ?freshness, "Cache checked");
+            checks.push(FolderCheck {
+                folder: folder.clone(),
+                freshness,
+            });
+        }
+        CONNECTION_POOL
+            .return_connection(&account.id, "status", session)
+            .await;
+
+        Ok(checks)
+    }
+}
@@ -0,0 +1,299 @@
+//! `otto verify`: audits the cache against the server. For every UID in a folder's sync
+//! window (or an evenly spaced sample of them) it reports messages missing from the cache,
+//! cached messages the server no longer has, and cached flags/labels that drifted, and can
+//! repair each kind without touching the incremental sync state. With a hash sample it also
+//! downloads some cached messages and compares their `raw_hash` with the server's copy, which
+//! catches bodies truncated or corrupted by a crash or a bad restore.
+use std::collections::{BTreeSet, HashSet};
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use tracing::{info, warn};
+
+use super::{CONNECTION_POOL, ImapSession, SyncEngine, cache_window_start};
+use crate::credentials::imap_secret;
+use crate::imap::build_uid_sequence;
+use crate::sanitize::compute_hash;
+use crate::types::Account;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VerifyOptions {
+    /// Check at most this many UIDs per folder instead of all of them.
+    pub sample: Option<usize>,
+    /// Also download up to this many cached messages per folder and compare their `raw_hash`.
+    pub hash_sample: Option<usize>,
+    /// Fetch missing messages, drop extra ones, overwrite drifted flags and re-download bodies
+    /// whose hash differs.
+    pub repair: bool,
+}
+
+/// Differences found in one folder; UIDs are sorted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FolderDrift {
+    pub folder: String,
+    /// UIDs compared (after sampling).
+    pub checked: usize,
+    /// On the server inside the sync window but not cached.
+    pub missing: Vec<u32>,
+    /// Cached but no longer on the server.
+    pub extra: Vec<u32>,
+    /// Cached with flags or labels that differ from the server's.
+    pub mismatched: Vec<u32>,
+    /// Messages whose hash was compared (`--hash-sample`).
+    pub hashed: usize,
+    /// Cached raw message whose hash differs from the server's copy.
+    pub corrupt: Vec<u32>,
+    /// The cache belongs to an older UIDVALIDITY; nothing was compared (a sync resets it).
+    pub uidvalidity_changed: bool,
+    pub repaired: bool,
+}
+
+impl FolderDrift {
+    pub fn is_clean(&self) -> bool {
+        !self.uidvalidity_changed
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+            && self.mismatched.is_empty()
+            && self.corrupt.is_empty()
+    }
+}
+
+/// Up to `limit` UIDs spread evenly over `uids` (sorted), so a sample covers old and new mail.
+pub fn sample_uids(uids: &[u32], limit: usize) -> Vec<u32> {
+    if limit == 0 {
+        return Vec::new();
+    }
+    if uids.len() <= limit {
+        return uids.to_vec();
+    }
+    (0..limit).map(|i| uids[i * uids.len() / limit]).collect()
+}
+
+/// Flags and labels as a comparable set. `\Recent` is per-session state, not drift.
+fn flag_set(flags: &[String], labels: &[String]) -> BTreeSet<String> {
+    flags
+        .iter()
+        .filter(|f| f.as_str() != "Recent")
+        .cloned()
+        .chain(labels.iter().map(|l| format!("label:{}", l)))
+        .collect()
+}
+
+impl SyncEngine {
+    /// Verifies `folder` (default: every enabled folder) of `account`.
+    pub async fn verify(
+        &self,
+        account: &Account,
+        folder: Option<&str>,
+        options: VerifyOptions,
+    ) -> Result<Vec<FolderDrift>> {
+        let secret = imap_secret(account).await?;
+        let mut session = CONNECTION_POOL
+            .get_or_create(account, "verify", &secret)
+            .await
+            .context("connecting for verify")?;
+
+        let folders: Vec<String> = match folder {
+            Some(name) => vec![name.to_string()],
+            None => super::synced_folders(self.db.as_ref(), account).await?,
+        };
+        let mut reports = Vec::new();
+        for folder_name in &folders {
+            match self
+                .verify_folder(&mut session, account, folder_name, options)
+                .await
+            {
+                Ok(drift) => reports.push(drift),
+                Err(e) => {
+                    warn!(account = %account.id, folder = %folder_name, error = %e, "Verify failed")
+                }
+            }
+        }
+        CONNECTION_POOL
+            .return_connection(&account.id, "verify", session)
+            .await;
+        Ok(reports)
+    }
+
+    async fn verify_folder(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        options: VerifyOptions,
+    ) -> Result<FolderDrift> {
+        let mut drift = FolderDrift {
+            folder: folder_name.to_string(),
+            ..Default::default()
+        };
+        // EXAMINE is read-only: verifying never clears \Recent or otherwise touches the folder.
+        let mailbox = session
+            .examine(folder_name)
+            .await
+            .with_context(|| format!("examining folder {}", folder_name))?;
+        let state = self
+            .db
+            .list_folders(&account.id)
+            .await?
+            .into_iter()
+            .find(|f| f.name == folder_name);
+        if let Some(stored) = state.as_ref().and_then(|s| s.uidvalidity)
+            && Some(stored) != mailbox.uid_validity
+        {
+            drift.uidvalidity_changed = true;
+            return Ok(drift);
+        }
+
+        let cutoff = cache_window_start(account, folder_name, state.as_ref());
+        let query = format!("SINCE {}", cutoff.format("%d-%b-%Y"));
+        let remote: HashSet<u32> = session
+            .uid_search(&query)
+            .await
+            .with_context(|| format!("UID SEARCH verify: {}", query))?
+            .into_iter()
+            .collect();
+        let cached = self.db.load_flags_by_uid(&account.id, folder_name).await?;
+
+        let mut all: Vec<u32> = remote.iter().chain(cached.keys()).copied().collect();
+        all.sort_unstable();
+        all.dedup();
+        let checked = match options.sample {
+            Some(limit) => sample_uids(&all, limit),
+            None => all,
+        };
+        drift.checked = checked.len();
+
+        let mut both = Vec::new();
+        for uid in checked {
+            match (remote.contains(&uid), cached.contains_key(&uid)) {
+                (true, false) => drift.missing.push(uid),
+                (false, true) => drift.extra.push(uid),
+                (true, true) => both.push(uid),
+                (false, false) => {}
+            }
+        }
+
+        let mut corrupt_entries = Vec::new();
+        if let Some(limit) = options.hash_sample {
+            let hashes = self
+                .db
+                .load_raw_hashes_by_uid(&account.id, folder_name)
+                .await?;
+            let candidates: Vec<u32> = both
+                .iter()
+                .copied()
+                .filter(|uid| hashes.contains_key(uid))
+                .collect();
+            let sampled = sample_uids(&candidates, limit);
+            drift.hashed = sampled.len();
+            for (uid, server_hash) in fetch_raw_hashes(session, &sampled).await? {
+                if let Some((message_id, cached_hash)) = hashes.get(&uid)
+                    && *cached_hash != server_hash
+                {
+                    drift.corrupt.push(uid);
+                    corrupt_entries.push((uid, message_id.clone()));
+                }
+            }
+            drift.corrupt.sort_unstable();
+        }
+
+        // Local flag ops not yet pushed are expected differences, not drift.
+        let pending: HashSet<u32> = self
+            .db
+            .load_pending_flag_ops(&account.id, folder_name, &both)
+            .await?
+            .into_iter()
+            .map(|p| p.uid)
+            .collect();
+        both.retain(|uid| !pending.contains(uid));
+        let server_flags = self
+            .fetch_and_update_flags(session, account, folder_name, &both)
+            .await?;
+        let mut flag_repairs = Vec::new();
+        for (uid, flags, labels) in server_flags {
+            let Some((cached_flags, cached_labels)) = cached.get(&uid) else {
+                continue;
+            };
+            if flag_set(&flags, &labels) != flag_set(cached_flags, cached_labels) {
+                drift.mismatched.push(uid);
+                flag_repairs.push((uid, flags, labels));
+            }
+        }
+        drift.mismatched.sort_unstable();
+
+        if options.repair && !drift.is_clean() {
+            self.repair_folder(
+                session,
+                account,
+                folder_name,
+                &drift,
+                &flag_repairs,
+                &corrupt_entries,
+            )
+            .await?;
+            drift.repaired = true;
+        }
+        info!(
+            account = %account.id,
+            folder = %folder_name,
+            checked = drift.checked,
+            missing = drift.missing.len(),
+            extra = drift.extra.len(),
+            mismatched = drift.mismatched.len(),
+            corrupt = drift.corrupt.len(),
+            "Folder verified"
+        );
+        Ok(drift)
+    }
+
+    async fn repair_folder(
+        &self,
+        session: &mut ImapSession,
+        account: &Account,
+        folder_name: &str,
+        drift: &FolderDrift,
+        flag_repairs: &[(u32, Vec<String>, Vec<String>)],
+        corrupt: &[(u32, String)],
+    ) -> Result<()> {
+        self.db
+            .batch_update_message_flags_by_uid(&account.id, folder_name, flag_repairs)
+            .await?;
+        self.db
+            .delete_messages_by_folder_and_uids(&account.id, folder_name, &drift.extra)
+            .await?;
+        // Same write path as backfill: stores mail without moving MODSEQ/UID checkpoints.
+        for batch in drift.missing.chunks(super::CHECKPOINT_BATCH_UIDS) {
+            let (_, location_updates) = self
+                .fetch_and_handle_new_uids(session, account, folder_name, batch, false)
+                .await?;
+            self.db
+                .commit_backfill_batch(&account.id, folder_name, &[], &[], &location_updates, None)
+                .await?;
+        }
+        // Overwrites the stored raw message and re-runs the sanitizer, like `otto refetch`.
+        if !corrupt.is_empty() {
+            self.fetch_bodies_for_uids(session, account, folder_name, corrupt)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Downloads `uids` (BODY.PEEK[], so nothing is marked read) and hashes each raw message the
+/// way the sync stores it.
+async fn fetch_raw_hashes(session: &mut ImapSession, uids: &[u32]) -> Result<Vec<(u32, String)>> {
+    let mut hashes = Vec::new();
+    for chunk in uids.chunks(50) {
+        let mut stream = session
+            .uid_fetch(build_uid_sequence(chunk), "(UID BODY.PEEK[])")
+            .await
+            .context("fetching bodies to verify")?;
+        while let Some(fetch) = stream.next().await {
+            let fetch = fetch.context("reading body to verify")?;
+            if let (Some(uid), Some(body)) = (fetch.uid, fetch.body()) {
+                hashes.push((uid, compute_hash(body)));
+            }
+        }
+    }
+    Ok(hashes)
+}
@@ -0,0 +1,204 @@
+//! Reply graph of one thread for `otto thread`: who replied to whom and when, rebuilt from the
+//! References/In-Reply-To headers of the cached raw messages with the JWZ linking rules of
+//! `crate::threading`. Referenced messages that aren't cached stay in the graph as
+//! placeholders so branches still hang together. Rendered as Graphviz DOT or an indented tree.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::address::friendly_from;
+use crate::encoded_words::decode_mime_words;
+use crate::threading::{Threader, header_references};
+use crate::timefmt::{DisplayTz, format_absolute};
+use crate::types::{BodyRecord, MessageRecord};
+
+/// Longest subject shown on a node before it is cut with `...`.
+const SUBJECT_CHARS: usize = 60;
+
+/// One cached message of the thread.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ThreadMessage {
+    /// Normalized Message-ID, or the provider id for messages without one.
+    pub key: String,
+    pub from: String,
+    pub date: Option<i64>,
+    pub subject: Option<String>,
+    /// Parent Message-IDs, oldest first (empty when the raw message isn't cached).
+    pub references: Vec<String>,
+}
+
+impl ThreadMessage {
+    pub fn from_record(message: &MessageRecord, body: Option<&BodyRecord>) -> Self {
+        let references = body
+            .and_then(|b| b.raw_rfc822.as_deref())
+            .and_then(|raw| mailparse::parse_headers(raw).ok())
+            .map(|(headers, _)| header_references(&headers))
+            .unwrap_or_default();
+        Self {
+            key: message
+                .message_id_header
+                .clone()
+                .unwrap_or_else(|| message.id.clone()),
+            from: decode_mime_words(&friendly_from(
+                message.from_name.as_deref(),
+                message.from.as_deref(),
+            )),
+            date: message.internal_date,
+            subject: message.subject.as_deref().map(decode_mime_words),
+            references,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ThreadGraph {
+    /// Cached messages by key; a message stored in several folders appears once.
+    messages: HashMap<String, ThreadMessage>,
+    threader: Threader,
+}
+
+impl ThreadGraph {
+    pub fn new(messages: impl IntoIterator<Item = ThreadMessage>) -> Self {
+        let mut by_key: HashMap<String, ThreadMessage> = HashMap::new();
+        for message in messages {
+            by_key.entry(message.key.clone()).or_insert(message);
+        }
+        // Oldest first, so an older message's links win when headers disagree.
+        let mut ordered: Vec<&ThreadMessage> = by_key.values().collect();
+        ordered.sort_by(|a, b| (a.date, &a.key).cmp(&(b.date, &b.key)));
+        let mut threader = Threader::default();
+        for message in ordered {
+            threader.add_message(&message.key, &message.references);
+        }
+        Self {
+            messages: by_key,
+            threader,
+        }
+    }
+
+    /// Cached messages in the graph.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// `(parent, child)` reply links, parents first.
+    pub fn edges(&self) -> Vec<(&str, &str)> {
+        let mut edges: Vec<(&str, &str)> = self
+            .threader
+            .containers()
+            .filter_map(|(id, parent)| parent.map(|p| (p, id)))
+            .collect();
+        edges.sort_by_key(|(parent, child)| (self.sort_key(parent), self.sort_key(child)));
+        edges
+    }
+
+    /// Graphviz DOT, one node per message (dashed for referenced but uncached ones) with an
+    /// edge from each message to the one it replied to, laid out left to right in time.
+    pub fn to_dot(&self, tz: DisplayTz) -> String {
+        let mut out = String::from("digraph thread {\n");
+        out.push_str("  rankdir=LR;\n");
+        out.push_str("  node [shape=box, fontname=\"Helvetica\"];\n");
+        for id in self.nodes() {
+            let _ = match self.messages.get(id) {
+                Some(message) => writeln!(
+                    out,
+                    "  \"{}\" [label=\"{}\"];",
+                    escape(id),
+                    escape(&self.label(message, tz))
+                ),
+                None => writeln!(
+                    out,
+                    "  \"{}\" [label=\"(not cached)\\n{}\", style=dashed];",
+                    escape(id),
+                    escape(id)
+                ),
+            };
+        }
+        for (parent, child) in self.edges() {
+            let _ = writeln!(out, "  \"{}\" -> \"{}\";", escape(parent), escape(child));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Indented reply tree: replies under the message they answer, oldest first.
+    pub fn to_tree(&self, tz: DisplayTz) -> String {
+        let mut children: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let mut roots = Vec::new();
+        for id in self.nodes() {
+            match self.threader.parent(id) {
+                Some(parent) => children.entry(parent).or_default().push(id),
+                None => roots.push(id),
+            }
+        }
+        let mut out = String::new();
+        let mut stack: Vec<(&str, usize)> = roots.into_iter().rev().map(|id| (id, 0)).collect();
+        while let Some((id, depth)) = stack.pop() {
+            let line = match self.messages.get(id) {
+                Some(message) => self.label(message, tz).replace('\n', "  "),
+                None => format!("(not cached) <{}>", id),
+            };
+            let _ = writeln!(out, "{}{}", "  ".repeat(depth), line);
+            if let Some(replies) = children.get(id) {
+                stack.extend(replies.iter().rev().map(|reply| (*reply, depth + 1)));
+            }
+        }
+        out
+    }
+
+    /// Every node, cached or not, oldest first (uncached ones sort by their first reply).
+    fn nodes(&self) -> Vec<&str> {
+        let mut nodes: Vec<&str> = self.threader.containers().map(|(id, _)| id).collect();
+        nodes.sort_by_key(|id| self.sort_key(id));
+        nodes
+    }
+
+    fn sort_key<'a>(&'a self, id: &'a str) -> (Option<i64>, &'a str) {
+        let date = match self.messages.get(id) {
+            Some(message) => message.date,
+            None => self
+                .threader
+                .containers()
+                .filter(|(_, parent)| *parent == Some(id))
+                .filter_map(|(child, _)| self.messages.get(child).and_then(|m| m.date))
+                .min(),
+        };
+        (date, id)
+    }
+
+    fn label(&self, message: &ThreadMessage, tz: DisplayTz) -> String {
+        let when = message
+            .date
+            .map(|ts| format_absolute(ts, tz))
+            .unwrap_or_else(|| "(no date)".to_string());
+        let mut label = format!("{}\n{}", message.from, when);
+        if let Some(subject) = message.subject.as_deref().filter(|s| !s.is_empty()) {
+            label.push('\n');
+            if subject.chars().count() > SUBJECT_CHARS {
+                label.extend(subject.chars().take(SUBJECT_CHARS));
+                label.push_str("...");
+            } else {
+                label.push_str(subject);
+            }
+        }
+        label
+    }
+}
+
+/// Quotes text for a DOT string; newlines become DOT's `\n` line breaks.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
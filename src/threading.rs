@@ -0,0 +1,131 @@
+//! JWZ-style threading (<https://www.jwz.org/doc/threading.html>) for messages without
+//! X-GM-THRID. Every Message-ID seen, including referenced messages that aren't cached, gets
+//! a container; `References`/`In-Reply-To` chains link containers to parents, and a message's
+//! thread is the root of its container tree. Subject-based grouping is deliberately left out:
+//! it merges unrelated "Re: hello" threads too often for a cache that never re-threads.
+use std::collections::HashMap;
+
+use mailparse::{MailHeader, MailHeaderMap, ParsedMail};
+
+use crate::address::normalize_message_id;
+
+/// Parent Message-IDs of `parsed`, oldest first: `References`, then `In-Reply-To` when it
+/// isn't already the last reference.
+pub fn parent_references(parsed: &ParsedMail) -> Vec<String> {
+    header_references(&parsed.headers)
+}
+
+/// [`parent_references`] from a bare header block (`mailparse::parse_headers`).
+pub fn header_references(headers: &[MailHeader]) -> Vec<String> {
+    let mut refs = headers
+        .get_first_value("References")
+        .map(|raw| parse_message_id_list(&raw))
+        .unwrap_or_default();
+    let in_reply_to = headers
+        .get_first_value("In-Reply-To")
+        .and_then(|raw| parse_message_id_list(&raw).into_iter().next());
+    if let Some(parent) = in_reply_to
+        && refs.last() != Some(&parent)
+    {
+        refs.push(parent);
+    }
+    refs
+}
+
+/// Every `<id>` in a `References`-style header, normalized, without duplicates.
+pub fn parse_message_id_list(raw: &str) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for chunk in raw.split('<').skip(1) {
+        let Some(end) = chunk.find('>') else {
+            continue;
+        };
+        if let Some(id) = normalize_message_id(&chunk[..end])
+            && !ids.contains(&id)
+        {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+/// Container graph keyed by Message-ID. Only parent links are kept: a thread id only needs
+/// the root, and children are one query away in the `threads` table.
+#[derive(Debug, Default)]
+pub struct Threader {
+    parents: HashMap<String, Option<String>>,
+}
+
+impl Threader {
+    /// Seeds a container whose parent is already known (e.g. loaded from storage).
+    pub fn insert(&mut self, id: &str, parent: Option<&str>) {
+        self.parents
+            .insert(id.to_string(), parent.map(str::to_string));
+    }
+
+    /// JWZ step 1 for one message: links each reference to the next unless that child already
+    /// has a parent or the link would close a loop, then makes the last reference the
+    /// message's own parent (its headers are authoritative, so they replace an older guess).
+    pub fn add_message(&mut self, id: &str, references: &[String]) {
+        for id in references.iter().map(String::as_str).chain([id]) {
+            self.parents.entry(id.to_string()).or_default();
+        }
+        for pair in references.windows(2) {
+            let (parent, child) = (&pair[0], &pair[1]);
+            if self.parent(child).is_none() && !self.is_ancestor(child, parent) {
+                self.parents.insert(child.clone(), Some(parent.clone()));
+            }
+        }
+        let parent = references
+            .last()
+            .filter(|p| p.as_str() != id && !self.is_ancestor(id, p));
+        self.parents.insert(id.to_string(), parent.cloned());
+    }
+
+    pub fn parent(&self, id: &str) -> Option<&str> {
+        self.parents.get(id).and_then(|p| p.as_deref())
+    }
+
+    /// Whether `ancestor` is `id` or above it.
+    fn is_ancestor(&self, ancestor: &str, id: &str) -> bool {
+        let mut current = Some(id);
+        let mut steps = 0;
+        while let Some(node) = current {
+            if node == ancestor {
+                return true;
+            }
+            // Seeded data could already hold a cycle; never walk it forever.
+            steps += 1;
+            if steps > self.parents.len() {
+                return true;
+            }
+            current = self.parent(node);
+        }
+        false
+    }
+
+    /// Topmost container above `id` (the thread root).
+    pub fn root<'a>(&'a self, id: &'a str) -> &'a str {
+        let mut current = id;
+        let mut steps = 0;
+        while let Some(parent) = self.parent(current) {
+            steps += 1;
+            if steps > self.parents.len() {
+                break;
+            }
+            current = parent;
+        }
+        current
+    }
+
+    /// Containers with their parents, for persisting.
+    pub fn containers(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.parents
+            .iter()
+            .map(|(id, parent)| (id.as_str(), parent.as_deref()))
+    }
+}
+
+/// Thread id for a tree rooted at Message-ID `root` (distinct from numeric X-GM-THRIDs).
+pub fn thread_id_for_root(root: &str) -> String {
+    format!("jwz:{}", root)
+}
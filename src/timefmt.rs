@@ -0,0 +1,108 @@
+//! Message date rendering in the user's timezone, with relative labels for recent mail.
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Timezone dates are shown in: the system zone, or an IANA zone (`OTTO_TIMEZONE`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DisplayTz {
+    #[default]
+    Local,
+    Named(Tz),
+}
+
+impl DisplayTz {
+    /// `local` (or empty) selects the system zone; anything else must be an IANA name
+    /// such as `Europe/Istanbul` or `UTC`.
+    pub fn parse(raw: &str) -> Result<Self> {
+        let raw = raw.trim();
+        if raw.is_empty() || raw.eq_ignore_ascii_case("local") {
+            return Ok(Self::Local);
+        }
+        raw.parse::<Tz>()
+            .map(Self::Named)
+            .map_err(|e| anyhow!("unknown timezone {:?}: {}", raw, e))
+    }
+}
+
+/// Formats a unix timestamp relative to the current time; see [`format_relative`].
+pub fn format_timestamp(ts: Option<i64>, tz: DisplayTz) -> String {
+    format_relative(ts, Utc::now(), tz)
+}
+
+/// `2024-03-04 09:15:02` in `tz`, for logs where relative labels would be ambiguous.
+pub fn format_absolute(ts: i64, tz: DisplayTz) -> String {
+    let Some(dt) = DateTime::<Utc>::from_timestamp(ts, 0) else {
+        return "Unknown".to_string();
+    };
+    match tz {
+        DisplayTz::Local => dt
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+        DisplayTz::Named(zone) => dt
+            .with_timezone(&zone)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+    }
+}
+
+/// Calendar day of a unix timestamp in `tz`.
+pub fn local_date(ts: i64, tz: DisplayTz) -> Option<NaiveDate> {
+    let dt = DateTime::<Utc>::from_timestamp(ts, 0)?;
+    Some(match tz {
+        DisplayTz::Local => dt.with_timezone(&Local).date_naive(),
+        DisplayTz::Named(zone) => dt.with_timezone(&zone).date_naive(),
+    })
+}
+
+/// Compact age of something `secs` old: `just now`, `5m ago`, `3h ago`, `2d ago`. Unlike
+/// `format_relative` it never switches to a date, for ages that matter as durations.
+pub fn format_age(secs: i64) -> String {
+    match secs.max(0) {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s if s < 86_400 => format!("{}h ago", s / 3600),
+        s => format!("{}d ago", s / 86_400),
+    }
+}
+
+/// `just now` / `5m ago` / `3h ago` for today, `Yesterday 18:04`, weekday within the last
+/// week, then `Mar 04 09:15` (same year) or `2023-03-04 09:15`. Calendar days are those of `tz`.
+pub fn format_relative(ts: Option<i64>, now: DateTime<Utc>, tz: DisplayTz) -> String {
+    let Some(dt) = ts.and_then(|ts| DateTime::<Utc>::from_timestamp(ts, 0)) else {
+        return "Unknown".to_string();
+    };
+    match tz {
+        DisplayTz::Local => render(dt.with_timezone(&Local), now.with_timezone(&Local)),
+        DisplayTz::Named(zone) => render(dt.with_timezone(&zone), now.with_timezone(&zone)),
+    }
+}
+
+fn render<Z: TimeZone>(dt: DateTime<Z>, now: DateTime<Z>) -> String
+where
+    Z::Offset: std::fmt::Display,
+{
+    let age = now.clone().signed_duration_since(dt.clone());
+    let day_gap = (now.date_naive() - dt.date_naive()).num_days();
+
+    // Future timestamps (clock skew, bogus INTERNALDATE) get an absolute date.
+    if age < Duration::zero() {
+        return dt.format("%Y-%m-%d %H:%M").to_string();
+    }
+    if age < Duration::minutes(1) {
+        return "just now".to_string();
+    }
+    if age < Duration::hours(1) {
+        return format!("{}m ago", age.num_minutes());
+    }
+    match day_gap {
+        0 => format!("{}h ago", age.num_hours()),
+        1 => dt.format("Yesterday %H:%M").to_string(),
+        2..=6 => dt.format("%a %H:%M").to_string(),
+        _ if dt.format("%Y").to_string() == now.format("%Y").to_string() => {
+            dt.format("%b %d %H:%M").to_string()
+        }
+        _ => dt.format("%Y-%m-%d %H:%M").to_string(),
+    }
+}
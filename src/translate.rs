@@ -0,0 +1,306 @@
+//! Inline translation of message bodies (`T` in the TUI, `otto translate`). The sanitized body
+//! goes to the configured backend: a DeepL-compatible API (`POST .../v2/translate`) or an LLM
+//! behind an OpenAI-compatible chat completions endpoint (hosted, or local such as Ollama or
+//! llama.cpp). Results are cached per message and target language (`message_translations`), so
+//! reopening a translated message costs no request and works offline.
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::{Value, json};
+
+use crate::storage::MailStore;
+use crate::storage::translations::{self, MessageTranslation};
+use crate::types::now_ts;
+
+/// Translation requests give up after this long (LLMs answer slowly on long mail).
+const TRANSLATE_TIMEOUT: Duration = Duration::from_secs(90);
+/// Bodies longer than this (in characters) are refused rather than sent in pieces.
+pub const MAX_TRANSLATE_CHARS: usize = 50_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranslateBackend {
+    /// DeepL's `/v2/translate` or a compatible server (LibreTranslate's DeepL shim, ...).
+    DeepL,
+    /// An OpenAI-compatible `/v1/chat/completions` endpoint.
+    Llm,
+}
+
+impl TranslateBackend {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "deepl" => Ok(Self::DeepL),
+            "llm" | "openai" => Ok(Self::Llm),
+            other => bail!("unknown translation backend {:?} (deepl or llm)", other),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::DeepL => "deepl",
+            Self::Llm => "llm",
+        }
+    }
+}
+
+/// Where the TUI shows a translation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TranslationView {
+    /// Original and translation next to each other.
+    #[default]
+    Side,
+    /// The translation in place of the original.
+    Inline,
+}
+
+impl TranslationView {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "side" | "side-by-side" => Ok(Self::Side),
+            "inline" | "in-place" => Ok(Self::Inline),
+            other => bail!("unknown translation view {:?} (side or inline)", other),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranslateConfig {
+    pub backend: TranslateBackend,
+    /// Full endpoint URL, e.g. `https://api-free.deepl.com/v2/translate` or
+    /// `http://localhost:11434/v1/chat/completions`.
+    pub url: String,
+    pub api_key: Option<String>,
+    /// Model name for LLM backends (left out of the request when unset).
+    pub model: Option<String>,
+    /// Default target language (`de`, `pt-BR`).
+    pub target: String,
+    pub view: TranslationView,
+}
+
+impl TranslateConfig {
+    /// From `OTTO_TRANSLATE_URL` (unset: no translation), `OTTO_TRANSLATE_BACKEND` (default
+    /// `deepl` for DeepL hosts, else `llm`), `OTTO_TRANSLATE_API_KEY`, `OTTO_TRANSLATE_MODEL`,
+    /// `OTTO_TRANSLATE_TO` (default: the language of `LC_ALL`/`LC_MESSAGES`/`LANG`, else `en`)
+    /// and `OTTO_TRANSLATE_VIEW`.
+    pub fn from_env(get: impl Fn(&str) -> Option<String>) -> Result<Option<Self>> {
+        let get = |name: &str| get(name).filter(|v| !v.trim().is_empty());
+        let Some(url) = get("OTTO_TRANSLATE_URL") else {
+            return Ok(None);
+        };
+        let backend = match get("OTTO_TRANSLATE_BACKEND") {
+            Some(raw) => TranslateBackend::parse(&raw)?,
+            None if url.contains("deepl") => TranslateBackend::DeepL,
+            None => TranslateBackend::Llm,
+        };
+        let target = match get("OTTO_TRANSLATE_TO") {
+            Some(raw) => parse_language(&raw)?,
+            None => ["LC_ALL", "LC_MESSAGES", "LANG"]
+                .into_iter()
+                .find_map(&get)
+                .and_then(|raw| locale_language(&raw))
+                .unwrap_or_else(|| "en".to_string()),
+        };
+        let view = match get("OTTO_TRANSLATE_VIEW") {
+            Some(raw) => TranslationView::parse(&raw)?,
+            None => TranslationView::default(),
+        };
+        Ok(Some(Self {
+            backend,
+            url: url.trim().to_string(),
+            api_key: get("OTTO_TRANSLATE_API_KEY"),
+            model: get("OTTO_TRANSLATE_MODEL"),
+            target,
+            view,
+        }))
+    }
+}
+
+/// A target language tag: letters with optional `-`/`_` subtags (`de`, `pt-BR`, `zh_Hant`).
+pub fn parse_language(raw: &str) -> Result<String> {
+    let tag = raw.trim().replace('_', "-");
+    let valid = !tag.is_empty()
+        && tag
+            .split('-')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !valid {
+        bail!("invalid language {:?} (e.g. de, fr, pt-BR)", raw);
+    }
+    Ok(tag)
+}
+
+/// The language of a POSIX locale name (`de_DE.UTF-8` -> `de`); `None` for `C`/`POSIX`.
+fn locale_language(raw: &str) -> Option<String> {
+    let language = raw.split(['_', '.', '@']).next()?.trim();
+    if language.is_empty()
+        || language.eq_ignore_ascii_case("C")
+        || language.eq_ignore_ascii_case("POSIX")
+    {
+        return None;
+    }
+    parse_language(language).ok()
+}
+
+/// What a backend returned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Translated {
+    pub text: String,
+    /// Language the backend detected in the original, when it reports one.
+    pub source_lang: Option<String>,
+}
+
+pub struct Translator {
+    config: TranslateConfig,
+    client: reqwest::Client,
+}
+
+impl Translator {
+    pub fn new(config: TranslateConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn config(&self) -> &TranslateConfig {
+        &self.config
+    }
+
+    /// Sends `text` to the backend for translation into `target`.
+    pub async fn translate(&self, text: &str, target: &str) -> Result<Translated> {
+        let request = self
+            .client
+            .post(&self.config.url)
+            .timeout(TRANSLATE_TIMEOUT);
+        let request = match (self.config.backend, &self.config.api_key) {
+            (TranslateBackend::DeepL, Some(key)) => {
+                request.header("Authorization", format!("DeepL-Auth-Key {}", key))
+            }
+            (TranslateBackend::Llm, Some(key)) => request.bearer_auth(key),
+            (_, None) => request,
+        };
+        let body = match self.config.backend {
+            TranslateBackend::DeepL => json!({
+                "text": [text],
+                "target_lang": target.to_ascii_uppercase(),
+            }),
+            TranslateBackend::Llm => {
+                let mut body = json!({
+                    "messages": [
+                        {"role": "system", "content": llm_instructions(target)},
+                        {"role": "user", "content": text},
+                    ],
+                    "temperature": 0,
+                });
+                if let Some(model) = &self.config.model {
+                    body["model"] = json!(model);
+                }
+                body
+            }
+        };
+        let response: Value = request
+            .json(&body)
+            .send()
+            .await
+            .with_context(|| format!("posting to {}", self.config.url))?
+            .error_for_status()
+            .with_context(|| format!("translation backend {}", self.config.url))?
+            .json()
+            .await
+            .context("reading the translation response")?;
+        match self.config.backend {
+            TranslateBackend::DeepL => parse_deepl(&response),
+            TranslateBackend::Llm => parse_llm(&response),
+        }
+    }
+}
+
+fn llm_instructions(target: &str) -> String {
+    format!(
+        "Translate the email the user sends into the language with the BCP 47 tag {:?}. \
+         Reply with the translation only. Keep the line breaks, quoted lines, links, \
+         addresses and names as they are; do not summarize or add comments.",
+        target
+    )
+}
+
+/// `{"translations": [{"detected_source_language": "DE", "text": "..."}]}`
+fn parse_deepl(response: &Value) -> Result<Translated> {
+    let first = &response["translations"][0];
+    let text = first["text"]
+        .as_str()
+        .ok_or_else(|| anyhow!("no translation in the DeepL response"))?;
+    Ok(Translated {
+        text: text.to_string(),
+        source_lang: first["detected_source_language"]
+            .as_str()
+            .map(str::to_ascii_lowercase),
+    })
+}
+
+/// `{"choices": [{"message": {"content": "..."}}]}`
+fn parse_llm(response: &Value) -> Result<Translated> {
+    let text = response["choices"][0]["message"]["content"]
+        .as_str()
+        .ok_or_else(|| anyhow!("no translation in the chat completions response"))?;
+    Ok(Translated {
+        text: text.trim().to_string(),
+        source_lang: None,
+    })
+}
+
+/// The translation of the account's message into `target`. A cached translation of the
+/// current body is reused unless `refresh` is set; otherwise the body goes to `translator`
+/// (refused when `offline` or when there is none) and the result is cached.
+pub async fn translate_message(
+    db: &dyn MailStore,
+    translator: Option<&Translator>,
+    account_id: &str,
+    message_id: &str,
+    target: &str,
+    refresh: bool,
+    offline: bool,
+) -> Result<MessageTranslation> {
+    let thread = db.load_thread(account_id, message_id).await?;
+    let Some((_, body)) = thread.iter().find(|(msg, _)| msg.id == message_id) else {
+        bail!("no cached message {}", message_id);
+    };
+    let text = body
+        .as_ref()
+        .and_then(|b| b.sanitized_text.as_deref())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .ok_or_else(|| anyhow!("message {} has no downloaded text body", message_id))?;
+    let hash = translations::source_hash(text);
+
+    if !refresh
+        && let Some(cached) = db
+            .load_message_translation(account_id, message_id, target)
+            .await?
+        && cached.source_hash == hash
+    {
+        return Ok(cached);
+    }
+    if offline {
+        bail!("offline and no cached translation into {}", target);
+    }
+    let Some(translator) = translator else {
+        bail!("no translation backend configured (set OTTO_TRANSLATE_URL)");
+    };
+    if text.chars().count() > MAX_TRANSLATE_CHARS {
+        bail!(
+            "message body is longer than {} characters; not translating it",
+            MAX_TRANSLATE_CHARS
+        );
+    }
+
+    let translated = translator.translate(text, target).await?;
+    let translation = MessageTranslation {
+        message_id: message_id.to_string(),
+        target: target.to_string(),
+        text: translated.text,
+        source_lang: translated.source_lang,
+        source_hash: hash,
+        updated_at: now_ts(),
+    };
+    db.set_message_translation(account_id, &translation).await?;
+    Ok(translation)
+}
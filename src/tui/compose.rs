@@ -0,0 +1,489 @@
+//! The compose view (`w`): a markdown draft with To, Cc, Subject and Body fields, ranked
+//! address suggestions while typing a recipient, an attach prompt with Tab completion
+//! (`Ctrl-A`), and each attachment's size plus the whole message's shown before sending
+//! (`Ctrl-S`). Sending and the address lookups are the app's (`TuiAction::Send`,
+//! `TuiAction::SuggestRecipients`).
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
+
+use crate::compose::attachments::{
+    Attachment, MAX_MESSAGE_BYTES, PathCompletion, complete_path, human_size,
+};
+use crate::compose::{
+    Draft, complete_recipient, current_recipient, message_size, split_recipients,
+};
+use crate::storage::addresses::AddressSuggestion;
+use crate::timefmt::format_age;
+use crate::types::now_ts;
+
+/// Candidates shown under the attach prompt after Tab.
+const MAX_CANDIDATES: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Field {
+    To,
+    Cc,
+    Subject,
+    Body,
+    Attachments,
+}
+
+impl Field {
+    const ORDER: [Field; 5] = [
+        Field::To,
+        Field::Cc,
+        Field::Subject,
+        Field::Body,
+        Field::Attachments,
+    ];
+
+    fn step(self, step: isize) -> Self {
+        let len = Self::ORDER.len() as isize;
+        let idx = Self::ORDER.iter().position(|f| *f == self).unwrap_or(0) as isize;
+        Self::ORDER[(idx + step).rem_euclid(len) as usize]
+    }
+}
+
+/// What a key did to the compose view, for the app to act on.
+pub(super) enum ComposeOutcome {
+    Stay,
+    /// Closed without sending.
+    Discard,
+    Send(Draft),
+    /// Look up addresses for the recipient being typed.
+    Suggest(String),
+}
+
+pub(super) struct Compose {
+    to: String,
+    cc: String,
+    subject: String,
+    body: String,
+    attachments: Vec<Attachment>,
+    focus: Field,
+    /// Addresses for the recipient being typed in To or Cc, best first; ↑/↓ pick, Tab or Enter
+    /// takes one.
+    suggestions: Vec<AddressSuggestion>,
+    selected_suggestion: usize,
+    /// What the suggestions were last asked for; answers to older queries are dropped.
+    suggestion_query: String,
+    /// Attachment under the cursor while the Attachments field has focus.
+    selected_attachment: usize,
+    /// The attach prompt: the path typed so far and what the last Tab matched.
+    attach: Option<PathCompletion>,
+    /// Handed to the app; keys other than Esc wait for its answer.
+    sending: bool,
+    /// `message_size` of the draft, recomputed on every change.
+    size: u64,
+    /// Last problem (a file that could not be attached, a failed send), until the next key.
+    error: Option<String>,
+}
+
+impl Compose {
+    pub(super) fn new() -> Self {
+        let mut compose = Self {
+            to: String::new(),
+            cc: String::new(),
+            subject: String::new(),
+            body: String::new(),
+            attachments: Vec::new(),
+            focus: Field::To,
+            suggestions: Vec::new(),
+            selected_suggestion: 0,
+            suggestion_query: String::new(),
+            selected_attachment: 0,
+            attach: None,
+            sending: false,
+            size: 0,
+            error: None,
+        };
+        compose.refresh_size();
+        compose
+    }
+
+    /// The draft as typed. `from` is left empty: the app sends from the account's address.
+    fn draft(&self) -> Draft {
+        Draft {
+            from: String::new(),
+            to: split_recipients(&self.to),
+            cc: split_recipients(&self.cc),
+            subject: self.subject.clone(),
+            body_markdown: self.body.clone(),
+            attachments: self.attachments.clone(),
+        }
+    }
+
+    fn refresh_size(&mut self) {
+        self.size = message_size(&self.draft());
+    }
+
+    /// The app's answer to `ComposeOutcome::Send`; a failure keeps the draft open to retry.
+    pub(super) fn send_failed(&mut self, error: String) {
+        self.sending = false;
+        self.error = Some(error);
+    }
+
+    /// The app's answer to `ComposeOutcome::Suggest`, shown if it is still what is being typed.
+    pub(super) fn set_suggestions(&mut self, query: &str, suggestions: Vec<AddressSuggestion>) {
+        if query == self.suggestion_query {
+            self.suggestions = suggestions;
+            self.selected_suggestion = 0;
+        }
+    }
+
+    pub(super) fn handle_key(&mut self, key: KeyEvent) -> ComposeOutcome {
+        if key.code == KeyCode::Esc {
+            if self.attach.take().is_some() {
+                return ComposeOutcome::Stay;
+            }
+            if !self.suggestions.is_empty() {
+                self.suggestions.clear();
+                return ComposeOutcome::Stay;
+            }
+            return ComposeOutcome::Discard;
+        }
+        if self.sending {
+            return ComposeOutcome::Stay;
+        }
+        self.error = None;
+        if self.attach.is_some() {
+            self.attach_key(key);
+            return ComposeOutcome::Stay;
+        }
+
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if !self.suggestions.is_empty() && self.suggestion_key(key.code) {
+            return self.refresh_suggestions();
+        }
+        match key.code {
+            KeyCode::Char('s') if ctrl => return self.send(),
+            KeyCode::Char('a') if ctrl => self.attach = Some(PathCompletion::default()),
+            KeyCode::Tab => self.focus = self.focus.step(1),
+            KeyCode::BackTab => self.focus = self.focus.step(-1),
+            KeyCode::Up if self.focus == Field::Attachments => {
+                self.selected_attachment = self.selected_attachment.saturating_sub(1);
+            }
+            KeyCode::Down
+                if self.focus == Field::Attachments
+                    && self.selected_attachment + 1 < self.attachments.len() =>
+            {
+                self.selected_attachment += 1;
+            }
+            KeyCode::Backspace | KeyCode::Delete
+                if self.focus == Field::Attachments
+                    && self.selected_attachment < self.attachments.len() =>
+            {
+                self.attachments.remove(self.selected_attachment);
+                self.selected_attachment = self
+                    .selected_attachment
+                    .min(self.attachments.len().saturating_sub(1));
+                self.refresh_size();
+            }
+            KeyCode::Enter if self.focus == Field::Body => {
+                self.body.push('\n');
+                self.refresh_size();
+            }
+            KeyCode::Enter => self.focus = self.focus.step(1),
+            KeyCode::Backspace => {
+                if let Some(text) = self.focused_text() {
+                    text.pop();
+                    self.refresh_size();
+                }
+            }
+            KeyCode::Char(c) if !ctrl => {
+                if let Some(text) = self.focused_text() {
+                    text.push(c);
+                    self.refresh_size();
+                }
+            }
+            _ => {}
+        }
+        self.refresh_suggestions()
+    }
+
+    /// Moves through or takes a suggestion; false for keys the list doesn't use.
+    fn suggestion_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Up => {
+                self.selected_suggestion = self.selected_suggestion.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                self.selected_suggestion =
+                    (self.selected_suggestion + 1).min(self.suggestions.len() - 1);
+            }
+            KeyCode::Tab | KeyCode::Enter => {
+                let Some(suggestion) = self.suggestions.get(self.selected_suggestion) else {
+                    return false;
+                };
+                let recipient = suggestion.recipient();
+                if let Some(text) = self.focused_text() {
+                    *text = complete_recipient(text, &recipient);
+                }
+                self.suggestions.clear();
+                self.refresh_size();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Asks for suggestions when the recipient being typed in To or Cc changed; clears them
+    /// anywhere else.
+    fn refresh_suggestions(&mut self) -> ComposeOutcome {
+        let query = match self.focus {
+            Field::To => current_recipient(&self.to).to_string(),
+            Field::Cc => current_recipient(&self.cc).to_string(),
+            _ => String::new(),
+        };
+        if query == self.suggestion_query {
+            return ComposeOutcome::Stay;
+        }
+        self.suggestions.clear();
+        self.suggestion_query = query.clone();
+        if query.is_empty() {
+            return ComposeOutcome::Stay;
+        }
+        ComposeOutcome::Suggest(query)
+    }
+
+    fn focused_text(&mut self) -> Option<&mut String> {
+        match self.focus {
+            Field::To => Some(&mut self.to),
+            Field::Cc => Some(&mut self.cc),
+            Field::Subject => Some(&mut self.subject),
+            Field::Body => Some(&mut self.body),
+            Field::Attachments => None,
+        }
+    }
+
+    fn attach_key(&mut self, key: KeyEvent) {
+        let Some(prompt) = self.attach.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Tab => *prompt = complete_path(&prompt.input),
+            KeyCode::Enter => match Attachment::from_path(&prompt.input) {
+                Ok(attachment) => {
+                    self.attachments.push(attachment);
+                    self.selected_attachment = self.attachments.len() - 1;
+                    self.attach = None;
+                    self.refresh_size();
+                }
+                Err(e) => self.error = Some(format!("{:#}", e)),
+            },
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                prompt.candidates.clear();
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                prompt.candidates.clear();
+            }
+            _ => {}
+        }
+    }
+
+    fn send(&mut self) -> ComposeOutcome {
+        if self.size > MAX_MESSAGE_BYTES {
+            self.error = Some(format!(
+                "The message is {}; the limit is {}",
+                human_size(self.size),
+                human_size(MAX_MESSAGE_BYTES)
+            ));
+            return ComposeOutcome::Stay;
+        }
+        self.sending = true;
+        ComposeOutcome::Send(self.draft())
+    }
+}
+
+pub(super) fn draw_compose(f: &mut ratatui::Frame, compose: &Compose, area: Rect) {
+    let attachment_rows = match &compose.attach {
+        Some(prompt) if !prompt.candidates.is_empty() => {
+            prompt.candidates.len().min(MAX_CANDIDATES) + 1
+        }
+        _ => compose.attachments.len().max(1) + 1,
+    };
+    let suggestion_rows = match compose.focus {
+        Field::To | Field::Cc => compose.suggestions.len(),
+        _ => 0,
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(5),
+                Constraint::Length(if suggestion_rows > 0 {
+                    suggestion_rows as u16 + 2
+                } else {
+                    0
+                }), // To, Cc, Subject
+                Constraint::Min(3),                             // body
+                Constraint::Length(attachment_rows as u16 + 2), // attachments + total
+                Constraint::Length(3),                          // action bar
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let header_line = |field: Field, label: &str, value: &str| {
+        let focused = compose.focus == field;
+        let label_style = if focused {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        Line::from(vec![
+            Span::styled(format!("{:<9}", label), label_style),
+            Span::raw(format!("{}{}", value, if focused { "_" } else { "" })),
+        ])
+    };
+    let headers = Paragraph::new(vec![
+        header_line(Field::To, "To:", &compose.to),
+        header_line(Field::Cc, "Cc:", &compose.cc),
+        header_line(Field::Subject, "Subject:", &compose.subject),
+    ])
+    .block(Block::default().borders(Borders::ALL).title("Compose"));
+    f.render_widget(headers, chunks[0]);
+    if suggestion_rows > 0 {
+        draw_suggestions(f, compose, chunks[1]);
+    }
+
+    let body_cursor = if compose.focus == Field::Body {
+        "_"
+    } else {
+        ""
+    };
+    let body = Paragraph::new(format!("{}{}", compose.body, body_cursor))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Body (markdown)"),
+        )
+        .wrap(Wrap { trim: false });
+    f.render_widget(body, chunks[2]);
+
+    draw_attachments(f, compose, chunks[3]);
+
+    let line = if let Some(error) = &compose.error {
+        Line::from(Span::styled(error.clone(), Style::default().fg(Color::Red)))
+    } else if let Some(prompt) = &compose.attach {
+        Line::from(format!(
+            "Attach file: {}_  [Tab] complete  [Enter] attach  [Esc] cancel",
+            prompt.input
+        ))
+    } else if compose.sending {
+        Line::from("Sending...")
+    } else {
+        Line::from(
+            "[Tab] next field  [Ctrl-A] attach  [Del] remove attachment  [Ctrl-S] send  [Esc] discard",
+        )
+    };
+    let bar = Paragraph::new(line).block(Block::default().borders(Borders::ALL).title("Actions"));
+    f.render_widget(bar, chunks[4]);
+}
+
+/// Ranked addresses for the recipient being typed, with how often and how recently they
+/// appeared.
+fn draw_suggestions(f: &mut ratatui::Frame, compose: &Compose, area: Rect) {
+    let now = now_ts();
+    let items: Vec<ListItem> = compose
+        .suggestions
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let style = if i == compose.selected_suggestion {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(s.recipient()),
+                Span::styled(
+                    format!(
+                        "  {} message(s), last {}",
+                        s.uses,
+                        format_age(now - s.last_seen)
+                    ),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]))
+            .style(style)
+        })
+        .collect();
+    let list = List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Suggestions (↑/↓ pick, Tab/Enter take, Esc hide)"),
+    );
+    f.render_widget(list, area);
+}
+
+/// Attached files with their sizes and the message total, or the attach prompt's matches.
+fn draw_attachments(f: &mut ratatui::Frame, compose: &Compose, area: Rect) {
+    if let Some(prompt) = compose.attach.as_ref().filter(|p| !p.candidates.is_empty()) {
+        let mut items: Vec<ListItem> = prompt
+            .candidates
+            .iter()
+            .take(MAX_CANDIDATES)
+            .map(|c| ListItem::new(c.as_str()))
+            .collect();
+        if prompt.candidates.len() > MAX_CANDIDATES {
+            items.push(ListItem::new(format!(
+                "... {} more",
+                prompt.candidates.len() - MAX_CANDIDATES
+            )));
+        }
+        let list = List::new(items).block(Block::default().borders(Borders::ALL).title("Matches"));
+        f.render_widget(list, area);
+        return;
+    }
+
+    let focused = compose.focus == Field::Attachments;
+    let mut items: Vec<ListItem> = compose
+        .attachments
+        .iter()
+        .enumerate()
+        .map(|(i, a)| {
+            let style = if focused && i == compose.selected_attachment {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            ListItem::new(format!(
+                "{:>9}  {}  ({})",
+                human_size(a.size()),
+                a.filename,
+                a.content_type
+            ))
+            .style(style)
+        })
+        .collect();
+    if items.is_empty() {
+        items.push(ListItem::new("No attachments ([Ctrl-A] attaches a file)"));
+    }
+    let over = compose.size > MAX_MESSAGE_BYTES;
+    items.push(
+        ListItem::new(format!(
+            "Total message: {} of {}{}",
+            human_size(compose.size),
+            human_size(MAX_MESSAGE_BYTES),
+            if over { " (too large to send)" } else { "" }
+        ))
+        .style(if over {
+            Style::default().fg(Color::Red)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        }),
+    );
+    let title = if focused {
+        "Attachments (↑/↓ select, Del removes)"
+    } else {
+        "Attachments"
+    };
+    let list = List::new(items).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(list, area);
+}
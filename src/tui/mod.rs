@@ -0,0 +1,1799 @@
+mod compose;
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Tabs};
+use tokio::sync::mpsc::UnboundedSender;
+
+use self::compose::{Compose, ComposeOutcome, draw_compose};
+
+use crate::address::{friendly_from, full_from, parse_mailbox};
+use crate::collation::{Sorter, subject_sort_key};
+use crate::compose::Draft;
+use crate::learn::RuleSuggestion;
+use crate::preview;
+use crate::storage::addresses::AddressSuggestion;
+use crate::storage::ops::MessageOp;
+use crate::sync::SyncReport;
+use crate::timefmt::{DisplayTz, format_age, format_timestamp};
+use crate::translate::TranslationView;
+use crate::types::{
+    BodyRecord, BodyStatus, FolderCounts, ListSort, MessageRecord, SyncProgress, now_ts,
+};
+
+#[derive(Clone)]
+pub struct MailItem {
+    pub id: String,
+    pub subject: String,
+    /// Display name (or address when there is none) for list rows.
+    pub from: String,
+    /// `Name <addr>` for the detail pane.
+    pub from_full: String,
+    pub date: String,
+    pub folder: String,
+    pub is_read: bool,
+    pub preview: String,
+    pub body: String,
+    /// Local changes to this message not yet sent to the server.
+    pub queued_ops: usize,
+    /// Sidebar folders holding this message: its own, plus any its Gmail labels put it in.
+    pub folders: Vec<String>,
+    /// Smart folders whose query matches this message.
+    pub smart_folders: Vec<String>,
+    /// Set when the message is in the reply-later queue.
+    pub reply_later: Option<ReplyMark>,
+    /// The message's private note (`n` edits it).
+    pub note: Option<String>,
+    /// Lowercased sender address; keys the sender's badge color.
+    pub sender_key: String,
+    /// User labels (Gmail system labels like `\Inbox` left out), shown as badges.
+    pub labels: Vec<String>,
+    /// Set on the row standing for a group of repeats (see [`collapse_repeats`]).
+    pub repeat: Option<Repeat>,
+    /// The body is a tag-stripped fallback (HTML rendering ran over its budget); `H` renders
+    /// it in full.
+    pub degraded: bool,
+}
+
+/// A run of near-identical messages (same sender, same subject up to numbers and ids, e.g. CI
+/// failure mail) shown as one row; the row is the newest of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Repeat {
+    /// [`repeat_key`] of the group.
+    pub key: String,
+    /// The other messages of the group, newest first; listed under the row when expanded.
+    pub others: Vec<String>,
+    pub expanded: bool,
+}
+
+/// Subject as compared for repeat collapsing: `Re:`/`Fwd:` prefixes dropped, digit runs and
+/// hex ids (commit hashes) turned into `#`, whitespace collapsed, lowercased.
+pub fn repeat_subject(subject: &str) -> String {
+    let mut rest = subject.trim();
+    loop {
+        let lower = rest.to_ascii_lowercase();
+        let Some(prefix) = ["re:", "fwd:", "fw:"]
+            .iter()
+            .find(|prefix| lower.starts_with(**prefix))
+        else {
+            break;
+        };
+        rest = rest[prefix.len()..].trim_start();
+    }
+    rest.split_whitespace()
+        .map(|word| {
+            let core = word.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+            if core.len() >= 7
+                && core.chars().all(|c| c.is_ascii_hexdigit())
+                && core.chars().any(|c| c.is_ascii_digit())
+            {
+                return word.replacen(core, "#", 1).to_lowercase();
+            }
+            let mut out = String::with_capacity(word.len());
+            for c in word.chars().flat_map(char::to_lowercase) {
+                if c.is_ascii_digit() {
+                    if !out.ends_with('#') {
+                        out.push('#');
+                    }
+                } else {
+                    out.push(c);
+                }
+            }
+            out
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// What groups `item` with its repeats: sender address plus [`repeat_subject`].
+pub fn repeat_key(item: &MailItem) -> String {
+    format!("{}\n{}", item.sender_key, repeat_subject(&item.subject))
+}
+
+/// Folds every group of two or more repeats in `items` (newest first) into its newest
+/// message, which keeps its place and carries the group as [`MailItem::repeat`]. Groups whose
+/// key is in `expanded` list the rest of their messages right under that row.
+pub fn collapse_repeats(items: Vec<MailItem>, expanded: &HashSet<String>) -> Vec<MailItem> {
+    let keys: Vec<String> = items.iter().map(repeat_key).collect();
+    let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (idx, key) in keys.iter().enumerate() {
+        groups.entry(key.as_str()).or_default().push(idx);
+    }
+    let mut slots: Vec<Option<MailItem>> = items.into_iter().map(Some).collect();
+    let mut rows = Vec::with_capacity(slots.len());
+    for idx in 0..slots.len() {
+        let members = &groups[keys[idx].as_str()];
+        if members.len() < 2 {
+            rows.extend(slots[idx].take());
+            continue;
+        }
+        if members[0] != idx {
+            continue;
+        }
+        let Some(mut head) = slots[idx].take() else {
+            continue;
+        };
+        let open = expanded.contains(&keys[idx]);
+        head.repeat = Some(Repeat {
+            key: keys[idx].clone(),
+            others: members[1..]
+                .iter()
+                .filter_map(|&m| slots[m].as_ref().map(|item| item.id.clone()))
+                .collect(),
+            expanded: open,
+        });
+        rows.push(head);
+        if open {
+            rows.extend(members[1..].iter().filter_map(|&m| slots[m].take()));
+        }
+    }
+    rows
+}
+
+/// A message's place in the reply-later queue.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplyMark {
+    pub due: Option<NaiveDate>,
+    pub overdue: bool,
+}
+
+/// Folders listed in the sidebar after "All mail": the account's enabled sync folders, then
+/// its smart folders.
+#[derive(Clone, Debug, Default)]
+pub struct Sidebar {
+    pub folders: Vec<String>,
+    pub smart_folders: Vec<String>,
+    /// Sync state of each folder the account syncs (in All Mail mode, All Mail rather than the
+    /// sidebar folders), for the list title.
+    pub sync: BTreeMap<String, FolderSync>,
+    /// Data older than this is flagged stale.
+    pub poll_interval_secs: i64,
+    /// Storage usage (`crate::status::quota_summary`), shown under the folder list.
+    pub quota: Option<String>,
+    /// Server-reported counts per folder; empty while local changes are queued, so the counts
+    /// over the loaded messages show those changes instead.
+    pub counts: BTreeMap<String, FolderCounts>,
+}
+
+/// When a synced folder last completed a sync, and whether its latest run failed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FolderSync {
+    pub last_sync_ts: Option<i64>,
+    /// Error of the latest run when it failed.
+    pub last_error: Option<String>,
+}
+
+/// Freshness shown after a list title; `warn` when the data is stale or the last sync failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyncNote {
+    pub text: String,
+    pub warn: bool,
+}
+
+/// Summarizes `folders` at `now`: the oldest last sync (a folder never synced counts as
+/// stale), older than `poll_interval_secs` flagged as stale, plus the first failed folder.
+pub fn sync_note(folders: &[&FolderSync], now: i64, poll_interval_secs: i64) -> Option<SyncNote> {
+    if folders.is_empty() {
+        return None;
+    }
+    let oldest: Option<Vec<i64>> = folders.iter().map(|f| f.last_sync_ts).collect();
+    let oldest = oldest.and_then(|ts| ts.into_iter().min());
+    let (mut text, mut warn) = match oldest {
+        None => ("never synced".to_string(), true),
+        Some(ts) if now - ts > poll_interval_secs => {
+            (format!("synced {} (stale)", format_age(now - ts)), true)
+        }
+        Some(ts) => (format!("synced {}", format_age(now - ts)), false),
+    };
+    if let Some(error) = folders.iter().find_map(|f| f.last_error.as_deref()) {
+        let error: String = error.chars().take(60).collect();
+        text.push_str(&format!(", last sync failed: {}", error));
+        warn = true;
+    }
+    Some(SyncNote { text, warn })
+}
+
+/// Which messages the list shows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum FolderView {
+    All,
+    /// The reply-later queue, soonest due first.
+    ReplyLater,
+    Folder(String),
+    Smart(String),
+}
+
+impl FolderView {
+    fn contains(&self, item: &MailItem) -> bool {
+        match self {
+            FolderView::All => true,
+            FolderView::ReplyLater => item.reply_later.is_some(),
+            FolderView::Folder(name) => item.folders.contains(name),
+            FolderView::Smart(name) => item.smart_folders.contains(name),
+        }
+    }
+
+    fn label(&self) -> &str {
+        match self {
+            FolderView::All => "All mail",
+            FolderView::ReplyLater => "Reply later",
+            FolderView::Folder(name) | FolderView::Smart(name) => name,
+        }
+    }
+}
+
+pub struct TuiState {
+    pub mail_items: Vec<MailItem>,
+    pub sidebar: Sidebar,
+    pub updates: Option<Receiver<TuiEvent>>,
+    /// Where message actions are sent; `None` keeps the TUI read-only (safe mode).
+    pub actions: Option<UnboundedSender<TuiAction>>,
+    /// Asks the app to reload settings and restart the background sync (`R`).
+    pub reload: Option<UnboundedSender<()>>,
+    /// Open straight into triage mode (`--triage`).
+    pub triage: bool,
+    /// Offline (travel) mode, shared with the app's sync task; toggled with `o`.
+    pub offline: Arc<AtomicBool>,
+    /// Ops queued for the account when the TUI starts.
+    pub queued_ops: usize,
+    /// Color senders and show label badges in the list (`OTTO_TUI_BADGES`); toggled with `b`.
+    pub badges: bool,
+    /// Fold repeated automated messages into one row (`OTTO_TUI_COLLAPSE_REPEATS`); toggled
+    /// with `g`.
+    pub collapse_repeats: bool,
+    /// Initial list order (`OTTO_TUI_SORT`); cycled with `s`.
+    pub sort: ListSort,
+    /// How `T` shows a translation (`OTTO_TRANSLATE_VIEW`).
+    pub translation_view: TranslationView,
+}
+
+/// Label triage's "snooze" decision adds; messages carrying it are set aside, not resurfaced.
+pub const SNOOZE_LABEL: &str = "Otto/Snoozed";
+/// Label triage's "task" decision adds.
+pub const TASK_LABEL: &str = "Otto/Task";
+
+/// Requests from the TUI that need the database (handled by the app on the tokio runtime).
+pub enum TuiAction {
+    Apply {
+        op: MessageOp,
+        message_ids: Vec<String>,
+    },
+    /// Queue messages to reply to later; `due` is the typed due date (empty = none).
+    ReplyLater {
+        message_ids: Vec<String>,
+        due: String,
+    },
+    /// Take messages off the reply-later queue.
+    ReplyDone { message_ids: Vec<String> },
+    /// Set a message's private note; an empty `note` removes it.
+    Note { message_id: String, note: String },
+    /// Translate a message's body (answered with `TuiEvent::Translation`).
+    Translate { message_id: String },
+    /// Render a degraded body in full, with no time budget.
+    Rerender { message_id: String },
+    /// Accept (create the rule) or reject a learned rule suggestion.
+    SettleSuggestion {
+        suggestion: RuleSuggestion,
+        accept: bool,
+    },
+    /// Send a composed message from the account's address (answered with `TuiEvent::Sent`).
+    Send { draft: Draft },
+    /// Look up addresses for a recipient being typed in compose (answered with
+    /// `TuiEvent::RecipientSuggestions`).
+    SuggestRecipients { query: String },
+}
+
+struct App {
+    updates: Option<Receiver<TuiEvent>>,
+    actions: Option<UnboundedSender<TuiAction>>,
+    reload: Option<UnboundedSender<()>>,
+    tabs: Vec<&'static str>,
+    selected_tab: usize,
+    selected_mail: usize,
+    /// Every loaded message; `mail_items` is the part `folder_view` selects.
+    all_items: Vec<MailItem>,
+    mail_items: Vec<MailItem>,
+    sidebar: Sidebar,
+    folder_view: FolderView,
+    /// Message ids toggled with space (kept by id so list reloads don't shift the selection).
+    marked: HashSet<String>,
+    /// Start of a `v` visual range; the range runs to the cursor.
+    visual_anchor: Option<usize>,
+    /// Text being typed after `l` (label), `m` (move), `c` (copy), `L` (reply later) or `n`
+    /// (note).
+    prompt: Option<(Prompt, String)>,
+    /// Active triage pass (`t`), one unread message at a time.
+    triage: Option<Triage>,
+    /// Message being written (`w`); replaces the list while open.
+    compose: Option<Compose>,
+    offline: Arc<AtomicBool>,
+    /// Ops waiting to be sent, across the whole account.
+    queued_ops: usize,
+    badges: bool,
+    collapse_repeats: bool,
+    sort: ListSort,
+    /// Repeat groups opened with Enter, by [`repeat_key`].
+    expanded_repeats: HashSet<String>,
+    /// Translations shown with `T`, by message id (`T` again shows the original).
+    translations: HashMap<String, Translation>,
+    translation_view: TranslationView,
+    /// Learned rules waiting for a yes or no, asked one at a time in the action bar.
+    suggestions: VecDeque<RuleSuggestion>,
+    status: Option<String>,
+    sync_in_progress: bool,
+    sync_stats: SyncStats,
+    spinner_index: usize,
+    last_tick: Instant,
+}
+
+/// One triage pass: the unread messages still to decide on, and a tally of decisions.
+#[derive(Default)]
+struct Triage {
+    /// Messages already decided; kept by id since reloads reorder and restate the list.
+    decided: HashSet<String>,
+    /// Unread messages when the pass started (the progress denominator).
+    total: usize,
+    archived: usize,
+    deleted: usize,
+    kept: usize,
+    snoozed: usize,
+    tasks: usize,
+}
+
+/// Single-key triage decisions.
+#[derive(Clone, Copy, Debug)]
+enum Decision {
+    Archive,
+    Delete,
+    Keep,
+    Snooze,
+    Task,
+}
+
+impl Decision {
+    /// Ops applied to the message. Everything but delete marks it read so it leaves the queue
+    /// on the server too.
+    fn ops(self) -> Vec<MessageOp> {
+        match self {
+            Decision::Archive => vec![MessageOp::MarkRead, MessageOp::Archive],
+            Decision::Delete => vec![MessageOp::Delete],
+            Decision::Keep => vec![MessageOp::MarkRead],
+            Decision::Snooze => vec![
+                MessageOp::MarkRead,
+                MessageOp::AddLabel(SNOOZE_LABEL.to_string()),
+            ],
+            Decision::Task => vec![
+                MessageOp::MarkRead,
+                MessageOp::AddLabel(TASK_LABEL.to_string()),
+            ],
+        }
+    }
+}
+
+impl Triage {
+    fn record(&mut self, id: String, decision: Decision) {
+        self.decided.insert(id);
+        match decision {
+            Decision::Archive => self.archived += 1,
+            Decision::Delete => self.deleted += 1,
+            Decision::Keep => self.kept += 1,
+            Decision::Snooze => self.snoozed += 1,
+            Decision::Task => self.tasks += 1,
+        }
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} archived, {} deleted, {} kept, {} snoozed, {} task(s)",
+            self.archived, self.deleted, self.kept, self.snoozed, self.tasks
+        )
+    }
+}
+
+/// What the action-bar prompt's text becomes on Enter.
+#[derive(Clone, Copy, Debug)]
+enum Prompt {
+    Label,
+    Move,
+    Copy,
+    ReplyLater,
+    Note,
+}
+
+impl Prompt {
+    fn title(self) -> &'static str {
+        match self {
+            Prompt::Label => "Label",
+            Prompt::Move => "Move to folder",
+            Prompt::Copy => "Copy to folder",
+            Prompt::ReplyLater => "Reply later, due (YYYY-MM-DD, +N days, empty = no date)",
+            Prompt::Note => "Note (empty = remove)",
+        }
+    }
+}
+
+/// Aggregated counters for the current background sync, built from `SyncProgress` events.
+#[derive(Default)]
+struct SyncStats {
+    folders_total: usize,
+    folders_done: usize,
+    planned: usize,
+    fetched: usize,
+    written: usize,
+    bytes: u64,
+}
+
+impl SyncStats {
+    fn apply(&mut self, event: &SyncProgress) {
+        match event {
+            SyncProgress::AccountStarted { folders, .. } => self.folders_total += folders,
+            SyncProgress::FolderFinished { .. } => self.folders_done += 1,
+            SyncProgress::UidsPlanned { count, .. } => self.planned += count,
+            SyncProgress::MessagesFetched { count, bytes, .. } => {
+                self.fetched += count;
+                self.bytes += bytes;
+            }
+            SyncProgress::MessagesWritten { count, .. } => self.written += count,
+            SyncProgress::FolderStarted { .. }
+            | SyncProgress::MessagesParsed { .. }
+            | SyncProgress::MessagesUpdated { .. }
+            | SyncProgress::MessagesExpunged { .. }
+            | SyncProgress::AccountFinished { .. } => {}
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut out = format!("{}/{} folders", self.folders_done, self.folders_total);
+        if self.planned > 0 {
+            out.push_str(&format!(
+                " | {}/{} msgs ({} KiB) | {} saved",
+                self.fetched,
+                self.planned,
+                self.bytes / 1024,
+                self.written
+            ));
+        }
+        out
+    }
+}
+
+pub enum TuiEvent {
+    SyncStarted,
+    SyncFinished(SyncReport),
+    Progress(SyncProgress),
+    MailItems(Vec<MailItem>),
+    /// Sync and smart folders after a list refresh.
+    Sidebar(Sidebar),
+    /// Ops still queued for the account after a list refresh.
+    QueuedOps(usize),
+    /// One-line message for the action bar (e.g. settings reloaded).
+    Status(String),
+    /// The account turned out to be in safe mode once loaded: drop the action handler.
+    ReadOnly,
+    /// A message's body translated into `target` (after `T`).
+    Translation {
+        message_id: String,
+        target: String,
+        text: String,
+    },
+    /// Learned rules to ask about (see `crate::learn`).
+    Suggestions(Vec<RuleSuggestion>),
+    /// How a `TuiAction::Send` went; an error keeps the compose view open.
+    Sent(Result<(), String>),
+    /// Ranked addresses for a `TuiAction::SuggestRecipients` query.
+    RecipientSuggestions {
+        query: String,
+        suggestions: Vec<AddressSuggestion>,
+    },
+}
+
+/// A translation on screen, as `TuiEvent::Translation` delivered it.
+struct Translation {
+    target: String,
+    text: String,
+}
+
+const SPINNER_FRAMES: [&str; 4] = ["|", "/", "-", "\\"];
+
+impl App {
+    fn new(state: TuiState) -> Self {
+        let mut app = Self {
+            updates: state.updates,
+            actions: state.actions,
+            reload: state.reload,
+            tabs: vec!["Calendar", "Mail", "Notes", "Projects"],
+            selected_tab: 1, // Mail
+            selected_mail: 0,
+            all_items: state.mail_items,
+            mail_items: Vec::new(),
+            sidebar: state.sidebar,
+            folder_view: FolderView::All,
+            marked: HashSet::new(),
+            visual_anchor: None,
+            prompt: None,
+            triage: None,
+            compose: None,
+            offline: state.offline,
+            queued_ops: state.queued_ops,
+            badges: state.badges,
+            collapse_repeats: state.collapse_repeats,
+            sort: state.sort,
+            expanded_repeats: HashSet::new(),
+            translations: HashMap::new(),
+            suggestions: VecDeque::new(),
+            translation_view: state.translation_view,
+            status: None,
+            sync_in_progress: false,
+            sync_stats: SyncStats::default(),
+            spinner_index: 0,
+            last_tick: Instant::now(),
+        };
+        app.apply_folder_view();
+        if state.triage {
+            app.start_triage();
+        }
+        app
+    }
+
+    /// Freshness of the current view: its folder, or every synced folder for the other views
+    /// (and for sidebar folders that All Mail mode syncs as part of All Mail).
+    fn sync_note(&self) -> Option<SyncNote> {
+        let sync = &self.sidebar.sync;
+        let folders: Vec<&FolderSync> = match &self.folder_view {
+            FolderView::Folder(name) if sync.contains_key(name) => vec![&sync[name]],
+            _ => sync.values().collect(),
+        };
+        sync_note(&folders, now_ts(), self.sidebar.poll_interval_secs)
+    }
+
+    /// "All mail", "Reply later", then sync folders, then smart folders.
+    fn folder_views(&self) -> Vec<FolderView> {
+        [FolderView::All, FolderView::ReplyLater]
+            .into_iter()
+            .chain(self.sidebar.folders.iter().cloned().map(FolderView::Folder))
+            .chain(
+                self.sidebar
+                    .smart_folders
+                    .iter()
+                    .cloned()
+                    .map(FolderView::Smart),
+            )
+            .collect()
+    }
+
+    /// Moves the sidebar selection `step` entries, wrapping around.
+    fn cycle_folder(&mut self, step: isize) {
+        let views = self.folder_views();
+        let current = views
+            .iter()
+            .position(|v| *v == self.folder_view)
+            .unwrap_or(0);
+        let next = (current as isize + step).rem_euclid(views.len() as isize) as usize;
+        self.folder_view = views[next].clone();
+        self.selected_mail = 0;
+        self.clear_selection();
+        self.apply_folder_view();
+    }
+
+    /// The loaded messages the selected folder shows, before repeats are collapsed.
+    fn view_items(&self) -> impl Iterator<Item = &MailItem> + '_ {
+        self.all_items
+            .iter()
+            .filter(|item| self.folder_view.contains(item))
+    }
+
+    /// Rebuilds `mail_items` from `all_items` for the selected folder, keeping the cursor and
+    /// selection in range.
+    fn apply_folder_view(&mut self) {
+        self.mail_items = self.view_items().cloned().collect();
+        if self.folder_view == FolderView::ReplyLater {
+            // Soonest due first, undated last; the sort is stable, so newest first within a day.
+            self.mail_items.sort_by_key(|item| {
+                let due = item.reply_later.as_ref().and_then(|mark| mark.due);
+                (due.is_none(), due)
+            });
+        } else if self.sort != ListSort::Date {
+            // Stable, so each sender's (or subject's) messages stay newest first.
+            let sorter = Sorter::current();
+            self.mail_items.sort_by(|a, b| match self.sort {
+                ListSort::Sender => sorter.compare(&a.from, &b.from),
+                _ => sorter.compare(subject_sort_key(&a.subject), subject_sort_key(&b.subject)),
+            });
+        }
+        if self.collapse_repeats {
+            self.mail_items =
+                collapse_repeats(std::mem::take(&mut self.mail_items), &self.expanded_repeats);
+        }
+        let ids: HashSet<&str> = self.mail_items.iter().map(|m| m.id.as_str()).collect();
+        self.marked.retain(|id| ids.contains(id.as_str()));
+        if self
+            .visual_anchor
+            .is_some_and(|anchor| anchor >= self.mail_items.len())
+        {
+            self.visual_anchor = None;
+        }
+        if self.mail_items.is_empty() {
+            self.selected_mail = 0;
+        } else if self.selected_mail >= self.mail_items.len() {
+            self.selected_mail = self.mail_items.len() - 1;
+        }
+    }
+
+    fn next_mail(&mut self) {
+        if self.mail_items.is_empty() {
+            return;
+        }
+        self.selected_mail = (self.selected_mail + 1).min(self.mail_items.len() - 1);
+    }
+
+    fn prev_mail(&mut self) {
+        if self.mail_items.is_empty() {
+            return;
+        }
+        if self.selected_mail > 0 {
+            self.selected_mail -= 1;
+        }
+    }
+
+    /// Turns repeat collapsing on or off, keeping the cursor on the same message (or the row
+    /// now standing for it).
+    fn toggle_collapse_repeats(&mut self) {
+        self.collapse_repeats = !self.collapse_repeats;
+        let current = self.mail_items.get(self.selected_mail).map(repeat_key);
+        self.apply_folder_view();
+        if let Some(key) = current
+            && let Some(idx) = self.mail_items.iter().position(|m| repeat_key(m) == key)
+        {
+            self.selected_mail = idx;
+        }
+        self.status = Some(if self.collapse_repeats {
+            "Repeated messages collapsed (Enter expands a group)".to_string()
+        } else {
+            "Showing every message".to_string()
+        });
+    }
+
+    /// Switches to the next list order (date, sender, subject), keeping the cursor on the same
+    /// message.
+    fn cycle_sort(&mut self) {
+        self.sort = self.sort.next();
+        let current = self
+            .mail_items
+            .get(self.selected_mail)
+            .map(|m| m.id.clone());
+        self.apply_folder_view();
+        if let Some(id) = current
+            && let Some(idx) = self.mail_items.iter().position(|m| m.id == id)
+        {
+            self.selected_mail = idx;
+        }
+        self.status = Some(format!("Sorted by {}", self.sort.as_str()));
+    }
+
+    /// Expands the repeat group at the cursor, or folds the expanded group it belongs to back
+    /// into its first row.
+    fn toggle_repeat_group(&mut self) {
+        let Some(current) = self.mail_items.get(self.selected_mail) else {
+            return;
+        };
+        let key = repeat_key(current);
+        if !self.expanded_repeats.remove(&key) {
+            if current.repeat.is_none() {
+                return;
+            }
+            self.expanded_repeats.insert(key.clone());
+        }
+        self.apply_folder_view();
+        if let Some(idx) = self
+            .mail_items
+            .iter()
+            .position(|m| m.repeat.as_ref().is_some_and(|r| r.key == key))
+        {
+            self.selected_mail = idx;
+        }
+    }
+
+    fn toggle_mark(&mut self) {
+        if let Some(item) = self.mail_items.get(self.selected_mail)
+            && !self.marked.remove(&item.id)
+        {
+            self.marked.insert(item.id.clone());
+        }
+    }
+
+    /// Starts a visual range at the cursor, or folds the active range into the marked set.
+    fn toggle_visual(&mut self) {
+        match self.visual_anchor.take() {
+            Some(_) => {
+                let ids: Vec<String> = self.visual_range_ids().collect();
+                self.marked.extend(ids);
+            }
+            None if !self.mail_items.is_empty() => self.visual_anchor = Some(self.selected_mail),
+            None => {}
+        }
+    }
+
+    fn clear_selection(&mut self) {
+        self.marked.clear();
+        self.visual_anchor = None;
+    }
+
+    fn visual_range_ids(&self) -> impl Iterator<Item = String> + '_ {
+        let range = self
+            .visual_anchor
+            .map(|anchor| anchor.min(self.selected_mail)..=anchor.max(self.selected_mail));
+        self.mail_items
+            .iter()
+            .enumerate()
+            .filter(move |(idx, _)| range.as_ref().is_some_and(|r| r.contains(idx)))
+            .map(|(_, item)| item.id.clone())
+    }
+
+    fn is_selected(&self, idx: usize, item: &MailItem) -> bool {
+        self.marked.contains(&item.id)
+            || self.visual_anchor.is_some_and(|anchor| {
+                (anchor.min(self.selected_mail)..=anchor.max(self.selected_mail)).contains(&idx)
+            })
+    }
+
+    /// Marked + visual-range messages, or just the cursor message when nothing is selected. A
+    /// collapsed repeat row stands for its whole group.
+    fn target_ids(&self) -> Vec<String> {
+        let mut rows: Vec<&MailItem> = self
+            .mail_items
+            .iter()
+            .enumerate()
+            .filter(|(idx, item)| self.is_selected(*idx, item))
+            .map(|(_, item)| item)
+            .collect();
+        if rows.is_empty()
+            && let Some(item) = self.mail_items.get(self.selected_mail)
+        {
+            rows.push(item);
+        }
+        let mut ids = Vec::new();
+        for row in rows {
+            ids.push(row.id.clone());
+            if let Some(repeat) = row.repeat.as_ref().filter(|r| !r.expanded) {
+                ids.extend(repeat.others.iter().cloned());
+            }
+        }
+        ids
+    }
+
+    fn request_reload(&mut self) {
+        let sent = self.reload.as_ref().is_some_and(|tx| tx.send(()).is_ok());
+        self.status = Some(if sent {
+            "Reloading settings…".to_string()
+        } else {
+            "Settings reload is unavailable".to_string()
+        });
+    }
+
+    /// Flips offline mode. Going back online asks for a reload, which starts the sync pass that
+    /// sends everything queued meanwhile.
+    fn toggle_offline(&mut self) {
+        let offline = !self.offline.load(Ordering::SeqCst);
+        self.offline.store(offline, Ordering::SeqCst);
+        if offline {
+            self.status = Some("Offline: sync paused, changes queue locally".to_string());
+            return;
+        }
+        let sent = self.reload.as_ref().is_some_and(|tx| tx.send(()).is_ok());
+        self.status = Some(if sent {
+            format!("Back online: sending {} queued change(s)…", self.queued_ops)
+        } else {
+            "Back online; sync is unavailable in this session".to_string()
+        });
+    }
+
+    fn dispatch(&mut self, op: MessageOp) {
+        let message_ids = self.target_ids();
+        if message_ids.is_empty() {
+            return;
+        }
+        let count = message_ids.len();
+        let kind = op.kind();
+        if self.send_op(op, message_ids) {
+            self.status = Some(format!("Queued {} for {} message(s)", kind, count));
+            self.clear_selection();
+        }
+    }
+
+    /// Hands `op` to the action handler; sets the status and returns false if it can't.
+    fn send_op(&mut self, op: MessageOp, message_ids: Vec<String>) -> bool {
+        self.send_action(TuiAction::Apply { op, message_ids })
+    }
+
+    fn send_action(&mut self, action: TuiAction) -> bool {
+        let Some(actions) = self.actions.as_ref() else {
+            self.status = Some("Safe mode: message actions are disabled".to_string());
+            return false;
+        };
+        if actions.send(action).is_ok() {
+            true
+        } else {
+            self.status = Some("Action handler stopped; nothing queued".to_string());
+            false
+        }
+    }
+
+    /// Adds the target messages to the reply-later queue (or changes their due date); the app
+    /// parses `due` and reports back in the status line.
+    fn reply_later(&mut self, due: String) {
+        let message_ids = self.target_ids();
+        if !message_ids.is_empty() && self.send_action(TuiAction::ReplyLater { message_ids, due }) {
+            self.clear_selection();
+        }
+    }
+
+    /// Opens the note prompt on the current message, starting from its note.
+    fn edit_note(&mut self) {
+        let Some(current) = self.mail_items.get(self.selected_mail) else {
+            return;
+        };
+        let note = current.note.clone().unwrap_or_default();
+        self.prompt = Some((Prompt::Note, note));
+    }
+
+    fn save_note(&mut self, note: String) {
+        let Some(current) = self.mail_items.get(self.selected_mail) else {
+            return;
+        };
+        let message_id = current.id.clone();
+        if current.note.is_none() && note.is_empty() {
+            return;
+        }
+        self.send_action(TuiAction::Note { message_id, note });
+    }
+
+    /// Asks for a translation of the current message, or goes back to its original when a
+    /// translation is showing.
+    fn toggle_translation(&mut self) {
+        let Some(current) = self.mail_items.get(self.selected_mail) else {
+            return;
+        };
+        let message_id = current.id.clone();
+        if self.translations.remove(&message_id).is_some() {
+            return;
+        }
+        if self.send_action(TuiAction::Translate { message_id }) {
+            self.status = Some("Translating...".to_string());
+        }
+    }
+
+    /// Asks for a full rendering of the current message when its body is degraded.
+    fn rerender(&mut self) {
+        let Some(current) = self.mail_items.get(self.selected_mail) else {
+            return;
+        };
+        if !current.degraded {
+            self.status = Some("Body is already fully rendered".to_string());
+            return;
+        }
+        let message_id = current.id.clone();
+        if self.send_action(TuiAction::Rerender { message_id }) {
+            self.status = Some("Rendering...".to_string());
+        }
+    }
+
+    /// Answers the learned rule suggestion on screen; `None` leaves it for later (it stays
+    /// pending for `otto suggestions` and the next start).
+    fn settle_suggestion(&mut self, accept: Option<bool>) {
+        let Some(suggestion) = self.suggestions.pop_front() else {
+            return;
+        };
+        let Some(accept) = accept else {
+            return;
+        };
+        let rule = suggestion.rule_name();
+        if self.send_action(TuiAction::SettleSuggestion { suggestion, accept }) && !accept {
+            self.status = Some(format!("Not creating {}", rule));
+        }
+    }
+
+    fn reply_done(&mut self) {
+        let message_ids: Vec<String> = self
+            .target_ids()
+            .into_iter()
+            .filter(|id| {
+                self.all_items
+                    .iter()
+                    .any(|m| &m.id == id && m.reply_later.is_some())
+            })
+            .collect();
+        if message_ids.is_empty() {
+            self.status = Some("Not in the reply-later queue".to_string());
+            return;
+        }
+        let count = message_ids.len();
+        if self.send_action(TuiAction::ReplyDone { message_ids }) {
+            self.status = Some(format!("Replied: {} message(s) off the queue", count));
+            self.clear_selection();
+        }
+    }
+
+    fn start_compose(&mut self) {
+        if self.actions.is_none() {
+            self.status = Some("Safe mode: sending is disabled".to_string());
+            return;
+        }
+        self.compose = Some(Compose::new());
+    }
+
+    fn compose_key(&mut self, key: KeyEvent) {
+        let Some(compose) = self.compose.as_mut() else {
+            return;
+        };
+        match compose.handle_key(key) {
+            ComposeOutcome::Stay => {}
+            ComposeOutcome::Discard => {
+                self.compose = None;
+                self.status = Some("Draft discarded".to_string());
+            }
+            ComposeOutcome::Suggest(query) => {
+                self.send_action(TuiAction::SuggestRecipients { query });
+            }
+            ComposeOutcome::Send(draft) => {
+                if !self.send_action(TuiAction::Send { draft }) {
+                    let error = self.status.take().unwrap_or_default();
+                    if let Some(compose) = self.compose.as_mut() {
+                        compose.send_failed(error);
+                    }
+                }
+            }
+        }
+    }
+
+    fn start_triage(&mut self) {
+        let total = self.view_items().filter(|m| !m.is_read).count();
+        self.clear_selection();
+        self.triage = Some(Triage {
+            total,
+            ..Default::default()
+        });
+    }
+
+    /// The next unread message the triage pass hasn't decided on.
+    fn triage_current(&self) -> Option<&MailItem> {
+        let triage = self.triage.as_ref()?;
+        self.view_items()
+            .find(|m| !m.is_read && !triage.decided.contains(&m.id))
+    }
+
+    fn decide(&mut self, decision: Decision) {
+        let Some(id) = self.triage_current().map(|m| m.id.clone()) else {
+            return;
+        };
+        for op in decision.ops() {
+            if !self.send_op(op, vec![id.clone()]) {
+                return;
+            }
+        }
+        if let Some(triage) = self.triage.as_mut() {
+            triage.record(id, decision);
+        }
+    }
+
+    fn stop_triage(&mut self) {
+        if let Some(triage) = self.triage.take() {
+            self.status = Some(format!("Triage: {}", triage.summary()));
+        }
+    }
+
+    fn drain_updates(&mut self) {
+        if let Some(rx) = self.updates.take() {
+            while let Ok(event) = rx.try_recv() {
+                self.apply_event(event);
+            }
+            self.updates = Some(rx);
+        }
+    }
+
+    fn apply_event(&mut self, event: TuiEvent) {
+        match event {
+            TuiEvent::SyncStarted => {
+                self.sync_in_progress = true;
+                self.sync_stats = SyncStats::default();
+            }
+            TuiEvent::SyncFinished(report) => {
+                self.sync_in_progress = false;
+                // Failures stay visible until the next status message; clean passes say nothing.
+                if !report.is_ok() {
+                    let mut status = format!("Sync problems: {}", report.summary());
+                    if let Some(first) = report.problems().first() {
+                        status.push_str(&format!(" | {}", first));
+                    }
+                    self.status = Some(status);
+                }
+            }
+            TuiEvent::Progress(progress) => {
+                self.sync_stats.apply(&progress);
+            }
+            TuiEvent::QueuedOps(count) => {
+                self.queued_ops = count;
+            }
+            TuiEvent::Status(status) => {
+                self.status = Some(status);
+            }
+            TuiEvent::MailItems(items) => {
+                self.all_items = items;
+                self.apply_folder_view();
+                // Triage opened before the list loaded (lazy startup) counts from the first list.
+                if let Some(triage) = self.triage.as_mut()
+                    && triage.total == 0
+                    && triage.decided.is_empty()
+                {
+                    triage.total = self
+                        .all_items
+                        .iter()
+                        .filter(|m| self.folder_view.contains(m) && !m.is_read)
+                        .count();
+                }
+            }
+            TuiEvent::ReadOnly => {
+                self.actions = None;
+            }
+            TuiEvent::Translation {
+                message_id,
+                target,
+                text,
+            } => {
+                self.status = Some(format!("Translated to {} ([T] shows the original)", target));
+                self.translations
+                    .insert(message_id, Translation { target, text });
+            }
+            TuiEvent::Sent(Ok(())) => {
+                self.compose = None;
+                self.status = Some("Message sent".to_string());
+            }
+            TuiEvent::Sent(Err(error)) => match self.compose.as_mut() {
+                Some(compose) => compose.send_failed(error),
+                None => self.status = Some(error),
+            },
+            TuiEvent::RecipientSuggestions { query, suggestions } => {
+                if let Some(compose) = self.compose.as_mut() {
+                    compose.set_suggestions(&query, suggestions);
+                }
+            }
+            TuiEvent::Suggestions(suggestions) => {
+                for suggestion in suggestions {
+                    if !self.suggestions.contains(&suggestion) {
+                        self.suggestions.push_back(suggestion);
+                    }
+                }
+            }
+            TuiEvent::Sidebar(sidebar) => {
+                self.sidebar = sidebar;
+                // A removed (or renamed) folder falls back to the whole list.
+                if !self.folder_views().contains(&self.folder_view) {
+                    self.folder_view = FolderView::All;
+                    self.apply_folder_view();
+                }
+            }
+        }
+    }
+
+    fn advance_spinner(&mut self) {
+        if self.sync_in_progress {
+            self.spinner_index = (self.spinner_index + 1) % SPINNER_FRAMES.len();
+        }
+    }
+
+    fn spinner_frame(&self) -> &str {
+        SPINNER_FRAMES[self.spinner_index % SPINNER_FRAMES.len()]
+    }
+}
+
+pub fn run(state: TuiState) -> Result<()> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    crossterm::execute!(
+        stdout,
+        crossterm::terminal::EnterAlternateScreen,
+        crossterm::event::EnableMouseCapture
+    )?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+
+    let res = run_app(&mut terminal, state);
+
+    disable_raw_mode()?;
+    crossterm::execute!(
+        terminal.backend_mut(),
+        crossterm::event::DisableMouseCapture,
+        crossterm::terminal::LeaveAlternateScreen
+    )?;
+    terminal.show_cursor()?;
+
+    res
+}
+
+fn run_app<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    state: TuiState,
+) -> Result<()> {
+    let mut app = App::new(state);
+    let tick_rate = Duration::from_millis(200);
+
+    loop {
+        app.drain_updates();
+        terminal.draw(|f| draw(f, &app))?;
+
+        let timeout = tick_rate
+            .checked_sub(app.last_tick.elapsed())
+            .unwrap_or_else(|| Duration::from_secs(0));
+
+        if crossterm::event::poll(timeout)?
+            && let Event::Key(key) = event::read()?
+            && handle_key(&mut app, key)?
+        {
+            break;
+        }
+
+        if app.last_tick.elapsed() >= tick_rate {
+            app.last_tick = Instant::now();
+            app.advance_spinner();
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_key(app: &mut App, key: KeyEvent) -> Result<bool> {
+    if app.compose.is_some() {
+        app.compose_key(key);
+        return Ok(false);
+    }
+
+    if let Some((prompt, input)) = app.prompt.as_mut() {
+        match key.code {
+            KeyCode::Enter => {
+                let value = input.trim().to_string();
+                let prompt = *prompt;
+                app.prompt = None;
+                match prompt {
+                    Prompt::ReplyLater => app.reply_later(value),
+                    Prompt::Note => app.save_note(value),
+                    _ if value.is_empty() => {}
+                    Prompt::Label => app.dispatch(MessageOp::AddLabel(value)),
+                    Prompt::Move => app.dispatch(MessageOp::Move(value)),
+                    Prompt::Copy => app.dispatch(MessageOp::Copy(value)),
+                }
+            }
+            KeyCode::Esc => app.prompt = None,
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) => input.push(c),
+            _ => {}
+        }
+        return Ok(false);
+    }
+
+    if app.triage.is_some() {
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => return Ok(true),
+            (KeyCode::Char('q'), _) | (KeyCode::Esc, _) => app.stop_triage(),
+            (KeyCode::Char('a'), _) => app.decide(Decision::Archive),
+            (KeyCode::Char('d'), _) => app.decide(Decision::Delete),
+            (KeyCode::Char('k'), _) => app.decide(Decision::Keep),
+            (KeyCode::Char('s'), _) => app.decide(Decision::Snooze),
+            (KeyCode::Char('t'), _) => app.decide(Decision::Task),
+            _ => {}
+        }
+        return Ok(false);
+    }
+
+    // A learned rule question takes y/n/Esc; every other key works as usual meanwhile.
+    if !app.suggestions.is_empty() {
+        let answer = match key.code {
+            KeyCode::Char('y') => Some(Some(true)),
+            KeyCode::Char('n') => Some(Some(false)),
+            KeyCode::Esc => Some(None),
+            _ => None,
+        };
+        if let Some(answer) = answer {
+            app.settle_suggestion(answer);
+            return Ok(false);
+        }
+    }
+
+    match (key.code, key.modifiers) {
+        (KeyCode::Char('q'), _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+            return Ok(true);
+        }
+        (KeyCode::Down, _) | (KeyCode::Char('j'), _) => {
+            app.next_mail();
+        }
+        (KeyCode::Up, _) | (KeyCode::Char('k'), _) => {
+            app.prev_mail();
+        }
+        (KeyCode::Left, _) if app.selected_tab > 0 => {
+            app.selected_tab -= 1;
+        }
+        (KeyCode::Right, _) if app.selected_tab + 1 < app.tabs.len() => {
+            app.selected_tab += 1;
+        }
+        (KeyCode::Tab, _) => app.cycle_folder(1),
+        (KeyCode::BackTab, _) => app.cycle_folder(-1),
+        (KeyCode::Char(' '), _) => app.toggle_mark(),
+        (KeyCode::Char('v'), _) => app.toggle_visual(),
+        (KeyCode::Esc, _) => app.clear_selection(),
+        (KeyCode::Char('a'), _) => app.dispatch(MessageOp::Archive),
+        (KeyCode::Char('d'), _) => app.dispatch(MessageOp::Delete),
+        (KeyCode::Char('r'), _) => app.dispatch(MessageOp::MarkRead),
+        (KeyCode::Char('l'), _) => app.prompt = Some((Prompt::Label, String::new())),
+        (KeyCode::Char('m'), _) => app.prompt = Some((Prompt::Move, String::new())),
+        (KeyCode::Char('c'), _) => app.prompt = Some((Prompt::Copy, String::new())),
+        (KeyCode::Char('L'), _) => app.prompt = Some((Prompt::ReplyLater, String::new())),
+        (KeyCode::Char('x'), _) => app.reply_done(),
+        (KeyCode::Char('n'), _) => app.edit_note(),
+        (KeyCode::Char('T'), _) => app.toggle_translation(),
+        (KeyCode::Char('H'), _) => app.rerender(),
+        (KeyCode::Char('R'), _) => app.request_reload(),
+        (KeyCode::Char('t'), _) => app.start_triage(),
+        (KeyCode::Char('w'), _) => app.start_compose(),
+        (KeyCode::Char('o'), _) => app.toggle_offline(),
+        (KeyCode::Char('b'), _) => app.badges = !app.badges,
+        (KeyCode::Char('g'), _) => app.toggle_collapse_repeats(),
+        (KeyCode::Char('s'), _) => app.cycle_sort(),
+        (KeyCode::Enter, _) => app.toggle_repeat_group(),
+        _ => {}
+    }
+    Ok(false)
+}
+
+fn draw(f: &mut ratatui::Frame, app: &App) {
+    let size = f.area();
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(size);
+
+    draw_top_bar(f, app, chunks[0]);
+    draw_body(f, app, chunks[1]);
+}
+
+fn draw_top_bar(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let titles: Vec<Line> = app.tabs.iter().map(|t| Line::from(Span::raw(*t))).collect();
+
+    let mut title_text = if app.offline.load(Ordering::SeqCst) {
+        "Otto | OFFLINE".to_string()
+    } else if app.sync_in_progress {
+        format!(
+            "Otto | Syncing {} {}",
+            app.spinner_frame(),
+            app.sync_stats.summary()
+        )
+    } else {
+        "Otto".to_string()
+    };
+    if app.queued_ops > 0 {
+        title_text.push_str(&format!(" | {} change(s) queued", app.queued_ops));
+    }
+
+    let tabs = Tabs::new(titles)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(Line::from(title_text)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .select(app.selected_tab);
+
+    f.render_widget(tabs, area);
+}
+
+fn draw_body(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
+        .split(area);
+
+    draw_mail_area(f, app, chunks[0]);
+    draw_agent_panel(f, chunks[1]);
+}
+
+fn draw_mail_area(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    if let Some(compose) = &app.compose {
+        draw_compose(f, compose, area);
+        return;
+    }
+    if let Some(triage) = &app.triage {
+        draw_triage(f, app, triage, area);
+        return;
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(5),    // list + detail
+                Constraint::Length(3), // action bar
+            ]
+            .as_ref(),
+        )
+        .split(area);
+
+    let inner = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(20),
+                Constraint::Percentage(30),
+                Constraint::Percentage(50),
+            ]
+            .as_ref(),
+        )
+        .split(chunks[0]);
+
+    draw_sidebar(f, app, inner[0]);
+    draw_mail_list(f, app, inner[1]);
+    draw_mail_detail(f, app, inner[2]);
+    draw_action_bar(f, app, chunks[1]);
+}
+
+/// Folder list with unread/total counts, as the server reported them when known, else over the
+/// loaded messages; smart folders are marked `*`.
+fn draw_sidebar(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let views = app.folder_views();
+    let items: Vec<ListItem> = views
+        .iter()
+        .map(|view| {
+            let server = match view {
+                FolderView::Folder(name) => app.sidebar.counts.get(name),
+                _ => None,
+            };
+            let (unread, total) = match server {
+                Some(counts) => (counts.unseen as usize, counts.messages as usize),
+                None => app
+                    .all_items
+                    .iter()
+                    .filter(|item| view.contains(item))
+                    .fold((0, 0), |(unread, total), item| {
+                        (unread + usize::from(!item.is_read), total + 1)
+                    }),
+            };
+            let marker = if matches!(view, FolderView::Smart(_)) {
+                "*"
+            } else {
+                " "
+            };
+            ListItem::new(Line::from(format!(
+                "{}{} ({}/{})",
+                marker,
+                view.label(),
+                unread,
+                total
+            )))
+        })
+        .collect();
+
+    let mut state = ratatui::widgets::ListState::default();
+    state.select(views.iter().position(|v| *v == app.folder_view));
+    let mut block = Block::default().borders(Borders::ALL).title("Folders");
+    if let Some(quota) = &app.sidebar.quota {
+        block = block.title_bottom(Line::from(quota.as_str()));
+    }
+    let list = List::new(items)
+        .block(block)
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+fn draw_mail_list(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .mail_items
+        .iter()
+        .enumerate()
+        .map(|(idx, m)| {
+            let status = if m.is_read { "R" } else { "U" };
+            let mark = if app.is_selected(idx, m) { "*" } else { " " };
+            // ↑ = local changes not yet on the server.
+            let queued = if m.queued_ops > 0 { "↑" } else { " " };
+            // ↩ = reply later, ! = past its due date.
+            let reply = match &m.reply_later {
+                Some(mark) if mark.overdue => "!",
+                Some(_) => "↩",
+                None => " ",
+            };
+            let prefix = format!("{}[{}]{}{} ", mark, status, queued, reply);
+            // ×N = a collapsed group of N repeats (▾N once expanded).
+            let subject = match &m.repeat {
+                Some(repeat) => format!(
+                    "{} {}{}",
+                    m.subject,
+                    if repeat.expanded { "▾" } else { "×" },
+                    repeat.others.len() + 1
+                ),
+                None => m.subject.clone(),
+            };
+            if !app.badges {
+                return ListItem::new(Line::from(format!("{}{} — {}", prefix, m.from, subject)));
+            }
+            let mut spans = vec![
+                Span::raw(prefix),
+                Span::styled(
+                    m.from.clone(),
+                    Style::default().fg(badge_color(&m.sender_key)),
+                ),
+            ];
+            for label in &m.labels {
+                spans.push(Span::raw(" "));
+                spans.push(Span::styled(
+                    badge_text(label),
+                    Style::default().fg(Color::Black).bg(badge_color(label)),
+                ));
+            }
+            spans.push(Span::raw(format!(" — {}", subject)));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let mut title = vec![Span::raw(app.folder_view.label().to_string())];
+    if app.sort != ListSort::Date && app.folder_view != FolderView::ReplyLater {
+        title.push(Span::raw(format!(" · by {}", app.sort.as_str())));
+    }
+    if let Some(note) = app.sync_note() {
+        let style = if note.warn {
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        title.push(Span::styled(format!(" · {}", note.text), style));
+    }
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(Line::from(title)),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("▶ ");
+
+    f.render_stateful_widget(list, area, &mut make_list_state(app));
+}
+
+/// Colors badges cycle through: the terminal's own palette, minus black, white and gray, so they
+/// read on both dark and light themes.
+const BADGE_COLORS: [Color; 12] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+];
+
+/// Deterministic color for a sender address or label: the same (case-insensitive) key gets the
+/// same color in every session.
+pub fn badge_color(key: &str) -> Color {
+    // FNV-1a: stable across runs and Rust releases, unlike `DefaultHasher`.
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte.to_ascii_lowercase())).wrapping_mul(0x0100_0000_01b3)
+    });
+    BADGE_COLORS[(hash % BADGE_COLORS.len() as u64) as usize]
+}
+
+/// Compact badge: the last path segment of a nested label, cut to 12 characters.
+fn badge_text(label: &str) -> String {
+    let name = label.rsplit('/').next().unwrap_or(label);
+    let mut text: String = name.chars().take(12).collect();
+    if name.chars().count() > 12 {
+        text.push('…');
+    }
+    format!(" {} ", text)
+}
+
+fn make_list_state(app: &App) -> ratatui::widgets::ListState {
+    let mut state = ratatui::widgets::ListState::default();
+    if !app.mail_items.is_empty() {
+        state.select(Some(app.selected_mail));
+    }
+    state
+}
+
+fn draw_mail_detail(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let translation = app
+        .mail_items
+        .get(app.selected_mail)
+        .and_then(|current| app.translations.get(&current.id));
+    let mut title = "Body".to_string();
+    let content = if app.mail_items.is_empty() && !app.all_items.is_empty() {
+        format!("No loaded messages in {}.", app.folder_view.label())
+    } else if app.mail_items.is_empty() {
+        "No messages loaded yet.\n\nRun sync first to populate the cache.".to_string()
+    } else {
+        let current = &app.mail_items[app.selected_mail];
+        let queued = if current.queued_ops > 0 {
+            format!("Queued: {} change(s) not sent yet\n", current.queued_ops)
+        } else {
+            String::new()
+        };
+        let reply = match &current.reply_later {
+            Some(ReplyMark { due: None, .. }) => "Reply later: no due date\n".to_string(),
+            Some(ReplyMark {
+                due: Some(due),
+                overdue,
+            }) => format!(
+                "Reply later: due {}{}\n",
+                due,
+                if *overdue { " (overdue)" } else { "" }
+            ),
+            None => String::new(),
+        };
+        let note = current
+            .note
+            .as_deref()
+            .map(|note| format!("Note: {}\n", note))
+            .unwrap_or_default();
+        let repeats = match &current.repeat {
+            Some(repeat) if repeat.expanded => format!(
+                "Repeats: newest of {} similar messages (Enter collapses)\n",
+                repeat.others.len() + 1
+            ),
+            Some(repeat) => format!(
+                "Repeats: {} more like this hidden; actions apply to all (Enter expands)\n",
+                repeat.others.len()
+            ),
+            None => String::new(),
+        };
+        let degraded = if current.degraded {
+            "Simplified: the HTML took too long to render ([H] renders it in full)\n"
+        } else {
+            ""
+        };
+        let body = match translation {
+            Some(translation) if app.translation_view == TranslationView::Inline => {
+                title = format!("Body (translated to {})", translation.target);
+                &translation.text
+            }
+            _ => &current.body,
+        };
+        format!(
+            "From: {}\nFolder: {}\nDate: {}\n{}{}{}{}{}\n{}",
+            current.from_full,
+            current.folder,
+            current.date,
+            queued,
+            reply,
+            note,
+            repeats,
+            degraded,
+            body
+        )
+    };
+
+    let area = match translation {
+        Some(translation) if app.translation_view == TranslationView::Side => {
+            let halves = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(area);
+            let side = Paragraph::new(translation.text.as_str())
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!("Translation ({})", translation.target)),
+                )
+                .wrap(ratatui::widgets::Wrap { trim: true });
+            f.render_widget(side, halves[1]);
+            halves[0]
+        }
+        _ => area,
+    };
+    let paragraph = Paragraph::new(content)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(ratatui::widgets::Wrap { trim: true });
+
+    f.render_widget(paragraph, area);
+}
+
+fn draw_triage(f: &mut ratatui::Frame, app: &App, triage: &Triage, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(5), Constraint::Length(3)].as_ref())
+        .split(area);
+
+    let done = triage.decided.len();
+    let (title, content) = match app.triage_current() {
+        Some(current) => (
+            format!("Triage {}/{}", done + 1, triage.total.max(done + 1)),
+            format!(
+                "From: {}\nSubject: {}\nFolder: {}\nDate: {}\n\n{}",
+                current.from_full, current.subject, current.folder, current.date, current.body
+            ),
+        ),
+        None => (
+            "Triage".to_string(),
+            format!(
+                "No unread messages left.\n\n{}\n\nPress q to go back to the list.",
+                triage.summary()
+            ),
+        ),
+    };
+    let paragraph = Paragraph::new(content)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(ratatui::widgets::Wrap { trim: true });
+    f.render_widget(paragraph, chunks[0]);
+
+    let mut spans = Vec::new();
+    if let Some(status) = &app.status {
+        spans.push(Span::raw(format!("{}  ", status)));
+    }
+    spans.push(Span::raw(
+        "[a]rchive [d]elete [k]eep [s]nooze [t]ask  [q/Esc] leave triage",
+    ));
+    let bar = Paragraph::new(Line::from(spans))
+        .block(Block::default().borders(Borders::ALL).title("Actions"));
+    f.render_widget(bar, chunks[1]);
+}
+
+fn draw_action_bar(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let line = if let Some((prompt, input)) = &app.prompt {
+        Line::from(format!(
+            "{}: {}_  [Enter] apply  [Esc] cancel",
+            prompt.title(),
+            input
+        ))
+    } else if let Some(suggestion) = app.suggestions.front() {
+        Line::from(format!(
+            "{}  [y] create rule  [n] no  [Esc] ask later",
+            suggestion.question()
+        ))
+    } else if let Some(status) = &app.status {
+        Line::from(vec![
+            Span::raw(format!("{}  ", status)),
+            Span::raw(
+                "[space/v] select  [a]rchive [d]elete [r]ead [l]abel [m]ove [c]opy [L]ater [x] done [n]ote [T]ranslate [w]rite  [q] quit",
+            ),
+        ])
+    } else {
+        Line::from(vec![
+            Span::raw("[j/k] move  "),
+            Span::raw("[Tab] folder  "),
+            Span::raw("[space/v] select  "),
+            Span::raw("[a]rchive [d]elete [r]ead [l]abel [m]ove [c]opy  "),
+            Span::raw("[L] reply later [x] replied  "),
+            Span::raw("[n]ote  "),
+            Span::raw("[T]ranslate  "),
+            Span::raw("[t]riage  "),
+            Span::raw("[w]rite  "),
+            Span::raw("[←/→] switch tab  "),
+            Span::raw("[R] reload  "),
+            Span::raw("[o]ffline  "),
+            Span::raw("[b]adges  "),
+            Span::raw("[g]roup repeats  "),
+            Span::raw("[s]ort  "),
+            Span::raw("[q] quit"),
+        ])
+    };
+
+    let paragraph =
+        Paragraph::new(line).block(Block::default().borders(Borders::ALL).title("Actions"));
+
+    f.render_widget(paragraph, area);
+}
+
+fn draw_agent_panel(f: &mut ratatui::Frame, area: Rect) {
+    let text = "Agent chat (future)\n\nThis panel will host conversations with coding agents (Codex, Claude, etc). For now it is a read-only placeholder.";
+    let paragraph =
+        Paragraph::new(text).block(Block::default().borders(Borders::ALL).title("Agent"));
+    f.render_widget(paragraph, area);
+}
+
+/// Body placeholder for messages skipped by `max_message_bytes`; empty for everything else.
+fn too_large_marker(msg: &MessageRecord) -> String {
+    if msg.body_status != BodyStatus::TooLarge {
+        return String::new();
+    }
+    let size = msg.size_bytes.map_or_else(
+        || "unknown size".to_string(),
+        |bytes| format!("{:.1} MB", f64::from(bytes) / (1024.0 * 1024.0)),
+    );
+    format!(
+        "[Body not fetched (too large, {}). Run `otto fetch-bodies {}` to download it.]",
+        size, msg.id
+    )
+}
+
+pub fn build_mail_items(
+    messages: &[(MessageRecord, Option<BodyRecord>)],
+    tz: DisplayTz,
+) -> Vec<MailItem> {
+    messages
+        .iter()
+        .map(|(msg, body)| {
+            let date = format_timestamp(msg.internal_date, tz);
+
+            let from = friendly_from(msg.from_name.as_deref(), msg.from.as_deref());
+            let from_full = full_from(msg.from_name.as_deref(), msg.from.as_deref());
+            let subject = msg
+                .subject
+                .clone()
+                .unwrap_or_else(|| "(No Subject)".to_string());
+            let is_read = msg.flags.iter().any(|f| f.eq("Seen") || f.eq("\\Seen"));
+
+            let body_text = body
+                .as_ref()
+                .and_then(|b| b.sanitized_text.as_deref())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| too_large_marker(msg));
+
+            let preview = preview::preview_text(preview::default_source(), &body_text, None, 1);
+
+            MailItem {
+                id: msg.id.clone(),
+                subject,
+                from,
+                from_full,
+                date,
+                folder: msg.folder.clone(),
+                is_read,
+                preview,
+                body: body_text,
+                queued_ops: 0,
+                folders: vec![msg.folder.clone()],
+                smart_folders: Vec::new(),
+                reply_later: None,
+                note: None,
+                sender_key: msg
+                    .from
+                    .as_deref()
+                    .map(|raw| parse_mailbox(raw).map_or_else(|| raw.to_string(), |m| m.addr))
+                    .unwrap_or_default()
+                    .to_lowercase(),
+                labels: msg
+                    .labels
+                    .iter()
+                    .filter(|label| !label.starts_with('\\'))
+                    .cloned()
+                    .collect(),
+                repeat: None,
+                degraded: body.as_ref().is_some_and(|b| b.degraded),
+            }
+        })
+        .collect()
+}
@@ -1,9 +1,57 @@
+use anyhow::{Result, bail};
 use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+use crate::cleanup::CleanupRule;
+use crate::notify::NotifyRule;
+use crate::smart_folders::SmartFolder;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum)]
 pub enum Provider {
+    /// Gmail and Google Workspace: Google OAuth, Gmail IMAP extensions.
+    #[value(name = "gmail")]
     GmailImap,
+    /// Microsoft 365 and Outlook.com: Microsoft identity platform OAuth, plain IMAP where
+    /// copies across folders are matched by Message-ID.
+    #[value(name = "outlook")]
+    OutlookImap,
+    /// Any other IMAP server, signed in with a password: no OAuth, no Gmail extensions and no
+    /// Gmail SMTP. Password accounts get it unless they point at Gmail's server.
+    #[value(skip)]
+    Imap,
+}
+
+impl Provider {
+    /// IMAP server new accounts of this provider connect to unless `OTTO_IMAP_HOST` says
+    /// otherwise; generic IMAP has none (empty host).
+    pub fn imap_endpoint(&self) -> ImapEndpoint {
+        match self {
+            Provider::GmailImap => ImapEndpoint::default(),
+            Provider::OutlookImap => ImapEndpoint {
+                host: "outlook.office365.com".to_string(),
+                ..ImapEndpoint::default()
+            },
+            Provider::Imap => ImapEndpoint {
+                host: String::new(),
+                ..ImapEndpoint::default()
+            },
+        }
+    }
+
+    /// The provider of a password account on `endpoint`: Gmail for Gmail's own server (app
+    /// passwords), generic IMAP for everything else.
+    pub fn for_password_account(endpoint: &ImapEndpoint) -> Self {
+        if endpoint
+            .host
+            .eq_ignore_ascii_case(&ImapEndpoint::default().host)
+        {
+            Provider::GmailImap
+        } else {
+            Provider::Imap
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -23,6 +71,330 @@ pub struct AccountSettings {
     pub poll_interval_minutes: u32,
     pub prefetch_recent: u32,
     pub safe_mode: bool,
+    /// Download cap for FETCH streams in bytes/sec (shared by all folders); `None` = unlimited.
+    pub max_download_bytes_per_sec: Option<u64>,
+    /// Per-folder overrides keyed by folder name; folders without an entry use the defaults.
+    pub folder_policies: BTreeMap<String, FolderPolicy>,
+    /// Subject, sender and body columns are sealed with the account's keyring key.
+    pub encrypt_columns: bool,
+    /// Every sync only fetches unread mail (see `SyncOptions::unread_only`).
+    pub unread_only: bool,
+    /// Messages larger than this are stored without their body (`BodyStatus::TooLarge`);
+    /// `None` = no limit.
+    pub max_message_bytes: Option<u64>,
+    /// Saved queries shown as virtual folders in the TUI sidebar, in order.
+    pub smart_folders: Vec<SmartFolder>,
+    /// Sync Gmail's All Mail once instead of each folder; membership comes from labels
+    /// (see `sync::synced_folders`).
+    pub all_mail_mode: bool,
+    /// IMAP server address and transport security (Gmail over implicit TLS by default).
+    pub imap: ImapEndpoint,
+    /// Sync passes and the daemon skip the account while false (`otto pause`), e.g. while its
+    /// credentials are broken or the server rate-limits it; the cache stays readable.
+    pub enabled: bool,
+    /// What the account signs in to IMAP with.
+    pub credential: Credential,
+    /// Age-based archive/delete rules the daemon runs before scheduled passes.
+    pub cleanup_rules: Vec<CleanupRule>,
+    /// Which new mail notifies through which channel, checked after daemon passes.
+    pub notify_rules: Vec<NotifyRule>,
+    /// Folder prefix and hierarchy delimiter, learned at discovery; `None` until then.
+    pub namespace: Option<MailboxNamespace>,
+    /// TLS versions and cipher suites the account's IMAP and SMTP connections may use.
+    pub tls_policy: TlsPolicy,
+}
+
+/// How an account authenticates (stored as JSON in `accounts.credential`; `NULL` = OAuth).
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Credential {
+    /// Google OAuth (XOAUTH2), with the refresh token in the OS keyring.
+    #[default]
+    #[serde(rename = "oauth")]
+    OAuth,
+    /// A password or app password kept in the OS keyring (`otto accounts password --stdin`).
+    Password,
+    /// A password printed by a shell command (`pass show mail/work`, `op read ...`), run on each
+    /// connection so rotated passwords are picked up.
+    PasswordCommand { command: String },
+}
+
+impl Credential {
+    pub fn is_oauth(&self) -> bool {
+        matches!(self, Credential::OAuth)
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Credential::OAuth => "Google OAuth".to_string(),
+            Credential::Password => "password in the keyring".to_string(),
+            Credential::PasswordCommand { command } => format!("password command `{}`", command),
+        }
+    }
+}
+
+/// How the IMAP connection is secured.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum TlsMode {
+    /// TLS from the first byte (IMAPS, usually port 993).
+    #[default]
+    Tls,
+    /// Plain connection upgraded with `STARTTLS` before authenticating (usually port 143).
+    Starttls,
+    /// No encryption at all; only accepted for loopback hosts such as a local Protonmail
+    /// Bridge or Davmail.
+    Plain,
+}
+
+impl TlsMode {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "tls" | "ssl" | "imaps" => Ok(Self::Tls),
+            "starttls" => Ok(Self::Starttls),
+            "plain" | "none" => Ok(Self::Plain),
+            other => bail!(
+                "unknown TLS mode {:?} (expected tls, starttls or plain)",
+                other
+            ),
+        }
+    }
+
+    /// The IANA port for the mode: 993 for implicit TLS, 143 otherwise.
+    pub const fn default_port(self) -> u16 {
+        match self {
+            Self::Tls => 993,
+            Self::Starttls | Self::Plain => 143,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Tls => "tls",
+            Self::Starttls => "starttls",
+            Self::Plain => "plain",
+        }
+    }
+}
+
+/// Where the account's IMAP server lives and how to trust it (stored as JSON on the account).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ImapEndpoint {
+    pub host: String,
+    pub port: u16,
+    pub tls: TlsMode,
+    /// Lowercase hex SHA-256 of the server's certificate. When set, exactly that certificate
+    /// is accepted (self-signed ones included) and the CA and hostname checks are skipped.
+    pub cert_sha256: Option<String>,
+    /// PEM bundle of extra CAs (e.g. a company's internal CA) trusted next to the system
+    /// ones. Ignored while a fingerprint is pinned.
+    pub ca_file: Option<PathBuf>,
+    /// Certificate presented to servers that require mutual TLS.
+    pub client_cert: Option<ClientCert>,
+}
+
+/// Which TLS versions and cipher suites an account's connections may negotiate, for
+/// compliance requirements (stored as JSON in `accounts.tls_policy`). The default is
+/// whatever rustls offers: TLS 1.2 and 1.3 with its safe cipher suites.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TlsPolicy {
+    /// Lowest protocol version accepted; `None` = TLS 1.2.
+    pub min_version: Option<TlsVersion>,
+    /// Cipher suites allowed, by IANA name (`TLS13_AES_256_GCM_SHA384`,
+    /// `TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384`); empty = all of rustls's.
+    pub cipher_suites: Vec<String>,
+}
+
+impl TlsPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// `TLS 1.3+, suites TLS13_AES_256_GCM_SHA384` style, for CLI output.
+    pub fn describe(&self) -> String {
+        let version = self
+            .min_version
+            .map(|version| format!("TLS {}+", version.as_str()))
+            .unwrap_or_else(|| "TLS 1.2+".to_string());
+        if self.cipher_suites.is_empty() {
+            version
+        } else {
+            format!("{}, suites {}", version, self.cipher_suites.join(" "))
+        }
+    }
+}
+
+/// A TLS protocol version (rustls supports nothing older than 1.2).
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum,
+)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    #[value(name = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+impl TlsVersion {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Tls12 => "1.2",
+            Self::Tls13 => "1.3",
+        }
+    }
+}
+
+/// A TLS client certificate: the PEM chain and its private key (PKCS#8, PKCS#1 or SEC1).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ClientCert {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl Default for ImapEndpoint {
+    fn default() -> Self {
+        Self {
+            host: "imap.gmail.com".to_string(),
+            port: 993,
+            tls: TlsMode::Tls,
+            cert_sha256: None,
+            ca_file: None,
+            client_cert: None,
+        }
+    }
+}
+
+impl ImapEndpoint {
+    /// Whether the host is this machine (`localhost` or a loopback address).
+    pub fn is_loopback(&self) -> bool {
+        let host = self.host.trim_start_matches('[').trim_end_matches(']');
+        host.eq_ignore_ascii_case("localhost")
+            || host
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback())
+    }
+
+    /// Normalizes a certificate fingerprint as printed by `openssl x509 -fingerprint -sha256`
+    /// (colons, any case) to the stored lowercase hex form.
+    pub fn parse_fingerprint(raw: &str) -> Result<String> {
+        let hex: String = raw
+            .chars()
+            .filter(|c| *c != ':' && !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!(
+                "expected a SHA-256 fingerprint (64 hex digits), got {:?}",
+                raw
+            );
+        }
+        Ok(hex)
+    }
+}
+
+/// How much of each new message a folder downloads.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyFetch {
+    #[default]
+    Full,
+    /// Headers, flags and labels only; rows stay `body_status = 'pending'`.
+    MetadataOnly,
+}
+
+/// Where a message's one-line list preview comes from (`crate::preview`).
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum PreviewSource {
+    /// The body without quoted replies and newsletter boilerplate ("View in browser", ...).
+    #[default]
+    Clean,
+    /// The message's stored summary, falling back to `Clean` when it has none.
+    Summary,
+    /// The first non-empty line of the body as it is.
+    Raw,
+}
+
+impl PreviewSource {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "clean" => Ok(Self::Clean),
+            "summary" => Ok(Self::Summary),
+            "raw" => Ok(Self::Raw),
+            other => bail!(
+                "unknown preview source {:?} (expected clean, summary or raw)",
+                other
+            ),
+        }
+    }
+}
+
+/// Order of the TUI message list (`OTTO_TUI_SORT`, cycled with `s`). Sender and subject sorts
+/// compare with the configured collation (`crate::collation`); ties stay newest first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListSort {
+    #[default]
+    Date,
+    Sender,
+    Subject,
+}
+
+impl ListSort {
+    pub fn parse(raw: &str) -> Result<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "date" => Ok(Self::Date),
+            "sender" | "from" => Ok(Self::Sender),
+            "subject" => Ok(Self::Subject),
+            other => bail!(
+                "unknown list sort {:?} (expected date, sender or subject)",
+                other
+            ),
+        }
+    }
+
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Date => Self::Sender,
+            Self::Sender => Self::Subject,
+            Self::Subject => Self::Date,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Date => "date",
+            Self::Sender => "sender",
+            Self::Subject => "subject",
+        }
+    }
+}
+
+/// Per-folder overrides of the account-wide sync settings (stored as JSON on the account).
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct FolderPolicy {
+    /// Replaces `AccountSettings::cutoff_since` for this folder.
+    pub cutoff_since: Option<NaiveDate>,
+    pub body_fetch: BodyFetch,
+    /// Disabled folders are skipped by sync, the body phase and backfill.
+    pub enabled: bool,
+    /// Replaces the global preview source (`OTTO_PREVIEW_SOURCE`) for this folder's list.
+    pub preview: Option<PreviewSource>,
+}
+
+impl Default for FolderPolicy {
+    fn default() -> Self {
+        Self {
+            cutoff_since: None,
+            body_fetch: BodyFetch::Full,
+            enabled: true,
+            preview: None,
+        }
+    }
 }
 
 impl AccountSettings {
@@ -30,18 +402,326 @@ impl AccountSettings {
         Self {
             folders: vec![
                 "INBOX".to_string(),
-                "[Gmail]/Sent Mail".to_string(),
-                "[Gmail]/Trash".to_string(),
-                "[Gmail]/Spam".to_string(),
+                FolderRole::Sent.gmail_default().to_string(),
+                FolderRole::Trash.gmail_default().to_string(),
+                FolderRole::Junk.gmail_default().to_string(),
             ],
             cutoff_since,
             poll_interval_minutes: 5,
             prefetch_recent: 100,
             safe_mode: false,
+            max_download_bytes_per_sec: None,
+            folder_policies: BTreeMap::new(),
+            encrypt_columns: false,
+            unread_only: false,
+            max_message_bytes: None,
+            smart_folders: Vec::new(),
+            all_mail_mode: false,
+            imap: ImapEndpoint::default(),
+            enabled: true,
+            credential: Credential::OAuth,
+            cleanup_rules: Vec::new(),
+            notify_rules: Vec::new(),
+            namespace: None,
+            tls_policy: TlsPolicy::default(),
+        }
+    }
+
+    /// The server's name for a folder path as typed in config or on the command line
+    /// (`MailboxNamespace::normalize`); unchanged until the namespace is known.
+    pub fn server_folder(&self, path: &str) -> String {
+        match &self.namespace {
+            Some(namespace) => namespace.normalize(path),
+            None => path.to_string(),
+        }
+    }
+
+    /// Records the account's namespace and rewrites the configured folders and folder policies
+    /// to server names; true when anything changed.
+    pub fn apply_namespace(&mut self, namespace: MailboxNamespace) -> bool {
+        let before = (
+            self.namespace.clone(),
+            self.folders.clone(),
+            self.folder_policies.clone(),
+        );
+        let mut folders: Vec<String> = Vec::new();
+        for folder in &self.folders {
+            let name = namespace.normalize(folder);
+            if !folders.contains(&name) {
+                folders.push(name);
+            }
+        }
+        self.folders = folders;
+        self.folder_policies = std::mem::take(&mut self.folder_policies)
+            .into_iter()
+            .map(|(folder, policy)| (namespace.normalize(&folder), policy))
+            .collect();
+        self.namespace = Some(namespace);
+        before
+            != (
+                self.namespace.clone(),
+                self.folders.clone(),
+                self.folder_policies.clone(),
+            )
+    }
+
+    /// Replaces configured folders still at a built-in default name (Gmail's, or the common
+    /// `Sent`/`Junk`/... of generic IMAP accounts) that `mailboxes` doesn't list by the mailbox
+    /// advertising the same special-use role, carrying folder policies along. Returns whether
+    /// anything changed.
+    pub fn apply_special_use(&mut self, mailboxes: &[MailboxInfo]) -> bool {
+        let listed = |name: &str| mailboxes.iter().any(|m| m.name == name && m.selectable());
+        let mut folders: Vec<String> = Vec::new();
+        let mut changed = false;
+        for folder in &self.folders {
+            let role = FolderRole::ALL_ROLES.into_iter().find(|role| {
+                role.gmail_default() == folder || role.imap_default() == Some(folder.as_str())
+            });
+            let name = match role.and_then(|role| special_use_folder(mailboxes, role)) {
+                Some(found) if found != folder && !listed(folder) => {
+                    if let Some(policy) = self.folder_policies.remove(folder) {
+                        self.folder_policies
+                            .entry(found.to_string())
+                            .or_insert(policy);
+                    }
+                    changed = true;
+                    found.to_string()
+                }
+                _ => folder.clone(),
+            };
+            if !folders.contains(&name) {
+                folders.push(name);
+            }
         }
+        self.folders = folders;
+        changed
+    }
+
+    pub fn folder_policy(&self, folder: &str) -> FolderPolicy {
+        self.folder_policies
+            .get(folder)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The folder's own cutoff, falling back to the account cutoff.
+    pub fn folder_cutoff(&self, folder: &str) -> NaiveDate {
+        self.folder_policy(folder)
+            .cutoff_since
+            .unwrap_or(self.cutoff_since)
+    }
+
+    /// Configured folders whose policy has not disabled them, in configured order.
+    pub fn enabled_folders(&self) -> impl Iterator<Item = &String> {
+        self.folders
+            .iter()
+            .filter(|folder| self.folder_policy(folder).enabled)
     }
 }
 
+/// Storage usage an account's server reported (IMAP QUOTA); counts are `None` when the
+/// server has no limit on that resource.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountQuota {
+    pub account_id: String,
+    /// Bytes (QUOTA's `STORAGE` is in KiB).
+    pub storage_used: Option<u64>,
+    pub storage_limit: Option<u64>,
+    pub messages_used: Option<u64>,
+    pub messages_limit: Option<u64>,
+    pub fetched_at: i64,
+}
+
+impl AccountQuota {
+    /// Storage used as a percentage of the limit, when there is one.
+    pub fn storage_percent(&self) -> Option<u64> {
+        match (self.storage_used, self.storage_limit) {
+            (Some(used), Some(limit)) if limit > 0 => Some(used.saturating_mul(100) / limit),
+            _ => None,
+        }
+    }
+}
+
+/// What an account's server said about itself in answer to IMAP ID (RFC 2971), kept for
+/// diagnostics.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerIdentity {
+    pub account_id: String,
+    /// Fields as the server sent them (`name`, `vendor`, `version`, ...).
+    pub fields: BTreeMap<String, String>,
+    pub fetched_at: i64,
+}
+
+impl ServerIdentity {
+    /// `name=Dovecot, version=2.3` style, for CLI output; `none` when the server sent no fields.
+    pub fn describe(&self) -> String {
+        if self.fields.is_empty() {
+            return "none".to_string();
+        }
+        self.fields
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The account's personal namespace: the prefix its folders live under (`INBOX.` on
+/// Courier-style servers, empty on most) and the hierarchy delimiter. Learned at discovery
+/// from `NAMESPACE`, or from `LIST "" ""` on servers without it.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MailboxNamespace {
+    pub prefix: String,
+    /// `None` for a flat namespace.
+    pub delimiter: Option<String>,
+}
+
+impl MailboxNamespace {
+    /// The server's name for a folder path written with `/` as separator: "INBOX/Archive"
+    /// becomes "INBOX.Archive" under a `.` delimiter, and "Archive" becomes "INBOX.Archive"
+    /// under an `INBOX.` prefix. INBOX itself and names already in server form are kept.
+    pub fn normalize(&self, path: &str) -> String {
+        if path.eq_ignore_ascii_case("INBOX") {
+            return "INBOX".to_string();
+        }
+        let mut name = match self.delimiter.as_deref() {
+            Some(delimiter) if delimiter != "/" => path.replace('/', delimiter),
+            _ => path.to_string(),
+        };
+        let prefix = self.prefix.as_str();
+        match name.get(..prefix.len()) {
+            Some(head) if head == prefix => {}
+            // "inbox.Archive" under "INBOX.": INBOX is case-insensitive.
+            Some(head)
+                if head.eq_ignore_ascii_case(prefix)
+                    && prefix.to_ascii_uppercase().starts_with("INBOX") =>
+            {
+                name.replace_range(..prefix.len(), prefix)
+            }
+            _ => name.insert_str(0, prefix),
+        }
+        name
+    }
+}
+
+/// A mailbox reported by IMAP `LIST`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MailboxInfo {
+    pub name: String,
+    pub delimiter: Option<String>,
+    /// Attributes as sent by the server (`\Noselect`, `\Sent`, ...).
+    pub attributes: Vec<String>,
+}
+
+impl MailboxInfo {
+    /// Whether the mailbox holds messages (`\Noselect` entries are hierarchy placeholders).
+    pub fn selectable(&self) -> bool {
+        !self.attributes.iter().any(|attr| {
+            attr.eq_ignore_ascii_case("\\Noselect") || attr.eq_ignore_ascii_case("\\NonExistent")
+        })
+    }
+
+    /// The special-use role the server advertised for this mailbox, if any.
+    pub fn role(&self) -> Option<FolderRole> {
+        self.attributes
+            .iter()
+            .find_map(|attr| FolderRole::from_attribute(attr))
+    }
+}
+
+/// Special-use mailbox roles (RFC 6154, plus the older Gmail XLIST spellings).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FolderRole {
+    Sent,
+    Trash,
+    Junk,
+    Drafts,
+    /// Gmail's All Mail, where archived messages live.
+    All,
+}
+
+impl FolderRole {
+    pub const ALL_ROLES: [FolderRole; 5] = [
+        FolderRole::Sent,
+        FolderRole::Trash,
+        FolderRole::Junk,
+        FolderRole::Drafts,
+        FolderRole::All,
+    ];
+
+    /// Maps a LIST/XLIST attribute (`\Sent`, `\Spam`, `\AllMail`, ...) to its role.
+    pub fn from_attribute(attr: &str) -> Option<Self> {
+        let name = attr.strip_prefix('\\')?.to_ascii_lowercase();
+        match name.as_str() {
+            "sent" => Some(FolderRole::Sent),
+            "trash" => Some(FolderRole::Trash),
+            "junk" | "spam" => Some(FolderRole::Junk),
+            "drafts" => Some(FolderRole::Drafts),
+            "all" | "allmail" => Some(FolderRole::All),
+            _ => None,
+        }
+    }
+
+    /// The RFC 6154 attribute advertising this role.
+    pub const fn attribute(self) -> &'static str {
+        match self {
+            FolderRole::Sent => "\\Sent",
+            FolderRole::Trash => "\\Trash",
+            FolderRole::Junk => "\\Junk",
+            FolderRole::Drafts => "\\Drafts",
+            FolderRole::All => "\\All",
+        }
+    }
+
+    /// English Gmail name, used until discovery has found the server's own.
+    pub const fn gmail_default(self) -> &'static str {
+        match self {
+            FolderRole::Sent => "[Gmail]/Sent Mail",
+            FolderRole::Trash => "[Gmail]/Trash",
+            FolderRole::Junk => "[Gmail]/Spam",
+            FolderRole::Drafts => "[Gmail]/Drafts",
+            FolderRole::All => "[Gmail]/All Mail",
+        }
+    }
+
+    /// Common name on other servers (Dovecot, Fastmail, ...), used for generic IMAP accounts
+    /// until discovery has found the server's own; Gmail's All Mail has no counterpart.
+    pub const fn imap_default(self) -> Option<&'static str> {
+        match self {
+            FolderRole::Sent => Some("Sent"),
+            FolderRole::Trash => Some("Trash"),
+            FolderRole::Junk => Some("Junk"),
+            FolderRole::Drafts => Some("Drafts"),
+            FolderRole::All => None,
+        }
+    }
+
+    /// The role a built-in default folder name stands for.
+    pub fn for_gmail_default(name: &str) -> Option<Self> {
+        Self::ALL_ROLES
+            .into_iter()
+            .find(|role| role.gmail_default() == name)
+    }
+}
+
+/// The selectable mailbox `mailboxes` advertises for `role`.
+pub fn special_use_folder(mailboxes: &[MailboxInfo], role: FolderRole) -> Option<&str> {
+    mailboxes
+        .iter()
+        .find(|m| m.selectable() && m.role() == Some(role))
+        .map(|m| m.name.as_str())
+}
+
+/// A folder's message counts as the server reported them (`STATUS (MESSAGES UNSEEN)`), kept
+/// for badge counts that don't depend on how much of the folder is cached.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FolderCounts {
+    pub folder: String,
+    pub unseen: u32,
+    pub messages: u32,
+    pub checked_at: i64,
+}
+
 #[derive(Clone, Debug)]
 pub struct FolderState {
     pub id: i64,
@@ -53,6 +733,11 @@ pub struct FolderState {
     pub exists_count: Option<u32>,
     pub last_sync_ts: Option<i64>,
     pub last_uid_scan_ts: Option<i64>,
+    pub baseline_scan_uid: Option<u32>,
+    pub resume_modseq: Option<u64>,
+    pub resume_uid: Option<u32>,
+    /// Oldest date (inclusive) fetched by `otto backfill`; older than `cutoff_since` when set.
+    pub backfill_since: Option<NaiveDate>,
 }
 
 #[derive(Clone, Debug)]
@@ -64,7 +749,8 @@ pub struct MessageRecord {
     pub thread_id: Option<String>,
     pub internal_date: Option<i64>,
     pub subject: Option<String>,
-    pub from: Option<String>,
+    pub from: Option<String>, // bare address (legacy rows may hold the raw header value)
+    pub from_name: Option<String>,
     pub to: Option<String>,
     pub cc: Option<String>,
     pub bcc: Option<String>,
@@ -73,10 +759,29 @@ pub struct MessageRecord {
     pub has_attachments: bool,
     pub size_bytes: Option<u32>,
     pub raw_hash: Option<String>,
+    /// Normalized `Message-ID` header; matches copies across folders when the server has no
+    /// stable id (X-GM-MSGID).
+    pub message_id_header: Option<String>,
+    /// Parent Message-IDs from References/In-Reply-To, oldest first. Only feeds threading at
+    /// write time (kept in the `threads` table); empty on rows loaded back.
+    pub references: Vec<String>,
+    pub body_status: BodyStatus,
     pub created_at: i64,
     pub updated_at: i64,
 }
 
+/// Whether a message's body has been downloaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyStatus {
+    /// Raw RFC822 + sanitized text are stored.
+    Full,
+    /// Only envelope/headers are stored (headers-first sync); the body is fetched later.
+    Pending,
+    /// Over the account's `max_message_bytes`; headers only until requested with
+    /// `otto fetch-bodies`.
+    TooLarge,
+}
+
 #[derive(Clone, Debug)]
 pub struct BodyRecord {
     pub message_id: String,
@@ -85,6 +790,104 @@ pub struct BodyRecord {
     pub mime_summary: Option<String>,
     pub attachments_json: Option<String>,
     pub sanitized_at: Option<i64>,
+    /// `sanitize::SANITIZER_VERSION` that produced `sanitized_text` (`None` before versioning).
+    pub sanitizer_version: Option<i64>,
+    /// `sanitized_text` is the tag-stripped fallback: html2text ran past its time budget.
+    pub degraded: bool,
+}
+
+/// Progress events emitted by the sync engine over a broadcast channel.
+#[derive(Clone, Debug)]
+pub enum SyncProgress {
+    AccountStarted {
+        account_id: String,
+        folders: usize,
+    },
+    FolderStarted {
+        account_id: String,
+        folder: String,
+    },
+    /// UIDs whose bodies will be downloaded in this folder run.
+    UidsPlanned {
+        account_id: String,
+        folder: String,
+        count: usize,
+    },
+    MessagesFetched {
+        account_id: String,
+        folder: String,
+        count: usize,
+        bytes: u64,
+    },
+    MessagesParsed {
+        account_id: String,
+        folder: String,
+        count: usize,
+    },
+    MessagesWritten {
+        account_id: String,
+        folder: String,
+        count: usize,
+    },
+    /// Existing messages whose flags, labels or location were updated.
+    MessagesUpdated {
+        account_id: String,
+        folder: String,
+        count: usize,
+    },
+    /// Cached messages purged because the server expunged them.
+    MessagesExpunged {
+        account_id: String,
+        folder: String,
+        count: usize,
+    },
+    FolderFinished {
+        account_id: String,
+        folder: String,
+        ok: bool,
+    },
+    AccountFinished {
+        account_id: String,
+    },
+}
+
+/// One folder's outcome in one account sync pass (`sync_runs` row).
+#[derive(Clone, Debug)]
+pub struct SyncRunRecord {
+    pub account_id: String,
+    pub folder: String,
+    /// When the account pass started; shared by every folder row of that pass.
+    pub run_started_at: i64,
+    /// Folder wall time, including the wait for a connection permit.
+    pub duration_ms: i64,
+    pub added: usize,
+    pub updated: usize,
+    pub deleted: usize,
+    pub bytes: u64,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// A new-message FETCH that failed (no response for the UID, or an unparseable message), kept in
+/// `fetch_retries` so later syncs try it again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchRetry {
+    pub uid: u32,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub first_failed_at: i64,
+    pub last_attempt_at: i64,
+}
+
+/// Signature appended to composed mail. `alias` scopes it to a send-as address; `None` is the
+/// account default.
+#[derive(Clone, Debug)]
+pub struct Signature {
+    pub account_id: String,
+    pub alias: Option<String>,
+    pub body: String,
+    /// On replies, place the signature above the quoted text instead of at the very bottom.
+    pub above_quote: bool,
 }
 
 pub fn now_ts() -> i64 {
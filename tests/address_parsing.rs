@@ -0,0 +1,70 @@
+use otto::address::{
+    Mailbox, friendly_from, full_from, normalize_message_id, parse_mailbox, parse_mailbox_header,
+};
+
+#[test]
+fn parses_display_name_and_address() {
+    assert_eq!(
+        parse_mailbox("\"Doe, Jane\" <jane@example.com>"),
+        Some(Mailbox {
+            name: Some("Doe, Jane".to_string()),
+            addr: "jane@example.com".to_string(),
+        })
+    );
+    assert_eq!(
+        parse_mailbox("jane@example.com"),
+        Some(Mailbox {
+            name: None,
+            addr: "jane@example.com".to_string(),
+        })
+    );
+}
+
+#[test]
+fn decodes_encoded_names_with_commas_from_header() {
+    let (header, _) =
+        mailparse::parse_header(b"From: =?UTF-8?Q?M=C3=BCller,_Hans?= <hans@example.de>").unwrap();
+    let mailbox = parse_mailbox_header(&header).unwrap();
+    assert_eq!(mailbox.name.as_deref(), Some("Müller, Hans"));
+    assert_eq!(mailbox.addr, "hans@example.de");
+}
+
+#[test]
+fn friendly_from_prefers_name_and_handles_legacy_rows() {
+    assert_eq!(
+        friendly_from(Some("Jane"), Some("jane@example.com")),
+        "Jane"
+    );
+    assert_eq!(
+        friendly_from(None, Some("jane@example.com")),
+        "jane@example.com"
+    );
+    // Rows stored before the split hold the raw header value.
+    assert_eq!(
+        friendly_from(None, Some("Jane Doe <jane@example.com>")),
+        "Jane Doe"
+    );
+    assert_eq!(friendly_from(None, None), "Unknown");
+    assert_eq!(
+        full_from(Some("Jane"), Some("jane@example.com")),
+        "Jane <jane@example.com>"
+    );
+}
+
+#[test]
+fn normalizes_message_id_headers() {
+    assert_eq!(
+        normalize_message_id(" <CAF+abc@mail.example.com>\r\n"),
+        Some("CAF+abc@mail.example.com".to_string())
+    );
+    assert_eq!(
+        normalize_message_id("<split\r\n @example.com> (comment)"),
+        Some("split@example.com".to_string())
+    );
+    assert_eq!(
+        normalize_message_id("bare@example.com"),
+        Some("bare@example.com".to_string())
+    );
+    assert_eq!(normalize_message_id("  "), None);
+    assert_eq!(normalize_message_id("<>"), None);
+}
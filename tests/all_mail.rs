@@ -0,0 +1,97 @@
+use otto::storage::Database;
+use otto::storage::ops::MessageOp;
+use otto::sync::{FolderLabels, synced_folders};
+use otto::types::{Account, BodyStatus, MessageRecord};
+
+mod common;
+
+const ALL_MAIL: &str = "[Gmail]/All Mail";
+
+fn message(id: &str, uid: u32, labels: &[&str], flags: &[&str]) -> MessageRecord {
+    MessageRecord {
+        subject: Some(id.into()),
+        flags: flags.iter().map(|f| f.to_string()).collect(),
+        labels: labels.iter().map(|l| l.to_string()).collect(),
+        body_status: BodyStatus::Pending,
+        ..common::message(id, ALL_MAIL, uid)
+    }
+}
+
+fn account(all_mail_mode: bool) -> Account {
+    let mut account = common::account("acct");
+    account.settings.prefetch_recent = 10;
+    account.settings.all_mail_mode = all_mail_mode;
+    account
+}
+
+#[tokio::test]
+async fn all_mail_mode_syncs_once_and_maps_labels_to_folders() {
+    let dir = std::env::temp_dir().join(format!("otto-all-mail-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+
+    db.save_account(&account(false)).await.unwrap();
+    let per_folder = db.list_accounts().await.unwrap().remove(0);
+    assert!(!per_folder.settings.all_mail_mode);
+    assert_eq!(
+        synced_folders(&db, &per_folder).await.unwrap(),
+        per_folder.settings.folders
+    );
+
+    db.save_account(&account(true)).await.unwrap();
+    let all_mail = db.list_accounts().await.unwrap().remove(0);
+    assert!(all_mail.settings.all_mail_mode);
+    assert_eq!(
+        synced_folders(&db, &all_mail).await.unwrap(),
+        vec![ALL_MAIL, "[Gmail]/Trash", "[Gmail]/Spam"]
+    );
+
+    db.commit_backfill_batch(
+        "acct",
+        ALL_MAIL,
+        &[
+            message("inbox-unread", 1, &["\\Inbox", "\\Important"], &[]),
+            message("sent", 2, &["\\Sent"], &["Seen"]),
+            message("work-unread", 3, &["\\Inbox", "Work"], &[]),
+        ],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let labels = FolderLabels::load(&db, "acct").await.unwrap();
+    let inbox = vec!["\\Inbox".to_string()];
+    assert!(labels.contains("INBOX", ALL_MAIL, &inbox));
+    assert!(labels.contains("[Gmail]/Sent Mail", ALL_MAIL, &["\\Sent".to_string()]));
+    assert!(labels.contains("Work", ALL_MAIL, &["Work".to_string()]));
+    assert!(!labels.contains("[Gmail]/Sent Mail", ALL_MAIL, &inbox));
+
+    let counts = db.unread_label_counts("acct", ALL_MAIL).await.unwrap();
+    assert_eq!(counts.get("\\Inbox"), Some(&2));
+    assert_eq!(counts.get("Work"), Some(&1));
+    assert_eq!(counts.get("\\Sent"), None);
+
+    // Moving a message out of the inbox keeps it in All Mail and swaps the labels.
+    db.apply_message_op(
+        "acct",
+        &MessageOp::Move("Receipts".into()),
+        &["work-unread".into()],
+    )
+    .await
+    .unwrap();
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    let moved = &loaded
+        .iter()
+        .find(|(m, _)| m.id == "work-unread")
+        .unwrap()
+        .0;
+    assert_eq!((moved.folder.as_str(), moved.uid), (ALL_MAIL, Some(3)));
+    assert_eq!(
+        moved.labels,
+        vec!["Work".to_string(), "Receipts".to_string()]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
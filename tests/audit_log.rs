@@ -0,0 +1,55 @@
+use otto::storage::Database;
+use otto::storage::audit::AuditRecord;
+
+fn record(account: &str, ts: i64, op: &str) -> AuditRecord {
+    AuditRecord {
+        account_id: account.into(),
+        ts,
+        actor: "ops-replay".into(),
+        op: op.into(),
+        folder: "INBOX".into(),
+        destination: Some("[Gmail]/Trash".into()),
+        uids: vec![3, 4, 5],
+        op_ids: vec![11, 12, 13],
+        outcome: "ok".into(),
+    }
+}
+
+#[tokio::test]
+async fn audit_log_is_append_only_and_filterable() {
+    let dir = std::env::temp_dir().join(format!("otto-audit-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+
+    db.record_audit(&record("a", 100, "delete")).await.unwrap();
+    db.record_audit(&record("a", 200, "expunge")).await.unwrap();
+    db.record_audit(&record("b", 300, "archive")).await.unwrap();
+
+    let all = db.load_audit_log(None, None, 10).await.unwrap();
+    assert_eq!(
+        all.iter().map(|r| r.op.as_str()).collect::<Vec<_>>(),
+        ["archive", "expunge", "delete"]
+    );
+    assert_eq!(all[2], record("a", 100, "delete"));
+
+    let recent_a = db.load_audit_log(Some("a"), Some(150), 10).await.unwrap();
+    assert_eq!(recent_a.len(), 1);
+    assert_eq!(recent_a[0].op, "expunge");
+    assert_eq!(db.load_audit_log(None, None, 1).await.unwrap().len(), 1);
+
+    assert!(
+        sqlx::query("DELETE FROM audit_log")
+            .execute(db.pool())
+            .await
+            .is_err()
+    );
+    assert!(
+        sqlx::query("UPDATE audit_log SET outcome = 'ok'")
+            .execute(db.pool())
+            .await
+            .is_err()
+    );
+    assert_eq!(db.load_audit_log(None, None, 10).await.unwrap().len(), 3);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,224 @@
+use otto::sanitize::SANITIZER_VERSION;
+use otto::sanitize::resanitize::resanitize_account;
+use otto::storage::{BodyStorage, Database};
+use otto::types::{BodyRecord, MessageRecord};
+use tokio_util::sync::CancellationToken;
+
+mod common;
+
+const RAW: &[u8] = b"From: a@example.com\r\nSubject: hi\r\n\r\nsame bytes in two folders\r\n";
+
+fn message(id: &str, folder: &str) -> MessageRecord {
+    MessageRecord {
+        size_bytes: Some(RAW.len() as u32),
+        ..common::message(id, folder, 1)
+    }
+}
+
+fn body(id: &str) -> BodyRecord {
+    BodyRecord {
+        message_id: id.into(),
+        raw_rfc822: Some(RAW.to_vec()),
+        sanitized_text: Some("same bytes in two folders".into()),
+        mime_summary: None,
+        attachments_json: None,
+        sanitized_at: Some(1_700_000_000),
+        sanitizer_version: Some(1),
+        degraded: false,
+    }
+}
+
+async fn blob_count(db: &Database) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM blobs")
+        .fetch_one(db.pool())
+        .await
+        .unwrap()
+}
+
+async fn open_db(name: &str) -> (std::path::PathBuf, Database) {
+    let dir = std::env::temp_dir().join(format!("otto-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut account = common::account("acct");
+    account.settings.folders = vec!["INBOX".into(), "Receipts".into()];
+    account.settings.prefetch_recent = 10;
+    db.save_account(&account).await.unwrap();
+    (dir, db)
+}
+
+#[tokio::test]
+async fn identical_bodies_share_one_blob_until_the_last_reference_goes() {
+    let (dir, db) = open_db("blobs").await;
+    db.set_body_storage(BodyStorage::ContentAddressed);
+
+    db.batch_upsert_messages_with_bodies(
+        &[message("m1", "INBOX"), message("m2", "Receipts")],
+        &[body("m1"), body("m2")],
+    )
+    .await
+    .unwrap();
+    assert_eq!(blob_count(&db).await, 1);
+
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    assert_eq!(loaded.len(), 2);
+    for (_, body) in &loaded {
+        assert_eq!(body.as_ref().unwrap().raw_rfc822.as_deref(), Some(RAW));
+    }
+
+    db.delete_message("m1").await.unwrap();
+    assert_eq!(blob_count(&db).await, 1);
+    db.delete_message("m2").await.unwrap();
+    assert_eq!(blob_count(&db).await, 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn hybrid_mode_offloads_large_blobs_to_files() {
+    let (dir, db) = open_db("hybrid").await;
+    db.set_body_storage(BodyStorage::Hybrid {
+        min_file_bytes: RAW.len() as u64,
+    });
+
+    db.batch_upsert_messages_with_bodies(&[message("m1", "INBOX")], &[body("m1")])
+        .await
+        .unwrap();
+    let (stored_len, offloaded): (i64, i64) =
+        sqlx::query_as("SELECT length(data), offloaded FROM blobs")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+    assert_eq!((stored_len, offloaded), (0, 1));
+    let files = || {
+        std::fs::read_dir(dir.join("otto.blobs"))
+            .unwrap()
+            .flat_map(|shard| std::fs::read_dir(shard.unwrap().path()).unwrap())
+            .count()
+    };
+    assert_eq!(files(), 1);
+
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    assert_eq!(
+        loaded[0].1.as_ref().unwrap().raw_rfc822.as_deref(),
+        Some(RAW)
+    );
+
+    // The file outlives its row until the startup purge.
+    db.delete_message("m1").await.unwrap();
+    assert_eq!(blob_count(&db).await, 0);
+    assert_eq!(files(), 1);
+    assert_eq!(db.purge_blob_files().await.unwrap(), 1);
+    assert_eq!(files(), 0);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn resanitize_rebuilds_only_bodies_from_older_sanitizer_versions() {
+    let (dir, db) = open_db("resanitize").await;
+    db.set_body_storage(BodyStorage::ContentAddressed);
+
+    let mut stale = body("m1");
+    stale.sanitized_text = Some("old sanitizer output".into());
+    stale.sanitizer_version = None;
+    db.batch_upsert_messages_with_bodies(
+        &[message("m1", "INBOX"), message("m2", "Receipts")],
+        &[stale, body("m2")],
+    )
+    .await
+    .unwrap();
+
+    let cancel = CancellationToken::new();
+    assert_eq!(
+        resanitize_account(&db, "acct", false, &cancel)
+            .await
+            .unwrap(),
+        1
+    );
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    let m1 = loaded
+        .iter()
+        .find(|(m, _)| m.id == "m1")
+        .and_then(|(_, b)| b.as_ref())
+        .unwrap();
+    assert!(
+        m1.sanitized_text
+            .as_deref()
+            .unwrap()
+            .contains("same bytes in two folders")
+    );
+    assert_eq!(m1.sanitizer_version, Some(SANITIZER_VERSION));
+    assert_eq!(m1.raw_rfc822.as_deref(), Some(RAW));
+
+    assert_eq!(
+        resanitize_account(&db, "acct", false, &cancel)
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        resanitize_account(&db, "acct", true, &cancel)
+            .await
+            .unwrap(),
+        2
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn raw_bodies_are_compressed_and_old_rows_can_be_migrated() {
+    let (dir, db) = open_db("compress").await;
+    let html = format!(
+        "From: a@example.com\r\nSubject: newsletter\r\nContent-Type: text/html\r\n\r\n{}",
+        "<tr><td style=\"padding:8px;font-family:Arial\">row</td></tr>\r\n".repeat(200)
+    )
+    .into_bytes();
+    let big = |id: &str| BodyRecord {
+        raw_rfc822: Some(html.clone()),
+        ..body(id)
+    };
+    db.batch_upsert_messages_with_bodies(&[message("m1", "INBOX")], &[big("m1")])
+        .await
+        .unwrap();
+    let (stored_len, format): (i64, i64) =
+        sqlx::query_as("SELECT length(raw_rfc822), raw_format FROM bodies")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+    assert_eq!(format, 1);
+    assert!(stored_len * 3 < html.len() as i64);
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    assert_eq!(
+        loaded[0].1.as_ref().unwrap().raw_rfc822.as_deref(),
+        Some(html.as_slice())
+    );
+
+    // Rows written before compression: an inline body and a blob.
+    sqlx::query("UPDATE bodies SET raw_rfc822 = ?1, raw_format = 0")
+        .bind(&html)
+        .execute(db.pool())
+        .await
+        .unwrap();
+    db.set_body_storage(BodyStorage::ContentAddressed);
+    db.batch_upsert_messages_with_bodies(&[message("m2", "Receipts")], &[big("m2")])
+        .await
+        .unwrap();
+    sqlx::query("UPDATE blobs SET data = ?1, format = 0")
+        .bind(&html)
+        .execute(db.pool())
+        .await
+        .unwrap();
+
+    let stats = db.compress_raw_bodies("acct").await.unwrap();
+    assert_eq!(stats.compressed, 2);
+    assert_eq!(stats.bytes_before, 2 * html.len() as u64);
+    assert!(stats.bytes_after * 3 < stats.bytes_before);
+    assert_eq!(db.compress_raw_bodies("acct").await.unwrap().compressed, 0);
+    for (_, body) in db.load_messages("acct", 10).await.unwrap() {
+        assert_eq!(body.unwrap().raw_rfc822.as_deref(), Some(html.as_slice()));
+    }
+    db.vacuum().await.unwrap();
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
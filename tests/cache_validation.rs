@@ -0,0 +1,78 @@
+use otto::sync::{CacheFreshness, FolderStatus};
+use otto::types::FolderState;
+
+fn cached(uidvalidity: u32, highest_uid: u32, modseq: u64) -> FolderState {
+    FolderState {
+        id: 1,
+        account_id: "acct".into(),
+        name: "INBOX".into(),
+        uidvalidity: Some(uidvalidity),
+        highest_uid: Some(highest_uid),
+        highestmodseq: Some(modseq),
+        exists_count: Some(40),
+        last_sync_ts: Some(1_700_000_000),
+        last_uid_scan_ts: None,
+        baseline_scan_uid: None,
+        resume_modseq: None,
+        resume_uid: None,
+        backfill_since: None,
+    }
+}
+
+fn server(uidvalidity: u32, uid_next: u32, modseq: u64) -> FolderStatus {
+    FolderStatus {
+        uidvalidity: Some(uidvalidity),
+        uid_next: Some(uid_next),
+        messages: 40,
+        highestmodseq: Some(modseq),
+    }
+}
+
+#[test]
+fn status_spot_check_classifies_cached_folders() {
+    let state = cached(7, 120, 900);
+
+    assert_eq!(
+        CacheFreshness::classify(Some(&state), &server(7, 121, 900)),
+        CacheFreshness::Fresh
+    );
+    assert_eq!(
+        CacheFreshness::classify(Some(&state), &server(7, 124, 950)),
+        CacheFreshness::Stale {
+            new_uids: 3,
+            changed: true
+        }
+    );
+    assert_eq!(
+        CacheFreshness::classify(Some(&state), &server(7, 121, 901)),
+        CacheFreshness::Stale {
+            new_uids: 0,
+            changed: true
+        }
+    );
+    assert_eq!(
+        CacheFreshness::classify(Some(&state), &server(8, 5, 10)),
+        CacheFreshness::NeedsResync {
+            cached: 7,
+            server: 8
+        }
+    );
+    assert_eq!(
+        CacheFreshness::classify(None, &server(7, 121, 900)),
+        CacheFreshness::NeverSynced
+    );
+
+    // An interrupted pass is stale even when the server counters match.
+    let interrupted = FolderState {
+        resume_uid: Some(100),
+        resume_modseq: Some(900),
+        ..state
+    };
+    assert_eq!(
+        CacheFreshness::classify(Some(&interrupted), &server(7, 121, 900)),
+        CacheFreshness::Stale {
+            new_uids: 0,
+            changed: true
+        }
+    );
+}
@@ -0,0 +1,143 @@
+use otto::cleanup::{self, CleanupAction, CleanupRule, parse_age};
+use otto::storage::Database;
+use otto::timefmt::DisplayTz;
+use otto::types::MessageRecord;
+
+mod common;
+
+const NOW: i64 = 1_760_000_000;
+const DAY: i64 = 24 * 60 * 60;
+
+fn message(id: &str, uid: u32, from: &str, labels: &[&str], age_days: i64) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(NOW - age_days * DAY),
+        subject: Some(format!("about {}", id)),
+        from: Some(from.into()),
+        flags: vec!["\\Seen".into()],
+        labels: labels.iter().map(|l| l.to_string()).collect(),
+        ..common::message(id, "INBOX", uid)
+    }
+}
+
+#[test]
+fn rule_ages_accept_days_and_weeks() {
+    assert_eq!(parse_age("7d").unwrap(), 7);
+    assert_eq!(parse_age("2w").unwrap(), 14);
+    assert_eq!(parse_age("30").unwrap(), 30);
+    assert!(parse_age("d").is_err());
+    assert!(parse_age("soon").is_err());
+}
+
+#[tokio::test]
+async fn rules_queue_old_matches_once_and_record_a_report() {
+    let dir = std::env::temp_dir().join(format!("otto-cleanup-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut account = common::account("acct");
+    account.settings.cleanup_rules = vec![
+        CleanupRule {
+            name: "newsletters".into(),
+            query: "from:news@example.com".into(),
+            older_than_days: 7,
+            action: CleanupAction::Archive,
+            label: None,
+        },
+        CleanupRule {
+            name: "promos".into(),
+            query: "label:Promotions".into(),
+            older_than_days: 30,
+            action: CleanupAction::Delete,
+            label: None,
+        },
+    ];
+    db.save_account(&account).await.unwrap();
+    let account = db.list_accounts().await.unwrap().remove(0);
+    assert_eq!(account.settings.cleanup_rules.len(), 2);
+
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[
+            message("old-news", 1, "news@example.com", &[], 10),
+            message("new-news", 2, "news@example.com", &[], 3),
+            message("old-promo", 3, "shop@example.com", &["Promotions"], 40),
+            message("new-promo", 4, "shop@example.com", &["Promotions"], 10),
+            // Both rules match; the first one takes it.
+            message("old-news-promo", 5, "news@example.com", &["Promotions"], 40),
+        ],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+    let mut trashed = message("trashed-news", 1, "news@example.com", &[], 20);
+    trashed.folder = "[Gmail]/Trash".into();
+    db.commit_backfill_batch("acct", "[Gmail]/Trash", &[trashed], &[], &[], None)
+        .await
+        .unwrap();
+
+    let tz = DisplayTz::default();
+    let ids = |outcome: &cleanup::CleanupOutcome| {
+        let mut ids: Vec<String> = outcome.messages.iter().map(|m| m.id.clone()).collect();
+        ids.sort();
+        ids
+    };
+    let dry = cleanup::run_rules(&db, &account, None, NOW, tz, true)
+        .await
+        .unwrap();
+    assert_eq!(dry.len(), 2);
+    assert_eq!(ids(&dry[0]), ["old-news", "old-news-promo"]);
+    assert_eq!(ids(&dry[1]), ["old-promo"]);
+    assert_eq!(db.list_pending_ops("acct").await.unwrap().len(), 0);
+    assert!(
+        db.load_cleanup_runs(None, None, 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    let outcomes = cleanup::run_rules(&db, &account, None, NOW, tz, false)
+        .await
+        .unwrap();
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(db.list_pending_ops("acct").await.unwrap().len(), 3);
+    let folder_of = |msgs: &[MessageRecord], id: &str| {
+        msgs.iter()
+            .find(|m| m.id == id)
+            .map(|m| m.folder.clone())
+            .unwrap()
+    };
+    let all: Vec<String> = ["old-news", "old-promo", "new-news"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let moved = db.load_messages_by_ids("acct", &all).await.unwrap();
+    assert_eq!(folder_of(&moved, "old-news"), "[Gmail]/All Mail");
+    assert_eq!(folder_of(&moved, "old-promo"), "[Gmail]/Trash");
+    assert_eq!(folder_of(&moved, "new-news"), "INBOX");
+
+    let runs = db.load_cleanup_runs(Some("acct"), None, 10).await.unwrap();
+    assert_eq!(runs.len(), 2);
+    let promos = runs.iter().find(|r| r.rule == "promos").unwrap();
+    assert_eq!(
+        (promos.action.as_str(), promos.message_ids.as_slice()),
+        ("delete", ["old-promo".to_string()].as_slice())
+    );
+
+    // The archived newsletter is still a promotion, so the next run trashes it; after that,
+    // everything is where the rules leave it.
+    let again = cleanup::run_rules(&db, &account, None, NOW, tz, false)
+        .await
+        .unwrap();
+    assert_eq!(again.len(), 1);
+    assert_eq!(
+        (again[0].rule.as_str(), ids(&again[0])),
+        ("promos", vec!["old-news-promo".to_string()])
+    );
+    let last = cleanup::run_rules(&db, &account, None, NOW, tz, false)
+        .await
+        .unwrap();
+    assert!(last.is_empty(), "{last:?}");
+    let _ = std::fs::remove_dir_all(&dir);
+}
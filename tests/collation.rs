@@ -0,0 +1,67 @@
+use std::cmp::Ordering;
+
+use otto::collation::{Collation, Sorter, subject_sort_key};
+
+fn sorted(collation: &Collation, names: &[&str]) -> Vec<String> {
+    let sorter = Sorter::new(collation);
+    let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+    names.sort_by(|a, b| sorter.compare(a, b));
+    names
+}
+
+#[test]
+fn sender_and_subject_sorts_follow_the_configured_collation() {
+    let names = ["Zoe", "Émile", "ada", "Öberg", "\"Bob\"", "Oscar"];
+    assert_eq!(
+        sorted(&Collation::default(), &names),
+        vec!["ada", "\"Bob\"", "Émile", "Öberg", "Oscar", "Zoe"]
+    );
+    // Swedish puts Ö after Z.
+    assert_eq!(
+        sorted(&Collation::parse("sv_SE.UTF-8").unwrap(), &names),
+        vec!["ada", "\"Bob\"", "Émile", "Oscar", "Zoe", "Öberg"]
+    );
+    assert_eq!(
+        sorted(&Collation::Codepoint, &names),
+        vec!["\"Bob\"", "ada", "Oscar", "Zoe", "Émile", "Öberg"]
+    );
+    assert_eq!(
+        Sorter::new(&Collation::default()).compare("Build 9", "Build 10"),
+        Ordering::Less
+    );
+
+    assert_eq!(
+        subject_sort_key("Re: AW: Fwd:  Quarterly plan"),
+        "Quarterly plan"
+    );
+    assert_eq!(subject_sort_key("Re[2]: Lunch"), "Lunch");
+    assert_eq!(subject_sort_key("Agenda: Monday"), "Agenda: Monday");
+
+    assert_eq!(Collation::parse("C").unwrap(), Collation::Codepoint);
+    assert_eq!(Collation::parse("root").unwrap(), Collation::Locale(None));
+    assert_eq!(
+        Collation::parse("de-u-co-phonebk").unwrap(),
+        Collation::Locale(Some("de-u-co-phonebk".into()))
+    );
+    assert!(Collation::parse("not a locale!").is_err());
+
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        }
+    };
+    assert_eq!(
+        Collation::from_env(env(&[
+            ("LANG", "de_DE.UTF-8"),
+            ("LC_COLLATE", "sv_SE.UTF-8")
+        ])),
+        Collation::Locale(Some("sv-SE".into()))
+    );
+    assert_eq!(
+        Collation::from_env(env(&[("OTTO_COLLATION", "codepoint"), ("LANG", "de_DE")])),
+        Collation::Codepoint
+    );
+    assert_eq!(Collation::from_env(env(&[])), Collation::Locale(None));
+}
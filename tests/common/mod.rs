@@ -0,0 +1,65 @@
+//! Fixtures shared by the integration tests. Each fills in the fields most tests don't care
+//! about; override the rest with struct update syntax (`MessageRecord { .., ..message(..) }`).
+#![allow(dead_code)]
+
+use chrono::NaiveDate;
+use otto::types::{
+    Account, AccountSettings, BodyStatus, ImapEndpoint, MessageRecord, Provider, TlsMode,
+};
+
+/// `internal_date`, `created_at` and `updated_at` of [`message`].
+pub const DATE: i64 = 1_700_000_000;
+
+/// A downloaded message of account `acct`, from a@example.com with subject "hi".
+pub fn message(id: &str, folder: &str, uid: u32) -> MessageRecord {
+    MessageRecord {
+        id: id.into(),
+        account_id: "acct".into(),
+        folder: folder.into(),
+        uid: Some(uid),
+        thread_id: None,
+        internal_date: Some(DATE),
+        subject: Some("hi".into()),
+        from: Some("a@example.com".into()),
+        from_name: None,
+        to: None,
+        cc: None,
+        bcc: None,
+        flags: Vec::new(),
+        labels: Vec::new(),
+        has_attachments: false,
+        size_bytes: None,
+        raw_hash: None,
+        message_id_header: None,
+        references: Vec::new(),
+        body_status: BodyStatus::Full,
+        created_at: DATE,
+        updated_at: DATE,
+    }
+}
+
+/// A Gmail account `id` (me@example.com) with default settings and a 2025-01-01 cutoff.
+pub fn account(id: &str) -> Account {
+    Account {
+        id: id.into(),
+        email: "me@example.com".into(),
+        provider: Provider::GmailImap,
+        settings: AccountSettings::with_defaults(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()),
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+/// [`account`] pointed at a plaintext IMAP server on localhost `port`.
+pub fn local_account(id: &str, port: u16) -> Account {
+    let mut account = account(id);
+    account.settings.imap = ImapEndpoint {
+        host: "127.0.0.1".into(),
+        port,
+        tls: TlsMode::Plain,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    };
+    account
+}
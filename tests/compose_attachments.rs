@@ -0,0 +1,166 @@
+use mailparse::{DispositionType, parse_mail};
+
+use otto::compose::attachments::{Attachment, complete_path, content_type_for};
+use otto::compose::{
+    Draft, build_message, complete_recipient, current_recipient, message_size, split_recipients,
+};
+
+fn draft(attachments: Vec<Attachment>) -> Draft {
+    Draft {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: Vec::new(),
+        subject: "Report".to_string(),
+        body_markdown: "See **attached**.".to_string(),
+        attachments,
+    }
+}
+
+#[test]
+fn attachments_wrap_the_alternative_in_multipart_mixed() {
+    let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+    let draft = draft(vec![
+        Attachment {
+            filename: "report.pdf".to_string(),
+            content_type: "application/pdf".to_string(),
+            data: data.clone(),
+        },
+        Attachment {
+            filename: "Übersicht.txt".to_string(),
+            content_type: "text/plain".to_string(),
+            data: b"hello".to_vec(),
+        },
+    ]);
+
+    let raw = build_message(&draft).unwrap();
+    assert_eq!(message_size(&draft), raw.len() as u64);
+    assert!(
+        raw.split(|b| *b == b'\n')
+            .all(|line| line.len() <= 78 || line.starts_with(b"=?"))
+    );
+
+    let parsed = parse_mail(&raw).unwrap();
+    assert_eq!(parsed.ctype.mimetype, "multipart/mixed");
+    assert_eq!(parsed.subparts.len(), 3);
+    assert_eq!(parsed.subparts[0].ctype.mimetype, "multipart/alternative");
+    assert!(
+        parsed.subparts[0].subparts[0]
+            .get_body()
+            .unwrap()
+            .contains("See **attached**.")
+    );
+
+    let pdf = &parsed.subparts[1];
+    assert_eq!(pdf.ctype.mimetype, "application/pdf");
+    let disposition = pdf.get_content_disposition();
+    assert_eq!(disposition.disposition, DispositionType::Attachment);
+    assert_eq!(
+        disposition.params.get("filename").map(String::as_str),
+        Some("report.pdf")
+    );
+    assert_eq!(pdf.get_body_raw().unwrap(), data);
+
+    let text = &parsed.subparts[2];
+    assert_eq!(
+        text.get_content_disposition()
+            .params
+            .get("filename")
+            .map(String::as_str),
+        Some("Übersicht.txt")
+    );
+    assert_eq!(text.get_body_raw().unwrap(), b"hello");
+}
+
+#[test]
+fn plain_drafts_stay_multipart_alternative() {
+    let draft = draft(Vec::new());
+    let raw = build_message(&draft).unwrap();
+    assert_eq!(message_size(&draft), raw.len() as u64);
+    assert_eq!(
+        parse_mail(&raw).unwrap().ctype.mimetype,
+        "multipart/alternative"
+    );
+}
+
+#[test]
+fn files_are_read_and_typed_by_extension() {
+    let dir = std::env::temp_dir().join(format!("otto-attach-read-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("photo.JPG");
+    std::fs::write(&path, b"not really a jpeg").unwrap();
+
+    let attachment = Attachment::from_path(path.to_str().unwrap()).unwrap();
+    assert_eq!(attachment.filename, "photo.JPG");
+    assert_eq!(attachment.content_type, "image/jpeg");
+    assert_eq!(attachment.size(), 17);
+
+    assert!(Attachment::from_path(dir.to_str().unwrap()).is_err());
+    assert!(Attachment::from_path(dir.join("missing").to_str().unwrap()).is_err());
+    assert_eq!(
+        content_type_for("archive.tar.bz2"),
+        "application/octet-stream"
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn tab_completion_extends_to_the_common_prefix() {
+    let dir = std::env::temp_dir().join(format!("otto-attach-complete-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("reports")).unwrap();
+    std::fs::write(dir.join("report.pdf"), b"x").unwrap();
+    std::fs::write(dir.join("notes.md"), b"x").unwrap();
+    std::fs::write(dir.join(".hidden"), b"x").unwrap();
+    let base = format!("{}/", dir.display());
+
+    let both = complete_path(&format!("{}rep", base));
+    assert_eq!(both.input, format!("{}report", base));
+    assert_eq!(
+        both.candidates,
+        vec![format!("{}report.pdf", base), format!("{}reports/", base)]
+    );
+
+    let one = complete_path(&format!("{}reports", base));
+    assert_eq!(one.input, format!("{}reports/", base));
+
+    let all = complete_path(&base);
+    assert_eq!(all.candidates.len(), 3);
+    assert!(!all.candidates.iter().any(|c| c.contains(".hidden")));
+    assert_eq!(complete_path(&format!("{}.h", base)).candidates.len(), 1);
+
+    let none = complete_path(&format!("{}zzz", base));
+    assert_eq!(none.input, format!("{}zzz", base));
+    assert!(none.candidates.is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn recipients_split_on_commas_outside_quotes() {
+    assert_eq!(
+        split_recipients(r#"a@example.com, "Doe, Jane" <jane@example.com>,, "#),
+        vec![
+            "a@example.com".to_string(),
+            r#""Doe, Jane" <jane@example.com>"#.to_string()
+        ]
+    );
+    assert!(split_recipients("  ").is_empty());
+}
+
+#[test]
+fn completing_a_recipient_replaces_only_the_one_being_typed() {
+    let typed = r#"a@example.com, "Doe, Ja"#;
+    assert_eq!(current_recipient(typed), r#""Doe, Ja"#);
+    assert_eq!(current_recipient("a@example.com, bo"), "bo");
+    assert_eq!(current_recipient("a@example.com, "), "");
+    assert_eq!(
+        complete_recipient("a@example.com, bo", "Bob <bob@example.com>"),
+        "a@example.com, Bob <bob@example.com>, "
+    );
+    assert_eq!(
+        complete_recipient("bo", "bob@example.com"),
+        "bob@example.com, "
+    );
+}
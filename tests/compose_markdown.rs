@@ -0,0 +1,145 @@
+use mailparse::parse_mail;
+
+use otto::compose::{ComposeError, Draft, apply_signature, build_message, is_valid_addr_spec};
+use otto::types::Signature;
+
+#[test]
+fn markdown_draft_builds_multipart_alternative() {
+    let draft = Draft {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: Vec::new(),
+        subject: "Grüße".to_string(),
+        body_markdown: "Hello **world**\n\n- one\n- two\n".to_string(),
+        attachments: Vec::new(),
+    };
+
+    let raw = build_message(&draft).unwrap();
+    let parsed = parse_mail(&raw).expect("parse_mail");
+
+    assert_eq!(parsed.ctype.mimetype, "multipart/alternative");
+    assert_eq!(parsed.subparts.len(), 2);
+    assert_eq!(
+        parsed
+            .headers
+            .iter()
+            .find(|h| h.get_key() == "Subject")
+            .map(|h| h.get_value()),
+        Some("Grüße".to_string())
+    );
+
+    let text = &parsed.subparts[0];
+    assert_eq!(text.ctype.mimetype, "text/plain");
+    assert!(text.get_body().unwrap().contains("Hello **world**"));
+
+    let html = &parsed.subparts[1];
+    assert_eq!(html.ctype.mimetype, "text/html");
+    let html_body = html.get_body().unwrap();
+    assert!(html_body.contains("<strong>world</strong>"));
+    assert!(html_body.contains("<li>two</li>"));
+}
+
+#[test]
+fn signature_is_delimited_and_respects_quote_placement() {
+    let mut signature = Signature {
+        account_id: "me@example.com".to_string(),
+        alias: None,
+        body: "Berker\n".to_string(),
+        above_quote: false,
+    };
+    let quote = "> earlier message";
+
+    let below = apply_signature("Thanks!\n", Some(quote), &signature);
+    assert_eq!(below, "Thanks!\n\n> earlier message\n\n-- \nBerker\n");
+
+    signature.above_quote = true;
+    let above = apply_signature("Thanks!", Some(quote), &signature);
+    assert_eq!(above, "Thanks!\n\n-- \nBerker\n\n> earlier message\n");
+
+    assert_eq!(
+        apply_signature("Hi", None, &signature),
+        "Hi\n\n-- \nBerker\n"
+    );
+}
+
+#[test]
+fn outgoing_headers_are_generated_and_addresses_validated() {
+    let mut draft = Draft {
+        from: "Jane Doe <jane@example.com>".to_string(),
+        to: vec![
+            "Zoë <zoe@example.org>".to_string(),
+            "bob@example.net".to_string(),
+        ],
+        cc: Vec::new(),
+        subject: "Status".to_string(),
+        body_markdown: "Hi".to_string(),
+        attachments: Vec::new(),
+    };
+
+    let raw = build_message(&draft).unwrap();
+    assert!(raw.windows(2).all(|w| w[1] != b'\n' || w[0] == b'\r'));
+    let parsed = parse_mail(&raw).unwrap();
+    let header = |name: &str| {
+        parsed
+            .headers
+            .iter()
+            .find(|h| h.get_key().eq_ignore_ascii_case(name))
+            .map(|h| h.get_value())
+    };
+    assert!(header("Date").is_some_and(|d| mailparse::dateparse(&d).is_ok()));
+    assert!(
+        header("Message-ID").is_some_and(|id| id.starts_with('<') && id.ends_with("@example.com>"))
+    );
+    assert_eq!(header("MIME-Version").as_deref(), Some("1.0"));
+    assert!(header("User-Agent").is_some_and(|ua| ua.starts_with("otto/")));
+    assert_eq!(
+        header("To").as_deref(),
+        Some("Zoë <zoe@example.org>, bob@example.net")
+    );
+
+    draft.to = vec!["bob@example".to_string()];
+    assert_eq!(
+        build_message(&draft),
+        Err(ComposeError::InvalidAddress {
+            field: "To",
+            address: "bob@example".to_string()
+        })
+    );
+    draft.to.clear();
+    assert_eq!(build_message(&draft), Err(ComposeError::NoRecipients));
+    draft.to = vec!["bob@example.net".to_string()];
+    draft.subject = "Hi\r\nBcc: victim@example.com".to_string();
+    assert_eq!(
+        build_message(&draft),
+        Err(ComposeError::LineBreak { field: "Subject" })
+    );
+
+    assert!(is_valid_addr_spec("first.last+tag@mail.example.co.uk"));
+    assert!(!is_valid_addr_spec("two@@example.com"));
+    assert!(!is_valid_addr_spec("dot..dot@example.com"));
+    assert!(!is_valid_addr_spec("me@-example.com"));
+}
+
+#[test]
+fn long_subjects_fold_into_short_encoded_words() {
+    let subject = "Grüße aus Köln ".repeat(10);
+    let draft = Draft {
+        from: "me@example.com".to_string(),
+        to: vec!["you@example.com".to_string()],
+        cc: Vec::new(),
+        subject: subject.clone(),
+        body_markdown: "Hi".to_string(),
+        attachments: Vec::new(),
+    };
+    let raw = String::from_utf8(build_message(&draft).unwrap()).unwrap();
+    let head = raw.split("\r\n\r\n").next().unwrap();
+    assert!(head.split("\r\n").all(|line| line.len() <= 78));
+
+    let parsed = parse_mail(raw.as_bytes()).unwrap();
+    let decoded = parsed
+        .headers
+        .iter()
+        .find(|h| h.get_key() == "Subject")
+        .map(|h| h.get_value());
+    assert_eq!(decoded, Some(subject));
+}
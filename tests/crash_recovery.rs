@@ -0,0 +1,183 @@
+use otto::storage::Database;
+use otto::storage::db::FolderStateUpdate;
+use otto::storage::recovery::RewoundFolder;
+use otto::storage::store::BodyStorage;
+use otto::types::BodyRecord;
+
+mod common;
+
+fn body(id: &str) -> BodyRecord {
+    BodyRecord {
+        message_id: id.into(),
+        raw_rfc822: Some(b"Subject: hi\r\n\r\nhello\r\n".to_vec()),
+        sanitized_text: Some("hello".into()),
+        mime_summary: None,
+        attachments_json: None,
+        sanitized_at: None,
+        sanitizer_version: None,
+        degraded: false,
+    }
+}
+
+/// Commits two messages (UIDs 1 and 2) with the folder checkpointed at UID 10, the last
+/// batch covering `checkpoint_from_uid..=10`.
+async fn commit(db: &Database, folder: &str, status: &str, checkpoint_from_uid: Option<u32>) {
+    let messages = [
+        common::message(&format!("{folder}-1"), folder, 1),
+        common::message(&format!("{folder}-2"), folder, 2),
+    ];
+    let bodies = [body(&messages[0].id), body(&messages[1].id)];
+    db.commit_folder_batch(
+        "acct",
+        folder,
+        &messages,
+        &bodies,
+        &[],
+        &[],
+        &FolderStateUpdate {
+            uidvalidity: Some(7),
+            highest_uid: Some(10),
+            highestmodseq: Some(500),
+            exists_count: Some(10),
+            last_sync_ts: Some(1_700_000_000),
+            last_uid_scan_ts: None,
+            baseline_scan_uid: Some(10),
+            resume_modseq: None,
+            resume_uid: None,
+            checkpoint_from_uid,
+        },
+        status,
+        Some(500),
+        Some(10),
+    )
+    .await
+    .unwrap();
+}
+
+async fn open_db(name: &str) -> (std::path::PathBuf, Database) {
+    let dir = std::env::temp_dir().join(format!("otto-recovery-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    (dir, db)
+}
+
+#[tokio::test]
+async fn interrupted_writes_are_repaired_on_startup() {
+    let (dir, db) = open_db("writes").await;
+    assert!(db.recover_interrupted_writes().await.unwrap().is_empty());
+
+    // INBOX checkpointed a batch (5..=10) none of which was stored. Sent's batch (2..=10)
+    // landed in part; Drafts never checkpointed and merely ended above its stored UIDs; Archive
+    // finished its pass.
+    commit(&db, "INBOX", "in_progress", Some(5)).await;
+    commit(&db, "Sent", "in_progress", Some(2)).await;
+    commit(&db, "Drafts", "in_progress", None).await;
+    commit(&db, "Archive", "ok", Some(5)).await;
+
+    // A body left behind by a message delete that didn't cascade, and a message whose body
+    // row never landed.
+    let mut conn = db.pool().acquire().await.unwrap();
+    sqlx::query("PRAGMA foreign_keys = OFF")
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM messages WHERE id = 'INBOX-1'")
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    sqlx::query("PRAGMA foreign_keys = ON")
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    drop(conn);
+    sqlx::query("DELETE FROM bodies WHERE message_id = 'Archive-2'")
+        .execute(db.pool())
+        .await
+        .unwrap();
+
+    let report = db.recover_interrupted_writes().await.unwrap();
+    assert_eq!(report.orphan_bodies, 1);
+    assert_eq!(report.bodies_requeued, 1);
+    // Only the checkpoint committed without any of its batch is rewound, to just below it.
+    assert_eq!(
+        report.folders_rewound,
+        vec![RewoundFolder {
+            account_id: "acct".into(),
+            folder: "INBOX".into(),
+            checkpoint_uid: 10,
+            rewound_to: 4,
+        }]
+    );
+
+    let folders = db.list_folders("acct").await.unwrap();
+    let inbox = folders.iter().find(|f| f.name == "INBOX").unwrap();
+    assert_eq!(inbox.highest_uid, Some(4));
+    assert_eq!(inbox.baseline_scan_uid, Some(4));
+    assert_eq!(inbox.highestmodseq, None);
+    for name in ["Sent", "Drafts", "Archive"] {
+        let folder = folders.iter().find(|f| f.name == name).unwrap();
+        assert_eq!(
+            (folder.highest_uid, folder.highestmodseq),
+            (Some(10), Some(500)),
+            "{name}"
+        );
+    }
+
+    let status: String =
+        sqlx::query_scalar("SELECT body_status FROM messages WHERE id = 'Archive-2'")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+    assert_eq!(status, "pending");
+
+    assert!(db.recover_interrupted_writes().await.unwrap().is_empty());
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn missing_and_stray_blob_files_are_repaired_on_startup() {
+    let (dir, db) = open_db("blobs").await;
+    db.set_body_storage(BodyStorage::Hybrid { min_file_bytes: 0 });
+    commit(&db, "INBOX", "ok", None).await;
+    let blob_dir = dir.join("otto.blobs");
+    let files = || -> Vec<std::path::PathBuf> {
+        std::fs::read_dir(&blob_dir)
+            .unwrap()
+            .flat_map(|shard| std::fs::read_dir(shard.unwrap().path()).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .collect()
+    };
+    // Both messages share one body, so one file.
+    let [stored] = files().try_into().unwrap();
+    assert!(db.recover_interrupted_writes().await.unwrap().is_empty());
+
+    // The offloaded file went missing, and a crashed write left a file without a row: recent
+    // ones may still be committing, old ones are removed.
+    std::fs::remove_file(&stored).unwrap();
+    let stray = blob_dir.join("ab").join("ab-stray");
+    let fresh = blob_dir.join("ab").join("ab-fresh");
+    std::fs::create_dir_all(stray.parent().unwrap()).unwrap();
+    std::fs::write(&stray, b"partial").unwrap();
+    std::fs::write(&fresh, b"partial").unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&stray)
+        .unwrap()
+        .set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 60 * 60))
+        .unwrap();
+
+    let report = db.recover_interrupted_writes().await.unwrap();
+    assert_eq!(report.blob_files_missing, 1);
+    assert_eq!(report.bodies_requeued, 2);
+    assert_eq!(report.blob_files_removed, 1);
+    assert_eq!(files(), vec![fresh]);
+    let pending: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE body_status = 'pending'")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+    assert_eq!(pending, 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
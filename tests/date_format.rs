@@ -0,0 +1,64 @@
+use chrono::{TimeZone, Utc};
+use otto::timefmt::{DisplayTz, format_relative};
+
+#[test]
+fn recent_dates_render_relative() {
+    let tz = DisplayTz::parse("UTC").unwrap();
+    let now = Utc.with_ymd_and_hms(2026, 3, 10, 15, 0, 0).unwrap();
+    let at = |h, m| {
+        Some(
+            Utc.with_ymd_and_hms(2026, 3, 10, h, m, 0)
+                .unwrap()
+                .timestamp(),
+        )
+    };
+
+    let seconds_ago = Utc.with_ymd_and_hms(2026, 3, 10, 14, 59, 30).unwrap();
+    assert_eq!(
+        format_relative(Some(seconds_ago.timestamp()), now, tz),
+        "just now"
+    );
+    assert_eq!(format_relative(at(14, 20), now, tz), "40m ago");
+    assert_eq!(format_relative(at(13, 0), now, tz), "2h ago");
+
+    let yesterday = Utc.with_ymd_and_hms(2026, 3, 9, 23, 30, 0).unwrap();
+    assert_eq!(
+        format_relative(Some(yesterday.timestamp()), now, tz),
+        "Yesterday 23:30"
+    );
+    let last_week = Utc.with_ymd_and_hms(2026, 3, 6, 8, 5, 0).unwrap();
+    assert_eq!(
+        format_relative(Some(last_week.timestamp()), now, tz),
+        "Fri 08:05"
+    );
+    let older = Utc.with_ymd_and_hms(2026, 1, 2, 8, 5, 0).unwrap();
+    assert_eq!(
+        format_relative(Some(older.timestamp()), now, tz),
+        "Jan 02 08:05"
+    );
+    let last_year = Utc.with_ymd_and_hms(2025, 12, 31, 8, 5, 0).unwrap();
+    assert_eq!(
+        format_relative(Some(last_year.timestamp()), now, tz),
+        "2025-12-31 08:05"
+    );
+    assert_eq!(format_relative(None, now, tz), "Unknown");
+}
+
+#[test]
+fn calendar_days_follow_the_display_timezone() {
+    // 23:30 UTC on the 9th is already the 10th in Istanbul (UTC+3).
+    let now = Utc.with_ymd_and_hms(2026, 3, 10, 15, 0, 0).unwrap();
+    let ts = Some(
+        Utc.with_ymd_and_hms(2026, 3, 9, 23, 30, 0)
+            .unwrap()
+            .timestamp(),
+    );
+
+    let istanbul = DisplayTz::parse("Europe/Istanbul").unwrap();
+    assert_eq!(format_relative(ts, now, istanbul), "15h ago");
+    let utc = DisplayTz::parse("UTC").unwrap();
+    assert_eq!(format_relative(ts, now, utc), "Yesterday 23:30");
+
+    assert_eq!(DisplayTz::parse("local").unwrap(), DisplayTz::Local);
+    assert!(DisplayTz::parse("Mars/Olympus").is_err());
+}
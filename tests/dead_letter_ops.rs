@@ -0,0 +1,85 @@
+use otto::storage::Database;
+use otto::storage::ops::{MAX_OP_ATTEMPTS, MessageOp};
+use otto::types::{BodyStatus, MessageRecord};
+
+mod common;
+
+async fn folder(db: &Database) -> String {
+    db.load_messages("acct", 10).await.unwrap()[0]
+        .0
+        .folder
+        .clone()
+}
+
+#[tokio::test]
+async fn repeatedly_rejected_ops_are_dead_lettered_until_retried_or_dropped() {
+    let dir = std::env::temp_dir().join(format!("otto-dead-ops-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    let message = MessageRecord {
+        subject: Some("subject a".into()),
+        from: None,
+        body_status: BodyStatus::Pending,
+        ..common::message("a", "INBOX", 1)
+    };
+    db.commit_backfill_batch("acct", "INBOX", &[message], &[], &[], None)
+        .await
+        .unwrap();
+    db.apply_message_op("acct", &MessageOp::Move("Work".into()), &["a".into()])
+        .await
+        .unwrap();
+    let id = db.list_pending_ops("acct").await.unwrap()[0].id;
+
+    // Rejections below the limit keep the op queued for the next pass.
+    for _ in 1..MAX_OP_ATTEMPTS {
+        assert_eq!(
+            db.record_op_failures(&[id], "NO no such message")
+                .await
+                .unwrap(),
+            0
+        );
+    }
+    assert_eq!(
+        db.load_replayable_location_ops("acct").await.unwrap().len(),
+        1
+    );
+    assert_eq!(
+        db.record_op_failures(&[id], "NO no such message")
+            .await
+            .unwrap(),
+        1
+    );
+    assert!(
+        db.load_replayable_location_ops("acct")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let op = &db.list_pending_ops("acct").await.unwrap()[0];
+    assert_eq!(op.attempts, MAX_OP_ATTEMPTS);
+    assert_eq!(op.last_error.as_deref(), Some("NO no such message"));
+    assert!(op.dead_at.is_some());
+    // Dead-lettered ops keep their local effect.
+    assert_eq!(folder(&db).await, "Work");
+
+    assert_eq!(db.retry_dead_ops(&[id]).await.unwrap(), 1);
+    let op = &db.list_pending_ops("acct").await.unwrap()[0];
+    assert_eq!((op.attempts, op.dead_at), (0, None));
+    assert_eq!(
+        db.load_replayable_location_ops("acct").await.unwrap().len(),
+        1
+    );
+
+    // Dropping undoes the move.
+    for _ in 0..MAX_OP_ATTEMPTS {
+        db.record_op_failures(&[id], "NO still missing")
+            .await
+            .unwrap();
+    }
+    db.rollback_pending_ops(&[id]).await.unwrap();
+    assert!(db.list_pending_ops("acct").await.unwrap().is_empty());
+    assert_eq!(folder(&db).await, "INBOX");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,129 @@
+use base64::Engine;
+use proptest::prelude::*;
+
+use otto::encoded_words::{decode_mime_words, decode_quoted_printable_rfc2047};
+use otto::sanitize::clean_url;
+
+fn b_word(text: &str) -> String {
+    format!(
+        "=?UTF-8?B?{}?=",
+        base64::engine::general_purpose::STANDARD.encode(text)
+    )
+}
+
+fn q_word(text: &str) -> String {
+    let hex: String = text.bytes().map(|b| format!("={:02X}", b)).collect();
+    format!("=?utf-8?q?{}?=", hex)
+}
+
+/// Header-ish text biased towards the characters the decoders treat specially.
+fn headerish() -> impl Strategy<Value = String> {
+    prop::collection::vec(
+        prop_oneof![
+            Just("=?".to_string()),
+            Just("?=".to_string()),
+            Just("?".to_string()),
+            Just("=".to_string()),
+            Just("_".to_string()),
+            Just(" ".to_string()),
+            Just("Q".to_string()),
+            Just("B".to_string()),
+            Just("UTF-8".to_string()),
+            "[0-9a-fA-F]{1,2}",
+            any::<char>().prop_map(String::from),
+        ],
+        0..40,
+    )
+    .prop_map(|parts| parts.concat())
+}
+
+fn tracking_key() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("utm_source".to_string()),
+        Just("utm_campaign".to_string()),
+        Just("gclid".to_string()),
+        Just("fbclid".to_string()),
+        Just("mc_eid".to_string()),
+    ]
+}
+
+fn kept_key() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just("id".to_string()),
+        Just("page".to_string()),
+        Just("q".to_string()),
+        Just("view".to_string()),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn decoders_never_panic(text in headerish(), any_text in ".*") {
+        let _ = decode_mime_words(&text);
+        let _ = decode_mime_words(&any_text);
+        let _ = decode_quoted_printable_rfc2047(&text);
+        let _ = decode_quoted_printable_rfc2047(&any_text);
+    }
+
+    #[test]
+    fn plain_text_is_untouched(text in "[^=]*") {
+        prop_assert_eq!(decode_mime_words(&text), text);
+    }
+
+    #[test]
+    fn encoded_words_round_trip(
+        prefix in "[a-zA-Z0-9 ,.!]{0,10}",
+        text in any::<String>(),
+        other in any::<String>(),
+    ) {
+        let header = format!("{}{} {}", prefix, b_word(&text), q_word(&other));
+        prop_assert_eq!(decode_mime_words(&header), format!("{}{}{}", prefix, text, other));
+    }
+
+    #[test]
+    fn undecodable_words_are_kept(prefix in "[a-z ]{0,10}", body in "[A-Za-z0-9]{1,12}") {
+        let header = format!("{}=?UTF-8?X?{}?= tail", prefix, body);
+        prop_assert_eq!(decode_mime_words(&header), header);
+    }
+
+    #[test]
+    fn q_text_without_escapes_is_untouched(text in "[^=_]*") {
+        prop_assert_eq!(decode_quoted_printable_rfc2047(&text), Some(text));
+    }
+
+    #[test]
+    fn clean_url_never_panics(raw in ".*", path in "[a-zA-Z0-9/%?&=#._~-]*") {
+        let _ = clean_url(&raw);
+        let _ = clean_url(&format!("https://example.com/{}", path));
+    }
+
+    #[test]
+    fn clean_url_drops_tracking_params_only(
+        path in "[a-z0-9/]{0,20}",
+        kept in prop::collection::vec((kept_key(), "[a-zA-Z0-9]{1,8}"), 0..4),
+        tracking in prop::collection::vec((tracking_key(), "[a-zA-Z0-9]{1,8}"), 0..4),
+    ) {
+        let pairs: Vec<String> = kept
+            .iter()
+            .chain(tracking.iter())
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect();
+        let raw = if pairs.is_empty() {
+            format!("https://example.com/{}", path)
+        } else {
+            format!("https://example.com/{}?{}", path, pairs.join("&"))
+        };
+
+        let cleaned = clean_url(&raw);
+        if tracking.is_empty() {
+            prop_assert_eq!(&cleaned, &raw);
+        }
+        let url = url::Url::parse(&cleaned).expect("cleaned URL parses");
+        let remaining: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        prop_assert_eq!(remaining, kept);
+        prop_assert_eq!(clean_url(&cleaned), cleaned.clone());
+    }
+}
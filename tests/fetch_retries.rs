@@ -0,0 +1,56 @@
+use otto::storage::Database;
+
+mod common;
+
+#[tokio::test]
+async fn failures_accumulate_attempts_until_cleared() {
+    let dir = std::env::temp_dir().join(format!("otto-fetch-retries-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+
+    db.record_fetch_failures(
+        "acct",
+        "INBOX",
+        &[(7, "connection reset".into()), (9, "bad MIME".into())],
+    )
+    .await
+    .unwrap();
+    db.record_fetch_failures("acct", "INBOX", &[(7, "no FETCH response for UID".into())])
+        .await
+        .unwrap();
+
+    let retries = db.load_fetch_retries("acct", "INBOX").await.unwrap();
+    let summary: Vec<(u32, u32, Option<&str>)> = retries
+        .iter()
+        .map(|r| (r.uid, r.attempts, r.last_error.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (7, 2, Some("no FETCH response for UID")),
+            (9, 1, Some("bad MIME"))
+        ]
+    );
+    assert!(
+        db.load_fetch_retries("acct", "Sent")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    assert_eq!(
+        db.clear_fetch_retries("acct", "INBOX", &[7]).await.unwrap(),
+        1
+    );
+    let left: Vec<u32> = db
+        .load_fetch_retries("acct", "INBOX")
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.uid)
+        .collect();
+    assert_eq!(left, vec![9]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,158 @@
+use otto::storage::Database;
+use otto::storage::ops::{ConflictResolution, FlagConflictPolicy, MessageOp, is_remote_conflict};
+use otto::types::{BodyStatus, MessageRecord};
+
+mod common;
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+#[test]
+fn policies_settle_server_changes_against_queued_ops() {
+    // Locally marked read and labelled; meanwhile the server flagged it.
+    let server_flags = strings(&["Flagged"]);
+    let server_labels = strings(&["\\Inbox"]);
+    let pending = vec![
+        MessageOp::MarkRead,
+        MessageOp::AddLabel("Receipts".to_string()),
+    ];
+
+    assert_eq!(
+        FlagConflictPolicy::ServerWins.resolve(&server_flags, &server_labels, &pending),
+        Some((server_flags.clone(), server_labels.clone()))
+    );
+    assert_eq!(
+        FlagConflictPolicy::LocalWins.resolve(&server_flags, &server_labels, &pending),
+        None
+    );
+    assert_eq!(
+        FlagConflictPolicy::Merge.resolve(&server_flags, &server_labels, &pending),
+        Some((
+            strings(&["Flagged", "Seen"]),
+            strings(&["\\Inbox", "Receipts"])
+        ))
+    );
+    // Already-present values are not duplicated.
+    assert_eq!(
+        FlagConflictPolicy::Merge.resolve(&strings(&["Seen"]), &[], &[MessageOp::MarkRead]),
+        Some((strings(&["Seen"]), Vec::new()))
+    );
+}
+
+#[test]
+fn policy_names_parse() {
+    assert_eq!(
+        FlagConflictPolicy::parse("server-wins").unwrap(),
+        FlagConflictPolicy::ServerWins
+    );
+    assert_eq!(
+        FlagConflictPolicy::parse(" Local_Wins ").unwrap(),
+        FlagConflictPolicy::LocalWins
+    );
+    assert_eq!(
+        FlagConflictPolicy::parse("merge").unwrap(),
+        FlagConflictPolicy::Merge
+    );
+    assert_eq!(
+        FlagConflictPolicy::parse("flag").unwrap(),
+        FlagConflictPolicy::Flag
+    );
+    assert_eq!(FlagConflictPolicy::default(), FlagConflictPolicy::Flag);
+    assert!(FlagConflictPolicy::parse("newest").is_err());
+}
+
+#[test]
+fn only_remote_changes_the_queued_ops_would_overwrite_conflict() {
+    let snapshot_flags = strings(&[]);
+    let snapshot_labels = strings(&["\\Inbox"]);
+    let snapshot = (&snapshot_flags[..], &snapshot_labels[..]);
+    let pending = [MessageOp::MarkRead];
+    let check = |flags: &[&str], labels: &[&str]| {
+        is_remote_conflict(snapshot, (&strings(flags), &strings(labels)), &pending)
+    };
+
+    // Unchanged on the server, or the server already made the same change.
+    assert!(!check(&[], &["\\Inbox"]));
+    assert!(!check(&["\\Seen"], &["\\Inbox"]));
+    // Starred or relabelled elsewhere while our mark-read was queued.
+    assert!(check(&["Flagged"], &["\\Inbox"]));
+    assert!(check(&["Seen"], &["Receipts"]));
+}
+
+#[tokio::test]
+async fn held_ops_skip_replay_until_settled() {
+    let dir = std::env::temp_dir().join(format!("otto-conflicts-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+
+    let message = |id: &str, uid: u32| MessageRecord {
+        subject: Some(format!("subject {id}")),
+        from: None,
+        labels: strings(&["\\Inbox"]),
+        body_status: BodyStatus::Pending,
+        ..common::message(id, "INBOX", uid)
+    };
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("a", 1), message("b", 2)],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+    db.apply_message_op("acct", &MessageOp::MarkRead, &["a".into(), "b".into()])
+        .await
+        .unwrap();
+
+    let pending = db
+        .load_pending_flag_ops("acct", "INBOX", &[1, 2])
+        .await
+        .unwrap();
+    assert_eq!(
+        pending[0].snapshot,
+        Some((Vec::new(), strings(&["\\Inbox"])))
+    );
+
+    let server_flags = strings(&["Flagged"]);
+    for p in &pending {
+        db.mark_op_conflicts(&[p.id], &server_flags, &strings(&["\\Inbox"]))
+            .await
+            .unwrap();
+    }
+    assert!(
+        db.load_replayable_flag_ops("acct")
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    let conflicts = db.list_op_conflicts("acct").await.unwrap();
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].local_flags, strings(&["Seen"]));
+    assert_eq!(conflicts[0].server_flags, server_flags);
+    assert_eq!(conflicts[0].subject.as_deref(), Some("subject a"));
+
+    // Keep local for "a": replayed on the next pass.
+    db.resolve_op_conflicts(&[conflicts[0].id], ConflictResolution::KeepLocal)
+        .await
+        .unwrap();
+    let replayable = db.load_replayable_flag_ops("acct").await.unwrap();
+    assert_eq!(replayable.len(), 1);
+    assert_eq!(replayable[0].id, conflicts[0].id);
+
+    // Keep server for "b": its flags become the server's and the op is gone.
+    db.resolve_op_conflicts(&[conflicts[1].id], ConflictResolution::KeepServer)
+        .await
+        .unwrap();
+    assert!(db.list_op_conflicts("acct").await.unwrap().is_empty());
+    assert_eq!(db.list_pending_ops("acct").await.unwrap().len(), 1);
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    let b = &loaded.iter().find(|(m, _)| m.id == "b").unwrap().0;
+    assert_eq!(b.flags, server_flags);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
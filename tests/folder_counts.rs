@@ -0,0 +1,185 @@
+use otto::imap::ImapClient;
+use otto::status;
+use otto::storage::Database;
+use otto::storage::ops::MessageOp;
+use otto::types::{Account, BodyStatus, FolderCounts, MessageRecord};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+mod common;
+
+fn account(port: u16) -> Account {
+    let mut account = common::local_account("acct", port);
+    account.settings.folders = vec!["INBOX".into(), "Archive".into()];
+    account
+}
+
+/// Logs in advertising `capabilities`, then answers each command with `reply(command)` (the
+/// untagged lines and the tagged completion) until the client hangs up; returns the commands.
+async fn counts_server(
+    capabilities: &'static str,
+    reply: fn(&str) -> &'static str,
+) -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap();
+        write
+            .write_all(format!("* CAPABILITY {capabilities}\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+
+        let mut commands = Vec::new();
+        while let Ok(Some(command)) = lines.next_line().await {
+            let (tag, rest) = command.split_once(' ').unwrap();
+            let response = reply(rest).replace("TAG", tag);
+            write.write_all(response.as_bytes()).await.unwrap();
+            commands.push(rest.to_string());
+        }
+        commands
+    });
+    (port, server)
+}
+
+fn folders() -> Vec<String> {
+    vec!["INBOX".into(), "Archive".into(), "Gone".into()]
+}
+
+#[tokio::test]
+async fn counts_come_from_list_status_or_one_status_per_folder() {
+    let (port, server) = counts_server("IMAP4rev1 LIST-STATUS", |_| {
+        "* LIST () \"/\" \"INBOX\"\r\n\
+         * STATUS \"INBOX\" (MESSAGES 120 UNSEEN 4)\r\n\
+         * LIST (\\Noselect) \"/\" \"[Gmail]\"\r\n\
+         * LIST () \"/\" \"Archive\"\r\n\
+         * STATUS \"Archive\" (MESSAGES 7 UNSEEN 0)\r\n\
+         TAG OK LIST completed\r\n"
+    })
+    .await;
+    let mut session = ImapClient::connect(&account(port), "token").await.unwrap();
+    assert!(session.caps().list_status);
+    let counts = ImapClient::folder_counts(&mut session, &folders(), 50)
+        .await
+        .unwrap();
+    assert_eq!(
+        counts,
+        vec![
+            FolderCounts {
+                folder: "INBOX".into(),
+                unseen: 4,
+                messages: 120,
+                checked_at: 50,
+            },
+            FolderCounts {
+                folder: "Archive".into(),
+                unseen: 0,
+                messages: 7,
+                checked_at: 50,
+            },
+        ]
+    );
+    drop(session);
+    assert_eq!(
+        server.await.unwrap(),
+        vec!["LIST \"\" * RETURN (STATUS (MESSAGES UNSEEN))"]
+    );
+
+    let (port, server) = counts_server("IMAP4rev1", |command| {
+        if command.contains("INBOX") {
+            "* STATUS INBOX (MESSAGES 3 UNSEEN 1)\r\nTAG OK STATUS completed\r\n"
+        } else if command.contains("Archive") {
+            "* STATUS Archive (MESSAGES 0 UNSEEN 0)\r\nTAG OK STATUS completed\r\n"
+        } else {
+            "TAG NO no such mailbox\r\n"
+        }
+    })
+    .await;
+    let mut session = ImapClient::connect(&account(port), "token").await.unwrap();
+    let counts = ImapClient::folder_counts(&mut session, &folders(), 50)
+        .await
+        .unwrap();
+    let summary: Vec<_> = counts
+        .iter()
+        .map(|c| (c.folder.as_str(), c.unseen, c.messages))
+        .collect();
+    assert_eq!(summary, vec![("INBOX", 1, 3), ("Archive", 0, 0)]);
+    drop(session);
+    assert_eq!(
+        server.await.unwrap(),
+        vec![
+            "STATUS \"INBOX\" (MESSAGES UNSEEN)",
+            "STATUS \"Archive\" (MESSAGES UNSEEN)",
+            "STATUS \"Gone\" (MESSAGES UNSEEN)",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn status_uses_stored_counts_unless_local_changes_are_queued() {
+    let dir = std::env::temp_dir().join(format!("otto-folder-counts-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let account = account(993);
+    db.save_account(&account).await.unwrap();
+    let message = MessageRecord {
+        from: None,
+        body_status: BodyStatus::Pending,
+        ..common::message("a", "INBOX", 1)
+    };
+    db.commit_backfill_batch("acct", "INBOX", &[message], &[], &[], None)
+        .await
+        .unwrap();
+
+    let counts = |unseen, messages| FolderCounts {
+        folder: "INBOX".into(),
+        unseen,
+        messages,
+        checked_at: 1_700_000_000,
+    };
+    db.save_folder_counts("acct", &[counts(4, 120)])
+        .await
+        .unwrap();
+    db.save_folder_counts(
+        "acct",
+        &[FolderCounts {
+            folder: "Archive".into(),
+            ..counts(0, 7)
+        }],
+    )
+    .await
+    .unwrap();
+    let stored = db.load_folder_counts("acct").await.unwrap();
+    assert_eq!(stored["INBOX"], counts(4, 120));
+    assert_eq!(stored["Archive"].messages, 7);
+
+    let statuses = status::collect(&db, std::slice::from_ref(&account), 1_700_000_000)
+        .await
+        .unwrap();
+    assert_eq!(statuses[0].unread["INBOX"], 4);
+    assert_eq!(statuses[0].total["INBOX"], 120);
+
+    // A read the server hasn't seen yet shows in the cached counts.
+    db.apply_message_op("acct", &MessageOp::MarkRead, &["a".into()])
+        .await
+        .unwrap();
+    let statuses = status::collect(&db, &[account], 1_700_000_000)
+        .await
+        .unwrap();
+    assert_eq!(statuses[0].unread["INBOX"], 0);
+    assert_eq!(statuses[0].total["INBOX"], 120);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
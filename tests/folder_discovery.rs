@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use otto::onboarding::{select_folders, trash_and_spam};
+use otto::storage::Database;
+use otto::sync::SyncEngine;
+use otto::types::{BodyFetch, Credential, FolderPolicy, FolderRole, MailboxInfo, Provider};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+mod common;
+
+fn mailbox(name: &str, attributes: &[&str]) -> MailboxInfo {
+    MailboxInfo {
+        name: name.into(),
+        delimiter: Some("/".into()),
+        attributes: attributes.iter().map(|a| a.to_string()).collect(),
+    }
+}
+
+#[test]
+fn onboarding_keeps_configured_folders_and_maps_localized_defaults() {
+    let discovered = vec![
+        mailbox("INBOX", &[]),
+        mailbox("[Gmail]", &["\\Noselect"]),
+        mailbox("[Gmail]/Gesendet", &["\\Sent"]),
+        mailbox("[Gmail]/Trash", &["\\Trash"]),
+    ];
+    let configured: Vec<String> = ["inbox", "[Gmail]/Sent Mail", "[Gmail]/Trash", "[Gmail]"]
+        .iter()
+        .map(|f| f.to_string())
+        .collect();
+    assert_eq!(
+        select_folders(&configured, &discovered),
+        vec![
+            "inbox".to_string(),
+            "[Gmail]/Gesendet".to_string(),
+            "[Gmail]/Trash".to_string()
+        ]
+    );
+}
+
+#[test]
+fn trash_and_spam_follow_special_use_roles() {
+    let discovered = vec![
+        mailbox("INBOX", &[]),
+        mailbox("[Gmail]/Papierkorb", &["\\Trash"]),
+    ];
+    let folders: Vec<String> = ["INBOX", "[Gmail]/Papierkorb", "[Gmail]/Spam"]
+        .iter()
+        .map(|f| f.to_string())
+        .collect();
+    // Spam is not advertised, so its Gmail default name is used.
+    assert_eq!(
+        trash_and_spam(&folders, &discovered),
+        vec!["[Gmail]/Papierkorb".to_string(), "[Gmail]/Spam".to_string()]
+    );
+    assert!(trash_and_spam(&folders[..1], &discovered).is_empty());
+}
+
+#[tokio::test]
+async fn rediscovery_replaces_attributes_without_touching_sync_state() {
+    let dir = std::env::temp_dir().join(format!("otto-discovery-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut account = common::account("acct");
+    account.settings.folders = vec!["INBOX".into()];
+    account.settings.prefetch_recent = 10;
+    db.save_account(&account).await.unwrap();
+
+    db.record_discovered_folders("acct", &[mailbox("INBOX", &[]), mailbox("Old", &[])])
+        .await
+        .unwrap();
+    sqlx::query("UPDATE folders SET uidvalidity = 7 WHERE name = 'INBOX'")
+        .execute(db.pool())
+        .await
+        .unwrap();
+    db.record_discovered_folders(
+        "acct",
+        &[
+            mailbox("INBOX", &["\\Marked"]),
+            mailbox("[Gmail]/Papierkorb", &["\\HasNoChildren", "\\Trash"]),
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        db.role_folder("acct", FolderRole::Trash).await.unwrap(),
+        "[Gmail]/Papierkorb"
+    );
+    assert_eq!(
+        db.role_folder("acct", FolderRole::All).await.unwrap(),
+        "[Gmail]/All Mail"
+    );
+
+    assert_eq!(
+        db.list_discovered_folders("acct").await.unwrap(),
+        vec![
+            mailbox("INBOX", &["\\Marked"]),
+            mailbox("[Gmail]/Papierkorb", &["\\HasNoChildren", "\\Trash"]),
+        ]
+    );
+    let folders = db.list_folders("acct").await.unwrap();
+    let inbox = folders.iter().find(|f| f.name == "INBOX").unwrap();
+    assert_eq!(inbox.uidvalidity, Some(7));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// A German Dovecot-style server: logs anyone in and lists `Gesendet` as `\\Sent` and
+/// `Papierkorb` as `\\Trash`, with no `Junk` at all.
+async fn localized_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"* OK ready\r\n").await.unwrap();
+            while let Ok(Some(command)) = lines.next_line().await {
+                let (tag, verb) = command.split_once(' ').unwrap();
+                let mut reply = String::new();
+                if verb == "CAPABILITY" {
+                    reply.push_str("* CAPABILITY IMAP4rev1 AUTH=PLAIN\r\n");
+                } else if verb == "AUTHENTICATE PLAIN" {
+                    write.write_all(b"+ \r\n").await.unwrap();
+                    lines.next_line().await.unwrap();
+                } else if verb == "LIST \"\" \"\"" {
+                    reply.push_str("* LIST (\\Noselect) \"/\" \"\"\r\n");
+                } else if verb.starts_with("LIST") {
+                    reply.push_str(
+                        "* LIST (\\HasNoChildren) \"/\" INBOX\r\n\
+                         * LIST (\\HasNoChildren \\Sent) \"/\" Gesendet\r\n\
+                         * LIST (\\HasNoChildren \\Trash) \"/\" Papierkorb\r\n",
+                    );
+                } else if verb == "LOGOUT" {
+                    reply.push_str("* BYE\r\n");
+                }
+                reply.push_str(&format!("{tag} OK done\r\n"));
+                write.write_all(reply.as_bytes()).await.unwrap();
+            }
+        }
+    });
+    port
+}
+
+#[tokio::test]
+async fn first_discovery_moves_default_folders_to_special_use_mailboxes() {
+    let dir = std::env::temp_dir().join(format!("otto-discovery-roles-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Arc::new(Database::open_at(dir.join("otto.db")).await.unwrap());
+    let mut account = common::local_account("acct", localized_server().await);
+    account.provider = Provider::Imap;
+    account.settings.credential = Credential::PasswordCommand {
+        command: "echo pw".into(),
+    };
+    // Added while offline: the Gmail defaults were never checked against the server.
+    account.settings.folders = vec![
+        "INBOX".into(),
+        "[Gmail]/Sent Mail".into(),
+        "Trash".into(),
+        "[Gmail]/Spam".into(),
+    ];
+    account.settings.folder_policies.insert(
+        "Trash".into(),
+        FolderPolicy {
+            body_fetch: BodyFetch::MetadataOnly,
+            ..FolderPolicy::default()
+        },
+    );
+    db.save_account(&account).await.unwrap();
+
+    SyncEngine::new(db.clone(), 1)
+        .discover_folders(&mut account)
+        .await
+        .unwrap();
+    let stored = db.list_accounts().await.unwrap().remove(0);
+    assert_eq!(stored.settings.folders, account.settings.folders);
+    // Spam has no special-use mailbox, so it stays as configured.
+    assert_eq!(
+        stored.settings.folders,
+        vec!["INBOX", "Gesendet", "Papierkorb", "[Gmail]/Spam"]
+    );
+    assert_eq!(
+        stored.settings.folder_policy("Papierkorb").body_fetch,
+        BodyFetch::MetadataOnly
+    );
+    assert!(!stored.settings.folder_policies.contains_key("Trash"));
+
+    // Nothing is left to move once the folders are the server's own.
+    let discovered = db.list_discovered_folders("acct").await.unwrap();
+    assert!(!account.settings.apply_special_use(&discovered));
+    let _ = std::fs::remove_dir_all(&dir);
+}
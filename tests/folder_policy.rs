@@ -0,0 +1,93 @@
+use chrono::NaiveDate;
+use otto::storage::Database;
+use otto::types::{
+    AccountSettings, BodyFetch, BodyRecord, BodyStatus, FolderPolicy, MessageRecord,
+};
+
+mod common;
+
+#[test]
+fn folder_policies_override_account_defaults() {
+    let account_cutoff = NaiveDate::from_ymd_opt(2025, 12, 1).unwrap();
+    let spam_cutoff = NaiveDate::from_ymd_opt(2026, 3, 1).unwrap();
+    let mut settings = AccountSettings::with_defaults(account_cutoff);
+
+    // Missing fields in stored JSON fall back to the defaults (enabled, full bodies).
+    let spam: FolderPolicy =
+        serde_json::from_str(r#"{"cutoff_since":"2026-03-01","body_fetch":"metadata_only"}"#)
+            .unwrap();
+    assert!(spam.enabled);
+    settings
+        .folder_policies
+        .insert("[Gmail]/Spam".to_string(), spam);
+    settings.folder_policies.insert(
+        "[Gmail]/Trash".to_string(),
+        FolderPolicy {
+            enabled: false,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(settings.folder_cutoff("INBOX"), account_cutoff);
+    assert_eq!(settings.folder_cutoff("[Gmail]/Spam"), spam_cutoff);
+    assert_eq!(
+        settings.folder_policy("[Gmail]/Spam").body_fetch,
+        BodyFetch::MetadataOnly
+    );
+    assert_eq!(settings.folder_policy("INBOX").body_fetch, BodyFetch::Full);
+
+    let enabled: Vec<&String> = settings.enabled_folders().collect();
+    assert_eq!(enabled, ["INBOX", "[Gmail]/Sent Mail", "[Gmail]/Spam"]);
+}
+
+#[tokio::test]
+async fn metadata_only_folders_drop_cached_bodies() {
+    let dir = std::env::temp_dir().join(format!("otto-folder-policy-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+
+    let message = |id: &str, folder: &str, uid: u32| MessageRecord {
+        subject: Some(format!("subject {id}")),
+        from: None,
+        ..common::message(id, folder, uid)
+    };
+    let body = |id: &str| BodyRecord {
+        message_id: id.into(),
+        raw_rfc822: Some(b"Subject: hi\r\n\r\nclick here".to_vec()),
+        sanitized_text: Some("click here".into()),
+        mime_summary: None,
+        attachments_json: None,
+        sanitized_at: Some(1_700_000_000),
+        sanitizer_version: Some(1),
+        degraded: false,
+    };
+    for (id, folder) in [("kept", "INBOX"), ("spam", "[Gmail]/Spam")] {
+        db.commit_backfill_batch(
+            "acct",
+            folder,
+            &[message(id, folder, 1)],
+            &[body(id)],
+            &[],
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    assert_eq!(
+        db.drop_folder_bodies("acct", "[Gmail]/Spam").await.unwrap(),
+        1
+    );
+    for (message, body) in db.load_messages("acct", 10).await.unwrap() {
+        if message.id == "spam" {
+            assert_eq!(message.body_status, BodyStatus::Pending);
+            assert!(body.is_none());
+        } else {
+            assert_eq!(message.body_status, BodyStatus::Full);
+            assert!(body.is_some());
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,693 @@
+use std::sync::Arc;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use otto::imap::{
+    AppendMessage, ImapClient, ProtocolTrace, ServerCaps, check_tls_policy, supported_cipher_suites,
+};
+use otto::storage::Database;
+use otto::types::{Account, ImapEndpoint, ServerIdentity, TlsMode, TlsPolicy, TlsVersion};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::TcpListener;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+mod common;
+
+fn account(imap: ImapEndpoint) -> Account {
+    let mut account = common::account("bridge");
+    account.email = "me@proton.me".into();
+    account.settings.imap = imap;
+    account
+}
+
+/// Answers the CAPABILITY probe the client sends right after logging in.
+async fn answer_capability(
+    lines: &mut Lines<BufReader<OwnedReadHalf>>,
+    write: &mut OwnedWriteHalf,
+    capabilities: &str,
+) {
+    let command = lines.next_line().await.unwrap().unwrap();
+    let (tag, verb) = command.split_once(' ').unwrap();
+    assert_eq!(verb, "CAPABILITY");
+    write
+        .write_all(format!("* CAPABILITY {capabilities}\r\n{tag} OK done\r\n").as_bytes())
+        .await
+        .unwrap();
+}
+
+#[test]
+fn server_caps_gate_extensions() {
+    let gmail = ServerCaps::from_names(["IMAP4rev1", "X-GM-EXT-1", "condstore", "MOVE", "UIDPLUS"]);
+    assert!(gmail.gmail && gmail.condstore && gmail.move_ext && gmail.uidplus);
+    assert!(!gmail.qresync);
+    assert_eq!(
+        gmail.fetch_query("UID FLAGS"),
+        "(UID FLAGS X-GM-MSGID X-GM-THRID X-GM-LABELS)"
+    );
+
+    // QRESYNC servers support CONDSTORE even when they only list QRESYNC.
+    let dovecot = ServerCaps::from_names(["IMAP4rev1", "QRESYNC", "AUTH=PLAIN"]);
+    assert!(dovecot.qresync && dovecot.condstore && !dovecot.gmail);
+    assert_eq!(dovecot.fetch_query("UID FLAGS"), "(UID FLAGS)");
+    assert_eq!(dovecot.describe(), "CONDSTORE QRESYNC");
+    assert!(ServerCaps::from_names(["compress=deflate"]).compress_deflate);
+    assert!(ServerCaps::from_names(["QUOTA=RES-STORAGE"]).quota);
+    assert_eq!(ServerCaps::default().describe(), "none");
+}
+
+#[test]
+fn endpoint_settings_parse_and_default_to_gmail() {
+    let gmail = ImapEndpoint::default();
+    assert_eq!(
+        (gmail.host.as_str(), gmail.port, gmail.tls),
+        ("imap.gmail.com", 993, TlsMode::Tls)
+    );
+    assert!(!gmail.is_loopback());
+
+    for host in ["localhost", "127.0.0.1", "::1", "[::1]"] {
+        let endpoint = ImapEndpoint {
+            host: host.into(),
+            ..ImapEndpoint::default()
+        };
+        assert!(endpoint.is_loopback(), "{host}");
+    }
+
+    assert_eq!(TlsMode::parse("STARTTLS").unwrap(), TlsMode::Starttls);
+    assert_eq!(TlsMode::Starttls.default_port(), 143);
+    assert!(TlsMode::parse("ssl3").is_err());
+
+    let colons = "AB:".repeat(31) + "AB";
+    assert_eq!(
+        ImapEndpoint::parse_fingerprint(&colons).unwrap(),
+        "ab".repeat(32)
+    );
+    assert!(ImapEndpoint::parse_fingerprint("abcd").is_err());
+
+    // Accounts saved before the endpoint existed store `{}`.
+    let legacy: ImapEndpoint = serde_json::from_str("{}").unwrap();
+    assert_eq!(legacy, gmail);
+}
+
+#[tokio::test]
+async fn plain_mode_is_refused_for_remote_hosts() {
+    let remote = account(ImapEndpoint {
+        host: "mail.example.com".into(),
+        port: 143,
+        tls: TlsMode::Plain,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    });
+    let err = ImapClient::connect(&remote, "token").await.err().unwrap();
+    assert!(
+        err.to_string().contains("only allowed for localhost"),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn plain_mode_authenticates_against_a_local_bridge() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Bridge ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        assert!(command.ends_with("AUTHENTICATE XOAUTH2"), "{command}");
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        answer_capability(&mut lines, &mut write, "IMAP4rev1 CONDSTORE MOVE").await;
+        command
+    });
+
+    let bridge = account(ImapEndpoint {
+        host: "127.0.0.1".into(),
+        port,
+        tls: TlsMode::Plain,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    });
+    let session = ImapClient::connect(&bridge, "token").await.unwrap();
+    // Probed once after login and kept with the connection.
+    assert_eq!(
+        *session.caps(),
+        ServerCaps {
+            condstore: true,
+            move_ext: true,
+            ..ServerCaps::default()
+        }
+    );
+    drop(session);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn compression_is_negotiated_when_advertised() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Bridge ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        answer_capability(&mut lines, &mut write, "IMAP4rev1 COMPRESS=DEFLATE").await;
+        let command = lines.next_line().await.unwrap().unwrap();
+        let (tag, verb) = command.split_once(' ').unwrap();
+        assert_eq!(verb, "COMPRESS DEFLATE");
+        write
+            .write_all(format!("{tag} OK compressing\r\n").as_bytes())
+            .await
+            .unwrap();
+
+        // From here on both directions are raw DEFLATE.
+        let mut read = lines.into_inner().into_inner();
+        let mut inflate = Decompress::new(false);
+        let mut command = Vec::with_capacity(1024);
+        while !command.ends_with(b"\r\n") {
+            let mut chunk = [0u8; 1024];
+            let n = read.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "connection closed before a full command");
+            inflate
+                .decompress_vec(&chunk[..n], &mut command, FlushDecompress::None)
+                .unwrap();
+        }
+        let command = String::from_utf8(command).unwrap();
+        let (tag, verb) = command.trim_end().split_once(' ').unwrap();
+        assert_eq!(verb, "NOOP");
+        let mut deflate = Compress::new(Compression::default(), false);
+        let mut reply = Vec::with_capacity(1024);
+        deflate
+            .compress_vec(
+                format!("{tag} OK noop\r\n").as_bytes(),
+                &mut reply,
+                FlushCompress::Sync,
+            )
+            .unwrap();
+        write.write_all(&reply).await.unwrap();
+    });
+
+    let bridge = account(ImapEndpoint {
+        host: "127.0.0.1".into(),
+        port,
+        tls: TlsMode::Plain,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    });
+    let mut session = ImapClient::connect(&bridge, "token").await.unwrap();
+    assert!(session.caps().compress_deflate);
+    assert!(session.get_ref().get_ref().is_compressed());
+    session.noop().await.unwrap();
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn id_names_the_client_and_keeps_the_server_answer() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Coremail ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        answer_capability(&mut lines, &mut write, "IMAP4rev1 ID").await;
+        let command = lines.next_line().await.unwrap().unwrap();
+        let (tag, verb) = command.split_once(' ').unwrap();
+        write
+            .write_all(
+                format!("* ID (\"name\" \"Coremail Imap\" \"vendor\" \"Mailtech\")\r\n{tag} OK ID completed\r\n")
+                    .as_bytes(),
+            )
+            .await
+            .unwrap();
+        verb.to_string()
+    });
+
+    let bridge = account(ImapEndpoint {
+        host: "127.0.0.1".into(),
+        port,
+        tls: TlsMode::Plain,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    });
+    let session = ImapClient::connect(&bridge, "token").await.unwrap();
+    assert!(session.caps().id);
+    let fields = session.server_id().unwrap().clone();
+    drop(session);
+    assert_eq!(
+        server.await.unwrap(),
+        format!(
+            "ID (\"name\" \"otto\" \"version\" \"{}\")",
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+
+    // Stored per account for `otto imap-server`.
+    let dir = std::env::temp_dir().join(format!("otto-server-id-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let identity = ServerIdentity {
+        account_id: "bridge".into(),
+        fields,
+        fetched_at: 1_700_000_000,
+    };
+    db.save_server_identity(&identity).await.unwrap();
+    let stored = db.load_server_identity("bridge").await.unwrap().unwrap();
+    assert_eq!(stored, identity);
+    assert_eq!(stored.describe(), "name=Coremail Imap, vendor=Mailtech");
+    assert_eq!(db.load_server_identity("other").await.unwrap(), None);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn protocol_trace_records_the_conversation_without_credentials() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Bridge ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        answer_capability(&mut lines, &mut write, "IMAP4rev1").await;
+        credentials
+    });
+
+    let path = std::env::temp_dir().join(format!("otto-trace-{}.log", std::process::id()));
+    let trace = Arc::new(ProtocolTrace::create(&path).unwrap());
+    let bridge = account(ImapEndpoint {
+        host: "127.0.0.1".into(),
+        port,
+        tls: TlsMode::Plain,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    });
+    let session = ImapClient::connect_traced(&bridge, "secret-token", Some(trace))
+        .await
+        .unwrap();
+    let credentials = server.await.unwrap();
+    drop(session);
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    let lines: Vec<&str> = log
+        .lines()
+        .map(|line| line.trim_start().split_once(' ').unwrap().1)
+        .collect();
+    assert_eq!(lines[0], "S: * OK Bridge ready");
+    assert!(lines[1].starts_with("C: ") && lines[1].ends_with("AUTHENTICATE XOAUTH2"));
+    assert_eq!(lines[2], "S: + ");
+    assert_eq!(lines[3], "C: <redacted>");
+    assert!(lines[4].ends_with("OK authenticated"));
+    assert!(!log.contains(&credentials));
+}
+
+#[tokio::test]
+async fn an_extra_ca_bundle_is_trusted_next_to_the_system_roots() {
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use tokio_rustls::TlsAcceptor;
+    use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+    let server_key = KeyPair::generate().unwrap();
+    let server_cert = CertificateParams::new(vec!["localhost".to_string()])
+        .unwrap()
+        .signed_by(&server_key, &ca, &ca_key)
+        .unwrap();
+    let ca_file = std::env::temp_dir().join(format!("otto-ca-{}.pem", std::process::id()));
+    std::fs::write(&ca_file, ca.pem()).unwrap();
+    assert_eq!(otto::imap::load_ca_file(&ca_file).unwrap().len(), 1);
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![Certificate(server_cert.der().to_vec())],
+            PrivateKey(server_key.serialize_der()),
+        )
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        // The first client only trusts the system roots and gives up during the handshake.
+        let (socket, _) = listener.accept().await.unwrap();
+        assert!(acceptor.accept(socket).await.is_err());
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let stream = acceptor.accept(socket).await.unwrap();
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Internal ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write
+            .write_all(format!("* CAPABILITY IMAP4rev1\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+    });
+
+    let mut internal = account(ImapEndpoint {
+        host: "localhost".into(),
+        port,
+        tls: TlsMode::Tls,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    });
+    let err = ImapClient::connect(&internal, "token").await.err().unwrap();
+    assert!(format!("{err:#}").contains("TLS"), "{err:#}");
+
+    internal.settings.imap.ca_file = Some(ca_file.clone());
+    let session = ImapClient::connect(&internal, "token").await.unwrap();
+    drop(session);
+    server.await.unwrap();
+    let _ = std::fs::remove_file(&ca_file);
+}
+
+#[test]
+fn tls_policies_are_checked_against_what_rustls_offers() {
+    assert!(supported_cipher_suites().contains(&"TLS13_AES_256_GCM_SHA384".to_string()));
+    check_tls_policy(&TlsPolicy::default()).unwrap();
+    check_tls_policy(&TlsPolicy {
+        min_version: Some(TlsVersion::Tls12),
+        cipher_suites: vec![
+            "TLS13_AES_256_GCM_SHA384".into(),
+            "tls_ecdhe_rsa_with_aes_256_gcm_sha384".into(),
+        ],
+    })
+    .unwrap();
+
+    let unknown = check_tls_policy(&TlsPolicy {
+        min_version: None,
+        cipher_suites: vec!["TLS_RSA_WITH_RC4_128_MD5".into()],
+    })
+    .unwrap_err();
+    assert!(
+        unknown.to_string().contains("unknown cipher suite"),
+        "{unknown:#}"
+    );
+    // TLS 1.3 only, with nothing but a TLS 1.2 suite allowed.
+    let policy = TlsPolicy {
+        min_version: Some(TlsVersion::Tls13),
+        cipher_suites: vec!["TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384".into()],
+    };
+    assert!(check_tls_policy(&policy).is_err());
+    assert_eq!(
+        policy.describe(),
+        "TLS 1.3+, suites TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"
+    );
+
+    // Stored as JSON; accounts saved before the policy existed read back the default.
+    let json = serde_json::to_string(&policy).unwrap();
+    assert_eq!(serde_json::from_str::<TlsPolicy>(&json).unwrap(), policy);
+    assert!(json.contains("\"1.3\""), "{json}");
+    assert!(
+        serde_json::from_str::<TlsPolicy>("{}")
+            .unwrap()
+            .is_default()
+    );
+}
+
+#[tokio::test]
+async fn a_minimum_tls_version_refuses_older_servers() {
+    use rcgen::{CertificateParams, KeyPair};
+    use sha2::{Digest, Sha256};
+    use tokio_rustls::TlsAcceptor;
+    use tokio_rustls::rustls::version::TLS12;
+    use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+
+    let key = KeyPair::generate().unwrap();
+    let cert = CertificateParams::new(vec!["localhost".to_string()])
+        .unwrap()
+        .self_signed(&key)
+        .unwrap();
+    let fingerprint: String = Sha256::digest(cert.der())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    // A server stuck on TLS 1.2.
+    let config = ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&[&TLS12])
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![Certificate(cert.der().to_vec())],
+            PrivateKey(key.serialize_der()),
+        )
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        // The first client requires TLS 1.3 and gives up during the handshake.
+        let (socket, _) = listener.accept().await.unwrap();
+        assert!(acceptor.accept(socket).await.is_err());
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let stream = acceptor.accept(socket).await.unwrap();
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Legacy ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write
+            .write_all(format!("* CAPABILITY IMAP4rev1\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+    });
+
+    let mut legacy = account(ImapEndpoint {
+        host: "localhost".into(),
+        port,
+        tls: TlsMode::Tls,
+        cert_sha256: Some(fingerprint),
+        ca_file: None,
+        client_cert: None,
+    });
+    legacy.settings.tls_policy.min_version = Some(TlsVersion::Tls13);
+    let err = ImapClient::connect(&legacy, "token").await.err().unwrap();
+    assert!(format!("{err:#}").contains("TLS"), "{err:#}");
+
+    legacy.settings.tls_policy = TlsPolicy::default();
+    let session = ImapClient::connect(&legacy, "token").await.unwrap();
+    drop(session);
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn a_client_certificate_is_presented_when_the_server_requires_one() {
+    use otto::types::ClientCert;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use tokio_rustls::TlsAcceptor;
+    use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+    use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+    let server_key = KeyPair::generate().unwrap();
+    let server_cert = CertificateParams::new(vec!["localhost".to_string()])
+        .unwrap()
+        .signed_by(&server_key, &ca, &ca_key)
+        .unwrap();
+    let client_key = KeyPair::generate().unwrap();
+    let client_cert = CertificateParams::new(vec!["me.example.com".to_string()])
+        .unwrap()
+        .signed_by(&client_key, &ca, &ca_key)
+        .unwrap();
+    let dir = std::env::temp_dir();
+    let ca_file = dir.join(format!("otto-mtls-ca-{}.pem", std::process::id()));
+    let client = ClientCert {
+        cert: dir.join(format!("otto-mtls-cert-{}.pem", std::process::id())),
+        key: dir.join(format!("otto-mtls-key-{}.pem", std::process::id())),
+    };
+    std::fs::write(&ca_file, ca.pem()).unwrap();
+    std::fs::write(&client.cert, client_cert.pem()).unwrap();
+    std::fs::write(&client.key, client_key.serialize_pem()).unwrap();
+    assert!(otto::imap::load_client_cert(&client).is_ok());
+
+    let mut client_roots = RootCertStore::empty();
+    client_roots.add(&Certificate(ca.der().to_vec())).unwrap();
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(client_roots).boxed())
+        .with_single_cert(
+            vec![Certificate(server_cert.der().to_vec())],
+            PrivateKey(server_key.serialize_der()),
+        )
+        .unwrap();
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        // The first client has no certificate to show.
+        let (socket, _) = listener.accept().await.unwrap();
+        assert!(acceptor.accept(socket).await.is_err());
+
+        let (socket, _) = listener.accept().await.unwrap();
+        let stream = acceptor.accept(socket).await.unwrap();
+        let (read, mut write) = tokio::io::split(stream);
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Hardened ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write
+            .write_all(format!("* CAPABILITY IMAP4rev1\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+    });
+
+    let mut hardened = account(ImapEndpoint {
+        host: "localhost".into(),
+        port,
+        tls: TlsMode::Tls,
+        cert_sha256: None,
+        ca_file: Some(ca_file.clone()),
+        client_cert: None,
+    });
+    assert!(ImapClient::connect(&hardened, "token").await.is_err());
+
+    hardened.settings.imap.client_cert = Some(client.clone());
+    let session = ImapClient::connect(&hardened, "token").await.unwrap();
+    drop(session);
+    server.await.unwrap();
+    for path in [&ca_file, &client.cert, &client.key] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[tokio::test]
+async fn append_uploads_a_crlf_literal_with_flags_and_date() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Bridge ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        answer_capability(&mut lines, &mut write, "IMAP4rev1").await;
+
+        let command = lines.next_line().await.unwrap().unwrap();
+        let (tag, rest) = command.split_once(' ').unwrap();
+        let size: usize = rest
+            .rsplit_once('{')
+            .unwrap()
+            .1
+            .trim_end_matches('}')
+            .parse()
+            .unwrap();
+        write.write_all(b"+ go ahead\r\n").await.unwrap();
+        let mut read = lines.into_inner();
+        let mut literal = vec![0u8; size + 2];
+        read.read_exact(&mut literal).await.unwrap();
+        write
+            .write_all(format!("{tag} OK APPEND completed\r\n").as_bytes())
+            .await
+            .unwrap();
+        (rest.to_string(), literal)
+    });
+
+    let bridge = account(ImapEndpoint {
+        host: "127.0.0.1".into(),
+        port,
+        tls: TlsMode::Plain,
+        cert_sha256: None,
+        ca_file: None,
+        client_cert: None,
+    });
+    let mut session = ImapClient::connect(&bridge, "token").await.unwrap();
+    let message = AppendMessage::from_raw(
+        b"Date: Fri, 5 Jan 2024 12:00:00 +0200\nSubject: Hi\n\nBody\n".to_vec(),
+        vec!["\\Seen".into()],
+    );
+    assert_eq!(message.internal_date, Some(1_704_448_800));
+    ImapClient::append(&mut session, "Archive", &message)
+        .await
+        .unwrap();
+    assert!(
+        ImapClient::append(&mut session, "Bad\"Name", &message)
+            .await
+            .is_err()
+    );
+
+    let (command, literal) = server.await.unwrap();
+    assert_eq!(
+        command,
+        "APPEND \"Archive\" (\\Seen) \"05-Jan-2024 10:00:00 +0000\" {59}"
+    );
+    assert_eq!(
+        literal,
+        b"Date: Fri, 5 Jan 2024 12:00:00 +0200\r\nSubject: Hi\r\n\r\nBody\r\n\r\n"
+    );
+}
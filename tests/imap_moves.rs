@@ -0,0 +1,205 @@
+use otto::imap::{CopyUid, ImapClient};
+use otto::storage::Database;
+use otto::storage::db::FolderStateUpdate;
+use otto::storage::ops::MessageOp;
+use otto::types::{Account, BodyStatus, MessageRecord};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+mod common;
+
+fn account(port: u16) -> Account {
+    common::local_account("acct", port)
+}
+
+/// Serves one connection advertising `capabilities`: UIDs 3 and 4 are moved or copied to
+/// UIDs 142 and 143 (with `COPYUID` when UIDPLUS is advertised), and UID 9 is already flagged
+/// `\Deleted`. Returns the commands received after login.
+async fn server(capabilities: &'static str) -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let uidplus = capabilities.contains("UIDPLUS");
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap();
+        write
+            .write_all(format!("* CAPABILITY {capabilities}\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+
+        let mut commands = Vec::new();
+        while let Ok(Some(command)) = lines.next_line().await {
+            let (tag, rest) = command.split_once(' ').unwrap();
+            commands.push(rest.to_string());
+            let reply = if rest.starts_with("SELECT") {
+                format!("* 9 EXISTS\r\n* OK [UIDVALIDITY 7] ok\r\n{tag} OK [READ-WRITE] done\r\n")
+            } else if rest.starts_with("UID MOVE") {
+                format!(
+                    "* OK [COPYUID 7 3:4 142:143] moved\r\n* 3 EXPUNGE\r\n* 3 EXPUNGE\r\n{tag} OK done\r\n"
+                )
+            } else if rest.starts_with("UID COPY") && uidplus {
+                format!("{tag} OK [COPYUID 7 3:4 142:143] copied\r\n")
+            } else if rest.starts_with("UID SEARCH") {
+                format!("* SEARCH 3 4 9\r\n{tag} OK done\r\n")
+            } else if rest.starts_with("EXPUNGE") || rest.starts_with("UID EXPUNGE") {
+                format!("* 3 EXPUNGE\r\n* 3 EXPUNGE\r\n{tag} OK done\r\n")
+            } else {
+                format!("{tag} OK done\r\n")
+            };
+            write.write_all(reply.as_bytes()).await.unwrap();
+        }
+        commands
+    });
+    (port, server)
+}
+
+/// Moves UIDs 3 and 4 from INBOX to Work; returns the `COPYUID` and the commands sent.
+async fn move_on(capabilities: &'static str) -> (Option<CopyUid>, Vec<String>) {
+    let (port, server) = server(capabilities).await;
+    let mut session = ImapClient::connect(&account(port), "token").await.unwrap();
+    session.select("INBOX").await.unwrap();
+    let copy_uid = session.move_uids(&[3, 4], "Work").await.unwrap();
+    drop(session);
+    (copy_uid, server.await.unwrap())
+}
+
+#[tokio::test]
+async fn moves_prefer_uid_move_and_fall_back_to_copy_and_expunge() {
+    let mapped = Some(CopyUid {
+        uid_validity: 7,
+        uids: vec![(3, 142), (4, 143)],
+    });
+
+    let (copy_uid, commands) = move_on("IMAP4rev1 MOVE UIDPLUS").await;
+    assert_eq!(copy_uid, mapped);
+    assert_eq!(commands[1..], ["UID MOVE 3:4 \"Work\""]);
+
+    // Without MOVE: copy, flag and expunge just the moved UIDs.
+    let (copy_uid, commands) = move_on("IMAP4rev1 UIDPLUS").await;
+    assert_eq!(copy_uid, mapped);
+    assert_eq!(
+        commands[1..],
+        [
+            "UID COPY 3:4 \"Work\"",
+            "UID STORE 3:4 +FLAGS.SILENT (\\Deleted)",
+            "UID EXPUNGE 3:4",
+        ]
+    );
+
+    // Without UIDPLUS either: UID 9 is unflagged around the plain EXPUNGE so it survives.
+    let (copy_uid, commands) = move_on("IMAP4rev1").await;
+    assert_eq!(copy_uid, None);
+    assert_eq!(
+        commands[1..],
+        [
+            "UID COPY 3:4 \"Work\"",
+            "UID STORE 3:4 +FLAGS.SILENT (\\Deleted)",
+            "UID SEARCH DELETED",
+            "UID STORE 9 -FLAGS.SILENT (\\Deleted)",
+            "EXPUNGE",
+            "UID STORE 9 +FLAGS.SILENT (\\Deleted)",
+        ]
+    );
+}
+
+fn message(id: &str, folder: &str, uid: u32) -> MessageRecord {
+    MessageRecord {
+        body_status: BodyStatus::Pending,
+        ..common::message(id, folder, uid)
+    }
+}
+
+async fn commit(db: &Database, folder: &str, messages: &[MessageRecord]) {
+    db.commit_folder_batch(
+        "acct",
+        folder,
+        messages,
+        &[],
+        &[],
+        &[],
+        &FolderStateUpdate {
+            uidvalidity: Some(7),
+            highest_uid: Some(140),
+            highestmodseq: None,
+            exists_count: Some(messages.len() as u32),
+            last_sync_ts: Some(1_700_000_000),
+            last_uid_scan_ts: None,
+            baseline_scan_uid: None,
+            resume_modseq: None,
+            resume_uid: None,
+            checkpoint_from_uid: None,
+        },
+        "ok",
+        None,
+        Some(140),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn copyuid_links_moved_rows_so_later_ops_replay_at_once() {
+    let dir = std::env::temp_dir().join(format!("otto-moves-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&account(143)).await.unwrap();
+    commit(
+        &db,
+        "INBOX",
+        &[message("a", "INBOX", 3), message("b", "INBOX", 4)],
+    )
+    .await;
+    commit(&db, "Work", &[message("w", "Work", 143)]).await;
+
+    db.apply_message_op(
+        "acct",
+        &MessageOp::Move("Work".into()),
+        &["a".into(), "b".into()],
+    )
+    .await
+    .unwrap();
+    db.apply_message_op("acct", &MessageOp::Star, &["a".into()])
+        .await
+        .unwrap();
+    let moves: Vec<i64> = db
+        .load_replayable_location_ops("acct")
+        .await
+        .unwrap()
+        .iter()
+        .map(|op| op.id)
+        .collect();
+    // The star waits: the moved row has no uid in Work yet.
+    let star = &db.load_replayable_flag_ops("acct").await.unwrap()[0];
+    assert_eq!((star.folder.as_deref(), star.uid), (Some("Work"), None));
+
+    let links = [(moves[0], 142), (moves[1], 143)];
+    // A COPYUID from another UIDVALIDITY names different messages.
+    assert_eq!(
+        db.link_moved_uids("acct", "Work", 8, &links).await.unwrap(),
+        0
+    );
+    // UID 143 is already cached (as "w"), so only "a" is linked.
+    assert_eq!(
+        db.link_moved_uids("acct", "Work", 7, &links).await.unwrap(),
+        1
+    );
+    let star = &db.load_replayable_flag_ops("acct").await.unwrap()[0];
+    assert_eq!(
+        (star.folder.as_deref(), star.uid),
+        (Some("Work"), Some(142))
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,137 @@
+use chrono::NaiveDate;
+use otto::imap::ImapClient;
+use otto::storage::Database;
+use otto::types::{Account, AccountSettings, FolderPolicy, MailboxNamespace};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+mod common;
+
+fn dotted(prefix: &str) -> MailboxNamespace {
+    MailboxNamespace {
+        prefix: prefix.into(),
+        delimiter: Some(".".into()),
+    }
+}
+
+fn account(port: u16) -> Account {
+    common::local_account("courier", port)
+}
+
+#[test]
+fn folder_paths_follow_the_server_delimiter_and_prefix() {
+    let flat = dotted("");
+    assert_eq!(flat.normalize("INBOX/Archive"), "INBOX.Archive");
+    assert_eq!(flat.normalize("INBOX.Archive"), "INBOX.Archive");
+    assert_eq!(flat.normalize("inbox"), "INBOX");
+
+    let courier = dotted("INBOX.");
+    assert_eq!(courier.normalize("Archive"), "INBOX.Archive");
+    assert_eq!(courier.normalize("Work/2024"), "INBOX.Work.2024");
+    assert_eq!(courier.normalize("INBOX/Archive"), "INBOX.Archive");
+    assert_eq!(courier.normalize("inbox.Archive"), "INBOX.Archive");
+    assert_eq!(courier.normalize("Inbox"), "INBOX");
+
+    // Slash servers keep dots, which are ordinary characters there.
+    let slash = MailboxNamespace {
+        prefix: String::new(),
+        delimiter: Some("/".into()),
+    };
+    assert_eq!(slash.normalize("Releases/v1.2"), "Releases/v1.2");
+
+    let mut settings = AccountSettings::with_defaults(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+    settings.folders = vec!["INBOX".into(), "Archive".into(), "INBOX/Archive".into()];
+    settings
+        .folder_policies
+        .insert("Archive".into(), FolderPolicy::default());
+    assert_eq!(settings.server_folder("Archive"), "Archive");
+    assert!(settings.apply_namespace(courier.clone()));
+    assert_eq!(settings.folders, vec!["INBOX", "INBOX.Archive"]);
+    assert!(settings.folder_policies.contains_key("INBOX.Archive"));
+    assert_eq!(settings.server_folder("Sent"), "INBOX.Sent");
+    assert!(!settings.apply_namespace(courier));
+}
+
+/// Logs in, advertising `capabilities`, then answers `NAMESPACE` or `LIST "" ""` with
+/// `untagged` and returns the command it got.
+async fn namespace_server(
+    capabilities: &'static str,
+    untagged: &'static str,
+) -> (u16, tokio::task::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap();
+        write
+            .write_all(format!("* CAPABILITY {capabilities}\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+
+        let command = lines.next_line().await.unwrap().unwrap();
+        let (tag, rest) = command.split_once(' ').unwrap();
+        write
+            .write_all(format!("{untagged}\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+        rest.to_string()
+    });
+    (port, server)
+}
+
+#[tokio::test]
+async fn the_namespace_comes_from_namespace_or_list() {
+    let (port, server) = namespace_server(
+        "IMAP4rev1 NAMESPACE",
+        "* NAMESPACE ((\"INBOX.\" \".\")) NIL ((\"#shared.\" \".\"))",
+    )
+    .await;
+    let mut session = ImapClient::connect(&account(port), "token").await.unwrap();
+    assert!(session.caps().namespace);
+    assert_eq!(
+        ImapClient::namespace(&mut session).await.unwrap(),
+        dotted("INBOX.")
+    );
+    assert_eq!(server.await.unwrap(), "NAMESPACE");
+
+    let (port, server) = namespace_server("IMAP4rev1", "* LIST (\\Noselect) \".\" \"\"").await;
+    let mut session = ImapClient::connect(&account(port), "token").await.unwrap();
+    assert_eq!(
+        ImapClient::namespace(&mut session).await.unwrap(),
+        dotted("")
+    );
+    assert_eq!(server.await.unwrap(), "LIST \"\" \"\"");
+}
+
+#[tokio::test]
+async fn the_namespace_is_stored_on_the_account() {
+    let dir = std::env::temp_dir().join(format!("otto-namespace-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut account = account(993);
+    db.save_account(&account).await.unwrap();
+    assert_eq!(
+        db.list_accounts().await.unwrap()[0].settings.namespace,
+        None
+    );
+
+    account.settings.apply_namespace(dotted("INBOX."));
+    db.save_account(&account).await.unwrap();
+    let stored = db.list_accounts().await.unwrap().remove(0);
+    assert_eq!(stored.settings.namespace, Some(dotted("INBOX.")));
+    assert_eq!(stored.settings.folders, account.settings.folders);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,157 @@
+use std::io;
+
+use futures::StreamExt;
+use otto::imap::{ImapClient, SelectedMailbox, is_connection_lost, is_session_lost};
+use otto::types::Account;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::TcpListener;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+
+mod common;
+
+fn account(port: u16) -> Account {
+    common::local_account("flaky", port)
+}
+
+/// Greets, accepts any XOAUTH2 login and answers the CAPABILITY probe.
+async fn log_in(lines: &mut Lines<BufReader<OwnedReadHalf>>, write: &mut OwnedWriteHalf) {
+    write.write_all(b"* OK ready\r\n").await.unwrap();
+    let command = lines.next_line().await.unwrap().unwrap();
+    let tag = command.split(' ').next().unwrap().to_string();
+    write.write_all(b"+ \r\n").await.unwrap();
+    let _credentials = lines.next_line().await.unwrap().unwrap();
+    write
+        .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+        .await
+        .unwrap();
+    let command = lines.next_line().await.unwrap().unwrap();
+    let tag = command.split(' ').next().unwrap();
+    write
+        .write_all(format!("* CAPABILITY IMAP4rev1\r\n{tag} OK done\r\n").as_bytes())
+        .await
+        .unwrap();
+}
+
+/// Serves one connection per entry of `uid_validities`: login, a SELECT answered with that
+/// UIDVALIDITY, then a FETCH that gets one message before the server hangs up. Returns the
+/// commands each connection received.
+async fn flaky_server(
+    uid_validities: Vec<u32>,
+) -> (u16, tokio::task::JoinHandle<Vec<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let mut seen = Vec::new();
+        for uid_validity in uid_validities {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            log_in(&mut lines, &mut write).await;
+            let mut commands = Vec::new();
+            while let Ok(Some(command)) = lines.next_line().await {
+                let tag = command.split(' ').next().unwrap().to_string();
+                commands.push(command.clone());
+                if command.contains("SELECT") || command.contains("EXAMINE") {
+                    let reply = format!(
+                        "* 3 EXISTS\r\n* OK [UIDVALIDITY {uid_validity}] ok\r\n* OK [UIDNEXT 4] ok\r\n{tag} OK [READ-WRITE] done\r\n"
+                    );
+                    write.write_all(reply.as_bytes()).await.unwrap();
+                } else if command.contains("UID FETCH") {
+                    write
+                        .write_all(b"* 1 FETCH (UID 1 FLAGS (\\Seen))\r\n")
+                        .await
+                        .unwrap();
+                    break;
+                } else {
+                    write
+                        .write_all(format!("{tag} OK done\r\n").as_bytes())
+                        .await
+                        .unwrap();
+                }
+            }
+            seen.push(commands);
+        }
+        seen
+    });
+    (port, server)
+}
+
+#[test]
+fn lost_connections_are_told_apart_from_refusals() {
+    assert!(is_connection_lost(
+        &async_imap::error::Error::ConnectionLost
+    ));
+    for kind in [
+        io::ErrorKind::ConnectionReset,
+        io::ErrorKind::BrokenPipe,
+        io::ErrorKind::UnexpectedEof,
+        io::ErrorKind::TimedOut,
+    ] {
+        let error = async_imap::error::Error::Io(io::Error::new(kind, "gone"));
+        assert!(is_connection_lost(&error), "{kind:?}");
+        assert!(is_session_lost(
+            &anyhow::Error::from(error).context("fetching")
+        ));
+    }
+    assert!(!is_connection_lost(&async_imap::error::Error::No(
+        "mailbox busy".into()
+    )));
+    assert!(!is_connection_lost(&async_imap::error::Error::Io(
+        io::Error::new(io::ErrorKind::InvalidData, "bad bytes")
+    )));
+    assert!(!is_session_lost(&anyhow::anyhow!("no such message")));
+}
+
+#[tokio::test]
+async fn reconnect_reopens_the_folder_unless_uidvalidity_changed() {
+    let (port, server) = flaky_server(vec![9, 9, 10]).await;
+    let account = account(port);
+
+    let mut session = ImapClient::connect(&account, "token").await.unwrap();
+    assert_eq!(session.selected(), None);
+    session.select("Archive").await.unwrap();
+    let selected = SelectedMailbox {
+        name: "Archive".into(),
+        uid_validity: Some(9),
+        read_only: false,
+        condstore: false,
+    };
+    assert_eq!(session.selected(), Some(&selected));
+
+    // The server hangs up mid-FETCH: the stream ends with a lost-connection error.
+    let results: Vec<_> = session
+        .uid_fetch("1:3", "(UID FLAGS)")
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(results[0].as_ref().unwrap().uid, Some(1));
+    let error = results
+        .iter()
+        .find_map(|r| r.as_ref().err())
+        .expect("a lost connection");
+    assert!(is_connection_lost(error), "{error:?}");
+
+    ImapClient::reconnect(&account, "token", &mut session)
+        .await
+        .unwrap();
+    assert_eq!(session.selected(), Some(&selected));
+    let results: Vec<_> = session
+        .uid_fetch("2:3", "(UID FLAGS)")
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert_eq!(results[0].as_ref().unwrap().uid, Some(1));
+
+    // The folder was recreated meanwhile: the old session is kept and the caller gives up.
+    let err = ImapClient::reconnect(&account, "token", &mut session)
+        .await
+        .unwrap_err();
+    assert!(format!("{err:#}").contains("UIDVALIDITY"), "{err:#}");
+
+    let seen = server.await.unwrap();
+    assert_eq!(seen.len(), 3);
+    assert!(seen[1][0].contains("SELECT \"Archive\""), "{:?}", seen[1]);
+    assert!(seen[1][1].contains("UID FETCH 2:3"), "{:?}", seen[1]);
+}
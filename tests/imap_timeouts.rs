@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use futures::StreamExt;
+use otto::imap::{ImapClient, ImapSession, ImapTimeouts, is_timeout, set_timeouts};
+use otto::types::Account;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::TcpListener;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::sync::oneshot;
+
+mod common;
+
+fn account(port: u16) -> Account {
+    common::local_account("slow", port)
+}
+
+/// Greets, accepts any XOAUTH2 login and answers the CAPABILITY probe.
+async fn log_in(lines: &mut Lines<BufReader<OwnedReadHalf>>, write: &mut OwnedWriteHalf) {
+    write.write_all(b"* OK ready\r\n").await.unwrap();
+    let command = lines.next_line().await.unwrap().unwrap();
+    let tag = command.split(' ').next().unwrap().to_string();
+    write.write_all(b"+ \r\n").await.unwrap();
+    let _credentials = lines.next_line().await.unwrap().unwrap();
+    write
+        .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+        .await
+        .unwrap();
+    let command = lines.next_line().await.unwrap().unwrap();
+    let tag = command.split(' ').next().unwrap();
+    write
+        .write_all(format!("* CAPABILITY IMAP4rev1\r\n{tag} OK done\r\n").as_bytes())
+        .await
+        .unwrap();
+}
+
+/// A server that logs in, runs `script` on the first command after login, then keeps the
+/// socket open (silent) until `done` fires.
+async fn stalling_server<F>(script: F) -> (u16, oneshot::Sender<()>)
+where
+    F: FnOnce(String) -> Vec<u8> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (done, wait) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        log_in(&mut lines, &mut write).await;
+        if let Ok(Some(command)) = lines.next_line().await {
+            write.write_all(&script(command)).await.unwrap();
+        }
+        let _ = wait.await;
+    });
+    (port, done)
+}
+
+async fn connect(port: u16) -> ImapSession {
+    ImapClient::connect(&account(port), "token").await.unwrap()
+}
+
+// One test, since the limits are process-wide.
+#[tokio::test]
+async fn stalled_servers_time_out_instead_of_hanging() {
+    let limits = ImapTimeouts {
+        connect: Duration::from_millis(300),
+        select: Duration::from_millis(300),
+        search: Duration::from_millis(300),
+        fetch_idle: Duration::from_millis(300),
+    };
+
+    // No greeting at all: the connect limit trips before the read gap.
+    set_timeouts(ImapTimeouts {
+        fetch_idle: Duration::from_secs(3),
+        ..limits
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let silent = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        drop(socket);
+    });
+    let err = ImapClient::connect(&account(port), "token")
+        .await
+        .unwrap_err();
+    assert!(is_timeout(&err), "{err:#}");
+    assert!(
+        format!("{err:#}").contains("IMAP connect timed out after 300ms"),
+        "{err:#}"
+    );
+    silent.abort();
+    set_timeouts(limits);
+
+    // SELECT is never answered: it fails, and so does anything after it on that connection.
+    let (port, done) = stalling_server(|_| Vec::new()).await;
+    let mut session = connect(port).await;
+    let err = anyhow::Error::from(session.select("INBOX").await.unwrap_err());
+    assert!(is_timeout(&err), "{err:#}");
+    assert!(session.timed_out());
+    assert!(session.noop().await.is_err());
+    drop(done);
+
+    // FETCH answers one message, then goes quiet mid-stream.
+    let (port, done) = stalling_server(|command| {
+        assert!(command.contains("UID FETCH"), "{command}");
+        b"* 1 FETCH (UID 7 FLAGS (\\Seen))\r\n".to_vec()
+    })
+    .await;
+    let mut session = connect(port).await;
+    let started = std::time::Instant::now();
+    let results: Vec<_> = session
+        .uid_fetch("7:8", "(UID FLAGS)")
+        .await
+        .unwrap()
+        .collect()
+        .await;
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(results.len(), 2, "{results:?}");
+    assert_eq!(results[0].as_ref().unwrap().uid, Some(7));
+    let err = anyhow::Error::from(results.into_iter().nth(1).unwrap().unwrap_err());
+    assert!(is_timeout(&err), "{err:#}");
+    assert!(session.timed_out());
+    drop(done);
+
+    set_timeouts(ImapTimeouts::default());
+}
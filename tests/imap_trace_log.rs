@@ -0,0 +1,92 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use otto::imap::trace::{TRACE_LOG_KEEP, set_shared_log};
+use otto::imap::{ImapClient, ProtocolTrace, TraceLog};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+mod common;
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("otto-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn the_shared_log_rotates_and_keeps_a_few_old_files() {
+    let dir = scratch_dir("trace-rotate");
+    let path = dir.join("imap-trace.log");
+    let log = Arc::new(TraceLog::rotating(&path, 512).unwrap());
+    let trace = ProtocolTrace::connection(log.clone(), "work", "imap.example.com:993");
+    trace.client(b"a1 LOGIN me@example.com hunter2\r\n");
+    for n in 0..100 {
+        trace.client(format!("a{} NOOP\r\n", n + 2).as_bytes());
+        trace.server(format!("a{} OK NOOP completed\r\n", n + 2).as_bytes());
+    }
+    drop(trace);
+
+    let numbered = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    assert!(path.exists());
+    for n in 1..=TRACE_LOG_KEEP {
+        let size = std::fs::metadata(numbered(n)).unwrap().len();
+        assert!((512..1024).contains(&size), "{n}: {size} bytes");
+    }
+    assert!(!numbered(TRACE_LOG_KEEP + 1).exists());
+
+    let current = std::fs::read_to_string(&path).unwrap();
+    let last = current.lines().last().unwrap();
+    assert!(last.ends_with(" work S: a101 OK NOOP completed"), "{last}");
+    assert!(last.contains('#'), "lines carry the connection tag: {last}");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn connections_trace_to_the_shared_log_once_enabled() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap().to_string();
+        write.write_all(b"+ \r\n").await.unwrap();
+        let _credentials = lines.next_line().await.unwrap().unwrap();
+        write
+            .write_all(format!("{tag} OK authenticated\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap();
+        write
+            .write_all(format!("* CAPABILITY IMAP4rev1\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+    });
+
+    let dir = scratch_dir("trace-shared");
+    let path = dir.join("imap-trace.log");
+    set_shared_log(Some(Arc::new(
+        TraceLog::rotating(&path, 10 * 1024 * 1024).unwrap(),
+    )));
+    let account = common::local_account("bridge", port);
+    let session = ImapClient::connect(&account, "secret-token").await.unwrap();
+    server.await.unwrap();
+    drop(session);
+    set_shared_log(None);
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_dir_all(&dir);
+    assert!(
+        log.contains(&format!("bridge connecting to 127.0.0.1:{port}")),
+        "{log}"
+    );
+    assert!(log.contains(" bridge S: * OK ready"), "{log}");
+    assert!(log.contains(" bridge C: <redacted>"), "{log}");
+    assert!(log.contains(" bridge S: * CAPABILITY IMAP4rev1"), "{log}");
+    assert!(!log.contains("secret-token"));
+}
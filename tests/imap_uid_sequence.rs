@@ -0,0 +1,32 @@
+use otto::imap::build_uid_sequence;
+
+#[test]
+fn compresses_consecutive_uids_into_ranges() {
+    assert_eq!(
+        build_uid_sequence(&[1, 2, 3, 4, 5, 7, 10, 11, 12, 13, 14, 15]),
+        "1:5,7,10:15"
+    );
+}
+
+#[test]
+fn handles_unsorted_and_duplicate_uids() {
+    assert_eq!(
+        build_uid_sequence(&[15, 3, 7, 1, 2, 3, 7, 14]),
+        "1:3,7,14:15"
+    );
+}
+
+#[test]
+fn single_uid_and_empty_input() {
+    assert_eq!(build_uid_sequence(&[42]), "42");
+    assert_eq!(build_uid_sequence(&[42, 42, 42]), "42");
+    assert_eq!(build_uid_sequence(&[]), "1");
+}
+
+#[test]
+fn handles_uid_max_without_overflow() {
+    assert_eq!(
+        build_uid_sequence(&[u32::MAX, u32::MAX - 1, 5]),
+        format!("5,{}:{}", u32::MAX - 1, u32::MAX)
+    );
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use otto::cleanup::{self, CleanupAction};
+use otto::learn::{self, LearnConfig, LearnedAction};
+use otto::storage::Database;
+use otto::storage::crypto::ColumnCipher;
+use otto::storage::learning::SuggestionStatus;
+use otto::storage::ops::MessageOp;
+use otto::timefmt::DisplayTz;
+use otto::tui::SNOOZE_LABEL;
+use otto::types::MessageRecord;
+
+mod common;
+
+const NOW: i64 = 1_760_000_000;
+
+fn message(id: &str, uid: u32, from: &str) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(NOW - 60),
+        subject: Some(format!("about {}", id)),
+        from: Some(from.into()),
+        ..common::message(id, "INBOX", uid)
+    }
+}
+
+fn ids(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn learning_is_configured_from_the_environment() {
+    let config = |vars: &[(&str, &str)]| {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        LearnConfig::from_env(|name| vars.get(name).cloned())
+    };
+    assert_eq!(config(&[]).unwrap(), LearnConfig::default());
+    assert_eq!(config(&[]).unwrap().threshold, 5);
+    let off = config(&[("OTTO_LEARN_THRESHOLD", "0")]).unwrap();
+    assert!(!off.enabled());
+    let auto = config(&[("OTTO_LEARN_THRESHOLD", "3"), ("OTTO_LEARN_AUTO", "true")]).unwrap();
+    assert_eq!((auto.threshold, auto.auto_accept), (3, true));
+    assert!(config(&[("OTTO_LEARN_THRESHOLD", "often")]).is_err());
+
+    assert_eq!(
+        LearnedAction::from_op(&MessageOp::AddLabel("Receipts".into())),
+        Some(LearnedAction::Label("Receipts".into()))
+    );
+    assert_eq!(
+        LearnedAction::from_op(&MessageOp::AddLabel(SNOOZE_LABEL.into())),
+        None
+    );
+    assert_eq!(LearnedAction::from_op(&MessageOp::MarkRead), None);
+}
+
+#[tokio::test]
+async fn repeated_actions_suggest_rules_that_need_approval() {
+    let dir = std::env::temp_dir().join(format!("otto-learn-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[
+            message("n1", 1, "News <News@Example.com>"),
+            message("n2", 2, "news@example.com"),
+            message("n3", 3, "news@example.com"),
+            message("b1", 4, "billing@shop.example"),
+            message("b2", 5, "billing@shop.example"),
+            message("b3", 6, "billing@shop.example"),
+            message("p1", 7, "friend@example.org"),
+        ],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+    let config = LearnConfig {
+        threshold: 3,
+        auto_accept: false,
+    };
+
+    // Two archives from the newsletter (one batch, mixed with a friend) are not enough yet.
+    let archive = MessageOp::Archive;
+    let first = learn::observe(&db, "acct", &archive, &ids(&["n1", "n2", "p1"]), config)
+        .await
+        .unwrap();
+    assert!(first.is_empty());
+    let third = learn::observe(&db, "acct", &archive, &ids(&["n3"]), config)
+        .await
+        .unwrap();
+    assert_eq!(third.len(), 1);
+    let suggestion = &third[0];
+    assert_eq!(suggestion.sender, "news@example.com");
+    assert_eq!(suggestion.action, LearnedAction::Archive);
+    assert_eq!(suggestion.rule_name(), "auto-archive-news@example.com");
+    assert_eq!(
+        suggestion.question(),
+        "Always archive mail from news@example.com (3 so far)?"
+    );
+    assert_eq!(learn::pending(&db, "acct").await.unwrap(), third);
+    // Nothing is created before the user says yes.
+    let account = db.list_accounts().await.unwrap().remove(0);
+    assert!(account.settings.cleanup_rules.is_empty());
+
+    learn::reject(&db, "acct", suggestion).await.unwrap();
+    assert!(learn::pending(&db, "acct").await.unwrap().is_empty());
+    // Rejected pairs keep counting without being suggested again.
+    let again = learn::observe(&db, "acct", &archive, &ids(&["n1"]), config)
+        .await
+        .unwrap();
+    assert!(again.is_empty());
+
+    // Labeling the shop's bills three times suggests a label rule; accepting creates it.
+    let label = MessageOp::AddLabel("Receipts".into());
+    let suggested = learn::observe(&db, "acct", &label, &ids(&["b1", "b2", "b3"]), config)
+        .await
+        .unwrap();
+    assert_eq!(suggested.len(), 1);
+    assert_eq!(
+        suggested[0].rule_name(),
+        "auto-label-Receipts-billing@shop.example"
+    );
+    learn::accept(&db, "acct", &suggested[0]).await.unwrap();
+    assert!(learn::pending(&db, "acct").await.unwrap().is_empty());
+    let account = db.list_accounts().await.unwrap().remove(0);
+    let rule = &account.settings.cleanup_rules[0];
+    assert_eq!(rule.query, "from:billing@shop.example");
+    assert_eq!(rule.older_than_days, 0);
+    assert_eq!(rule.action, CleanupAction::Label);
+    assert_eq!(rule.describe_action(), "label Receipts");
+
+    // The rule labels new mail from the sender and skips mail that already has the label.
+    let mut labeled = message("b4", 8, "billing@shop.example");
+    labeled.labels = vec!["receipts".into()];
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("b5", 9, "billing@shop.example"), labeled],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+    let outcomes = cleanup::run_rules(&db, &account, None, NOW, DisplayTz::default(), true)
+        .await
+        .unwrap();
+    let mut matched: Vec<&str> = outcomes[0].messages.iter().map(|m| m.id.as_str()).collect();
+    matched.sort();
+    // `observe` only counts; the TUI's own label ops were never applied here.
+    assert_eq!(matched, ["b1", "b2", "b3", "b5"]);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn sender_counts_are_sealed_and_survive_resealing() {
+    let dir = std::env::temp_dir().join(format!("otto-learn-sealed-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+
+    let cipher = ColumnCipher::new(&[9u8; 32]);
+    db.reseal_account("acct", None, Some(&cipher))
+        .await
+        .unwrap();
+    db.register_cipher("acct", Some(cipher));
+    assert_eq!(
+        db.record_sender_action("acct", "news@example.com", "delete", "", 2, 3)
+            .await
+            .unwrap(),
+        None
+    );
+    let rows = db.load_sender_actions("acct", None).await.unwrap();
+    assert_eq!(rows[0].sender, "news@example.com");
+    assert_eq!(rows[0].status, SuggestionStatus::Counting);
+    db.register_cipher("acct", None);
+    let sealed = db.load_sender_actions("acct", None).await.unwrap();
+    assert_ne!(sealed[0].sender, "news@example.com");
+
+    // Back to plaintext: the pair is rekeyed, so counting carries on from 2.
+    db.reseal_account("acct", Some(&ColumnCipher::new(&[9u8; 32])), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        db.record_sender_action("acct", "news@example.com", "delete", "", 1, 3)
+            .await
+            .unwrap(),
+        Some(3)
+    );
+    let rows = db.load_sender_actions("acct", None).await.unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].sender, "news@example.com");
+    assert_eq!(rows[0].status, SuggestionStatus::Pending);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,84 @@
+use otto::timefmt::DisplayTz;
+use std::collections::HashSet;
+
+use otto::tui::{badge_color, build_mail_items, collapse_repeats, repeat_key, repeat_subject};
+use otto::types::MessageRecord;
+
+mod common;
+
+#[test]
+fn badge_colors_are_stable_per_key() {
+    assert_eq!(
+        badge_color("boss@example.com"),
+        badge_color("Boss@Example.com")
+    );
+    let colors: std::collections::HashSet<_> = ["work", "family", "receipts", "travel", "ops"]
+        .iter()
+        .map(|label| badge_color(label))
+        .collect();
+    assert!(colors.len() > 1);
+}
+
+fn message(id: &str, from: &str, subject: &str) -> MessageRecord {
+    MessageRecord {
+        subject: Some(subject.into()),
+        from: Some(from.into()),
+        ..common::message(id, "INBOX", 1)
+    }
+}
+
+#[test]
+fn list_items_carry_sender_key_and_user_labels() {
+    let mut msg = message("m1", "\"Pat Boss\" <Pat@Example.com>", "Plan");
+    msg.labels = vec!["\\Inbox".into(), "Work/Q3".into(), "\\Important".into()];
+    let items = build_mail_items(&[(msg, None)], DisplayTz::Named(chrono_tz::UTC));
+    assert_eq!(items[0].sender_key, "pat@example.com");
+    assert_eq!(items[0].labels, vec!["Work/Q3".to_string()]);
+}
+
+#[test]
+fn repeat_subjects_ignore_numbers_hashes_and_reply_prefixes() {
+    assert_eq!(
+        repeat_subject("[CI] Build #1234 failed on main (a1b2c3d4)"),
+        repeat_subject("Re: [ci] Build #1240 failed on  main (9f8e7d6c)")
+    );
+    assert_eq!(
+        repeat_subject("Run failed: deploy 12 of 30"),
+        "run failed: deploy # of #"
+    );
+    assert_ne!(
+        repeat_subject("Build #1234 failed"),
+        repeat_subject("Build #1234 passed")
+    );
+}
+
+#[test]
+fn repeats_collapse_into_the_newest_row_and_expand_in_place() {
+    let ci = "CI <ci@example.com>";
+    let messages: Vec<_> = [
+        message("n1", ci, "Build #12 failed"),
+        message("p1", "Pat <pat@example.com>", "Lunch?"),
+        message("n2", ci, "Build #11 failed"),
+        message("o1", "Other CI <bot@example.com>", "Build #10 failed"),
+        message("n3", ci, "Build #9 failed"),
+    ]
+    .into_iter()
+    .map(|msg| (msg, None))
+    .collect();
+    let items = build_mail_items(&messages, DisplayTz::Named(chrono_tz::UTC));
+
+    let rows = collapse_repeats(items.clone(), &HashSet::new());
+    let ids: Vec<&str> = rows.iter().map(|row| row.id.as_str()).collect();
+    assert_eq!(ids, ["n1", "p1", "o1"]);
+    let repeat = rows[0].repeat.as_ref().unwrap();
+    assert_eq!(repeat.others, ["n2", "n3"]);
+    assert!(!repeat.expanded);
+    assert!(rows[1].repeat.is_none() && rows[2].repeat.is_none());
+
+    let expanded = HashSet::from([repeat_key(&items[0])]);
+    let rows = collapse_repeats(items, &expanded);
+    let ids: Vec<&str> = rows.iter().map(|row| row.id.as_str()).collect();
+    assert_eq!(ids, ["n1", "n2", "n3", "p1", "o1"]);
+    assert!(rows[0].repeat.as_ref().unwrap().expanded);
+    assert!(rows[1].repeat.is_none());
+}
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use mailparse::parse_mail;
+use otto::compose::merge::{self, MergeLog, MergeTemplate};
+use otto::smtp::SmtpClient;
+use otto::storage::MailStore;
+use otto::storage::db::Database;
+use otto::types::Signature;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio_util::sync::CancellationToken;
+
+mod common;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("otto-merge-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn fields(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn template_fills_placeholders_and_rejects_unknown_columns() {
+    let template =
+        MergeTemplate::parse("Subject: Hi {{ Name }}\n\nDear {{name}},\nyour code is {{code}}.\n")
+            .unwrap();
+    assert_eq!(template.subject, "Hi {{ Name }}");
+    assert_eq!(template.body, "Dear {{name}},\nyour code is {{code}}.\n");
+
+    let (subject, body) = template
+        .render(&fields(&[("name", "Ada"), ("code", "42")]))
+        .unwrap();
+    assert_eq!(subject, "Hi Ada");
+    assert_eq!(body, "Dear Ada,\nyour code is 42.\n");
+
+    let err = template.render(&fields(&[("name", "Ada")])).unwrap_err();
+    assert!(format!("{:#}", err).contains("code"));
+    assert!(MergeTemplate::parse("Hello\n\nbody").is_err());
+}
+
+#[test]
+fn contacts_need_an_email_column_and_bad_rows_fail_before_sending() {
+    let dir = temp_dir("contacts");
+    let csv = dir.join("contacts.csv");
+    std::fs::write(
+        &csv,
+        "Email, Name\nada@example.com, Ada\nnot-an-address, Bob\n",
+    )
+    .unwrap();
+    let rows = merge::read_contacts(&csv).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].email(), "ada@example.com");
+    assert_eq!(rows[0].fields["name"], "Ada");
+
+    let template = MergeTemplate::parse("Subject: Hi {{name}}\n\nHello").unwrap();
+    let err = merge::render_all(&template, &rows, "me@example.com", None).unwrap_err();
+    let err = format!("{:#}", err);
+    assert!(err.contains("row 2"), "{}", err);
+    assert!(!err.contains("row 1"), "{}", err);
+
+    let no_email = dir.join("no-email.csv");
+    std::fs::write(&no_email, "name\nAda\n").unwrap();
+    assert!(merge::read_contacts(&no_email).is_err());
+}
+
+/// Scripted SMTP server: rejects RCPT for `bounce@example.com`, accepts everything else, and
+/// returns the DATA payloads it received.
+async fn fake_server(stream: DuplexStream) -> Vec<String> {
+    let mut stream = BufReader::new(stream);
+    let mut messages = Vec::new();
+    stream.get_mut().write_all(b"220 ready\r\n").await.unwrap();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await.unwrap() == 0 {
+            return messages;
+        }
+        let reply: &[u8] = match line.trim_end() {
+            l if l.starts_with("EHLO") => b"250-hello\r\n250 AUTH XOAUTH2\r\n",
+            l if l.starts_with("AUTH") => b"235 ok\r\n",
+            l if l.contains("bounce@example.com") => b"550 no such user\r\n",
+            "DATA" => {
+                stream.get_mut().write_all(b"354 go\r\n").await.unwrap();
+                let mut data = String::new();
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    if line == ".\r\n" {
+                        break;
+                    }
+                    data.push_str(&line);
+                }
+                messages.push(data);
+                b"250 queued\r\n"
+            }
+            "QUIT" => {
+                stream.get_mut().write_all(b"221 bye\r\n").await.unwrap();
+                return messages;
+            }
+            _ => b"250 ok\r\n",
+        };
+        stream.get_mut().write_all(reply).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn send_all_logs_outcomes_skips_rejections_and_resumes() {
+    let dir = temp_dir("send");
+    let csv = dir.join("contacts.csv");
+    std::fs::write(
+        &csv,
+        "email,name\nada@example.com,Ada\nbounce@example.com,Bo\ncy@example.com,Cy\n",
+    )
+    .unwrap();
+    let template = MergeTemplate::parse("Subject: Hi {{name}}\n\n.hidden line\n").unwrap();
+    let rows = merge::read_contacts(&csv).unwrap();
+    let messages = merge::render_all(&template, &rows, "Me <me@example.com>", None).unwrap();
+    let log = MergeLog::new(MergeLog::default_path(&csv));
+    assert!(log.path().ends_with("contacts.csv.sent.log"));
+
+    let (client_side, server_side) = tokio::io::duplex(64 * 1024);
+    let server = tokio::spawn(fake_server(server_side));
+    let mut client = SmtpClient::start(client_side, "me@example.com", "token")
+        .await
+        .unwrap();
+    let summary = merge::send_all(
+        &mut client,
+        &messages,
+        &log,
+        Duration::ZERO,
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+    client.quit().await.unwrap();
+    let received = server.await.unwrap();
+
+    assert_eq!((summary.sent, summary.failed), (2, 1));
+    assert_eq!(received.len(), 2);
+    assert!(received[0].contains("Subject: Hi Ada"));
+    assert!(received[0].contains("\r\n..hidden line"), "{}", received[0]);
+
+    let sent = log.sent().unwrap();
+    assert!(sent.contains("ada@example.com") && sent.contains("cy@example.com"));
+    assert!(!sent.contains("bounce@example.com"));
+}
+
+#[tokio::test]
+async fn merge_messages_end_with_the_stored_signature() {
+    let dir = temp_dir("signature");
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let store: &dyn MailStore = &db;
+    store.save_account(&common::account("acct")).await.unwrap();
+    store
+        .save_signature(&Signature {
+            account_id: "acct".into(),
+            alias: None,
+            body: "Ada Lovelace\nAnalytical Engines".into(),
+            above_quote: false,
+        })
+        .await
+        .unwrap();
+    let csv = dir.join("contacts.csv");
+    std::fs::write(&csv, "email,name\nbo@example.com,Bo\n").unwrap();
+    let template = MergeTemplate::parse("Subject: Hi {{name}}\n\nHello {{name}}\n").unwrap();
+    let rows = merge::read_contacts(&csv).unwrap();
+    let signature = store.load_signature("acct", None).await.unwrap();
+    let messages =
+        merge::render_all(&template, &rows, "me@example.com", signature.as_ref()).unwrap();
+
+    let (client_side, server_side) = tokio::io::duplex(64 * 1024);
+    let server = tokio::spawn(fake_server(server_side));
+    let mut client = SmtpClient::start(client_side, "me@example.com", "token")
+        .await
+        .unwrap();
+    let log = MergeLog::new(MergeLog::default_path(&csv));
+    merge::send_all(
+        &mut client,
+        &messages,
+        &log,
+        Duration::ZERO,
+        &CancellationToken::new(),
+    )
+    .await
+    .unwrap();
+    client.quit().await.unwrap();
+    let received = server.await.unwrap();
+
+    assert_eq!(received.len(), 1);
+    let parsed = parse_mail(received[0].as_bytes()).unwrap();
+    let text = parsed.subparts[0].get_body().unwrap().replace("\r\n", "\n");
+    assert_eq!(
+        text.trim_end(),
+        "Hello Bo\n\n-- \nAda Lovelace\nAnalytical Engines"
+    );
+}
@@ -0,0 +1,86 @@
+use otto::storage::Database;
+use otto::types::{BodyRecord, MessageRecord};
+
+mod common;
+
+fn message(folder: &str, uid: u32) -> MessageRecord {
+    MessageRecord {
+        subject: Some("quarterly numbers".into()),
+        size_bytes: Some(64),
+        message_id_header: Some("q3@example.com".into()),
+        // Non-Gmail servers have no X-GM-MSGID, so ids fall back to account:folder:uid.
+        ..common::message(&format!("acct:{}:{}", folder, uid), folder, uid)
+    }
+}
+
+fn body(message: &MessageRecord) -> BodyRecord {
+    BodyRecord {
+        message_id: message.id.clone(),
+        raw_rfc822: Some(b"Message-ID: <q3@example.com>\r\n\r\nnumbers\r\n".to_vec()),
+        sanitized_text: Some("numbers".into()),
+        mime_summary: None,
+        attachments_json: None,
+        sanitized_at: Some(1_700_000_000),
+        sanitizer_version: Some(1),
+        degraded: false,
+    }
+}
+
+#[tokio::test]
+async fn copies_in_other_folders_are_relinked_by_message_id() {
+    let dir = std::env::temp_dir().join(format!("otto-msgid-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut account = common::account("acct");
+    account.settings.folders = vec!["INBOX".into(), "Archive".into()];
+    account.settings.prefetch_recent = 10;
+    db.save_account(&account).await.unwrap();
+
+    let inbox = message("INBOX", 1);
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        std::slice::from_ref(&inbox),
+        &[body(&inbox)],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let headers = vec!["q3@example.com".to_string()];
+    let copies = db
+        .load_message_ids_by_header("acct", "Archive", &headers)
+        .await
+        .unwrap();
+    assert_eq!(copies.get("q3@example.com"), Some(&inbox.id));
+    // Duplicates inside one folder are left alone.
+    assert!(
+        db.load_message_ids_by_header("acct", "INBOX", &headers)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    // The parallel-sync race: the copy arrives without the lookup and is relinked on write.
+    let archived = message("Archive", 7);
+    db.commit_backfill_batch(
+        "acct",
+        "Archive",
+        std::slice::from_ref(&archived),
+        &[body(&archived)],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let stored = db.load_messages("acct", 10).await.unwrap();
+    assert_eq!(stored.len(), 1);
+    let (message, body) = &stored[0];
+    assert_eq!(message.id, inbox.id);
+    assert_eq!((message.folder.as_str(), message.uid), ("Archive", Some(7)));
+    assert!(body.is_some());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
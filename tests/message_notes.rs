@@ -0,0 +1,95 @@
+use otto::smart_folders::SmartQuery;
+use otto::storage::Database;
+use otto::storage::crypto::ColumnCipher;
+use otto::timefmt::DisplayTz;
+use otto::types::MessageRecord;
+
+mod common;
+
+fn message(id: &str, uid: u32) -> MessageRecord {
+    MessageRecord {
+        subject: Some(format!("about {}", id)),
+        ..common::message(id, "INBOX", uid)
+    }
+}
+
+#[tokio::test]
+async fn notes_are_kept_per_message_and_sealed_with_the_columns() {
+    let dir = std::env::temp_dir().join(format!("otto-notes-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("m1", 1), message("m2", 2)],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert!(!db.set_message_note("acct", "unknown", "x").await.unwrap());
+    assert!(db.set_message_note("acct", "m1", "draft").await.unwrap());
+    // Setting again replaces the note.
+    assert!(
+        db.set_message_note("acct", "m1", "called them back 3/12")
+            .await
+            .unwrap()
+    );
+    assert!(db.set_message_note("acct", "m2", "invoice").await.unwrap());
+    assert!(db.clear_message_note("acct", "m2").await.unwrap());
+    assert!(!db.clear_message_note("acct", "m2").await.unwrap());
+
+    let notes = db.load_message_notes("acct").await.unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].message_id, "m1");
+    assert_eq!(notes[0].note, "called them back 3/12");
+    assert_eq!(notes[0].subject.as_deref(), Some("about m1"));
+
+    let cipher = ColumnCipher::new(&[7u8; 32]);
+    db.reseal_account("acct", None, Some(&cipher))
+        .await
+        .unwrap();
+    db.register_cipher("acct", Some(cipher));
+    assert!(db.set_message_note("acct", "m2", "paid").await.unwrap());
+    let notes = db.load_message_notes("acct").await.unwrap();
+    assert_eq!(notes.len(), 2);
+    assert!(notes.iter().any(|n| n.note == "called them back 3/12"));
+    assert!(notes.iter().any(|n| n.note == "paid"));
+    db.register_cipher("acct", None);
+    let sealed = db.load_message_notes("acct").await.unwrap();
+    assert!(
+        sealed
+            .iter()
+            .all(|n| n.note != "paid" && !n.note.contains("called"))
+    );
+
+    db.delete_message("m1").await.unwrap();
+    assert_eq!(db.load_message_notes("acct").await.unwrap().len(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn queries_match_the_note() {
+    let msg = message("m1", 1);
+    let note = Some("Called them back 3/12");
+    let matches = |query: &str, note: Option<&str>| {
+        SmartQuery::parse(query)
+            .unwrap()
+            .matches_with_note(&msg, note, 0, DisplayTz::Local)
+    };
+    assert!(matches("note:\"called them\"", note));
+    assert!(matches("has:note", note));
+    assert!(matches("back", note));
+    assert!(!matches("back", None));
+    assert!(!matches("has:note", None));
+    assert!(matches("-has:note", None));
+    assert!(
+        !SmartQuery::parse("note:back")
+            .unwrap()
+            .matches(&msg, 0, DisplayTz::Local)
+    );
+}
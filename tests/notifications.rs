@@ -0,0 +1,193 @@
+use otto::notify::{
+    self, ChannelConfig, NoteMessage, Notification, NotificationChannel, NotifyRule, Ntfy, Quiet,
+    Webhook, select,
+};
+use otto::smtp::SmtpEndpoint;
+use otto::storage::Database;
+use otto::storage::db::FolderStateUpdate;
+use otto::timefmt::DisplayTz;
+use otto::types::MessageRecord;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+mod common;
+
+fn message(id: &str, folder: &str, from: &str, seen: bool) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(0),
+        subject: Some(format!("about {}", id)),
+        from: Some(from.into()),
+        flags: if seen {
+            vec!["\\Seen".into()]
+        } else {
+            Vec::new()
+        },
+        ..common::message(id, folder, 1)
+    }
+}
+
+fn rule(name: &str, query: Option<&str>) -> NotifyRule {
+    NotifyRule {
+        name: name.into(),
+        query: query.map(Into::into),
+        channel: ChannelConfig::Desktop,
+    }
+}
+
+#[test]
+fn rules_select_unread_mail_from_others() {
+    let quiet = Quiet {
+        sent: "[Gmail]/Sent Mail".into(),
+        ignored: vec!["[Gmail]/Spam".into()],
+    };
+    let new = vec![
+        message("hello", "INBOX", "bob@example.com", false),
+        message("boss", "Work", "Boss <boss@example.com>", false),
+        message("read", "INBOX", "bob@example.com", true),
+        message("mine", "INBOX", "Me <me@example.com>", false),
+        message("sent", "[Gmail]/Sent Mail", "me@example.com", false),
+        message("spam", "[Gmail]/Spam", "boss@example.com", false),
+    ];
+    let rules = vec![
+        rule("inbox", None),
+        rule("boss", Some("from:boss@example.com")),
+        rule("nothing", Some("from:nobody@example.com")),
+    ];
+
+    let selected: Vec<(&str, Vec<&str>)> =
+        select(&rules, &new, "me@example.com", &quiet, 0, DisplayTz::Local)
+            .into_iter()
+            .map(|(rule, msgs)| {
+                (
+                    rule.name.as_str(),
+                    msgs.iter().map(|m| m.id.as_str()).collect(),
+                )
+            })
+            .collect();
+    assert_eq!(selected, [("inbox", vec!["hello"]), ("boss", vec!["boss"])]);
+}
+
+/// Serves one HTTP request with 200 and returns it (headers and body) as text.
+async fn one_request(listener: TcpListener) -> String {
+    answer(&listener, "200 OK").await
+}
+
+/// Reads the next request on `listener`, replies with `status` and returns the request.
+async fn answer(listener: &TcpListener, status: &str) -> String {
+    let (mut socket, _) = listener.accept().await.unwrap();
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length = text[..end]
+                .lines()
+                .find_map(|l| {
+                    l.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            if request.len() >= end + 4 + length {
+                break;
+            }
+        }
+        if n == 0 {
+            break;
+        }
+    }
+    socket
+        .write_all(
+            format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await
+        .unwrap();
+    String::from_utf8(request).unwrap()
+}
+
+fn sample() -> Notification {
+    Notification::new(
+        "me@example.com",
+        "inbox",
+        vec![NoteMessage {
+            id: "m1".into(),
+            folder: "INBOX".into(),
+            from: "Bob".into(),
+            subject: "Lunch?".into(),
+        }],
+    )
+}
+
+#[tokio::test]
+async fn ntfy_and_webhook_channels_post_the_notification() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let server = format!("http://{}", listener.local_addr().unwrap());
+    let request = tokio::spawn(one_request(listener));
+    Ntfy::new(&server, "mail").send(&sample()).await.unwrap();
+    let request = request.await.unwrap();
+    assert!(request.starts_with("POST /mail "), "{}", request);
+    assert!(
+        request
+            .to_ascii_lowercase()
+            .contains("title: 1 new message(s) for me@example.com (inbox)")
+    );
+    assert!(request.ends_with("Bob — Lunch?"));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    let request = tokio::spawn(one_request(listener));
+    Webhook::new(&url).send(&sample()).await.unwrap();
+    let request = request.await.unwrap();
+    let body = &request[request.find("\r\n\r\n").unwrap() + 4..];
+    let json: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(json["rule"], "inbox");
+    assert_eq!(json["messages"][0]["subject"], "Lunch?");
+}
+
+#[tokio::test]
+async fn failed_notifications_are_retried_and_sent_ones_are_not_repeated() {
+    let dir = std::env::temp_dir().join(format!("otto-notify-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut account = common::account("acct");
+    account.settings.notify_rules = vec![NotifyRule {
+        name: "inbox".into(),
+        query: None,
+        channel: ChannelConfig::Webhook {
+            url: format!("http://{}/hook", listener.local_addr().unwrap()),
+        },
+    }];
+    db.save_account(&account).await.unwrap();
+    db.commit_folder_batch(
+        "acct",
+        "INBOX",
+        &[message("lunch", "INBOX", "bob@example.com", false)],
+        &[],
+        &[],
+        &[],
+        &FolderStateUpdate::default(),
+        "ok",
+        None,
+        Some(1),
+    )
+    .await
+    .unwrap();
+    let smtp = SmtpEndpoint::default();
+    let dispatch = || notify::dispatch(&db, &account, 0, common::DATE, DisplayTz::Local, &smtp);
+
+    // The webhook fails, so the message is handed back for the next pass.
+    let (sent, request) = tokio::join!(dispatch(), answer(&listener, "503 Service Unavailable"));
+    assert_eq!(sent.unwrap(), 0);
+    assert!(request.contains("lunch"), "{}", request);
+
+    let (sent, request) = tokio::join!(dispatch(), answer(&listener, "200 OK"));
+    assert_eq!(sent.unwrap(), 1);
+    assert!(request.contains("lunch"), "{}", request);
+
+    assert_eq!(dispatch().await.unwrap(), 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
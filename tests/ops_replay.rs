@@ -0,0 +1,80 @@
+use otto::storage::ops::{MessageOp, ReplayOp};
+use otto::sync::OpsExecutor;
+
+fn queued(id: i64, op: MessageOp, folder: &str, uid: Option<u32>) -> ReplayOp {
+    ReplayOp {
+        id,
+        op,
+        folder: Some(folder.to_string()),
+        uid,
+    }
+}
+
+#[test]
+fn replay_plan_sends_net_effect_per_folder() {
+    let ops = vec![
+        queued(1, MessageOp::MarkRead, "INBOX", Some(7)),
+        queued(2, MessageOp::MarkRead, "INBOX", Some(3)),
+        queued(3, MessageOp::MarkUnread, "INBOX", Some(7)),
+        queued(4, MessageOp::AddLabel("Receipts".into()), "INBOX", Some(3)),
+        queued(5, MessageOp::Star, "[Gmail]/Sent Mail", Some(11)),
+        // Archived copy waiting for its All Mail uid: not sent, not settled.
+        queued(6, MessageOp::MarkRead, "[Gmail]/All Mail", None),
+        // Archive/delete are not flag ops.
+        queued(7, MessageOp::Archive, "INBOX", Some(9)),
+    ];
+
+    let plan = OpsExecutor::plan(&ops);
+    assert_eq!(plan.len(), 2);
+
+    let inbox = &plan[0];
+    assert_eq!(inbox.folder, "INBOX");
+    assert_eq!(inbox.op_ids, vec![1, 2, 3, 4]);
+    let stores: Vec<(&str, &[u32])> = inbox
+        .stores
+        .iter()
+        .map(|(item, uids)| (item.as_str(), uids.as_slice()))
+        .collect();
+    assert_eq!(
+        stores,
+        vec![
+            ("+FLAGS.SILENT (\\Seen)", &[3][..]),
+            ("+X-GM-LABELS (\"Receipts\")", &[3][..]),
+            ("-FLAGS.SILENT (\\Seen)", &[7][..]),
+        ]
+    );
+
+    let sent = &plan[1];
+    assert_eq!(sent.folder, "[Gmail]/Sent Mail");
+    assert_eq!(sent.op_ids, vec![5]);
+    assert_eq!(
+        sent.stores.get("+FLAGS.SILENT (\\Flagged)"),
+        Some(&vec![11])
+    );
+}
+
+#[test]
+fn location_ops_batch_consecutive_runs_in_queue_order() {
+    let ops = vec![
+        queued(1, MessageOp::Move("Projects".into()), "INBOX", Some(4)),
+        queued(2, MessageOp::Move("Projects".into()), "INBOX", Some(5)),
+        queued(3, MessageOp::Delete, "INBOX", Some(6)),
+        // Not re-linked yet: waits for a later pass.
+        queued(4, MessageOp::Delete, "[Gmail]/Trash", None),
+        queued(5, MessageOp::Move("Projects".into()), "INBOX", Some(8)),
+    ];
+
+    let batches = OpsExecutor::plan_locations(&ops);
+    let summary: Vec<(MessageOp, Vec<u32>, Vec<i64>)> = batches
+        .into_iter()
+        .map(|b| (b.op, b.uids, b.op_ids))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (MessageOp::Move("Projects".into()), vec![4, 5], vec![1, 2]),
+            (MessageOp::Delete, vec![6], vec![3]),
+            (MessageOp::Move("Projects".into()), vec![8], vec![5]),
+        ]
+    );
+}
@@ -0,0 +1,81 @@
+use base64::Engine;
+use chrono::NaiveDate;
+use otto::oauth::{email_from_id_token, mail_scopes, onboarding_scopes};
+use otto::storage::Database;
+use otto::sync::synced_folders;
+use otto::types::{Account, AccountSettings, Provider};
+
+fn id_token(claims: &str) -> String {
+    let encode = |s: &str| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(s);
+    format!(
+        "{}.{}.signature",
+        encode(r#"{"alg":"RS256","typ":"JWT"}"#),
+        encode(claims)
+    )
+}
+
+#[test]
+fn outlook_accounts_use_microsoft_scopes_and_the_id_token_address() {
+    let scopes: Vec<String> = onboarding_scopes(&Provider::OutlookImap)
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    assert_eq!(
+        scopes,
+        vec![
+            "https://outlook.office.com/IMAP.AccessAsUser.All",
+            "offline_access",
+            "openid",
+            "email"
+        ]
+    );
+    assert_eq!(
+        mail_scopes(&Provider::GmailImap)[0].to_string(),
+        "https://mail.google.com/"
+    );
+    assert_eq!(
+        Provider::OutlookImap.imap_endpoint().host,
+        "outlook.office365.com"
+    );
+    assert_eq!(Provider::GmailImap.imap_endpoint().host, "imap.gmail.com");
+
+    assert_eq!(
+        email_from_id_token(&id_token(r#"{"email":"ana@contoso.com","name":"Ana"}"#)).unwrap(),
+        "ana@contoso.com"
+    );
+    // Personal accounts often carry the address only as the sign-in name.
+    assert_eq!(
+        email_from_id_token(&id_token(r#"{"preferred_username":"ana@outlook.com"}"#)).unwrap(),
+        "ana@outlook.com"
+    );
+    assert!(email_from_id_token(&id_token(r#"{"preferred_username":"+15550100"}"#)).is_err());
+    assert!(email_from_id_token("not-a-jwt").is_err());
+}
+
+#[tokio::test]
+async fn the_provider_is_stored_and_all_mail_mode_stays_gmail_only() {
+    let dir = std::env::temp_dir().join(format!("otto-outlook-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut settings = AccountSettings::with_defaults(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+    settings.folders = vec!["INBOX".into(), "Sent Items".into()];
+    settings.all_mail_mode = true;
+    let account = Account {
+        id: "ana@contoso.com".into(),
+        email: "ana@contoso.com".into(),
+        provider: Provider::OutlookImap,
+        settings,
+        created_at: 0,
+        updated_at: 0,
+    };
+    db.save_account(&account).await.unwrap();
+    let stored = db.list_accounts().await.unwrap().remove(0);
+    assert_eq!(stored.provider, Provider::OutlookImap);
+
+    assert_eq!(
+        synced_folders(&db, &stored).await.unwrap(),
+        vec!["INBOX", "Sent Items"]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,120 @@
+use otto::storage::Database;
+use otto::types::{BodyStatus, MessageRecord};
+
+mod common;
+
+fn message(id: &str, uid: u32, date: i64, status: BodyStatus) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(date),
+        subject: Some("big".into()),
+        size_bytes: Some(50 * 1024 * 1024),
+        body_status: status,
+        created_at: date,
+        updated_at: date,
+        ..common::message(id, "INBOX", uid)
+    }
+}
+
+async fn store(db: &Database, messages: &[MessageRecord]) {
+    db.commit_backfill_batch("acct", "INBOX", messages, &[], &[], None)
+        .await
+        .unwrap();
+}
+
+async fn open_db(name: &str) -> (std::path::PathBuf, Database) {
+    let dir = std::env::temp_dir().join(format!("otto-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut account = common::account("acct");
+    account.settings.folders = vec!["INBOX".into()];
+    account.settings.prefetch_recent = 10;
+    account.settings.max_message_bytes = Some(10 * 1024 * 1024);
+    db.save_account(&account).await.unwrap();
+    (dir, db)
+}
+
+#[tokio::test]
+async fn oversized_messages_wait_until_requested() {
+    let (dir, db) = open_db("oversized").await;
+    let accounts = db.list_accounts().await.unwrap();
+    assert_eq!(
+        accounts[0].settings.max_message_bytes,
+        Some(10 * 1024 * 1024)
+    );
+
+    store(
+        &db,
+        &[
+            message("old", 1, 1_700_000_000, BodyStatus::TooLarge),
+            message("new", 2, 1_700_100_000, BodyStatus::TooLarge),
+        ],
+    )
+    .await;
+
+    // The regular body phase never picks up skipped bodies.
+    assert!(
+        db.load_pending_body_targets("acct", &[], 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    assert!(
+        loaded
+            .iter()
+            .all(|(m, _)| m.body_status == BodyStatus::TooLarge)
+    );
+
+    // Re-syncing a message whose body is already stored keeps it.
+    store(&db, &[message("full", 3, 1_700_000_000, BodyStatus::Full)]).await;
+    store(
+        &db,
+        &[message("full", 3, 1_700_000_000, BodyStatus::TooLarge)],
+    )
+    .await;
+
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    let full = loaded.iter().find(|(m, _)| m.id == "full").unwrap();
+    assert_eq!(full.0.body_status, BodyStatus::Full);
+
+    let one = db
+        .request_oversized_bodies("acct", &["old".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(one, vec![("old".to_string(), "INBOX".to_string(), 1)]);
+    assert!(
+        db.request_oversized_bodies("acct", &["old".to_string()])
+            .await
+            .unwrap()
+            .is_empty()
+    );
+
+    let rest = db.request_oversized_bodies("acct", &[]).await.unwrap();
+    assert_eq!(rest, vec![("new".to_string(), "INBOX".to_string(), 2)]);
+    let pending: Vec<String> = db
+        .load_pending_body_targets("acct", &[], 10)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(id, _, _)| id)
+        .collect();
+    assert_eq!(pending, vec!["new".to_string(), "old".to_string()]);
+
+    // Refetch targets any listed message with a UID, whatever its body status.
+    let targets = db
+        .load_body_targets(
+            "acct",
+            &["full".to_string(), "new".to_string(), "missing".to_string()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        targets,
+        vec![
+            ("new".to_string(), "INBOX".to_string(), 2),
+            ("full".to_string(), "INBOX".to_string(), 3),
+        ]
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
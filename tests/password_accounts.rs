@@ -0,0 +1,279 @@
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use otto::config::AppDefaults;
+use otto::credentials::{imap_secret, run_password_cmd, run_password_cmd_within};
+use otto::imap::ImapClient;
+use otto::onboarding::{PasswordAccountSpec, onboard_password_account, parse_accounts_file};
+use otto::storage::Database;
+use otto::types::{Account, Credential, Provider, TlsMode};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+mod common;
+
+fn account(port: u16, credential: Credential) -> Account {
+    let mut account = common::local_account("me@example.com", port);
+    account.settings.credential = credential;
+    account
+}
+
+/// A server advertising `capabilities` that accepts any login; returns the client lines it
+/// read after CAPABILITY.
+async fn fake_server(capabilities: &'static str) -> (u16, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"* OK Dovecot ready\r\n").await.unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let (tag, verb) = command.split_once(' ').unwrap();
+        assert_eq!(verb, "CAPABILITY");
+        write
+            .write_all(format!("* CAPABILITY {capabilities}\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+        let mut seen = Vec::new();
+        let Ok(Some(command)) = lines.next_line().await else {
+            return seen;
+        };
+        let tag = command.split(' ').next().unwrap().to_string();
+        seen.push(command.clone());
+        if command.ends_with("AUTHENTICATE PLAIN") {
+            write.write_all(b"+ \r\n").await.unwrap();
+            seen.push(lines.next_line().await.unwrap().unwrap());
+        }
+        write
+            .write_all(format!("{tag} OK logged in\r\n").as_bytes())
+            .await
+            .unwrap();
+        let command = lines.next_line().await.unwrap().unwrap();
+        let tag = command.split(' ').next().unwrap();
+        write
+            .write_all(format!("* CAPABILITY IMAP4rev1\r\n{tag} OK done\r\n").as_bytes())
+            .await
+            .unwrap();
+        seen
+    });
+    (port, server)
+}
+
+#[test]
+fn accounts_file_lists_password_accounts() {
+    let specs = parse_accounts_file(
+        r#"
+        [[account]]
+        email = "me@fastmail.com"
+        host = "imap.fastmail.com"
+        password_cmd = "pass show mail/fastmail"
+
+        [[account]]
+        email = "me@proton.me"
+        host = "127.0.0.1"
+        port = 1143
+        tls = "plain"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(specs.len(), 2);
+
+    let fastmail = specs[0].endpoint().unwrap();
+    assert_eq!(
+        (fastmail.host.as_str(), fastmail.port, fastmail.tls),
+        ("imap.fastmail.com", 993, TlsMode::Tls)
+    );
+    assert_eq!(
+        specs[0].credential(),
+        Credential::PasswordCommand {
+            command: "pass show mail/fastmail".into()
+        }
+    );
+    let bridge = specs[1].endpoint().unwrap();
+    assert_eq!((bridge.port, bridge.tls), (1143, TlsMode::Plain));
+    // Without a command the password is expected in the keyring.
+    assert_eq!(specs[1].credential(), Credential::Password);
+
+    assert!(parse_accounts_file("").unwrap().is_empty());
+    // Typos fail loudly instead of adding an account without its password command.
+    let typo = "[[account]]\nemail = \"a@b.c\"\nhost = \"h\"\npasword_cmd = \"x\"\n";
+    assert!(parse_accounts_file(typo).is_err());
+    let remote_plain = "[[account]]\nemail = \"a@b.c\"\nhost = \"mail.b.c\"\ntls = \"plain\"\npassword_cmd = \"x\"\n";
+    let err = parse_accounts_file(remote_plain).unwrap_err();
+    assert!(
+        err.to_string().contains("only allowed for localhost"),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn password_command_output_is_its_first_line() {
+    assert_eq!(
+        run_password_cmd("printf 'hunter2\\nlogin: me\\n'")
+            .await
+            .unwrap(),
+        "hunter2"
+    );
+    let failed = run_password_cmd("echo locked >&2; exit 1")
+        .await
+        .unwrap_err();
+    assert!(failed.to_string().contains("locked"), "{failed:#}");
+    let empty = run_password_cmd("echo 'no entry' >&2").await.unwrap_err();
+    assert!(empty.to_string().contains("no entry"), "{empty:#}");
+}
+
+#[tokio::test]
+async fn stuck_password_commands_are_killed() {
+    let started = Instant::now();
+    let stuck = run_password_cmd_within(
+        "echo 'waiting for pinentry' >&2; sleep 30",
+        Duration::from_millis(300),
+    )
+    .await
+    .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    let message = stuck.to_string();
+    assert!(message.contains("timed out"), "{message}");
+    assert!(message.contains("waiting for pinentry"), "{message}");
+}
+
+#[tokio::test]
+async fn password_accounts_prefer_auth_plain() {
+    let (port, server) = fake_server("IMAP4rev1 AUTH=PLAIN LOGINDISABLED").await;
+    let account = account(
+        port,
+        Credential::PasswordCommand {
+            command: "echo app-password".into(),
+        },
+    );
+    let secret = imap_secret(&account).await.unwrap();
+    let session = ImapClient::connect(&account, &secret).await.unwrap();
+    drop(session);
+
+    let seen = server.await.unwrap();
+    assert!(seen[0].ends_with("AUTHENTICATE PLAIN"), "{seen:?}");
+    assert_eq!(
+        STANDARD.decode(&seen[1]).unwrap(),
+        b"\0me@example.com\0app-password"
+    );
+}
+
+#[tokio::test]
+async fn password_accounts_fall_back_to_login() {
+    let (port, server) = fake_server("IMAP4rev1").await;
+    let with_command = account(
+        port,
+        Credential::PasswordCommand {
+            command: "echo app-password".into(),
+        },
+    );
+    let session = ImapClient::connect(&with_command, "app-password")
+        .await
+        .unwrap();
+    drop(session);
+    let seen = server.await.unwrap();
+    assert!(
+        seen[0].ends_with("LOGIN \"me@example.com\" \"app-password\""),
+        "{seen:?}"
+    );
+
+    let (port, server) = fake_server("IMAP4rev1 LOGINDISABLED").await;
+    let err = ImapClient::connect(&account(port, Credential::Password), "pw")
+        .await
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("LOGINDISABLED"), "{err:#}");
+    assert!(server.await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn credentials_persist_and_legacy_password_commands_migrate() {
+    let dir = std::env::temp_dir().join(format!("otto-password-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let command = Credential::PasswordCommand {
+        command: "pass show mail".into(),
+    };
+    db.save_account(&account(993, command.clone()))
+        .await
+        .unwrap();
+    let mut oauth = account(993, Credential::OAuth);
+    oauth.id = "gmail".into();
+    db.save_account(&oauth).await.unwrap();
+    let loaded = db.list_accounts().await.unwrap();
+    let credential_of = |id: &str| {
+        loaded
+            .iter()
+            .find(|a| a.id == id)
+            .map(|a| a.settings.credential.clone())
+    };
+    assert_eq!(credential_of("me@example.com"), Some(command.clone()));
+    assert_eq!(credential_of("gmail"), Some(Credential::OAuth));
+
+    // Password commands were briefly stored inside the endpoint JSON.
+    sqlx::query(
+        "UPDATE accounts SET credential = NULL, imap_endpoint = json_set(imap_endpoint, '$.password_cmd', 'pass show mail') WHERE id = 'me@example.com'",
+    )
+    .execute(db.pool())
+    .await
+    .unwrap();
+    drop(db);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let migrated = db.list_accounts().await.unwrap();
+    let account = migrated.iter().find(|a| a.id == "me@example.com").unwrap();
+    assert_eq!(account.settings.credential, command);
+    // ...and password accounts off Gmail's server were stored as Gmail.
+    assert_eq!(account.provider, Provider::Imap);
+    let gmail = migrated.iter().find(|a| a.id == "gmail").unwrap();
+    assert_eq!(gmail.provider, Provider::GmailImap);
+    let endpoint: String =
+        sqlx::query_scalar("SELECT imap_endpoint FROM accounts WHERE id = 'me@example.com'")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+    assert!(!endpoint.contains("password_cmd"), "{endpoint}");
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn unreachable_password_accounts_are_generic_imap_with_common_folder_names() {
+    let mut defaults = AppDefaults::load().unwrap();
+    defaults.all_mail_mode = true;
+    let spec = PasswordAccountSpec {
+        email: "me@example.com".into(),
+        host: Some("127.0.0.1".into()),
+        preset: None,
+        port: Some(1),
+        tls: TlsMode::Plain,
+        password_cmd: Some("echo pw".into()),
+    };
+    let (account, discovered) = onboard_password_account(&defaults, &spec).await.unwrap();
+    assert!(discovered.is_empty());
+    assert_eq!(account.provider, Provider::Imap);
+    assert!(!account.settings.all_mail_mode);
+    let expected: Vec<String> = defaults
+        .folders
+        .iter()
+        .map(|folder| match folder.as_str() {
+            "[Gmail]/Sent Mail" => "Sent".to_string(),
+            "[Gmail]/Trash" => "Trash".to_string(),
+            "[Gmail]/Spam" => "Junk".to_string(),
+            other => other.to_string(),
+        })
+        .collect();
+    assert_eq!(account.settings.folders, expected);
+
+    let gmail = PasswordAccountSpec {
+        host: Some("imap.gmail.com".into()),
+        port: None,
+        tls: TlsMode::Tls,
+        ..spec
+    };
+    assert_eq!(
+        Provider::for_password_account(&gmail.endpoint().unwrap()),
+        Provider::GmailImap
+    );
+}
@@ -0,0 +1,299 @@
+//! Runs against a live server when `OTTO_TEST_POSTGRES_URL` is set; skipped otherwise. Each
+//! test works under its own account, so runs can share one database.
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use otto::storage::db::FolderStateUpdate;
+use otto::storage::ops::MessageOp;
+use otto::storage::postgres::PgStore;
+use otto::storage::{BodyStorage, MailStore};
+use otto::types::{BodyRecord, MessageRecord, Signature};
+
+mod common;
+
+const RAW: &[u8] =
+    b"From: a@example.com\r\nTo: Bo <bo@example.com>\r\nSubject: hi\r\n\r\nhello\r\n";
+
+async fn open_store(name: &str) -> Option<(PgStore, String)> {
+    let url = std::env::var("OTTO_TEST_POSTGRES_URL").ok()?;
+    let store = PgStore::connect(&url).await.unwrap();
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let account_id = format!("pg-{}-{}-{}", name, std::process::id(), nanos);
+    store
+        .save_account(&common::account(&account_id))
+        .await
+        .unwrap();
+    Some((store, account_id))
+}
+
+fn message(account_id: &str, id: &str, uid: u32) -> MessageRecord {
+    MessageRecord {
+        id: format!("{}:{}", account_id, id),
+        account_id: account_id.into(),
+        to: Some("Bo <bo@example.com>".into()),
+        size_bytes: Some(RAW.len() as u32),
+        message_id_header: Some(format!("<{}@example.com>", id)),
+        ..common::message(id, "INBOX", uid)
+    }
+}
+
+fn body(message: &MessageRecord) -> BodyRecord {
+    BodyRecord {
+        message_id: message.id.clone(),
+        raw_rfc822: Some(RAW.to_vec()),
+        sanitized_text: Some("hello".into()),
+        mime_summary: None,
+        attachments_json: None,
+        sanitized_at: Some(common::DATE),
+        sanitizer_version: Some(1),
+        degraded: false,
+    }
+}
+
+async fn commit(store: &PgStore, account_id: &str, messages: &[MessageRecord]) {
+    let bodies: Vec<BodyRecord> = messages.iter().map(body).collect();
+    store
+        .commit_folder_batch(
+            account_id,
+            "INBOX",
+            messages,
+            &bodies,
+            &[],
+            &[],
+            &FolderStateUpdate {
+                uidvalidity: Some(7),
+                highest_uid: Some(2),
+                ..Default::default()
+            },
+            "ok",
+            None,
+            Some(2),
+        )
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn accounts_round_trip() {
+    let Some((store, account_id)) = open_store("accounts").await else {
+        return;
+    };
+    assert!(store.describe().starts_with("postgres "));
+
+    let accounts = store.list_accounts().await.unwrap();
+    let saved = accounts.iter().find(|a| a.id == account_id).unwrap();
+    assert_eq!(saved.email, "me@example.com");
+    assert_eq!(
+        saved.settings.cutoff_since,
+        common::account(&account_id).settings.cutoff_since
+    );
+    assert!(saved.settings.enabled);
+}
+
+#[tokio::test]
+async fn folder_batches_store_messages_and_bodies_in_both_layouts() {
+    for storage in [BodyStorage::Inline, BodyStorage::ContentAddressed] {
+        let Some((store, account_id)) = open_store("batch").await else {
+            return;
+        };
+        store.set_body_storage(storage);
+        commit(
+            &store,
+            &account_id,
+            &[message(&account_id, "1", 1), message(&account_id, "2", 2)],
+        )
+        .await;
+
+        let loaded = store.load_messages(&account_id, 10).await.unwrap();
+        assert_eq!(loaded.len(), 2);
+        for (message, body) in &loaded {
+            assert_eq!(message.subject.as_deref(), Some("hi"));
+            assert!(message.thread_id.is_some());
+            assert_eq!(body.as_ref().unwrap().raw_rfc822.as_deref(), Some(RAW));
+        }
+        let folders = store.list_folders(&account_id).await.unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].uidvalidity, Some(7));
+        assert_eq!(
+            store.unread_counts(&account_id).await.unwrap(),
+            HashMap::from([("INBOX".to_string(), 2)])
+        );
+        let raw_id = format!("{}:1", account_id);
+        assert_eq!(
+            store.load_raw_message(&account_id, &raw_id).await.unwrap(),
+            Some(RAW.to_vec())
+        );
+        assert_eq!(
+            store
+                .delete_messages_by_folder(&account_id, "INBOX")
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(
+            store
+                .load_messages(&account_id, 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let report = store.recover_interrupted_writes().await.unwrap();
+        assert_eq!(report.orphan_bodies, 0);
+    }
+}
+
+#[tokio::test]
+async fn message_ops_queue_and_roll_back() {
+    let Some((store, account_id)) = open_store("ops").await else {
+        return;
+    };
+    let first = message(&account_id, "1", 1);
+    commit(&store, &account_id, std::slice::from_ref(&first)).await;
+
+    let queued = store
+        .apply_message_op(
+            &account_id,
+            &MessageOp::MarkRead,
+            std::slice::from_ref(&first.id),
+        )
+        .await
+        .unwrap();
+    assert_eq!(queued, 1);
+    assert!(store.unread_counts(&account_id).await.unwrap().is_empty());
+
+    let pending = store.list_pending_ops(&account_id).await.unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].target, first.id);
+    store.rollback_pending_ops(&[pending[0].id]).await.unwrap();
+    assert!(
+        store
+            .list_pending_ops(&account_id)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(
+        store.unread_counts(&account_id).await.unwrap(),
+        HashMap::from([("INBOX".to_string(), 1)])
+    );
+}
+
+#[tokio::test]
+async fn notes_reply_later_signatures_and_addresses() {
+    let Some((store, account_id)) = open_store("extras").await else {
+        return;
+    };
+    let first = message(&account_id, "1", 1);
+    commit(&store, &account_id, std::slice::from_ref(&first)).await;
+
+    assert!(
+        store
+            .set_message_note(&account_id, &first.id, "call back")
+            .await
+            .unwrap()
+    );
+    let notes = store.load_message_notes(&account_id).await.unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].note, "call back");
+    assert_eq!(notes[0].subject.as_deref(), Some("hi"));
+
+    assert_eq!(
+        store
+            .set_reply_later(&account_id, std::slice::from_ref(&first.id), None)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(store.load_reply_later(&account_id).await.unwrap().len(), 1);
+
+    store
+        .save_signature(&Signature {
+            account_id: account_id.clone(),
+            alias: None,
+            body: "Bo".into(),
+            above_quote: true,
+        })
+        .await
+        .unwrap();
+    let signature = store
+        .load_signature(&account_id, Some("alias@example.com"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (signature.body.as_str(), signature.above_quote),
+        ("Bo", true)
+    );
+    assert!(store.clear_signature(&account_id, None).await.unwrap());
+    assert!(
+        store
+            .load_signature(&account_id, None)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let suggestions = store.suggest_addresses(&account_id, "bo", 5).await.unwrap();
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].addr, "bo@example.com");
+    assert_eq!(suggestions[0].name.as_deref(), Some("Bo"));
+}
+
+#[tokio::test]
+async fn concurrent_claims_never_share_a_message() {
+    let Some((store, account_id)) = open_store("claims").await else {
+        return;
+    };
+    let messages: Vec<MessageRecord> = (1..=200)
+        .map(|uid| message(&account_id, &format!("m{uid}"), uid))
+        .collect();
+    commit(&store, &account_id, &messages).await;
+
+    let store = Arc::new(store);
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let store = store.clone();
+            let account_id = account_id.clone();
+            tokio::spawn(async move {
+                let mut ids = Vec::new();
+                loop {
+                    let claimed = store
+                        .claim_unprocessed_messages("summarizer", &account_id, 0, 7)
+                        .await
+                        .unwrap();
+                    if claimed.is_empty() {
+                        return ids;
+                    }
+                    ids.extend(claimed.into_iter().map(|m| m.id));
+                }
+            })
+        })
+        .collect();
+    let mut seen = HashSet::new();
+    for worker in workers {
+        for id in worker.await.unwrap() {
+            assert!(seen.insert(id.clone()), "{id} claimed twice");
+        }
+    }
+    assert_eq!(seen.len(), messages.len());
+
+    let released: Vec<String> = messages[..3].iter().map(|m| m.id.clone()).collect();
+    assert_eq!(
+        store
+            .release_processed_messages("summarizer", &released)
+            .await
+            .unwrap(),
+        3
+    );
+    let again = store
+        .claim_unprocessed_messages("summarizer", &account_id, 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        again.into_iter().map(|m| m.id).collect::<Vec<_>>(),
+        released
+    );
+}
@@ -0,0 +1,148 @@
+use chrono::NaiveDate;
+use otto::preview::{clean_lines, preview_text, source_for};
+use otto::storage::Database;
+use otto::storage::crypto::ColumnCipher;
+use otto::types::{AccountSettings, FolderPolicy, MessageRecord, PreviewSource};
+
+mod common;
+
+const NEWSLETTER: &str = "View this email in your browser\n\
+    [Acme logo]\n\
+    https://acme.example.com/newsletter/42\n\
+    ----------------------------------------\n\
+    \n\
+    Spring sale: everything 20% off until Friday.\n\
+    Shop the [collection][1] now.\n\
+    \n\
+    [1]: https://acme.example.com/shop\n";
+
+const REPLY: &str = "Sounds good, see you at 3.\n\
+    \n\
+    On Mon, 3 Mar 2025 at 10:00, Ana <ana@example.com> wrote:\n\
+    > Can we meet tomorrow?\n";
+
+#[test]
+fn clean_previews_skip_boilerplate_and_quotes() {
+    assert_eq!(
+        preview_text(PreviewSource::Clean, NEWSLETTER, None, 1),
+        "Spring sale: everything 20% off until Friday."
+    );
+    assert_eq!(
+        preview_text(PreviewSource::Raw, NEWSLETTER, None, 1),
+        "View this email in your browser"
+    );
+    assert_eq!(
+        preview_text(PreviewSource::Clean, NEWSLETTER, None, 2),
+        "Spring sale: everything 20% off until Friday. Shop the [collection][1] now."
+    );
+
+    assert_eq!(
+        clean_lines(REPLY).collect::<Vec<_>>(),
+        vec!["Sounds good, see you at 3."]
+    );
+    assert_eq!(
+        preview_text(PreviewSource::Clean, "> quoted only\n", None, 1),
+        ""
+    );
+
+    assert_eq!(
+        preview_text(
+            PreviewSource::Summary,
+            NEWSLETTER,
+            Some("Acme: 20% off sale"),
+            1
+        ),
+        "Acme: 20% off sale"
+    );
+    // No summary yet: the clean preview stands in.
+    assert_eq!(
+        preview_text(PreviewSource::Summary, REPLY, None, 1),
+        "Sounds good, see you at 3."
+    );
+}
+
+#[test]
+fn folder_policies_override_the_global_source() {
+    let mut settings = AccountSettings::with_defaults(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap());
+    settings.folder_policies.insert(
+        "Newsletters".into(),
+        FolderPolicy {
+            preview: Some(PreviewSource::Summary),
+            ..FolderPolicy::default()
+        },
+    );
+    assert_eq!(
+        source_for(Some(&settings), "Newsletters"),
+        PreviewSource::Summary
+    );
+    assert_eq!(source_for(Some(&settings), "INBOX"), PreviewSource::Clean);
+    assert_eq!(source_for(None, "Newsletters"), PreviewSource::Clean);
+    assert_eq!(PreviewSource::parse(" RAW ").unwrap(), PreviewSource::Raw);
+    assert!(PreviewSource::parse("first-line").is_err());
+}
+
+fn message(id: &str, uid: u32) -> MessageRecord {
+    MessageRecord {
+        subject: Some(format!("about {}", id)),
+        ..common::message(id, "INBOX", uid)
+    }
+}
+
+#[tokio::test]
+async fn summaries_are_stored_per_message_and_sealed_with_the_columns() {
+    let dir = std::env::temp_dir().join(format!("otto-summaries-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("m1", 1), message("m2", 2)],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert!(
+        !db.set_message_summary("acct", "unknown", "x")
+            .await
+            .unwrap()
+    );
+    assert!(db.set_message_summary("acct", "m1", "draft").await.unwrap());
+    assert!(
+        db.set_message_summary("acct", "m1", "Invoice due Friday")
+            .await
+            .unwrap()
+    );
+    let summaries = db.load_message_summaries("acct").await.unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries["m1"], "Invoice due Friday");
+
+    let cipher = ColumnCipher::new(&[7u8; 32]);
+    db.reseal_account("acct", None, Some(&cipher))
+        .await
+        .unwrap();
+    db.register_cipher("acct", Some(cipher));
+    assert!(
+        db.set_message_summary("acct", "m2", "Lunch?")
+            .await
+            .unwrap()
+    );
+    let summaries = db.load_message_summaries("acct").await.unwrap();
+    assert_eq!(summaries["m1"], "Invoice due Friday");
+    assert_eq!(summaries["m2"], "Lunch?");
+    db.register_cipher("acct", None);
+    let sealed = db.load_message_summaries("acct").await.unwrap();
+    assert!(
+        sealed
+            .values()
+            .all(|s| s != "Lunch?" && !s.contains("Invoice"))
+    );
+
+    db.delete_message("m1").await.unwrap();
+    assert_eq!(db.load_message_summaries("acct").await.unwrap().len(), 1);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,114 @@
+use std::collections::HashSet;
+
+use otto::storage::MailStore;
+use otto::storage::db::{Database, FolderStateUpdate};
+use otto::types::MessageRecord;
+
+mod common;
+
+async fn open(name: &str, count: u32) -> Database {
+    let dir = std::env::temp_dir().join(format!("otto-processed-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    let messages: Vec<MessageRecord> = (1..=count)
+        .map(|uid| MessageRecord {
+            internal_date: Some(common::DATE + uid as i64),
+            created_at: common::DATE + uid as i64,
+            ..common::message(&format!("m{uid}"), "INBOX", uid)
+        })
+        .collect();
+    db.commit_folder_batch(
+        "acct",
+        "INBOX",
+        &messages,
+        &[],
+        &[],
+        &[],
+        &FolderStateUpdate {
+            uidvalidity: Some(1),
+            highest_uid: Some(count),
+            ..Default::default()
+        },
+        "ok",
+        None,
+        Some(count),
+    )
+    .await
+    .unwrap();
+    db
+}
+
+/// Claims `batch` at a time until nothing is left; returns every id this consumer got.
+async fn drain(store: Database, batch: usize) -> Vec<String> {
+    let mut ids = Vec::new();
+    loop {
+        let claimed = store
+            .claim_unprocessed_messages("summarizer", "acct", 0, batch)
+            .await
+            .unwrap();
+        if claimed.is_empty() {
+            return ids;
+        }
+        ids.extend(claimed.into_iter().map(|m| m.id));
+        tokio::task::yield_now().await;
+    }
+}
+
+#[tokio::test]
+async fn concurrent_claims_never_share_a_message() {
+    let db = open("concurrent", 300).await;
+    let workers: Vec<_> = (0..4).map(|_| tokio::spawn(drain(db.clone(), 7))).collect();
+    let mut seen = HashSet::new();
+    for worker in workers {
+        for id in worker.await.unwrap() {
+            assert!(seen.insert(id.clone()), "{id} claimed twice");
+        }
+    }
+    assert_eq!(seen.len(), 300);
+}
+
+#[tokio::test]
+async fn claims_go_oldest_first_and_released_messages_come_back() {
+    let db = open("release", 5).await;
+    let store: &dyn MailStore = &db;
+    let first = store
+        .claim_unprocessed_messages("rules", "acct", 0, 2)
+        .await
+        .unwrap();
+    let ids: Vec<String> = first.iter().map(|m| m.id.clone()).collect();
+    assert_eq!(ids, ["m1", "m2"]);
+
+    // Another consumer keeps its own cursor; only messages cached since the bound count.
+    let recent = store
+        .claim_unprocessed_messages("mcp", "acct", common::DATE + 4, 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        recent.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+        ["m4", "m5"]
+    );
+
+    assert_eq!(
+        store
+            .release_processed_messages("rules", &ids[1..])
+            .await
+            .unwrap(),
+        1
+    );
+    let again = store
+        .claim_unprocessed_messages("rules", "acct", 0, 10)
+        .await
+        .unwrap();
+    assert_eq!(
+        again.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+        ["m2", "m3", "m4", "m5"]
+    );
+    assert!(
+        store
+            .claim_unprocessed_messages("rules", "acct", 0, 10)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,56 @@
+use otto::profile;
+
+#[test]
+fn profiles_get_their_own_directory_and_keyring_service() {
+    let base = std::env::temp_dir().join(format!("otto-profiles-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(&base).unwrap();
+
+    assert_eq!(profile::resolve(None, None, &base).unwrap(), None);
+    assert_eq!(profile::list(&base).unwrap(), vec!["default"]);
+
+    let work = profile::switch(&base, "work").unwrap();
+    assert_eq!(work, base.join("profiles").join("work"));
+    assert!(work.is_dir());
+    profile::switch(&base, "home-2").unwrap();
+    assert_eq!(
+        profile::list(&base).unwrap(),
+        vec!["default", "home-2", "work"]
+    );
+
+    // The flag beats the environment, which beats the switched-to profile.
+    assert_eq!(
+        profile::resolve(None, None, &base).unwrap().as_deref(),
+        Some("home-2")
+    );
+    assert_eq!(
+        profile::resolve(None, Some("work"), &base)
+            .unwrap()
+            .as_deref(),
+        Some("work")
+    );
+    assert_eq!(
+        profile::resolve(Some("default"), Some("work"), &base).unwrap(),
+        None
+    );
+    assert!(profile::resolve(Some("../escape"), None, &base).is_err());
+    assert!(profile::switch(&base, "a/b").is_err());
+
+    profile::switch(&base, "default").unwrap();
+    assert_eq!(profile::resolve(None, None, &base).unwrap(), None);
+    assert_eq!(profile::data_dir(&base, None), base);
+
+    profile::set_active(Some("work".into()));
+    assert_eq!(
+        profile::keyring_service("otto-imap-password"),
+        "otto-imap-password@work"
+    );
+    profile::set_active(Some("default".into()));
+    assert_eq!(profile::active(), None);
+    assert_eq!(
+        profile::keyring_service("otto-imap-password"),
+        "otto-imap-password"
+    );
+
+    let _ = std::fs::remove_dir_all(&base);
+}
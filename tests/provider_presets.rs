@@ -0,0 +1,78 @@
+use otto::onboarding::{parse_accounts_file, select_folders};
+use otto::presets;
+use otto::types::{FolderRole, MailboxInfo, TlsMode};
+
+fn mailbox(name: &str, attributes: &[&str]) -> MailboxInfo {
+    MailboxInfo {
+        name: name.into(),
+        delimiter: Some("/".into()),
+        attributes: attributes.iter().map(|a| a.to_string()).collect(),
+    }
+}
+
+#[test]
+fn presets_fill_in_server_and_special_folders() {
+    let specs = parse_accounts_file(
+        r#"
+        [[account]]
+        email = "me@fastmail.com"
+        preset = "Fastmail"
+
+        [[account]]
+        email = "me@icloud.com"
+        preset = "icloud"
+        port = 1993
+        password_cmd = "pass show icloud"
+
+        [[account]]
+        email = "me@yahoo.com"
+        preset = "yahoo"
+        host = "imap.example.net"
+        "#,
+    )
+    .unwrap();
+    let endpoints: Vec<_> = specs.iter().map(|s| s.endpoint().unwrap()).collect();
+    assert_eq!(endpoints[0].host, "imap.fastmail.com");
+    assert_eq!((endpoints[0].port, endpoints[0].tls), (993, TlsMode::Tls));
+    assert_eq!(
+        (endpoints[1].host.as_str(), endpoints[1].port),
+        ("imap.mail.me.com", 1993)
+    );
+    // An explicit host overrides the preset's server.
+    assert_eq!(endpoints[2].host, "imap.example.net");
+
+    // OAuth providers are onboarded with --provider, unknown names are refused.
+    assert!(
+        parse_accounts_file("[[account]]\nemail = \"a@gmail.com\"\npreset = \"gmail\"").is_err()
+    );
+    assert!(parse_accounts_file("[[account]]\nemail = \"a@b.c\"\npreset = \"aol\"").is_err());
+    assert!(parse_accounts_file("[[account]]\nemail = \"a@b.c\"").is_err());
+
+    let yahoo = presets::find("yahoo").unwrap();
+    assert_eq!(yahoo.folder(FolderRole::Junk), Some("Bulk"));
+    let defaults = vec![
+        "INBOX".to_string(),
+        "[Gmail]/Sent Mail".to_string(),
+        "[Gmail]/Spam".to_string(),
+    ];
+    assert_eq!(
+        yahoo.rename_defaults(&defaults),
+        vec!["INBOX", "Sent", "Bulk"]
+    );
+
+    // A server without SPECIAL-USE gets the preset's roles, so the Gmail defaults resolve.
+    let mut listed = vec![
+        mailbox("INBOX", &[]),
+        mailbox("Sent", &[]),
+        mailbox("Bulk", &[]),
+        mailbox("Spam", &["\\Junk"]),
+    ];
+    yahoo.apply_roles(&mut listed);
+    assert_eq!(listed[1].role(), Some(FolderRole::Sent));
+    // The server's own attribute wins over the preset's name.
+    assert_eq!(listed[2].role(), None);
+    assert_eq!(
+        select_folders(&defaults, &listed),
+        vec!["INBOX", "Sent", "Spam"]
+    );
+}
@@ -0,0 +1,199 @@
+use chrono::NaiveDate;
+use otto::address::{Mailbox, parse_address_list};
+use otto::smart_folders::SmartQuery;
+use otto::storage::Database;
+use otto::storage::addresses::{AddressField, Recipient};
+use otto::timefmt::DisplayTz;
+use otto::types::{Account, AccountSettings, MessageRecord, Provider, now_ts};
+
+mod common;
+
+fn message(id: &str, date: i64, to: &str, cc: Option<&str>) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(date),
+        subject: Some(format!("about {}", id)),
+        from: Some("boss@example.com".into()),
+        to: Some(to.into()),
+        cc: cc.map(str::to_string),
+        created_at: date,
+        updated_at: date,
+        ..common::message(id, "INBOX", date as u32)
+    }
+}
+
+#[test]
+fn address_lists_keep_every_mailbox_in_order() {
+    assert_eq!(
+        parse_address_list("\"Doe, Jane\" <jane@example.com>, bob@example.com"),
+        vec![
+            Mailbox {
+                name: Some("Doe, Jane".into()),
+                addr: "jane@example.com".into(),
+            },
+            Mailbox {
+                name: None,
+                addr: "bob@example.com".into(),
+            },
+        ]
+    );
+    assert_eq!(
+        parse_address_list("team: a@example.com, b@example.com;")
+            .into_iter()
+            .map(|m| m.addr)
+            .collect::<Vec<_>>(),
+        ["a@example.com", "b@example.com"]
+    );
+}
+
+#[tokio::test]
+async fn recipients_are_indexed_by_field() {
+    let dir = std::env::temp_dir().join(format!("otto-recipients-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    let direct = message("direct", 3, "Me <Me@Example.com>", None);
+    let both = message(
+        "both",
+        2,
+        "me@example.com, team@example.com",
+        Some("me@example.com"),
+    );
+    let copied = message("copied", 1, "team@example.com", Some("Me <me@example.com>"));
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[direct.clone(), both.clone(), copied.clone()],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        db.load_recipients("copied").await.unwrap(),
+        vec![
+            Recipient {
+                field: AddressField::To,
+                name: None,
+                addr: "team@example.com".into(),
+            },
+            Recipient {
+                field: AddressField::Cc,
+                name: Some("Me".into()),
+                addr: "me@example.com".into(),
+            },
+        ]
+    );
+    let find = |fields: &'static [AddressField], not: &'static [AddressField]| {
+        let db = &db;
+        async move {
+            db.find_messages_by_recipient("acct", "ME@example.com", fields, not)
+                .await
+                .unwrap()
+        }
+    };
+    assert_eq!(
+        find(&[AddressField::To, AddressField::Cc], &[]).await,
+        ["direct", "both", "copied"]
+    );
+    assert_eq!(
+        find(&[AddressField::To], &[AddressField::Cc]).await,
+        ["direct"]
+    );
+    assert_eq!(
+        find(&[AddressField::Cc], &[AddressField::To]).await,
+        ["copied"]
+    );
+
+    // Re-syncing a message rewrites its rows.
+    let mut moved = direct.clone();
+    moved.to = Some("team@example.com".into());
+    db.commit_backfill_batch("acct", "INBOX", &[moved], &[], &[], None)
+        .await
+        .unwrap();
+    assert_eq!(
+        find(&[AddressField::To], &[AddressField::Cc]).await,
+        Vec::<String>::new()
+    );
+
+    let query = SmartQuery::parse("to:me@example.com -cc:me@example.com").unwrap();
+    let tz = DisplayTz::Named(chrono_tz::UTC);
+    assert!(query.matches(&direct, 0, tz));
+    assert!(!query.matches(&both, 0, tz));
+    assert!(!query.matches(&copied, 0, tz));
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[tokio::test]
+async fn suggestions_rank_by_frequency_and_recency() {
+    let dir = std::env::temp_dir().join(format!("otto-suggest-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&Account {
+        id: "acct".into(),
+        email: "Me@example.com".into(),
+        provider: Provider::GmailImap,
+        settings: AccountSettings::with_defaults(NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()),
+        created_at: 0,
+        updated_at: 0,
+    })
+    .await
+    .unwrap();
+    let now = now_ts();
+    let day = 86_400;
+    let mut messages = Vec::new();
+    let mut add = |to: &str, days_ago: i64| {
+        let date = now - days_ago * day + messages.len() as i64;
+        messages.push(message(&format!("m{}", messages.len()), date, to, None));
+    };
+    for _ in 0..3 {
+        add("alice@example.com, me@example.com", 400);
+    }
+    add("Alina Smith <alina@example.com>", 0);
+    add("\"Alder, Bob\" <bob@example.com>", 10);
+    for _ in 0..5 {
+        add("carl@example.com", 60);
+    }
+    add("cara@example.com", 0);
+    db.commit_backfill_batch("acct", "INBOX", &messages, &[], &[], None)
+        .await
+        .unwrap();
+
+    let suggest = |prefix: &'static str| {
+        let db = &db;
+        async move {
+            db.suggest_addresses("acct", prefix, 10)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|s| s.recipient())
+                .collect::<Vec<_>>()
+        }
+    };
+    // Recent beats old; a name word matches as well as the address.
+    assert_eq!(
+        suggest("AL").await,
+        [
+            "Alina Smith <alina@example.com>",
+            "\"Alder, Bob\" <bob@example.com>",
+            "alice@example.com"
+        ]
+    );
+    // Five messages two months ago outrank one today.
+    assert_eq!(
+        suggest("car").await,
+        ["carl@example.com", "cara@example.com"]
+    );
+    // The account's own address and LIKE wildcards never match.
+    assert!(suggest("me").await.is_empty());
+    assert!(suggest("a_").await.is_empty());
+    assert!(suggest(" ").await.is_empty());
+
+    let alice = db.suggest_addresses("acct", "alice", 1).await.unwrap();
+    assert_eq!(alice[0].uses, 3);
+    assert_eq!(alice[0].last_seen, now - 400 * day + 2);
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
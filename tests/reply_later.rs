@@ -0,0 +1,103 @@
+use chrono::NaiveDate;
+use otto::storage::Database;
+use otto::storage::reply_later::parse_due;
+use otto::types::{Account, AccountSettings, MessageRecord, Provider};
+
+mod common;
+
+fn day(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+fn message(id: &str, uid: u32) -> MessageRecord {
+    MessageRecord {
+        subject: Some(format!("about {}", id)),
+        ..common::message(id, "INBOX", uid)
+    }
+}
+
+#[test]
+fn due_dates_accept_absolute_and_relative_forms() {
+    let today = day(2026, 10, 17);
+    assert_eq!(parse_due("", today).unwrap(), None);
+    assert_eq!(
+        parse_due("2026-11-01", today).unwrap(),
+        Some(day(2026, 11, 1))
+    );
+    assert_eq!(parse_due("+3", today).unwrap(), Some(day(2026, 10, 20)));
+    assert_eq!(parse_due("+14d", today).unwrap(), Some(day(2026, 10, 31)));
+    assert_eq!(
+        parse_due("Tomorrow", today).unwrap(),
+        Some(day(2026, 10, 18))
+    );
+    assert!(parse_due("next week", today).is_err());
+}
+
+#[tokio::test]
+async fn reply_later_queue_orders_by_due_date_and_follows_its_messages() {
+    let dir = std::env::temp_dir().join(format!("otto-reply-later-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&Account {
+        id: "acct".into(),
+        email: "me@example.com".into(),
+        provider: Provider::GmailImap,
+        settings: AccountSettings::with_defaults(day(2025, 1, 1)),
+        created_at: 0,
+        updated_at: 0,
+    })
+    .await
+    .unwrap();
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("m1", 1), message("m2", 2), message("m3", 3)],
+        &[],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let ids = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        db.set_reply_later("acct", &ids(&["m1", "unknown"]), None)
+            .await
+            .unwrap(),
+        1
+    );
+    db.set_reply_later("acct", &ids(&["m2"]), Some(day(2026, 10, 20)))
+        .await
+        .unwrap();
+    db.set_reply_later("acct", &ids(&["m3"]), Some(day(2026, 10, 30)))
+        .await
+        .unwrap();
+    // Re-adding changes the due date.
+    db.set_reply_later("acct", &ids(&["m3"]), Some(day(2026, 10, 10)))
+        .await
+        .unwrap();
+
+    let queue = db.load_reply_later("acct").await.unwrap();
+    assert_eq!(
+        queue
+            .iter()
+            .map(|e| e.message_id.as_str())
+            .collect::<Vec<_>>(),
+        ["m3", "m2", "m1"]
+    );
+    assert_eq!(queue[0].subject.as_deref(), Some("about m3"));
+    assert!(queue[0].is_overdue(day(2026, 10, 17)));
+    assert!(!queue[1].is_overdue(day(2026, 10, 17)));
+    assert!(!queue[2].is_overdue(day(2026, 10, 17)));
+
+    assert_eq!(
+        db.clear_reply_later("acct", &ids(&["m2"])).await.unwrap(),
+        1
+    );
+    db.delete_message("m3").await.unwrap();
+    let queue = db.load_reply_later("acct").await.unwrap();
+    assert_eq!(queue.len(), 1);
+    assert_eq!(queue[0].message_id, "m1");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,62 @@
+use otto::responses::{Folders, format_duration, track};
+use otto::types::MessageRecord;
+
+mod common;
+
+const HOUR: i64 = 60 * 60;
+
+fn message(id: &str, thread: &str, folder: &str, from: &str, at: i64) -> MessageRecord {
+    MessageRecord {
+        thread_id: Some(thread.into()),
+        internal_date: Some(at),
+        subject: Some(format!("about {}", thread)),
+        from: Some(from.into()),
+        message_id_header: Some(format!("<{}@example.com>", id)),
+        ..common::message(id, folder, 1)
+    }
+}
+
+#[test]
+fn replies_are_matched_through_threads() {
+    let folders = Folders {
+        sent: "[Gmail]/Sent Mail".into(),
+        ignored: vec!["[Gmail]/Drafts".into(), "[Gmail]/Trash".into()],
+    };
+    let sent = "[Gmail]/Sent Mail";
+    let messages = vec![
+        // Answered after two hours, then answered again (an hour later).
+        message("ask", "t1", sent, "me@example.com", 0),
+        message("answer", "t1", "INBOX", "bob@example.com", 2 * HOUR),
+        message("thanks", "t1", sent, "Me <me@example.com>", 3 * HOUR),
+        message("welcome", "t1", "INBOX", "bob@example.com", 4 * HOUR),
+        // Followed up before any reply: only the follow-up waits.
+        message("ping", "t2", sent, "me@example.com", 5 * HOUR),
+        message("ping-again", "t2", sent, "me@example.com", 6 * HOUR),
+        // Incoming only.
+        message("news", "t3", "INBOX", "news@example.com", 7 * HOUR),
+        // A note to self cached twice; a draft and trashed mail never count.
+        message("to-self", "t4", sent, "me@example.com", 20 * HOUR),
+        message("to-self", "t4", "INBOX", "me@example.com", 20 * HOUR),
+        message("draft", "t5", "[Gmail]/Drafts", "me@example.com", 8 * HOUR),
+        message("gone", "t6", "[Gmail]/Trash", "me@example.com", 9 * HOUR),
+    ];
+
+    let stats = track(&messages, "me@example.com", &folders);
+    assert_eq!(stats.sent, 5);
+    let answered: Vec<(&str, &str, i64)> = stats
+        .answered
+        .iter()
+        .map(|a| (a.sent.id.as_str(), a.reply.id.as_str(), a.response_secs()))
+        .collect();
+    assert_eq!(
+        answered,
+        [("ask", "answer", 2 * HOUR), ("thanks", "welcome", HOUR)]
+    );
+    assert_eq!(stats.average_response_secs(), Some(HOUR + HOUR / 2));
+    let awaiting: Vec<&str> = stats.awaiting.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(awaiting, ["ping-again", "to-self"]);
+
+    assert_eq!(format_duration(HOUR + HOUR / 2), "1h 30m");
+    assert_eq!(format_duration(50 * HOUR), "2d 2h");
+    assert_eq!(format_duration(30), "<1m");
+}
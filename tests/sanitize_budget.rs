@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use mailparse::parse_mail;
+use otto::sanitize::resanitize::rerender_message;
+use otto::sanitize::{build_body_record, sanitize_message_with_budget, strip_tags};
+use otto::storage::Database;
+use otto::timefmt::DisplayTz;
+use otto::tui::build_mail_items;
+use otto::types::MessageRecord;
+
+mod common;
+
+/// An HTML message whose table nesting keeps html2text busy for a while.
+fn nested_tables() -> Vec<u8> {
+    let depth = 400;
+    let html = format!(
+        "<html><head><style>td {{ color: red }}</style></head><body>{}<b>Invoice</b> &amp; receipt{}<p>Thanks</p></body></html>",
+        "<table><tr><td>".repeat(depth),
+        "</td></tr></table>".repeat(depth),
+    );
+    format!(
+        "Subject: nested\r\nContent-Type: text/html; charset=utf-8\r\n\r\n{}\r\n",
+        html
+    )
+    .into_bytes()
+}
+
+#[test]
+fn strip_tags_keeps_the_text_and_line_breaks() {
+    assert_eq!(
+        strip_tags(
+            "<style>p { x: 1 }</style><!-- hi --><p>One &amp; <b>two</b></p><div>three<br>four</div><script>x()</script>"
+        ),
+        "One & two\nthree\nfour"
+    );
+}
+
+#[test]
+fn html_over_budget_is_tag_stripped_and_marked_degraded() {
+    let raw = nested_tables();
+    let parsed = parse_mail(&raw).unwrap();
+
+    let degraded = sanitize_message_with_budget(&parsed, &raw, Some(Duration::from_nanos(1)));
+    assert!(degraded.degraded);
+    assert!(degraded.sanitized_text.contains("Invoice & receipt"));
+    assert!(!degraded.sanitized_text.contains('<'));
+
+    let full = sanitize_message_with_budget(&parsed, &raw, None);
+    assert!(!full.degraded);
+    assert!(full.sanitized_text.contains("Invoice"));
+}
+
+#[test]
+fn small_html_is_rendered_in_full_whatever_the_budget() {
+    let raw = b"Subject: short\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<p>Short <b>note</b></p>\r\n";
+    let parsed = parse_mail(raw).unwrap();
+
+    let sanitized = sanitize_message_with_budget(&parsed, raw, Some(Duration::from_nanos(1)));
+    assert!(!sanitized.degraded);
+    assert!(sanitized.sanitized_text.contains("Short"));
+}
+
+fn message(id: &str) -> MessageRecord {
+    MessageRecord {
+        subject: Some("nested".into()),
+        ..common::message(id, "INBOX", 1)
+    }
+}
+
+#[tokio::test]
+async fn degraded_bodies_are_stored_and_rerendered_on_demand() {
+    let dir = std::env::temp_dir().join(format!("otto-sanitize-budget-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+
+    let raw = nested_tables();
+    let parsed = parse_mail(&raw).unwrap();
+    let sanitized = sanitize_message_with_budget(&parsed, &raw, Some(Duration::from_nanos(1)));
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("m1")],
+        &[build_body_record("m1", Some(raw.clone()), sanitized)],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+    let items = build_mail_items(
+        &db.load_messages("acct", 10).await.unwrap(),
+        DisplayTz::default(),
+    );
+    assert!(items[0].degraded);
+
+    assert!(rerender_message(&db, "acct", "m1").await.unwrap());
+    let loaded = db.load_messages("acct", 10).await.unwrap();
+    let body = loaded[0].1.as_ref().unwrap();
+    assert!(!body.degraded);
+    assert_eq!(body.raw_rfc822.as_deref(), Some(raw.as_slice()));
+    assert!(!build_mail_items(&loaded, DisplayTz::default())[0].degraded);
+
+    assert!(!rerender_message(&db, "acct", "missing").await.unwrap());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
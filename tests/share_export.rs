@@ -0,0 +1,97 @@
+use otto::share::{ShareOptions, render_thread_html};
+use otto::timefmt::DisplayTz;
+use otto::types::{BodyRecord, MessageRecord};
+
+mod common;
+
+const RAW: &str = "From: Ana <ana@example.com>\r\n\
+Subject: Plans\r\n\
+Message-ID: <r1@example.com>\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+See attached.\r\n\
+--b\r\n\
+Content-Type: text/plain; name=\"notes.txt\"\r\n\
+Content-Disposition: attachment; filename=\"notes.txt\"\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+aGVsbG8=\r\n\
+--b--\r\n";
+
+fn message(id: &str, date: i64, subject: &str) -> MessageRecord {
+    MessageRecord {
+        thread_id: Some("t1".into()),
+        internal_date: Some(date),
+        subject: Some(subject.into()),
+        from: Some("ana@example.com".into()),
+        from_name: Some("Ana".into()),
+        to: Some("me@example.com".into()),
+        bcc: Some("secret@example.com".into()),
+        message_id_header: Some(format!("<{}@example.com>", id)),
+        created_at: date,
+        updated_at: date,
+        ..common::message(id, "INBOX", 1)
+    }
+}
+
+fn body(id: &str, text: &str, raw: Option<&str>) -> BodyRecord {
+    BodyRecord {
+        message_id: id.into(),
+        raw_rfc822: raw.map(|r| r.as_bytes().to_vec()),
+        sanitized_text: Some(text.into()),
+        mime_summary: None,
+        attachments_json: None,
+        sanitized_at: None,
+        sanitizer_version: None,
+        degraded: false,
+    }
+}
+
+#[test]
+fn the_page_is_escaped_ordered_and_self_contained() {
+    let reply = message("r2", 1_700_000_100, "Re: Plans");
+    let first = message("r1", 1_700_000_000, "Plans <b>&</b>");
+    let messages = vec![
+        (
+            reply.clone(),
+            Some(body("r2", "<script>alert(1)</script> ok", None)),
+        ),
+        (first.clone(), Some(body("r1", "See attached.", Some(RAW)))),
+        // The same message cached in a second folder shows once.
+        (reply, None),
+    ];
+    let options = ShareOptions {
+        attachments: false,
+        tz: DisplayTz::parse("UTC").unwrap(),
+    };
+    let html = render_thread_html(&messages, options);
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("default-src 'none'"));
+    assert!(!html.contains("<script"));
+    assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt; ok"));
+    assert!(html.contains("<title>Plans &lt;b&gt;&amp;&lt;/b&gt;</title>"));
+    assert!(!html.contains("secret@example.com"));
+    assert!(html.find("See attached.").unwrap() < html.find("alert(1)").unwrap());
+    assert_eq!(html.matches("<article>").count(), 2);
+    assert!(html.contains("<li>notes.txt (text/plain, 5 B)</li>"));
+    assert!(!html.contains("data:text/plain"));
+
+    let embedded = render_thread_html(
+        &messages,
+        ShareOptions {
+            attachments: true,
+            ..options
+        },
+    );
+    assert!(embedded.contains(
+        "<a download=\"notes.txt\" href=\"data:text/plain;base64,aGVsbG8=\">notes.txt (text/plain, 5 B)</a>"
+    ));
+
+    let missing = render_thread_html(&[(first, None)], options);
+    assert!(missing.contains("(body not downloaded)"));
+}
@@ -0,0 +1,86 @@
+use chrono::NaiveDate;
+use otto::smart_folders::SmartQuery;
+use otto::timefmt::DisplayTz;
+use otto::types::MessageRecord;
+
+mod common;
+
+fn ts(y: i32, m: u32, d: u32) -> i64 {
+    NaiveDate::from_ymd_opt(y, m, d)
+        .unwrap()
+        .and_hms_opt(12, 0, 0)
+        .unwrap()
+        .and_utc()
+        .timestamp()
+}
+
+fn message(subject: &str, from: &str, date: i64, flags: &[&str]) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(date),
+        subject: Some(subject.into()),
+        from: Some(from.into()),
+        from_name: Some("Pat Boss".into()),
+        to: Some("me@example.com".into()),
+        flags: flags.iter().map(|f| f.to_string()).collect(),
+        labels: vec!["\\Important".into()],
+        created_at: date,
+        updated_at: date,
+        ..common::message(subject, "INBOX", 1)
+    }
+}
+
+fn matches(query: &str, msg: &MessageRecord) -> bool {
+    SmartQuery::parse(query).unwrap().matches(
+        msg,
+        ts(2026, 3, 18),
+        DisplayTz::Named(chrono_tz::UTC),
+    )
+}
+
+#[test]
+fn terms_combine_with_and_and_negation() {
+    let unread = message("Quarterly plan", "boss@example.com", ts(2026, 3, 17), &[]);
+    let read = message(
+        "Your receipt",
+        "shop@example.com",
+        ts(2026, 2, 2),
+        &["\\Seen"],
+    );
+
+    assert!(matches(
+        "is:unread from:boss@example.com,vip@example.com",
+        &unread
+    ));
+    assert!(!matches("is:unread from:vip@example.com", &unread));
+    assert!(matches("from:\"pat boss\" label:important", &unread));
+    assert!(matches("-is:unread receipt", &read));
+    assert!(!matches("is:starred", &read));
+
+    assert!(matches(
+        "subject:receipt after:2026-02-01 before:2026-02-03",
+        &read
+    ));
+    assert!(!matches("subject:receipt date:this-month", &read));
+    assert!(matches("date:this-month newer:7d", &unread));
+    assert!(matches("date:this-week -date:today", &unread));
+    assert!(matches("folder:inbox -has:attachment", &unread));
+}
+
+#[test]
+fn bad_queries_are_rejected() {
+    for query in [
+        "",
+        "is:maybe",
+        "after:yesterday",
+        "newer:7w",
+        "subject:\"unclosed",
+        "color:red",
+        "from:",
+    ] {
+        assert!(
+            SmartQuery::parse(query).is_err(),
+            "{:?} should not parse",
+            query
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use chrono::{TimeZone, Utc};
+use otto::status::{AccountStatus, StatusFormat, quota_summary, render};
+use otto::timefmt::DisplayTz;
+use otto::types::AccountQuota;
+
+fn status(inbox: u32, stale: bool) -> AccountStatus {
+    AccountStatus {
+        email: "me@example.com".into(),
+        unread: BTreeMap::from([
+            ("INBOX".to_string(), inbox),
+            ("[Gmail]/Spam".to_string(), 9),
+        ]),
+        total: BTreeMap::from([("INBOX".to_string(), 120)]),
+        last_sync_ts: Some(1_700_000_000),
+        stale,
+        quota: None,
+    }
+}
+
+#[test]
+fn status_bar_formats() {
+    let now = Utc.timestamp_opt(1_700_000_300, 0).unwrap();
+    let tz = DisplayTz::parse("UTC").unwrap();
+
+    let waybar: serde_json::Value =
+        serde_json::from_str(&render(StatusFormat::Waybar, &[status(3, false)], now, tz)).unwrap();
+    assert_eq!(waybar["text"], "3");
+    assert_eq!(waybar["class"], serde_json::json!(["unread"]));
+    assert_eq!(waybar["tooltip"], "me@example.com: 3 unread, synced 5m ago");
+
+    assert_eq!(
+        render(StatusFormat::I3blocks, &[status(0, true)], now, tz),
+        "mail 0 (stale)\n0 (stale)\n#999999\n"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&render(StatusFormat::Json, &[status(2, false)], now, tz)).unwrap();
+    assert_eq!(json["unread"], 2);
+    assert_eq!(json["accounts"][0]["unread"]["[Gmail]/Spam"], 9);
+    assert_eq!(json["accounts"][0]["total"]["INBOX"], 120);
+    assert_eq!(json["accounts"][0]["last_sync"], 1_700_000_000);
+    assert!(json["accounts"][0]["quota"].is_null());
+}
+
+#[test]
+fn quota_shows_usage_against_the_limit() {
+    let now = Utc.timestamp_opt(1_700_000_300, 0).unwrap();
+    let tz = DisplayTz::parse("UTC").unwrap();
+    let quota = AccountQuota {
+        account_id: "acct".into(),
+        storage_used: Some(8_100_000_000),
+        storage_limit: Some(15_000_000_000),
+        messages_used: None,
+        messages_limit: None,
+        fetched_at: 1_700_000_000,
+    };
+    assert_eq!(quota_summary(&quota).unwrap(), "8.1 GB of 15 GB (54%)");
+    let unlimited = AccountQuota {
+        storage_limit: None,
+        storage_used: Some(512_000),
+        ..quota.clone()
+    };
+    assert_eq!(quota_summary(&unlimited).unwrap(), "512 KB used");
+
+    let mut with_quota = status(1, false);
+    with_quota.quota = Some(quota);
+    let waybar: serde_json::Value = serde_json::from_str(&render(
+        StatusFormat::Waybar,
+        &[with_quota.clone()],
+        now,
+        tz,
+    ))
+    .unwrap();
+    assert_eq!(
+        waybar["tooltip"],
+        "me@example.com: 1 unread, synced 5m ago, 8.1 GB of 15 GB (54%)"
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&render(StatusFormat::Json, &[with_quota], now, tz)).unwrap();
+    assert_eq!(
+        json["accounts"][0]["quota"]["storage_limit"],
+        15_000_000_000u64
+    );
+}
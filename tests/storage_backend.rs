@@ -0,0 +1,38 @@
+use std::path::PathBuf;
+
+use otto::storage::StorageBackend;
+
+#[test]
+fn database_url_selects_backend() {
+    assert_eq!(
+        StorageBackend::from_url(None).unwrap(),
+        StorageBackend::SqliteDefault
+    );
+    assert_eq!(
+        StorageBackend::from_url(Some("  ")).unwrap(),
+        StorageBackend::SqliteDefault
+    );
+    assert_eq!(
+        StorageBackend::from_url(Some("sqlite:///srv/otto/otto.db?mode=rwc")).unwrap(),
+        StorageBackend::SqliteFile(PathBuf::from("/srv/otto/otto.db"))
+    );
+    assert_eq!(
+        StorageBackend::from_url(Some("postgresql://otto@db.home/mail")).unwrap(),
+        StorageBackend::Postgres("postgresql://otto@db.home/mail".to_string())
+    );
+    assert!(StorageBackend::from_url(Some("sqlite://")).is_err());
+    assert!(StorageBackend::from_url(Some("mysql://host/db")).is_err());
+
+    assert_eq!(
+        StorageBackend::SqliteFile(PathBuf::from("/srv/otto/otto.db"))
+            .sqlite_path()
+            .unwrap(),
+        Some(PathBuf::from("/srv/otto/otto.db"))
+    );
+    assert_eq!(
+        StorageBackend::Postgres("postgres://db.home/mail".to_string())
+            .sqlite_path()
+            .unwrap(),
+        None
+    );
+}
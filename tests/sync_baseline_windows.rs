@@ -0,0 +1,90 @@
+use otto::sync::{BASELINE_WINDOW_UIDS, baseline_uid_windows};
+
+/// Checks the windows cover `(resume_after, highest]` without gaps or overlaps: they start right
+/// after the resume point, each closed one spans a full window, and only the last is open.
+fn assert_contiguous(resume_after: u32, highest: Option<u32>) {
+    let windows = baseline_uid_windows(resume_after, highest);
+    assert_eq!(windows[0].0, resume_after.saturating_add(1));
+    let (last, closed) = windows.split_last().unwrap();
+    assert_eq!(last.1, None, "{windows:?}");
+    let mut next = windows[0].0;
+    for &(lo, hi) in closed {
+        let hi = hi.unwrap();
+        assert_eq!(lo, next, "{windows:?}");
+        assert_eq!(hi - lo + 1, BASELINE_WINDOW_UIDS, "{windows:?}");
+        assert!(hi < highest.unwrap(), "{windows:?}");
+        next = hi + 1;
+    }
+    assert_eq!(last.0, next, "{windows:?}");
+    if let Some(highest) = highest {
+        assert!(last.0 <= highest.max(resume_after.saturating_add(1)));
+    }
+}
+
+#[test]
+fn empty_mailbox_gets_one_open_window() {
+    assert_eq!(baseline_uid_windows(0, Some(0)), [(1, None)]);
+}
+
+#[test]
+fn unknown_highest_uid_scans_everything_after_the_resume_point() {
+    assert_eq!(baseline_uid_windows(0, None), [(1, None)]);
+    assert_eq!(baseline_uid_windows(12_345, None), [(12_346, None)]);
+}
+
+#[test]
+fn highest_uid_at_a_window_multiple_stays_in_the_open_window() {
+    assert_eq!(baseline_uid_windows(0, Some(10_000)), [(1, None)]);
+    assert_eq!(
+        baseline_uid_windows(0, Some(10_001)),
+        [(1, Some(10_000)), (10_001, None)]
+    );
+    assert_eq!(
+        baseline_uid_windows(0, Some(20_000)),
+        [(1, Some(10_000)), (10_001, None)]
+    );
+    assert_eq!(
+        baseline_uid_windows(0, Some(30_001)),
+        [
+            (1, Some(10_000)),
+            (10_001, Some(20_000)),
+            (20_001, Some(30_000)),
+            (30_001, None)
+        ]
+    );
+    // Resuming right after a closed window picks up at its successor.
+    assert_eq!(
+        baseline_uid_windows(10_000, Some(30_000)),
+        [(10_001, Some(20_000)), (20_001, None)]
+    );
+}
+
+#[test]
+fn resume_point_inside_a_window_starts_the_next_uid() {
+    assert_eq!(
+        baseline_uid_windows(4_999, Some(30_000)),
+        [
+            (5_000, Some(14_999)),
+            (15_000, Some(24_999)),
+            (25_000, None)
+        ]
+    );
+    // Nothing left below the highest UID: only the open tail remains.
+    assert_eq!(baseline_uid_windows(30_000, Some(30_000)), [(30_001, None)]);
+    assert_eq!(baseline_uid_windows(35_000, Some(30_000)), [(35_001, None)]);
+}
+
+#[test]
+fn windows_cover_every_uid_exactly_once() {
+    let edges = [0, 1, 9_999, 10_000, 10_001, 19_999, 20_000, 20_001, 123_456];
+    for resume_after in edges {
+        assert_contiguous(resume_after, None);
+        for highest in edges {
+            assert_contiguous(resume_after, Some(highest));
+        }
+    }
+    // UIDs near the top of the 32-bit range must not overflow.
+    assert_contiguous(u32::MAX - 5_000, Some(u32::MAX));
+    assert_contiguous(u32::MAX - 25_000, Some(u32::MAX));
+    assert_contiguous(u32::MAX, Some(u32::MAX));
+}
@@ -0,0 +1,49 @@
+use otto::timefmt::format_age;
+use otto::tui::{FolderSync, SyncNote, sync_note};
+
+fn synced(ts: Option<i64>, error: Option<&str>) -> FolderSync {
+    FolderSync {
+        last_sync_ts: ts,
+        last_error: error.map(str::to_string),
+    }
+}
+
+#[test]
+fn list_titles_flag_stale_and_failed_folders() {
+    assert_eq!(format_age(30), "just now");
+    assert_eq!(format_age(5 * 60 + 10), "5m ago");
+    assert_eq!(format_age(3 * 3600), "3h ago");
+    assert_eq!(format_age(2 * 86_400 + 5), "2d ago");
+
+    let now = 100_000;
+    let inbox = synced(Some(now - 120), None);
+    let sent = synced(Some(now - 900), None);
+    assert_eq!(sync_note(&[], now, 300), None);
+    assert_eq!(
+        sync_note(&[&inbox], now, 300),
+        Some(SyncNote {
+            text: "synced 2m ago".into(),
+            warn: false,
+        })
+    );
+    // The oldest folder decides.
+    assert_eq!(
+        sync_note(&[&inbox, &sent], now, 300),
+        Some(SyncNote {
+            text: "synced 15m ago (stale)".into(),
+            warn: true,
+        })
+    );
+    assert_eq!(
+        sync_note(&[&inbox, &synced(None, None)], now, 300).map(|n| n.text),
+        Some("never synced".into())
+    );
+    let failed = synced(Some(now - 60), Some("connection reset by peer"));
+    assert_eq!(
+        sync_note(&[&inbox, &failed], now, 300),
+        Some(SyncNote {
+            text: "synced 2m ago, last sync failed: connection reset by peer".into(),
+            warn: true,
+        })
+    );
+}
@@ -0,0 +1,57 @@
+use otto::sync::{AccountSyncReport, SyncReport};
+use otto::types::SyncRunRecord;
+
+fn run(folder: &str, added: usize, error: Option<&str>) -> SyncRunRecord {
+    SyncRunRecord {
+        account_id: "acct".to_string(),
+        folder: folder.to_string(),
+        run_started_at: 0,
+        duration_ms: 10,
+        added,
+        updated: 1,
+        deleted: 0,
+        bytes: 0,
+        ok: error.is_none(),
+        error: error.map(str::to_string),
+    }
+}
+
+#[test]
+fn report_lists_folder_and_account_failures() {
+    let clean = SyncReport {
+        accounts: vec![AccountSyncReport {
+            account_id: "acct".to_string(),
+            email: "me@example.com".to_string(),
+            folders: vec![run("INBOX", 3, None)],
+            ..AccountSyncReport::default()
+        }],
+        cancelled: false,
+    };
+    assert!(clean.is_ok());
+    assert!(clean.problems().is_empty());
+    assert_eq!(
+        clean.summary(),
+        "1 account(s), 1 folder(s): 3 new, 1 updated, 0 deleted"
+    );
+
+    let mut failed = clean.clone();
+    failed.accounts[0]
+        .folders
+        .push(run("Work", 0, Some("selecting folder Work: NO")));
+    failed.accounts[0]
+        .errors
+        .push("replaying queued ops: timed out".to_string());
+    failed.cancelled = true;
+    assert!(!failed.is_ok());
+    assert_eq!(
+        failed.problems(),
+        vec![
+            "me@example.com: replaying queued ops: timed out".to_string(),
+            "me@example.com / Work: selecting folder Work: NO".to_string(),
+        ]
+    );
+    assert_eq!(
+        failed.summary(),
+        "1 account(s), 2 folder(s) (1 failed): 3 new, 2 updated, 0 deleted, 1 account error(s) (cancelled)"
+    );
+}
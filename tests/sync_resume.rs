@@ -0,0 +1,194 @@
+use std::sync::Arc;
+
+use otto::imap::ProtocolTrace;
+use otto::storage::Database;
+use otto::storage::db::FolderStateUpdate;
+use otto::sync::{SyncEngine, SyncOptions};
+use otto::types::{Account, BodyStatus, Credential, MessageRecord, Provider};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+
+mod common;
+
+const RAW: &str = "From: a@example.com\r\nSubject: hi\r\n\r\nhello\r\n";
+/// Seeded before the pass; UID 2 is the one whose flags change on the server.
+const SEEDED: [u32; 3] = [1, 2, 3];
+/// Arrived since the stored MODSEQ: one more than a checkpoint batch.
+const NEW: std::ops::RangeInclusive<u32> = 11..=511;
+
+fn account(port: u16) -> Account {
+    let mut account = common::local_account("acct", port);
+    account.provider = Provider::OutlookImap;
+    account.settings.credential = Credential::PasswordCommand {
+        command: "echo pw".into(),
+    };
+    account
+}
+
+fn message(uid: u32) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(1_740_000_000),
+        body_status: BodyStatus::Pending,
+        created_at: 1_740_000_000,
+        updated_at: 1_740_000_000,
+        ..common::message(&format!("acct:INBOX:{uid}"), "INBOX", uid)
+    }
+}
+
+fn uid_set(set: &str) -> Vec<u32> {
+    set.split(',')
+        .flat_map(|part| match part.split_once(':') {
+            Some((lo, hi)) => (lo.parse().unwrap()..=hi.parse().unwrap()).collect(),
+            None => vec![part.parse().unwrap()],
+        })
+        .collect()
+}
+
+/// A CONDSTORE server at HIGHESTMODSEQ 200 where UID 2 was marked seen and `NEW` arrived.
+/// `cancel` fires at the first body fetch, so the pass stops after its first checkpoint commit.
+async fn condstore_server(cancel: CancellationToken) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        loop {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (read, mut write) = socket.into_split();
+            let mut lines = BufReader::new(read).lines();
+            write.write_all(b"* OK ready\r\n").await.unwrap();
+            while let Ok(Some(command)) = lines.next_line().await {
+                let (tag, verb) = command.split_once(' ').unwrap();
+                let mut reply = String::new();
+                if verb == "CAPABILITY" {
+                    reply.push_str("* CAPABILITY IMAP4rev1 AUTH=PLAIN CONDSTORE\r\n");
+                } else if verb == "AUTHENTICATE PLAIN" {
+                    write.write_all(b"+ \r\n").await.unwrap();
+                    lines.next_line().await.unwrap();
+                } else if verb.starts_with("SELECT") {
+                    reply.push_str(
+                        "* 504 EXISTS\r\n* OK [UIDVALIDITY 1] ok\r\n* OK [UIDNEXT 512] ok\r\n* OK [HIGHESTMODSEQ 200] ok\r\n",
+                    );
+                } else if verb.starts_with("UID SEARCH") && verb.contains("MODSEQ") {
+                    let uids: Vec<String> = std::iter::once(2)
+                        .chain(NEW)
+                        .map(|u| u.to_string())
+                        .collect();
+                    reply.push_str(&format!("* SEARCH {}\r\n", uids.join(" ")));
+                } else if verb.starts_with("UID SEARCH") {
+                    let uids: Vec<String> = SEEDED
+                        .into_iter()
+                        .chain(NEW)
+                        .map(|u| u.to_string())
+                        .collect();
+                    reply.push_str(&format!("* SEARCH {}\r\n", uids.join(" ")));
+                } else if let Some(args) = verb.strip_prefix("UID FETCH ") {
+                    let (set, items) = args.split_once(' ').unwrap();
+                    for uid in uid_set(set) {
+                        if items == "(UID FLAGS)" {
+                            let flags = if uid == 2 { "\\Seen" } else { "" };
+                            reply.push_str(&format!(
+                                "* {uid} FETCH (UID {uid} FLAGS ({flags}))\r\n"
+                            ));
+                            continue;
+                        }
+                        reply.push_str(&format!(
+                            "* {uid} FETCH (UID {uid} FLAGS () INTERNALDATE \"01-Feb-2025 10:00:00 +0000\" RFC822.SIZE {}",
+                            RAW.len()
+                        ));
+                        if items.contains("BODY.PEEK[]") {
+                            cancel.cancel();
+                            reply.push_str(&format!(" BODY[] {{{}}}\r\n{RAW}", RAW.len()));
+                        }
+                        reply.push_str(")\r\n");
+                    }
+                } else if verb == "LOGOUT" {
+                    reply.push_str("* BYE\r\n");
+                }
+                reply.push_str(&format!("{tag} OK done\r\n"));
+                write.write_all(reply.as_bytes()).await.unwrap();
+            }
+        }
+    });
+    port
+}
+
+async fn flags_of(db: &Database, uid: u32) -> String {
+    sqlx::query_scalar("SELECT flags FROM messages WHERE account_id = 'acct' AND uid = ?")
+        .bind(uid)
+        .fetch_one(db.pool())
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn interrupted_condstore_pass_keeps_flag_changes_below_the_checkpoint() {
+    let dir = std::env::temp_dir().join(format!("otto-sync-resume-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let db = Arc::new(Database::open_at(dir.join("otto.db")).await.unwrap());
+    let cancel = CancellationToken::new();
+    let account = account(condstore_server(cancel.clone()).await);
+    db.save_account(&account).await.unwrap();
+    let seeded: Vec<MessageRecord> = SEEDED.into_iter().map(message).collect();
+    db.commit_folder_batch(
+        "acct",
+        "INBOX",
+        &seeded,
+        &[],
+        &[],
+        &[],
+        &FolderStateUpdate {
+            uidvalidity: Some(1),
+            highest_uid: Some(3),
+            highestmodseq: Some(100),
+            exists_count: Some(3),
+            last_sync_ts: Some(1_740_000_000),
+            last_uid_scan_ts: None,
+            baseline_scan_uid: None,
+            resume_modseq: None,
+            resume_uid: None,
+            checkpoint_from_uid: None,
+        },
+        "ok",
+        Some(100),
+        Some(3),
+    )
+    .await
+    .unwrap();
+
+    let trace = || Arc::new(ProtocolTrace::create(&dir.join("trace.log")).unwrap());
+
+    // The first pass dies right after committing its first checkpoint batch.
+    let engine = SyncEngine::new(db.clone(), 1).with_cancellation(cancel);
+    let err = engine
+        .sync_folder_traced(&account, "INBOX", SyncOptions::default(), trace())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("cancelled"), "{err:#}");
+    let folder = &db.list_folders("acct").await.unwrap()[0];
+    assert_eq!(
+        (
+            folder.highestmodseq,
+            folder.resume_modseq,
+            folder.resume_uid
+        ),
+        (Some(100), Some(100), Some(510))
+    );
+    // UID 2 sits below the checkpoint, so its new flags went in with it.
+    assert_eq!(flags_of(&db, 2).await, r#"["Seen"]"#);
+
+    // The resumed pass skips everything up to the checkpoint and finishes the rest.
+    SyncEngine::new(db.clone(), 1)
+        .sync_folder_traced(&account, "INBOX", SyncOptions::default(), trace())
+        .await
+        .unwrap();
+    let folder = &db.list_folders("acct").await.unwrap()[0];
+    assert_eq!((folder.highestmodseq, folder.resume_uid), (Some(200), None));
+    assert_eq!(flags_of(&db, 2).await, r#"["Seen"]"#);
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE account_id = 'acct'")
+        .fetch_one(db.pool())
+        .await
+        .unwrap();
+    assert_eq!(stored, 3 + NEW.count() as i64);
+    let _ = std::fs::remove_dir_all(&dir);
+}
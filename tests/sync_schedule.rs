@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use otto::daemon::Schedule;
+use otto::types::Account;
+
+mod common;
+
+fn account(id: &str, poll_interval_minutes: u32) -> Account {
+    let mut account = common::account(id);
+    account.email = format!("{}@example.com", id);
+    account.settings.poll_interval_minutes = poll_interval_minutes;
+    account
+}
+
+fn ids(accounts: &[Account]) -> Vec<&str> {
+    accounts.iter().map(|a| a.id.as_str()).collect()
+}
+
+#[test]
+fn accounts_sync_on_their_own_intervals() {
+    let accounts = [account("fast", 1), account("slow", 5)];
+    let mut schedule = Schedule::default();
+
+    assert_eq!(ids(&schedule.take_due(&accounts, 1_000)), ["fast", "slow"]);
+    assert!(schedule.take_due(&accounts, 1_030).is_empty());
+    assert_eq!(
+        schedule.next_due_in(&accounts, 1_030),
+        Some(Duration::from_secs(30))
+    );
+
+    assert_eq!(ids(&schedule.take_due(&accounts, 1_060)), ["fast"]);
+    assert_eq!(ids(&schedule.take_due(&accounts, 1_300)), ["fast", "slow"]);
+
+    // A manual pass restarts the interval; a new account is due at once.
+    schedule.mark_started(&accounts[..1], 1_330);
+    let accounts = [accounts[0].clone(), accounts[1].clone(), account("new", 5)];
+    assert_eq!(ids(&schedule.take_due(&accounts, 1_360)), ["new"]);
+    assert_eq!(schedule.next_due_in(&[], 1_360), None);
+}
+
+#[test]
+fn paused_accounts_are_never_due() {
+    let mut paused = account("paused", 1);
+    paused.settings.enabled = false;
+    let mut accounts = [account("active", 5), paused];
+    let mut schedule = Schedule::default();
+
+    assert_eq!(ids(&schedule.take_due(&accounts, 1_000)), ["active"]);
+    assert_eq!(
+        schedule.next_due_in(&accounts, 1_000),
+        Some(Duration::from_secs(300))
+    );
+    assert_eq!(schedule.next_due_in(&accounts[1..], 1_000), None);
+
+    // Resuming makes the account due at once.
+    accounts[1].settings.enabled = true;
+    assert_eq!(ids(&schedule.take_due(&accounts, 1_010)), ["paused"]);
+}
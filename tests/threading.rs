@@ -0,0 +1,163 @@
+use otto::storage::Database;
+use otto::thread_graph::{ThreadGraph, ThreadMessage};
+use otto::threading::{Threader, parent_references, parse_message_id_list};
+use otto::timefmt::DisplayTz;
+use otto::types::{BodyStatus, MessageRecord};
+
+mod common;
+
+fn ids(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn parses_references_and_in_reply_to() {
+    assert_eq!(
+        parse_message_id_list("<a@x>\r\n <b@x> junk <a@x> <c@x"),
+        ids(&["a@x", "b@x"])
+    );
+
+    let raw = b"Message-ID: <d@x>\r\nReferences: <a@x> <b@x>\r\nIn-Reply-To: <c@x>\r\n\r\nbody";
+    let parsed = mailparse::parse_mail(raw).unwrap();
+    assert_eq!(parent_references(&parsed), ids(&["a@x", "b@x", "c@x"]));
+
+    let raw = b"References: <a@x> <b@x>\r\nIn-Reply-To: <b@x>\r\n\r\nbody";
+    let parsed = mailparse::parse_mail(raw).unwrap();
+    assert_eq!(parent_references(&parsed), ids(&["a@x", "b@x"]));
+}
+
+#[test]
+fn links_reference_chains_without_loops() {
+    let mut threader = Threader::default();
+    threader.add_message("c@x", &ids(&["a@x", "b@x"]));
+    assert_eq!(threader.parent("c@x"), Some("b@x"));
+    assert_eq!(threader.parent("b@x"), Some("a@x"));
+    assert_eq!(threader.root("c@x"), "a@x");
+
+    // A bogus header claiming a@x replies to c@x would close a loop; it is ignored.
+    threader.add_message("a@x", &ids(&["c@x"]));
+    assert_eq!(threader.parent("a@x"), None);
+    assert_eq!(threader.root("c@x"), "a@x");
+
+    // An existing parent link is not overridden by someone else's References.
+    threader.add_message("e@x", &ids(&["z@x", "b@x"]));
+    assert_eq!(threader.parent("b@x"), Some("a@x"));
+    assert_eq!(threader.root("e@x"), "a@x");
+}
+
+fn message(uid: u32, message_id: &str, references: &[&str]) -> MessageRecord {
+    MessageRecord {
+        internal_date: Some(1_700_000_000 + uid as i64),
+        subject: Some("plans".into()),
+        size_bytes: Some(64),
+        message_id_header: Some(message_id.into()),
+        references: ids(references),
+        body_status: BodyStatus::Pending,
+        ..common::message(&format!("acct:INBOX:{}", uid), "INBOX", uid)
+    }
+}
+
+#[tokio::test]
+async fn assigns_and_merges_threads_without_gmail_ids() {
+    let dir = std::env::temp_dir().join(format!("otto-threads-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    let mut account = common::account("acct");
+    account.settings.folders = vec!["INBOX".into()];
+    account.settings.prefetch_recent = 10;
+    db.save_account(&account).await.unwrap();
+
+    // Two replies to a root we never cached start out as separate threads...
+    let commit = |messages: Vec<MessageRecord>| {
+        let db = db.clone();
+        async move {
+            db.commit_backfill_batch("acct", "INBOX", &messages, &[], &[], None)
+                .await
+                .unwrap()
+        }
+    };
+    commit(vec![message(1, "b@x", &["a@x"])]).await;
+    commit(vec![message(2, "d@x", &["c@x"])]).await;
+    let thread_of = |uid: u32| {
+        let db = db.clone();
+        async move {
+            let messages = db.load_messages("acct", 10).await.unwrap();
+            messages
+                .into_iter()
+                .find(|(m, _)| m.uid == Some(uid))
+                .and_then(|(m, _)| m.thread_id)
+                .unwrap()
+        }
+    };
+    assert_eq!(thread_of(1).await, "jwz:a@x");
+    assert_ne!(thread_of(1).await, thread_of(2).await);
+
+    // ...until a message whose References span both joins them into one.
+    commit(vec![message(3, "e@x", &["a@x", "c@x", "d@x"])]).await;
+    let joined = thread_of(3).await;
+    assert_eq!(thread_of(1).await, joined);
+    assert_eq!(thread_of(2).await, joined);
+
+    // `otto thread` takes a message id or the thread id.
+    assert_eq!(
+        db.load_thread("acct", "acct:INBOX:1").await.unwrap().len(),
+        3
+    );
+    assert_eq!(db.load_thread("acct", &joined).await.unwrap().len(), 3);
+    assert!(db.load_thread("acct", "nope").await.unwrap().is_empty());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn thread_message(key: &str, minute: i64, references: &[&str]) -> ThreadMessage {
+    ThreadMessage {
+        key: key.into(),
+        from: key.split('@').next().unwrap().into(),
+        date: Some(1_700_000_000 + minute * 60),
+        subject: Some("Re: \"plans\"".into()),
+        references: ids(references),
+    }
+}
+
+#[test]
+fn thread_graph_renders_branches_and_uncached_parents() {
+    let graph = ThreadGraph::new([
+        thread_message("dana@x", 30, &["ann@x", "gone@x"]),
+        thread_message("ann@x", 0, &[]),
+        thread_message("bob@x", 10, &["ann@x"]),
+        thread_message("cat@x", 20, &["ann@x"]),
+        // The same message cached in a second folder is drawn once.
+        thread_message("bob@x", 10, &["ann@x"]),
+    ]);
+    assert_eq!(graph.len(), 4);
+    assert_eq!(
+        graph.edges(),
+        vec![
+            ("ann@x", "bob@x"),
+            ("ann@x", "cat@x"),
+            ("ann@x", "gone@x"),
+            ("gone@x", "dana@x"),
+        ]
+    );
+
+    let dot = graph.to_dot(DisplayTz::parse("UTC").unwrap());
+    assert!(dot.starts_with("digraph thread {\n"), "{dot}");
+    assert!(
+        dot.contains("\"bob@x\" [label=\"bob\\n2023-11-14 22:23:20\\nRe: \\\"plans\\\"\"];"),
+        "{dot}"
+    );
+    assert!(
+        dot.contains("\"gone@x\" [label=\"(not cached)\\ngone@x\", style=dashed];"),
+        "{dot}"
+    );
+    assert!(dot.contains("\"gone@x\" -> \"dana@x\";"), "{dot}");
+
+    let tree = graph.to_tree(DisplayTz::parse("UTC").unwrap());
+    let lines: Vec<&str> = tree.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("ann  "), "{tree}");
+    assert!(lines[1].starts_with("  bob  "), "{tree}");
+    assert!(lines[2].starts_with("  cat  "), "{tree}");
+    assert_eq!(lines[3], "  (not cached) <gone@x>");
+    assert!(lines[4].starts_with("    dana  "), "{tree}");
+}
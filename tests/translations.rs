@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use otto::storage::Database;
+use otto::storage::crypto::ColumnCipher;
+use otto::translate::{
+    TranslateBackend, TranslateConfig, TranslationView, Translator, parse_language,
+    translate_message,
+};
+use otto::types::{BodyRecord, MessageRecord};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+mod common;
+
+fn config(vars: &[(&str, &str)]) -> anyhow::Result<Option<TranslateConfig>> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    TranslateConfig::from_env(|name| vars.get(name).cloned())
+}
+
+#[test]
+fn config_comes_from_the_environment() {
+    assert_eq!(config(&[]).unwrap(), None);
+
+    let deepl = config(&[
+        (
+            "OTTO_TRANSLATE_URL",
+            "https://api-free.deepl.com/v2/translate",
+        ),
+        ("OTTO_TRANSLATE_API_KEY", "secret"),
+        ("LANG", "de_DE.UTF-8"),
+    ])
+    .unwrap()
+    .unwrap();
+    assert_eq!(deepl.backend, TranslateBackend::DeepL);
+    assert_eq!(deepl.target, "de");
+    assert_eq!(deepl.view, TranslationView::Side);
+    assert_eq!(deepl.api_key.as_deref(), Some("secret"));
+
+    let llm = config(&[
+        (
+            "OTTO_TRANSLATE_URL",
+            "http://localhost:11434/v1/chat/completions",
+        ),
+        ("OTTO_TRANSLATE_MODEL", "llama3"),
+        ("OTTO_TRANSLATE_TO", "pt_BR"),
+        ("OTTO_TRANSLATE_VIEW", "inline"),
+        ("LANG", "C"),
+    ])
+    .unwrap()
+    .unwrap();
+    assert_eq!(llm.backend, TranslateBackend::Llm);
+    assert_eq!(llm.target, "pt-BR");
+    assert_eq!(llm.view, TranslationView::Inline);
+
+    let fallback = config(&[("OTTO_TRANSLATE_URL", "http://x"), ("LANG", "POSIX")])
+        .unwrap()
+        .unwrap();
+    assert_eq!(fallback.target, "en");
+
+    assert!(
+        config(&[
+            ("OTTO_TRANSLATE_URL", "http://x"),
+            ("OTTO_TRANSLATE_BACKEND", "bing")
+        ])
+        .is_err()
+    );
+    assert!(parse_language("de fr").is_err());
+    assert!(parse_language("").is_err());
+}
+
+fn message(id: &str) -> MessageRecord {
+    MessageRecord {
+        thread_id: Some("t1".into()),
+        subject: Some("Termin".into()),
+        ..common::message(id, "INBOX", 1)
+    }
+}
+
+fn body(id: &str, text: &str) -> BodyRecord {
+    BodyRecord {
+        message_id: id.into(),
+        raw_rfc822: None,
+        sanitized_text: Some(text.into()),
+        mime_summary: None,
+        attachments_json: None,
+        sanitized_at: Some(1_700_000_000),
+        sanitizer_version: Some(1),
+        degraded: false,
+    }
+}
+
+/// Answers one DeepL request and returns the request (headers and body) as text.
+async fn deepl_reply(listener: TcpListener, translated: &str) -> String {
+    let (mut socket, _) = listener.accept().await.unwrap();
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some(end) = text.find("\r\n\r\n") {
+            let length = text[..end]
+                .lines()
+                .find_map(|l| {
+                    l.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|v| v.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or(0);
+            if request.len() >= end + 4 + length {
+                break;
+            }
+        }
+        if n == 0 {
+            break;
+        }
+    }
+    let body = serde_json::json!({
+        "translations": [{"detected_source_language": "DE", "text": translated}]
+    })
+    .to_string();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    socket.write_all(response.as_bytes()).await.unwrap();
+    String::from_utf8(request).unwrap()
+}
+
+#[tokio::test]
+async fn translations_are_cached_per_message_and_language() {
+    let dir = std::env::temp_dir().join(format!("otto-translations-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let db = Database::open_at(dir.join("otto.db")).await.unwrap();
+    db.save_account(&common::account("acct")).await.unwrap();
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("m1")],
+        &[body("m1", "Treffen wir uns morgen?")],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/v2/translate", listener.local_addr().unwrap());
+    let server = tokio::spawn(deepl_reply(listener, "Shall we meet tomorrow?"));
+    let translator = Translator::new(TranslateConfig {
+        backend: TranslateBackend::DeepL,
+        url,
+        api_key: Some("secret".into()),
+        model: None,
+        target: "en".into(),
+        view: TranslationView::Side,
+    });
+
+    let translation = translate_message(&db, Some(&translator), "acct", "m1", "en", false, false)
+        .await
+        .unwrap();
+    assert_eq!(translation.text, "Shall we meet tomorrow?");
+    assert_eq!(translation.source_lang.as_deref(), Some("de"));
+    let request = server.await.unwrap();
+    assert!(request.contains("DeepL-Auth-Key secret"), "{}", request);
+    let sent = &request[request.find("\r\n\r\n").unwrap() + 4..];
+    let sent: serde_json::Value = serde_json::from_str(sent).unwrap();
+    assert_eq!(sent["target_lang"], "EN");
+    assert_eq!(sent["text"][0], "Treffen wir uns morgen?");
+
+    // Cached: no backend and offline still answer; another language does not.
+    let cached = translate_message(&db, None, "acct", "m1", "en", false, true)
+        .await
+        .unwrap();
+    assert_eq!(cached, translation);
+    assert!(
+        translate_message(&db, None, "acct", "m1", "fr", false, true)
+            .await
+            .is_err()
+    );
+    assert!(
+        translate_message(&db, None, "acct", "m1", "en", true, false)
+            .await
+            .is_err()
+    );
+
+    // Sealed with the account's columns.
+    let cipher = ColumnCipher::new(&[7u8; 32]);
+    db.reseal_account("acct", None, Some(&cipher))
+        .await
+        .unwrap();
+    db.register_cipher("acct", Some(cipher));
+    let opened = db
+        .load_message_translation("acct", "m1", "en")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(opened.text, "Shall we meet tomorrow?");
+    db.register_cipher("acct", None);
+    let sealed = db
+        .load_message_translation("acct", "m1", "en")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(sealed.text, "Shall we meet tomorrow?");
+
+    // A rebuilt body is not served the old translation.
+    db.register_cipher("acct", Some(ColumnCipher::new(&[7u8; 32])));
+    db.commit_backfill_batch(
+        "acct",
+        "INBOX",
+        &[message("m1")],
+        &[body("m1", "Treffen wir uns übermorgen?")],
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(
+        translate_message(&db, None, "acct", "m1", "en", false, true)
+            .await
+            .is_err()
+    );
+
+    db.delete_message("m1").await.unwrap();
+    assert!(
+        db.load_message_translation("acct", "m1", "en")
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,25 @@
+use otto::sanitize::compute_hash;
+use otto::sync::{FolderDrift, sample_uids};
+
+#[test]
+fn samples_spread_over_the_whole_uid_range() {
+    let uids: Vec<u32> = (1..=100).collect();
+    assert_eq!(sample_uids(&uids, 4), vec![1, 26, 51, 76]);
+    assert_eq!(sample_uids(&uids[..3], 10), vec![1, 2, 3]);
+    assert!(sample_uids(&uids, 0).is_empty());
+}
+
+#[test]
+fn differing_hashes_count_as_drift() {
+    let mut drift = FolderDrift {
+        folder: "INBOX".into(),
+        checked: 10,
+        hashed: 2,
+        ..FolderDrift::default()
+    };
+    assert!(drift.is_clean());
+    drift.corrupt.push(7);
+    assert!(!drift.is_clean());
+    assert_eq!(compute_hash(b"raw"), compute_hash(b"raw"));
+    assert_ne!(compute_hash(b"raw"), compute_hash(b"raw, truncated"));
+}